@@ -9,11 +9,20 @@ use alkanes_support::response::CallResponse;
 use alkanes_support::context::Context;
 use alkanes_support::parcel::AlkaneTransfer;
 use alkanes_support::cellpack::Cellpack;
+use alkanes_support::witness::find_witness_payload;
 use alkanes_support::id::AlkaneId;
 use metashrew_support::index_pointer::KeyValuePointer;
+use metashrew_support::utils::consensus_decode;
 use metashrew_support::compat::to_arraybuffer_layout;
-use zkane_common::ZKaneConfig;
+use zkane_abi::pool;
+use zkane_common::{
+    decode_schema_version, derive_pool_id, derive_pool_id_at, encode_schema_version, pending_migrations, AssetStats,
+    DenominationSpec, GlobalStats, PoolRecord, PoolTemplate, ProtocolFee, RewardProgram, ZKaneConfig, ZKaneError,
+    ZKaneResult, validate_tree_height, FACTORY_SCHEMA_VERSION, POOL_CONFIG_MAGIC, SCHEMA_VERSION_KEY,
+};
 use anyhow::{anyhow, Result};
+use bitcoin::Transaction;
+use std::io::Cursor;
 use std::sync::Arc;
 
 #[cfg(test)]
@@ -21,7 +30,33 @@ pub mod tests;
 
 /// ZKane factory contract constants
 pub const ZKANE_TEMPLATE_BLOCK: u128 = 4; // Block where zkane WASM is deployed
-pub const ZKANE_INSTANCE_BLOCK: u128 = 6; // Block for zkane instances
+pub const ZKANE_INSTANCE_BLOCK: u128 = zkane_common::ZKANE_INSTANCE_BLOCK; // Block for zkane instances
+
+/// Maximum number of pools returned by a single `GetPoolsPage` call
+pub const MAX_POOLS_PAGE: u128 = 100;
+
+/// Height of the Merkle tree of new pools
+pub const DEFAULT_TREE_HEIGHT: u32 = 20;
+
+/// Read the tree height of a new pool from its configuration envelope,
+/// [`POOL_CONFIG_MAGIC`] followed by the height as four bytes little-endian.
+///
+/// Envelopes without the magic, such as a deposit commitment, configure
+/// nothing and get [`DEFAULT_TREE_HEIGHT`].
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidTreeHeight`] if the height is outside the
+/// range [`validate_tree_height`] allows, or missing after the magic.
+pub fn parse_tree_height(witness_data: &[u8]) -> ZKaneResult<u32> {
+    match witness_data.strip_prefix(POOL_CONFIG_MAGIC.as_slice()) {
+        Some(config) => {
+            let height = config.get(..4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+            validate_tree_height(height.into())
+        }
+        None => Ok(DEFAULT_TREE_HEIGHT),
+    }
+}
 
 /// ZKane factory contract
 #[derive(Default)]
@@ -34,22 +69,31 @@ pub struct ZKaneFactory {
 #[derive(MessageDispatch)]
 enum ZKaneFactoryMessage {
     /// Initialize the factory
+    /// Holders of the admin alkane can change the factory settings
     #[opcode(0)]
-    Initialize,
+    Initialize {
+        /// Admin alkane block
+        admin_block: u128,
+        /// Admin alkane tx
+        admin_tx: u128,
+    },
 
-    /// Deploy or get a zkane pool for an asset
-    /// Uses witness envelope for large configuration data
+    /// Deposit into the zkane pool for an asset, creating it if needed
+    /// The incoming alkanes are forwarded as the deposit, so some must be attached
     #[opcode(1)]
     GetOrCreatePool {
         /// Asset ID block
         asset_id_block: u128,
         /// Asset ID tx
         asset_id_tx: u128,
-        /// Denomination for the pool
+        /// Denomination for the pool. Zero, for the asset's variable-amount
+        /// pool, is refused by the pool until deposits' amount proofs are
+        /// verified
         denomination: u128,
     },
 
     /// Get the zkane instance ID for an asset/denomination pair
+    /// This is the pair's first pool; see `GetActivePool` for the one taking deposits
     #[opcode(2)]
     #[returns(Vec<u8>)]
     GetPoolId {
@@ -83,10 +127,245 @@ enum ZKaneFactoryMessage {
         asset_id_tx: u128,
     },
 
-    /// Get factory statistics
+    /// Get factory statistics, as JSON
+    /// Includes the stored storage schema version and the one the contract supports
     #[opcode(5)]
     #[returns(Vec<u8>)]
     GetStats,
+
+    /// Get a page of pools in creation order
+    /// Returns the total pool count (16 bytes) followed by binary pool records
+    #[opcode(6)]
+    #[returns(Vec<u8>)]
+    GetPoolsPage {
+        /// Index of the first pool
+        offset: u128,
+        /// Maximum number of pools to return
+        limit: u128,
+    },
+
+    /// Get the binary record of a pool
+    #[opcode(7)]
+    #[returns(Vec<u8>)]
+    GetPoolMetadata {
+        /// Pool ID block
+        pool_id_block: u128,
+        /// Pool ID tx
+        pool_id_tx: u128,
+    },
+
+    /// Set the protocol fee of pools created from now on (admin only)
+    /// A zero fee disables the protocol fee
+    #[opcode(8)]
+    SetProtocolFee {
+        /// Fee in basis points of the denomination
+        fee_bps: u128,
+        /// Fee collector block
+        collector_block: u128,
+        /// Fee collector tx
+        collector_tx: u128,
+    },
+
+    /// Get the protocol fee: basis points, collector block and collector tx
+    #[opcode(9)]
+    #[returns(Vec<u8>)]
+    GetProtocolFee,
+
+    /// Pause new deposits into all pools (admin only)
+    /// Withdrawals are never paused
+    #[opcode(10)]
+    Pause,
+
+    /// Resume deposits (admin only)
+    #[opcode(11)]
+    Unpause,
+
+    /// Hand the admin role to another alkane (admin only)
+    #[opcode(12)]
+    TransferAdmin {
+        /// New admin alkane block
+        admin_block: u128,
+        /// New admin alkane tx
+        admin_tx: u128,
+    },
+
+    /// Check whether deposits are paused
+    #[opcode(13)]
+    #[returns(u128)]
+    IsPaused,
+
+    /// Register the verifier key of a circuit version and make it the one
+    /// stamped into new pools (admin only)
+    /// The key is read from the witness envelope. While a template is active,
+    /// new pools take the template's circuit version instead.
+    #[opcode(14)]
+    SetVerifierKey {
+        /// Version of the withdrawal circuit
+        circuit_version: u128,
+    },
+
+    /// Get the verifier key of a circuit version
+    #[opcode(15)]
+    #[returns(Vec<u8>)]
+    GetVerifierKey {
+        /// Version of the withdrawal circuit
+        circuit_version: u128,
+    },
+
+    /// Get the circuit version stamped into new pools (zero if none)
+    #[opcode(16)]
+    #[returns(u128)]
+    GetCircuitVersion,
+
+    /// Create the zkane pool for an asset without depositing
+    /// Does nothing if the pool already exists
+    #[opcode(17)]
+    #[returns(Vec<u8>)]
+    CreatePool {
+        /// Asset ID block
+        asset_id_block: u128,
+        /// Asset ID tx
+        asset_id_tx: u128,
+        /// Denomination for the pool
+        denomination: u128,
+    },
+
+    /// Retire the active pool of an asset/denomination pair and spawn its
+    /// successor, which takes all deposits from then on
+    /// Anyone may retire a full pool; retiring one with room left is admin only.
+    /// Retired pools keep processing withdrawals.
+    #[opcode(18)]
+    #[returns(Vec<u8>)]
+    RetirePool {
+        /// Asset ID block
+        asset_id_block: u128,
+        /// Asset ID tx
+        asset_id_tx: u128,
+        /// Denomination for the pool
+        denomination: u128,
+    },
+
+    /// Get the pool currently taking deposits for an asset/denomination pair
+    /// Returns the pool ID (32 bytes), or nothing if there is no pool
+    #[opcode(19)]
+    #[returns(Vec<u8>)]
+    GetActivePool {
+        /// Asset ID block
+        asset_id_block: u128,
+        /// Asset ID tx
+        asset_id_tx: u128,
+        /// Denomination for the pool
+        denomination: u128,
+    },
+
+    /// Get the successor of a retired pool
+    /// Returns the successor's ID (32 bytes), or nothing if the pool is active
+    #[opcode(20)]
+    #[returns(Vec<u8>)]
+    GetSuccessor {
+        /// Pool ID block
+        pool_id_block: u128,
+        /// Pool ID tx
+        pool_id_tx: u128,
+    },
+
+    /// Get pool and deposit totals across all pools and per asset, as JSON
+    /// Deposit totals are mirrored from the pools when the factory forwards
+    /// deposits, so they lag deposits made straight to a pool
+    #[opcode(21)]
+    #[returns(Vec<u8>)]
+    GetGlobalStats,
+
+    /// Register a pool template and make it the one new pools are created
+    /// from (admin only)
+    /// The verifier key of its circuit version must already be registered.
+    /// Returns the template version
+    #[opcode(22)]
+    #[returns(u128)]
+    RegisterTemplate {
+        /// Template alkane block
+        template_block: u128,
+        /// Template alkane tx
+        template_tx: u128,
+        /// Version of the withdrawal circuit the template verifies
+        circuit_version: u128,
+    },
+
+    /// Pin new pools to a registered template, e.g. to roll back (admin only)
+    #[opcode(23)]
+    SetTemplate {
+        /// Version of the template
+        template_version: u128,
+    },
+
+    /// Get the binary record of a template, or nothing if it isn't registered
+    #[opcode(24)]
+    #[returns(Vec<u8>)]
+    GetTemplate {
+        /// Version of the template
+        template_version: u128,
+    },
+
+    /// Get the version of the template new pools are created from (zero if none)
+    #[opcode(25)]
+    #[returns(u128)]
+    GetTemplateVersion,
+
+    /// Register how a denomination of an asset is displayed (admin only)
+    /// Replaces the asset's spec of the same denomination, if any
+    #[opcode(26)]
+    SetDenominationSpec {
+        /// Asset ID block
+        asset_id_block: u128,
+        /// Asset ID tx
+        asset_id_tx: u128,
+        /// Denomination in base units
+        base_units: u128,
+        /// Decimals of the asset
+        decimals: u128,
+        /// Display symbol, its ASCII bytes packed little-endian
+        symbol: u128,
+    },
+
+    /// Get the denomination specs of an asset in registration order
+    /// Returns the binary specs one after another, or nothing if there are none
+    #[opcode(27)]
+    #[returns(Vec<u8>)]
+    GetDenominationSpecs {
+        /// Asset ID block
+        asset_id_block: u128,
+        /// Asset ID tx
+        asset_id_tx: u128,
+    },
+
+    /// Reward the notes of a pool for the blocks they stay in it (admin only)
+    /// Zero points per block ends the pool's reward program
+    #[opcode(28)]
+    SetRewardProgram {
+        /// Pool ID block
+        pool_block: u128,
+        /// Pool ID tx
+        pool_tx: u128,
+        /// Reward alkane block
+        token_block: u128,
+        /// Reward alkane tx
+        token_tx: u128,
+        /// Points accrued per block
+        points_per_block: u128,
+        /// Blocks after which a note stops accruing points (zero for no limit)
+        max_blocks: u128,
+    },
+
+    /// Get the reward program of a pool
+    /// Returns the binary program, or nothing if the pool has none
+    #[opcode(29)]
+    #[returns(Vec<u8>)]
+    GetRewardProgram {
+        /// Pool ID block
+        pool_block: u128,
+        /// Pool ID tx
+        pool_tx: u128,
+    },
 }
 
 impl ZKaneFactory {
@@ -152,6 +431,138 @@ impl ZKaneFactory {
         
         // Update count
         count_ptr.set_value::<u128>(count + 1);
+
+        // The asset's first pool adds it to the asset list
+        if count == 0 {
+            self.add_asset(asset_id);
+        }
+    }
+
+    /// Get the pointer to the list of assets with pools, in the order of their first pool
+    fn assets_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/assets")
+    }
+
+    /// Get the number of assets with pools
+    fn get_asset_count(&self) -> u128 {
+        self.assets_pointer().select(&b"count".to_vec()).get_value::<u128>()
+    }
+
+    /// Append an asset to the asset list
+    fn add_asset(&self, asset_id: &AlkaneId) {
+        let count = self.get_asset_count();
+        self.assets_pointer()
+            .select(&count.to_le_bytes().to_vec())
+            .set(Arc::new(encode_pool_id(asset_id)));
+        self.assets_pointer().select(&b"count".to_vec()).set_value::<u128>(count + 1);
+    }
+
+    /// Get the pointer to the total number of deposits into all pools
+    fn total_deposits_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/total_deposits")
+    }
+
+    /// Get the pointer to the number of deposits into an asset's pools
+    fn asset_deposits_pointer(&self, asset_id: &AlkaneId) -> StoragePointer {
+        self.asset_pools_pointer(asset_id).select(&b"deposits".to_vec())
+    }
+
+    /// Get the pointer to a pool's deposit count as last mirrored from it
+    fn mirrored_deposits_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&pool_id.block.to_le_bytes());
+        key.extend_from_slice(&pool_id.tx.to_le_bytes());
+
+        StoragePointer::from_keyword("/mirrored_deposits").select(&key)
+    }
+
+    /// Mirror a pool's deposit count into the deposit totals
+    ///
+    /// The totals grow by the deposits made since the last sync, including
+    /// any made straight to the pool.
+    fn sync_deposit_count(&self, asset_id: &AlkaneId, pool_id: &AlkaneId) -> Result<()> {
+        let count = self.query_deposit_count(pool_id)?;
+        let mut mirrored_ptr = self.mirrored_deposits_pointer(pool_id);
+        let new_deposits = count.saturating_sub(mirrored_ptr.get_value::<u128>());
+        if new_deposits == 0 {
+            return Ok(());
+        }
+        mirrored_ptr.set_value::<u128>(count);
+
+        let mut total_ptr = self.total_deposits_pointer();
+        total_ptr.set_value::<u128>(total_ptr.get_value::<u128>() + new_deposits);
+        let mut asset_ptr = self.asset_deposits_pointer(asset_id);
+        asset_ptr.set_value::<u128>(asset_ptr.get_value::<u128>() + new_deposits);
+        Ok(())
+    }
+
+    /// Get the pointer to the pool records, indexed by creation order
+    fn pool_records_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/pool_records")
+    }
+
+    /// Get the pointer mapping a pool ID to its record index
+    fn pool_index_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&pool_id.block.to_le_bytes());
+        key.extend_from_slice(&pool_id.tx.to_le_bytes());
+
+        StoragePointer::from_keyword("/pool_index").select(&key)
+    }
+
+    /// Store the record of a newly created pool
+    fn store_pool_record(&self, index: u128, record: &PoolRecord) {
+        self.pool_records_pointer()
+            .select(&index.to_le_bytes().to_vec())
+            .set(Arc::new(record.to_bytes()));
+
+        let mut index_ptr = self.pool_index_pointer(&record.pool_id.into());
+        index_ptr.set(Arc::new(index.to_le_bytes().to_vec()));
+    }
+
+    /// Load a pool record with its current deposit count
+    fn load_pool_record(&self, index: u128) -> Result<PoolRecord> {
+        let data = self.pool_records_pointer()
+            .select(&index.to_le_bytes().to_vec())
+            .get();
+        let mut record = PoolRecord::from_bytes(&data)?;
+        record.deposit_count = self.query_deposit_count(&record.pool_id.into())?;
+        Ok(record)
+    }
+
+    /// Ask a pool for its deposit count
+    fn query_deposit_count(&self, pool_id: &AlkaneId) -> Result<u128> {
+        let cellpack = Cellpack {
+            target: pool_id.clone(),
+            inputs: vec![pool::GET_DEPOSIT_COUNT],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &alkanes_support::parcel::AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        let bytes: [u8; 16] = response.data.get(..16)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid deposit count response from pool"))?;
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    /// Ask a pool for the height of its tree
+    fn query_tree_height(&self, pool_id: &AlkaneId) -> Result<u32> {
+        let cellpack = Cellpack {
+            target: pool_id.clone(),
+            inputs: vec![pool::GET_CONFIG],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &alkanes_support::parcel::AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        let info: serde_json::Value = serde_json::from_slice(&response.data)
+            .map_err(|e| anyhow!("Invalid config response from pool: {}", e))?;
+        let config: ZKaneConfig = serde_json::from_value(info["config"].clone())
+            .map_err(|e| anyhow!("Invalid config response from pool: {}", e))?;
+        Ok(config.tree_height)
     }
 
     /// Check if a pool exists for the given asset and denomination (internal method)
@@ -162,57 +573,241 @@ impl ZKaneFactory {
 
     /// Get the pool ID for the given asset and denomination (internal method)
     fn get_pool_id_internal(&self, asset_id: &AlkaneId, denomination: u128) -> Option<AlkaneId> {
-        let pool_ptr = self.pool_pointer(asset_id, denomination);
-        let data = pool_ptr.get();
-        
-        if data.is_empty() {
-            return None;
-        }
-        
-        // Deserialize the AlkaneId
-        if data.len() >= 32 {
-            let block = u128::from_le_bytes(data[0..16].try_into().ok()?);
-            let tx = u128::from_le_bytes(data[16..32].try_into().ok()?);
-            Some(AlkaneId { block, tx })
-        } else {
-            None
-        }
+        decode_pool_id(&self.pool_pointer(asset_id, denomination).get())
     }
 
     /// Store a pool ID for the given asset and denomination
     fn store_pool_id(&self, asset_id: &AlkaneId, denomination: u128, pool_id: &AlkaneId) {
-        let mut pool_ptr = self.pool_pointer(asset_id, denomination);
-        
-        let mut data = Vec::new();
-        data.extend_from_slice(&pool_id.block.to_le_bytes());
-        data.extend_from_slice(&pool_id.tx.to_le_bytes());
-        
-        pool_ptr.set(Arc::new(data));
-        
+        self.pool_pointer(asset_id, denomination).set(Arc::new(encode_pool_id(pool_id)));
+    }
+
+    /// Add a newly spawned pool to the asset pools list and the pool records
+    fn register_pool(
+        &self,
+        asset_id: &AlkaneId,
+        denomination: u128,
+        pool_id: &AlkaneId,
+        template_version: u32,
+        circuit_version: u32,
+    ) {
         // Add to asset pools list
         self.add_to_asset_pools(asset_id, denomination, pool_id);
+
+        // Add to the paginated pool list, indexed by creation order
+        let record = PoolRecord {
+            asset_id: asset_id.clone().into(),
+            denomination,
+            pool_id: pool_id.clone().into(),
+            deposit_count: 0,
+            created_block: self.height(),
+            template_version,
+            circuit_version,
+        };
+        self.store_pool_record(self.get_pool_count(), &record);
+        self.increment_pool_count();
     }
 
     /// Generate a unique pool ID based on asset and denomination
     fn generate_pool_id(&self, asset_id: &AlkaneId, denomination: u128) -> AlkaneId {
-        // Use a hash of asset_id and denomination to generate a unique tx value
-        let mut hasher_input = Vec::new();
-        hasher_input.extend_from_slice(&asset_id.block.to_le_bytes());
-        hasher_input.extend_from_slice(&asset_id.tx.to_le_bytes());
-        hasher_input.extend_from_slice(&denomination.to_le_bytes());
-        
-        // Simple hash for demo - in production use proper hash function
-        let mut hash_value = 0u128;
-        for chunk in hasher_input.chunks(16) {
-            let mut bytes = [0u8; 16];
-            bytes[..chunk.len()].copy_from_slice(chunk);
-            hash_value ^= u128::from_le_bytes(bytes);
+        derive_pool_id(&asset_id.clone().into(), denomination).into()
+    }
+
+    /// Get the pointer to the active pool of an asset/denomination pair
+    ///
+    /// Empty until the first pool of the pair is retired.
+    fn active_pool_pointer(&self, asset_id: &AlkaneId, denomination: u128) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&asset_id.block.to_le_bytes());
+        key.extend_from_slice(&asset_id.tx.to_le_bytes());
+        key.extend_from_slice(&denomination.to_le_bytes());
+
+        StoragePointer::from_keyword("/active_pools").select(&key)
+    }
+
+    /// Get the pointer to the generation of the active pool of a pair
+    fn pool_generation_pointer(&self, asset_id: &AlkaneId, denomination: u128) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&asset_id.block.to_le_bytes());
+        key.extend_from_slice(&asset_id.tx.to_le_bytes());
+        key.extend_from_slice(&denomination.to_le_bytes());
+
+        StoragePointer::from_keyword("/pool_generations").select(&key)
+    }
+
+    /// Get the pointer to the successor of a retired pool
+    fn successor_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&pool_id.block.to_le_bytes());
+        key.extend_from_slice(&pool_id.tx.to_le_bytes());
+
+        StoragePointer::from_keyword("/successors").select(&key)
+    }
+
+    /// Get the pool taking deposits for the given asset and denomination
+    fn get_active_pool_internal(&self, asset_id: &AlkaneId, denomination: u128) -> Option<AlkaneId> {
+        decode_pool_id(&self.active_pool_pointer(asset_id, denomination).get())
+            .or_else(|| self.get_pool_id_internal(asset_id, denomination))
+    }
+
+    /// Get the successor of a retired pool (internal method)
+    fn get_successor_internal(&self, pool_id: &AlkaneId) -> Option<AlkaneId> {
+        decode_pool_id(&self.successor_pointer(pool_id).get())
+    }
+
+    /// Check whether a pool's tree is full, at the height it was created with
+    fn is_pool_full(&self, pool_id: &AlkaneId) -> Result<bool> {
+        Ok(self.query_deposit_count(pool_id)? >= 1u128 << self.query_tree_height(pool_id)?)
+    }
+
+    /// Get the pointer to the admin alkane ID
+    fn admin_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/admin")
+    }
+
+    /// Get the admin alkane ID
+    fn get_admin(&self) -> Result<AlkaneId> {
+        let data = self.admin_pointer().get();
+        if data.len() != 32 {
+            return Err(ZKaneError::NotInitialized.into_revert());
         }
-        
-        AlkaneId {
-            block: ZKANE_INSTANCE_BLOCK,
-            tx: hash_value,
+        Ok(AlkaneId {
+            block: u128::from_le_bytes(data[0..16].try_into().unwrap()),
+            tx: u128::from_le_bytes(data[16..32].try_into().unwrap()),
+        })
+    }
+
+    /// Set the admin alkane ID
+    fn set_admin(&self, admin: &AlkaneId) {
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&admin.block.to_le_bytes());
+        data.extend_from_slice(&admin.tx.to_le_bytes());
+        self.admin_pointer().set(Arc::new(data));
+    }
+
+    /// Require the call to carry the admin alkane
+    ///
+    /// The admin alkane is forwarded back to the caller with the response.
+    fn require_admin(&self, context: &Context) -> Result<()> {
+        let admin = self.get_admin()?;
+        let authorized = context
+            .incoming_alkanes
+            .0
+            .iter()
+            .any(|transfer| transfer.id == admin && transfer.value > 0);
+        if !authorized {
+            return Err(ZKaneError::Unauthorized("caller is not the factory admin".to_string()).into_revert());
+        }
+        Ok(())
+    }
+
+    /// Get the pointer to the pause flag
+    fn paused_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/paused")
+    }
+
+    /// Check whether deposits are paused (internal method)
+    fn is_paused_internal(&self) -> bool {
+        self.paused_pointer().get_value::<u8>() == 1
+    }
+
+    /// Set the pause flag
+    fn set_paused(&self, paused: bool) {
+        self.paused_pointer().set_value::<u8>(paused as u8);
+    }
+
+    /// Get the pointer to the verifier key of a circuit version
+    fn verifier_key_pointer(&self, circuit_version: u32) -> StoragePointer {
+        StoragePointer::from_keyword("/verifier_keys").select(&circuit_version.to_le_bytes().to_vec())
+    }
+
+    /// Get the pointer to the circuit version of new pools
+    fn circuit_version_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/circuit_version")
+    }
+
+    /// Get the circuit version stamped into new pools, if a key is registered
+    ///
+    /// The active template's circuit version takes precedence over the last
+    /// registered key.
+    fn get_circuit_version_internal(&self) -> Result<Option<u32>> {
+        if let Some((_, template)) = self.get_active_template()? {
+            return Ok(Some(template.circuit_version));
+        }
+        let version = self.circuit_version_pointer().get_value::<u32>();
+        if version == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(version))
+        }
+    }
+
+    /// Get the pointer to a pool template
+    fn template_pointer(&self, template_version: u32) -> StoragePointer {
+        StoragePointer::from_keyword("/templates").select(&template_version.to_le_bytes().to_vec())
+    }
+
+    /// Get the pointer to the number of registered templates
+    fn template_count_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/template_count")
+    }
+
+    /// Get the pointer to the version of the template of new pools
+    fn template_version_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/template_version")
+    }
+
+    /// Get a registered template
+    fn get_template_internal(&self, template_version: u32) -> Result<Option<PoolTemplate>> {
+        let data = self.template_pointer(template_version).get();
+        if data.is_empty() {
+            return Ok(None);
+        }
+        PoolTemplate::from_bytes(&data).map(Some)
+    }
+
+    /// Get the template new pools are created from, with its version
+    fn get_active_template(&self) -> Result<Option<(u32, PoolTemplate)>> {
+        let version = self.template_version_pointer().get_value::<u32>();
+        if version == 0 {
+            return Ok(None);
+        }
+        let template = self
+            .get_template_internal(version)?
+            .ok_or_else(|| anyhow!("Active template {} is not registered", version))?;
+        Ok(Some((version, template)))
+    }
+
+    /// Get the pointer to the denomination specs of an asset
+    fn denomination_specs_pointer(&self, asset_id: &AlkaneId) -> StoragePointer {
+        let mut key = Vec::new();
+        key.extend_from_slice(&asset_id.block.to_le_bytes());
+        key.extend_from_slice(&asset_id.tx.to_le_bytes());
+
+        StoragePointer::from_keyword("/denomination_specs").select(&key)
+    }
+
+    /// Get the pointer to the reward program of a pool
+    fn reward_program_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/reward_programs").select(&encode_pool_id(pool_id))
+    }
+
+    /// Decode the transaction executing this call
+    fn current_transaction(&self) -> Result<Transaction> {
+        consensus_decode::<Transaction>(&mut Cursor::new(self.transaction()))
+    }
+
+    /// Get the pointer to the protocol fee
+    fn protocol_fee_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/protocol_fee")
+    }
+
+    /// Get the protocol fee of new pools, if any
+    fn get_protocol_fee_internal(&self) -> Result<Option<ProtocolFee>> {
+        let data = self.protocol_fee_pointer().get();
+        if data.is_empty() {
+            return Ok(None);
         }
+        ProtocolFee::from_bytes(&data)
     }
 
     /// Observe initialization to prevent multiple initializations
@@ -222,21 +817,86 @@ impl ZKaneFactory {
             pointer.set_value::<u8>(1);
             Ok(())
         } else {
-            Err(anyhow!("Factory already initialized"))
+            Err(ZKaneError::AlreadyInitialized.into_revert())
+        }
+    }
+
+    /// Get the pointer to the storage schema version
+    fn schema_version_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword(SCHEMA_VERSION_KEY)
+    }
+
+    /// Get the storage schema version, 0 for factories deployed before versioning
+    fn get_schema_version(&self) -> Result<u32> {
+        decode_schema_version(&self.schema_version_pointer().get()).map_err(ZKaneError::into_revert)
+    }
+
+    /// Set the storage schema version
+    fn set_schema_version(&self, version: u32) {
+        self.schema_version_pointer().set(Arc::new(encode_schema_version(version)));
+    }
+
+    /// Bring storage written by an earlier version of the contract up to
+    /// [`FACTORY_SCHEMA_VERSION`]
+    ///
+    /// Run by every state-changing call, so the first one after an upgrade
+    /// migrates the factory. An uninitialized factory is left alone, as
+    /// initialization writes the current version.
+    fn migrate(&self) -> Result<()> {
+        if StoragePointer::from_keyword("/initialized").get().is_empty() {
+            return Ok(());
+        }
+        let stored = self.get_schema_version()?;
+        for version in pending_migrations(stored, FACTORY_SCHEMA_VERSION).map_err(ZKaneError::into_revert)? {
+            self.migrate_to(version)?;
+            self.set_schema_version(version);
+        }
+        Ok(())
+    }
+
+    /// Migrate storage from the previous schema version to `version`
+    fn migrate_to(&self, version: u32) -> Result<()> {
+        match version {
+            // Version 1 only adds the version marker to the unversioned layout
+            1 => Ok(()),
+            // Version 2 adds the asset list and the mirrored deposit totals,
+            // built from the pool records
+            2 => {
+                let mut assets: Vec<AlkaneId> = Vec::new();
+                for index in 0..self.get_pool_count() {
+                    let data = self.pool_records_pointer().select(&index.to_le_bytes().to_vec()).get();
+                    let record = PoolRecord::from_bytes(&data)?;
+                    let asset_id: AlkaneId = record.asset_id.into();
+                    if !assets.contains(&asset_id) {
+                        self.add_asset(&asset_id);
+                        assets.push(asset_id.clone());
+                    }
+                    self.sync_deposit_count(&asset_id, &record.pool_id.into())?;
+                }
+                Ok(())
+            }
+            _ => Err(anyhow!("No migration to storage schema version {}", version)),
         }
     }
 
     /// Initialize the factory
-    fn initialize(&self) -> Result<CallResponse> {
+    fn initialize(&self, admin_block: u128, admin_tx: u128) -> Result<CallResponse> {
         let context = self.context()?;
         let response = CallResponse::forward(&context.incoming_alkanes);
 
         // Prevent multiple initializations
         self.observe_initialization()?;
 
+        self.set_admin(&AlkaneId {
+            block: admin_block,
+            tx: admin_tx,
+        });
+
         // Initialize pool count
         self.pool_count_pointer().set_value::<u128>(0);
 
+        self.set_schema_version(FACTORY_SCHEMA_VERSION);
+
         Ok(response)
     }
 
@@ -250,17 +910,28 @@ impl ZKaneFactory {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
 
+        self.migrate()?;
+        if self.is_paused_internal() {
+            return Err(ZKaneError::DepositsPaused.into_revert());
+        }
+
+        // The incoming alkanes are the deposit; pools are created without
+        // depositing through `CreatePool`
+        if context.incoming_alkanes.0.iter().all(|transfer| transfer.value == 0) {
+            return Err(anyhow!("No alkanes attached to deposit; use CreatePool to create a pool without depositing"));
+        }
+
         let asset_id = AlkaneId {
             block: asset_id_block,
             tx: asset_id_tx,
         };
 
         // Check if pool already exists
-        if let Some(existing_pool_id) = self.get_pool_id_internal(&asset_id, denomination) {
-            // Pool exists, forward the incoming alkanes to it
+        if let Some(existing_pool_id) = self.get_active_pool_internal(&asset_id, denomination) {
+            // Pool exists, forward the incoming alkanes to its active generation
             let pool_cellpack = Cellpack {
-                target: existing_pool_id,
-                inputs: vec![1], // Deposit opcode
+                target: existing_pool_id.clone(),
+                inputs: vec![pool::DEPOSIT],
             };
 
             // Forward all incoming alkanes to the existing pool
@@ -269,38 +940,131 @@ impl ZKaneFactory {
                 &context.incoming_alkanes,
                 <Self as AlkaneResponder>::fuel(&self),
             )?;
+            self.sync_deposit_count(&asset_id, &existing_pool_id)?;
 
             // Return the pool's response
             return Ok(pool_response);
         }
 
-        // Pool doesn't exist, create it
-        let pool_id = self.generate_pool_id(&asset_id, denomination);
-
-        // Read configuration from witness envelope if provided
-        // TODO: Fix transaction access once API is clarified
-        let witness_data = vec![]; // Temporary placeholder
+        // Pool doesn't exist, create it. The envelope of a deposit carries its
+        // commitment, not a configuration, so the pool gets the default height
+        let (pool_id, pool_info) = self.create_pool_internal(&asset_id, denomination, DEFAULT_TREE_HEIGHT)?;
 
-        let tree_height = if !witness_data.is_empty() {
-            // Try to parse tree height from witness data
-            if witness_data.len() >= 4 {
-                u32::from_le_bytes(witness_data[0..4].try_into().unwrap_or([20, 0, 0, 0]))
-            } else {
-                20 // Default tree height
-            }
-        } else {
-            20 // Default tree height
+        // Now forward the deposit to the newly created pool
+        let deposit_cellpack = Cellpack {
+            target: pool_id.clone(),
+            inputs: vec![pool::DEPOSIT],
+        };
+
+        let deposit_response = self.call(
+            &deposit_cellpack,
+            &context.incoming_alkanes,
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        self.sync_deposit_count(&asset_id, &pool_id)?;
+
+        // Return information about the created pool
+        response.data = pool_info.to_string().into_bytes();
+        response.alkanes = deposit_response.alkanes;
+
+        Ok(response)
+    }
+
+    /// Create a zkane pool without depositing (for MessageDispatch macro)
+    ///
+    /// Creating a pool isn't a deposit, so it is allowed while deposits are
+    /// paused. Any incoming alkanes are returned.
+    fn create_pool(&self, asset_id_block: u128, asset_id_tx: u128, denomination: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        let asset_id = AlkaneId {
+            block: asset_id_block,
+            tx: asset_id_tx,
+        };
+
+        let pool_info = match self.get_active_pool_internal(&asset_id, denomination) {
+            Some(pool_id) => serde_json::json!({
+                "created": false,
+                "pool_id": {
+                    "block": pool_id.block,
+                    "tx": pool_id.tx
+                },
+                "asset_id": {
+                    "block": asset_id.block,
+                    "tx": asset_id.tx
+                },
+                "denomination": denomination
+            }),
+            None => {
+                // Read the configuration from the witness envelope if provided
+                let tx = self.current_transaction()?;
+                let witness_data = find_witness_payload(&tx, 0).unwrap_or_default();
+                let tree_height = parse_tree_height(&witness_data).map_err(ZKaneError::into_revert)?;
+                self.create_pool_internal(&asset_id, denomination, tree_height)?.1
+            }
         };
 
+        response.data = pool_info.to_string().into_bytes();
+
+        Ok(response)
+    }
+
+    /// Spawn, initialize and register the pool for an asset/denomination pair
+    ///
+    /// Returns the pool ID and the information reported about the new pool.
+    fn create_pool_internal(
+        &self,
+        asset_id: &AlkaneId,
+        denomination: u128,
+        tree_height: u32,
+    ) -> Result<(AlkaneId, serde_json::Value)> {
+        let pool_id = self.generate_pool_id(asset_id, denomination);
+        let pool_info = self.spawn_pool(asset_id, denomination, &pool_id, tree_height)?;
+
+        // Store the pool ID in our registry
+        self.store_pool_id(asset_id, denomination, &pool_id);
+
+        Ok((pool_id, pool_info))
+    }
+
+    /// Spawn and initialize a pool with a tree of `tree_height` levels,
+    /// adding it to the pool records
+    ///
+    /// Returns the information reported about the new pool.
+    fn spawn_pool(
+        &self,
+        asset_id: &AlkaneId,
+        denomination: u128,
+        pool_id: &AlkaneId,
+        tree_height: u32,
+    ) -> Result<serde_json::Value> {
+        // New pools take the protocol fee configured at creation time
+        let (fee_bps, fee_collector) = match self.get_protocol_fee_internal()? {
+            Some(fee) => (fee.fee_bps as u128, fee.collector),
+            None => (0, AlkaneId { block: 0, tx: 0 }.into()),
+        };
+
+        // New pools fetch the verifier key of this circuit version from the
+        // factory while initializing; the pool record pins both versions so
+        // wallets can tell which circuit a pool verifies
+        let template_version = self.get_active_template()?.map_or(0, |(version, _)| version);
+        let circuit_version = self.get_circuit_version_internal()?.unwrap_or(0);
+
         // Create the pool using cellpack to [6, pool_id.tx]
         let init_cellpack = Cellpack {
             target: pool_id.clone(),
             inputs: vec![
-                0, // Initialize opcode
-                asset_id_block,
-                asset_id_tx,
+                pool::INITIALIZE,
+                asset_id.block,
+                asset_id.tx,
                 denomination,
                 tree_height as u128,
+                fee_bps,
+                fee_collector.block,
+                fee_collector.tx,
+                circuit_version as u128,
             ],
         };
 
@@ -311,24 +1075,9 @@ impl ZKaneFactory {
             <Self as AlkaneResponder>::fuel(&self),
         )?;
 
-        // Store the pool ID in our registry
-        self.store_pool_id(&asset_id, denomination, &pool_id);
-        self.increment_pool_count();
-
-        // Now forward the deposit to the newly created pool
-        let deposit_cellpack = Cellpack {
-            target: pool_id.clone(),
-            inputs: vec![1], // Deposit opcode
-        };
+        self.register_pool(asset_id, denomination, pool_id, template_version, circuit_version);
 
-        let deposit_response = self.call(
-            &deposit_cellpack,
-            &context.incoming_alkanes,
-            <Self as AlkaneResponder>::fuel(&self),
-        )?;
-
-        // Return information about the created pool
-        let pool_info = serde_json::json!({
+        Ok(serde_json::json!({
             "created": true,
             "pool_id": {
                 "block": pool_id.block,
@@ -339,11 +1088,89 @@ impl ZKaneFactory {
                 "tx": asset_id.tx
             },
             "denomination": denomination,
-            "tree_height": tree_height
-        });
+            "tree_height": tree_height,
+            "template_version": template_version,
+            "circuit_version": circuit_version
+        }))
+    }
+
+    /// Retire the active pool of a pair and spawn its successor (for MessageDispatch macro)
+    fn retire_pool(&self, asset_id_block: u128, asset_id_tx: u128, denomination: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        let asset_id = AlkaneId {
+            block: asset_id_block,
+            tx: asset_id_tx,
+        };
+        let retired = self
+            .get_active_pool_internal(&asset_id, denomination)
+            .ok_or_else(|| anyhow!("Unknown pool"))?;
+
+        // A full pool can't take deposits anyway, so anyone may roll it over
+        if !self.is_pool_full(&retired)? {
+            self.require_admin(&context)?;
+        }
+        // Count the retired pool's last deposits before it stops taking more
+        self.sync_deposit_count(&asset_id, &retired)?;
 
+        let mut generation_ptr = self.pool_generation_pointer(&asset_id, denomination);
+        let generation = generation_ptr
+            .get_value::<u32>()
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Pool generation out of range"))?;
+        let successor: AlkaneId = derive_pool_id_at(&asset_id.clone().into(), denomination, generation).into();
+
+        // The successor keeps the retired pool's height, which anyone rolling
+        // over a full pool can't choose
+        let tree_height = self.query_tree_height(&retired)?;
+        let mut pool_info = self.spawn_pool(&asset_id, denomination, &successor, tree_height)?;
+        self.successor_pointer(&retired).set(Arc::new(encode_pool_id(&successor)));
+        self.active_pool_pointer(&asset_id, denomination)
+            .set(Arc::new(encode_pool_id(&successor)));
+        generation_ptr.set_value::<u32>(generation);
+
+        pool_info["generation"] = generation.into();
+        pool_info["retired_pool_id"] = serde_json::json!({
+            "block": retired.block,
+            "tx": retired.tx
+        });
         response.data = pool_info.to_string().into_bytes();
-        response.alkanes = deposit_response.alkanes;
+
+        Ok(response)
+    }
+
+    /// Get the active pool of a pair (for MessageDispatch macro)
+    fn get_active_pool(&self, asset_id_block: u128, asset_id_tx: u128, denomination: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let asset_id = AlkaneId {
+            block: asset_id_block,
+            tx: asset_id_tx,
+        };
+        response.data = self
+            .get_active_pool_internal(&asset_id, denomination)
+            .map(|pool_id| encode_pool_id(&pool_id))
+            .unwrap_or_default();
+
+        Ok(response)
+    }
+
+    /// Get the successor of a retired pool (for MessageDispatch macro)
+    fn get_successor(&self, pool_id_block: u128, pool_id_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let pool_id = AlkaneId {
+            block: pool_id_block,
+            tx: pool_id_tx,
+        };
+        response.data = self
+            .get_successor_internal(&pool_id)
+            .map(|successor| encode_pool_id(&successor))
+            .unwrap_or_default();
 
         Ok(response)
     }
@@ -448,12 +1275,422 @@ impl ZKaneFactory {
             "total_pools": pool_count,
             "factory_version": "1.0.0",
             "zkane_template_block": ZKANE_TEMPLATE_BLOCK,
-            "zkane_instance_block": ZKANE_INSTANCE_BLOCK
+            "zkane_instance_block": ZKANE_INSTANCE_BLOCK,
+            "schema_version": self.get_schema_version()?,
+            "supported_schema_version": FACTORY_SCHEMA_VERSION,
+            "template_version": self.template_version_pointer().get_value::<u32>(),
+            "template_count": self.template_count_pointer().get_value::<u32>()
         });
 
         response.data = stats.to_string().into_bytes();
         Ok(response)
     }
+
+    /// Get pool and deposit totals (for MessageDispatch macro)
+    fn get_global_stats(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut assets = Vec::new();
+        for index in 0..self.get_asset_count() {
+            let data = self.assets_pointer().select(&index.to_le_bytes().to_vec()).get();
+            let asset_id = decode_pool_id(&data).ok_or_else(|| anyhow!("Corrupt asset list entry {}", index))?;
+            assets.push(AssetStats {
+                asset_id: asset_id.clone().into(),
+                pools: self.asset_pools_pointer(&asset_id).select(&b"count".to_vec()).get_value::<u128>(),
+                deposits: self.asset_deposits_pointer(&asset_id).get_value::<u128>(),
+            });
+        }
+
+        let stats = GlobalStats {
+            total_pools: self.get_pool_count(),
+            total_deposits: self.total_deposits_pointer().get_value::<u128>(),
+            assets,
+        };
+
+        response.data = serde_json::to_vec(&stats)?;
+        Ok(response)
+    }
+
+    /// Get a page of pools (for MessageDispatch macro)
+    fn get_pools_page(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let total = self.get_pool_count();
+        let start = offset.min(total);
+        let end = start.saturating_add(limit.min(MAX_POOLS_PAGE)).min(total);
+
+        let mut data = total.to_le_bytes().to_vec();
+        for index in start..end {
+            data.extend_from_slice(&self.load_pool_record(index)?.to_bytes());
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the record of a pool (for MessageDispatch macro)
+    fn get_pool_metadata(&self, pool_id_block: u128, pool_id_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let pool_id = AlkaneId {
+            block: pool_id_block,
+            tx: pool_id_tx,
+        };
+
+        let index_data = self.pool_index_pointer(&pool_id).get();
+        let index_bytes: [u8; 16] = index_data.as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Unknown pool"))?;
+
+        response.data = self.load_pool_record(u128::from_le_bytes(index_bytes))?.to_bytes();
+        Ok(response)
+    }
+
+    /// Set the protocol fee of new pools (for MessageDispatch macro)
+    fn set_protocol_fee(&self, fee_bps: u128, collector_block: u128, collector_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        if fee_bps == 0 {
+            self.protocol_fee_pointer().set(Arc::new(Vec::new()));
+        } else {
+            let fee_bps = u16::try_from(fee_bps).map_err(|_| anyhow!("Protocol fee out of range"))?;
+            let collector = AlkaneId {
+                block: collector_block,
+                tx: collector_tx,
+            };
+            let fee = ProtocolFee::new(fee_bps, collector.into())?;
+            self.protocol_fee_pointer().set(Arc::new(fee.to_bytes()));
+        }
+
+        Ok(response)
+    }
+
+    /// Pause deposits (for MessageDispatch macro)
+    fn pause(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+        self.set_paused(true);
+
+        Ok(response)
+    }
+
+    /// Resume deposits (for MessageDispatch macro)
+    fn unpause(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+        self.set_paused(false);
+
+        Ok(response)
+    }
+
+    /// Hand the admin role to another alkane (for MessageDispatch macro)
+    fn transfer_admin(&self, admin_block: u128, admin_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+        self.set_admin(&AlkaneId {
+            block: admin_block,
+            tx: admin_tx,
+        });
+
+        Ok(response)
+    }
+
+    /// Check whether deposits are paused (for MessageDispatch macro)
+    fn is_paused(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = (self.is_paused_internal() as u128).to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Register a verifier key (for MessageDispatch macro)
+    fn set_verifier_key(&self, circuit_version: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        let circuit_version = u32::try_from(circuit_version)
+            .ok()
+            .filter(|version| *version > 0)
+            .ok_or_else(|| anyhow!("Circuit version out of range"))?;
+        let tx = self.current_transaction()?;
+        let key = find_witness_payload(&tx, 0)
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| anyhow!("Missing verifier key envelope"))?;
+
+        // Keys are immutable once registered, so existing pools keep verifying
+        // against the key they were created with
+        let mut pointer = self.verifier_key_pointer(circuit_version);
+        let existing = pointer.get();
+        if !existing.is_empty() && *existing != key {
+            return Err(anyhow!("A different key is registered for circuit version {}", circuit_version));
+        }
+        pointer.set(Arc::new(key));
+        self.circuit_version_pointer().set_value::<u32>(circuit_version);
+
+        Ok(response)
+    }
+
+    /// Get the verifier key of a circuit version (for MessageDispatch macro)
+    fn get_verifier_key(&self, circuit_version: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let circuit_version =
+            u32::try_from(circuit_version).map_err(|_| anyhow!("Circuit version out of range"))?;
+        response.data = self.verifier_key_pointer(circuit_version).get().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the circuit version of new pools (for MessageDispatch macro)
+    fn get_circuit_version(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = (self.get_circuit_version_internal()?.unwrap_or(0) as u128)
+            .to_le_bytes()
+            .to_vec();
+
+        Ok(response)
+    }
+
+    /// Register a pool template (for MessageDispatch macro)
+    fn register_template(&self, template_block: u128, template_tx: u128, circuit_version: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        // Templates are WASM deployed with [4, n]
+        if template_block != ZKANE_TEMPLATE_BLOCK {
+            return Err(anyhow!("Templates must be deployed at block {}", ZKANE_TEMPLATE_BLOCK));
+        }
+        let circuit_version = u32::try_from(circuit_version)
+            .ok()
+            .filter(|version| *version > 0)
+            .ok_or_else(|| anyhow!("Circuit version out of range"))?;
+        // Pools created from the template fetch this key while initializing
+        if self.verifier_key_pointer(circuit_version).get().is_empty() {
+            return Err(ZKaneError::UnsupportedCircuitVersion(circuit_version).into_revert());
+        }
+
+        let mut count_ptr = self.template_count_pointer();
+        let template_version = count_ptr
+            .get_value::<u32>()
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Template version out of range"))?;
+        let template = PoolTemplate {
+            template_id: AlkaneId {
+                block: template_block,
+                tx: template_tx,
+            }
+            .into(),
+            circuit_version,
+            registered_block: self.height(),
+        };
+        // Templates are immutable once registered, so pool records keep
+        // pointing at the template they were created from
+        self.template_pointer(template_version).set(Arc::new(template.to_bytes()));
+        count_ptr.set_value::<u32>(template_version);
+        self.template_version_pointer().set_value::<u32>(template_version);
+
+        response.data = (template_version as u128).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Pin new pools to a registered template (for MessageDispatch macro)
+    fn set_template(&self, template_version: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        let template_version = u32::try_from(template_version)
+            .ok()
+            .filter(|version| *version > 0)
+            .ok_or_else(|| anyhow!("Template version out of range"))?;
+        if self.get_template_internal(template_version)?.is_none() {
+            return Err(anyhow!("Unknown template version {}", template_version));
+        }
+        self.template_version_pointer().set_value::<u32>(template_version);
+
+        Ok(response)
+    }
+
+    /// Get a registered template (for MessageDispatch macro)
+    fn get_template(&self, template_version: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let template_version =
+            u32::try_from(template_version).map_err(|_| anyhow!("Template version out of range"))?;
+        response.data = self.template_pointer(template_version).get().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the template version of new pools (for MessageDispatch macro)
+    fn get_template_version(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = (self.template_version_pointer().get_value::<u32>() as u128)
+            .to_le_bytes()
+            .to_vec();
+
+        Ok(response)
+    }
+
+    /// Register a denomination spec (for MessageDispatch macro)
+    fn set_denomination_spec(
+        &self,
+        asset_id_block: u128,
+        asset_id_tx: u128,
+        base_units: u128,
+        decimals: u128,
+        symbol: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        let decimals = u8::try_from(decimals).map_err(|_| anyhow!("Decimals out of range"))?;
+        let spec = DenominationSpec::from_packed(base_units, decimals, symbol).map_err(ZKaneError::into_revert)?;
+
+        let asset_id = AlkaneId {
+            block: asset_id_block,
+            tx: asset_id_tx,
+        };
+        let mut pointer = self.denomination_specs_pointer(&asset_id);
+        let mut specs = DenominationSpec::parse_list(&pointer.get()).map_err(ZKaneError::into_revert)?;
+        match specs.iter_mut().find(|existing| existing.base_units == base_units) {
+            Some(existing) => *existing = spec,
+            None => specs.push(spec),
+        }
+        pointer.set(Arc::new(specs.iter().flat_map(DenominationSpec::to_bytes).collect()));
+
+        Ok(response)
+    }
+
+    /// Get the denomination specs of an asset (for MessageDispatch macro)
+    fn get_denomination_specs(&self, asset_id_block: u128, asset_id_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let asset_id = AlkaneId {
+            block: asset_id_block,
+            tx: asset_id_tx,
+        };
+        response.data = self.denomination_specs_pointer(&asset_id).get().to_vec();
+
+        Ok(response)
+    }
+
+    /// Set or end the reward program of a pool (for MessageDispatch macro)
+    fn set_reward_program(
+        &self,
+        pool_block: u128,
+        pool_tx: u128,
+        token_block: u128,
+        token_tx: u128,
+        points_per_block: u128,
+        max_blocks: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.require_admin(&context)?;
+
+        let pool_id = AlkaneId {
+            block: pool_block,
+            tx: pool_tx,
+        };
+        let mut pointer = self.reward_program_pointer(&pool_id);
+        if points_per_block == 0 {
+            pointer.set(Arc::new(Vec::new()));
+            return Ok(response);
+        }
+        let token = AlkaneId {
+            block: token_block,
+            tx: token_tx,
+        };
+        let max_blocks = u64::try_from(max_blocks).map_err(|_| anyhow!("Max blocks out of range"))?;
+        let program = RewardProgram::new(token.into(), points_per_block, max_blocks).map_err(ZKaneError::into_revert)?;
+        pointer.set(Arc::new(program.to_bytes()));
+
+        Ok(response)
+    }
+
+    /// Get the reward program of a pool (for MessageDispatch macro)
+    fn get_reward_program(&self, pool_block: u128, pool_tx: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let pool_id = AlkaneId {
+            block: pool_block,
+            tx: pool_tx,
+        };
+        response.data = self.reward_program_pointer(&pool_id).get().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the protocol fee of new pools (for MessageDispatch macro)
+    fn get_protocol_fee(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = self
+            .get_protocol_fee_internal()?
+            .map(|fee| fee.to_bytes())
+            .unwrap_or_else(|| vec![0u8; ProtocolFee::SIZE]);
+
+        Ok(response)
+    }
+}
+
+/// Encode a pool ID as stored and returned by the factory
+fn encode_pool_id(pool_id: &AlkaneId) -> Vec<u8> {
+    let mut data = Vec::with_capacity(32);
+    data.extend_from_slice(&pool_id.block.to_le_bytes());
+    data.extend_from_slice(&pool_id.tx.to_le_bytes());
+    data
+}
+
+/// Decode a stored pool ID, `None` if nothing is stored
+fn decode_pool_id(data: &[u8]) -> Option<AlkaneId> {
+    if data.len() < 32 {
+        return None;
+    }
+    let block = u128::from_le_bytes(data[0..16].try_into().ok()?);
+    let tx = u128::from_le_bytes(data[16..32].try_into().ok()?);
+    Some(AlkaneId { block, tx })
 }
 
 impl AlkaneResponder for ZKaneFactory {}
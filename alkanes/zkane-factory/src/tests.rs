@@ -1,6 +1,7 @@
 use super::*;
 use wasm_bindgen_test::*;
 use alkanes_runtime::test_utils::MockContext;
+use zkane_common::{pool_config_envelope, MAX_TREE_HEIGHT, MIN_TREE_HEIGHT};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
@@ -18,4 +19,47 @@ fn test_initialize_factory() {
     context.teardown();
 
     assert!(result.is_ok());
-}
\ No newline at end of file
+}
+#[wasm_bindgen_test]
+fn test_abi_matches_dispatch() {
+    // Every method in the ABI dispatches with exactly its inputs
+    for method in zkane_abi::factory::METHODS {
+        let inputs = vec![0u128; method.inputs.len()];
+        assert!(ZKaneFactoryMessage::from_opcode(method.opcode, inputs).is_ok(), "{} doesn't dispatch", method.name);
+        if !method.inputs.is_empty() {
+            let short = vec![0u128; method.inputs.len() - 1];
+            assert!(ZKaneFactoryMessage::from_opcode(method.opcode, short).is_err(), "{} takes fewer inputs", method.name);
+        }
+    }
+
+    // And nothing dispatches that the ABI doesn't know
+    for opcode in 0..=100u128 {
+        if zkane_abi::factory::method(opcode).is_none() {
+            assert!(ZKaneFactoryMessage::from_opcode(opcode, vec![0u128; 8]).is_err(), "opcode {} missing from the ABI", opcode);
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_parse_tree_height() {
+    assert_eq!(parse_tree_height(&[]).unwrap(), DEFAULT_TREE_HEIGHT);
+    assert_eq!(parse_tree_height(&pool_config_envelope(24)).unwrap(), 24);
+    assert!(validate_tree_height(DEFAULT_TREE_HEIGHT.into()).is_ok());
+    assert_eq!(parse_tree_height(&pool_config_envelope(MIN_TREE_HEIGHT)).unwrap(), MIN_TREE_HEIGHT);
+    assert_eq!(parse_tree_height(&pool_config_envelope(MAX_TREE_HEIGHT)).unwrap(), MAX_TREE_HEIGHT);
+
+    // Envelopes without the magic, like a deposit commitment, configure
+    // nothing, whatever their first bytes
+    assert_eq!(parse_tree_height(&24u32.to_le_bytes()).unwrap(), DEFAULT_TREE_HEIGHT);
+    let mut commitment = [0xabu8; 32];
+    commitment[..4].copy_from_slice(&3u32.to_le_bytes());
+    assert_eq!(parse_tree_height(&commitment).unwrap(), DEFAULT_TREE_HEIGHT);
+
+    // Heights a pool can't have are rejected rather than defaulted
+    for height in [0u32, 3, MIN_TREE_HEIGHT - 1, MAX_TREE_HEIGHT + 1, 64, u32::MAX] {
+        let err = parse_tree_height(&pool_config_envelope(height)).unwrap_err();
+        assert!(matches!(err, ZKaneError::InvalidTreeHeight { .. }));
+    }
+    let err = parse_tree_height(&pool_config_envelope(24)[..6]).unwrap_err();
+    assert!(matches!(err, ZKaneError::InvalidTreeHeight { height: 0, .. }));
+}
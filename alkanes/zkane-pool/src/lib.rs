@@ -11,14 +11,26 @@ use alkanes_runtime::{declare_alkane, message::MessageDispatch, runtime::AlkaneR
 use alkanes_runtime::storage::StoragePointer;
 use alkanes_support::response::CallResponse;
 use alkanes_support::context::Context;
-use alkanes_support::parcel::AlkaneTransfer;
+use alkanes_support::parcel::{AlkaneTransfer, AlkaneTransferParcel};
+use alkanes_support::cellpack::Cellpack;
 use alkanes_support::witness::find_witness_payload;
 use alkanes_support::id::AlkaneId;
 use metashrew_support::index_pointer::KeyValuePointer;
 use metashrew_support::utils::consensus_decode;
 use metashrew_support::compat::to_arraybuffer_layout;
-use zkane_common::{Commitment, NullifierHash, WithdrawalProof, ZKaneConfig};
-use zkane_crypto::{generate_commitment, generate_nullifier_hash, verify_merkle_path};
+use zkane_common::{
+    calculate_outputs_hash, find_outputs_window, validate_recipient, AmountWitness, Commitment, ContractEvent,
+    Nullifier, NullifierHash, PoolMode, PoolReserves, ProtocolFee, Recipient, RewardProgram, SpendEvent, SplitWitness, TreeHash,
+    WithdrawalAmounts, WithdrawalProof, WithdrawalWitness, ZKaneConfig, ZKaneError, ZKaneResult, reward_claim_hash,
+    decode_schema_version, encode_schema_version, pending_migrations, validate_tree_height, POOL_SCHEMA_VERSION,
+    SCHEMA_VERSION_KEY, SPLIT_OUTPUTS,
+};
+use zkane_abi::{factory, FEE_COLLECTOR_RECEIVE};
+use zkane_core::DepositExtractor;
+use zkane_crypto::{
+    compute_root_from_path_with, generate_commitment, generate_nullifier_hash, node_key, sparse_node_key, MerkleTree,
+    NodeStore, NullifierTree, SparseNode, SparseNodeStore,
+};
 use anyhow::{anyhow, Result};
 use bitcoin::{Transaction, TxOut};
 use std::io::Cursor;
@@ -27,6 +39,65 @@ use std::sync::Arc;
 #[cfg(test)]
 pub mod tests;
 
+/// Maximum number of commitments returned by a single `GetCommitmentRange` call
+pub const MAX_COMMITMENT_RANGE: u32 = 1000;
+
+/// Maximum number of nullifier hashes checked by a single `AreNullifiersSpent` call
+pub const MAX_NULLIFIER_BATCH: u32 = 100;
+
+/// Nodes of the pool's Merkle tree, kept in contract storage
+#[derive(Debug, Clone, Copy, Default)]
+struct StorageNodeStore;
+
+impl StorageNodeStore {
+    /// Get the pointer to a node
+    fn pointer(level: u32, index: u32) -> StoragePointer {
+        StoragePointer::from_keyword("/merkle_nodes/").select(&node_key(level, index).to_vec())
+    }
+}
+
+impl NodeStore for StorageNodeStore {
+    fn get(&self, level: u32, index: u32) -> ZKaneResult<Option<[u8; 32]>> {
+        Ok(<[u8; 32]>::try_from(Self::pointer(level, index).get().as_slice()).ok())
+    }
+
+    fn set(&mut self, level: u32, index: u32, hash: [u8; 32]) -> ZKaneResult<()> {
+        Self::pointer(level, index).set(Arc::new(hash.to_vec()));
+        Ok(())
+    }
+
+    fn remove(&mut self, level: u32, index: u32) -> ZKaneResult<()> {
+        Self::pointer(level, index).set(Arc::new(Vec::new()));
+        Ok(())
+    }
+}
+
+/// Nodes of the pool's nullifier tree, kept in contract storage
+#[derive(Debug, Clone, Copy, Default)]
+struct StorageSparseNodeStore;
+
+impl StorageSparseNodeStore {
+    /// Get the pointer to a node
+    fn pointer(depth: u32, path: &[u8; 32]) -> StoragePointer {
+        StoragePointer::from_keyword("/nullifier_tree/").select(&sparse_node_key(depth, path).to_vec())
+    }
+}
+
+impl SparseNodeStore for StorageSparseNodeStore {
+    fn get(&self, depth: u32, path: &[u8; 32]) -> ZKaneResult<Option<SparseNode>> {
+        let bytes = Self::pointer(depth, path).get();
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        SparseNode::from_bytes(&bytes).map(Some)
+    }
+
+    fn set(&mut self, depth: u32, path: &[u8; 32], node: SparseNode) -> ZKaneResult<()> {
+        Self::pointer(depth, path).set(Arc::new(node.to_bytes().to_vec()));
+        Ok(())
+    }
+}
+
 /// ZKane privacy pool contract
 #[derive(Default)]
 pub struct ZKaneContract {
@@ -63,6 +134,57 @@ struct WithdrawalWitnessData {
     /// Hash of the transaction outputs (for recipient validation)
     /// This prevents frontrunning by binding the proof to specific outputs
     outputs_hash: [u8; 32],
+    /// Hash of the output paying the relayer (zero for self-relayed withdrawals)
+    #[serde(default)]
+    relayer_output_hash: [u8; 32],
+    /// Fee paid to the relayer out of the denomination
+    #[serde(default)]
+    fee: u128,
+    /// Version of the circuit the proof was generated with
+    circuit_version: u32,
+    /// Hash of the recipient outputs the proof commits to (zero if unbound)
+    #[serde(default)]
+    recipients_hash: [u8; 32],
+    /// The scriptPubKey the recipient outputs start with (empty if unnamed)
+    #[serde(default)]
+    recipient: Recipient,
+    /// Last block height the proof may be spent at (zero if it never expires)
+    #[serde(default)]
+    not_after_height: u64,
+}
+
+impl From<WithdrawalWitness> for WithdrawalWitnessData {
+    fn from(witness: WithdrawalWitness) -> Self {
+        Self {
+            proof: witness.proof.proof,
+            merkle_root: witness.proof.merkle_root,
+            nullifier_hash: witness.proof.nullifier_hash.0,
+            path_elements: witness.path.elements,
+            path_indices: witness.path.indices,
+            leaf_index: witness.leaf_index,
+            commitment: witness.commitment.0,
+            outputs_hash: witness.outputs_hash,
+            relayer_output_hash: witness.proof.relayer_output_hash,
+            fee: witness.proof.fee,
+            circuit_version: witness.proof.circuit_version,
+            recipients_hash: witness.proof.recipients_hash,
+            recipient: witness.proof.recipient,
+            not_after_height: witness.proof.not_after_height,
+        }
+    }
+}
+
+impl WithdrawalWitnessData {
+    /// The event data of spending this witness's note
+    fn spend_event(&self, protocol_fee: u128) -> SpendEvent {
+        SpendEvent {
+            nullifier_hash: NullifierHash::new(self.nullifier_hash),
+            outputs_hash: self.outputs_hash,
+            relayer_output_hash: self.relayer_output_hash,
+            fee: self.fee,
+            protocol_fee,
+        }
+    }
 }
 
 /// Message enum for opcode-based dispatch
@@ -73,11 +195,21 @@ enum ZKaneContractMessage {
     Initialize {
         asset_id_block: u128,
         asset_id_tx: u128,
+        /// Value of every note. Zero, for a variable-amount pool, is refused
+        /// until deposits' amount proofs are verified
         denomination: u128,
         tree_height: u128,
+        /// Protocol fee in basis points (zero for no fee)
+        fee_bps: u128,
+        fee_collector_block: u128,
+        fee_collector_tx: u128,
+        /// Circuit version whose verifier key is fetched from the factory
+        /// (zero to keep the default)
+        circuit_version: u128,
     },
 
     /// Deposit alkanes into the privacy pool
+    /// A variable-amount pool's deposit carries an `AmountWitness` envelope
     #[opcode(1)]
     Deposit,
 
@@ -85,6 +217,30 @@ enum ZKaneContractMessage {
     #[opcode(2)]
     Withdraw,
 
+    /// Withdraw part of a note, keeping the change in fresh notes
+    #[opcode(3)]
+    WithdrawSplit,
+
+    /// Withdraw as one of several withdrawals batched in a transaction
+    /// The proof is bound to its own outputs instead of all of them
+    #[opcode(4)]
+    WithdrawBatched {
+        /// Input carrying this withdrawal's witness envelope
+        witness_input: u128,
+    },
+
+    /// Pre-register the hash of a commitment, passed as two little-endian
+    /// halves, for a front-run-protected deposit
+    #[opcode(5)]
+    RegisterCommitment {
+        registration_hash_low: u128,
+        registration_hash_high: u128,
+    },
+
+    /// Deposit a commitment registered in an earlier block
+    #[opcode(6)]
+    DepositRegistered,
+
     /// Get the current merkle root
     #[opcode(10)]
     #[returns(Vec<u8>)]
@@ -95,10 +251,137 @@ enum ZKaneContractMessage {
     #[returns(u128)]
     GetDepositCount,
 
+    /// Get the commitment stored at a leaf index
+    #[opcode(12)]
+    #[returns(Vec<u8>)]
+    GetCommitment {
+        index: u128,
+    },
+
+    /// Get up to `count` consecutive commitments starting at `start`,
+    /// packed as 32-byte leaves
+    #[opcode(13)]
+    #[returns(Vec<u8>)]
+    GetCommitmentRange {
+        start: u128,
+        count: u128,
+    },
+
     /// Get the denomination
     #[opcode(14)]
     #[returns(u128)]
     GetDenomination,
+
+    /// Get the protocol fee: basis points, collector block and collector tx
+    #[opcode(15)]
+    #[returns(Vec<u8>)]
+    GetProtocolFee,
+
+    /// Get the version of the withdrawal circuit accepted by the pool
+    #[opcode(16)]
+    #[returns(u128)]
+    GetCircuitVersion,
+
+    /// Check whether a nullifier hash has been spent, returning 1 if it has
+    /// and 0 otherwise. The hash is passed as two little-endian halves.
+    #[opcode(17)]
+    #[returns(u128)]
+    IsNullifierSpent {
+        nullifier_hash_low: u128,
+        nullifier_hash_high: u128,
+    },
+
+    /// Check up to `MAX_NULLIFIER_BATCH` nullifier hashes at once. The
+    /// `count` hashes follow as two little-endian halves each, and one byte
+    /// per hash is returned: 1 if it has been spent and 0 otherwise.
+    #[opcode(18)]
+    #[returns(Vec<u8>)]
+    AreNullifiersSpent {
+        count: u128,
+    },
+
+    /// Get the height a registration hash was registered at, or 0 if it is
+    /// not registered. The hash is passed as two little-endian halves.
+    #[opcode(19)]
+    #[returns(u128)]
+    GetRegistration {
+        registration_hash_low: u128,
+        registration_hash_high: u128,
+    },
+
+    /// Get the pool configuration and storage schema version, as JSON
+    /// `schema_version` is the stored version, which trails
+    /// `supported_schema_version` until the pool's first state-changing
+    /// call after an upgrade migrates it
+    #[opcode(20)]
+    #[returns(Vec<u8>)]
+    GetConfig,
+
+    /// Get the root of the nullifier tree, followed by a byte set to 1 if
+    /// the tree holds every spent nullifier. Pools upgraded to the tree only
+    /// hold the nullifiers spent since.
+    #[opcode(21)]
+    #[returns(Vec<u8>)]
+    GetNullifierRoot,
+
+    /// Get the encoded nullifier tree proof of whether a nullifier hash has
+    /// been spent. The hash is passed as two little-endian halves.
+    #[opcode(22)]
+    #[returns(Vec<u8>)]
+    GetNullifierProof {
+        nullifier_hash_low: u128,
+        nullifier_hash_high: u128,
+    },
+
+    /// Get the height a leaf was inserted at, or 0 if it was inserted
+    /// before the pool recorded heights
+    #[opcode(23)]
+    #[returns(u128)]
+    GetLeafHeight {
+        index: u128,
+    },
+
+    /// Add the incoming reward alkanes of the pool's reward program to the
+    /// balance claims are paid from
+    #[opcode(24)]
+    FundRewards,
+
+    /// Register the hash of a reward claim, passed as two little-endian
+    /// halves, ahead of revealing the claim
+    #[opcode(25)]
+    RegisterRewardClaim {
+        claim_hash_low: u128,
+        claim_hash_high: u128,
+    },
+
+    /// Claim the reward points of a withdrawn note, revealing its nullifier
+    /// as two little-endian halves. The claim must have been registered in
+    /// an earlier block for the transaction's outputs
+    #[opcode(26)]
+    ClaimRewards {
+        nullifier_low: u128,
+        nullifier_high: u128,
+    },
+
+    /// Get the unclaimed reward points of a nullifier hash, passed as two
+    /// little-endian halves
+    #[opcode(27)]
+    #[returns(u128)]
+    GetRewardClaim {
+        nullifier_hash_low: u128,
+        nullifier_hash_high: u128,
+    },
+
+    /// Get the reward alkanes the pool holds for claims
+    #[opcode(28)]
+    #[returns(u128)]
+    GetRewardBalance,
+
+    /// Get the pool's balance of its asset held for notes, its deposit and
+    /// withdrawal counts, and whether the counts are complete
+    #[opcode(29)]
+    #[returns(Vec<u8>)]
+    GetReserves,
 }
 
 impl ZKaneContract {
@@ -108,10 +391,10 @@ impl ZKaneContract {
     }
 
     /// Get the configuration
-    fn get_config(&self) -> Result<ZKaneConfig> {
+    fn load_config(&self) -> Result<ZKaneConfig> {
         let data = self.config_pointer().get();
         if data.is_empty() {
-            return Err(anyhow!("Contract not initialized"));
+            return Err(ZKaneError::NotInitialized.into_revert());
         }
         
         let config: ZKaneConfig = serde_json::from_slice(&data)?;
@@ -163,6 +446,29 @@ impl ZKaneContract {
         self.deposit_count_pointer().set_value::<u32>(count);
     }
 
+    /// Get the pointer to the number of notes spent
+    fn withdrawal_count_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/withdrawal_count")
+    }
+
+    /// Get the pointer to the flag set if the withdrawal count and the
+    /// liabilities cover every note
+    fn withdrawal_count_complete_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/withdrawal_count_complete")
+    }
+
+    /// Get the pointer to the value of the notes the pool holds
+    fn liabilities_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/liabilities")
+    }
+
+    /// Add the value of a deposited note to the pool's liabilities
+    fn add_liabilities(&self, amount: u128) {
+        let mut liabilities = self.liabilities_pointer();
+        let value = liabilities.get_value::<u128>();
+        liabilities.set_value::<u128>(value.saturating_add(amount));
+    }
+
     /// Get the pointer to commitments
     fn commitments_pointer(&self) -> StoragePointer {
         StoragePointer::from_keyword("/commitments")
@@ -215,17 +521,238 @@ impl ZKaneContract {
     }
 
     /// Check if a nullifier hash has been spent
-    fn is_nullifier_spent(&self, nullifier_hash: &[u8; 32]) -> bool {
+    fn has_spent_nullifier(&self, nullifier_hash: &[u8; 32]) -> bool {
         self.nullifiers_pointer()
             .select(&nullifier_hash.to_vec())
             .get_value::<u8>() == 1
     }
 
-    /// Mark a nullifier hash as spent
-    fn spend_nullifier(&self, nullifier_hash: &[u8; 32]) {
+    /// Mark a nullifier hash as spent, also inserting it into the nullifier
+    /// tree
+    ///
+    /// The public amount paid out is taken from the liabilities. The rest of
+    /// the note's value, if any, stays in the fresh notes of a split.
+    fn spend_nullifier(&self, config: &ZKaneConfig, nullifier_hash: &[u8; 32], public_amount: u128) -> Result<()> {
         self.nullifiers_pointer()
             .select(&nullifier_hash.to_vec())
             .set_value::<u8>(1);
+        self.nullifier_tree(config)
+            .insert(&NullifierHash::new(*nullifier_hash))
+            .map_err(ZKaneError::into_revert)?;
+        let mut withdrawal_count = self.withdrawal_count_pointer();
+        let count = withdrawal_count.get_value::<u32>();
+        withdrawal_count.set_value::<u32>(count + 1);
+        let mut liabilities = self.liabilities_pointer();
+        let value = liabilities.get_value::<u128>();
+        liabilities.set_value::<u128>(value.saturating_sub(public_amount));
+        Ok(())
+    }
+
+    /// Open the pool's nullifier tree on contract storage, hashed like the
+    /// commitment tree
+    fn nullifier_tree(&self, config: &ZKaneConfig) -> NullifierTree<TreeHash, StorageSparseNodeStore> {
+        NullifierTree::open(config.tree_hash, StorageSparseNodeStore)
+    }
+
+    /// Get the pointer to the flag set if the nullifier tree holds every
+    /// spent nullifier
+    fn nullifier_tree_complete_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/nullifier_tree_complete")
+    }
+
+    /// Get the pointer to commitment pre-registrations
+    fn registrations_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/registrations")
+    }
+
+    /// Get the height a registration hash was registered at, 0 if it isn't
+    fn get_registration_height(&self, registration_hash: &[u8; 32]) -> u64 {
+        self.registrations_pointer()
+            .select(&registration_hash.to_vec())
+            .get_value::<u64>()
+    }
+
+    /// Record a registration hash at a height
+    fn set_registration_height(&self, registration_hash: &[u8; 32], height: u64) {
+        self.registrations_pointer()
+            .select(&registration_hash.to_vec())
+            .set_value::<u64>(height);
+    }
+
+    /// Get the pointer to the factory that created the pool
+    fn factory_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/factory")
+    }
+
+    /// Remember the factory that created the pool
+    fn set_factory(&self, factory: &AlkaneId) {
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&factory.block.to_le_bytes());
+        data.extend_from_slice(&factory.tx.to_le_bytes());
+        self.factory_pointer().set(Arc::new(data));
+    }
+
+    /// Get the factory that created the pool, if any
+    fn get_factory(&self) -> Option<AlkaneId> {
+        let data = self.factory_pointer().get();
+        if data.len() != 32 {
+            return None;
+        }
+        let factory = AlkaneId {
+            block: u128::from_le_bytes(data[0..16].try_into().unwrap()),
+            tx: u128::from_le_bytes(data[16..32].try_into().unwrap()),
+        };
+        // Pools initialized directly by a transaction have no factory
+        if factory.block == 0 && factory.tx == 0 {
+            None
+        } else {
+            Some(factory)
+        }
+    }
+
+    /// Get the pointer to the flag of a deposit or withdrawal in progress
+    fn in_progress_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/in_progress")
+    }
+
+    /// Run a deposit or withdrawal, refusing calls made while another is
+    /// in progress
+    ///
+    /// Withdrawals call out to the fee collector, which could call back into
+    /// the pool before the outer call finishes. The flag is set for the
+    /// duration of the call and cleared at its end, so such nested calls
+    /// revert with [`ZKaneError::ReentrantCall`]. If the outer call fails,
+    /// the revert rolls the flag back with the rest of its storage writes.
+    fn non_reentrant<T>(&self, call: impl FnOnce() -> Result<T>) -> Result<T> {
+        let mut flag = self.in_progress_pointer();
+        if !flag.get().is_empty() {
+            return Err(ZKaneError::ReentrantCall.into_revert());
+        }
+        flag.set(Arc::new(vec![1]));
+        let result = call();
+        flag.set(Arc::new(Vec::new()));
+        result
+    }
+
+    /// Get the pointer to the insertion height of each leaf
+    fn leaf_heights_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/leaf_heights")
+    }
+
+    /// Get the height a leaf was inserted at, 0 if it isn't recorded
+    fn get_leaf_height_value(&self, index: u32) -> u64 {
+        self.leaf_heights_pointer()
+            .select(&index.to_le_bytes().to_vec())
+            .get_value::<u64>()
+    }
+
+    /// Get the pointer to the unclaimed reward points of each spent
+    /// nullifier hash
+    fn reward_claims_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/reward_claims")
+    }
+
+    /// Get the pointer to the heights reward claim hashes were registered at
+    fn reward_claim_registrations_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/reward_claim_registrations")
+    }
+
+    /// Get the pointer to the balance of a reward alkane
+    fn reward_balance_pointer(&self, token: &AlkaneId) -> StoragePointer {
+        let mut key = Vec::with_capacity(32);
+        key.extend_from_slice(&token.block.to_le_bytes());
+        key.extend_from_slice(&token.tx.to_le_bytes());
+        StoragePointer::from_keyword("/reward_balance").select(&key)
+    }
+
+    /// Ask the factory for the pool's reward program, if it has one
+    fn reward_program(&self, context: &Context) -> Result<Option<RewardProgram>> {
+        let Some(factory) = self.get_factory() else {
+            return Ok(None);
+        };
+        let cellpack = Cellpack {
+            target: factory,
+            inputs: vec![factory::GET_REWARD_PROGRAM, context.myself.block, context.myself.tx],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        RewardProgram::from_bytes(&response.data).map_err(ZKaneError::into_revert)
+    }
+
+    /// Record the reward points of a withdrawn note, if the pool rewards
+    /// deposits
+    ///
+    /// The points cover the blocks from the insertion of the note's leaf to
+    /// the withdrawal, and are claimed later with `ClaimRewards`. A factory
+    /// that can't be asked for the program pays no rewards rather than
+    /// blocking withdrawals.
+    fn record_reward(&self, context: &Context, witness_data: &WithdrawalWitnessData) {
+        let Ok(Some(program)) = self.reward_program(context) else {
+            return;
+        };
+        let points = program.points(self.get_leaf_height_value(witness_data.leaf_index), self.height());
+        if points > 0 {
+            self.reward_claims_pointer()
+                .select(&witness_data.nullifier_hash.to_vec())
+                .set_value::<u128>(points);
+        }
+    }
+
+    /// Ask the factory whether deposits are paused
+    fn deposits_paused(&self) -> Result<bool> {
+        let Some(factory) = self.get_factory() else {
+            return Ok(false);
+        };
+        let cellpack = Cellpack {
+            target: factory,
+            inputs: vec![factory::IS_PAUSED],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        let bytes: [u8; 16] = response.data.get(..16)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid pause response from factory"))?;
+        Ok(u128::from_le_bytes(bytes) != 0)
+    }
+
+    /// Ask the factory whether this pool was retired for a successor
+    fn is_retired(&self, context: &Context) -> Result<bool> {
+        let Some(factory) = self.get_factory() else {
+            return Ok(false);
+        };
+        let cellpack = Cellpack {
+            target: factory,
+            inputs: vec![factory::GET_SUCCESSOR, context.myself.block, context.myself.tx],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        Ok(!response.data.is_empty())
+    }
+
+    /// Fetch the verifier key of a circuit version from the factory
+    fn fetch_verifier_key(&self, factory: &AlkaneId, circuit_version: u32) -> Result<Vec<u8>> {
+        let cellpack = Cellpack {
+            target: factory.clone(),
+            inputs: vec![factory::GET_VERIFIER_KEY, circuit_version as u128],
+        };
+        let response = self.staticcall(
+            &cellpack,
+            &AlkaneTransferParcel::default(),
+            <Self as AlkaneResponder>::fuel(&self),
+        )?;
+        if response.data.is_empty() {
+            return Err(anyhow!("Factory has no verifier key for circuit version {}", circuit_version));
+        }
+        Ok(response.data)
     }
 
     /// Observe initialization to prevent multiple initializations
@@ -235,50 +762,255 @@ impl ZKaneContract {
             pointer.set_value::<u8>(1);
             Ok(())
         } else {
-            Err(anyhow!("Contract already initialized"))
+            Err(ZKaneError::AlreadyInitialized.into_revert())
         }
     }
 
-    /// Parse witness data for deposits (simplified for compilation)
+    /// Get the pointer to the storage schema version
+    fn schema_version_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword(SCHEMA_VERSION_KEY)
+    }
+
+    /// Get the storage schema version, 0 for pools deployed before versioning
+    fn get_schema_version(&self) -> Result<u32> {
+        decode_schema_version(&self.schema_version_pointer().get()).map_err(ZKaneError::into_revert)
+    }
+
+    /// Set the storage schema version
+    fn set_schema_version(&self, version: u32) {
+        self.schema_version_pointer().set(Arc::new(encode_schema_version(version)));
+    }
+
+    /// Bring storage written by an earlier version of the contract up to
+    /// [`POOL_SCHEMA_VERSION`]
+    ///
+    /// Run by every state-changing call, so the first one after an upgrade
+    /// migrates the pool. Uninitialized pools are left alone, as
+    /// initialization writes the current version.
+    fn migrate(&self) -> Result<()> {
+        if StoragePointer::from_keyword("/initialized").get().is_empty() {
+            return Ok(());
+        }
+        let stored = self.get_schema_version()?;
+        for version in pending_migrations(stored, POOL_SCHEMA_VERSION).map_err(ZKaneError::into_revert)? {
+            self.migrate_to(version)?;
+            self.set_schema_version(version);
+        }
+        Ok(())
+    }
+
+    /// Migrate storage from the previous schema version to `version`
+    fn migrate_to(&self, version: u32) -> Result<()> {
+        match version {
+            // Version 1 only adds the version marker to the unversioned layout
+            1 => Ok(()),
+            // Version 2 replaces the placeholder root with the tree's nodes,
+            // rebuilt from the stored leaves
+            2 => {
+                let config = self.load_config()?;
+                let mut tree = self.merkle_tree(&config, 0)?;
+                for index in 0..self.get_deposit_count_value() {
+                    let commitment = self
+                        .get_commitment_by_index(index)
+                        .ok_or_else(|| anyhow!("Missing commitment at index {}", index))?;
+                    tree.insert(&Commitment::new(commitment)).map_err(ZKaneError::into_revert)?;
+                }
+                self.set_root(&tree.root());
+                Ok(())
+            }
+            // Version 3 adds the nullifier tree. Spent nullifiers aren't
+            // listed anywhere, so it starts empty and isn't marked complete
+            3 => Ok(()),
+            // Version 4 records leaf heights from now on; earlier leaves
+            // have none and earn no rewards
+            4 => Ok(()),
+            // Version 5 counts withdrawals and the value of the notes held.
+            // Earlier ones can't be counted, so neither is marked complete
+            5 => Ok(()),
+            _ => Err(anyhow!("No migration to storage schema version {}", version)),
+        }
+    }
+
+    /// Parse witness data for deposits
+    ///
+    /// The commitment is found by the same [`DepositExtractor`] the indexers
+    /// use, so every deposit accepted here is seen by them.
     fn parse_deposit_witness(&self) -> Result<DepositWitnessData> {
-        // TODO: Implement transaction parsing once we figure out the correct API
-        // For now, return a dummy commitment
+        let tx = self.current_transaction()?;
+        let extracted = DepositExtractor::default()
+            .extract(&tx)
+            .ok_or_else(|| ZKaneError::CommitmentNotFound.into_revert())?;
         Ok(DepositWitnessData {
-            commitment: [0u8; 32]
+            commitment: *extracted.commitment.as_bytes(),
         })
     }
 
-    /// Parse witness data for withdrawals (simplified for compilation)
-    fn parse_withdrawal_witness(&self) -> Result<WithdrawalWitnessData> {
-        // TODO: Implement transaction parsing once we figure out the correct API
-        // For now, return dummy withdrawal data
-        Ok(WithdrawalWitnessData {
-            proof: vec![1, 2, 3], // Dummy proof
-            merkle_root: [0u8; 32],
-            nullifier_hash: [0u8; 32],
-            path_elements: vec![],
-            path_indices: vec![],
-            leaf_index: 0,
-            commitment: [0u8; 32],
-            outputs_hash: [0u8; 32],
-        })
+    /// Parse witness data for withdrawals
+    ///
+    /// The envelope of the given input holds a [`WithdrawalWitness`] in any
+    /// of its envelope formats.
+    fn parse_withdrawal_witness(&self, witness_input: usize) -> Result<WithdrawalWitnessData> {
+        let tx = self.current_transaction()?;
+        let payload = find_witness_payload(&tx, witness_input)
+            .ok_or_else(|| anyhow!("Missing withdrawal witness envelope"))?;
+        let witness = WithdrawalWitness::from_envelope(&payload).map_err(ZKaneError::into_revert)?;
+        Ok(witness.into())
+    }
+
+    /// Parse witness data for split withdrawals
+    ///
+    /// Returns the spend of the note, the public amount and the fresh
+    /// commitments.
+    fn parse_split_witness(&self) -> Result<(WithdrawalWitnessData, u128, Vec<[u8; 32]>)> {
+        let tx = self.current_transaction()?;
+        let payload = find_witness_payload(&tx, 0)
+            .ok_or_else(|| anyhow!("Missing split witness envelope"))?;
+        let witness = SplitWitness::from_envelope(&payload).map_err(ZKaneError::into_revert)?;
+        let outputs = witness.output_commitments.iter().map(|commitment| commitment.0).collect();
+        Ok((witness.withdrawal.into(), witness.public_amount, outputs))
+    }
+
+    /// Parse the amount witness of a variable-amount deposit
+    fn parse_amount_witness(&self) -> Result<AmountWitness> {
+        let tx = self.current_transaction()?;
+        let payload = find_witness_payload(&tx, 0)
+            .ok_or_else(|| anyhow!("Missing amount witness envelope"))?;
+        AmountWitness::from_envelope(&payload).map_err(ZKaneError::into_revert)
+    }
+
+    /// Validate that a variable-amount deposit's commitment opens to the
+    /// amount received
+    fn validate_deposit_amount(&self, config: &ZKaneConfig, commitment: &[u8; 32], received_amount: u128) -> Result<()> {
+        config.check_deposit_amount(received_amount).map_err(ZKaneError::into_revert)?;
+
+        let witness = self.parse_amount_witness()?;
+        if witness.commitment.0 != *commitment {
+            return Err(anyhow!("Amount witness is for another commitment"));
+        }
+        if witness.amount != received_amount {
+            return Err(ZKaneError::InvalidAmount(format!(
+                "witness commits to {}, got {}",
+                witness.amount, received_amount
+            ))
+            .into_revert());
+        }
+
+        // TODO: Verify the amount proof
+        // The proof should validate that the commitment opens to a note of
+        // the pool's asset and the witness amount
+        if witness.proof.is_empty() {
+            return Err(ZKaneError::InvalidProof("empty amount proof".to_string()).into_revert());
+        }
+
+        Ok(())
+    }
+
+    /// Decode the transaction executing this call
+    fn current_transaction(&self) -> Result<Transaction> {
+        consensus_decode::<Transaction>(&mut Cursor::new(self.transaction()))
     }
 
-    /// Hash the transaction outputs for recipient validation (simplified)
-    fn hash_transaction_outputs(&self, _tx: &Transaction) -> [u8; 32] {
-        // TODO: Implement once we have transaction access
-        [0u8; 32]
+    /// Hash the transaction outputs for recipient validation
+    ///
+    /// The protostone output is skipped, so the hash only covers the outputs
+    /// that receive funds.
+    fn hash_transaction_outputs(&self, tx: &Transaction) -> [u8; 32] {
+        calculate_outputs_hash(&tx.output)
     }
 
-    /// Validate that the transaction outputs match the expected hash (simplified)
-    fn validate_transaction_outputs(&self, _expected_outputs_hash: &[u8; 32]) -> Result<()> {
-        // TODO: Implement once we have transaction access
+    /// Validate that the transaction outputs match the expected hash
+    ///
+    /// A batched withdrawal only needs its own contiguous run of outputs to
+    /// match, the rest belonging to the other withdrawals of the batch.
+    fn validate_transaction_outputs(&self, expected_outputs_hash: &[u8; 32], batched: bool) -> Result<()> {
+        let tx = self.current_transaction()?;
+        let matches = if batched {
+            find_outputs_window(&tx.output, expected_outputs_hash).is_some()
+        } else {
+            self.hash_transaction_outputs(&tx) == *expected_outputs_hash
+        };
+        if !matches {
+            return Err(ZKaneError::OutputsMismatch.into_revert());
+        }
+        Ok(())
+    }
+
+    /// Validate that the transaction pays the recipient outputs of the proof
+    ///
+    /// The recipients must appear in order as one contiguous run of outputs,
+    /// the first of them paying the proof's recipient script if it names one.
+    fn validate_recipient_outputs(&self, recipients_hash: &[u8; 32], recipient: &Recipient) -> Result<()> {
+        if *recipients_hash == [0u8; 32] && recipient.is_empty() {
+            return Ok(());
+        }
+        let tx = self.current_transaction()?;
+        validate_recipient(&tx.output, recipients_hash, recipient).map_err(ZKaneError::into_revert)
+    }
+
+    /// Validate that the transaction contains the relayer fee output
+    fn validate_relayer_output(&self, relayer_output_hash: &[u8; 32]) -> Result<()> {
+        let tx = self.current_transaction()?;
+        let found = tx
+            .output
+            .iter()
+            .any(|output: &TxOut| calculate_outputs_hash(std::slice::from_ref(output)) == *relayer_output_hash);
+        if !found {
+            return Err(anyhow!("Relayer output not found in transaction"));
+        }
+        Ok(())
+    }
+
+    /// Validate the relayer fee declared in the withdrawal witness
+    ///
+    /// The fee is paid out of what is left of the public amount after the
+    /// protocol fee, and a non-zero fee must be backed by an output paying
+    /// the relayer. The public amount is the denomination for a full
+    /// withdrawal.
+    ///
+    /// Returns how the public amount is split between recipient, relayer and
+    /// protocol.
+    fn validate_relayer_fee(
+        &self,
+        witness_data: &WithdrawalWitnessData,
+        config: &ZKaneConfig,
+        public_amount: u128,
+    ) -> Result<WithdrawalAmounts> {
+        let amounts = config.split_amounts(public_amount, witness_data.fee)?;
+
+        if witness_data.fee > 0 {
+            if witness_data.relayer_output_hash == [0u8; 32] {
+                return Err(anyhow!("Relayer fee declared without a relayer output"));
+            }
+            self.validate_relayer_output(&witness_data.relayer_output_hash)?;
+        }
+
+        Ok(amounts)
+    }
+
+    /// Send the protocol fee of a withdrawal to the fee collector
+    fn pay_protocol_fee(&self, config: &ZKaneConfig, amount: u128) -> Result<()> {
+        let Some(protocol_fee) = config.protocol_fee else {
+            return Ok(());
+        };
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let cellpack = Cellpack {
+            target: protocol_fee.collector.into(),
+            inputs: vec![FEE_COLLECTOR_RECEIVE],
+        };
+        let fee_transfer = AlkaneTransferParcel(vec![AlkaneTransfer {
+            id: config.asset_id.into(),
+            value: amount,
+        }]);
+        self.call(&cellpack, &fee_transfer, <Self as AlkaneResponder>::fuel(&self))?;
         Ok(())
     }
 
     /// Generate a simple merkle path (placeholder implementation)
     fn generate_merkle_path(&self, leaf_index: u32) -> Result<Vec<u8>> {
-        let config = self.get_config()?;
+        let config = self.load_config()?;
         let deposit_count = self.get_deposit_count_value();
         
         if leaf_index >= deposit_count {
@@ -311,10 +1043,24 @@ impl ZKaneContract {
         asset_id_tx: u128,
         denomination: u128,
         tree_height: u128,
+        fee_bps: u128,
+        fee_collector_block: u128,
+        fee_collector_tx: u128,
+        circuit_version: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         let response = CallResponse::forward(&context.incoming_alkanes);
 
+        // A tree too small fills up, one too tall can't be built
+        let tree_height = validate_tree_height(tree_height).map_err(ZKaneError::into_revert)?;
+
+        // Nothing checks yet that a variable-amount deposit's commitment
+        // opens to the amount paid in, so its depositor could pay in a little
+        // for a note worth a lot
+        if denomination == 0 {
+            return Err(ZKaneError::WrongPoolMode(PoolMode::Variable).into_revert());
+        }
+
         // Prevent multiple initializations
         self.observe_initialization()?;
 
@@ -324,32 +1070,154 @@ impl ZKaneContract {
             tx: asset_id_tx,
         };
 
-        let config = ZKaneConfig::new(
+        let mut config = ZKaneConfig::new(
             asset_id.into(),
             denomination,
-            tree_height as u32,
-            vec![], // TODO: Add verifier key
+            tree_height,
+            vec![],
         );
 
+        if circuit_version > 0 {
+            let circuit_version =
+                u32::try_from(circuit_version).map_err(|_| anyhow!("Circuit version out of range"))?;
+            let verifier_key = self.fetch_verifier_key(&context.caller, circuit_version)?;
+            config = config.with_verifier_key(circuit_version, verifier_key);
+        }
+
+        if fee_bps > 0 {
+            let fee_bps = u16::try_from(fee_bps).map_err(|_| anyhow!("Protocol fee out of range"))?;
+            let collector = AlkaneId {
+                block: fee_collector_block,
+                tx: fee_collector_tx,
+            };
+            config = config.with_protocol_fee(ProtocolFee::new(fee_bps, collector.into())?);
+        }
+
         // Store configuration
         self.set_config(&config)?;
 
+        // The factory spawns and initializes its pools, so the caller is the
+        // factory whose pause switch applies to this pool
+        self.set_factory(&context.caller);
+
         // Initialize merkle root to zero
         self.set_root(&[0u8; 32]);
 
         // Initialize deposit count
         self.set_deposit_count(0);
 
+        // No nullifier has been spent, so the empty tree holds them all
+        self.nullifier_tree_complete_pointer().set_value::<u8>(1);
+        self.withdrawal_count_complete_pointer().set_value::<u8>(1);
+
+        self.set_schema_version(POOL_SCHEMA_VERSION);
+
         Ok(response)
     }
 
+    /// Open the pool's Merkle tree with `leaf_count` leaves on contract storage
+    ///
+    /// The tree is the same the off-chain pools build, so the root it
+    /// computes is the one withdrawal proofs are generated against.
+    fn merkle_tree(&self, config: &ZKaneConfig, leaf_count: u32) -> Result<MerkleTree<TreeHash, StorageNodeStore>> {
+        MerkleTree::open(config.tree_height, config.tree_hash, StorageNodeStore, leaf_count)
+            .map_err(ZKaneError::into_revert)
+    }
+
+    /// Insert a commitment as the next leaf of the tree
+    ///
+    /// Returns the leaf index of the commitment.
+    fn insert_leaf(&self, config: &ZKaneConfig, commitment: &[u8; 32]) -> Result<u32> {
+        let deposit_count = self.get_deposit_count_value();
+        let mut tree = self.merkle_tree(config, deposit_count)?;
+        tree.insert(&Commitment::new(*commitment)).map_err(ZKaneError::into_revert)?;
+
+        // Add commitment to storage
+        self.add_commitment(commitment);
+
+        // Store commitment by index for merkle path generation
+        self.store_commitment_by_index(deposit_count, commitment);
+
+        // The insertion height is where a note's reward points start
+        self.leaf_heights_pointer()
+            .select(&deposit_count.to_le_bytes().to_vec())
+            .set_value::<u64>(self.height());
+
+        // Update deposit count
+        self.set_deposit_count(deposit_count + 1);
+
+        self.set_root(&tree.root());
+
+        Ok(deposit_count)
+    }
+
     /// Process a deposit (reads commitment from witness envelope)
     fn deposit(&self) -> Result<CallResponse> {
+        self.non_reentrant(|| self.deposit_commitment(false))
+    }
+
+    /// Pre-register a commitment hash (for MessageDispatch macro)
+    ///
+    /// The registration reserves the commitment without revealing it, so the
+    /// deposit revealing it can't be front-run with a plain deposit. A hash
+    /// registered twice keeps its first height.
+    fn register_commitment(&self, registration_hash_low: u128, registration_hash_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.load_config()?;
+        let registration_hash = hash_from_halves(registration_hash_low, registration_hash_high);
+        if self.get_registration_height(&registration_hash) == 0 {
+            self.set_registration_height(&registration_hash, self.height());
+        }
+
+        Ok(response)
+    }
+
+    /// Deposit a pre-registered commitment (for MessageDispatch macro)
+    ///
+    /// A copy of the reveal confirming first deposits the same note with the
+    /// copier's funds, so the depositor's note is never lost.
+    fn deposit_registered(&self) -> Result<CallResponse> {
+        self.non_reentrant(|| self.deposit_commitment(true))
+    }
+
+    /// Get the registration height of a hash (for MessageDispatch macro)
+    fn get_registration(&self, registration_hash_low: u128, registration_hash_high: u128) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
 
+        let registration_hash = hash_from_halves(registration_hash_low, registration_hash_high);
+        response.data = (self.get_registration_height(&registration_hash) as u128).to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Deposit the commitment of the witness envelope
+    ///
+    /// A `registered` deposit needs the commitment registered in an earlier
+    /// block, and clears the registration. A plain deposit is refused for a
+    /// registered commitment. A variable-amount pool takes any amount, with
+    /// the envelope's proof that the commitment opens to it.
+    fn deposit_commitment(&self, registered: bool) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+
         // Get configuration
-        let config = self.get_config()?;
+        let config = self.load_config()?;
+
+        // Only deposits can be paused; withdrawals must always stay available
+        if self.deposits_paused()? {
+            return Err(ZKaneError::DepositsPaused.into_revert());
+        }
+
+        // Retired pools only process withdrawals; deposits go to the successor
+        if self.is_retired(&context)? {
+            return Err(ZKaneError::PoolRetired.into_revert());
+        }
 
         // Parse witness data to get commitment
         let witness_data = self.parse_deposit_witness()?;
@@ -357,7 +1225,19 @@ impl ZKaneContract {
 
         // Check if commitment already exists
         if self.has_commitment(&commitment) {
-            return Err(anyhow!("Commitment already exists"));
+            return Err(ZKaneError::DuplicateCommitment(hex::encode(commitment)).into_revert());
+        }
+
+        // A registration must be in an earlier block than its reveal, so the
+        // reveal can't be front-run by registering after seeing it
+        let registration_hash = Commitment::new(commitment).registration_hash();
+        let registered_at = self.get_registration_height(&registration_hash);
+        if registered {
+            if registered_at == 0 || registered_at >= self.height() {
+                return Err(ZKaneError::CommitmentNotRegistered(hex::encode(commitment)).into_revert());
+            }
+        } else if registered_at != 0 {
+            return Err(ZKaneError::CommitmentReserved.into_revert());
         }
 
         // Verify the correct amount of the correct asset was sent
@@ -368,39 +1248,30 @@ impl ZKaneContract {
             }
         }
 
-        if received_amount != config.denomination {
-            return Err(anyhow!(
-                "Invalid deposit amount: expected {}, got {}",
-                config.denomination,
-                received_amount
-            ));
+        if config.is_variable() {
+            self.validate_deposit_amount(&config, &commitment, received_amount)?;
+        } else if received_amount != config.denomination {
+            return Err(ZKaneError::InvalidAmount(format!(
+                "expected {}, got {}",
+                config.denomination, received_amount
+            ))
+            .into_revert());
         }
 
-        // Add commitment to storage
-        self.add_commitment(&commitment);
-
-        // Store commitment by index for merkle path generation
-        let deposit_count = self.get_deposit_count_value();
-        self.store_commitment_by_index(deposit_count, &commitment);
-
-        // Update deposit count
-        self.set_deposit_count(deposit_count + 1);
-
-        // TODO: Update merkle tree root properly
-        // For now, we'll use a simple hash of the commitment count
-        let mut new_root = [0u8; 32];
-        new_root[0..4].copy_from_slice(&(deposit_count + 1).to_le_bytes());
-        self.set_root(&new_root);
-
-        // Emit deposit event
-        let deposit_data = serde_json::json!({
-            "type": "deposit",
-            "commitment": hex::encode(commitment),
-            "leaf_index": deposit_count,
-            "timestamp": context.myself.block
-        });
+        let deposit_count = self.insert_leaf(&config, &commitment)?;
+        self.add_liabilities(received_amount);
+        if registered {
+            self.set_registration_height(&registration_hash, 0);
+        }
 
-        response.data = deposit_data.to_string().into_bytes();
+        // Emit deposit event, with the root so indexers can follow the
+        // root history without rebuilding the tree
+        response.data = ContractEvent::Deposit {
+            commitment: Commitment::new(commitment),
+            leaf_index: deposit_count,
+            root: self.get_merkle_root(),
+        }
+        .to_bytes();
 
         Ok(response)
     }
@@ -408,33 +1279,170 @@ impl ZKaneContract {
     /// Process a withdrawal (reads proof and path from witness envelope)
     /// The recipient is determined by the Bitcoin transaction vouts, not by contract parameters
     fn withdraw(&self) -> Result<CallResponse> {
+        self.non_reentrant(|| self.withdraw_from(0, false))
+    }
+
+    /// Process one withdrawal of a batch (for MessageDispatch macro)
+    ///
+    /// Each withdrawal of the batch has its own protostone and witness
+    /// envelope, and its proof covers its own outputs.
+    fn withdraw_batched(&self, witness_input: u128) -> Result<CallResponse> {
+        let witness_input = usize::try_from(witness_input).map_err(|_| anyhow!("Invalid witness input"))?;
+        self.non_reentrant(|| self.withdraw_from(witness_input, true))
+    }
+
+    /// Process a withdrawal whose witness envelope is in the given input
+    fn withdraw_from(&self, witness_input: usize, batched: bool) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
 
+        self.migrate()?;
+
         // Get configuration
-        let config = self.get_config()?;
+        let config = self.load_config()?;
+
+        // Notes of a variable pool have no fixed value; they are withdrawn
+        // with split withdrawals, which prove the value they pay out
+        if config.is_variable() {
+            return Err(ZKaneError::WrongPoolMode(config.mode).into_revert());
+        }
 
         // Parse witness data to get withdrawal information
-        let witness_data = self.parse_withdrawal_witness()?;
+        let witness_data = self.parse_withdrawal_witness(witness_input)?;
+
+        let amounts = self.validate_spend(&witness_data, &config, config.denomination, batched)?;
+
+        // Mark nullifier as spent
+        self.spend_nullifier(&config, &witness_data.nullifier_hash, config.denomination)?;
+        self.record_reward(&context, &witness_data);
+
+        // The protocol's share goes straight to the fee collector
+        self.pay_protocol_fee(&config, amounts.protocol)?;
+
+        // Return alkanes to be distributed according to transaction vouts
+        // The actual recipient is determined by the Bitcoin transaction structure;
+        // for relayed withdrawals the transaction edicts pay `fee` to the relayer
+        // output and the remainder to the recipient
+        response.alkanes.0.push(AlkaneTransfer {
+            id: config.asset_id.into(),
+            value: amounts.recipient + amounts.relayer,
+        });
+
+        // Emit withdrawal event
+        response.data = ContractEvent::Withdrawal(witness_data.spend_event(amounts.protocol)).to_bytes();
+
+        Ok(response)
+    }
+
+    /// Process a split withdrawal (reads the split witness from the envelope)
+    ///
+    /// The public amount is paid out like a withdrawal and the rest of the
+    /// note's value stays in the pool, in the fresh commitments of the
+    /// witness. The proof shows the note's value equals the public amount
+    /// plus the outputs' values. This is how notes of a variable-amount pool
+    /// are withdrawn, in whole or in part.
+    fn withdraw_split(&self) -> Result<CallResponse> {
+        self.non_reentrant(|| self.split_note())
+    }
+
+    /// Spend a note into a public amount and fresh commitments
+    fn split_note(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+
+        let config = self.load_config()?;
+        let (witness_data, public_amount, output_commitments) = self.parse_split_witness()?;
+
+        // Splits have a fixed number of outputs, so their count reveals nothing
+        if output_commitments.len() != SPLIT_OUTPUTS {
+            return Err(anyhow!(
+                "Split must have {} outputs, got {}",
+                SPLIT_OUTPUTS,
+                output_commitments.len()
+            ));
+        }
+        for (index, commitment) in output_commitments.iter().enumerate() {
+            if self.has_commitment(commitment) || output_commitments[..index].contains(commitment) {
+                return Err(ZKaneError::DuplicateCommitment(hex::encode(commitment)).into_revert());
+            }
+        }
+
+        let amounts = self.validate_spend(&witness_data, &config, public_amount, false)?;
+
+        self.spend_nullifier(&config, &witness_data.nullifier_hash, public_amount)?;
+        self.record_reward(&context, &witness_data);
+        // The fresh commitments get consecutive leaves
+        let first_leaf_index = self.get_deposit_count_value();
+        for commitment in &output_commitments {
+            self.insert_leaf(&config, commitment)?;
+        }
+
+        self.pay_protocol_fee(&config, amounts.protocol)?;
+
+        response.alkanes.0.push(AlkaneTransfer {
+            id: config.asset_id.into(),
+            value: amounts.recipient + amounts.relayer,
+        });
+
+        response.data = ContractEvent::Split {
+            spend: witness_data.spend_event(amounts.protocol),
+            public_amount,
+            first_leaf_index,
+            commitments: output_commitments.iter().copied().map(Commitment::new).collect(),
+        }
+        .to_bytes();
+
+        Ok(response)
+    }
+
+    /// Check the spend of a note in a withdrawal or split
+    ///
+    /// Returns how the public amount is split between recipient, relayer and
+    /// protocol. Nothing is written: the caller spends the nullifier.
+    fn validate_spend(
+        &self,
+        witness_data: &WithdrawalWitnessData,
+        config: &ZKaneConfig,
+        public_amount: u128,
+        batched: bool,
+    ) -> Result<WithdrawalAmounts> {
+        // Only proofs for the circuit of the pool's verifier key are accepted
+        if witness_data.circuit_version != config.circuit_version {
+            return Err(ZKaneError::UnsupportedCircuitVersion(witness_data.circuit_version).into_revert());
+        }
+
+        // Expiring proofs can't be spent after their height, so a proof held
+        // back by a relayer or stolen can't be replayed much later
+        let height = self.height();
+        if witness_data.not_after_height != 0 && height > witness_data.not_after_height {
+            return Err(ZKaneError::ProofExpired { not_after_height: witness_data.not_after_height, height }
+                .into_revert());
+        }
 
         // Validate that the transaction outputs match the proof
         // This prevents frontrunning by binding the proof to specific outputs
-        self.validate_transaction_outputs(&witness_data.outputs_hash)?;
+        self.validate_transaction_outputs(&witness_data.outputs_hash, batched)?;
+        self.validate_recipient_outputs(&witness_data.recipients_hash, &witness_data.recipient)?;
+
+        // Validate the relayer fee and make sure the relayer output is present
+        let amounts = self.validate_relayer_fee(witness_data, config, public_amount)?;
 
         // Check if nullifier has already been spent
-        if self.is_nullifier_spent(&witness_data.nullifier_hash) {
-            return Err(anyhow!("Nullifier already spent"));
+        if self.has_spent_nullifier(&witness_data.nullifier_hash) {
+            return Err(ZKaneError::NullifierAlreadySpent.into_revert());
         }
 
         // Check if commitment exists
         if !self.has_commitment(&witness_data.commitment) {
-            return Err(anyhow!("Unknown commitment"));
+            return Err(ZKaneError::UnknownCommitment.into_revert());
         }
 
         // Verify merkle root is valid (current root)
         let current_root = self.get_merkle_root();
         if witness_data.merkle_root != current_root {
-            return Err(anyhow!("Invalid merkle root"));
+            return Err(ZKaneError::InvalidMerkleRoot.into_revert());
         }
 
         // TODO: Verify the zero-knowledge proof
@@ -442,61 +1450,87 @@ impl ZKaneContract {
         // 1. Knowledge of secret and nullifier for the commitment
         // 2. Merkle tree inclusion
         // 3. Transaction outputs hash matches intended recipient
+        // 4. Recipients hash matches the recipient outputs
+        // 5. Relayer output hash, fee and expiry height match the public inputs
         // For now, we'll skip proof verification in this demo
         if witness_data.proof.is_empty() {
-            return Err(anyhow!("Empty proof provided"));
+            return Err(ZKaneError::InvalidProof("empty proof".to_string()).into_revert());
         }
 
         // Verify merkle path (as a backup check)
         let commitment_obj = Commitment::new(witness_data.commitment);
         let path = zkane_common::MerklePath::new(
-            witness_data.path_elements,
-            witness_data.path_indices,
+            witness_data.path_elements.clone(),
+            witness_data.path_indices.clone(),
         )?;
         
-        let path_valid = verify_merkle_path(
+        if path.len() != config.tree_height as usize {
+            return Err(ZKaneError::InvalidMerklePath.into_revert());
+        }
+        // A path contradicting its leaf index reverts naming the level
+        let computed_root = compute_root_from_path_with(
+            &config.tree_hash,
             &commitment_obj,
             witness_data.leaf_index,
             &path,
-            &witness_data.merkle_root,
-            config.tree_height,
-        ).map_err(|e| anyhow!("Merkle path verification failed: {}", e))?;
+        ).map_err(ZKaneError::into_revert)?;
 
-        if !path_valid {
-            return Err(anyhow!("Invalid merkle path"));
+        if computed_root != witness_data.merkle_root {
+            return Err(ZKaneError::InvalidMerklePath.into_revert());
         }
 
-        // Mark nullifier as spent
-        self.spend_nullifier(&witness_data.nullifier_hash);
+        Ok(amounts)
+    }
 
-        // Return alkanes to be distributed according to transaction vouts
-        // The actual recipient is determined by the Bitcoin transaction structure
-        response.alkanes.0.push(AlkaneTransfer {
-            id: config.asset_id.into(),
-            value: config.denomination,
-        });
 
-        // Emit withdrawal event
-        let withdrawal_data = serde_json::json!({
-            "type": "withdrawal",
-            "nullifier_hash": hex::encode(witness_data.nullifier_hash),
-            "outputs_hash": hex::encode(witness_data.outputs_hash),
-            "timestamp": context.myself.block
-        });
+    /// Get the denomination (for MessageDispatch macro)
+    fn get_denomination(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-        response.data = withdrawal_data.to_string().into_bytes();
+        let config = self.load_config()?;
+        response.data = config.denomination.to_le_bytes().to_vec();
 
         Ok(response)
     }
 
+    /// Get the protocol fee (for MessageDispatch macro)
+    fn get_protocol_fee(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-    /// Get the denomination (for MessageDispatch macro)
-    fn get_denomination(&self) -> Result<CallResponse> {
+        let config = self.load_config()?;
+        response.data = config
+            .protocol_fee
+            .map(|fee| fee.to_bytes())
+            .unwrap_or_else(|| vec![0u8; ProtocolFee::SIZE]);
+
+        Ok(response)
+    }
+
+    /// Get the circuit version (for MessageDispatch macro)
+    fn get_circuit_version(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-        let config = self.get_config()?;
-        response.data = config.denomination.to_le_bytes().to_vec();
+        let config = self.load_config()?;
+        response.data = (config.circuit_version as u128).to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the configuration and storage schema version (for MessageDispatch macro)
+    fn get_config(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let config = self.load_config()?;
+        let info = serde_json::json!({
+            "config": config,
+            "schema_version": self.get_schema_version()?,
+            "supported_schema_version": POOL_SCHEMA_VERSION
+        });
+        response.data = info.to_string().into_bytes();
 
         Ok(response)
     }
@@ -512,6 +1546,273 @@ impl ZKaneContract {
         Ok(response)
     }
 
+    /// Get the commitment at a leaf index (for MessageDispatch macro)
+    fn get_commitment(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let index = u32::try_from(index).map_err(|_| anyhow!("Leaf index out of range"))?;
+        let commitment = self
+            .get_commitment_by_index(index)
+            .ok_or_else(|| anyhow!("No commitment at index {}", index))?;
+        response.data = commitment.to_vec();
+
+        Ok(response)
+    }
+
+    /// Get a range of commitments (for MessageDispatch macro)
+    ///
+    /// The range is clamped to the deposits made so far and to
+    /// `MAX_COMMITMENT_RANGE` leaves, so light clients page through the tree
+    /// until fewer leaves than requested are returned.
+    fn get_commitment_range(&self, start: u128, count: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let deposit_count = self.get_deposit_count_value();
+        let start = u32::try_from(start).unwrap_or(u32::MAX).min(deposit_count);
+        let count = u32::try_from(count).unwrap_or(u32::MAX).min(MAX_COMMITMENT_RANGE);
+        let end = start.saturating_add(count).min(deposit_count);
+
+        let mut data = Vec::with_capacity((end - start) as usize * 32);
+        for index in start..end {
+            let commitment = self
+                .get_commitment_by_index(index)
+                .ok_or_else(|| anyhow!("Missing commitment at index {}", index))?;
+            data.extend_from_slice(&commitment);
+        }
+        response.data = data;
+
+        Ok(response)
+    }
+
+    /// Check whether a nullifier hash has been spent (for MessageDispatch macro)
+    fn is_nullifier_spent(&self, nullifier_hash_low: u128, nullifier_hash_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let nullifier_hash = NullifierHash::from_u128_pair(nullifier_hash_low, nullifier_hash_high);
+        let spent = self.has_spent_nullifier(nullifier_hash.as_bytes());
+        response.data = (spent as u128).to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Check a batch of nullifier hashes (for MessageDispatch macro)
+    ///
+    /// The hashes are read from the inputs following `count`, so a wallet
+    /// learns the status of all its notes in one call.
+    fn are_nullifiers_spent(&self, count: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        if count > MAX_NULLIFIER_BATCH as u128 {
+            return Err(anyhow!("At most {} nullifier hashes can be checked at once", MAX_NULLIFIER_BATCH));
+        }
+        // Inputs are the opcode, the count, then the hash halves
+        let halves = context.inputs.get(2..2 + 2 * count as usize).ok_or_else(|| {
+            anyhow!("Expected {} nullifier hashes, got {} inputs", count, context.inputs.len().saturating_sub(2))
+        })?;
+        response.data = halves
+            .chunks_exact(2)
+            .map(|pair| {
+                let nullifier_hash = NullifierHash::from_u128_pair(pair[0], pair[1]);
+                self.has_spent_nullifier(nullifier_hash.as_bytes()) as u8
+            })
+            .collect();
+
+        Ok(response)
+    }
+
+    /// Get the nullifier tree root and completeness flag (for MessageDispatch macro)
+    fn get_nullifier_root(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let config = self.load_config()?;
+        let mut data = self.nullifier_tree(&config).try_root().map_err(ZKaneError::into_revert)?.to_vec();
+        data.push(self.nullifier_tree_complete_pointer().get_value::<u8>());
+        response.data = data;
+
+        Ok(response)
+    }
+
+    /// Prove whether a nullifier hash has been spent (for MessageDispatch macro)
+    fn get_nullifier_proof(&self, nullifier_hash_low: u128, nullifier_hash_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let config = self.load_config()?;
+        let nullifier_hash = NullifierHash::from_u128_pair(nullifier_hash_low, nullifier_hash_high);
+        let proof = self.nullifier_tree(&config).prove(&nullifier_hash).map_err(ZKaneError::into_revert)?;
+        response.data = proof.to_bytes();
+
+        Ok(response)
+    }
+
+    /// Get the insertion height of a leaf (for MessageDispatch macro)
+    fn get_leaf_height(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let index = u32::try_from(index).map_err(|_| anyhow!("Leaf index out of range"))?;
+        response.data = (self.get_leaf_height_value(index) as u128).to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Fund reward claims (for MessageDispatch macro)
+    ///
+    /// The reward alkanes stay with the pool; anything else is forwarded
+    /// back.
+    fn fund_rewards(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::default();
+
+        self.migrate()?;
+        let program = self
+            .reward_program(&context)?
+            .ok_or_else(|| anyhow!("Pool has no reward program"))?;
+        let token: AlkaneId = program.token.into();
+
+        let mut funded = 0u128;
+        for transfer in &context.incoming_alkanes.0 {
+            if transfer.id == token {
+                funded = funded
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Reward balance overflow"))?;
+            } else {
+                response.alkanes.0.push(transfer.clone());
+            }
+        }
+        if funded == 0 {
+            return Err(anyhow!("No reward alkanes sent"));
+        }
+        let mut balance = self.reward_balance_pointer(&token);
+        let total = balance
+            .get_value::<u128>()
+            .checked_add(funded)
+            .ok_or_else(|| anyhow!("Reward balance overflow"))?;
+        balance.set_value::<u128>(total);
+
+        Ok(response)
+    }
+
+    /// Register a reward claim hash (for MessageDispatch macro)
+    ///
+    /// A hash registered twice keeps its first height.
+    fn register_reward_claim(&self, claim_hash_low: u128, claim_hash_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        self.load_config()?;
+        let claim_hash = hash_from_halves(claim_hash_low, claim_hash_high);
+        let mut registration = self.reward_claim_registrations_pointer().select(&claim_hash.to_vec());
+        if registration.get_value::<u64>() == 0 {
+            registration.set_value::<u64>(self.height());
+        }
+
+        Ok(response)
+    }
+
+    /// Claim a withdrawn note's reward points (for MessageDispatch macro)
+    ///
+    /// The nullifier proves the claim is the note owner's, and the claim
+    /// registered for this transaction's outputs in an earlier block keeps
+    /// a copy of the reveal from paying anyone else.
+    fn claim_rewards(&self, nullifier_low: u128, nullifier_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        self.migrate()?;
+        let program = self
+            .reward_program(&context)?
+            .ok_or_else(|| anyhow!("Pool has no reward program"))?;
+
+        let nullifier = Nullifier::new(hash_from_halves(nullifier_low, nullifier_high));
+        let nullifier_hash = generate_nullifier_hash(&nullifier)?;
+        let mut claim = self.reward_claims_pointer().select(&nullifier_hash.as_bytes().to_vec());
+        let points = claim.get_value::<u128>();
+        if points == 0 {
+            return Err(ZKaneError::NoRewardClaim.into_revert());
+        }
+
+        let tx = self.current_transaction()?;
+        let claim_hash = reward_claim_hash(&nullifier, &calculate_outputs_hash(&tx.output));
+        let mut registration = self.reward_claim_registrations_pointer().select(&claim_hash.to_vec());
+        let registered_at = registration.get_value::<u64>();
+        if registered_at == 0 || registered_at >= self.height() {
+            return Err(ZKaneError::RewardClaimNotRegistered(hex::encode(claim_hash)).into_revert());
+        }
+
+        let token: AlkaneId = program.token.into();
+        let mut balance = self.reward_balance_pointer(&token);
+        let available = balance.get_value::<u128>();
+        if available < points {
+            return Err(ZKaneError::RewardsUnderfunded { available, owed: points }.into_revert());
+        }
+        balance.set_value::<u128>(available - points);
+        claim.set_value::<u128>(0);
+        registration.set_value::<u64>(0);
+
+        response.alkanes.0.push(AlkaneTransfer { id: token, value: points });
+
+        Ok(response)
+    }
+
+    /// Get the unclaimed reward points of a nullifier hash (for MessageDispatch macro)
+    fn get_reward_claim(&self, nullifier_hash_low: u128, nullifier_hash_high: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let nullifier_hash = NullifierHash::from_u128_pair(nullifier_hash_low, nullifier_hash_high);
+        let points = self
+            .reward_claims_pointer()
+            .select(&nullifier_hash.as_bytes().to_vec())
+            .get_value::<u128>();
+        response.data = points.to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the reward alkanes held for claims (for MessageDispatch macro)
+    fn get_reward_balance(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let balance = match self.reward_program(&context)? {
+            Some(program) => self.reward_balance_pointer(&program.token.into()).get_value::<u128>(),
+            None => 0,
+        };
+        response.data = balance.to_le_bytes().to_vec();
+
+        Ok(response)
+    }
+
+    /// Get the pool's reserves (for MessageDispatch macro)
+    ///
+    /// Reward alkanes of the pool's own asset are held for claims, not
+    /// notes, so they aren't counted.
+    fn get_reserves(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let config = self.load_config()?;
+        let asset: AlkaneId = config.asset_id.into();
+        let rewards = self.reward_balance_pointer(&asset).get_value::<u128>();
+        let reserves = PoolReserves {
+            held: self.balance(&context.myself, &asset).saturating_sub(rewards),
+            deposit_count: self.get_deposit_count_value(),
+            withdrawal_count: self.withdrawal_count_pointer().get_value::<u32>(),
+            liabilities: self.liabilities_pointer().get_value::<u128>(),
+            counts_complete: self.withdrawal_count_complete_pointer().get_value::<u8>() == 1,
+        };
+        response.data = reserves.to_bytes();
+
+        Ok(response)
+    }
+
     /// Get the deposit count (for MessageDispatch macro)
     fn get_deposit_count(&self) -> Result<CallResponse> {
         let context = self.context()?;
@@ -524,6 +1825,14 @@ impl ZKaneContract {
     }
 }
 
+/// Rebuild a 32-byte hash passed as two little-endian `u128` halves
+fn hash_from_halves(low: u128, high: u128) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash[..16].copy_from_slice(&low.to_le_bytes());
+    hash[16..].copy_from_slice(&high.to_le_bytes());
+    hash
+}
+
 impl AlkaneResponder for ZKaneContract {}
 
 // Use the MessageDispatch macro for opcode handling
@@ -1,192 +1,224 @@
-use anyhow::Result;
+//! Builds the alkanes contracts to WASM and embeds them for the tests.
+//!
+//! Each crate under `alkanes/` is built for `wasm32-unknown-unknown` in its
+//! own target directory, with the flags fixed here rather than taken from the
+//! environment and the checkout path remapped, so the same tree gives the same
+//! WASM on any machine. The WASM is copied to `OUT_DIR` and
+//! `OUT_DIR/contract_builds.rs` gets a `<crate>_build` module per contract:
+//!
+//! - `WASM`, the contract embedded with `include_bytes!`
+//! - `VERSION`, the contract crate's version
+//! - `SHA256`, the hex SHA-256 of `WASM`
+//! - `BUILT`, false when a stub was embedded instead
+//! - `get_bytes()`, `WASM` as a `Vec<u8>`
+//!
+//! `src/tests/std` includes that file, so the tests always run against the
+//! contract code in the repo. Setting `ZKANE_SKIP_BUILD` embeds a stub WASM
+//! module instead, for native runs that never deploy the contracts.
+
+use anyhow::{anyhow, Context, Result};
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use hex;
+use sha2::{Digest, Sha256};
 use std::env;
+use std::fmt::Write as _;
 use std::fs;
 use std::io::prelude::*;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// The `[target.wasm32-unknown-unknown]` rustflags of `.cargo/config.toml`.
+/// Setting `CARGO_ENCODED_RUSTFLAGS` replaces the config ones, so they're
+/// repeated here along with the path remapping
+const WASM_RUSTFLAGS: &[&str] = &["-C", "target-feature=+atomics,+bulk-memory"];
+
+/// A WASM module exporting a single `sum` function, embedded when the build
+/// is skipped
+const STUB_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f,
+    0x01, 0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x07, 0x01, 0x03, 0x73, 0x75, 0x6d, 0x00, 0x00,
+    0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+];
+
+/// A contract crate under `alkanes/`
+struct Contract {
+    /// Package name, e.g. `zkane-pool`
+    name: String,
+    /// Package version
+    version: String,
+}
+
+impl Contract {
+    /// Name of the WASM artifact and of the generated module prefix
+    fn ident(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
 
-fn compress(binary: Vec<u8>) -> Result<Vec<u8>> {
+fn compress(binary: &[u8]) -> Result<Vec<u8>> {
     let mut writer = GzEncoder::new(Vec::<u8>::with_capacity(binary.len()), Compression::best());
-    writer.write_all(&binary)?;
+    writer.write_all(binary)?;
     Ok(writer.finish()?)
 }
 
-fn build_alkane(wasm_str: &str, features: Vec<&'static str>) -> Result<()> {
-    if features.len() != 0 {
-        let _ = Command::new("cargo")
-            .env("CARGO_TARGET_DIR", wasm_str)
-            .arg("build")
-            .arg("--release")
-            .arg("--features")
-            .arg(features.join(","))
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()?
-            .wait()?;
-        Ok(())
-    } else {
-        Command::new("cargo")
-            .env("CARGO_TARGET_DIR", wasm_str)
-            .arg("build")
-            .arg("--release")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()?
-            .wait()?;
-        Ok(())
+/// Reads the `name` and `version` of a contract's `[package]`
+fn read_contract(manifest: &Path) -> Result<Contract> {
+    let text = fs::read_to_string(manifest)
+        .with_context(|| format!("reading {}", manifest.display()))?;
+    let mut name = None;
+    let mut version = None;
+    let mut in_package = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim() {
+                "name" => name = Some(value),
+                "version" => version = Some(value),
+                _ => {}
+            }
+        }
     }
+    Ok(Contract {
+        name: name.ok_or_else(|| anyhow!("no package name in {}", manifest.display()))?,
+        version: version.ok_or_else(|| anyhow!("no package version in {}", manifest.display()))?,
+    })
 }
 
-fn main() {
-    // ZKANE CHADSON v69.0.0 - BOILER PATTERN TESTING FIX ✅
-    // 🎯 MISSION ACCOMPLISHED: NO MORE BYTECODE DUMPS OR ^^^ SYMBOLS!
-    //
-    // The fix works! Compilation logs are now clean without bytecode spam.
-    // Only remaining issue is disk space, not our target problem.
-    
-    // AGGRESSIVE skip conditions - boiler proven pattern
-    let skip_conditions = [
-        env::var("CARGO_CFG_TEST").is_ok(),
-        env::var("ZKANE_SKIP_BUILD").is_ok(),
-        env::var("RUST_TEST_THREADS").is_ok(),
-        env::var("ZKANE_TEST_MODE").is_ok(),
-        env::args().any(|arg| arg == "test"),
-        env::args().any(|arg| arg.contains("test")),
-        env::args().any(|arg| arg.starts_with("--test")),
-        cfg!(test), // Compile-time test detection
-        // Additional detection for common test scenarios
-        env::var("CARGO_PRIMARY_PACKAGE").map_or(false, |p| p == "zkane"),
-        std::env::current_dir().map_or(false, |d| d.to_string_lossy().contains("test")),
-    ];
-    
-    if skip_conditions.iter().any(|&condition| condition) {
-        // Create minimal stub files to prevent missing module errors
-        if let Ok(out_dir) = env::var("OUT_DIR") {
-            let base_dir = Path::new(&out_dir)
-                .parent().unwrap()
-                .parent().unwrap()
-                .parent().unwrap()
-                .parent().unwrap();
-            let write_dir = base_dir
-                .parent().unwrap()
-                .join("src")
-                .join("tests");
-            
-            if let Ok(_) = fs::create_dir_all(&write_dir.join("std")) {
-                // Create stub build modules for testing
-                let stub_code = "use hex_lit::hex;\n#[allow(long_running_const_eval)]\npub fn get_bytes() -> Vec<u8> { (&hex!(\"0061736d0100000001070160027f7f017f030201000707010373756d00000a09010700200020016a0b\")).to_vec() }";
-                
-                let _ = fs::write(
-                    &write_dir.join("std").join("zkane_factory_build.rs"),
-                    stub_code
-                );
-                let _ = fs::write(
-                    &write_dir.join("std").join("zkane_pool_build.rs"),
-                    stub_code
-                );
-                
-                // Create mod.rs with proper module declarations
-                let _ = fs::write(
-                    &write_dir.join("std").join("mod.rs"),
-                    "pub mod zkane_factory_build;\npub mod zkane_pool_build;\n"
-                );
-            }
+/// The contract crates, sorted so the generated file doesn't depend on
+/// directory order
+fn contracts(alkanes_dir: &Path) -> Result<Vec<Contract>> {
+    let mut contracts = Vec::new();
+    for entry in fs::read_dir(alkanes_dir)? {
+        let manifest = entry?.path().join("Cargo.toml");
+        if manifest.exists() {
+            contracts.push(read_contract(&manifest)?);
         }
-        
-        println!("cargo:warning=🎯 ZKANE CHADSON: Stub modules generated for testing!");
-        return;
     }
-    
-    let env_var = env::var_os("OUT_DIR").unwrap();
-    let base_dir = Path::new(&env_var)
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap();
-    let out_dir = base_dir.join("release");
-    let wasm_dir = base_dir.parent().unwrap().join("alkanes");
-    fs::create_dir_all(&wasm_dir).unwrap();
-    let wasm_str = wasm_dir.to_str().unwrap();
-    let write_dir = Path::new(&out_dir)
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .join("src")
-        .join("tests");
-
-    fs::create_dir_all(&write_dir.join("std")).unwrap();
-    let crates_dir = out_dir
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .join("alkanes");
-    
-    // Early exit if no alkanes directory
-    if !crates_dir.exists() {
-        fs::write(&write_dir.join("std").join("mod.rs"), "// No alkane modules found\n").unwrap();
-        return;
+    contracts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(contracts)
+}
+
+/// Builds a contract to WASM and returns the artifact path
+fn build_alkane(root: &Path, target_dir: &Path, contract: &Contract) -> Result<PathBuf> {
+    let mut rustflags: Vec<String> = WASM_RUSTFLAGS.iter().map(|flag| flag.to_string()).collect();
+    rustflags.push(format!("--remap-path-prefix={}=/zkane", root.display()));
+    if let Some(cargo_home) = env::var_os("CARGO_HOME") {
+        rustflags.push(format!("--remap-path-prefix={}=/cargo", Path::new(&cargo_home).display()));
+    }
+
+    let mut command = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    command
+        .current_dir(root)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .env("CARGO_ENCODED_RUSTFLAGS", rustflags.join("\x1f"))
+        .env_remove("RUSTFLAGS")
+        .env_remove("CARGO_BUILD_RUSTFLAGS")
+        .env_remove("RUSTC_WRAPPER")
+        .env_remove("RUSTC_WORKSPACE_WRAPPER")
+        .env("SOURCE_DATE_EPOCH", "0")
+        .args(["build", "--release", "--target", WASM_TARGET, "-p", &contract.name]);
+    if root.join("Cargo.lock").exists() {
+        command.arg("--locked");
+    }
+    let output = command
+        .output()
+        .with_context(|| format!("running cargo for {}", contract.name))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "building {} failed:\n{}",
+            contract.name,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(target_dir
+        .join(WASM_TARGET)
+        .join("release")
+        .join(contract.ident() + ".wasm"))
+}
+
+/// Appends a contract's `<crate>_build` module
+fn write_module(out: &mut String, contract: &Contract, wasm: &[u8], path: &Path, built: bool) {
+    let ident = contract.ident();
+    let hash = hex::encode(Sha256::digest(wasm));
+    let _ = write!(
+        out,
+        "/// The `{name}` contract\n\
+         pub mod {ident}_build {{\n\
+         \x20   /// Version of the `{name}` crate\n\
+         \x20   pub const VERSION: &str = {version:?};\n\
+         \x20   /// Hex SHA-256 of [`WASM`]\n\
+         \x20   pub const SHA256: &str = {hash:?};\n\
+         \x20   /// Whether [`WASM`] is the contract, false for the stub embedded with `ZKANE_SKIP_BUILD`\n\
+         \x20   pub const BUILT: bool = {built};\n\
+         \x20   /// The contract WASM\n\
+         \x20   pub const WASM: &[u8] = include_bytes!({path:?});\n\
+         \n\
+         \x20   pub fn get_bytes() -> Vec<u8> {{\n\
+         \x20       WASM.to_vec()\n\
+         \x20   }}\n\
+         }}\n\n",
+        name = contract.name,
+        version = contract.version,
+        path = path.display().to_string(),
+    );
+}
+
+fn run() -> Result<()> {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    let alkanes_dir = root.join("alkanes");
+
+    println!("cargo:rerun-if-env-changed=ZKANE_SKIP_BUILD");
+    println!("cargo:rerun-if-changed=alkanes");
+    for dir in ["crates/zkane-abi", "crates/zkane-common", "crates/zkane-crypto", "crates/zkane-core"] {
+        println!("cargo:rerun-if-changed={dir}/src");
+        println!("cargo:rerun-if-changed={dir}/Cargo.toml");
+    }
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=.cargo/config.toml");
+
+    let skip = env::var_os("ZKANE_SKIP_BUILD").is_some();
+    if skip {
+        println!("cargo:warning=ZKANE_SKIP_BUILD is set, embedding stub contract WASM");
     }
-    
-    std::env::set_current_dir(&crates_dir).unwrap();
-    let mods = fs::read_dir(&crates_dir)
-        .unwrap()
-        .filter_map(|v| {
-            let name = v.ok()?.file_name().into_string().ok()?;
-            Some(name)
-        })
-        .collect::<Vec<String>>();
-    let files = mods
-        .clone()
-        .into_iter()
-        .filter_map(|name| {
-            Some(name)
-        })
-        .collect::<Vec<String>>();
-    files.into_iter()
-        .map(|v| -> Result<String> {
-            std::env::set_current_dir(&crates_dir.clone().join(v.clone()))?;
-            build_alkane(wasm_str, vec![])?;
-            std::env::set_current_dir(&crates_dir)?;
-            let subbed = v.clone().replace("-", "_");
-            let f: Vec<u8> = fs::read(
-                &Path::new(&wasm_str)
-                    .join("wasm32-unknown-unknown")
-                    .join("release")
-                    .join(subbed.clone() + ".wasm"),
-            )?;
-            let compressed: Vec<u8> = compress(f.clone())?;
-            fs::write(&Path::new(&wasm_str).join("wasm32-unknown-unknown").join("release").join(subbed.clone() + ".wasm.gz"), &compressed)?;
-            let data: String = hex::encode(&f);
-            fs::write(
-                &write_dir.join("std").join(subbed.clone() + "_build.rs"),
-                String::from("use hex_lit::hex;\n#[allow(long_running_const_eval)]\npub fn get_bytes() -> Vec<u8> { (&hex!(\"")
-                    + data.as_str()
-                    + "\")).to_vec() }",
-            )?;
-            Ok(subbed)
-        })
-        .collect::<Result<Vec<String>>>()
-        .unwrap();
-    fs::write(
-        &write_dir.join("std").join("mod.rs"),
-        mods.into_iter()
-            .map(|v| v.replace("-", "_"))
-            .fold(String::default(), |r, v| {
-                r + "pub mod " + v.as_str() + "_build;\n"
-            }),
-    )
-    .unwrap();
-}
\ No newline at end of file
+
+    // Next to the main target directory so the contracts keep their own
+    // build cache and lock
+    let target_dir = match env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => PathBuf::from(dir).join("alkanes"),
+        None => root.join("target").join("alkanes"),
+    };
+
+    let mut generated = String::from("// Generated by build.rs, do not edit\n\n");
+    for contract in contracts(&alkanes_dir)? {
+        let path = out_dir.join(contract.ident() + ".wasm");
+        let wasm = if skip {
+            STUB_WASM.to_vec()
+        } else {
+            let artifact = build_alkane(&root, &target_dir, &contract)?;
+            let wasm = fs::read(&artifact)
+                .with_context(|| format!("reading {}", artifact.display()))?;
+            fs::write(artifact.with_extension("wasm.gz"), compress(&wasm)?)?;
+            wasm
+        };
+        fs::write(&path, &wasm)?;
+        write_module(&mut generated, &contract, &wasm, &path, !skip);
+    }
+    fs::write(out_dir.join("contract_builds.rs"), generated)?;
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        panic!("building the alkanes contracts: {err:#}");
+    }
+}
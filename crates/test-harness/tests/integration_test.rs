@@ -10,7 +10,7 @@ use std::sync::Arc;
 use wiremock::matchers::{body_json, method};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 use zkane_core::PrivacyPool;
-use zkane_common::{SerializableAlkaneId, ZKaneConfig};
+use zkane_common::{ZKaneConfig, ZkAssetId};
 
 #[tokio::test]
 async fn test_get_block_count_with_mock() -> Result<()> {
@@ -114,7 +114,7 @@ async fn test_privacy_pool_with_concrete_provider() -> Result<()> {
 
     // Arrange: Create the PrivacyPool
     let config = ZKaneConfig::new(
-        SerializableAlkaneId { block: 1, tx: 1 },
+        ZkAssetId { block: 1, tx: 1 },
         1000,
         20,
         vec![],
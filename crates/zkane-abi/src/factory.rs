@@ -0,0 +1,138 @@
+//! Opcodes of the factory contract, `ZKaneFactoryMessage`.
+
+use crate::{find, Method, Returns};
+
+/// Initialize the factory with its admin alkane
+pub const INITIALIZE: u128 = 0;
+/// Deposit into the pool of an asset and denomination, creating it if needed
+pub const GET_OR_CREATE_POOL: u128 = 1;
+/// Get the pool ID of an asset and denomination
+pub const GET_POOL_ID: u128 = 2;
+/// Check whether the pool of an asset and denomination exists
+pub const POOL_EXISTS: u128 = 3;
+/// Get the pools of an asset
+pub const GET_ASSET_POOLS: u128 = 4;
+/// Get the factory statistics
+pub const GET_STATS: u128 = 5;
+/// Get a page of pool records
+pub const GET_POOLS_PAGE: u128 = 6;
+/// Get the record of a pool
+pub const GET_POOL_METADATA: u128 = 7;
+/// Set the protocol fee of new pools (admin only)
+pub const SET_PROTOCOL_FEE: u128 = 8;
+/// Get the protocol fee of new pools
+pub const GET_PROTOCOL_FEE: u128 = 9;
+/// Pause deposits into every pool (admin only)
+pub const PAUSE: u128 = 10;
+/// Resume deposits (admin only)
+pub const UNPAUSE: u128 = 11;
+/// Hand the factory to another admin alkane (admin only)
+pub const TRANSFER_ADMIN: u128 = 12;
+/// Check whether deposits are paused
+pub const IS_PAUSED: u128 = 13;
+/// Register the verifier key of a circuit version (admin only)
+pub const SET_VERIFIER_KEY: u128 = 14;
+/// Get the verifier key of a circuit version
+pub const GET_VERIFIER_KEY: u128 = 15;
+/// Get the circuit version stamped into new pools
+pub const GET_CIRCUIT_VERSION: u128 = 16;
+/// Create the pool of an asset and denomination without depositing
+pub const CREATE_POOL: u128 = 17;
+/// Retire the active pool of an asset and denomination
+pub const RETIRE_POOL: u128 = 18;
+/// Get the pool taking deposits for an asset and denomination
+pub const GET_ACTIVE_POOL: u128 = 19;
+/// Get the successor of a retired pool
+pub const GET_SUCCESSOR: u128 = 20;
+/// Get pool and deposit totals across all pools, as JSON
+pub const GET_GLOBAL_STATS: u128 = 21;
+/// Register a pool template (admin only)
+pub const REGISTER_TEMPLATE: u128 = 22;
+/// Make a registered template the one new pools are created from (admin only)
+pub const SET_TEMPLATE: u128 = 23;
+/// Get a registered template
+pub const GET_TEMPLATE: u128 = 24;
+/// Get the version of the active template
+pub const GET_TEMPLATE_VERSION: u128 = 25;
+/// Set the display decimals and symbol of a denomination (admin only)
+pub const SET_DENOMINATION_SPEC: u128 = 26;
+/// Get the denomination specs of an asset
+pub const GET_DENOMINATION_SPECS: u128 = 27;
+/// Set or clear the reward program of a pool (admin only)
+pub const SET_REWARD_PROGRAM: u128 = 28;
+/// Get the reward program of a pool
+pub const GET_REWARD_PROGRAM: u128 = 29;
+
+/// Every method of the factory contract
+pub const METHODS: &[Method] = &[
+    Method::new("Initialize", INITIALIZE, &["admin_block", "admin_tx"], Returns::Nothing),
+    Method::new(
+        "GetOrCreatePool",
+        GET_OR_CREATE_POOL,
+        &["asset_id_block", "asset_id_tx", "denomination"],
+        Returns::Nothing,
+    ),
+    Method::new("GetPoolId", GET_POOL_ID, &["asset_id_block", "asset_id_tx", "denomination"], Returns::Bytes),
+    Method::new("PoolExists", POOL_EXISTS, &["asset_id_block", "asset_id_tx", "denomination"], Returns::U128),
+    Method::new("GetAssetPools", GET_ASSET_POOLS, &["asset_id_block", "asset_id_tx"], Returns::Bytes),
+    Method::new("GetStats", GET_STATS, &[], Returns::Bytes),
+    Method::new("GetPoolsPage", GET_POOLS_PAGE, &["offset", "limit"], Returns::Bytes),
+    Method::new("GetPoolMetadata", GET_POOL_METADATA, &["pool_id_block", "pool_id_tx"], Returns::Bytes),
+    Method::new(
+        "SetProtocolFee",
+        SET_PROTOCOL_FEE,
+        &["fee_bps", "collector_block", "collector_tx"],
+        Returns::Nothing,
+    ),
+    Method::new("GetProtocolFee", GET_PROTOCOL_FEE, &[], Returns::Bytes),
+    Method::new("Pause", PAUSE, &[], Returns::Nothing),
+    Method::new("Unpause", UNPAUSE, &[], Returns::Nothing),
+    Method::new("TransferAdmin", TRANSFER_ADMIN, &["admin_block", "admin_tx"], Returns::Nothing),
+    Method::new("IsPaused", IS_PAUSED, &[], Returns::U128),
+    Method::new("SetVerifierKey", SET_VERIFIER_KEY, &["circuit_version"], Returns::Nothing),
+    Method::new("GetVerifierKey", GET_VERIFIER_KEY, &["circuit_version"], Returns::Bytes),
+    Method::new("GetCircuitVersion", GET_CIRCUIT_VERSION, &[], Returns::U128),
+    Method::new("CreatePool", CREATE_POOL, &["asset_id_block", "asset_id_tx", "denomination"], Returns::Bytes),
+    Method::new("RetirePool", RETIRE_POOL, &["asset_id_block", "asset_id_tx", "denomination"], Returns::Bytes),
+    Method::new(
+        "GetActivePool",
+        GET_ACTIVE_POOL,
+        &["asset_id_block", "asset_id_tx", "denomination"],
+        Returns::Bytes,
+    ),
+    Method::new("GetSuccessor", GET_SUCCESSOR, &["pool_id_block", "pool_id_tx"], Returns::Bytes),
+    Method::new("GetGlobalStats", GET_GLOBAL_STATS, &[], Returns::Bytes),
+    Method::new(
+        "RegisterTemplate",
+        REGISTER_TEMPLATE,
+        &["template_block", "template_tx", "circuit_version"],
+        Returns::U128,
+    ),
+    Method::new("SetTemplate", SET_TEMPLATE, &["template_version"], Returns::Nothing),
+    Method::new("GetTemplate", GET_TEMPLATE, &["template_version"], Returns::Bytes),
+    Method::new("GetTemplateVersion", GET_TEMPLATE_VERSION, &[], Returns::U128),
+    Method::new(
+        "SetDenominationSpec",
+        SET_DENOMINATION_SPEC,
+        &["asset_id_block", "asset_id_tx", "base_units", "decimals", "symbol"],
+        Returns::Nothing,
+    ),
+    Method::new(
+        "GetDenominationSpecs",
+        GET_DENOMINATION_SPECS,
+        &["asset_id_block", "asset_id_tx"],
+        Returns::Bytes,
+    ),
+    Method::new(
+        "SetRewardProgram",
+        SET_REWARD_PROGRAM,
+        &["pool_block", "pool_tx", "token_block", "token_tx", "points_per_block", "max_blocks"],
+        Returns::Nothing,
+    ),
+    Method::new("GetRewardProgram", GET_REWARD_PROGRAM, &["pool_block", "pool_tx"], Returns::Bytes),
+];
+
+/// Find the factory method of an opcode.
+pub fn method(opcode: u128) -> Option<&'static Method> {
+    find(METHODS, opcode)
+}
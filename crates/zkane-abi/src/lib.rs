@@ -0,0 +1,239 @@
+//! # ZKane Contract ABI
+//!
+//! The canonical opcodes of the pool and factory contracts, the inputs each
+//! takes and how its response is encoded. Clients, the contracts calling
+//! each other and the tests all take opcode numbers from here, and each
+//! contract's tests check its [`pool::METHODS`] or [`factory::METHODS`]
+//! against the `MessageDispatch` enum it actually dispatches, so the numbers
+//! can't drift apart again.
+//!
+//! Inputs are cellpack `u128`s following the opcode. 32-byte hashes are
+//! passed as two little-endian halves, low half first ([`split_hash`]), and
+//! alkane IDs as their block then tx. Responses are raw bytes: a `u128` is
+//! 16 little-endian bytes ([`decode_u128`]), an optional alkane ID is empty
+//! or 32 bytes ([`decode_alkane_id`]), and structured responses are decoded
+//! by their types in `zkane-common`.
+//!
+//! ```rust
+//! use zkane_abi::{pool, split_hash};
+//!
+//! let [low, high] = split_hash(&[7u8; 32]);
+//! let inputs = pool::method(pool::IS_NULLIFIER_SPENT).unwrap().call(&[low, high])?;
+//! assert_eq!(inputs, vec![pool::IS_NULLIFIER_SPENT, low, high]);
+//!
+//! // Missing inputs are caught before the call is made
+//! assert!(pool::method(pool::IS_NULLIFIER_SPENT).unwrap().call(&[low]).is_err());
+//! # Ok::<(), zkane_abi::AbiError>(())
+//! ```
+
+pub mod factory;
+pub mod pool;
+
+use thiserror::Error;
+
+/// Opcode of the fee collector contract receiving a pool's protocol fees
+pub const FEE_COLLECTOR_RECEIVE: u128 = 50;
+
+/// Errors building calls or decoding responses
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AbiError {
+    /// A call was given the wrong number of inputs
+    #[error("{method} takes {expected} inputs, got {got}")]
+    WrongInputCount {
+        /// Name of the method called
+        method: &'static str,
+        /// Inputs the method takes
+        expected: usize,
+        /// Inputs given
+        got: usize,
+    },
+
+    /// A response has the wrong length for its encoding
+    #[error("expected {expected}, got {got} bytes")]
+    WrongResponseLength {
+        /// Description of the expected encoding
+        expected: &'static str,
+        /// Length of the response
+        got: usize,
+    },
+}
+
+/// How a method's response data is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Returns {
+    /// No data; the method only moves alkanes or changes state
+    Nothing,
+    /// A little-endian `u128`
+    U128,
+    /// Bytes in a layout specific to the method
+    Bytes,
+}
+
+/// A contract method: its opcode, the names of its inputs and its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Method {
+    /// Name of the method in the contract's message enum
+    pub name: &'static str,
+    /// Opcode dispatching to the method
+    pub opcode: u128,
+    /// Names of the inputs following the opcode, in order
+    pub inputs: &'static [&'static str],
+    /// Encoding of the response data
+    pub returns: Returns,
+}
+
+impl Method {
+    /// Describe a method.
+    pub const fn new(name: &'static str, opcode: u128, inputs: &'static [&'static str], returns: Returns) -> Self {
+        Self {
+            name,
+            opcode,
+            inputs,
+            returns,
+        }
+    }
+
+    /// Build the cellpack inputs calling the method: the opcode, then
+    /// `args`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AbiError::WrongInputCount`] unless there is one argument per
+    /// input.
+    pub fn call(&self, args: &[u128]) -> Result<Vec<u128>, AbiError> {
+        if args.len() != self.inputs.len() {
+            return Err(AbiError::WrongInputCount {
+                method: self.name,
+                expected: self.inputs.len(),
+                got: args.len(),
+            });
+        }
+        Ok([&[self.opcode], args].concat())
+    }
+}
+
+/// Find the method of an opcode in a contract's methods.
+fn find(methods: &'static [Method], opcode: u128) -> Option<&'static Method> {
+    methods.iter().find(|method| method.opcode == opcode)
+}
+
+/// Split a 32-byte hash into the little-endian halves passed as inputs,
+/// low half first.
+pub fn split_hash(hash: &[u8; 32]) -> [u128; 2] {
+    [
+        u128::from_le_bytes(hash[..16].try_into().unwrap()),
+        u128::from_le_bytes(hash[16..].try_into().unwrap()),
+    ]
+}
+
+/// Rebuild a 32-byte hash from the halves of [`split_hash`].
+pub fn join_hash(low: u128, high: u128) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash[..16].copy_from_slice(&low.to_le_bytes());
+    hash[16..].copy_from_slice(&high.to_le_bytes());
+    hash
+}
+
+/// Decode a [`Returns::U128`] response.
+///
+/// Only the first 16 bytes are read, so responses extended with more data
+/// by later contract versions still decode.
+///
+/// # Errors
+///
+/// Returns [`AbiError::WrongResponseLength`] if the response is shorter than
+/// 16 bytes.
+pub fn decode_u128(data: &[u8]) -> Result<u128, AbiError> {
+    data.get(..16)
+        .map(|bytes| u128::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(AbiError::WrongResponseLength {
+            expected: "a 16-byte u128",
+            got: data.len(),
+        })
+}
+
+/// Decode a 32-byte hash response, such as a Merkle root.
+///
+/// # Errors
+///
+/// Returns [`AbiError::WrongResponseLength`] unless the response is exactly
+/// 32 bytes.
+pub fn decode_hash(data: &[u8]) -> Result<[u8; 32], AbiError> {
+    data.try_into().map_err(|_| AbiError::WrongResponseLength {
+        expected: "a 32-byte hash",
+        got: data.len(),
+    })
+}
+
+/// Decode an optional alkane ID response, as `(block, tx)`.
+///
+/// # Returns
+///
+/// `None` for the empty response meaning there is no such alkane.
+///
+/// # Errors
+///
+/// Returns [`AbiError::WrongResponseLength`] unless the response is empty
+/// or 32 bytes.
+pub fn decode_alkane_id(data: &[u8]) -> Result<Option<(u128, u128)>, AbiError> {
+    match data.len() {
+        0 => Ok(None),
+        32 => Ok(Some((
+            u128::from_le_bytes(data[..16].try_into().unwrap()),
+            u128::from_le_bytes(data[16..].try_into().unwrap()),
+        ))),
+        len => Err(AbiError::WrongResponseLength {
+            expected: "an empty or 32-byte alkane ID",
+            got: len,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn check_methods(methods: &[Method]) {
+        let opcodes: HashSet<u128> = methods.iter().map(|method| method.opcode).collect();
+        assert_eq!(opcodes.len(), methods.len(), "duplicate opcode");
+        let names: HashSet<&str> = methods.iter().map(|method| method.name).collect();
+        assert_eq!(names.len(), methods.len(), "duplicate name");
+    }
+
+    #[test]
+    fn test_methods_are_unique() {
+        check_methods(pool::METHODS);
+        check_methods(factory::METHODS);
+        assert_eq!(pool::method(pool::DEPOSIT).unwrap().name, "Deposit");
+        assert_eq!(factory::method(factory::CREATE_POOL).unwrap().name, "CreatePool");
+        assert!(pool::method(7).is_none());
+    }
+
+    #[test]
+    fn test_call_inputs() {
+        let method = factory::method(factory::GET_ACTIVE_POOL).unwrap();
+        assert_eq!(method.call(&[2, 1, 1000]).unwrap(), vec![factory::GET_ACTIVE_POOL, 2, 1, 1000]);
+        assert_eq!(
+            method.call(&[2, 1]),
+            Err(AbiError::WrongInputCount { method: "GetActivePool", expected: 3, got: 2 })
+        );
+    }
+
+    #[test]
+    fn test_decodings() {
+        let hash = [9u8; 32];
+        let [low, high] = split_hash(&hash);
+        assert_eq!(join_hash(low, high), hash);
+        assert_eq!(decode_hash(&hash).unwrap(), hash);
+        assert!(decode_hash(&hash[1..]).is_err());
+
+        assert_eq!(decode_u128(&[&5u128.to_le_bytes()[..], &[1]].concat()).unwrap(), 5);
+        assert!(decode_u128(&[0u8; 15]).is_err());
+
+        let id = [6u128.to_le_bytes(), 7u128.to_le_bytes()].concat();
+        assert_eq!(decode_alkane_id(&id).unwrap(), Some((6, 7)));
+        assert_eq!(decode_alkane_id(&[]).unwrap(), None);
+        assert!(decode_alkane_id(&[0u8; 16]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Opcodes of the pool contract, `ZKaneContractMessage`.
+
+use crate::{find, Method, Returns};
+
+/// Initialize the pool with its asset, denomination, tree height, protocol
+/// fee and circuit version
+pub const INITIALIZE: u128 = 0;
+/// Deposit the incoming alkanes, the commitment read from the envelope
+pub const DEPOSIT: u128 = 1;
+/// Withdraw a note, the witness read from the envelope
+pub const WITHDRAW: u128 = 2;
+/// Withdraw part of a note, keeping the change in fresh notes
+pub const WITHDRAW_SPLIT: u128 = 3;
+/// Withdraw as one of several withdrawals batched in a transaction
+pub const WITHDRAW_BATCHED: u128 = 4;
+/// Pre-register the hash of a commitment
+pub const REGISTER_COMMITMENT: u128 = 5;
+/// Deposit a commitment registered in an earlier block
+pub const DEPOSIT_REGISTERED: u128 = 6;
+/// Get the current Merkle root
+pub const GET_ROOT: u128 = 10;
+/// Get the number of deposits
+pub const GET_DEPOSIT_COUNT: u128 = 11;
+/// Get the commitment at a leaf index
+pub const GET_COMMITMENT: u128 = 12;
+/// Get a range of commitments, packed as 32-byte leaves
+pub const GET_COMMITMENT_RANGE: u128 = 13;
+/// Get the denomination
+pub const GET_DENOMINATION: u128 = 14;
+/// Get the protocol fee
+pub const GET_PROTOCOL_FEE: u128 = 15;
+/// Get the version of the withdrawal circuit the pool accepts
+pub const GET_CIRCUIT_VERSION: u128 = 16;
+/// Check whether a nullifier hash has been spent
+pub const IS_NULLIFIER_SPENT: u128 = 17;
+/// Check a batch of nullifier hashes, which follow the count
+pub const ARE_NULLIFIERS_SPENT: u128 = 18;
+/// Get the height a registration hash was registered at
+pub const GET_REGISTRATION: u128 = 19;
+/// Get the pool configuration and schema version, as JSON
+pub const GET_CONFIG: u128 = 20;
+/// Get the nullifier tree root and completeness flag
+pub const GET_NULLIFIER_ROOT: u128 = 21;
+/// Get the nullifier tree proof of a nullifier hash
+pub const GET_NULLIFIER_PROOF: u128 = 22;
+/// Get the height a leaf was inserted at
+pub const GET_LEAF_HEIGHT: u128 = 23;
+/// Add the incoming reward alkanes to the reward balance
+pub const FUND_REWARDS: u128 = 24;
+/// Register the hash of a reward claim
+pub const REGISTER_REWARD_CLAIM: u128 = 25;
+/// Claim the reward points of a withdrawn note
+pub const CLAIM_REWARDS: u128 = 26;
+/// Get the unclaimed reward points of a nullifier hash
+pub const GET_REWARD_CLAIM: u128 = 27;
+/// Get the reward alkanes held for claims
+pub const GET_REWARD_BALANCE: u128 = 28;
+/// Get the reserves held for notes and the note counts
+pub const GET_RESERVES: u128 = 29;
+
+/// Every method of the pool contract
+pub const METHODS: &[Method] = &[
+    Method::new(
+        "Initialize",
+        INITIALIZE,
+        &[
+            "asset_id_block",
+            "asset_id_tx",
+            "denomination",
+            "tree_height",
+            "fee_bps",
+            "fee_collector_block",
+            "fee_collector_tx",
+            "circuit_version",
+        ],
+        Returns::Nothing,
+    ),
+    Method::new("Deposit", DEPOSIT, &[], Returns::Nothing),
+    Method::new("Withdraw", WITHDRAW, &[], Returns::Nothing),
+    Method::new("WithdrawSplit", WITHDRAW_SPLIT, &[], Returns::Nothing),
+    Method::new("WithdrawBatched", WITHDRAW_BATCHED, &["witness_input"], Returns::Nothing),
+    Method::new(
+        "RegisterCommitment",
+        REGISTER_COMMITMENT,
+        &["registration_hash_low", "registration_hash_high"],
+        Returns::Nothing,
+    ),
+    Method::new("DepositRegistered", DEPOSIT_REGISTERED, &[], Returns::Nothing),
+    Method::new("GetRoot", GET_ROOT, &[], Returns::Bytes),
+    Method::new("GetDepositCount", GET_DEPOSIT_COUNT, &[], Returns::U128),
+    Method::new("GetCommitment", GET_COMMITMENT, &["index"], Returns::Bytes),
+    Method::new("GetCommitmentRange", GET_COMMITMENT_RANGE, &["start", "count"], Returns::Bytes),
+    Method::new("GetDenomination", GET_DENOMINATION, &[], Returns::U128),
+    Method::new("GetProtocolFee", GET_PROTOCOL_FEE, &[], Returns::Bytes),
+    Method::new("GetCircuitVersion", GET_CIRCUIT_VERSION, &[], Returns::U128),
+    Method::new(
+        "IsNullifierSpent",
+        IS_NULLIFIER_SPENT,
+        &["nullifier_hash_low", "nullifier_hash_high"],
+        Returns::U128,
+    ),
+    Method::new("AreNullifiersSpent", ARE_NULLIFIERS_SPENT, &["count"], Returns::Bytes),
+    Method::new(
+        "GetRegistration",
+        GET_REGISTRATION,
+        &["registration_hash_low", "registration_hash_high"],
+        Returns::U128,
+    ),
+    Method::new("GetConfig", GET_CONFIG, &[], Returns::Bytes),
+    Method::new("GetNullifierRoot", GET_NULLIFIER_ROOT, &[], Returns::Bytes),
+    Method::new(
+        "GetNullifierProof",
+        GET_NULLIFIER_PROOF,
+        &["nullifier_hash_low", "nullifier_hash_high"],
+        Returns::Bytes,
+    ),
+    Method::new("GetLeafHeight", GET_LEAF_HEIGHT, &["index"], Returns::U128),
+    Method::new("FundRewards", FUND_REWARDS, &[], Returns::Nothing),
+    Method::new(
+        "RegisterRewardClaim",
+        REGISTER_REWARD_CLAIM,
+        &["claim_hash_low", "claim_hash_high"],
+        Returns::Nothing,
+    ),
+    Method::new("ClaimRewards", CLAIM_REWARDS, &["nullifier_low", "nullifier_high"], Returns::Nothing),
+    Method::new(
+        "GetRewardClaim",
+        GET_REWARD_CLAIM,
+        &["nullifier_hash_low", "nullifier_hash_high"],
+        Returns::U128,
+    ),
+    Method::new("GetRewardBalance", GET_REWARD_BALANCE, &[], Returns::U128),
+    Method::new("GetReserves", GET_RESERVES, &[], Returns::Bytes),
+];
+
+/// Find the pool method of an opcode.
+pub fn method(opcode: u128) -> Option<&'static Method> {
+    find(METHODS, opcode)
+}
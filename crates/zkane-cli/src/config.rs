@@ -0,0 +1,505 @@
+//! # Network Profiles
+//!
+//! Each network the CLI talks to has a profile: the provider endpoints, the
+//! factory and pool template alkanes, the denominations offered by default
+//! and the directory holding the network's note store. Profiles are read
+//! from `~/.zkane/config.toml`, one table per network, and fall back to
+//! built-in values for anything the file leaves out:
+//!
+//! ```toml
+//! default_network = "signet"
+//!
+//! [networks.signet]
+//! sandshrew_rpc_url = "https://signet.example.com/v2/key"
+//! factory = "4:762"
+//! pool_template = "4:763"
+//! denominations = [100000, 1000000]
+//!
+//! [networks.signet.failover]
+//! sandshrew_rpc_urls = ["https://signet-backup.example.com/v2/key"]
+//! paranoid = true
+//!
+//! [networks.signet.cache]
+//! capacity = 50000
+//! disk = true
+//! ```
+//!
+//! `--network` picks the profile, and provider options given on the command
+//! line override the profile's endpoints.
+//!
+//! The `failover` table lists backup Sandshrew endpoints, tried after the
+//! profile's own when it fails or lags behind, see
+//! [`zkane_core::FailoverProvider`].
+//!
+//! The `cache` table sizes the cache of fetched transactions and blocks, and
+//! with `disk` keeps it in the `cache` directory under the data directory
+//! across runs, see [`zkane_core::CachingProvider`].
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zkane_common::ZkAssetId;
+use zkane_core::cache::DEFAULT_CACHE_CAPACITY;
+use zkane_core::{CacheConfig, FailoverConfig};
+
+/// Name of the config file in the ZKane home directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Name of the note store in a profile's data directory
+pub const NOTES_FILE: &str = "notes.enc";
+
+/// Name of the disk cache in a profile's data directory
+pub const CACHE_DIR: &str = "cache";
+
+/// Factory template deployed by the regtest setup scripts
+const REGTEST_FACTORY: ZkAssetId = ZkAssetId { block: 4, tx: 0x2FA };
+
+/// Pool template deployed by the regtest setup scripts
+const REGTEST_POOL_TEMPLATE: ZkAssetId = ZkAssetId { block: 4, tx: 0x2FB };
+
+/// Local Sandshrew endpoint of the regtest setup scripts
+const REGTEST_SANDSHREW_RPC_URL: &str = "http://localhost:18888";
+
+/// Manage network profiles
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Show the profile in effect for the selected network
+    Show,
+    /// Write a config file holding the built-in profiles, to be edited
+    Init {
+        /// Replace an existing config file
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+/// A network with a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkName {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl NetworkName {
+    /// All networks, in the order profiles are listed.
+    pub const ALL: [NetworkName; 4] = [Self::Mainnet, Self::Testnet, Self::Signet, Self::Regtest];
+
+    /// Get the Bitcoin network.
+    pub fn network(self) -> bitcoin::Network {
+        match self {
+            NetworkName::Mainnet => bitcoin::Network::Bitcoin,
+            NetworkName::Testnet => bitcoin::Network::Testnet,
+            NetworkName::Signet => bitcoin::Network::Signet,
+            NetworkName::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+impl fmt::Display for NetworkName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NetworkName::Mainnet => "mainnet",
+            NetworkName::Testnet => "testnet",
+            NetworkName::Signet => "signet",
+            NetworkName::Regtest => "regtest",
+        })
+    }
+}
+
+/// Settings of one network. Unset fields fall back to the built-in profile.
+///
+/// Alkane IDs are written as `block:tx` strings, as TOML integers can't hold
+/// every `u128`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    /// Sandshrew JSON-RPC endpoint, serving alkanes and metashrew calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandshrew_rpc_url: Option<String>,
+    /// Esplora endpoint, if not served by Sandshrew
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub esplora_url: Option<String>,
+    /// Bitcoin Core JSON-RPC endpoint, if not served by Sandshrew
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitcoin_rpc_url: Option<String>,
+    /// Pool factory
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "alkane_id")]
+    pub factory: Option<ZkAssetId>,
+    /// Template the factory spawns pools from
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "alkane_id")]
+    pub pool_template: Option<ZkAssetId>,
+    /// Denominations offered when none is given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denominations: Vec<u64>,
+    /// Directory of the network's note store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// Backup endpoints and their health checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover: Option<FailoverProfile>,
+    /// Size and persistence of the transaction cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheProfile>,
+}
+
+/// Failover settings of a network. Unset limits fall back to those of
+/// [`FailoverConfig::default`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailoverProfile {
+    /// Backup Sandshrew endpoints, highest priority first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sandshrew_rpc_urls: Vec<String>,
+    /// Cross-check every deposit transaction between two endpoints
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paranoid: bool,
+    /// Blocks a healthy endpoint may lag behind the highest tip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tip_lag: Option<u64>,
+    /// Slowest health check answer of a healthy endpoint, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_latency_ms: Option<u64>,
+}
+
+impl FailoverProfile {
+    /// Get the settings of the failover provider.
+    pub fn config(&self) -> FailoverConfig {
+        let default = FailoverConfig::default();
+        FailoverConfig {
+            paranoid: self.paranoid,
+            max_tip_lag: self.max_tip_lag.unwrap_or(default.max_tip_lag),
+            max_latency: self.max_latency_ms.map_or(default.max_latency, Duration::from_millis),
+        }
+    }
+}
+
+/// Transaction cache settings of a network.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheProfile {
+    /// Transactions, and blocks, kept in memory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<usize>,
+    /// Keep the cache in the data directory across runs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disk: bool,
+}
+
+impl NetworkProfile {
+    /// Get the built-in profile of a network.
+    ///
+    /// Only regtest has endpoints and contracts, those of the local setup
+    /// scripts; the public networks need them set in the config file.
+    pub fn builtin(network: NetworkName) -> Self {
+        match network {
+            NetworkName::Regtest => Self {
+                sandshrew_rpc_url: Some(REGTEST_SANDSHREW_RPC_URL.to_string()),
+                factory: Some(REGTEST_FACTORY),
+                pool_template: Some(REGTEST_POOL_TEMPLATE),
+                denominations: vec![100_000, 1_000_000, 10_000_000],
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Fill the unset fields from another profile.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            sandshrew_rpc_url: self.sandshrew_rpc_url.or(fallback.sandshrew_rpc_url),
+            esplora_url: self.esplora_url.or(fallback.esplora_url),
+            bitcoin_rpc_url: self.bitcoin_rpc_url.or(fallback.bitcoin_rpc_url),
+            factory: self.factory.or(fallback.factory),
+            pool_template: self.pool_template.or(fallback.pool_template),
+            denominations: if self.denominations.is_empty() { fallback.denominations } else { self.denominations },
+            data_dir: self.data_dir.or(fallback.data_dir),
+            failover: self.failover.or(fallback.failover),
+            cache: self.cache.or(fallback.cache),
+        }
+    }
+
+    /// Get the settings of the caching provider. The disk cache needs a data
+    /// directory.
+    pub fn cache_config(&self) -> CacheConfig {
+        let cache = self.cache.clone().unwrap_or_default();
+        CacheConfig {
+            capacity: cache.capacity.unwrap_or(DEFAULT_CACHE_CAPACITY),
+            directory: self.data_dir.as_ref().filter(|_| cache.disk).map(|dir| dir.join(CACHE_DIR)),
+        }
+    }
+
+    /// Get the note store location, `notes.enc` in the data directory.
+    pub fn notes_path(&self) -> Result<PathBuf> {
+        let data_dir = self.data_dir.as_ref().ok_or_else(|| anyhow!("no data directory configured, pass --notes-file"))?;
+        Ok(data_dir.join(NOTES_FILE))
+    }
+
+    /// Get the factory, failing with a hint if the profile has none.
+    pub fn factory(&self, network: NetworkName) -> Result<ZkAssetId> {
+        self.factory.ok_or_else(|| {
+            anyhow!("no factory configured for {}; pass --factory or set it in {}", network, CONFIG_FILE)
+        })
+    }
+}
+
+/// The config file: the default network and the profile of each network.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CliConfig {
+    /// Network used when `--network` isn't given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_network: Option<NetworkName>,
+    /// Profiles by network
+    #[serde(default)]
+    pub networks: BTreeMap<NetworkName, NetworkProfile>,
+}
+
+impl CliConfig {
+    /// Read the config file, or an empty config if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("malformed config file {}", path.display()))
+    }
+
+    /// Get a config holding every built-in profile.
+    pub fn builtin() -> Self {
+        Self {
+            default_network: Some(NetworkName::Regtest),
+            networks: NetworkName::ALL
+                .into_iter()
+                .map(|network| (network, NetworkProfile::builtin(network)))
+                .collect(),
+        }
+    }
+
+    /// Write the config file, creating its directory.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Get the network to use: the one asked for, else the configured
+    /// default, else regtest.
+    pub fn network(&self, requested: Option<NetworkName>) -> NetworkName {
+        requested.or(self.default_network).unwrap_or(NetworkName::Regtest)
+    }
+
+    /// Get the profile of a network, completed with the built-in one.
+    ///
+    /// The data directory defaults to `<home>/<network>`, except on mainnet
+    /// where it is `home` itself, so stores from before profiles existed
+    /// are still found.
+    pub fn profile(&self, network: NetworkName, home: &Path) -> NetworkProfile {
+        let data_dir = match network {
+            NetworkName::Mainnet => home.to_path_buf(),
+            _ => home.join(network.to_string()),
+        };
+        let fallback = NetworkProfile {
+            data_dir: Some(data_dir),
+            ..NetworkProfile::builtin(network)
+        };
+        self.networks.get(&network).cloned().unwrap_or_default().or(fallback)
+    }
+}
+
+/// Get the ZKane home directory, `~/.zkane`.
+pub fn home_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set, pass --config and --notes-file"))?;
+    Ok(PathBuf::from(home).join(".zkane"))
+}
+
+/// Point the provider at a profile's endpoints.
+///
+/// Endpoints given on the command line are kept.
+pub fn apply_profile(args: &mut deezel_common::commands::Args, network: NetworkName, profile: &NetworkProfile) {
+    args.provider = network.to_string();
+    if args.sandshrew_rpc_url.is_none() {
+        args.sandshrew_rpc_url = profile.sandshrew_rpc_url.clone();
+    }
+    if args.esplora_url.is_none() {
+        args.esplora_url = profile.esplora_url.clone();
+    }
+    if args.bitcoin_rpc_url.is_none() {
+        args.bitcoin_rpc_url = profile.bitcoin_rpc_url.clone();
+    }
+}
+
+/// Get the Sandshrew endpoints to fail over between, highest priority
+/// first: the provider's own, then the profile's backups.
+pub fn failover_endpoints(args: &deezel_common::commands::Args, profile: &NetworkProfile) -> Vec<String> {
+    let backups = profile.failover.iter().flat_map(|failover| failover.sandshrew_rpc_urls.iter().cloned());
+    let mut endpoints: Vec<String> = args.sandshrew_rpc_url.iter().cloned().chain(backups).collect();
+    let mut seen = std::collections::HashSet::new();
+    endpoints.retain(|url| seen.insert(url.clone()));
+    endpoints
+}
+
+/// Run a `config` subcommand.
+pub fn run(command: ConfigCommand, path: &Path, network: NetworkName, profile: &NetworkProfile) -> Result<()> {
+    match command {
+        ConfigCommand::Show => {
+            let display = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            println!("Config file:    {}", path.display());
+            println!("Network:        {}", network);
+            println!("Sandshrew RPC:  {}", display(profile.sandshrew_rpc_url.clone()));
+            println!("Esplora:        {}", display(profile.esplora_url.clone()));
+            println!("Bitcoin RPC:    {}", display(profile.bitcoin_rpc_url.clone()));
+            println!("Factory:        {}", display(profile.factory.map(|id| id.to_string())));
+            println!("Pool template:  {}", display(profile.pool_template.map(|id| id.to_string())));
+            let denominations: Vec<String> = profile.denominations.iter().map(u64::to_string).collect();
+            println!("Denominations:  {}", display(Some(denominations.join(", ")).filter(|list| !list.is_empty())));
+            println!("Data directory: {}", display(profile.data_dir.as_ref().map(|dir| dir.display().to_string())));
+            let failover = profile.failover.clone().unwrap_or_default();
+            println!("Backup RPCs:    {}", display(Some(failover.sandshrew_rpc_urls.join(", ")).filter(|list| !list.is_empty())));
+            let config = failover.config();
+            println!(
+                "Failover:       max {} blocks behind, {} ms{}",
+                config.max_tip_lag,
+                config.max_latency.as_millis(),
+                if config.paranoid { ", paranoid" } else { "" }
+            );
+            let cache = profile.cache_config();
+            println!(
+                "Tx cache:       {} entries, {}",
+                cache.capacity,
+                cache.directory.map_or("in memory".to_string(), |dir| format!("on disk in {}", dir.display()))
+            );
+        }
+        ConfigCommand::Init { force } => {
+            if path.exists() && !force {
+                return Err(anyhow!("{} already exists; pass --force to replace it", path.display()));
+            }
+            CliConfig::builtin().save(path)?;
+            println!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Serde of optional alkane IDs as `block:tx` strings.
+mod alkane_id {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use zkane_common::ZkAssetId;
+
+    pub fn serialize<S: Serializer>(id: &Option<ZkAssetId>, serializer: S) -> Result<S::Ok, S::Error> {
+        match id {
+            Some(id) => serializer.serialize_str(&id.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ZkAssetId>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|id| id.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let config: CliConfig = toml::from_str(
+            r#"
+            default_network = "signet"
+
+            [networks.signet]
+            sandshrew_rpc_url = "https://signet.example.com"
+            factory = "4:900"
+            denominations = [5000]
+
+            [networks.regtest]
+            esplora_url = "http://localhost:3000"
+            "#,
+        )
+        .unwrap();
+        let home = Path::new("/home/user/.zkane");
+
+        assert_eq!(config.network(None), NetworkName::Signet);
+        assert_eq!(config.network(Some(NetworkName::Regtest)), NetworkName::Regtest);
+
+        let signet = config.profile(NetworkName::Signet, home);
+        assert_eq!(signet.factory, Some(ZkAssetId { block: 4, tx: 900 }));
+        assert_eq!(signet.denominations, vec![5000]);
+        assert_eq!(signet.data_dir, Some(home.join("signet")));
+
+        // Regtest keeps its built-in contracts under the configured endpoint
+        let regtest = config.profile(NetworkName::Regtest, home);
+        assert_eq!(regtest.esplora_url.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(regtest.sandshrew_rpc_url.as_deref(), Some(REGTEST_SANDSHREW_RPC_URL));
+        assert_eq!(regtest.factory, Some(REGTEST_FACTORY));
+
+        let mainnet = config.profile(NetworkName::Mainnet, home);
+        assert_eq!(mainnet.data_dir, Some(home.to_path_buf()));
+        assert!(mainnet.factory(NetworkName::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_config_roundtrip() {
+        let config = CliConfig::builtin();
+        let encoded = toml::to_string_pretty(&config).unwrap();
+        assert!(encoded.contains(r#"factory = "4:762""#));
+        assert_eq!(toml::from_str::<CliConfig>(&encoded).unwrap(), config);
+
+        assert!(toml::from_str::<CliConfig>("[networks.regtest]\nfactory = \"nope\"").is_err());
+        assert!(toml::from_str::<CliConfig>("[networks.moonnet]").is_err());
+    }
+
+    #[test]
+    fn test_failover_profile() {
+        let config: CliConfig = toml::from_str(
+            r#"
+            [networks.signet]
+            sandshrew_rpc_url = "https://a.example.com"
+
+            [networks.signet.failover]
+            sandshrew_rpc_urls = ["https://b.example.com", "https://a.example.com"]
+            paranoid = true
+            max_latency_ms = 1500
+            "#,
+        )
+        .unwrap();
+        let profile = config.profile(NetworkName::Signet, Path::new("/home/user/.zkane"));
+        let failover = profile.failover.clone().unwrap();
+        assert_eq!(
+            failover.config(),
+            FailoverConfig { paranoid: true, max_tip_lag: 2, max_latency: Duration::from_millis(1500) }
+        );
+        assert_eq!(toml::from_str::<CliConfig>(&toml::to_string_pretty(&config).unwrap()).unwrap(), config);
+
+        assert_eq!(failover.sandshrew_rpc_urls.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_profile() {
+        let home = Path::new("/home/user/.zkane");
+        let config: CliConfig = toml::from_str(
+            r#"
+            [networks.signet.cache]
+            capacity = 500
+            disk = true
+
+            [networks.testnet.cache]
+            capacity = 20
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.profile(NetworkName::Signet, home).cache_config(),
+            CacheConfig { capacity: 500, directory: Some(home.join("signet").join(CACHE_DIR)) }
+        );
+        assert_eq!(config.profile(NetworkName::Testnet, home).cache_config().directory, None);
+        assert_eq!(config.profile(NetworkName::Regtest, home).cache_config(), CacheConfig::default());
+        assert_eq!(toml::from_str::<CliConfig>(&toml::to_string_pretty(&config).unwrap()).unwrap(), config);
+    }
+}
@@ -3,13 +3,28 @@
 //! The main entry point for the ZKane privacy pool CLI.
 
 use anyhow::Result;
-use clap::Parser;
+use bitcoin::psbt::Psbt;
+use bitcoin::{Amount, FeeRate, OutPoint, ScriptBuf, Transaction, TxOut, Txid};
+use clap::{Parser, ValueEnum};
 use deezel_common::traits::DeezelProvider;
 use deezel_common::System;
 use deezel_sys::SystemDeezel;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
-use zkane_common::ZKaneConfig;
-use zkane_core::PrivacyPool;
+use zkane_common::{
+    Commitment, DepositNote, NullifierHash, WithdrawalWitness, ZKaneError, ZkAssetId, COMMITMENT_HRP, NULLIFIER_HASH_HRP,
+    POOL_ID_HRP,
+};
+use zkane_core::signer::sign_and_broadcast;
+use zkane_core::{build_deposit_request, estimate_withdrawal_fee, FundingUtxo, PoolClient, ProviderSigner};
+
+mod config;
+mod notes;
+mod pool;
+mod prove;
+mod relay;
+mod rewards;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -17,42 +32,350 @@ pub struct Args {
     #[clap(flatten)]
     pub deezel_args: deezel_common::commands::Args,
 
+    /// Network profile to use (defaults to the config file's, else regtest)
+    #[clap(long, value_enum, global = true)]
+    pub network: Option<config::NetworkName>,
+
+    /// Config file holding the network profiles (defaults to ~/.zkane/config.toml)
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Parser)]
 pub enum Commands {
-    /// Deposit funds into the privacy pool
-    Deposit,
+    /// Plan the deposit of a note into its pool
+    ///
+    /// Prints the runestone, the witness envelope and the change of the
+    /// deposit transaction for a wallet to fund and sign.
+    Deposit {
+        /// Note file, in the note JSON schema
+        #[clap(long)]
+        note: PathBuf,
+        /// UTXO to spend, as txid:vout:sats:script_hex; the envelope commit
+        /// output first
+        #[clap(long = "utxo", required = true, value_parser = parse_utxo)]
+        utxos: Vec<FundingUtxo>,
+        /// Fee rate, in sat/vB
+        #[clap(long, default_value_t = 1)]
+        fee_rate: u64,
+        /// Print the plan as JSON
+        #[clap(long)]
+        json: bool,
+    },
     /// Withdraw funds from the privacy pool
-    Withdraw,
+    Withdraw {
+        /// Run every check of the pool contract on the withdrawal and report
+        /// which would fail, without broadcasting
+        #[clap(long, requires_all = ["pool_id", "witness"])]
+        dry_run: bool,
+        /// Pool alkane ID (block:tx or zkp1...)
+        #[clap(long)]
+        pool_id: Option<ZkAssetId>,
+        /// Hex-encoded withdrawal witness envelope
+        #[clap(long)]
+        witness: Option<String>,
+        /// Hex-encoded withdrawal transaction, to check the outputs it pays
+        #[clap(long)]
+        tx: Option<String>,
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Estimate the network fee of a withdrawal
+    ///
+    /// Models the signed transaction, the witness envelope and protostone
+    /// included, funded by the envelope input alone.
+    EstimateFee {
+        /// Size of the proof, in bytes
+        #[clap(long)]
+        proof_size: usize,
+        /// Height of the note's Merkle path
+        #[clap(long)]
+        path_len: usize,
+        /// Outputs paid, counting the recipients, any relayer fee output and change
+        #[clap(long, default_value_t = 1)]
+        outputs: usize,
+        /// Fee rate, in sat/vB
+        #[clap(long, default_value_t = 1)]
+        fee_rate: u64,
+        /// Print the estimate as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Decode a withdrawal witness envelope
+    DecodeWitness {
+        /// Hex-encoded witness
+        witness: String,
+    },
+    /// Sign, finalize and broadcast a PSBT with the wallet
+    Broadcast {
+        /// Hex-encoded PSBT
+        psbt: String,
+    },
+    /// Manage locally stored deposit notes
+    Notes {
+        /// Encrypted note store (defaults to notes.enc in the network's data directory)
+        #[clap(long)]
+        notes_file: Option<PathBuf>,
+        #[clap(subcommand)]
+        command: notes::NotesCommand,
+    },
+    /// Generate the withdrawal proof of a stored note
+    Prove(prove::ProveArgs),
+    /// Convert a commitment, nullifier hash or pool ID to or from bech32m
+    ///
+    /// Bech32m strings (zkc1..., zkn1..., zkp1...) are decoded to hex or
+    /// block:tx; anything else is encoded as the given kind.
+    Bech32 {
+        /// Kind of value to encode
+        #[clap(long, value_enum)]
+        kind: Option<Bech32Kind>,
+        /// Hex value, block:tx pool ID or bech32m string
+        value: String,
+    },
+    /// Inspect deployed pools
+    Pool {
+        /// Print JSON instead of a table
+        #[clap(long, global = true)]
+        json: bool,
+        #[clap(subcommand)]
+        command: pool::PoolCommand,
+    },
+    /// Submit withdrawals through relayers
+    Relay {
+        #[clap(subcommand)]
+        command: relay::RelayCommand,
+    },
+    /// Claim the reward points of withdrawn notes from pools that reward deposits
+    Rewards {
+        #[clap(subcommand)]
+        command: rewards::RewardsCommand,
+    },
+    /// Show or initialize the network profiles
+    Config {
+        #[clap(subcommand)]
+        command: config::ConfigCommand,
+    },
+}
+
+/// A value with a bech32m encoding
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Bech32Kind {
+    Commitment,
+    NullifierHash,
+    Pool,
+}
+
+/// Decode a bech32m string, or encode a value of `kind` as bech32m.
+fn convert_bech32(kind: Option<Bech32Kind>, value: &str) -> Result<String> {
+    let value = value.trim();
+    let hrp = value.split_once('1').map(|(hrp, _)| hrp.to_ascii_lowercase());
+    match hrp.as_deref() {
+        Some(COMMITMENT_HRP) => return Ok(Commitment::from_bech32(value)?.to_hex()),
+        Some(NULLIFIER_HASH_HRP) => return Ok(NullifierHash::from_bech32(value)?.to_hex()),
+        Some(POOL_ID_HRP) => return Ok(ZkAssetId::from_bech32(value)?.to_string()),
+        _ => {}
+    }
+    let hex = value.trim_start_matches("0x");
+    match kind {
+        Some(Bech32Kind::Commitment) => Ok(Commitment::from_hex(hex)?.to_bech32()),
+        Some(Bech32Kind::NullifierHash) => Ok(NullifierHash::from_hex(hex)?.to_bech32()),
+        Some(Bech32Kind::Pool) => Ok(value.parse::<ZkAssetId>()?.to_bech32()),
+        None => anyhow::bail!("'{}' is not a bech32m string; pass --kind to encode it", value),
+    }
+}
+
+/// Parse a `txid:vout:sats:script_hex` UTXO.
+fn parse_utxo(value: &str) -> Result<FundingUtxo> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    let [txid, vout, sats, script] = parts[..] else {
+        anyhow::bail!("'{}' is not txid:vout:sats:script_hex", value);
+    };
+    Ok(FundingUtxo {
+        outpoint: OutPoint::new(txid.parse::<Txid>()?, vout.parse()?),
+        txout: TxOut {
+            value: Amount::from_sat(sats.parse()?),
+            script_pubkey: ScriptBuf::from_bytes(hex::decode(script)?),
+        },
+    })
 }
 
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            // Library errors are reported with their stable code
+            match error.downcast_ref::<ZKaneError>() {
+                Some(zkane_error) => eprintln!("Error: ZK{}: {:#}", zkane_error.code(), error),
+                None => eprintln!("Error: {:#}", error),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
 
+async fn run(mut args: Args) -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&args.deezel_args.log_level))
         .init();
+    // Refuse to run, rather than generate notes or keys from a broken source
+    zkane_common::check_entropy()?;
+
+    let home = config::home_dir()?;
+    let config_path = args.config.clone().unwrap_or_else(|| home.join(config::CONFIG_FILE));
+    let cli_config = config::CliConfig::load(&config_path)?;
+    let network = cli_config.network(args.network);
+    let profile = cli_config.profile(network, &home);
+    if let Commands::Config { command } = args.command {
+        return config::run(command, &config_path, network, &profile);
+    }
+    if let Commands::EstimateFee { proof_size, path_len, outputs, fee_rate, json } = args.command {
+        let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or_else(|| anyhow::anyhow!("fee rate too high"))?;
+        let estimate = estimate_withdrawal_fee(proof_size, path_len, outputs, fee_rate)?;
+        if json {
+            let estimate = serde_json::json!({
+                "envelope_len": estimate.envelope_len,
+                "weight": estimate.weight.to_wu(),
+                "vsize": estimate.vsize,
+                "fee": estimate.fee.to_sat(),
+            });
+            println!("{}", serde_json::to_string_pretty(&estimate)?);
+        } else {
+            println!("Envelope: {} bytes", estimate.envelope_len);
+            println!("Weight:   {} WU", estimate.weight.to_wu());
+            println!("Fee:      {} sats ({} vB)", estimate.fee.to_sat(), estimate.vsize);
+        }
+        return Ok(());
+    }
+    config::apply_profile(&mut args.deezel_args, network, &profile);
+    if let Commands::Pool { json, command: pool::PoolCommand::Health } = args.command {
+        return pool::health(json, &args.deezel_args, &profile).await;
+    }
 
     let deezel = SystemDeezel::new(&args.deezel_args).await?;
-    let config = ZKaneConfig::new(
-        zkane_common::SerializableAlkaneId { block: 0, tx: 0 }, // Placeholder
-        1000000,
-        20,
-        vec![],
-    );
-    let _zkane_pool = PrivacyPool::new(config, Arc::new(deezel.provider().clone_box()));
 
     match args.command {
-        Commands::Deposit => {
-            println!("Depositing funds...");
+        Commands::Deposit { note, utxos, fee_rate, json } => {
+            let note = DepositNote::from_json(&std::fs::read_to_string(&note)?)?;
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or_else(|| anyhow::anyhow!("fee rate too high"))?;
+            let plan = build_deposit_request(&note, &utxos, fee_rate)?;
+            if json {
+                let plan = serde_json::json!({
+                    "pool_id": plan.pool_id.to_string(),
+                    "runestone": hex::encode(plan.runestone.as_bytes()),
+                    "envelope": hex::encode(&plan.envelope),
+                    "change": plan.change.to_sat(),
+                    "vsize": plan.vsize,
+                    "fee": plan.fee.to_sat(),
+                });
+                println!("{}", serde_json::to_string_pretty(&plan)?);
+            } else {
+                println!("Pool:      {}", plan.pool_id);
+                println!("Runestone: {}", hex::encode(plan.runestone.as_bytes()));
+                println!("Envelope:  {}", hex::encode(&plan.envelope));
+                println!("Change:    {} sats", plan.change.to_sat());
+                println!("Fee:       {} sats ({} vB)", plan.fee.to_sat(), plan.vsize);
+            }
         }
-        Commands::Withdraw => {
+        Commands::Withdraw { dry_run: false, .. } => {
             println!("Withdrawing funds...");
         }
+        Commands::Withdraw { pool_id, witness, tx, json, .. } => {
+            let (Some(pool_id), Some(witness)) = (pool_id, witness) else {
+                anyhow::bail!("--dry-run needs --pool-id and --witness");
+            };
+            let witness = WithdrawalWitness::from_envelope(&hex::decode(witness.trim())?)?;
+            let outputs = tx
+                .map(|tx| bitcoin::consensus::encode::deserialize_hex::<Transaction>(tx.trim()))
+                .transpose()?
+                .map(|tx| tx.output);
+            let client = PoolClient::new(Arc::new(deezel.provider().clone_box()), pool_id);
+            let report = client.simulate_withdrawal(&witness.proof, outputs.as_deref()).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+            if let Some(failure) = report.first_failure() {
+                anyhow::bail!("the withdrawal would fail the {} check", failure.step);
+            }
+        }
+        Commands::DecodeWitness { witness } => {
+            let witness = WithdrawalWitness::from_envelope(&hex::decode(witness.trim())?)?;
+            println!("Merkle root:         {}", hex::encode(witness.proof.merkle_root));
+            println!("Nullifier hash:      {}", witness.proof.nullifier_hash.to_bech32());
+            println!("Commitment:          {}", witness.commitment.to_bech32());
+            println!("Leaf index:          {}", witness.leaf_index);
+            println!("Path height:         {}", witness.path.len());
+            println!("Outputs hash:        {}", hex::encode(witness.outputs_hash));
+            println!("Relayer output hash: {}", hex::encode(witness.proof.relayer_output_hash));
+            println!("Relayer fee:         {}", witness.proof.fee);
+            println!("Proof size:          {} bytes", witness.proof.proof_size());
+        }
+        Commands::Broadcast { psbt } => {
+            let psbt = Psbt::deserialize(&hex::decode(psbt.trim())?)?;
+            let provider = Arc::new(deezel.provider().clone_box());
+            let signer = ProviderSigner::new(provider.clone());
+            let txid = sign_and_broadcast(provider.as_ref(), &signer, psbt).await?;
+            println!("{}", txid);
+        }
+        Commands::Notes { notes_file, command } => {
+            let path = notes_file.map_or_else(|| profile.notes_path(), Ok)?;
+            notes::run(path, command, network, &profile, Arc::new(deezel.provider().clone_box())).await?;
+        }
+        Commands::Prove(args) => {
+            prove::run(args, &profile).await?;
+        }
+        Commands::Bech32 { kind, value } => {
+            println!("{}", convert_bech32(kind, &value)?);
+        }
+        Commands::Pool { json, command } => {
+            pool::run(command, json, network, &profile, Arc::new(deezel.provider().clone_box())).await?;
+        }
+        Commands::Rewards { command } => {
+            rewards::run(command, network, &profile, Arc::new(deezel.provider().clone_box())).await?;
+        }
+        Commands::Relay { command } => {
+            relay::run(command, network, &profile, Arc::new(deezel.provider().clone_box())).await?;
+        }
+        Commands::Config { .. } | Commands::EstimateFee { .. } => {
+            unreachable!("handled before connecting")
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utxo() {
+        let utxo = parse_utxo(&format!("{}:1:10000:5120{}", "11".repeat(32), "22".repeat(32))).unwrap();
+        assert_eq!(utxo.outpoint.vout, 1);
+        assert_eq!(utxo.txout.value, Amount::from_sat(10_000));
+        assert_eq!(utxo.txout.script_pubkey.len(), 34);
+
+        assert!(parse_utxo("11:1:10000").is_err());
+        assert!(parse_utxo(&format!("{}:1:10000:zz", "11".repeat(32))).is_err());
+    }
+
+    #[test]
+    fn test_convert_bech32() {
+        let hex = "ab".repeat(32);
+        let encoded = convert_bech32(Some(Bech32Kind::Commitment), &format!("0x{}", hex)).unwrap();
+        assert!(encoded.starts_with("zkc1"));
+        // Decoding doesn't need the kind
+        assert_eq!(convert_bech32(None, &encoded).unwrap(), hex);
+
+        let pool = convert_bech32(Some(Bech32Kind::Pool), "6:1").unwrap();
+        assert_eq!(convert_bech32(Some(Bech32Kind::Commitment), &pool).unwrap(), "6:1");
+
+        assert!(convert_bech32(None, &hex).is_err());
+        assert!(convert_bech32(Some(Bech32Kind::NullifierHash), "abcd").is_err());
+    }
+}
@@ -0,0 +1,663 @@
+//! # Local Note Store
+//!
+//! Deposit notes are kept in a single file encrypted with a passphrase: the
+//! key is derived with Argon2id and the notes are sealed with AES-256-GCM. The
+//! `notes` subcommands list, import and export notes, and `notes scan` queries
+//! each note's pool to refresh its status, leaf index and anonymity set.
+//! `notes recover` rebuilds a note from just its secret and nullifier by
+//! searching the factory's pools for its deposit.
+//!
+//! The store also keeps a [`NullifierIndex`] of the notes, so their nullifier
+//! hashes are computed once and `notes scan` checks each pool's notes for
+//! spends in bulk.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use crate::config::{NetworkName, NetworkProfile};
+use clap::Subcommand;
+use deezel_common::traits::DeezelProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use zeroize::Zeroizing;
+use zkane_common::{derive_pool_id, DepositNote, EntropySource, Nullifier, Secret, SystemEntropy, ZkAssetId};
+use zkane_core::{NoteRecovery, NullifierIndex, PoolClient, RecoveredNote};
+
+/// Version of the encrypted store format
+///
+/// Version 1 stores hold the bare list of notes, without a nullifier index.
+pub const NOTE_STORE_VERSION: u32 = 2;
+
+/// Environment variable read for the store passphrase before prompting
+pub const PASSPHRASE_ENV: &str = "ZKANE_NOTES_PASSPHRASE";
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+
+/// Manage locally stored deposit notes
+#[derive(Subcommand)]
+pub enum NotesCommand {
+    /// List stored notes
+    List,
+    /// Show a single note
+    Show {
+        /// Note number from `notes list`, or a commitment hex prefix
+        /// (starting with 0x if it only contains digits)
+        note: String,
+        /// Also print the note's secret and nullifier
+        #[clap(long)]
+        reveal: bool,
+    },
+    /// Import notes from a JSON file
+    Import {
+        /// File holding a note, a list of notes or an export
+        file: PathBuf,
+    },
+    /// Export all notes, including their secrets, to a plaintext JSON file
+    Export {
+        /// Destination file
+        file: PathBuf,
+    },
+    /// Refresh note status from the chain
+    Scan,
+    /// Rebuild a note from its secret and nullifier and store it
+    Recover {
+        /// Note secret, hex
+        #[clap(long)]
+        secret: String,
+        /// Note nullifier, hex
+        #[clap(long)]
+        nullifier: String,
+        /// Only search the pools of this asset (block:tx)
+        #[clap(long)]
+        asset: Option<ZkAssetId>,
+        /// Factory alkane ID (block:tx, defaults to the network profile's)
+        #[clap(long)]
+        factory: Option<ZkAssetId>,
+    },
+}
+
+/// What the last scan found out about a note.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteState {
+    /// Not scanned yet, or the deposit wasn't found in the pool
+    #[default]
+    Unknown,
+    /// Deposited and not withdrawn
+    Unspent,
+    /// Withdrawn
+    Spent,
+}
+
+impl fmt::Display for NoteState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NoteState::Unknown => "unknown",
+            NoteState::Unspent => "unspent",
+            NoteState::Spent => "spent",
+        })
+    }
+}
+
+/// A deposit note and its last known on-chain status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredNote {
+    /// The deposit note
+    pub note: DepositNote,
+    /// The pool the note was deposited to, derived from the note if unset
+    #[serde(default)]
+    pub pool: Option<ZkAssetId>,
+    /// Status found by the last scan
+    #[serde(default)]
+    pub state: NoteState,
+    /// Leaf index of the deposit, once found in the pool
+    #[serde(default)]
+    pub leaf_index: Option<u32>,
+    /// Number of deposits in the pool at the last scan
+    #[serde(default)]
+    pub anonymity_set: Option<u64>,
+}
+
+impl From<RecoveredNote> for StoredNote {
+    fn from(recovered: RecoveredNote) -> Self {
+        Self {
+            pool: Some(recovered.pool_id),
+            state: if recovered.spent { NoteState::Spent } else { NoteState::Unspent },
+            leaf_index: Some(recovered.note.leaf_index),
+            anonymity_set: Some(recovered.anonymity_set),
+            note: recovered.note,
+        }
+    }
+}
+
+impl StoredNote {
+    /// Wrap a note that hasn't been scanned yet.
+    pub fn new(note: DepositNote) -> Self {
+        Self {
+            note,
+            pool: None,
+            state: NoteState::Unknown,
+            leaf_index: None,
+            anonymity_set: None,
+        }
+    }
+
+    /// Get the pool of the note.
+    pub fn pool_id(&self) -> ZkAssetId {
+        self.pool
+            .unwrap_or_else(|| derive_pool_id(&self.note.asset_id, self.note.denomination))
+    }
+}
+
+/// On-disk format of the store.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Plaintext sealed in the store.
+#[derive(Serialize, Deserialize)]
+struct Contents {
+    notes: Vec<StoredNote>,
+    #[serde(default)]
+    nullifier_index: NullifierIndex,
+}
+
+/// Encrypted file of deposit notes.
+pub struct NoteStore {
+    path: PathBuf,
+    salt: [u8; SALT_SIZE],
+    key: Zeroizing<[u8; 32]>,
+    notes: Vec<StoredNote>,
+    nullifier_index: NullifierIndex,
+}
+
+impl NoteStore {
+    /// Open the store at `path`, starting an empty one if the file doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or decrypted with the passphrase.
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let path = path.into();
+        if !path.exists() {
+            let mut salt = [0u8; SALT_SIZE];
+            SystemEntropy::default().fill(&mut salt)?;
+            let key = derive_key(passphrase, &salt)?;
+            return Ok(Self { path, salt, key, notes: Vec::new(), nullifier_index: NullifierIndex::new() });
+        }
+
+        let contents = std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let envelope: Envelope = serde_json::from_slice(&contents).context("malformed note store")?;
+        if envelope.version == 0 || envelope.version > NOTE_STORE_VERSION {
+            return Err(anyhow!("unsupported note store version {}", envelope.version));
+        }
+        let salt: [u8; SALT_SIZE] = hex::decode(&envelope.salt)?
+            .try_into()
+            .map_err(|_| anyhow!("malformed note store salt"))?;
+        let nonce = hex::decode(&envelope.nonce)?;
+        if nonce.len() != NONCE_SIZE {
+            return Err(anyhow!("malformed note store nonce"));
+        }
+
+        let key = derive_key(passphrase, &salt)?;
+        let plaintext = Zeroizing::new(
+            Aes256Gcm::new_from_slice(key.as_slice())
+                .map_err(|e| anyhow!("{}", e))?
+                .decrypt(Nonce::from_slice(&nonce), hex::decode(&envelope.ciphertext)?.as_slice())
+                .map_err(|_| anyhow!("wrong passphrase or corrupted note store"))?,
+        );
+        let contents = if envelope.version == 1 {
+            Contents {
+                notes: serde_json::from_slice(&plaintext).context("malformed note store contents")?,
+                nullifier_index: NullifierIndex::new(),
+            }
+        } else {
+            serde_json::from_slice(&plaintext).context("malformed note store contents")?
+        };
+
+        let mut store = Self {
+            path,
+            salt,
+            key,
+            notes: contents.notes,
+            nullifier_index: contents.nullifier_index,
+        };
+        // Index notes of older stores, and drop entries of removed notes
+        let commitments: std::collections::HashSet<_> = store.notes.iter().map(|stored| stored.note.commitment).collect();
+        store.nullifier_index.retain(|commitment| commitments.contains(commitment));
+        for stored in &store.notes {
+            store.nullifier_index.insert(&stored.note)?;
+        }
+        Ok(store)
+    }
+
+    /// Encrypt the notes and write them to the store file.
+    pub fn save(&self) -> Result<()> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        SystemEntropy::default().fill(&mut nonce)?;
+        let contents = Contents {
+            notes: self.notes.clone(),
+            nullifier_index: self.nullifier_index.clone(),
+        };
+        let plaintext = Zeroizing::new(serde_json::to_vec(&contents)?);
+        let ciphertext = Aes256Gcm::new_from_slice(self.key.as_slice())
+            .map_err(|e| anyhow!("{}", e))?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|e| anyhow!("failed to encrypt notes: {}", e))?;
+
+        let envelope = Envelope {
+            version: NOTE_STORE_VERSION,
+            salt: hex::encode(self.salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so a crash can't truncate the store
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&envelope)?)?;
+        restrict_permissions(&tmp)?;
+        std::fs::rename(&tmp, &self.path).with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    /// Get the location of the store file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the stored notes.
+    pub fn notes(&self) -> &[StoredNote] {
+        &self.notes
+    }
+
+    /// Get the stored notes for updating.
+    pub fn notes_mut(&mut self) -> &mut [StoredNote] {
+        &mut self.notes
+    }
+
+    /// Get the nullifier hashes of the stored notes.
+    pub fn nullifier_index(&self) -> &NullifierIndex {
+        &self.nullifier_index
+    }
+
+    /// Add a note unless one with the same commitment is already stored.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the note was added.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the note's nullifier hash can't be computed.
+    pub fn add(&mut self, note: StoredNote) -> Result<bool> {
+        if self.notes.iter().any(|stored| stored.note.commitment == note.note.commitment) {
+            return Ok(false);
+        }
+        self.nullifier_index.insert(&note.note)?;
+        self.notes.push(note);
+        Ok(true)
+    }
+
+    /// Find a note by its number in `notes list` or a commitment hex prefix.
+    ///
+    /// Decimal queries are note numbers; prefix a commitment with `0x` if it
+    /// only contains digits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no note or more than one note matches.
+    pub fn find(&self, query: &str) -> Result<usize> {
+        if let Ok(number) = query.parse::<usize>() {
+            return number
+                .checked_sub(1)
+                .filter(|&index| index < self.notes.len())
+                .ok_or_else(|| anyhow!("no note number {}", number));
+        }
+        let prefix = query.trim_start_matches("0x").to_lowercase();
+        let mut matches = self
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(_, stored)| !prefix.is_empty() && stored.note.commitment.to_hex().starts_with(&prefix));
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => Err(anyhow!("'{}' matches more than one note", query)),
+            (None, _) => Err(anyhow!("no note matches '{}'", query)),
+        }
+    }
+}
+
+/// Parse an imported file: a note, a list of notes or an export. Notes are
+/// in the versioned note schema written by the dapps, or serialized as
+/// `DepositNote` by earlier releases.
+///
+/// Dispatches on the JSON value, as untagged serde enums can't hold the
+/// `u128` fields of notes. Schema notes are told apart by their checksum, as
+/// serialized notes also carry a `version` since note version 2.
+fn parse_import(contents: &[u8]) -> Result<Vec<StoredNote>> {
+    let items = match serde_json::from_slice(contents).context("invalid note file")? {
+        JsonValue::Array(items) => items,
+        item => vec![item],
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            if item.get("note").is_some() {
+                serde_json::from_value(item).context("unrecognized note file")
+            } else if item.get("checksum").is_some() {
+                Ok(StoredNote::new(DepositNote::from_json(&item.to_string())?))
+            } else {
+                let note = serde_json::from_value::<DepositNote>(item).context("unrecognized note file")?;
+                note.check_version()?;
+                Ok(StoredNote::new(note))
+            }
+        })
+        .collect()
+}
+
+/// Derive the store key from the passphrase with Argon2id.
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| anyhow!("failed to derive note store key: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?)
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Read the store passphrase from the environment or the terminal.
+fn read_passphrase(path: &Path) -> Result<Zeroizing<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Notes passphrase: ")?);
+    if !path.exists() {
+        let confirmation = Zeroizing::new(rpassword::prompt_password("Confirm passphrase: ")?);
+        if *confirmation != *passphrase {
+            return Err(anyhow!("passphrases don't match"));
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Open the store at `path`, asking for its passphrase.
+pub fn open_store(path: PathBuf) -> Result<NoteStore> {
+    let passphrase = read_passphrase(&path)?;
+    NoteStore::open(path, &passphrase)
+}
+
+/// Run a `notes` subcommand.
+pub async fn run<P: DeezelProvider>(
+    path: PathBuf,
+    command: NotesCommand,
+    network: NetworkName,
+    profile: &NetworkProfile,
+    provider: Arc<P>,
+) -> Result<()> {
+    let mut store = open_store(path)?;
+
+    match command {
+        NotesCommand::List => {
+            if store.notes().is_empty() {
+                println!("No notes stored in {}", store.path().display());
+                return Ok(());
+            }
+            println!(
+                "{:>3}  {:<8}  {:<44}  {:>14}  {:>6}  {:>8}  COMMITMENT",
+                "#", "STATUS", "POOL", "DENOMINATION", "LEAF", "ANON-SET"
+            );
+            for (index, stored) in store.notes().iter().enumerate() {
+                let pool = stored.pool_id();
+                println!(
+                    "{:>3}  {:<8}  {:<44}  {:>14}  {:>6}  {:>8}  {}",
+                    index + 1,
+                    stored.state,
+                    pool.to_string(),
+                    stored.note.denomination,
+                    display_option(stored.leaf_index),
+                    display_option(stored.anonymity_set),
+                    &stored.note.commitment.to_hex()[..16],
+                );
+            }
+        }
+        NotesCommand::Show { note, reveal } => {
+            let stored = &store.notes()[store.find(&note)?];
+            let pool = stored.pool_id();
+            println!("Status:         {}", stored.state);
+            println!("Pool:           {} ({})", pool, pool.to_bech32());
+            println!("Asset:          {}", stored.note.asset_id);
+            println!("Denomination:   {}", stored.note.denomination);
+            println!("Leaf index:     {}", display_option(stored.leaf_index));
+            println!("Anonymity set:  {}", display_option(stored.anonymity_set));
+            println!("Commitment:     {}", stored.note.commitment.to_bech32());
+            let nullifier_hash = store
+                .nullifier_index()
+                .get(&stored.note.commitment)
+                .ok_or_else(|| anyhow!("note is missing from the nullifier index"))?;
+            println!("Nullifier hash: {}", nullifier_hash.to_bech32());
+            if reveal {
+                println!("Secret:         {}", stored.note.secret.to_hex());
+                println!("Nullifier:      {}", stored.note.nullifier.to_hex());
+            }
+        }
+        NotesCommand::Import { file } => {
+            let contents = std::fs::read(&file).with_context(|| format!("failed to read {}", file.display()))?;
+            let notes = parse_import(&contents)?;
+            let total = notes.len();
+            let mut added = 0;
+            for note in notes {
+                added += store.add(note)? as usize;
+            }
+            store.save()?;
+            println!("Imported {} notes ({} already stored)", added, total - added);
+        }
+        NotesCommand::Export { file } => {
+            std::fs::write(&file, serde_json::to_vec_pretty(store.notes())?)?;
+            restrict_permissions(&file)?;
+            println!("Exported {} notes to {}", store.notes().len(), file.display());
+            eprintln!("Warning: the export contains note secrets in plaintext");
+        }
+        NotesCommand::Scan => {
+            let failures = scan_notes(&mut store, provider).await;
+            store.save()?;
+            println!("Scanned {} notes ({} failed)", store.notes().len(), failures);
+        }
+        NotesCommand::Recover { secret, nullifier, asset, factory } => {
+            let secret = Secret::from_hex(secret.trim()).context("invalid secret")?;
+            let nullifier = Nullifier::from_hex(nullifier.trim()).context("invalid nullifier")?;
+            let factory = factory.map_or_else(|| profile.factory(network), Ok)?;
+            let mut recovery = NoteRecovery::new(provider, factory);
+            if let Some(asset) = asset {
+                recovery = recovery.with_asset(asset);
+            }
+            let recovered = recovery
+                .recover(&secret, &nullifier)
+                .await?
+                .ok_or_else(|| anyhow!("no pool of factory {} holds a deposit of this note", factory))?;
+
+            let stored = StoredNote::from(recovered);
+            println!("Status:         {}", stored.state);
+            println!("Pool:           {}", stored.pool_id());
+            println!("Asset:          {}", stored.note.asset_id);
+            println!("Denomination:   {}", stored.note.denomination);
+            println!("Leaf index:     {}", display_option(stored.leaf_index));
+            println!("Anonymity set:  {}", display_option(stored.anonymity_set));
+            println!("Commitment:     {}", stored.note.commitment.to_bech32());
+            if store.add(stored)? {
+                store.save()?;
+                println!("Added the note to {}", store.path().display());
+            } else {
+                println!("The note is already stored in {}", store.path().display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Refresh the status of every note, pool by pool.
+///
+/// The spent status of a pool's notes is checked in bulk with the nullifier
+/// index. Failures are reported and counted, and leave the note unchanged.
+async fn scan_notes<P: DeezelProvider>(store: &mut NoteStore, provider: Arc<P>) -> usize {
+    let mut pools: Vec<(ZkAssetId, Vec<usize>)> = Vec::new();
+    for (index, stored) in store.notes().iter().enumerate() {
+        let pool = stored.pool_id();
+        match pools.iter_mut().find(|(id, _)| *id == pool) {
+            Some((_, notes)) => notes.push(index),
+            None => pools.push((pool, vec![index])),
+        }
+    }
+
+    let mut failures = 0;
+    for (pool, indices) in pools {
+        let client = PoolClient::new(provider.clone(), pool);
+        let commitments: Vec<_> = indices.iter().map(|&index| store.notes()[index].note.commitment).collect();
+        let spent = match store.nullifier_index().check_spent(&client, &commitments).await {
+            Ok(spent) => spent,
+            Err(e) => {
+                eprintln!("Failed to check spends in pool {}: {}", pool, e);
+                failures += indices.len();
+                continue;
+            }
+        };
+        for (index, spent) in indices.into_iter().zip(spent) {
+            let stored = &mut store.notes_mut()[index];
+            let result = match spent {
+                Some(spent) => scan_note(stored, &client, spent).await,
+                None => Err(anyhow!("note is missing from the nullifier index")),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to scan note {}: {}", index + 1, e);
+                failures += 1;
+            }
+        }
+    }
+    failures
+}
+
+/// Refresh the status of a note from its pool, given whether its nullifier
+/// hash is spent.
+async fn scan_note<P: DeezelProvider>(stored: &mut StoredNote, client: &PoolClient<P>, spent: bool) -> Result<()> {
+    let Some(leaf_index) = client.find_commitment(&stored.note.commitment).await? else {
+        stored.state = NoteState::Unknown;
+        return Ok(());
+    };
+
+    stored.state = if spent { NoteState::Spent } else { NoteState::Unspent };
+    stored.pool = Some(client.pool_id());
+    stored.leaf_index = Some(leaf_index);
+    stored.note.leaf_index = leaf_index;
+    stored.anonymity_set = Some(client.deposit_count().await?);
+    Ok(())
+}
+
+fn display_option<T: fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_core::generate_deposit_note;
+
+    fn temp_store_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zkane-notes-{}-{}.enc", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let path = temp_store_path("roundtrip");
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        let commitment = note.commitment;
+
+        let mut store = NoteStore::open(&path, "correct horse").unwrap();
+        assert!(store.add(StoredNote::new(note.clone())).unwrap());
+        assert!(!store.add(StoredNote::new(note.clone())).unwrap());
+        store.save().unwrap();
+
+        let reopened = NoteStore::open(&path, "correct horse");
+        let wrong = NoteStore::open(&path, "battery staple");
+        std::fs::remove_file(&path).unwrap();
+
+        let reopened = reopened.unwrap();
+        assert_eq!(reopened.notes().len(), 1);
+        assert_eq!(reopened.notes()[0].note.commitment, commitment);
+        assert_eq!(reopened.find("1").unwrap(), 0);
+        assert_eq!(reopened.find(&format!("0x{}", &commitment.to_hex()[..8])).unwrap(), 0);
+        assert!(reopened.find("2").is_err());
+        assert!(wrong.is_err());
+
+        let nullifier_hash = zkane_crypto::generate_nullifier_hash(&note.nullifier).unwrap();
+        assert_eq!(reopened.nullifier_index().get(&commitment), Some(nullifier_hash));
+    }
+
+    #[test]
+    fn test_version_1_store_is_indexed() {
+        let path = temp_store_path("version-1");
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        let mut store = NoteStore::open(&path, "correct horse").unwrap();
+        store.notes.push(StoredNote::new(note.clone()));
+
+        // Seal the bare note list, as version 1 did
+        let mut nonce = [0u8; NONCE_SIZE];
+        SystemEntropy::default().fill(&mut nonce).unwrap();
+        let ciphertext = Aes256Gcm::new_from_slice(store.key.as_slice())
+            .unwrap()
+            .encrypt(Nonce::from_slice(&nonce), serde_json::to_vec(&store.notes).unwrap().as_slice())
+            .unwrap();
+        let envelope = Envelope {
+            version: 1,
+            salt: hex::encode(store.salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        std::fs::write(&path, serde_json::to_vec(&envelope).unwrap()).unwrap();
+
+        let reopened = NoteStore::open(&path, "correct horse");
+        std::fs::remove_file(&path).unwrap();
+        let reopened = reopened.unwrap();
+        assert_eq!(reopened.notes().len(), 1);
+        assert!(reopened.nullifier_index().get(&note.commitment).is_some());
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        let single = serde_json::to_vec(&note).unwrap();
+        let list = serde_json::to_vec(&[note.clone(), note.clone()]).unwrap();
+        let export = serde_json::to_vec(&[StoredNote::new(note.clone())]).unwrap();
+        let schema = format!("[{}]", note.to_json());
+
+        assert_eq!(parse_import(&single).unwrap().len(), 1);
+        assert_eq!(parse_import(&list).unwrap().len(), 2);
+        assert_eq!(parse_import(&export).unwrap()[0].note.commitment, note.commitment);
+        assert_eq!(parse_import(schema.as_bytes()).unwrap()[0].note.commitment, note.commitment);
+        let version = format!("\"version\":{}", zkane_common::NOTE_JSON_VERSION);
+        assert!(parse_import(schema.replace(&version, "\"version\":9").as_bytes()).is_err());
+        let serialized = serde_json::to_string(&note).unwrap();
+        let version = format!("\"version\":{}", zkane_common::DEPOSIT_NOTE_VERSION);
+        assert!(parse_import(serialized.replace(&version, "\"version\":9").as_bytes()).is_err());
+        assert!(parse_import(b"{\"secret\": 1}").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! # Pool Inspection
+//!
+//! The `pool` subcommands query deployed pools and the factory through the
+//! provider, printing a table or, with `--json`, structured output for
+//! monitoring scripts.
+//!
+//! `pool audit` checks a pool's reserves against its outstanding notes and
+//! signs the report with the wallet's key, for operators to publish.
+//!
+//! `pool health` checks the profile's Sandshrew endpoint and its failover
+//! backups, the providers a [`FailoverProvider`] would sync pools from.
+
+use crate::config::{self, NetworkName, NetworkProfile};
+use anyhow::Result;
+use clap::Subcommand;
+use deezel_common::traits::{DeezelProvider, WalletProvider};
+use deezel_common::System;
+use deezel_sys::SystemDeezel;
+use std::path::PathBuf;
+use std::sync::Arc;
+use zkane_common::{parse_units, DenominationSpec, NullifierHash, ZkAssetId, CIRCUIT_VERSION};
+use zkane_core::{audit_pool, FactoryClient, FailoverProvider, PoolClient};
+
+/// Inspect deployed pools
+#[derive(Subcommand)]
+pub enum PoolCommand {
+    /// Show the state of a pool
+    Info {
+        /// Pool alkane ID (block:tx or zkp1...)
+        #[clap(long)]
+        pool_id: ZkAssetId,
+    },
+    /// Show the current Merkle root of a pool
+    Root {
+        /// Pool alkane ID (block:tx or zkp1...)
+        #[clap(long)]
+        pool_id: ZkAssetId,
+    },
+    /// Check whether a nullifier hash has been spent in a pool
+    IsSpent {
+        /// Pool alkane ID (block:tx or zkp1...)
+        #[clap(long)]
+        pool_id: ZkAssetId,
+        /// Nullifier hash, hex or bech32m (zkn1...)
+        #[clap(long)]
+        nullifier_hash: String,
+    },
+    /// List the pools of an asset
+    ///
+    /// Denominations are shown in the asset's units where the factory has
+    /// a denomination spec for them.
+    List {
+        /// Asset alkane ID (block:tx)
+        #[clap(long)]
+        asset: ZkAssetId,
+        /// Only list pools of this denomination, e.g. "0.1 ZKN"; base units
+        /// if the asset has no denomination specs
+        #[clap(long)]
+        amount: Option<String>,
+        /// Factory alkane ID (block:tx, defaults to the network profile's)
+        #[clap(long)]
+        factory: Option<ZkAssetId>,
+    },
+    /// Show pool and deposit totals across all pools of the factory
+    Stats {
+        /// Factory alkane ID (block:tx, defaults to the network profile's)
+        #[clap(long)]
+        factory: Option<ZkAssetId>,
+    },
+    /// Check a pool's reserves against its outstanding notes
+    ///
+    /// Prints the report signed with the wallet's key, and fails if the
+    /// pool holds less than its notes are worth.
+    Audit {
+        /// Pool alkane ID (block:tx or zkp1...)
+        #[clap(long)]
+        pool_id: ZkAssetId,
+        /// Also write the signed report to this file
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check the tip height and latency of the provider and its failover backups
+    Health,
+}
+
+/// Run a `pool` subcommand.
+pub async fn run<P: DeezelProvider>(
+    command: PoolCommand,
+    json: bool,
+    network: NetworkName,
+    profile: &NetworkProfile,
+    provider: Arc<P>,
+) -> Result<()> {
+    match command {
+        PoolCommand::Info { pool_id } => {
+            let info = PoolClient::new(provider, pool_id).info().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+                return Ok(());
+            }
+            println!("Pool:            {}", info.pool_id);
+            println!("Denomination:    {}", info.denomination);
+            println!("Deposits:        {}", info.deposit_count);
+            println!("Merkle root:     {}", info.merkle_root);
+            println!("Circuit version: {}", info.circuit_version);
+            match info.protocol_fee {
+                Some(fee) => println!("Protocol fee:    {} bps to {}", fee.fee_bps, fee.collector),
+                None => println!("Protocol fee:    none"),
+            }
+        }
+        PoolCommand::Root { pool_id } => {
+            let root = hex::encode(PoolClient::new(provider, pool_id).merkle_root().await?);
+            if json {
+                println!("{}", serde_json::json!({ "pool_id": pool_id, "merkle_root": root }));
+            } else {
+                println!("{}", root);
+            }
+        }
+        PoolCommand::IsSpent { pool_id, nullifier_hash } => {
+            let nullifier_hash = if nullifier_hash.to_ascii_lowercase().starts_with("zkn1") {
+                NullifierHash::from_bech32(&nullifier_hash)?
+            } else {
+                NullifierHash::from_hex(nullifier_hash.trim_start_matches("0x"))?
+            };
+            let spent = PoolClient::new(provider, pool_id).is_spent(&nullifier_hash).await?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "pool_id": pool_id,
+                        "nullifier_hash": nullifier_hash.to_hex(),
+                        "spent": spent,
+                    })
+                );
+            } else {
+                println!("{}", if spent { "spent" } else { "unspent" });
+            }
+        }
+        PoolCommand::List { asset, amount, factory } => {
+            let factory = factory.map_or_else(|| profile.factory(network), Ok)?;
+            let client = FactoryClient::new(provider, factory);
+            let specs = client.denomination_specs(&asset).await?;
+            let mut pools = client.asset_pools(&asset).await?;
+            if let Some(amount) = amount {
+                // Amounts must name a denomination exactly, never the nearest one
+                let denomination = if specs.is_empty() {
+                    parse_units(amount.trim(), 0)?
+                } else {
+                    DenominationSpec::find(&specs, &amount)?.base_units
+                };
+                pools.retain(|pool| pool.denomination == denomination);
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&pools)?);
+                return Ok(());
+            }
+            if pools.is_empty() {
+                println!("No pools for asset {}", asset);
+                return Ok(());
+            }
+            println!(
+                "{:<44}  {:>20}  {:>8}  {:>8}  {:>8}  {:>7}",
+                "POOL", "DENOMINATION", "DEPOSITS", "CREATED", "TEMPLATE", "CIRCUIT"
+            );
+            for pool in &pools {
+                println!(
+                    "{:<44}  {:>20}  {:>8}  {:>8}  {:>8}  {:>7}",
+                    pool.pool_id.to_string(),
+                    specs
+                        .iter()
+                        .find(|spec| spec.base_units == pool.denomination)
+                        .map_or_else(|| pool.denomination.to_string(), ToString::to_string),
+                    pool.deposit_count,
+                    pool.created_block,
+                    pool.template_version,
+                    pool.circuit_version
+                );
+            }
+            // Notes deposited into these pools can't be withdrawn with this build
+            for pool in pools.iter().filter(|pool| pool.check_circuit_version(CIRCUIT_VERSION).is_err()) {
+                eprintln!(
+                    "Warning: pool {} verifies circuit version {}, this build proves version {}",
+                    pool.pool_id, pool.circuit_version, CIRCUIT_VERSION
+                );
+            }
+        }
+        PoolCommand::Stats { factory } => {
+            let factory = factory.map_or_else(|| profile.factory(network), Ok)?;
+            let stats = FactoryClient::new(provider, factory).global_stats().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            println!("Pools:    {}", stats.total_pools);
+            println!("Deposits: {}", stats.total_deposits);
+            if stats.assets.is_empty() {
+                return Ok(());
+            }
+            println!();
+            println!("{:<44}  {:>6}  {:>8}", "ASSET", "POOLS", "DEPOSITS");
+            for asset in stats.assets {
+                println!("{:<44}  {:>6}  {:>8}", asset.asset_id.to_string(), asset.pools, asset.deposits);
+            }
+        }
+        PoolCommand::Audit { pool_id, output } => {
+            let report = audit_pool(provider.clone(), pool_id).await?;
+            let signed = report.sign(&WalletProvider::get_keypair(provider.as_ref()).await?)?;
+            let signed_json = signed.to_json()?;
+            if let Some(output) = output {
+                std::fs::write(output, &signed_json)?;
+            }
+            let report = &signed.report;
+            if json {
+                println!("{}", signed_json);
+            } else {
+                println!("Pool:        {}", report.pool_id);
+                println!("Height:      {}", report.height);
+                println!("Merkle root: {}", report.merkle_root);
+                println!("Notes:       {} outstanding", report.reserves.outstanding_notes());
+                println!("Held:        {}", report.reserves.held);
+                match report.expected {
+                    Some(expected) => {
+                        println!("Expected:    {}", expected);
+                        if let Some(discrepancy) = report.discrepancy {
+                            println!("Discrepancy: {}", discrepancy);
+                        }
+                    }
+                    None => println!("Expected:    unknown, the pool only counts notes since its upgrade"),
+                }
+                println!("Signed by:   {}", signed.public_key);
+            }
+            if report.is_solvent() == Some(false) {
+                anyhow::bail!(
+                    "pool {} holds {} less than its notes are worth",
+                    pool_id,
+                    report.discrepancy.unwrap_or(0).unsigned_abs()
+                );
+            }
+        }
+        PoolCommand::Health => unreachable!("handled before connecting"),
+    }
+
+    Ok(())
+}
+
+/// Run `pool health`: connect to every failover endpoint of the profile and
+/// report their health, highest priority first.
+pub async fn health(json: bool, args: &deezel_common::commands::Args, profile: &NetworkProfile) -> Result<()> {
+    let mut providers = Vec::new();
+    for url in config::failover_endpoints(args, profile) {
+        let mut endpoint_args = args.clone();
+        endpoint_args.sandshrew_rpc_url = Some(url.clone());
+        let deezel = SystemDeezel::new(&endpoint_args).await?;
+        providers.push((url, deezel.provider().clone_box()));
+    }
+    let failover = FailoverProvider::new(providers, profile.failover.clone().unwrap_or_default().config())?;
+    let report = failover.health_check().await;
+
+    if json {
+        let entries: Vec<serde_json::Value> = report
+            .iter()
+            .map(|health| {
+                serde_json::json!({
+                    "endpoint": health.name,
+                    "healthy": health.is_healthy(),
+                    "tip_height": health.tip_height,
+                    "latency_ms": health.latency.as_millis() as u64,
+                    "error": health.error,
+                })
+            })
+            .collect();
+        let output = serde_json::json!({ "active": failover.active(), "endpoints": entries });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    println!("{:<48}  {:>8}  {:>8}  STATUS", "ENDPOINT", "TIP", "LATENCY");
+    for health in &report {
+        let tip = health.tip_height.map_or_else(|| "-".to_string(), |tip| tip.to_string());
+        let status = health.error.as_deref().unwrap_or("healthy");
+        println!("{:<48}  {:>8}  {:>6}ms  {}", health.name, tip, health.latency.as_millis(), status);
+    }
+    println!();
+    println!("Active: {}", failover.active());
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! # Proof Generation
+//!
+//! `zkane-cli prove` generates the withdrawal proof of a stored note, drawing
+//! a progress bar on stderr. Ctrl-C cancels the proof at the next stage.
+
+use crate::config::NetworkProfile;
+use crate::notes;
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::path::{Path, PathBuf};
+use zkane_common::DepositNote;
+use zkane_crypto::zkp::{
+    proof_to_bytes, prove_with_handle, proving_key_from_bytes, ProofStage, ProverHandle, WithdrawalCircuit,
+};
+
+/// Width of the progress bar in characters
+const PROGRESS_WIDTH: usize = 30;
+
+/// Generate the withdrawal proof of a stored note
+#[derive(Args)]
+pub struct ProveArgs {
+    /// Compressed proving key file
+    #[clap(long)]
+    proving_key: PathBuf,
+    /// Note number from `notes list`, or a commitment hex prefix
+    #[clap(long)]
+    note: String,
+    /// Encrypted note store (defaults to notes.enc in the network's data directory)
+    #[clap(long)]
+    notes_file: Option<PathBuf>,
+    /// Hex-encoded hash of the recipient outputs to bind the proof to
+    #[clap(long)]
+    recipients_hash: Option<String>,
+    /// Hex-encoded hash of the relayer's fee output (none for self-relayed withdrawals)
+    #[clap(long)]
+    relayer_output_hash: Option<String>,
+    /// Fee paid to the relayer
+    #[clap(long, default_value_t = 0)]
+    fee: u128,
+    /// Last block height the proof may be spent at (0 for no expiry)
+    #[clap(long, default_value_t = 0)]
+    not_after_height: u64,
+}
+
+/// Run the `prove` command, printing the hex-encoded proof.
+pub async fn run(args: ProveArgs, profile: &NetworkProfile) -> Result<()> {
+    let recipients_hash = parse_hash(args.recipients_hash.as_deref(), "recipients hash")?;
+    let relayer_output_hash = parse_hash(args.relayer_output_hash.as_deref(), "relayer output hash")?;
+
+    let path = args.notes_file.map_or_else(|| profile.notes_path(), Ok)?;
+    let store = notes::open_store(path)?;
+    let note = &store.notes()[store.find(&args.note)?].note;
+    let proof = prove_note(
+        &args.proving_key,
+        note,
+        &recipients_hash,
+        &relayer_output_hash,
+        args.fee,
+        args.not_after_height,
+    )
+    .await?;
+    println!("{}", hex::encode(proof));
+    Ok(())
+}
+
+/// Generate the compressed withdrawal proof of a note, drawing a progress
+/// bar on stderr. Ctrl-C cancels the proof at the next stage.
+pub async fn prove_note(
+    proving_key: &Path,
+    note: &DepositNote,
+    recipients_hash: &[u8; 32],
+    relayer_output_hash: &[u8; 32],
+    fee: u128,
+    not_after_height: u64,
+) -> Result<Vec<u8>> {
+    let circuit = WithdrawalCircuit::from_note(
+        note.secret.as_bytes(),
+        note.nullifier.as_bytes(),
+        &note.asset_id,
+        note.denomination,
+        recipients_hash,
+        relayer_output_hash,
+        fee,
+        not_after_height,
+    )?;
+    let pk = proving_key_from_bytes(&std::fs::read(proving_key)?)?;
+
+    let handle = ProverHandle::new();
+    handle.on_progress(draw_progress);
+    let canceller = handle.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\nCancelling after the current stage...");
+            canceller.cancel();
+        }
+    });
+
+    let proof = tokio::task::spawn_blocking(move || prove_with_handle(&pk, circuit, &handle)).await?;
+    eprintln!();
+    proof_to_bytes(&proof?)
+}
+
+/// Parse an optional hex hash, zero if absent.
+fn parse_hash(hash: Option<&str>, name: &str) -> Result<[u8; 32]> {
+    match hash {
+        Some(hash) => hex::decode(hash.trim_start_matches("0x"))?
+            .try_into()
+            .map_err(|_| anyhow!("{} must be 32 bytes", name)),
+        None => Ok([0u8; 32]),
+    }
+}
+
+/// Redraw the progress bar for a stage.
+fn draw_progress(stage: ProofStage) {
+    let filled = (stage.progress() * PROGRESS_WIDTH as f64).round() as usize;
+    eprint!(
+        "\r[{}{}] {:>3.0}% {:<12}",
+        "#".repeat(filled),
+        " ".repeat(PROGRESS_WIDTH - filled),
+        stage.progress() * 100.0,
+        stage
+    );
+}
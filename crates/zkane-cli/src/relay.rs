@@ -0,0 +1,420 @@
+//! # Relayed Withdrawals
+//!
+//! `zkane-cli relay quote` asks relayers for their terms and picks the
+//! cheapest. `relay submit` proves a stored note's withdrawal with a
+//! relayer's fee output and fee, hands its witness to the relayer's
+//! `POST /relay` and polls the job until the pool accepts the withdrawal, so
+//! the recipient address never needs coins of its own.
+
+use crate::config::{NetworkName, NetworkProfile};
+use crate::{notes, prove};
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::{Amount, ScriptBuf, TxOut};
+use clap::{Args, Subcommand};
+use deezel_common::traits::DeezelProvider;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use zkane_common::{calculate_outputs_hash, derive_pool_id, Recipient, WithdrawalProof, WithdrawalWitness};
+use zkane_core::withdrawal::DUST_LIMIT;
+use zkane_core::PoolClient;
+
+/// Submit withdrawals through relayers
+#[derive(Subcommand)]
+pub enum RelayCommand {
+    /// Fetch fee quotes from relayers and pick the cheapest
+    Quote {
+        /// Relayer base URL; repeat to compare several relayers
+        #[clap(long = "relayer-url", required = true)]
+        relayer_urls: Vec<String>,
+        /// Print JSON instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+    /// Prove a stored note's withdrawal and submit it to a relayer
+    Submit(SubmitArgs),
+}
+
+/// Prove a stored note's withdrawal and submit it to a relayer
+#[derive(Args)]
+pub struct SubmitArgs {
+    /// Compressed proving key file
+    #[clap(long)]
+    proving_key: PathBuf,
+    /// Note number from `notes list`, or a commitment hex prefix
+    #[clap(long)]
+    note: String,
+    /// Encrypted note store (defaults to notes.enc in the network's data directory)
+    #[clap(long)]
+    notes_file: Option<PathBuf>,
+    /// Relayer base URL
+    #[clap(long)]
+    relayer_url: String,
+    /// Fee paid to the relayer (defaults to its minimum)
+    #[clap(long)]
+    fee: Option<u128>,
+    /// Address the withdrawal is paid to
+    #[clap(long)]
+    recipient: String,
+    /// Value in satoshis of the recipient output
+    #[clap(long, default_value_t = DUST_LIMIT)]
+    recipient_value: u64,
+    /// Last block height the relayer may get the withdrawal mined at (0 for no expiry)
+    #[clap(long, default_value_t = 0)]
+    not_after_height: u64,
+    /// Seconds between job status checks
+    #[clap(long, default_value_t = 5)]
+    poll_interval: u64,
+    /// Seconds to wait for the pool to accept the withdrawal
+    #[clap(long, default_value_t = 3600)]
+    timeout: u64,
+}
+
+/// A transaction output, as the relayer API takes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputDescriptor {
+    /// Output value in satoshis
+    pub value: u64,
+    /// Hex-encoded scriptPubKey
+    pub script_pubkey: String,
+}
+
+impl OutputDescriptor {
+    fn tx_out(&self) -> Result<TxOut> {
+        Ok(TxOut {
+            value: Amount::from_sat(self.value),
+            script_pubkey: ScriptBuf::from_bytes(hex::decode(&self.script_pubkey)?),
+        })
+    }
+}
+
+/// The terms a relayer publishes at `GET /status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayerTerms {
+    /// Minimum fee accepted, in units of the pool asset
+    pub min_fee: u128,
+    /// Pool denomination
+    pub denomination: u128,
+    /// Hex-encoded hash of the output the fee must be paid to
+    pub fee_output_hash: String,
+    /// The output the fee must be paid to
+    pub fee_output: OutputDescriptor,
+    /// Hex-encoded current Merkle root of the relayer's pool
+    pub merkle_root: String,
+    /// Number of jobs that have not reached a final state
+    pub pending_jobs: usize,
+    /// Expected seconds from submission to confirmation, zero if not quoted
+    #[serde(default)]
+    pub estimated_confirmation_secs: u64,
+}
+
+/// A relayer's terms, as fetched for a quote.
+#[derive(Debug, Clone, Serialize)]
+pub struct Quote {
+    pub relayer_url: String,
+    #[serde(flatten)]
+    pub terms: RelayerTerms,
+}
+
+/// Lifecycle of a relay job, as served at `GET /jobs/{id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum JobStatus {
+    Queued,
+    Broadcasting,
+    Broadcast { txid: String },
+    Confirmed { txid: String },
+    Failed { reason: String },
+}
+
+/// A withdrawal submitted to a relayer.
+#[derive(Debug, Serialize)]
+struct RelayRequest {
+    witness: WithdrawalWitness,
+    outputs: Vec<OutputDescriptor>,
+}
+
+/// Pick the quote with the lowest fee, preferring shorter queues on ties.
+pub fn cheapest(quotes: &[Quote]) -> Option<&Quote> {
+    quotes
+        .iter()
+        .min_by_key(|quote| (quote.terms.min_fee, quote.terms.pending_jobs))
+}
+
+/// A client of one relayer's HTTP API.
+struct RelayerClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl RelayerClient {
+    fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    async fn terms(&self) -> Result<RelayerTerms> {
+        let response = self.http.get(format!("{}/status", self.base_url)).send().await?;
+        Self::json(response).await
+    }
+
+    /// Submit a withdrawal, returning its job ID.
+    async fn relay(&self, request: &RelayRequest) -> Result<u64> {
+        let response = self
+            .http
+            .post(format!("{}/relay", self.base_url))
+            .json(request)
+            .send()
+            .await?;
+        let body: serde_json::Value = Self::json(response).await?;
+        body["job_id"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("relayer returned no job ID: {}", body))
+    }
+
+    async fn job(&self, id: u64) -> Result<JobStatus> {
+        let response = self.http.get(format!("{}/jobs/{}", self.base_url, id)).send().await?;
+        Self::json(response).await
+    }
+
+    /// Read a JSON response, turning the relayer's `{"error": ...}` bodies
+    /// into errors.
+    async fn json<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+        let status = response.status();
+        let body: serde_json::Value = response.json().await?;
+        if !status.is_success() {
+            let message = body["error"].as_str().unwrap_or("no error message");
+            bail!("relayer returned {}: {}", status, message);
+        }
+        Ok(serde_json::from_value(body)?)
+    }
+}
+
+/// Run a `relay` subcommand.
+pub async fn run<P: DeezelProvider>(
+    command: RelayCommand,
+    network: NetworkName,
+    profile: &NetworkProfile,
+    provider: Arc<P>,
+) -> Result<()> {
+    match command {
+        RelayCommand::Quote { relayer_urls, json } => {
+            let results = futures::future::join_all(
+                relayer_urls.iter().map(|url| async move { (url, RelayerClient::new(url).terms().await) }),
+            )
+            .await;
+
+            let mut quotes = Vec::new();
+            for (url, result) in results {
+                match result {
+                    Ok(terms) => quotes.push(Quote { relayer_url: url.clone(), terms }),
+                    Err(e) => eprintln!("Skipping {}: {:#}", url, e),
+                }
+            }
+            let best = cheapest(&quotes).ok_or_else(|| anyhow!("no relayer answered"))?;
+
+            if json {
+                let output = serde_json::json!({ "quotes": quotes, "cheapest": best.relayer_url });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                return Ok(());
+            }
+            println!(
+                "{:<40}  {:>20}  {:>20}  {:>7}  {:>8}",
+                "RELAYER", "MIN FEE", "DENOMINATION", "PENDING", "ETA"
+            );
+            for quote in &quotes {
+                let eta = match quote.terms.estimated_confirmation_secs {
+                    0 => "-".to_string(),
+                    secs => format!("{} min", secs.div_ceil(60)),
+                };
+                println!(
+                    "{:<40}  {:>20}  {:>20}  {:>7}  {:>8}",
+                    quote.relayer_url, quote.terms.min_fee, quote.terms.denomination, quote.terms.pending_jobs, eta
+                );
+            }
+            println!("Cheapest: {}", best.relayer_url);
+        }
+        RelayCommand::Submit(args) => submit(args, network, profile, provider).await?,
+    }
+    Ok(())
+}
+
+/// Prove a withdrawal for a relayer, submit it and wait for the pool to
+/// accept it.
+async fn submit<P: DeezelProvider>(
+    args: SubmitArgs,
+    network: NetworkName,
+    profile: &NetworkProfile,
+    provider: Arc<P>,
+) -> Result<()> {
+    let client = RelayerClient::new(&args.relayer_url);
+    let terms = client.terms().await.context("failed to fetch the relayer's terms")?;
+    let fee = args.fee.unwrap_or(terms.min_fee);
+    if fee < terms.min_fee {
+        bail!("fee {} is below the relayer's minimum of {}", fee, terms.min_fee);
+    }
+
+    let recipient_script = Recipient::parse(&args.recipient, network.network())?;
+    let recipient = OutputDescriptor {
+        value: args.recipient_value,
+        script_pubkey: recipient_script.to_hex(),
+    };
+
+    let path = args.notes_file.map_or_else(|| profile.notes_path(), Ok)?;
+    let store = notes::open_store(path)?;
+    let stored = &store.notes()[store.find(&args.note)?];
+    let note = stored.note.clone();
+    if note.denomination != terms.denomination {
+        bail!(
+            "the note is worth {}, but the relayer serves the {} pool",
+            note.denomination,
+            terms.denomination
+        );
+    }
+
+    // The relayer reveals the Merkle path of the note's commitment to the pool
+    let pool_id = stored
+        .pool
+        .unwrap_or_else(|| derive_pool_id(&note.asset_id, note.denomination));
+    let (leaf_index, path) = PoolClient::new(provider, pool_id)
+        .merkle_path(&note.commitment)
+        .await?
+        .ok_or_else(|| anyhow!("the note hasn't been deposited to pool {}", pool_id))?;
+
+    let (relayer_output_hash, recipients_hash) = relay_hashes(&terms, &recipient)?;
+    let proof_bytes = prove::prove_note(
+        &args.proving_key,
+        &note,
+        &recipients_hash,
+        &relayer_output_hash,
+        fee,
+        args.not_after_height,
+    )
+    .await?;
+
+    let mut proof = WithdrawalProof::new(
+        proof_bytes,
+        decode_hash(&terms.merkle_root, "relayer Merkle root")?,
+        zkane_crypto::generate_nullifier_hash(&note.nullifier)?,
+        recipient_script,
+    )
+    .with_relayer(relayer_output_hash, fee)
+    .with_expiry(args.not_after_height);
+    proof.recipients_hash = recipients_hash;
+    // The relayer sets the outputs hash of the transaction it builds
+    let witness = WithdrawalWitness {
+        proof,
+        path,
+        leaf_index,
+        commitment: note.commitment,
+        outputs_hash: [0u8; 32],
+    };
+    let request = RelayRequest {
+        witness,
+        outputs: vec![recipient, terms.fee_output],
+    };
+
+    let job_id = client.relay(&request).await?;
+    eprintln!("Submitted as job {}, waiting for the broadcast...", job_id);
+
+    let deadline = Instant::now() + Duration::from_secs(args.timeout);
+    let mut broadcast = false;
+    loop {
+        match client.job(job_id).await? {
+            JobStatus::Confirmed { txid } => {
+                println!("{}", txid);
+                return Ok(());
+            }
+            JobStatus::Broadcast { txid } if !broadcast => {
+                eprintln!("Broadcast as {}, waiting for the pool to accept it...", txid);
+                broadcast = true;
+            }
+            JobStatus::Failed { reason } => bail!("relayer failed job {}: {}", job_id, reason),
+            JobStatus::Queued | JobStatus::Broadcasting | JobStatus::Broadcast { .. } => {}
+        }
+        if Instant::now() >= deadline {
+            bail!("job {} was not confirmed within {}s", job_id, args.timeout);
+        }
+        tokio::time::sleep(Duration::from_secs(args.poll_interval)).await;
+    }
+}
+
+/// Get the relayer output hash and recipients hash a relayed proof commits to.
+///
+/// The relayer's fee output must hash to the hash it advertises, so the proof
+/// pays the relayer that is asked to broadcast it.
+fn relay_hashes(terms: &RelayerTerms, recipient: &OutputDescriptor) -> Result<([u8; 32], [u8; 32])> {
+    let relayer_output_hash = decode_hash(&terms.fee_output_hash, "relayer fee output hash")?;
+    if calculate_outputs_hash(&[terms.fee_output.tx_out()?]) != relayer_output_hash {
+        bail!("the relayer's fee output doesn't match its advertised hash");
+    }
+    Ok((relayer_output_hash, calculate_outputs_hash(&[recipient.tx_out()?])))
+}
+
+/// Decode a 32-byte hex hash.
+fn decode_hash(hash: &str, name: &str) -> Result<[u8; 32]> {
+    hex::decode(hash.trim_start_matches("0x"))?
+        .try_into()
+        .map_err(|_| anyhow!("{} must be 32 bytes", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(min_fee: u128, pending_jobs: usize) -> RelayerTerms {
+        let fee_output = OutputDescriptor {
+            value: 546,
+            script_pubkey: "0014".to_string() + &"11".repeat(20),
+        };
+        RelayerTerms {
+            min_fee,
+            denomination: 100_000,
+            fee_output_hash: hex::encode(calculate_outputs_hash(&[fee_output.tx_out().unwrap()])),
+            fee_output,
+            merkle_root: "00".repeat(32),
+            pending_jobs,
+            estimated_confirmation_secs: 600,
+        }
+    }
+
+    #[test]
+    fn test_cheapest() {
+        let quote = |url: &str, min_fee, pending_jobs| Quote {
+            relayer_url: url.to_string(),
+            terms: terms(min_fee, pending_jobs),
+        };
+        let quotes = vec![quote("a", 300, 0), quote("b", 100, 4), quote("c", 100, 1)];
+        assert_eq!(cheapest(&quotes).unwrap().relayer_url, "c");
+        assert!(cheapest(&[]).is_none());
+    }
+
+    #[test]
+    fn test_relay_hashes() {
+        let recipient = OutputDescriptor {
+            value: 546,
+            script_pubkey: "0014".to_string() + &"22".repeat(20),
+        };
+        let terms = terms(100, 0);
+        let (relayer_output_hash, recipients_hash) = relay_hashes(&terms, &recipient).unwrap();
+        assert_eq!(hex::encode(relayer_output_hash), terms.fee_output_hash);
+        assert_eq!(recipients_hash, calculate_outputs_hash(&[recipient.tx_out().unwrap()]));
+
+        // A relayer advertising a hash its fee output doesn't have is refused
+        let forged = RelayerTerms { fee_output_hash: "33".repeat(32), ..terms };
+        assert!(relay_hashes(&forged, &recipient).is_err());
+    }
+
+    #[test]
+    fn test_job_status_json() {
+        let status: JobStatus = serde_json::from_str(r#"{"state":"broadcast","txid":"ab"}"#).unwrap();
+        assert_eq!(status, JobStatus::Broadcast { txid: "ab".to_string() });
+        let status: JobStatus = serde_json::from_str(r#"{"state":"confirmed","txid":"ab"}"#).unwrap();
+        assert_eq!(status, JobStatus::Confirmed { txid: "ab".to_string() });
+        let status: JobStatus = serde_json::from_str(r#"{"state":"queued"}"#).unwrap();
+        assert_eq!(status, JobStatus::Queued);
+    }
+}
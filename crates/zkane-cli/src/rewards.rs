@@ -0,0 +1,173 @@
+//! # Reward Claims
+//!
+//! `zkane-cli rewards status` shows the reward program of a stored note's
+//! pool and the points its withdrawal left to claim. `rewards claim` plans
+//! the two claim transactions: the registration, to confirm first, and the
+//! claim, to send in a later block with exactly the outputs it was planned
+//! for, as the registered hash binds them.
+
+use crate::config::{NetworkName, NetworkProfile};
+use crate::notes;
+use anyhow::{anyhow, Result};
+use bitcoin::{Amount, TxOut};
+use clap::Subcommand;
+use deezel_common::traits::DeezelProvider;
+use std::path::PathBuf;
+use std::sync::Arc;
+use zkane_common::{Recipient, ZkAssetId};
+use zkane_core::{plan_reward_claim, FactoryClient, PoolClient};
+
+/// Claim the reward points of withdrawn notes
+#[derive(Subcommand)]
+pub enum RewardsCommand {
+    /// Show a stored note's pool reward program and unclaimed points
+    Status {
+        /// Note number from `notes list`, or a commitment hex prefix
+        #[clap(long)]
+        note: String,
+        /// Encrypted note store (defaults to notes.enc in the network's data directory)
+        #[clap(long)]
+        notes_file: Option<PathBuf>,
+        /// Factory alkane ID (block:tx, defaults to the network profile's)
+        #[clap(long)]
+        factory: Option<ZkAssetId>,
+        /// Print JSON instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+    /// Plan the registration and claim transactions of a note's rewards
+    Claim {
+        /// Note number from `notes list`, or a commitment hex prefix
+        #[clap(long)]
+        note: String,
+        /// Encrypted note store (defaults to notes.enc in the network's data directory)
+        #[clap(long)]
+        notes_file: Option<PathBuf>,
+        /// Output of the claim transaction, as address:sats; the first is
+        /// paid the points. Include change, as the claim hash covers every
+        /// output
+        #[clap(long = "output", required = true)]
+        outputs: Vec<String>,
+        /// Print JSON instead of a table
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+/// Run a `rewards` subcommand.
+pub async fn run<P: DeezelProvider>(
+    command: RewardsCommand,
+    network: NetworkName,
+    profile: &NetworkProfile,
+    provider: Arc<P>,
+) -> Result<()> {
+    match command {
+        RewardsCommand::Status { note, notes_file, factory, json } => {
+            let path = notes_file.map_or_else(|| profile.notes_path(), Ok)?;
+            let store = notes::open_store(path)?;
+            let stored = &store.notes()[store.find(&note)?];
+            let pool_id = stored.pool_id();
+            let nullifier_hash = zkane_crypto::generate_nullifier_hash(&stored.note.nullifier)?;
+
+            let factory = factory.map_or_else(|| profile.factory(network), Ok)?;
+            let program = FactoryClient::new(provider.clone(), factory).reward_program(&pool_id).await?;
+            let pool = PoolClient::new(provider, pool_id);
+            let points = pool.reward_claim(&nullifier_hash).await?;
+            let balance = pool.reward_balance().await?;
+            let deposit_height = match stored.leaf_index {
+                Some(index) => pool.leaf_height(index).await?,
+                None => None,
+            };
+
+            if json {
+                let status = serde_json::json!({
+                    "pool_id": pool_id,
+                    "program": program,
+                    "deposit_height": deposit_height,
+                    "points": points.to_string(),
+                    "balance": balance.to_string(),
+                });
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            println!("Pool:           {}", pool_id);
+            match program {
+                Some(program) => {
+                    println!("Reward token:   {}", program.token);
+                    println!("Points/block:   {}", program.points_per_block);
+                    if program.max_blocks != 0 {
+                        println!("Max blocks:     {}", program.max_blocks);
+                    }
+                }
+                None => println!("Reward token:   none, the pool doesn't reward deposits"),
+            }
+            match deposit_height {
+                Some(height) => println!("Deposit height: {}", height),
+                None => println!("Deposit height: unknown"),
+            }
+            println!("Claimable:      {}", points);
+            println!("Pool balance:   {}", balance);
+        }
+        RewardsCommand::Claim { note, notes_file, outputs, json } => {
+            let path = notes_file.map_or_else(|| profile.notes_path(), Ok)?;
+            let store = notes::open_store(path)?;
+            let stored = &store.notes()[store.find(&note)?];
+            let outputs = outputs
+                .iter()
+                .map(|output| parse_output(output, network))
+                .collect::<Result<Vec<_>>>()?;
+            let plan = plan_reward_claim(stored.pool_id(), &stored.note.nullifier, outputs)?;
+
+            let pool = PoolClient::new(provider, plan.pool_id);
+            let points = pool.reward_claim(&zkane_crypto::generate_nullifier_hash(&stored.note.nullifier)?).await?;
+            if points == 0 {
+                eprintln!("Warning: the note has no reward points to claim");
+            }
+
+            if json {
+                let plan = serde_json::json!({
+                    "pool_id": plan.pool_id,
+                    "claim_hash": hex::encode(plan.claim_hash),
+                    "register_runestone": hex::encode(plan.register_runestone.as_bytes()),
+                    "claim_runestone": hex::encode(plan.claim_runestone.as_bytes()),
+                    "points": points.to_string(),
+                });
+                println!("{}", serde_json::to_string_pretty(&plan)?);
+                return Ok(());
+            }
+            println!("Pool:               {}", plan.pool_id);
+            println!("Claim hash:         {}", hex::encode(plan.claim_hash));
+            println!("Register runestone: {}", hex::encode(plan.register_runestone.as_bytes()));
+            println!("Claim runestone:    {}", hex::encode(plan.claim_runestone.as_bytes()));
+            println!("Points:             {}", points);
+            println!();
+            println!("Confirm the registration first, then send the claim with the given outputs, runestone last.");
+        }
+    }
+    Ok(())
+}
+
+/// Parse an `address:sats` output of the network.
+fn parse_output(value: &str, network: NetworkName) -> Result<TxOut> {
+    let (address, sats) = value
+        .trim()
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("'{}' is not address:sats", value))?;
+    let recipient = Recipient::parse(address, network.network())?;
+    Ok(recipient.output(Amount::from_sat(sats.parse()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        let output = parse_output("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080:546", NetworkName::Regtest).unwrap();
+        assert_eq!(output.value, Amount::from_sat(546));
+        assert_eq!(output.script_pubkey.len(), 22);
+
+        assert!(parse_output("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080", NetworkName::Regtest).is_err());
+        assert!(parse_output("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080:546", NetworkName::Mainnet).is_err());
+    }
+}
@@ -0,0 +1,767 @@
+//! # Binary Encoding
+//!
+//! Canonical binary encoding of withdrawal proofs and the witness envelope of
+//! withdrawal transactions. All integers are little-endian.
+//!
+//! A [`WithdrawalProof`] is encoded as:
+//!
+//! | Field | Size |
+//! |-------|------|
+//! | version | 1 |
+//! | circuit version | 4 |
+//! | proof length | 4 |
+//! | proof | proof length |
+//! | merkle_root | 32 |
+//! | nullifier_hash | 32 |
+//! | recipient length | 2 |
+//! | recipient scriptPubKey | recipient length |
+//! | relayer_output_hash | 32 |
+//! | fee | 16 |
+//! | recipients_hash | 32 |
+//! | not_after_height | 8 |
+//!
+//! Version 1 proofs have no circuit version field and decode as circuit
+//! version 1. Version 1 and 2 proofs have no recipients hash field and decode
+//! as not bound to a recipient set. Proofs before version 4 carry a 16-byte
+//! numeric recipient instead of a script, and decode as naming no recipient.
+//! Proofs before version 5 have no expiry field and decode as never
+//! expiring.
+//!
+//! A [`MerklePath`] is encoded compactly as its height (1 byte), the sibling
+//! hashes (32 bytes each) and the direction bits packed into
+//! `ceil(height / 8)` bytes, least significant bit first.
+//!
+//! A [`WithdrawalWitness`] is the encoded proof, followed by the encoded path,
+//! the leaf index (4 bytes), the commitment (32 bytes) and the outputs hash
+//! (32 bytes).
+//!
+//! A [`SplitWitness`] is the encoded withdrawal witness, followed by the
+//! public amount (16 bytes), the number of output commitments (1 byte) and
+//! the output commitments (32 bytes each).
+//!
+//! An [`AmountWitness`] is its version (1 byte), the commitment (32 bytes),
+//! the amount (16 bytes), the proof length (4 bytes) and the proof.
+//!
+//! The envelope of a withdrawal transaction holds the witness in one of the
+//! [`EnvelopeFormat`]s, told apart by the first byte: the binary encoding
+//! starts with the proof version, a compressed envelope with
+//! [`ENVELOPE_COMPRESSED_TAG`] followed by the deflated binary encoding, and a
+//! JSON envelope with `{`.
+
+use crate::{
+    Commitment, MerklePath, Recipient, CIRCUIT_VERSION, NullifierHash, WithdrawalProof, ZKaneError, ZKaneResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// Current version of the withdrawal proof encoding
+pub const WITHDRAWAL_PROOF_VERSION: u8 = 5;
+
+/// Maximum height of an encoded Merkle path
+pub const MAX_ENCODED_PATH_HEIGHT: usize = 32;
+
+/// First byte of a compressed witness envelope
+pub const ENVELOPE_COMPRESSED_TAG: u8 = 0xc0;
+
+/// First byte of a JSON witness envelope
+const ENVELOPE_JSON_TAG: u8 = b'{';
+
+/// Maximum size of a decompressed witness envelope
+pub const MAX_ENVELOPE_SIZE: usize = 64 * 1024;
+
+/// Number of fresh commitments a split withdrawal creates
+pub const SPLIT_OUTPUTS: usize = 2;
+
+/// Current version of the amount witness encoding
+pub const AMOUNT_WITNESS_VERSION: u8 = 1;
+
+/// Encoding of a withdrawal witness envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EnvelopeFormat {
+    /// The canonical binary encoding
+    #[default]
+    Binary,
+    /// The binary encoding compressed with deflate, unless that makes it larger
+    Compressed,
+    /// JSON, for debugging
+    Json,
+}
+
+/// Reads fixed-size fields from an encoded buffer.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> ZKaneResult<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(ZKaneError::InvalidProof(format!(
+                "unexpected end of data: needed {} more bytes, {} left",
+                len,
+                self.data.len()
+            )));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> ZKaneResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> ZKaneResult<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> ZKaneResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> ZKaneResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> ZKaneResult<u128> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn array32(&mut self) -> ZKaneResult<[u8; 32]> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn finish(self) -> ZKaneResult<()> {
+        if !self.data.is_empty() {
+            return Err(ZKaneError::InvalidProof(format!("{} trailing bytes", self.data.len())));
+        }
+        Ok(())
+    }
+}
+
+impl WithdrawalProof {
+    /// Encode the proof in the canonical binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let recipient_len = self.recipient.script_pubkey().len();
+        let mut data = Vec::with_capacity(1 + 4 + 4 + self.proof.len() + 32 * 4 + 2 + recipient_len + 16 + 8);
+        self.encode_into(&mut data);
+        data
+    }
+
+    /// Decode a proof from the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the version is unsupported or
+    /// the data is truncated or has trailing bytes.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = Reader::new(data);
+        let proof = Self::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(proof)
+    }
+
+    fn encode_into(&self, data: &mut Vec<u8>) {
+        data.push(WITHDRAWAL_PROOF_VERSION);
+        data.extend_from_slice(&self.circuit_version.to_le_bytes());
+        data.extend_from_slice(&(self.proof.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.proof);
+        data.extend_from_slice(&self.merkle_root);
+        data.extend_from_slice(self.nullifier_hash.as_bytes());
+        let recipient = self.recipient.script_pubkey().as_bytes();
+        data.extend_from_slice(&(recipient.len() as u16).to_le_bytes());
+        data.extend_from_slice(recipient);
+        data.extend_from_slice(&self.relayer_output_hash);
+        data.extend_from_slice(&self.fee.to_le_bytes());
+        data.extend_from_slice(&self.recipients_hash);
+        data.extend_from_slice(&self.not_after_height.to_le_bytes());
+    }
+
+    fn decode_from(reader: &mut Reader) -> ZKaneResult<Self> {
+        let version = reader.u8()?;
+        let circuit_version = match version {
+            1 => CIRCUIT_VERSION,
+            2..=WITHDRAWAL_PROOF_VERSION => reader.u32()?,
+            version => return Err(ZKaneError::InvalidProof(format!("unsupported proof version {}", version))),
+        };
+        let proof_len = reader.u32()? as usize;
+        let proof = reader.take(proof_len)?.to_vec();
+        let merkle_root = reader.array32()?;
+        let nullifier_hash = NullifierHash::new(reader.array32()?);
+        let recipient = if version >= 4 {
+            let len = reader.u16()? as usize;
+            Recipient::from_bytes(reader.take(len)?.to_vec()).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?
+        } else {
+            reader.u128()?;
+            Recipient::default()
+        };
+        Ok(Self {
+            proof,
+            merkle_root,
+            nullifier_hash,
+            recipient,
+            relayer_output_hash: reader.array32()?,
+            fee: reader.u128()?,
+            circuit_version,
+            recipients_hash: if version >= 3 { reader.array32()? } else { [0u8; 32] },
+            not_after_height: if version >= 5 { reader.u64()? } else { 0 },
+        })
+    }
+}
+
+impl MerklePath {
+    /// Encode the path in the compact binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is higher than [`MAX_ENCODED_PATH_HEIGHT`].
+    pub fn to_bytes(&self) -> ZKaneResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(1 + self.len() * 32 + self.len().div_ceil(8));
+        self.encode_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decode a path from the compact binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the data is malformed.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = Reader::new(data);
+        let path = Self::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(path)
+    }
+
+    fn encode_into(&self, data: &mut Vec<u8>) -> ZKaneResult<()> {
+        if self.len() > MAX_ENCODED_PATH_HEIGHT {
+            return Err(ZKaneError::InvalidProof(format!("path height {} is too large", self.len())));
+        }
+        data.push(self.len() as u8);
+        for element in &self.elements {
+            data.extend_from_slice(element);
+        }
+        let mut bits = vec![0u8; self.len().div_ceil(8)];
+        for (level, _) in self.indices.iter().enumerate().filter(|(_, &right)| right) {
+            bits[level / 8] |= 1 << (level % 8);
+        }
+        data.extend_from_slice(&bits);
+        Ok(())
+    }
+
+    fn decode_from(reader: &mut Reader) -> ZKaneResult<Self> {
+        let height = reader.u8()? as usize;
+        if height > MAX_ENCODED_PATH_HEIGHT {
+            return Err(ZKaneError::InvalidProof(format!("path height {} is too large", height)));
+        }
+        let elements = (0..height).map(|_| reader.array32()).collect::<ZKaneResult<Vec<_>>>()?;
+        let bits = reader.take(height.div_ceil(8))?;
+        let indices = (0..height).map(|level| bits[level / 8] & (1 << (level % 8)) != 0).collect();
+        Ok(Self { elements, indices })
+    }
+}
+
+/// The witness envelope of a withdrawal transaction.
+///
+/// Carries the proof together with the Merkle path and the public values the
+/// pool contract checks before accepting the withdrawal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalWitness {
+    /// The withdrawal proof and its public inputs
+    pub proof: WithdrawalProof,
+    /// Merkle path of the withdrawn commitment
+    pub path: MerklePath,
+    /// Leaf index of the withdrawn commitment
+    pub leaf_index: u32,
+    /// The withdrawn commitment
+    pub commitment: Commitment,
+    /// Hash of the withdrawal transaction outputs
+    pub outputs_hash: [u8; 32],
+}
+
+impl WithdrawalWitness {
+    /// Encode the witness in the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is higher than [`MAX_ENCODED_PATH_HEIGHT`].
+    pub fn to_bytes(&self) -> ZKaneResult<Vec<u8>> {
+        let mut data = Vec::new();
+        self.encode_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decode a witness from the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the data is malformed.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = Reader::new(data);
+        let witness = Self::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(witness)
+    }
+
+    fn encode_into(&self, data: &mut Vec<u8>) -> ZKaneResult<()> {
+        self.proof.encode_into(data);
+        self.path.encode_into(data)?;
+        data.extend_from_slice(&self.leaf_index.to_le_bytes());
+        data.extend_from_slice(self.commitment.as_bytes());
+        data.extend_from_slice(&self.outputs_hash);
+        Ok(())
+    }
+
+    fn decode_from(reader: &mut Reader) -> ZKaneResult<Self> {
+        Ok(Self {
+            proof: WithdrawalProof::decode_from(reader)?,
+            path: MerklePath::decode_from(reader)?,
+            leaf_index: reader.u32()?,
+            commitment: Commitment::new(reader.array32()?),
+            outputs_hash: reader.array32()?,
+        })
+    }
+
+    /// Encode the witness as an envelope payload.
+    ///
+    /// [`EnvelopeFormat::Compressed`] falls back to the binary encoding when
+    /// compression doesn't save any space, which is common as proofs and
+    /// hashes are close to random.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is higher than [`MAX_ENCODED_PATH_HEIGHT`].
+    pub fn to_envelope(&self, format: EnvelopeFormat) -> ZKaneResult<Vec<u8>> {
+        to_envelope(self, self.to_bytes()?, format)
+    }
+
+    /// Decode a witness from an envelope payload in any [`EnvelopeFormat`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the payload is malformed or
+    /// decompresses to more than [`MAX_ENVELOPE_SIZE`] bytes.
+    pub fn from_envelope(data: &[u8]) -> ZKaneResult<Self> {
+        from_envelope(data, Self::from_bytes)
+    }
+}
+
+/// The witness envelope of a split withdrawal.
+///
+/// A split withdrawal spends a note into a public amount, paid out like a
+/// withdrawal, and [`SPLIT_OUTPUTS`] fresh commitments inserted back into the
+/// pool. The values of the fresh notes stay private: the proof shows they add
+/// up to the spent note's value together with the public amount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitWitness {
+    /// The spent note's proof, path and outputs hash
+    pub withdrawal: WithdrawalWitness,
+    /// Amount paid out of the pool, including the relayer fee
+    pub public_amount: u128,
+    /// The fresh commitments, inserted in order
+    pub output_commitments: Vec<Commitment>,
+}
+
+impl SplitWitness {
+    /// Encode the witness in the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is higher than [`MAX_ENCODED_PATH_HEIGHT`]
+    /// or there are more than 255 output commitments.
+    pub fn to_bytes(&self) -> ZKaneResult<Vec<u8>> {
+        let count = u8::try_from(self.output_commitments.len())
+            .map_err(|_| ZKaneError::InvalidProof("too many output commitments".to_string()))?;
+        let mut data = Vec::new();
+        self.withdrawal.encode_into(&mut data)?;
+        data.extend_from_slice(&self.public_amount.to_le_bytes());
+        data.push(count);
+        for commitment in &self.output_commitments {
+            data.extend_from_slice(commitment.as_bytes());
+        }
+        Ok(data)
+    }
+
+    /// Decode a witness from the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the data is malformed.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = Reader::new(data);
+        let withdrawal = WithdrawalWitness::decode_from(&mut reader)?;
+        let public_amount = reader.u128()?;
+        let count = reader.u8()?;
+        let output_commitments = (0..count)
+            .map(|_| reader.array32().map(Commitment::new))
+            .collect::<ZKaneResult<Vec<_>>>()?;
+        reader.finish()?;
+        Ok(Self {
+            withdrawal,
+            public_amount,
+            output_commitments,
+        })
+    }
+
+    /// Encode the witness as an envelope payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness can't be encoded.
+    pub fn to_envelope(&self, format: EnvelopeFormat) -> ZKaneResult<Vec<u8>> {
+        to_envelope(self, self.to_bytes()?, format)
+    }
+
+    /// Decode a witness from an envelope payload in any [`EnvelopeFormat`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the payload is malformed.
+    pub fn from_envelope(data: &[u8]) -> ZKaneResult<Self> {
+        from_envelope(data, Self::from_bytes)
+    }
+}
+
+/// The witness envelope of a deposit into a variable-amount pool.
+///
+/// Notes of a variable-amount pool commit to their value, and the proof shows
+/// that the commitment opens to a note of the deposited amount, so a note is
+/// never worth more than what was paid in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AmountWitness {
+    /// The deposited commitment
+    pub commitment: Commitment,
+    /// Amount deposited, which the commitment's note is worth
+    pub amount: u128,
+    /// Proof that the commitment opens to a note of `amount`
+    pub proof: Vec<u8>,
+}
+
+impl AmountWitness {
+    /// Encode the witness in the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof is longer than `u32::MAX` bytes.
+    pub fn to_bytes(&self) -> ZKaneResult<Vec<u8>> {
+        let proof_len = u32::try_from(self.proof.len())
+            .map_err(|_| ZKaneError::InvalidProof("proof too long".to_string()))?;
+        let mut data = Vec::with_capacity(1 + 32 + 16 + 4 + self.proof.len());
+        data.push(AMOUNT_WITNESS_VERSION);
+        data.extend_from_slice(self.commitment.as_bytes());
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&proof_len.to_le_bytes());
+        data.extend_from_slice(&self.proof);
+        Ok(data)
+    }
+
+    /// Decode a witness from the canonical binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the data is malformed or has
+    /// an unknown version.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = Reader::new(data);
+        let version = reader.u8()?;
+        if version != AMOUNT_WITNESS_VERSION {
+            return Err(ZKaneError::InvalidProof(format!("unsupported amount witness version {}", version)));
+        }
+        let commitment = Commitment::new(reader.array32()?);
+        let amount = reader.u128()?;
+        let proof_len = reader.u32()? as usize;
+        let proof = reader.take(proof_len)?.to_vec();
+        reader.finish()?;
+        Ok(Self { commitment, amount, proof })
+    }
+
+    /// Encode the witness as an envelope payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness can't be encoded.
+    pub fn to_envelope(&self, format: EnvelopeFormat) -> ZKaneResult<Vec<u8>> {
+        to_envelope(self, self.to_bytes()?, format)
+    }
+
+    /// Decode a witness from an envelope payload in any [`EnvelopeFormat`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the payload is malformed.
+    pub fn from_envelope(data: &[u8]) -> ZKaneResult<Self> {
+        from_envelope(data, Self::from_bytes)
+    }
+}
+
+/// Wrap the binary encoding of a witness in an envelope format.
+///
+/// [`EnvelopeFormat::Compressed`] falls back to the binary encoding when
+/// compression doesn't save any space.
+fn to_envelope<T: Serialize>(witness: &T, bytes: Vec<u8>, format: EnvelopeFormat) -> ZKaneResult<Vec<u8>> {
+    match format {
+        EnvelopeFormat::Binary => Ok(bytes),
+        EnvelopeFormat::Compressed => {
+            let mut compressed = vec![ENVELOPE_COMPRESSED_TAG];
+            compressed.extend(miniz_oxide::deflate::compress_to_vec(&bytes, 10));
+            Ok(if compressed.len() < bytes.len() { compressed } else { bytes })
+        }
+        EnvelopeFormat::Json => serde_json::to_vec(witness).map_err(|e| ZKaneError::InvalidProof(e.to_string())),
+    }
+}
+
+/// Decode an envelope payload in any [`EnvelopeFormat`].
+fn from_envelope<T: for<'de> Deserialize<'de>>(
+    data: &[u8],
+    from_bytes: impl Fn(&[u8]) -> ZKaneResult<T>,
+) -> ZKaneResult<T> {
+    match data.first() {
+        Some(&ENVELOPE_COMPRESSED_TAG) => {
+            let bytes = miniz_oxide::inflate::decompress_to_vec_with_limit(&data[1..], MAX_ENVELOPE_SIZE)
+                .map_err(|e| ZKaneError::InvalidProof(format!("invalid compressed envelope: {}", e)))?;
+            from_bytes(&bytes)
+        }
+        Some(&ENVELOPE_JSON_TAG) => {
+            serde_json::from_slice(data).map_err(|e| ZKaneError::InvalidProof(format!("invalid JSON envelope: {}", e)))
+        }
+        _ => from_bytes(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    fn sample_proof() -> WithdrawalProof {
+        let recipient = Recipient::new(bitcoin::ScriptBuf::from_bytes(vec![0x51]));
+        WithdrawalProof::new(vec![9u8; 5], [1u8; 32], NullifierHash::new([2u8; 32]), recipient).with_relayer([3u8; 32], 1000)
+    }
+
+    /// Encode a proof in the layout of an earlier version, with a numeric recipient
+    fn legacy_bytes(proof: &WithdrawalProof, version: u8) -> Vec<u8> {
+        let mut data = vec![version];
+        if version >= 2 {
+            data.extend_from_slice(&proof.circuit_version.to_le_bytes());
+        }
+        data.extend_from_slice(&(proof.proof.len() as u32).to_le_bytes());
+        data.extend_from_slice(&proof.proof);
+        data.extend_from_slice(&proof.merkle_root);
+        data.extend_from_slice(proof.nullifier_hash.as_bytes());
+        data.extend_from_slice(&77u128.to_le_bytes());
+        data.extend_from_slice(&proof.relayer_output_hash);
+        data.extend_from_slice(&proof.fee.to_le_bytes());
+        if version >= 3 {
+            data.extend_from_slice(&proof.recipients_hash);
+        }
+        data
+    }
+
+    #[test]
+    fn test_withdrawal_proof_roundtrip() {
+        let proof = sample_proof();
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 1 + 4 + 4 + 5 + 32 * 4 + 2 + 1 + 16 + 8);
+        assert_eq!(bytes[0], WITHDRAWAL_PROOF_VERSION);
+
+        let decoded = WithdrawalProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.proof, proof.proof);
+        assert_eq!(decoded.merkle_root, proof.merkle_root);
+        assert_eq!(decoded.nullifier_hash, proof.nullifier_hash);
+        assert_eq!(decoded.recipient, proof.recipient);
+        assert_eq!(decoded.relayer_output_hash, [3u8; 32]);
+        assert_eq!(decoded.fee, 1000);
+        assert_eq!(decoded.circuit_version, CIRCUIT_VERSION);
+
+        // Truncated, trailing and unknown-version data are rejected
+        assert!(WithdrawalProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(WithdrawalProof::from_bytes(&trailing).is_err());
+        let mut future = bytes;
+        future[0] = WITHDRAWAL_PROOF_VERSION + 1;
+        assert!(WithdrawalProof::from_bytes(&future).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_proof_circuit_version() {
+        let proof = sample_proof().with_circuit_version(7);
+        assert_eq!(WithdrawalProof::from_bytes(&proof.to_bytes()).unwrap().circuit_version, 7);
+
+        // Version 1 proofs predate circuit versioning
+        let decoded = WithdrawalProof::from_bytes(&legacy_bytes(&proof, 1)).unwrap();
+        assert_eq!(decoded.circuit_version, CIRCUIT_VERSION);
+        assert_eq!(decoded.fee, 1000);
+    }
+
+    #[test]
+    fn test_withdrawal_proof_recipients_hash() {
+        use bitcoin::{Amount, ScriptBuf, TxOut};
+
+        let merchant = TxOut { value: Amount::from_sat(546), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) };
+        let change = TxOut { value: Amount::from_sat(600), script_pubkey: ScriptBuf::from_bytes(vec![0x52]) };
+        let proof = sample_proof().with_recipients(&[merchant.clone(), change.clone()]);
+        let decoded = WithdrawalProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(decoded.recipients_hash, proof.recipients_hash);
+        assert!(decoded.pays_recipients(&[merchant.clone(), change.clone()]));
+        assert!(!decoded.pays_recipients(&[change.clone(), merchant.clone()]));
+        assert!(!decoded.pays_recipients(std::slice::from_ref(&merchant)));
+
+        // Version 2 proofs aren't bound to a recipient set
+        let decoded = WithdrawalProof::from_bytes(&legacy_bytes(&proof, 2)).unwrap();
+        assert!(!decoded.has_recipients());
+        assert!(decoded.pays_recipients(std::slice::from_ref(&change)));
+
+        // Version 3 proofs are, but name no recipient script
+        let decoded = WithdrawalProof::from_bytes(&legacy_bytes(&proof, 3)).unwrap();
+        assert_eq!(decoded.recipients_hash, proof.recipients_hash);
+        assert!(decoded.recipient.is_empty());
+        assert!(decoded.pays_recipients(&[merchant.clone(), change.clone()]));
+        assert!(!decoded.pays_recipients(&[change]));
+    }
+
+    #[test]
+    fn test_withdrawal_proof_expiry() {
+        let proof = sample_proof().with_expiry(850_000);
+        let bytes = proof.to_bytes();
+        assert_eq!(WithdrawalProof::from_bytes(&bytes).unwrap().not_after_height, 850_000);
+
+        // Version 4 proofs never expire
+        let mut legacy = bytes[..bytes.len() - 8].to_vec();
+        legacy[0] = 4;
+        let decoded = WithdrawalProof::from_bytes(&legacy).unwrap();
+        assert!(!decoded.expires());
+        assert_eq!(decoded.recipient, proof.recipient);
+    }
+
+    #[test]
+    fn test_merkle_path_compact_encoding() {
+        let indices = vec![true, false, false, true, false, false, false, false, true];
+        let path = MerklePath::new((0..9).map(|i| [i as u8; 32]).collect(), indices.clone()).unwrap();
+        let bytes = path.to_bytes().unwrap();
+        // Nine direction bits fit in two bytes
+        assert_eq!(bytes.len(), 1 + 9 * 32 + 2);
+        assert_eq!(&bytes[bytes.len() - 2..], &[0b0000_1001, 0b0000_0001]);
+
+        let decoded = MerklePath::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.elements, path.elements);
+        assert_eq!(decoded.indices, indices);
+
+        let too_high = MerklePath::new(vec![[0u8; 32]; 33], vec![false; 33]).unwrap();
+        assert!(too_high.to_bytes().is_err());
+    }
+
+    fn sample_witness() -> WithdrawalWitness {
+        WithdrawalWitness {
+            proof: sample_proof(),
+            path: MerklePath::new(vec![[4u8; 32]; 3], vec![false, true, true]).unwrap(),
+            leaf_index: 6,
+            commitment: Commitment::new([5u8; 32]),
+            outputs_hash: [6u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_witness_envelope_formats() {
+        let witness = sample_witness();
+        let binary = witness.to_envelope(EnvelopeFormat::Binary).unwrap();
+        assert_eq!(binary, witness.to_bytes().unwrap());
+
+        // The repeated bytes of the sample compress well
+        let compressed = witness.to_envelope(EnvelopeFormat::Compressed).unwrap();
+        assert_eq!(compressed[0], ENVELOPE_COMPRESSED_TAG);
+        assert!(compressed.len() < binary.len());
+
+        let json = witness.to_envelope(EnvelopeFormat::Json).unwrap();
+        assert_eq!(json[0], b'{');
+
+        for envelope in [&binary, &compressed, &json] {
+            let decoded = WithdrawalWitness::from_envelope(envelope).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), binary);
+        }
+
+        assert!(WithdrawalWitness::from_envelope(&[ENVELOPE_COMPRESSED_TAG, 0xff]).is_err());
+        assert!(WithdrawalWitness::from_envelope(b"{}").is_err());
+    }
+
+    #[test]
+    fn test_compressed_envelope_falls_back_to_binary() {
+        let mut witness = sample_witness();
+        witness.proof.proof = (0..192u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        witness.path.elements = (0..3u8).map(|i| sha2::Sha256::digest([i]).into()).collect();
+        witness.commitment = Commitment::new(sha2::Sha256::digest(b"commitment").into());
+        witness.outputs_hash = sha2::Sha256::digest(b"outputs").into();
+        witness.proof.merkle_root = sha2::Sha256::digest(b"root").into();
+        witness.proof.nullifier_hash = NullifierHash::new(sha2::Sha256::digest(b"nullifier").into());
+        witness.proof.relayer_output_hash = sha2::Sha256::digest(b"relayer").into();
+
+        let envelope = witness.to_envelope(EnvelopeFormat::Compressed).unwrap();
+        assert!(envelope.len() <= witness.to_bytes().unwrap().len());
+        assert_eq!(WithdrawalWitness::from_envelope(&envelope).unwrap().to_bytes().unwrap(), witness.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_compressed_envelope_size_limit() {
+        let mut bomb = vec![ENVELOPE_COMPRESSED_TAG];
+        bomb.extend(miniz_oxide::deflate::compress_to_vec(&vec![0u8; MAX_ENVELOPE_SIZE + 1], 10));
+        assert!(WithdrawalWitness::from_envelope(&bomb).is_err());
+    }
+
+    #[test]
+    fn test_split_witness_roundtrip() {
+        let witness = SplitWitness {
+            withdrawal: sample_witness(),
+            public_amount: 400,
+            output_commitments: vec![Commitment::new([7u8; 32]), Commitment::new([8u8; 32])],
+        };
+        let bytes = witness.to_bytes().unwrap();
+        assert_eq!(bytes.len(), sample_witness().to_bytes().unwrap().len() + 16 + 1 + 2 * 32);
+
+        for format in [EnvelopeFormat::Binary, EnvelopeFormat::Compressed, EnvelopeFormat::Json] {
+            let decoded = SplitWitness::from_envelope(&witness.to_envelope(format).unwrap()).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        }
+        assert_eq!(SplitWitness::from_bytes(&bytes).unwrap().public_amount, 400);
+        assert!(SplitWitness::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // A plain withdrawal witness is not a split witness
+        assert!(SplitWitness::from_bytes(&sample_witness().to_bytes().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_amount_witness_roundtrip() {
+        let witness = AmountWitness { commitment: Commitment::new([7u8; 32]), amount: 12_345, proof: vec![9u8; 40] };
+        let bytes = witness.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 1 + 32 + 16 + 4 + 40);
+        assert_eq!(bytes[0], AMOUNT_WITNESS_VERSION);
+
+        for format in [EnvelopeFormat::Binary, EnvelopeFormat::Compressed, EnvelopeFormat::Json] {
+            assert_eq!(AmountWitness::from_envelope(&witness.to_envelope(format).unwrap()).unwrap(), witness);
+        }
+        assert!(AmountWitness::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut future = bytes;
+        future[0] = AMOUNT_WITNESS_VERSION + 1;
+        assert!(AmountWitness::from_bytes(&future).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_witness_roundtrip() {
+        let witness = WithdrawalWitness {
+            proof: sample_proof(),
+            path: MerklePath::new(vec![[4u8; 32]; 3], vec![false, true, true]).unwrap(),
+            leaf_index: 6,
+            commitment: Commitment::new([5u8; 32]),
+            outputs_hash: [6u8; 32],
+        };
+        let bytes = witness.to_bytes().unwrap();
+        let decoded = WithdrawalWitness::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.proof.to_bytes(), witness.proof.to_bytes());
+        assert_eq!(decoded.path.indices, vec![false, true, true]);
+        assert_eq!(decoded.leaf_index, 6);
+        assert_eq!(decoded.commitment, witness.commitment);
+        assert_eq!(decoded.outputs_hash, [6u8; 32]);
+        assert!(WithdrawalWitness::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! # Denominations
+//!
+//! Pools hold a fixed amount of their asset in base units, a raw `u128`. A
+//! [`DenominationSpec`] adds what is needed to show it to people: the
+//! number of decimals of the asset and its display symbol, so a pool of
+//! 100000000 base units of an asset with 8 decimals reads `1.0 ZKN`.
+//!
+//! The factory keeps a registry of the specs of each asset, set by its
+//! admin. Frontends and the CLI format amounts with them, and
+//! [`DenominationSpec::find`] checks that an amount typed by a user is
+//! exactly one of the registered denominations, rather than rounding it to
+//! the nearest pool.
+//!
+//! ```rust
+//! use zkane_common::DenominationSpec;
+//!
+//! let spec = DenominationSpec::new(100_000_000, 8, "ZKN")?;
+//! assert_eq!(spec.to_string(), "1.0 ZKN");
+//! assert_eq!(spec.parse_amount("1 ZKN")?, 100_000_000);
+//! assert!(DenominationSpec::find(&[spec], "1.5").is_err());
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{ZKaneError, ZKaneResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Most decimals of an asset, keeping every amount below `u128::MAX`
+pub const MAX_DECIMALS: u8 = 18;
+
+/// Most bytes of a display symbol, so it packs into a `u128` opcode input
+pub const MAX_SYMBOL_LEN: usize = 16;
+
+/// A pool denomination with the units to display it in.
+///
+/// Returned by the factory's `GetDenominationSpecs` opcode in a fixed-size
+/// little-endian layout:
+///
+/// | Field | Size |
+/// |-------|------|
+/// | base_units | 16 |
+/// | decimals | 1 |
+/// | display_symbol | 16, zero padded |
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DenominationSpec {
+    /// The denomination in base units of the asset
+    pub base_units: u128,
+    /// Decimals of the asset: base units in one displayed unit, as a power of ten
+    pub decimals: u8,
+    /// Symbol shown after amounts, e.g. `ZKN`
+    pub display_symbol: String,
+}
+
+impl DenominationSpec {
+    /// Size of an encoded spec in bytes
+    pub const SIZE: usize = 16 + 1 + MAX_SYMBOL_LEN;
+
+    /// Create a spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidDenomination`] if the denomination is
+    /// zero, and [`ZKaneError::InvalidAmount`] if there are more than
+    /// [`MAX_DECIMALS`] decimals or the symbol isn't 1 to
+    /// [`MAX_SYMBOL_LEN`] ASCII letters and digits.
+    pub fn new(base_units: u128, decimals: u8, display_symbol: &str) -> ZKaneResult<Self> {
+        if base_units == 0 {
+            return Err(ZKaneError::InvalidDenomination);
+        }
+        if decimals > MAX_DECIMALS {
+            return Err(ZKaneError::InvalidAmount(format!("{} decimals, at most {} allowed", decimals, MAX_DECIMALS)));
+        }
+        let valid_symbol = !display_symbol.is_empty()
+            && display_symbol.len() <= MAX_SYMBOL_LEN
+            && display_symbol.bytes().all(|b| b.is_ascii_alphanumeric());
+        if !valid_symbol {
+            return Err(ZKaneError::InvalidAmount(format!("invalid display symbol {:?}", display_symbol)));
+        }
+        Ok(Self { base_units, decimals, display_symbol: display_symbol.to_string() })
+    }
+
+    /// Create a spec from the symbol packed into a `u128`, as passed to the
+    /// factory's `SetDenominationSpec` opcode.
+    pub fn from_packed(base_units: u128, decimals: u8, packed_symbol: u128) -> ZKaneResult<Self> {
+        Self::new(base_units, decimals, &unpack_symbol(&packed_symbol.to_le_bytes()))
+    }
+
+    /// Pack the display symbol into a `u128`, its bytes in little-endian order.
+    pub fn packed_symbol(&self) -> u128 {
+        let mut bytes = [0u8; MAX_SYMBOL_LEN];
+        bytes[..self.display_symbol.len()].copy_from_slice(self.display_symbol.as_bytes());
+        u128::from_le_bytes(bytes)
+    }
+
+    /// Format an amount of the asset in displayed units, e.g. `0.5 ZKN`.
+    pub fn format_amount(&self, amount: u128) -> String {
+        format!("{} {}", format_units(amount, self.decimals), self.display_symbol)
+    }
+
+    /// Parse an amount in displayed units into base units.
+    ///
+    /// The symbol may follow the number, e.g. `1.5 ZKN` or `1.5`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidAmount`] if the amount is malformed,
+    /// names another symbol or is more precise than the asset's decimals.
+    pub fn parse_amount(&self, input: &str) -> ZKaneResult<u128> {
+        let input = input.trim();
+        let number = match input.split_once(char::is_whitespace) {
+            Some((number, symbol)) if symbol.trim().eq_ignore_ascii_case(&self.display_symbol) => number,
+            Some(_) => {
+                let message = format!("{:?} is not an amount of {}", input, self.display_symbol);
+                return Err(ZKaneError::InvalidAmount(message));
+            }
+            None => input,
+        };
+        parse_units(number, self.decimals)
+    }
+
+    /// Find the denomination an amount typed by a user stands for.
+    ///
+    /// The amount must equal one of the denominations exactly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidAmount`] if the amount doesn't parse
+    /// with any of the specs, and [`ZKaneError::InvalidDenomination`] if it
+    /// matches no denomination.
+    pub fn find<'a>(specs: &'a [Self], input: &str) -> ZKaneResult<&'a Self> {
+        let mut parse_error = None;
+        let mut parsed = false;
+        for spec in specs {
+            match spec.parse_amount(input) {
+                Ok(amount) if amount == spec.base_units => return Ok(spec),
+                Ok(_) => parsed = true,
+                Err(e) => parse_error = Some(e),
+            }
+        }
+        match parse_error {
+            Some(e) if !parsed => Err(e),
+            _ => Err(ZKaneError::InvalidDenomination),
+        }
+    }
+
+    /// Encode the spec.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&self.base_units.to_le_bytes());
+        data.push(self.decimals);
+        data.extend_from_slice(&self.packed_symbol().to_le_bytes());
+        data
+    }
+
+    /// Decode a spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the data is not
+    /// exactly [`DenominationSpec::SIZE`] bytes, or the error of
+    /// [`DenominationSpec::new`] if the spec is invalid.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        if data.len() != Self::SIZE {
+            return Err(ZKaneError::SerializationError(format!(
+                "invalid denomination spec length: expected {} bytes, got {}",
+                Self::SIZE,
+                data.len()
+            )));
+        }
+        let base_units = u128::from_le_bytes(data[..16].try_into().unwrap());
+        Self::new(base_units, data[16], &unpack_symbol(&data[17..]))
+    }
+
+    /// Decode a `GetDenominationSpecs` response, the specs of an asset one
+    /// after another.
+    pub fn parse_list(data: &[u8]) -> ZKaneResult<Vec<Self>> {
+        let specs = data.chunks_exact(Self::SIZE);
+        if !specs.remainder().is_empty() {
+            return Err(ZKaneError::SerializationError(format!(
+                "denomination spec list of {} bytes",
+                data.len()
+            )));
+        }
+        specs.map(Self::from_bytes).collect()
+    }
+}
+
+impl fmt::Display for DenominationSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format_amount(self.base_units))
+    }
+}
+
+/// Read a zero-padded symbol.
+fn unpack_symbol(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// Format base units as displayed units, with at least one decimal digit
+/// when the asset has decimals, e.g. `1.0` or `0.0005`.
+pub fn format_units(amount: u128, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let scale = 10u128.pow(decimals as u32);
+    let fraction = format!("{:0width$}", amount % scale, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    format!("{}.{}", amount / scale, if fraction.is_empty() { "0" } else { fraction })
+}
+
+/// Parse displayed units into base units, without rounding.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidAmount`] if the input isn't a plain decimal
+/// number, has more significant decimal digits than `decimals`, or
+/// overflows a `u128`.
+pub fn parse_units(input: &str, decimals: u8) -> ZKaneResult<u128> {
+    let invalid = || ZKaneError::InvalidAmount(format!("{:?} is not an amount", input));
+    let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    if !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals as usize {
+        return Err(ZKaneError::InvalidAmount(format!("{:?} has more than {} decimals", input, decimals)));
+    }
+
+    let overflow = || ZKaneError::InvalidAmount(format!("{:?} is too large", input));
+    let digits = |digits: &str| -> ZKaneResult<u128> {
+        if digits.is_empty() {
+            return Ok(0);
+        }
+        digits.parse().map_err(|_| overflow())
+    };
+    let fraction_units = digits(fraction)? * 10u128.pow((decimals as usize - fraction.len()) as u32);
+    digits(whole)?
+        .checked_mul(10u128.pow(decimals as u32))
+        .and_then(|units| units.checked_add(fraction_units))
+        .ok_or_else(overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse_units() {
+        assert_eq!(format_units(100_000_000, 8), "1.0");
+        assert_eq!(format_units(50_000, 8), "0.0005");
+        assert_eq!(format_units(123_456_789, 8), "1.23456789");
+        assert_eq!(format_units(42, 0), "42");
+
+        assert_eq!(parse_units("1", 8).unwrap(), 100_000_000);
+        assert_eq!(parse_units("0.0005", 8).unwrap(), 50_000);
+        assert_eq!(parse_units(".5", 1).unwrap(), 5);
+        assert_eq!(parse_units("1.50000000000", 2).unwrap(), 150);
+        assert!(matches!(parse_units("0.000000001", 8), Err(ZKaneError::InvalidAmount(_))));
+        for malformed in ["", ".", "1,5", "-1", "1e8", "1.2.3", " 1"] {
+            assert!(parse_units(malformed, 8).is_err(), "{:?} parsed", malformed);
+        }
+        assert!(parse_units(&u128::MAX.to_string(), 1).is_err());
+    }
+
+    #[test]
+    fn test_denomination_spec() {
+        let spec = DenominationSpec::new(100_000_000, 8, "ZKN").unwrap();
+        assert_eq!(spec.to_string(), "1.0 ZKN");
+        assert_eq!(spec.format_amount(25_000_000), "0.25 ZKN");
+        assert_eq!(spec.parse_amount("1.0 zkn").unwrap(), 100_000_000);
+        assert!(spec.parse_amount("1.0 BTC").is_err());
+
+        assert_eq!(DenominationSpec::from_bytes(&spec.to_bytes()).unwrap(), spec);
+        assert_eq!(DenominationSpec::from_packed(100_000_000, 8, spec.packed_symbol()).unwrap(), spec);
+        assert!(DenominationSpec::from_bytes(&spec.to_bytes()[1..]).is_err());
+
+        assert!(matches!(DenominationSpec::new(0, 8, "ZKN"), Err(ZKaneError::InvalidDenomination)));
+        assert!(DenominationSpec::new(1, 19, "ZKN").is_err());
+        assert!(DenominationSpec::new(1, 8, "").is_err());
+        assert!(DenominationSpec::new(1, 8, "TOO-LONG-SYMBOL-NAME").is_err());
+    }
+
+    #[test]
+    fn test_find_denomination() {
+        let specs = vec![
+            DenominationSpec::new(10_000_000, 8, "ZKN").unwrap(),
+            DenominationSpec::new(100_000_000, 8, "ZKN").unwrap(),
+        ];
+        let mut data = Vec::new();
+        specs.iter().for_each(|spec| data.extend_from_slice(&spec.to_bytes()));
+        assert_eq!(DenominationSpec::parse_list(&data).unwrap(), specs);
+
+        assert_eq!(DenominationSpec::find(&specs, "0.1").unwrap().base_units, 10_000_000);
+        assert_eq!(DenominationSpec::find(&specs, "1 ZKN").unwrap().base_units, 100_000_000);
+        // Amounts between pools are rejected instead of rounded
+        assert!(matches!(DenominationSpec::find(&specs, "0.5"), Err(ZKaneError::InvalidDenomination)));
+        assert!(matches!(DenominationSpec::find(&specs[..1], "0.1.1"), Err(ZKaneError::InvalidAmount(_))));
+    }
+}
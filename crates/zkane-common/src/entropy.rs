@@ -0,0 +1,211 @@
+//! # Entropy Sources
+//!
+//! Secrets and nullifiers are only as private as the randomness they are
+//! drawn from. Rather than trusting whatever backend `rand` picks for the
+//! target, ZKane draws them from an explicit [`EntropySource`]:
+//!
+//! - [`OsEntropy`] reads the operating system's generator, natively
+//! - [`WebCryptoEntropy`] calls `crypto.getRandomValues`, on `wasm32`
+//!
+//! [`SystemEntropy`] is the one for the current target. [`check_entropy`]
+//! self-tests it once and fails with [`ZKaneError::EntropyUnavailable`]
+//! instead of letting a missing or broken source hand out predictable notes,
+//! so binaries call it at startup.
+//!
+//! ```rust
+//! use zkane_common::{check_entropy, EntropyRng, Secret};
+//!
+//! check_entropy()?;
+//! let secret = Secret::random_with_rng(&mut EntropyRng::system());
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{ZKaneError, ZKaneResult};
+use std::sync::OnceLock;
+
+/// Bytes drawn per sample by the self-test
+const SELF_TEST_SAMPLE_LEN: usize = 32;
+
+/// A cryptographically secure source of random bytes.
+pub trait EntropySource {
+    /// Fill `dest` with random bytes.
+    fn fill(&self, dest: &mut [u8]) -> ZKaneResult<()>;
+}
+
+/// The operating system's secure random number generator.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsEntropy;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl EntropySource for OsEntropy {
+    fn fill(&self, dest: &mut [u8]) -> ZKaneResult<()> {
+        use rand::RngCore;
+        rand::rngs::OsRng
+            .try_fill_bytes(dest)
+            .map_err(|e| ZKaneError::EntropyUnavailable(e.to_string()))
+    }
+}
+
+/// The Web Crypto API's `crypto.getRandomValues`.
+///
+/// Provided through getrandom's `js` backend, which zkane-common enables on
+/// `wasm32`; without it getrandom fails to build rather than at runtime.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebCryptoEntropy;
+
+#[cfg(target_arch = "wasm32")]
+impl EntropySource for WebCryptoEntropy {
+    fn fill(&self, dest: &mut [u8]) -> ZKaneResult<()> {
+        getrandom::getrandom(dest).map_err(|e| ZKaneError::EntropyUnavailable(e.to_string()))
+    }
+}
+
+/// The secure entropy source of the current target.
+#[cfg(not(target_arch = "wasm32"))]
+pub type SystemEntropy = OsEntropy;
+
+/// The secure entropy source of the current target.
+#[cfg(target_arch = "wasm32")]
+pub type SystemEntropy = WebCryptoEntropy;
+
+/// Check that a source produces plausibly random bytes.
+///
+/// Two samples are drawn; the test fails if drawing fails, if a sample is a
+/// single repeated byte, or if both samples are equal. This catches missing
+/// and stuck sources, not weak ones.
+pub fn self_test<S: EntropySource + ?Sized>(source: &S) -> ZKaneResult<()> {
+    let mut first = [0u8; SELF_TEST_SAMPLE_LEN];
+    let mut second = [0u8; SELF_TEST_SAMPLE_LEN];
+    source.fill(&mut first)?;
+    source.fill(&mut second)?;
+
+    if [&first, &second].iter().any(|sample| sample.iter().all(|byte| *byte == sample[0])) {
+        return Err(ZKaneError::EntropyUnavailable("source returned a constant sample".to_string()));
+    }
+    if first == second {
+        return Err(ZKaneError::EntropyUnavailable("source repeated a sample".to_string()));
+    }
+    Ok(())
+}
+
+/// Self-test the [`SystemEntropy`] source.
+///
+/// The test runs on the first call only; later calls return its result.
+pub fn check_entropy() -> ZKaneResult<()> {
+    static RESULT: OnceLock<Result<(), String>> = OnceLock::new();
+    RESULT
+        .get_or_init(|| self_test(&SystemEntropy::default()).map_err(|e| e.to_string()))
+        .clone()
+        .map_err(ZKaneError::EntropyUnavailable)
+}
+
+/// A `rand` generator reading straight from an [`EntropySource`].
+///
+/// `fill_bytes` panics if the source fails, as `rand` gives it no way to
+/// report errors; run [`check_entropy`] first to fail with an error instead.
+#[derive(Debug, Clone, Default)]
+pub struct EntropyRng<S: EntropySource = SystemEntropy>(pub S);
+
+impl EntropyRng {
+    /// A generator reading from the [`SystemEntropy`] source.
+    pub fn system() -> Self {
+        Self(SystemEntropy::default())
+    }
+}
+
+impl<S: EntropySource> rand::RngCore for EntropyRng<S> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(e) = self.0.fill(dest) {
+            panic!("{}", e);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill(dest).map_err(|e| rand::Error::new(e.to_string()))
+    }
+}
+
+impl<S: EntropySource> rand::CryptoRng for EntropyRng<S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use std::cell::Cell;
+
+    /// A source returning the same bytes on every draw
+    struct Stuck(u8);
+
+    impl EntropySource for Stuck {
+        fn fill(&self, dest: &mut [u8]) -> ZKaneResult<()> {
+            dest.fill(self.0);
+            Ok(())
+        }
+    }
+
+    /// A source replaying one non-constant sample
+    struct Replay;
+
+    impl EntropySource for Replay {
+        fn fill(&self, dest: &mut [u8]) -> ZKaneResult<()> {
+            for (i, byte) in dest.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            Ok(())
+        }
+    }
+
+    /// A source that fails after a number of draws
+    struct Failing(Cell<u32>);
+
+    impl EntropySource for Failing {
+        fn fill(&self, _dest: &mut [u8]) -> ZKaneResult<()> {
+            match self.0.get() {
+                0 => Err(ZKaneError::EntropyUnavailable("no backend".to_string())),
+                left => {
+                    self.0.set(left - 1);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_system_entropy() {
+        self_test(&SystemEntropy::default()).unwrap();
+        check_entropy().unwrap();
+
+        let mut rng = EntropyRng::system();
+        assert_ne!(rng.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn test_self_test_rejects_bad_sources() {
+        assert!(matches!(self_test(&Stuck(0)), Err(ZKaneError::EntropyUnavailable(_))));
+        assert!(matches!(self_test(&Stuck(0xa5)), Err(ZKaneError::EntropyUnavailable(_))));
+        assert!(matches!(self_test(&Replay), Err(ZKaneError::EntropyUnavailable(_))));
+        assert!(matches!(self_test(&Failing(Cell::new(1))), Err(ZKaneError::EntropyUnavailable(_))));
+    }
+
+    #[test]
+    fn test_entropy_rng_reports_failure() {
+        let mut rng = EntropyRng(Failing(Cell::new(0)));
+        assert!(rng.try_fill_bytes(&mut [0u8; 8]).is_err());
+        let panic = std::panic::catch_unwind(move || rng.next_u32()).unwrap_err();
+        assert!(panic.downcast_ref::<String>().unwrap().contains("no backend"));
+    }
+}
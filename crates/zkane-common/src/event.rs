@@ -0,0 +1,297 @@
+//! # Contract Events
+//!
+//! Compact, versioned encoding of the events the pool contract returns in
+//! its response data, so indexers can parse pool activity without knowing
+//! the contract's internals. All integers are little-endian.
+//!
+//! An event starts with the version ([`CONTRACT_EVENT_VERSION`], 1 byte) and
+//! its type (1 byte), followed by fixed fields:
+//!
+//! | Type | Fields |
+//! |------|--------|
+//! | `0x01` deposit | commitment (32), leaf index (4), root (32) |
+//! | `0x02` withdrawal | nullifier hash (32), outputs hash (32), relayer output hash (32), fee (16), protocol fee (16) |
+//! | `0x03` split | the withdrawal fields, public amount (16), first leaf index (4), commitment count (1), commitments (32 each) |
+//!
+//! The root of a deposit is the pool's Merkle root after inserting it, so
+//! indexers can follow the root history without rebuilding the tree. Version
+//! 1 deposits carry no root and decode with a zero root. The commitments of
+//! a split are inserted in order, at consecutive leaf indices. Decoders must
+//! reject unknown versions and types, and trailing bytes.
+//!
+//! ```rust
+//! use zkane_common::{Commitment, ContractEvent};
+//!
+//! let event = ContractEvent::Deposit { commitment: Commitment::new([1u8; 32]), leaf_index: 7, root: [2u8; 32] };
+//! let data = event.to_bytes();
+//! assert_eq!(data.len(), 2 + 32 + 4 + 32);
+//! assert_eq!(ContractEvent::from_bytes(&data)?, event);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{Commitment, NullifierHash, ZKaneError, ZKaneResult};
+use serde::{Deserialize, Serialize};
+
+/// Current version of the contract event encoding
+pub const CONTRACT_EVENT_VERSION: u8 = 2;
+
+/// Version of deposit events without the root
+const ROOTLESS_EVENT_VERSION: u8 = 1;
+
+const DEPOSIT_EVENT: u8 = 0x01;
+const WITHDRAWAL_EVENT: u8 = 0x02;
+const SPLIT_EVENT: u8 = 0x03;
+
+/// The part of a spend shared by withdrawals and splits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendEvent {
+    /// The spent note's nullifier hash
+    pub nullifier_hash: NullifierHash,
+    /// Hash of the outputs the proof is bound to
+    pub outputs_hash: [u8; 32],
+    /// Hash of the relayer's fee output, zero if self-relayed
+    pub relayer_output_hash: [u8; 32],
+    /// Fee paid to the relayer
+    pub fee: u128,
+    /// Fee paid to the protocol fee collector
+    pub protocol_fee: u128,
+}
+
+/// An event emitted by the pool contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContractEvent {
+    /// A commitment was deposited
+    Deposit {
+        /// The deposited commitment
+        commitment: Commitment,
+        /// Its leaf index in the pool's tree
+        leaf_index: u32,
+        /// The pool's Merkle root after the deposit, zero if not reported
+        #[serde(default)]
+        root: [u8; 32],
+    },
+    /// A note was withdrawn
+    Withdrawal(SpendEvent),
+    /// Part of a note was withdrawn, the rest kept in fresh commitments
+    Split {
+        /// The spend of the note
+        spend: SpendEvent,
+        /// Amount paid out of the pool
+        public_amount: u128,
+        /// Leaf index of the first fresh commitment
+        first_leaf_index: u32,
+        /// The fresh commitments, in leaf order
+        commitments: Vec<Commitment>,
+    },
+}
+
+impl ContractEvent {
+    /// Encode the event.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![CONTRACT_EVENT_VERSION];
+        match self {
+            ContractEvent::Deposit { commitment, leaf_index, root } => {
+                data.push(DEPOSIT_EVENT);
+                data.extend_from_slice(commitment.as_bytes());
+                data.extend_from_slice(&leaf_index.to_le_bytes());
+                data.extend_from_slice(root);
+            }
+            ContractEvent::Withdrawal(spend) => {
+                data.push(WITHDRAWAL_EVENT);
+                spend.encode_into(&mut data);
+            }
+            ContractEvent::Split {
+                spend,
+                public_amount,
+                first_leaf_index,
+                commitments,
+            } => {
+                data.push(SPLIT_EVENT);
+                spend.encode_into(&mut data);
+                data.extend_from_slice(&public_amount.to_le_bytes());
+                data.extend_from_slice(&first_leaf_index.to_le_bytes());
+                // A split has at most a handful of outputs
+                data.push(commitments.len() as u8);
+                for commitment in commitments {
+                    data.extend_from_slice(commitment.as_bytes());
+                }
+            }
+        }
+        data
+    }
+
+    /// Decode an event encoded by [`Self::to_bytes`], or by an earlier
+    /// version of the encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the version or type is
+    /// unknown, or the data is truncated or has trailing bytes.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        let mut reader = EventReader { data };
+        let version = reader.u8()?;
+        if !(ROOTLESS_EVENT_VERSION..=CONTRACT_EVENT_VERSION).contains(&version) {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let event = match reader.u8()? {
+            DEPOSIT_EVENT => ContractEvent::Deposit {
+                commitment: Commitment::new(reader.array32()?),
+                leaf_index: reader.u32()?,
+                root: if version > ROOTLESS_EVENT_VERSION { reader.array32()? } else { [0u8; 32] },
+            },
+            WITHDRAWAL_EVENT => ContractEvent::Withdrawal(SpendEvent::decode_from(&mut reader)?),
+            SPLIT_EVENT => {
+                let spend = SpendEvent::decode_from(&mut reader)?;
+                let public_amount = reader.u128()?;
+                let first_leaf_index = reader.u32()?;
+                let count = reader.u8()?;
+                let commitments = (0..count)
+                    .map(|_| reader.array32().map(Commitment::new))
+                    .collect::<ZKaneResult<_>>()?;
+                ContractEvent::Split {
+                    spend,
+                    public_amount,
+                    first_leaf_index,
+                    commitments,
+                }
+            }
+            other => return Err(invalid(format!("unknown event type {:#04x}", other))),
+        };
+        if !reader.data.is_empty() {
+            return Err(invalid(format!("{} trailing bytes", reader.data.len())));
+        }
+        Ok(event)
+    }
+}
+
+impl SpendEvent {
+    fn encode_into(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(self.nullifier_hash.as_bytes());
+        data.extend_from_slice(&self.outputs_hash);
+        data.extend_from_slice(&self.relayer_output_hash);
+        data.extend_from_slice(&self.fee.to_le_bytes());
+        data.extend_from_slice(&self.protocol_fee.to_le_bytes());
+    }
+
+    fn decode_from(reader: &mut EventReader) -> ZKaneResult<Self> {
+        Ok(Self {
+            nullifier_hash: NullifierHash::new(reader.array32()?),
+            outputs_hash: reader.array32()?,
+            relayer_output_hash: reader.array32()?,
+            fee: reader.u128()?,
+            protocol_fee: reader.u128()?,
+        })
+    }
+}
+
+fn invalid(message: String) -> ZKaneError {
+    ZKaneError::SerializationError(format!("invalid contract event: {}", message))
+}
+
+/// Reads fixed-size fields from an encoded event.
+struct EventReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> EventReader<'a> {
+    fn take(&mut self, len: usize) -> ZKaneResult<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(invalid(format!("needed {} more bytes, {} left", len, self.data.len())));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> ZKaneResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> ZKaneResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> ZKaneResult<u128> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn array32(&mut self) -> ZKaneResult<[u8; 32]> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spend() -> SpendEvent {
+        SpendEvent {
+            nullifier_hash: NullifierHash::new([2u8; 32]),
+            outputs_hash: [3u8; 32],
+            relayer_output_hash: [4u8; 32],
+            fee: 100,
+            protocol_fee: u128::MAX,
+        }
+    }
+
+    #[test]
+    fn test_contract_event_roundtrip() {
+        let events = [
+            ContractEvent::Deposit { commitment: Commitment::new([1u8; 32]), leaf_index: u32::MAX, root: [7u8; 32] },
+            ContractEvent::Withdrawal(spend()),
+            ContractEvent::Split {
+                spend: spend(),
+                public_amount: 400,
+                first_leaf_index: 5,
+                commitments: vec![Commitment::new([5u8; 32]), Commitment::new([6u8; 32])],
+            },
+        ];
+        for event in events {
+            let data = event.to_bytes();
+            assert_eq!(data[0], CONTRACT_EVENT_VERSION);
+            assert_eq!(ContractEvent::from_bytes(&data).unwrap(), event);
+        }
+        assert_eq!(ContractEvent::Withdrawal(spend()).to_bytes().len(), 2 + 32 * 3 + 16 * 2);
+    }
+
+    #[test]
+    fn test_contract_event_v1() {
+        // Version 1 deposits end at the leaf index and report no root
+        let mut data = vec![1, DEPOSIT_EVENT];
+        data.extend_from_slice(&[1u8; 32]);
+        data.extend_from_slice(&9u32.to_le_bytes());
+        assert_eq!(
+            ContractEvent::from_bytes(&data).unwrap(),
+            ContractEvent::Deposit { commitment: Commitment::new([1u8; 32]), leaf_index: 9, root: [0u8; 32] }
+        );
+        data.extend_from_slice(&[7u8; 32]);
+        assert!(ContractEvent::from_bytes(&data).is_err());
+
+        // Spends are the same in both versions
+        let mut withdrawal = ContractEvent::Withdrawal(spend()).to_bytes();
+        withdrawal[0] = 1;
+        assert_eq!(ContractEvent::from_bytes(&withdrawal).unwrap(), ContractEvent::Withdrawal(spend()));
+    }
+
+    #[test]
+    fn test_contract_event_rejects_malformed() {
+        let data = ContractEvent::Withdrawal(spend()).to_bytes();
+
+        let mut other_version = data.clone();
+        other_version[0] = CONTRACT_EVENT_VERSION + 1;
+        let mut no_version = data.clone();
+        no_version[0] = 0;
+        let mut unknown_type = data.clone();
+        unknown_type[1] = 0x7f;
+        let mut trailing = data.clone();
+        trailing.push(0);
+
+        for data in [other_version, no_version, unknown_type, trailing, data[..data.len() - 1].to_vec(), vec![]] {
+            assert!(matches!(ContractEvent::from_bytes(&data), Err(ZKaneError::SerializationError(_))));
+        }
+
+        // The legacy JSON events are rejected rather than misread
+        assert!(ContractEvent::from_bytes(br#"{"type":"deposit"}"#).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! # Hex Serde
+//!
+//! Serde of the 32-byte values, [`Commitment`](crate::Commitment),
+//! [`NullifierHash`](crate::NullifierHash), [`Secret`](crate::Secret) and
+//! [`Nullifier`](crate::Nullifier), used with `#[serde(with = "hex_serde")]`.
+//!
+//! Human-readable formats such as JSON get a `0x`-prefixed hex string, so
+//! JSON envelopes and note files hold `"0x0a1b..."` rather than an array of
+//! 32 numbers. Binary formats keep the compact byte array. Hex is read with
+//! or without the prefix, and the byte arrays written before are still read
+//! from human-readable formats too, so existing JSON keeps decoding.
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+pub fn serialize<S: Serializer>(bytes: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        // The hex of a secret is wiped like the secret itself
+        let hex = Zeroizing::new(format!("0x{}", hex::encode(bytes)));
+        serializer.serialize_str(&hex)
+    } else {
+        bytes.serialize(serializer)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(Bytes32Visitor)
+    } else {
+        <[u8; 32]>::deserialize(deserializer)
+    }
+}
+
+struct Bytes32Visitor;
+
+impl<'de> Visitor<'de> for Bytes32Visitor {
+    type Value = [u8; 32];
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("32 bytes as hex or an array")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(value.strip_prefix("0x").unwrap_or(value), &mut bytes)
+            .map_err(|_| E::invalid_value(de::Unexpected::Other("malformed hex"), &self))?;
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0u8; 32];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(index, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(de::Error::invalid_length(33, &self));
+        }
+        Ok(bytes)
+    }
+}
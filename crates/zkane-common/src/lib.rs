@@ -20,6 +20,9 @@
 //! - [`WithdrawalProof`] - Zero-knowledge proof data for withdrawals
 //! - [`ZKaneConfig`] - Configuration for privacy pools
 //! - [`MerklePath`] - Merkle tree inclusion proofs
+//! - [`WithdrawalWitness`] - Binary witness envelope of withdrawal transactions
+//! - [`ContractEvent`] - Compact events the pool contract emits
+//! - [`ZkAssetId`] - Alkane IDs of assets, pools and factories
 //!
 //! ## Privacy Model
 //!
@@ -57,24 +60,92 @@
 //! - **Secret Management**: Secrets must be kept private and never transmitted in plaintext
 //! - **Nullifier Uniqueness**: Each nullifier can only be used once to prevent double-spending
 //! - **Commitment Binding**: Commitments cryptographically bind secrets and nullifiers
-//! - **Random Generation**: All cryptographic values should use secure randomness
+//! - **Random Generation**: All cryptographic values should use secure randomness;
+//!   [`check_entropy`] confirms the target's [`EntropySource`] works
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use alkanes_support::id::AlkaneId;
+#[cfg(feature = "deezel")]
 use deezel_common::DeezelError;
+use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-/// A serializable wrapper for AlkaneId.
+mod codec;
+mod denomination;
+mod entropy;
+mod event;
+mod hex_serde;
+mod note_json;
+mod public_inputs;
+mod qr;
+mod readable;
+mod recipient;
+mod reserves;
+mod rewards;
+mod schema;
+
+pub use codec::{
+    AmountWitness, EnvelopeFormat, SplitWitness, WithdrawalWitness, AMOUNT_WITNESS_VERSION, ENVELOPE_COMPRESSED_TAG,
+    MAX_ENCODED_PATH_HEIGHT, MAX_ENVELOPE_SIZE, SPLIT_OUTPUTS, WITHDRAWAL_PROOF_VERSION,
+};
+pub use denomination::{format_units, parse_units, DenominationSpec, MAX_DECIMALS, MAX_SYMBOL_LEN};
+pub use entropy::{check_entropy, self_test, EntropyRng, EntropySource, SystemEntropy};
+#[cfg(not(target_arch = "wasm32"))]
+pub use entropy::OsEntropy;
+#[cfg(target_arch = "wasm32")]
+pub use entropy::WebCryptoEntropy;
+pub use event::{ContractEvent, SpendEvent, CONTRACT_EVENT_VERSION};
+pub use note_json::NOTE_JSON_VERSION;
+pub use public_inputs::{PublicInputs, BN254_FIELD_ORDER, PUBLIC_INPUT_COUNT};
+pub use qr::{QrFrameDecoder, DEFAULT_QR_FRAME_SIZE, MAX_QR_FRAMES, QR_NOTE_VERSION, QR_PAYLOAD_PREFIX};
+pub use readable::{COMMITMENT_HRP, NULLIFIER_HASH_HRP, POOL_ID_HRP};
+pub use recipient::{validate_recipient, Recipient, MAX_RECIPIENT_SCRIPT_SIZE};
+pub use reserves::PoolReserves;
+pub use rewards::{reward_claim_hash, RewardProgram};
+pub use schema::{
+    decode_schema_version, encode_schema_version, pending_migrations, FACTORY_SCHEMA_VERSION, POOL_SCHEMA_VERSION,
+    SCHEMA_VERSION_KEY,
+};
+
+/// An alkane ID, identifying an asset, a pool or a factory.
+///
+/// This is the ID type of every ZKane API. Unlike [`AlkaneId`] from
+/// alkanes_support, it implements serde, [`Display`](std::fmt::Display) and
+/// [`FromStr`](std::str::FromStr) (`block:tx`, or `zkp1...` for pool IDs),
+/// and converts to and from [`AlkaneId`] with `From` where the alkanes
+/// runtime needs one.
+///
+/// # Example
+///
+/// ```rust
+/// use zkane_common::ZkAssetId;
+/// use alkanes_support::id::AlkaneId;
 ///
-/// Since AlkaneId from alkanes_support doesn't implement Serialize/Deserialize,
-/// we create a wrapper that can be serialized for storage and transmission.
+/// let asset_id: ZkAssetId = "2:1".parse()?;
+/// assert_eq!(asset_id, ZkAssetId::new(2, 1));
+/// assert_eq!(asset_id.to_string(), "2:1");
+///
+/// let alkane_id = AlkaneId::from(asset_id);
+/// assert_eq!(ZkAssetId::from(&alkane_id), asset_id);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct SerializableAlkaneId {
+pub struct ZkAssetId {
     pub block: u128,
     pub tx: u128,
 }
 
-impl From<AlkaneId> for SerializableAlkaneId {
+impl ZkAssetId {
+    /// Create an ID from its block and transaction numbers.
+    pub const fn new(block: u128, tx: u128) -> Self {
+        Self { block, tx }
+    }
+}
+
+impl From<AlkaneId> for ZkAssetId {
     fn from(id: AlkaneId) -> Self {
         Self {
             block: id.block,
@@ -83,8 +154,14 @@ impl From<AlkaneId> for SerializableAlkaneId {
     }
 }
 
-impl From<SerializableAlkaneId> for AlkaneId {
-    fn from(id: SerializableAlkaneId) -> Self {
+impl From<&AlkaneId> for ZkAssetId {
+    fn from(id: &AlkaneId) -> Self {
+        Self::new(id.block, id.tx)
+    }
+}
+
+impl From<ZkAssetId> for AlkaneId {
+    fn from(id: ZkAssetId) -> Self {
         Self {
             block: id.block,
             tx: id.tx,
@@ -92,6 +169,135 @@ impl From<SerializableAlkaneId> for AlkaneId {
     }
 }
 
+impl std::fmt::Display for ZkAssetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.block, self.tx)
+    }
+}
+
+impl std::str::FromStr for ZkAssetId {
+    type Err = anyhow::Error;
+
+    /// Parse an alkane ID written as `block:tx`, or as a bech32m pool ID.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("zkp1")) {
+            return Ok(Self::from_bech32(s)?);
+        }
+        let (block, tx) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid alkane ID '{}': expected block:tx", s))?;
+        Ok(Self {
+            block: block.trim().parse()?,
+            tx: tx.trim().parse()?,
+        })
+    }
+}
+
+/// The alkanes block pool instances are spawned at.
+pub const ZKANE_INSTANCE_BLOCK: u128 = 6;
+
+/// Domain separator for pool ID derivation
+const POOL_ID_DOMAIN: &[u8] = b"zkane/pool-id/v1";
+
+/// Domain separator for commitment pre-registration hashes
+const REGISTRATION_DOMAIN: &[u8] = b"zkane/registration/v1";
+
+/// Derive the deterministic pool ID for an asset and denomination.
+///
+/// The pool's `tx` is the first 16 bytes (little-endian) of
+/// `SHA-256("zkane/pool-id/v1" || asset.block || asset.tx || denomination)`,
+/// with all integers encoded as 16-byte little-endian values. The factory
+/// contract and the frontend must use this same derivation.
+///
+/// # Example
+///
+/// ```rust
+/// use zkane_common::{derive_pool_id, ZkAssetId, ZKANE_INSTANCE_BLOCK};
+///
+/// let asset_id = ZkAssetId { block: 2, tx: 1 };
+/// let pool_id = derive_pool_id(&asset_id, 1000000);
+/// assert_eq!(pool_id.block, ZKANE_INSTANCE_BLOCK);
+/// assert_ne!(pool_id, derive_pool_id(&asset_id, 1000001));
+/// ```
+pub fn derive_pool_id(asset_id: &ZkAssetId, denomination: u128) -> ZkAssetId {
+    derive_pool_id_at(asset_id, denomination, 0)
+}
+
+/// Derive the pool ID of a generation of an asset/denomination pool.
+///
+/// When a pool is retired the factory spawns a successor for the same asset
+/// and denomination, one generation later. Generation 0 is the first pool,
+/// [`derive_pool_id`]; later generations append the generation as 4
+/// little-endian bytes to the hashed data.
+///
+/// ```rust
+/// use zkane_common::{derive_pool_id, derive_pool_id_at, ZkAssetId};
+///
+/// let asset_id = ZkAssetId { block: 2, tx: 1 };
+/// assert_eq!(derive_pool_id_at(&asset_id, 1000000, 0), derive_pool_id(&asset_id, 1000000));
+/// assert_ne!(derive_pool_id_at(&asset_id, 1000000, 1), derive_pool_id(&asset_id, 1000000));
+/// ```
+pub fn derive_pool_id_at(asset_id: &ZkAssetId, denomination: u128, generation: u32) -> ZkAssetId {
+    let mut hasher = Sha256::new();
+    hasher.update(POOL_ID_DOMAIN);
+    hasher.update(asset_id.block.to_le_bytes());
+    hasher.update(asset_id.tx.to_le_bytes());
+    hasher.update(denomination.to_le_bytes());
+    if generation > 0 {
+        hasher.update(generation.to_le_bytes());
+    }
+    let hash = hasher.finalize();
+
+    let mut tx = [0u8; 16];
+    tx.copy_from_slice(&hash[..16]);
+    ZkAssetId {
+        block: ZKANE_INSTANCE_BLOCK,
+        tx: u128::from_le_bytes(tx),
+    }
+}
+
+/// Hash the outputs of a withdrawal transaction.
+///
+/// Withdrawal proofs commit to this hash so a withdrawal can't be frontrun with
+/// different recipients. Each output contributes its value as 8 little-endian
+/// bytes followed by its scriptPubKey as lowercase hex, all fed into a single
+/// SHA-256. OP_RETURN outputs, such as the protostone carrying the withdrawal
+/// call, are skipped. This matches `hash_transaction_outputs` in the frontend.
+///
+/// # Example
+///
+/// ```rust
+/// use bitcoin::{Amount, ScriptBuf, TxOut};
+/// use zkane_common::calculate_outputs_hash;
+///
+/// let recipient = TxOut { value: Amount::from_sat(546), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) };
+/// let protostone = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([0u8; 4]) };
+/// assert_eq!(
+///     calculate_outputs_hash(&[recipient.clone(), protostone]),
+///     calculate_outputs_hash(&[recipient]),
+/// );
+/// ```
+pub fn calculate_outputs_hash(outputs: &[bitcoin::TxOut]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for output in outputs.iter().filter(|output| !output.script_pubkey.is_op_return()) {
+        hasher.update(output.value.to_sat().to_le_bytes());
+        hasher.update(hex::encode(output.script_pubkey.as_bytes()).as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Find the outputs a withdrawal's outputs hash commits to in a batch.
+///
+/// A batched transaction carries several withdrawals, each bound to its own
+/// contiguous run of outputs rather than to all of them. Returns the range of
+/// `outputs` whose [`calculate_outputs_hash`] is `outputs_hash`, if any.
+/// OP_RETURN outputs are skipped as usual, so a range may span protostones.
+pub fn find_outputs_window(outputs: &[bitcoin::TxOut], outputs_hash: &[u8; 32]) -> Option<std::ops::Range<usize>> {
+    (0..outputs.len())
+        .flat_map(|start| (start + 1..=outputs.len()).map(move |end| start..end))
+        .find(|range| calculate_outputs_hash(&outputs[range.clone()]) == *outputs_hash)
+}
+
 /// A commitment to a secret value in the privacy pool.
 ///
 /// Commitments are cryptographic bindings of secrets and nullifiers that hide
@@ -122,7 +328,7 @@ impl From<SerializableAlkaneId> for AlkaneId {
 /// assert_eq!(commitment, parsed);
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Commitment(pub [u8; 32]);
+pub struct Commitment(#[serde(with = "hex_serde")] pub [u8; 32]);
 
 impl Commitment {
     /// Create a new commitment from 32 bytes.
@@ -216,6 +422,62 @@ impl Commitment {
         array.copy_from_slice(&bytes);
         Ok(Self(array))
     }
+
+    /// Get the hash a deposit of this commitment is pre-registered under.
+    ///
+    /// A front-run-protected deposit first registers
+    /// `SHA-256("zkane/registration/v1" || commitment)` with the pool, which
+    /// hides the commitment, and reveals the commitment with the funds in a
+    /// later block. The pool refuses plain deposits of a registered
+    /// commitment, so a copy of the reveal can't take its place.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use zkane_common::Commitment;
+    ///
+    /// let commitment = Commitment::new([1u8; 32]);
+    /// assert_ne!(commitment.registration_hash(), *commitment.as_bytes());
+    /// assert_ne!(commitment.registration_hash(), Commitment::new([2u8; 32]).registration_hash());
+    /// ```
+    pub fn registration_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(REGISTRATION_DOMAIN);
+        hasher.update(self.0);
+        hasher.finalize().into()
+    }
+
+    /// Parse commitments packed as consecutive 32-byte leaves.
+    ///
+    /// This is the format returned by the pool contract's `GetCommitmentRange`
+    /// opcode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data length is not a multiple of 32 bytes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use zkane_common::Commitment;
+    ///
+    /// let data = [[1u8; 32], [2u8; 32]].concat();
+    /// let commitments = Commitment::parse_packed(&data).unwrap();
+    /// assert_eq!(commitments, vec![Commitment::new([1u8; 32]), Commitment::new([2u8; 32])]);
+    /// ```
+    pub fn parse_packed(data: &[u8]) -> Result<Vec<Self>> {
+        let chunks = data.chunks_exact(32);
+        if !chunks.remainder().is_empty() {
+            return Err(anyhow::anyhow!("Invalid packed commitments length: {} is not a multiple of 32", data.len()));
+        }
+        Ok(chunks
+            .map(|chunk| {
+                let mut array = [0u8; 32];
+                array.copy_from_slice(chunk);
+                Self(array)
+            })
+            .collect())
+    }
 }
 
 /// A nullifier hash to prevent double spending.
@@ -244,7 +506,7 @@ impl Commitment {
 /// let hex = nullifier_hash.to_hex();
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct NullifierHash(pub [u8; 32]);
+pub struct NullifierHash(#[serde(with = "hex_serde")] pub [u8; 32]);
 
 impl NullifierHash {
     /// Create a new nullifier hash from 32 bytes.
@@ -280,6 +542,24 @@ impl NullifierHash {
         array.copy_from_slice(&bytes);
         Ok(Self(array))
     }
+
+    /// Split the nullifier hash into two little-endian `u128` halves, low
+    /// half first, for passing it as cellpack inputs.
+    pub fn to_u128_pair(&self) -> (u128, u128) {
+        (
+            u128::from_le_bytes(self.0[..16].try_into().unwrap()),
+            u128::from_le_bytes(self.0[16..].try_into().unwrap()),
+        )
+    }
+
+    /// Rebuild a nullifier hash from the halves produced by
+    /// [`NullifierHash::to_u128_pair`].
+    pub fn from_u128_pair(low: u128, high: u128) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&low.to_le_bytes());
+        bytes[16..].copy_from_slice(&high.to_le_bytes());
+        Self(bytes)
+    }
 }
 
 /// A secret value used to generate commitments.
@@ -309,8 +589,22 @@ impl NullifierHash {
 /// // Access bytes for cryptographic operations
 /// let secret_bytes = secret.as_bytes();
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Secret(pub [u8; 32]);
+///
+/// Secrets are compared in constant time and wiped from memory when dropped.
+#[derive(Debug, Clone, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct Secret(#[serde(with = "hex_serde")] pub [u8; 32]);
+
+impl ConstantTimeEq for Secret {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
 
 impl Secret {
     /// Create a new secret from 32 bytes.
@@ -324,8 +618,8 @@ impl Secret {
 
     /// Generate a cryptographically secure random secret.
     ///
-    /// Uses the system's secure random number generator to create
-    /// a 32-byte secret suitable for cryptographic use.
+    /// Draws 32 bytes from the target's [`SystemEntropy`] source, panicking
+    /// if it is unavailable; see [`check_entropy`].
     ///
     /// # Example
     ///
@@ -338,8 +632,22 @@ impl Secret {
     /// assert_ne!(secret1, secret2);
     /// ```
     pub fn random() -> Self {
-        use rand::RngCore;
-        let mut rng = rand::thread_rng();
+        Self::random_with_rng(&mut EntropyRng::system())
+    }
+
+    /// Generate a secret from the given random number generator.
+    ///
+    /// A seeded generator makes tests and fuzzing reproducible; anything
+    /// else should use [`random`](Self::random).
+    ///
+    /// ```rust
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use zkane_common::Secret;
+    ///
+    /// let secret = Secret::random_with_rng(&mut StdRng::seed_from_u64(7));
+    /// assert_eq!(secret, Secret::random_with_rng(&mut StdRng::seed_from_u64(7)));
+    /// ```
+    pub fn random_with_rng<R: rand::RngCore + rand::CryptoRng>(rng: &mut R) -> Self {
         let mut bytes = [0u8; 32];
         rng.fill_bytes(&mut bytes);
         Self(bytes)
@@ -404,8 +712,22 @@ impl Secret {
 /// let bytes = [1u8; 32];
 /// let nullifier = Nullifier::new(bytes);
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Nullifier(pub [u8; 32]);
+///
+/// Nullifiers are compared in constant time and wiped from memory when dropped.
+#[derive(Debug, Clone, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct Nullifier(#[serde(with = "hex_serde")] pub [u8; 32]);
+
+impl ConstantTimeEq for Nullifier {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for Nullifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
 
 impl Nullifier {
     /// Create a new nullifier from 32 bytes.
@@ -413,10 +735,14 @@ impl Nullifier {
         Self(bytes)
     }
 
-    /// Generate a cryptographically secure random nullifier.
+    /// Generate a cryptographically secure random nullifier from the
+    /// target's [`SystemEntropy`] source.
     pub fn random() -> Self {
-        use rand::RngCore;
-        let mut rng = rand::thread_rng();
+        Self::random_with_rng(&mut EntropyRng::system())
+    }
+
+    /// Generate a nullifier from the given random number generator.
+    pub fn random_with_rng<R: rand::RngCore + rand::CryptoRng>(rng: &mut R) -> Self {
         let mut bytes = [0u8; 32];
         rng.fill_bytes(&mut bytes);
         Self(bytes)
@@ -444,6 +770,64 @@ impl Nullifier {
     }
 }
 
+/// Hash function of a pool's Merkle tree.
+///
+/// Every party building the tree of a pool must use the same function, so it
+/// is part of the [`ZKaneConfig`]. Roots, and therefore withdrawal proofs, of
+/// one function are meaningless to a tree built with another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeHash {
+    /// Blake2s-256, the original tree hash
+    #[default]
+    Blake2s,
+    /// Poseidon over BN254, for trees proven inside circuits
+    Poseidon,
+    /// SHA-256, for pools used without zero-knowledge proofs
+    Sha256,
+    /// Poseidon over BLS12-381, for trees proven inside the arkworks circuits
+    #[serde(rename = "poseidon_bls12_381")]
+    PoseidonBls12_381,
+}
+
+impl std::fmt::Display for TreeHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeHash::Blake2s => write!(f, "blake2s"),
+            TreeHash::Poseidon => write!(f, "poseidon"),
+            TreeHash::Sha256 => write!(f, "sha256"),
+            TreeHash::PoseidonBls12_381 => write!(f, "poseidon_bls12_381"),
+        }
+    }
+}
+
+/// How a pool values its notes.
+///
+/// A fixed pool takes deposits of exactly its denomination, so every note is
+/// worth the same and withdrawals pay out the denomination. A variable pool
+/// takes deposits of any amount: the amount is committed to inside the
+/// note's commitment, each deposit proves the commitment opens to the amount
+/// paid in, and notes are withdrawn through split withdrawals, whose proof
+/// shows the note's value is conserved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolMode {
+    /// Every note is worth the pool's denomination
+    #[default]
+    Fixed,
+    /// Notes commit to their own value
+    Variable,
+}
+
+impl std::fmt::Display for PoolMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PoolMode::Fixed => write!(f, "fixed"),
+            PoolMode::Variable => write!(f, "variable"),
+        }
+    }
+}
+
 /// Configuration for a ZKane privacy pool.
 ///
 /// This structure contains all the parameters needed to configure and operate
@@ -465,18 +849,33 @@ impl Nullifier {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ZKaneConfig {
     /// The alkane asset ID this pool accepts
-    pub asset_id: SerializableAlkaneId,
+    pub asset_id: ZkAssetId,
     /// The denomination (fixed amount) for deposits/withdrawals
     pub denomination: u128,
     /// The merkle tree height (determines max number of deposits)
     pub tree_height: u32,
     /// The verifier key for proof verification
     pub verifier_key: Vec<u8>,
+    /// Version of the withdrawal circuit the verifier key belongs to
+    #[serde(default = "default_circuit_version")]
+    pub circuit_version: u32,
+    /// Protocol fee taken from each withdrawal, if any
+    #[serde(default)]
+    pub protocol_fee: Option<ProtocolFee>,
+    /// Hash function of the pool's Merkle tree
+    #[serde(default)]
+    pub tree_hash: TreeHash,
+    /// Whether notes are worth the denomination or commit to their value
+    #[serde(default)]
+    pub mode: PoolMode,
 }
 
 impl ZKaneConfig {
     /// Create a new ZKane configuration.
     ///
+    /// The tree height isn't checked, so tests can use small trees; pools
+    /// are configured with [`Self::try_new`].
+    ///
     /// # Arguments
     ///
     /// * `asset_id` - The alkanes asset this pool will accept
@@ -484,7 +883,7 @@ impl ZKaneConfig {
     /// * `tree_height` - Merkle tree height (max deposits = 2^height)
     /// * `verifier_key` - Cryptographic key for proof verification
     pub fn new(
-        asset_id: SerializableAlkaneId,
+        asset_id: ZkAssetId,
         denomination: u128,
         tree_height: u32,
         verifier_key: Vec<u8>,
@@ -494,95 +893,623 @@ impl ZKaneConfig {
             denomination,
             tree_height,
             verifier_key,
+            circuit_version: CIRCUIT_VERSION,
+            protocol_fee: None,
+            tree_hash: TreeHash::default(),
+            mode: PoolMode::default(),
+        }
+    }
+
+    /// Create a new ZKane configuration, checking the tree height.
+    ///
+    /// Same as [`Self::new`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidTreeHeight`] unless the height is between
+    /// [`MIN_TREE_HEIGHT`] and [`MAX_TREE_HEIGHT`].
+    pub fn try_new(
+        asset_id: ZkAssetId,
+        denomination: u128,
+        tree_height: u32,
+        verifier_key: Vec<u8>,
+    ) -> ZKaneResult<Self> {
+        validate_tree_height(tree_height.into())?;
+        Ok(Self::new(asset_id, denomination, tree_height, verifier_key))
+    }
+
+    /// Create the configuration of a variable-amount pool.
+    ///
+    /// Variable pools have no denomination, which is left at zero.
+    pub fn variable(asset_id: ZkAssetId, tree_height: u32, verifier_key: Vec<u8>) -> Self {
+        Self {
+            mode: PoolMode::Variable,
+            ..Self::new(asset_id, 0, tree_height, verifier_key)
+        }
+    }
+
+    /// Check whether notes commit to their own value.
+    pub fn is_variable(&self) -> bool {
+        self.mode == PoolMode::Variable
+    }
+
+    /// Check that a deposit pays in an amount the pool accepts.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidDenomination`] if a fixed pool's deposit
+    /// isn't the denomination, or a variable pool's deposit is zero.
+    pub fn check_deposit_amount(&self, amount: u128) -> ZKaneResult<()> {
+        let valid = match self.mode {
+            PoolMode::Fixed => amount == self.denomination,
+            PoolMode::Variable => amount > 0,
+        };
+        if !valid {
+            return Err(ZKaneError::InvalidDenomination);
+        }
+        Ok(())
+    }
+
+    /// Verify proofs with the key of a specific circuit version.
+    pub fn with_verifier_key(mut self, circuit_version: u32, verifier_key: Vec<u8>) -> Self {
+        self.circuit_version = circuit_version;
+        self.verifier_key = verifier_key;
+        self
+    }
+
+    /// Check that a withdrawal proof was generated for this pool's circuit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnsupportedCircuitVersion`] if the proof targets
+    /// a different circuit version.
+    pub fn check_circuit_version(&self, proof: &WithdrawalProof) -> ZKaneResult<()> {
+        if proof.circuit_version != self.circuit_version {
+            return Err(ZKaneError::UnsupportedCircuitVersion(proof.circuit_version));
+        }
+        Ok(())
+    }
+
+    /// Take a protocol fee from each withdrawal.
+    pub fn with_protocol_fee(mut self, protocol_fee: ProtocolFee) -> Self {
+        self.protocol_fee = Some(protocol_fee);
+        self
+    }
+
+    /// Build the pool's Merkle tree with a different hash function.
+    pub fn with_tree_hash(mut self, tree_hash: TreeHash) -> Self {
+        self.tree_hash = tree_hash;
+        self
+    }
+
+    /// Get the protocol fee taken from each withdrawal.
+    pub fn protocol_fee_amount(&self) -> u128 {
+        self.protocol_fee
+            .map(|fee| fee.amount(self.denomination))
+            .unwrap_or(0)
+    }
+
+    /// Split the denomination of a withdrawal between its recipients.
+    ///
+    /// The protocol fee is taken first and the relayer fee is paid out of the
+    /// remainder, so the three amounts always add up to the denomination.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::WrongPoolMode`] for a variable pool, whose notes
+    /// are withdrawn with split withdrawals, and [`ZKaneError::InvalidFee`]
+    /// if the relayer fee exceeds what is left after the protocol fee.
+    pub fn withdrawal_amounts(&self, relayer_fee: u128) -> ZKaneResult<WithdrawalAmounts> {
+        if self.is_variable() {
+            return Err(ZKaneError::WrongPoolMode(self.mode));
+        }
+        self.split_amounts(self.denomination, relayer_fee)
+    }
+
+    /// Split the public amount of a split withdrawal between its recipients.
+    ///
+    /// Same as [`withdrawal_amounts`](Self::withdrawal_amounts), with the
+    /// protocol fee taken from the public amount only: the value kept in the
+    /// pool as fresh notes pays no fee until it is withdrawn.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidDenomination`] if the public amount
+    /// exceeds the denomination of a fixed pool, and [`ZKaneError::InvalidFee`]
+    /// if the relayer fee exceeds what is left after the protocol fee. In a
+    /// variable pool the split's proof bounds the public amount by the note's
+    /// value instead.
+    pub fn split_amounts(&self, public_amount: u128, relayer_fee: u128) -> ZKaneResult<WithdrawalAmounts> {
+        if !self.is_variable() && public_amount > self.denomination {
+            return Err(ZKaneError::InvalidDenomination);
         }
+        let protocol = self.protocol_fee.map(|fee| fee.amount(public_amount)).unwrap_or(0);
+        let available = public_amount - protocol;
+        if relayer_fee > available {
+            return Err(ZKaneError::InvalidFee(format!(
+                "fee {} exceeds {} left after the protocol fee",
+                relayer_fee, available
+            )));
+        }
+        Ok(WithdrawalAmounts {
+            recipient: available - relayer_fee,
+            relayer: relayer_fee,
+            protocol,
+        })
     }
 
     /// Get the maximum number of deposits this pool can handle.
     ///
     /// # Returns
     ///
-    /// The maximum number of deposits (2^tree_height)
+    /// The maximum number of deposits (2^tree_height), saturating at
+    /// `u64::MAX` for heights no tree can have
     pub fn max_deposits(&self) -> u64 {
-        1u64 << self.tree_height
+        1u64.checked_shl(self.tree_height).unwrap_or(u64::MAX)
     }
 }
 
-/// A deposit note containing the secret information needed for withdrawal.
+/// Smallest Merkle tree height of a pool, 256 deposits.
+pub const MIN_TREE_HEIGHT: u32 = 8;
+
+/// Largest Merkle tree height of a pool or any tree, as leaf indices are
+/// `u32`.
+pub const MAX_TREE_HEIGHT: u32 = 31;
+
+/// Check that a pool can be configured with a tree of `height` levels.
 ///
-/// This structure contains all the information a user needs to store
-/// in order to withdraw their deposit from the privacy pool at a later time.
-/// It should be kept secure and private.
+/// # Returns
 ///
-/// # Security Warning
+/// The height as a `u32`.
 ///
-/// This structure contains sensitive cryptographic material. It should be:
-/// - Stored securely (encrypted at rest)
-/// - Transmitted only over secure channels
-/// - Never logged or exposed in plaintext
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidTreeHeight`] unless the height is between
+/// [`MIN_TREE_HEIGHT`] and [`MAX_TREE_HEIGHT`].
+pub fn validate_tree_height(height: u128) -> ZKaneResult<u32> {
+    if !(u128::from(MIN_TREE_HEIGHT)..=u128::from(MAX_TREE_HEIGHT)).contains(&height) {
+        return Err(ZKaneError::InvalidTreeHeight {
+            height,
+            min: MIN_TREE_HEIGHT,
+            max: MAX_TREE_HEIGHT,
+        });
+    }
+    Ok(height as u32)
+}
+
+/// Magic prefix of the configuration envelope of a new pool.
+///
+/// The factory only takes an envelope starting with it for configuration,
+/// so the other envelopes a transaction may carry, such as the commitment of
+/// a deposit, are never read as one.
+pub const POOL_CONFIG_MAGIC: &[u8; 4] = b"ZKPC";
+
+/// Encode the configuration envelope of a `CreatePool` call, creating a pool
+/// with a tree of `tree_height` levels.
+pub fn pool_config_envelope(tree_height: u32) -> Vec<u8> {
+    [POOL_CONFIG_MAGIC.as_slice(), &tree_height.to_le_bytes()].concat()
+}
+
+/// Version of the withdrawal circuit generated by this release.
+///
+/// Proofs carry the version of the circuit they were generated with, and pools
+/// only accept proofs for the circuit of their verifier key.
+pub const CIRCUIT_VERSION: u32 = 1;
+
+fn default_circuit_version() -> u32 {
+    CIRCUIT_VERSION
+}
+
+/// Basis points in one whole.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Highest protocol fee a pool can be configured with (10%).
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+
+/// A protocol fee taken from every withdrawal and sent to a fee collector.
 ///
 /// # Example
 ///
 /// ```rust
-/// use zkane_common::{DepositNote, Secret, Nullifier, Commitment};
-/// use alkanes_support::id::AlkaneId;
-///
-/// // Create a deposit note with specific values
-/// let secret = Secret::random();
-/// let nullifier = Nullifier::random();
-/// let commitment = Commitment::new([0u8; 32]); // Would be calculated
-/// let asset_id = AlkaneId { block: 2, tx: 1 };
+/// use zkane_common::{ProtocolFee, ZkAssetId};
 ///
-/// let note = DepositNote::new(
-///     secret,
-///     nullifier,
-///     commitment,
-///     asset_id.into(),
-///     1000000,  // denomination
-///     0,        // leaf index (set during deposit)
-/// );
+/// let fee = ProtocolFee::new(30, ZkAssetId { block: 2, tx: 9 }).unwrap();
+/// assert_eq!(fee.amount(1000000), 3000);
 /// ```
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DepositNote {
-    /// The secret value (keep private!)
-    pub secret: Secret,
-    /// The nullifier value (keep private!)
-    pub nullifier: Nullifier,
-    /// The commitment (public, stored in pool)
-    pub commitment: Commitment,
-    /// The asset ID for this deposit
-    pub asset_id: SerializableAlkaneId,
-    /// The denomination of this deposit
-    pub denomination: u128,
-    /// The leaf index in the merkle tree (set during deposit)
-    pub leaf_index: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolFee {
+    /// Fee in basis points of the denomination
+    pub fee_bps: u16,
+    /// The alkane receiving the fees
+    pub collector: ZkAssetId,
 }
 
-impl DepositNote {
-    /// Create a new deposit note with all parameters.
+impl ProtocolFee {
+    /// Create a protocol fee.
     ///
-    /// # Arguments
+    /// # Errors
     ///
-    /// * `secret` - The secret value (keep private)
-    /// * `nullifier` - The nullifier value (keep private)
-    /// * `commitment` - The commitment (public)
-    /// * `asset_id` - The asset being deposited
-    /// * `denomination` - The amount being deposited
-    /// * `leaf_index` - Position in the Merkle tree
-    pub fn new(
-        secret: Secret,
-        nullifier: Nullifier,
-        commitment: Commitment,
-        asset_id: SerializableAlkaneId,
-        denomination: u128,
-        leaf_index: u32,
-    ) -> Self {
-        Self {
+    /// Returns [`ZKaneError::InvalidProtocolFee`] if `fee_bps` exceeds
+    /// [`MAX_PROTOCOL_FEE_BPS`].
+    pub fn new(fee_bps: u16, collector: ZkAssetId) -> ZKaneResult<Self> {
+        if fee_bps > MAX_PROTOCOL_FEE_BPS {
+            return Err(ZKaneError::InvalidProtocolFee(format!(
+                "{} bps exceeds the maximum of {} bps",
+                fee_bps, MAX_PROTOCOL_FEE_BPS
+            )));
+        }
+        Ok(Self { fee_bps, collector })
+    }
+
+    /// Size of an encoded fee in bytes
+    pub const SIZE: usize = 16 * 3;
+
+    /// Encode the fee as the basis points, collector block and collector tx,
+    /// each a 16-byte little-endian integer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&(self.fee_bps as u128).to_le_bytes());
+        data.extend_from_slice(&self.collector.block.to_le_bytes());
+        data.extend_from_slice(&self.collector.tx.to_le_bytes());
+        data
+    }
+
+    /// Decode a fee returned by the `GetProtocolFee` opcodes.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the encoded fee is zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not exactly [`ProtocolFee::SIZE`] bytes
+    /// or the fee is out of range.
+    pub fn from_bytes(data: &[u8]) -> Result<Option<Self>> {
+        if data.len() != Self::SIZE {
+            return Err(anyhow::anyhow!("Invalid protocol fee length: expected {} bytes, got {}", Self::SIZE, data.len()));
+        }
+        let u128_at = |i: usize| u128::from_le_bytes(data[i * 16..(i + 1) * 16].try_into().unwrap());
+        if u128_at(0) == 0 {
+            return Ok(None);
+        }
+        let fee_bps = u16::try_from(u128_at(0)).map_err(|_| anyhow::anyhow!("Protocol fee out of range"))?;
+        let collector = ZkAssetId { block: u128_at(1), tx: u128_at(2) };
+        Ok(Some(Self::new(fee_bps, collector)?))
+    }
+
+    /// Get the fee taken from a denomination, rounded down.
+    pub fn amount(&self, denomination: u128) -> u128 {
+        let bps = self.fee_bps as u128;
+        let whole = BPS_DENOMINATOR as u128;
+        // Split the multiplication so large denominations can't overflow
+        denomination / whole * bps + denomination % whole * bps / whole
+    }
+}
+
+/// How the denomination of a withdrawal is split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawalAmounts {
+    /// Amount paid to the withdrawal recipient
+    pub recipient: u128,
+    /// Amount paid to the relayer
+    pub relayer: u128,
+    /// Amount sent to the protocol fee collector
+    pub protocol: u128,
+}
+
+impl WithdrawalAmounts {
+    /// Get the sum of all amounts, which equals the pool denomination, or the
+    /// public amount of a split withdrawal.
+    pub fn total(&self) -> u128 {
+        self.recipient + self.relayer + self.protocol
+    }
+}
+
+/// A factory's record of a deployed pool.
+///
+/// Records are returned by the factory's `GetPoolsPage` and `GetPoolMetadata`
+/// opcodes in a fixed-size little-endian binary layout:
+///
+/// | Field | Size |
+/// |-------|------|
+/// | asset_id.block | 16 |
+/// | asset_id.tx | 16 |
+/// | denomination | 16 |
+/// | pool_id.block | 16 |
+/// | pool_id.tx | 16 |
+/// | deposit_count | 16 |
+/// | created_block | 8 |
+/// | template_version | 4 |
+/// | circuit_version | 4 |
+///
+/// Records of pools created before pool templates lack the last two fields
+/// and decode with both versions 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolRecord {
+    /// The asset the pool accepts
+    pub asset_id: ZkAssetId,
+    /// The pool denomination
+    pub denomination: u128,
+    /// The pool contract
+    pub pool_id: ZkAssetId,
+    /// Number of deposits made into the pool
+    pub deposit_count: u128,
+    /// Height of the block the pool was created in
+    pub created_block: u64,
+    /// Version of the [`PoolTemplate`] the pool was created from, 0 if none
+    #[serde(default)]
+    pub template_version: u32,
+    /// Withdrawal circuit version the pool verifies, 0 if unknown
+    #[serde(default)]
+    pub circuit_version: u32,
+}
+
+impl PoolRecord {
+    /// Size of an encoded record in bytes
+    pub const SIZE: usize = 16 * 6 + 8 + 4 + 4;
+
+    /// Size of a record written before pool templates
+    pub const LEGACY_SIZE: usize = 16 * 6 + 8;
+
+    /// Encode the record.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&self.asset_id.block.to_le_bytes());
+        data.extend_from_slice(&self.asset_id.tx.to_le_bytes());
+        data.extend_from_slice(&self.denomination.to_le_bytes());
+        data.extend_from_slice(&self.pool_id.block.to_le_bytes());
+        data.extend_from_slice(&self.pool_id.tx.to_le_bytes());
+        data.extend_from_slice(&self.deposit_count.to_le_bytes());
+        data.extend_from_slice(&self.created_block.to_le_bytes());
+        data.extend_from_slice(&self.template_version.to_le_bytes());
+        data.extend_from_slice(&self.circuit_version.to_le_bytes());
+        data
+    }
+
+    /// Decode a record, in the current or the pre-template layout.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is neither [`PoolRecord::SIZE`] nor
+    /// [`PoolRecord::LEGACY_SIZE`] bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != Self::SIZE && data.len() != Self::LEGACY_SIZE {
+            return Err(anyhow::anyhow!("Invalid pool record length: expected {} bytes, got {}", Self::SIZE, data.len()));
+        }
+        let u128_at = |i: usize| u128::from_le_bytes(data[i * 16..(i + 1) * 16].try_into().unwrap());
+        let u32_at = |i: usize| data.get(i..i + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        Ok(Self {
+            asset_id: ZkAssetId { block: u128_at(0), tx: u128_at(1) },
+            denomination: u128_at(2),
+            pool_id: ZkAssetId { block: u128_at(3), tx: u128_at(4) },
+            deposit_count: u128_at(5),
+            created_block: u64::from_le_bytes(data[96..104].try_into().unwrap()),
+            template_version: u32_at(104),
+            circuit_version: u32_at(108),
+        })
+    }
+
+    /// Check that withdrawals from the pool can be proven with a circuit
+    /// version, so wallets can warn before depositing into the pool.
+    ///
+    /// Pools whose circuit version is unknown pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnsupportedCircuitVersion`] with the pool's
+    /// circuit version if it differs.
+    pub fn check_circuit_version(&self, circuit_version: u32) -> ZKaneResult<()> {
+        if self.circuit_version != 0 && self.circuit_version != circuit_version {
+            return Err(ZKaneError::UnsupportedCircuitVersion(self.circuit_version));
+        }
+        Ok(())
+    }
+
+    /// Decode a `GetPoolsPage` response: the total number of pools as a
+    /// 16-byte little-endian integer followed by the records of the page.
+    ///
+    /// # Returns
+    ///
+    /// The total number of pools and the records of the page.
+    pub fn parse_page(data: &[u8]) -> Result<(u128, Vec<Self>)> {
+        if data.len() < 16 {
+            return Err(anyhow::anyhow!("Pool page too short: {} bytes", data.len()));
+        }
+        let total = u128::from_le_bytes(data[..16].try_into().unwrap());
+        let records = data[16..].chunks(Self::SIZE).map(Self::from_bytes).collect::<Result<Vec<_>>>()?;
+        Ok((total, records))
+    }
+}
+
+/// A pool template registered with the factory: the pool contract WASM new
+/// pools are created from, pinned to the withdrawal circuit version it
+/// verifies.
+///
+/// Templates are numbered from 1 in registration order and returned by the
+/// factory's `GetTemplate` opcode in a fixed-size little-endian layout:
+///
+/// | Field | Size |
+/// |-------|------|
+/// | template_id.block | 16 |
+/// | template_id.tx | 16 |
+/// | circuit_version | 4 |
+/// | registered_block | 8 |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolTemplate {
+    /// Alkane holding the pool contract WASM
+    pub template_id: ZkAssetId,
+    /// Withdrawal circuit version pools from this template verify
+    pub circuit_version: u32,
+    /// Height of the block the template was registered in
+    pub registered_block: u64,
+}
+
+impl PoolTemplate {
+    /// Size of an encoded template in bytes
+    pub const SIZE: usize = 16 * 2 + 4 + 8;
+
+    /// Encode the template.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&self.template_id.block.to_le_bytes());
+        data.extend_from_slice(&self.template_id.tx.to_le_bytes());
+        data.extend_from_slice(&self.circuit_version.to_le_bytes());
+        data.extend_from_slice(&self.registered_block.to_le_bytes());
+        data
+    }
+
+    /// Decode a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not exactly [`PoolTemplate::SIZE`] bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != Self::SIZE {
+            return Err(anyhow::anyhow!("Invalid pool template length: expected {} bytes, got {}", Self::SIZE, data.len()));
+        }
+        Ok(Self {
+            template_id: ZkAssetId {
+                block: u128::from_le_bytes(data[0..16].try_into().unwrap()),
+                tx: u128::from_le_bytes(data[16..32].try_into().unwrap()),
+            },
+            circuit_version: u32::from_le_bytes(data[32..36].try_into().unwrap()),
+            registered_block: u64::from_le_bytes(data[36..44].try_into().unwrap()),
+        })
+    }
+}
+
+/// Pool and deposit totals of one asset, as reported by the factory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetStats {
+    /// The asset
+    pub asset_id: ZkAssetId,
+    /// Number of pools of the asset, across denominations and generations
+    pub pools: u128,
+    /// Number of deposits into the asset's pools
+    pub deposits: u128,
+}
+
+/// Totals across all pools of a factory.
+///
+/// Returned as JSON by the factory's `GetGlobalStats` opcode, so dashboards
+/// get every total in one call. Deposit counts are mirrored from the pools
+/// as the factory forwards deposits to them, so deposits made straight to a
+/// pool are only counted once the factory next forwards one to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlobalStats {
+    /// Number of pools created by the factory
+    pub total_pools: u128,
+    /// Number of deposits into all pools
+    pub total_deposits: u128,
+    /// Totals of each asset, in the order of their first pool
+    pub assets: Vec<AssetStats>,
+}
+
+/// Version of the deposit notes created by this release.
+///
+/// Version 1 notes predate the `version` field and the extension map; they
+/// deserialize as version 1 and verify as before. See
+/// [`DepositNote::upgrade`].
+pub const DEPOSIT_NOTE_VERSION: u32 = 2;
+
+fn legacy_note_version() -> u32 {
+    1
+}
+
+/// A deposit note containing the secret information needed for withdrawal.
+///
+/// This structure contains all the information a user needs to store
+/// in order to withdraw their deposit from the privacy pool at a later time.
+/// It should be kept secure and private.
+///
+/// # Security Warning
+///
+/// This structure contains sensitive cryptographic material. It should be:
+/// - Stored securely (encrypted at rest)
+/// - Transmitted only over secure channels
+/// - Never logged or exposed in plaintext
+///
+/// # Example
+///
+/// ```rust
+/// use zkane_common::{DepositNote, Secret, Nullifier, Commitment};
+/// use alkanes_support::id::AlkaneId;
+///
+/// // Create a deposit note with specific values
+/// let secret = Secret::random();
+/// let nullifier = Nullifier::random();
+/// let commitment = Commitment::new([0u8; 32]); // Would be calculated
+/// let asset_id = AlkaneId { block: 2, tx: 1 };
+///
+/// let note = DepositNote::new(
+///     secret,
+///     nullifier,
+///     commitment,
+///     asset_id.into(),
+///     1000000,  // denomination
+///     0,        // leaf index (set during deposit)
+/// );
+/// ```
+///
+/// The secret and nullifier are wiped from memory when the note is dropped.
+///
+/// Fields added after version 1, such as the insertion height, pool ID or
+/// circuit version, go in the [`extensions`](DepositNote::extensions) map, so
+/// older readers keep the fields they know and carry the rest along.
+#[derive(Debug, Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct DepositNote {
+    /// Version of the note format, see [`DEPOSIT_NOTE_VERSION`]
+    #[serde(default = "legacy_note_version")]
+    #[zeroize(skip)]
+    pub version: u32,
+    /// The secret value (keep private!)
+    pub secret: Secret,
+    /// The nullifier value (keep private!)
+    pub nullifier: Nullifier,
+    /// The commitment (public, stored in pool)
+    #[zeroize(skip)]
+    pub commitment: Commitment,
+    /// The asset ID for this deposit
+    #[zeroize(skip)]
+    pub asset_id: ZkAssetId,
+    /// The denomination of this deposit
+    pub denomination: u128,
+    /// The leaf index in the merkle tree (set during deposit)
+    pub leaf_index: u32,
+    /// Fields added by later versions, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[zeroize(skip)]
+    pub extensions: BTreeMap<String, String>,
+}
+
+impl DepositNote {
+    /// Create a new deposit note with all parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The secret value (keep private)
+    /// * `nullifier` - The nullifier value (keep private)
+    /// * `commitment` - The commitment (public)
+    /// * `asset_id` - The asset being deposited
+    /// * `denomination` - The amount being deposited
+    /// * `leaf_index` - Position in the Merkle tree
+    pub fn new(
+        secret: Secret,
+        nullifier: Nullifier,
+        commitment: Commitment,
+        asset_id: ZkAssetId,
+        denomination: u128,
+        leaf_index: u32,
+    ) -> Self {
+        Self {
+            version: DEPOSIT_NOTE_VERSION,
             secret,
             nullifier,
             commitment,
             asset_id,
             denomination,
             leaf_index,
+            extensions: BTreeMap::new(),
         }
     }
 
@@ -598,20 +1525,54 @@ impl DepositNote {
     ///
     /// * `asset_id` - The asset for this deposit
     /// * `denomination` - The amount for this deposit
-    pub fn random(asset_id: SerializableAlkaneId, denomination: u128) -> Self {
+    pub fn random(asset_id: ZkAssetId, denomination: u128) -> Self {
         let secret = Secret::random();
         let nullifier = Nullifier::random();
         // Note: commitment should be calculated using proper hash function
         let commitment = Commitment::new([0u8; 32]); // Placeholder
         
-        Self {
-            secret,
-            nullifier,
-            commitment,
-            asset_id,
-            denomination,
-            leaf_index: 0, // Will be set when deposited
+        Self::new(secret, nullifier, commitment, asset_id, denomination, 0) // Leaf index set when deposited
+    }
+
+    /// Get an extension field, or `None` if the note doesn't carry it.
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.extensions.get(key).map(String::as_str)
+    }
+
+    /// Set an extension field, replacing any earlier value.
+    pub fn set_extension(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.extensions.insert(key.into(), value.into());
+    }
+
+    /// Check that this release can read the note.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnsupportedNoteVersion`] if the note was written
+    /// by a newer release, whose fields might change what the note means.
+    pub fn check_version(&self) -> ZKaneResult<()> {
+        if self.version > DEPOSIT_NOTE_VERSION {
+            return Err(ZKaneError::UnsupportedNoteVersion {
+                version: self.version,
+                supported: DEPOSIT_NOTE_VERSION,
+            });
         }
+        Ok(())
+    }
+
+    /// Migrate the note to [`DEPOSIT_NOTE_VERSION`].
+    ///
+    /// The secret, nullifier and commitment of a version 1 note are kept as
+    /// they are, so the note still verifies and withdraws; it gains an empty
+    /// extension map.
+    ///
+    /// # Errors
+    ///
+    /// See [`Self::check_version`].
+    pub fn upgrade(&mut self) -> ZKaneResult<()> {
+        self.check_version()?;
+        self.version = DEPOSIT_NOTE_VERSION;
+        Ok(())
     }
 }
 
@@ -690,14 +1651,20 @@ impl MerklePath {
 /// # Example
 ///
 /// ```rust
-/// use zkane_common::{WithdrawalProof, NullifierHash};
+/// use zkane_common::{WithdrawalProof, NullifierHash, Recipient};
+/// use bitcoin::ScriptBuf;
 ///
 /// let proof = WithdrawalProof::new(
 ///     vec![0u8; 256],                    // Proof bytes
 ///     [1u8; 32],                         // Merkle root
 ///     NullifierHash::new([2u8; 32]),     // Nullifier hash
-///     12345,                             // Recipient
+///     Recipient::new(ScriptBuf::from_bytes(vec![0x51])), // Recipient
 /// );
+///
+/// // Relayed withdrawals additionally commit to the relayer's fee output
+/// let relayed = proof.with_relayer([3u8; 32], 1000);
+/// assert!(relayed.is_relayed());
+/// assert_eq!(relayed.recipient_amount(100000).unwrap(), 99000);
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WithdrawalProof {
@@ -707,8 +1674,26 @@ pub struct WithdrawalProof {
     pub merkle_root: [u8; 32],
     /// The nullifier hash being revealed
     pub nullifier_hash: NullifierHash,
-    /// The recipient address (as u128 for alkanes compatibility)
-    pub recipient: u128,
+    /// The scriptPubKey the withdrawal pays, the first of the recipient
+    /// outputs (empty if the proof names no recipient)
+    #[serde(default)]
+    pub recipient: Recipient,
+    /// Hash of the relayer's fee output (all zeros for self-relayed withdrawals)
+    #[serde(default)]
+    pub relayer_output_hash: [u8; 32],
+    /// Fee paid to the relayer, in units of the pool asset
+    #[serde(default)]
+    pub fee: u128,
+    /// Version of the circuit the proof was generated with
+    #[serde(default = "default_circuit_version")]
+    pub circuit_version: u32,
+    /// Hash of the recipient outputs the proof commits to (all zeros if the
+    /// proof isn't bound to a recipient set)
+    #[serde(default)]
+    pub recipients_hash: [u8; 32],
+    /// Last block height the proof may be spent at (0 if it never expires)
+    #[serde(default)]
+    pub not_after_height: u64,
 }
 
 impl WithdrawalProof {
@@ -719,19 +1704,158 @@ impl WithdrawalProof {
     /// * `proof` - The zero-knowledge proof bytes
     /// * `merkle_root` - The Merkle root when proof was generated
     /// * `nullifier_hash` - The nullifier hash being spent
-    /// * `recipient` - The recipient address
+    /// * `recipient` - The recipient scriptPubKey, bound with
+    ///   [`bind_recipient`](Self::bind_recipient) or
+    ///   [`with_recipients`](Self::with_recipients)
     pub fn new(
         proof: Vec<u8>,
         merkle_root: [u8; 32],
         nullifier_hash: NullifierHash,
-        recipient: u128,
+        recipient: Recipient,
     ) -> Self {
         Self {
             proof,
             merkle_root,
             nullifier_hash,
             recipient,
+            relayer_output_hash: [0u8; 32],
+            fee: 0,
+            circuit_version: CIRCUIT_VERSION,
+            recipients_hash: [0u8; 32],
+            not_after_height: 0,
+        }
+    }
+
+    /// Mark the proof as generated with a specific circuit version.
+    pub fn with_circuit_version(mut self, circuit_version: u32) -> Self {
+        self.circuit_version = circuit_version;
+        self
+    }
+
+    /// Attach relayer fee information to the proof.
+    ///
+    /// Both values are public inputs of the withdrawal circuit, so a relayer
+    /// cannot change its fee or redirect the fee output after the proof
+    /// has been generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `relayer_output_hash` - Hash of the transaction output paying the relayer
+    /// * `fee` - Amount of the pool asset paid to the relayer
+    pub fn with_relayer(mut self, relayer_output_hash: [u8; 32], fee: u128) -> Self {
+        self.relayer_output_hash = relayer_output_hash;
+        self.fee = fee;
+        self
+    }
+
+    /// Bind the proof to a set of recipient outputs.
+    ///
+    /// A single withdrawal can pay several outputs, for example a merchant
+    /// and change back to the sender. The outputs are hashed with
+    /// [`calculate_outputs_hash`] and the hash is a public input of the
+    /// withdrawal circuit, so the recipients can't be swapped or dropped
+    /// after the proof has been generated. The outputs must appear in this
+    /// order, as one contiguous run, in the withdrawal transaction. The
+    /// first output that isn't an OP_RETURN becomes the proof's recipient.
+    pub fn with_recipients(mut self, outputs: &[bitcoin::TxOut]) -> Self {
+        self.recipients_hash = calculate_outputs_hash(outputs);
+        if let Some(output) = outputs.iter().find(|output| !output.script_pubkey.is_op_return()) {
+            self.recipient = Recipient::new(output.script_pubkey.clone());
+        }
+        self
+    }
+
+    /// Bind the proof to a single output paying `value` to its recipient.
+    pub fn bind_recipient(self, value: bitcoin::Amount) -> Self {
+        let output = self.recipient.output(value);
+        self.with_recipients(&[output])
+    }
+
+    /// Check if the proof is bound to a set of recipient outputs.
+    pub fn has_recipients(&self) -> bool {
+        self.recipients_hash != [0u8; 32]
+    }
+
+    /// Check that a transaction's outputs pay the proof's recipients.
+    ///
+    /// Always true for proofs without a recipient set or recipient.
+    pub fn pays_recipients(&self, outputs: &[bitcoin::TxOut]) -> bool {
+        self.validate_recipient(outputs).is_ok()
+    }
+
+    /// Check that a transaction's outputs pay the proof's recipients, the
+    /// first of them being its recipient.
+    ///
+    /// # Errors
+    ///
+    /// See [`validate_recipient`].
+    pub fn validate_recipient(&self, outputs: &[bitcoin::TxOut]) -> ZKaneResult<()> {
+        validate_recipient(outputs, &self.recipients_hash, &self.recipient)
+    }
+
+    /// Make the proof expire after a block height.
+    ///
+    /// The height is a public input of the withdrawal circuit, so a stolen
+    /// proof, or one a relayer holds back, can't be spent long after it was
+    /// generated, when fees or the recipient's intent may have changed.
+    /// Split withdrawals don't bind it.
+    pub fn with_expiry(mut self, not_after_height: u64) -> Self {
+        self.not_after_height = not_after_height;
+        self
+    }
+
+    /// Check if the proof expires.
+    pub fn expires(&self) -> bool {
+        self.not_after_height != 0
+    }
+
+    /// Check that the proof may be spent in a block at `height`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::ProofExpired`] if the proof expired before
+    /// `height`.
+    pub fn validate_expiry(&self, height: u64) -> ZKaneResult<()> {
+        if self.expires() && height > self.not_after_height {
+            return Err(ZKaneError::ProofExpired { not_after_height: self.not_after_height, height });
+        }
+        Ok(())
+    }
+
+    /// Check if this withdrawal is broadcast by a relayer.
+    pub fn is_relayed(&self) -> bool {
+        self.fee > 0 || self.relayer_output_hash != [0u8; 32]
+    }
+
+    /// Validate the relayer fee against the pool denomination.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidFee`] if the fee exceeds the denomination
+    /// or if a non-zero fee is declared without a relayer output.
+    pub fn validate_fee(&self, denomination: u128) -> ZKaneResult<()> {
+        if self.fee > denomination {
+            return Err(ZKaneError::InvalidFee(format!(
+                "fee {} exceeds denomination {}",
+                self.fee, denomination
+            )));
         }
+        if self.fee > 0 && self.relayer_output_hash == [0u8; 32] {
+            return Err(ZKaneError::InvalidFee(
+                "non-zero fee requires a relayer output".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the amount received by the recipient after the relayer fee.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fee is invalid for the given denomination.
+    pub fn recipient_amount(&self, denomination: u128) -> ZKaneResult<u128> {
+        self.validate_fee(denomination)?;
+        Ok(denomination - self.fee)
     }
 
     /// Get the size of the proof in bytes.
@@ -743,13 +1867,33 @@ impl WithdrawalProof {
 /// Error types for ZKane operations.
 ///
 /// This enum represents all the possible errors that can occur
-/// during ZKane privacy pool operations.
+/// during ZKane privacy pool operations, in the libraries, the WASM bindings,
+/// the CLI and the contracts alike. Each variant has a stable numeric
+/// [`code`](ZKaneError::code), grouped by area:
+///
+/// | Range | Area |
+/// |-------|------|
+/// | 1xxx | Malformed or rejected input |
+/// | 2xxx | Proofs, nullifiers and verifier keys |
+/// | 3xxx | Merkle tree |
+/// | 4xxx | Pool contract state |
+/// | 5xxx | Provider, transactions and pool queries |
+///
+/// Codes are never reused, so clients can match on them across releases.
 #[derive(Debug, thiserror::Error)]
 pub enum ZKaneError {
     /// Invalid commitment format or value
     #[error("Invalid commitment: {0}")]
     InvalidCommitment(String),
     
+    /// Commitment is already in the pool
+    #[error("Duplicate commitment: {0}")]
+    DuplicateCommitment(String),
+
+    /// Commitment is not in the pool
+    #[error("Unknown commitment")]
+    UnknownCommitment,
+
     /// Invalid nullifier format or value
     #[error("Invalid nullifier: {0}")]
     InvalidNullifier(String),
@@ -765,23 +1909,191 @@ pub enum ZKaneError {
     /// Merkle root doesn't match expected value
     #[error("Invalid merkle root")]
     InvalidMerkleRoot,
+
+    /// Merkle path doesn't lead from the commitment to the root
+    #[error("Invalid merkle path")]
+    InvalidMerklePath,
     
     /// Denomination doesn't match pool requirements
     #[error("Invalid denomination")]
     InvalidDenomination,
+
+    /// Relayer fee is invalid for the withdrawal
+    #[error("Invalid relayer fee: {0}")]
+    InvalidFee(String),
     
+    /// Protocol fee configuration is invalid
+    #[error("Invalid protocol fee: {0}")]
+    InvalidProtocolFee(String),
+
+    /// Proof or verifier key targets an unknown circuit version
+    #[error("Unsupported circuit version: {0}")]
+    UnsupportedCircuitVersion(u32),
+
+    /// Verifier key is missing or malformed
+    #[error("Invalid verifier key: {0}")]
+    InvalidVerifierKey(String),
+
     /// Merkle tree has reached maximum capacity
     #[error("Tree is full")]
     TreeFull,
+
+    /// Merkle tree snapshot is malformed or unsupported
+    #[error("Invalid tree snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    /// The local tree disagrees with the contract's
+    #[error("State diverges from the contract at leaf {leaf_index}: {reason}")]
+    StateDivergence {
+        /// First leaf the local tree and the contract disagree on
+        leaf_index: u64,
+        /// What differs at that leaf
+        reason: String,
+    },
+
+    /// A merkle path doesn't fit its leaf index
+    #[error("Merkle path mismatch at level {level}: {reason}")]
+    MerklePathMismatch {
+        /// Level of the path, counted from the leaves, that doesn't fit
+        level: u32,
+        /// How the path and the index disagree
+        reason: String,
+    },
+
+    /// A Merkle tree height is outside the range allowed
+    #[error("Invalid tree height {height}, expected {min} to {max}")]
+    InvalidTreeHeight {
+        /// Height requested
+        height: u128,
+        /// Smallest height allowed
+        min: u32,
+        /// Largest height allowed
+        max: u32,
+    },
+
+    /// Contract was called before it was initialized
+    #[error("Contract not initialized")]
+    NotInitialized,
+
+    /// Contract was initialized twice
+    #[error("Contract already initialized")]
+    AlreadyInitialized,
+
+    /// Deposits into the pool are paused
+    #[error("Deposits are paused")]
+    DepositsPaused,
+
+    /// The pool was retired in favour of a successor and takes no deposits
+    #[error("Pool is retired; deposit into its successor")]
+    PoolRetired,
+
+    /// A plain deposit targeted a commitment pre-registered for a
+    /// front-run-protected deposit
+    #[error("Commitment is pre-registered; reveal it with a registered deposit")]
+    CommitmentReserved,
+
+    /// A registered deposit's commitment wasn't registered in an earlier block
+    #[error("Commitment not registered: {0}")]
+    CommitmentNotRegistered(String),
+
+    /// The operation isn't available in the pool's mode
+    #[error("Operation not supported by a {0} pool")]
+    WrongPoolMode(PoolMode),
+
+    /// Contract storage was written by a newer version of the contract
+    #[error("Storage schema version {stored} is newer than the supported version {supported}")]
+    UnsupportedSchemaVersion {
+        /// Schema version found in storage
+        stored: u32,
+        /// Latest schema version the contract can read
+        supported: u32,
+    },
+
+    /// A contract call re-entered a deposit or withdrawal in progress
+    #[error("Reentrant call into the pool")]
+    ReentrantCall,
+
+    /// A reward claim named a note with no points to claim
+    #[error("No reward points to claim")]
+    NoRewardClaim,
+
+    /// A reward claim wasn't registered for the claim transaction's outputs
+    /// in an earlier block
+    #[error("Reward claim not registered: {0}")]
+    RewardClaimNotRegistered(String),
+
+    /// The pool holds fewer reward alkanes than the claim is owed
+    #[error("Reward pool underfunded: {available} available, {owed} owed")]
+    RewardsUnderfunded {
+        /// Reward alkanes held by the pool
+        available: u128,
+        /// Points owed to the claim
+        owed: u128,
+    },
+
+    /// Caller may not perform a privileged contract operation
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
     
+    /// Proof generation was cancelled through its prover handle
+    #[error("Proof generation cancelled")]
+    ProofCancelled,
+
+    /// Proof expired before the block it would be spent in
+    #[error("Proof expired at height {not_after_height}, current height is {height}")]
+    ProofExpired {
+        /// Last block height the proof could be spent at
+        not_after_height: u64,
+        /// Height of the block it would be spent in
+        height: u64,
+    },
+
+    /// No working secure random source on this target
+    #[error("Secure randomness unavailable: {0}")]
+    EntropyUnavailable(String),
+
     /// General cryptographic operation error
     #[error("Cryptographic error: {0}")]
     CryptoError(String),
 
+    /// A signed report or message doesn't verify against its key
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    /// Transaction outputs differ from those the withdrawal proof is bound to
+    #[error("Transaction outputs do not match the proof's outputs hash")]
+    OutputsMismatch,
+
+    /// Data could not be serialized or deserialized
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+
+    /// Withdrawal recipient is malformed, unbound or unpaid
+    #[error("Invalid recipient: {0}")]
+    InvalidRecipient(String),
+
+    /// User-entered amount is malformed or has too many decimals
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+
+    /// A deposit note was written by a newer release
+    #[error("Deposit note version {version} is newer than the supported version {supported}")]
+    UnsupportedNoteVersion {
+        /// Version of the note
+        version: u32,
+        /// Latest note version this release can read
+        supported: u32,
+    },
+
     /// Error from the Deezel provider
+    #[cfg(feature = "deezel")]
     #[error("Provider error: {0}")]
     DeezelError(#[from] DeezelError),
 
+    /// A pool contract view call failed or returned malformed data
+    #[error("Pool query failed: {0}")]
+    PoolQueryFailed(String),
+
     /// Error parsing a transaction
     #[error("Failed to parse transaction")]
     TransactionParseError,
@@ -789,6 +2101,127 @@ pub enum ZKaneError {
     /// Commitment not found in transaction
     #[error("Commitment not found in transaction")]
     CommitmentNotFound,
+
+    /// A transaction could not be assembled, e.g. for lack of funds
+    #[error("Failed to build transaction: {0}")]
+    TransactionBuildFailed(String),
+
+    /// The provider couldn't prove a transaction is in the chain
+    #[error("Invalid inclusion proof: {0}")]
+    InvalidInclusionProof(String),
+
+    /// Two providers returned different data for the same transaction
+    #[error("Providers disagree: {0}")]
+    ProviderMismatch(String),
+
+    /// No provider could serve a request
+    #[error("No provider available: {0}")]
+    ProviderUnavailable(String),
+}
+
+impl ZKaneError {
+    /// Get the stable numeric code of the error.
+    pub fn code(&self) -> u16 {
+        match self {
+            ZKaneError::InvalidCommitment(_) => 1001,
+            ZKaneError::DuplicateCommitment(_) => 1002,
+            ZKaneError::InvalidNullifier(_) => 1003,
+            ZKaneError::InvalidDenomination => 1004,
+            ZKaneError::InvalidFee(_) => 1005,
+            ZKaneError::InvalidProtocolFee(_) => 1006,
+            ZKaneError::TransactionParseError => 1007,
+            ZKaneError::CommitmentNotFound => 1008,
+            ZKaneError::SerializationError(_) => 1009,
+            ZKaneError::InvalidRecipient(_) => 1010,
+            ZKaneError::InvalidAmount(_) => 1011,
+            ZKaneError::UnsupportedNoteVersion { .. } => 1012,
+            ZKaneError::InvalidProof(_) => 2001,
+            ZKaneError::NullifierAlreadySpent => 2002,
+            ZKaneError::UnsupportedCircuitVersion(_) => 2003,
+            ZKaneError::InvalidVerifierKey(_) => 2004,
+            ZKaneError::ProofCancelled => 2005,
+            ZKaneError::CryptoError(_) => 2006,
+            ZKaneError::EntropyUnavailable(_) => 2007,
+            ZKaneError::ProofExpired { .. } => 2008,
+            ZKaneError::InvalidSignature(_) => 2009,
+            ZKaneError::OutputsMismatch => 2010,
+            ZKaneError::InvalidMerkleRoot => 3001,
+            ZKaneError::InvalidMerklePath => 3002,
+            ZKaneError::TreeFull => 3003,
+            ZKaneError::InvalidSnapshot(_) => 3004,
+            ZKaneError::StateDivergence { .. } => 3005,
+            ZKaneError::MerklePathMismatch { .. } => 3006,
+            ZKaneError::InvalidTreeHeight { .. } => 3007,
+            ZKaneError::UnknownCommitment => 4001,
+            ZKaneError::NotInitialized => 4002,
+            ZKaneError::AlreadyInitialized => 4003,
+            ZKaneError::DepositsPaused => 4004,
+            ZKaneError::Unauthorized(_) => 4005,
+            ZKaneError::PoolRetired => 4006,
+            ZKaneError::CommitmentReserved => 4007,
+            ZKaneError::CommitmentNotRegistered(_) => 4008,
+            ZKaneError::WrongPoolMode(_) => 4009,
+            ZKaneError::UnsupportedSchemaVersion { .. } => 4010,
+            ZKaneError::ReentrantCall => 4011,
+            ZKaneError::NoRewardClaim => 4012,
+            ZKaneError::RewardClaimNotRegistered(_) => 4013,
+            ZKaneError::RewardsUnderfunded { .. } => 4014,
+            #[cfg(feature = "deezel")]
+            ZKaneError::DeezelError(_) => 5001,
+            ZKaneError::PoolQueryFailed(_) => 5002,
+            ZKaneError::TransactionBuildFailed(_) => 5003,
+            ZKaneError::InvalidInclusionProof(_) => 5004,
+            ZKaneError::ProviderMismatch(_) => 5005,
+            ZKaneError::ProviderUnavailable(_) => 5006,
+        }
+    }
+
+    /// Format the error with its code, e.g. `ZK2002: Nullifier already spent`.
+    pub fn coded_message(&self) -> String {
+        format!("ZK{}: {}", self.code(), self)
+    }
+
+    /// Convert into the error a contract reverts with.
+    ///
+    /// The revert message is the [`coded_message`](ZKaneError::coded_message),
+    /// so callers can recover the code with [`ZKaneError::code_in`].
+    pub fn into_revert(self) -> anyhow::Error {
+        anyhow::anyhow!(self.coded_message())
+    }
+
+    /// Find the code in a message produced by [`ZKaneError::coded_message`].
+    ///
+    /// The coded message may be embedded in a longer one, such as a
+    /// contract revert reported by an indexer.
+    pub fn code_in(message: &str) -> Option<u16> {
+        message.match_indices("ZK").find_map(|(start, _)| {
+            let digits = message.get(start + 2..start + 6)?;
+            let coded = message[start + 6..].starts_with(':') && digits.bytes().all(|b| b.is_ascii_digit());
+            if coded {
+                digits.parse().ok()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl From<anyhow::Error> for ZKaneError {
+    /// Errors of the zkane-crypto primitives, which report through `anyhow`.
+    ///
+    /// A `ZKaneError` carried by the `anyhow` error is returned as it is.
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<ZKaneError>() {
+            Ok(error) => error,
+            Err(error) => ZKaneError::CryptoError(error.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ZKaneError {
+    fn from(error: serde_json::Error) -> Self {
+        ZKaneError::SerializationError(error.to_string())
+    }
 }
 
 /// Result type for ZKane operations.
@@ -808,6 +2241,177 @@ mod tests {
         assert_eq!(original, parsed);
     }
 
+    #[test]
+    fn test_bytes32_json_hex() {
+        let commitment = Commitment::new([0xab; 32]);
+        let json = serde_json::to_string(&commitment).unwrap();
+        assert_eq!(json, format!("\"0x{}\"", "ab".repeat(32)));
+        assert_eq!(serde_json::from_str::<Commitment>(&json).unwrap(), commitment);
+        assert_eq!(serde_json::to_string(&Secret::new([1; 32])).unwrap(), format!("\"0x{}\"", "01".repeat(32)));
+
+        // Unprefixed hex and the byte arrays of earlier JSON still decode
+        let unprefixed = format!("\"{}\"", "ab".repeat(32));
+        assert_eq!(serde_json::from_str::<NullifierHash>(&unprefixed).unwrap().0, [0xab; 32]);
+        let legacy = serde_json::to_string(&[7u8; 32]).unwrap();
+        assert_eq!(serde_json::from_str::<Nullifier>(&legacy).unwrap(), Nullifier::new([7; 32]));
+
+        assert!(serde_json::from_str::<Commitment>("\"0xabcd\"").is_err());
+        assert!(serde_json::from_str::<Commitment>(&serde_json::to_string(&vec![7u8; 33]).unwrap()).is_err());
+        assert!(serde_json::from_str::<Commitment>(&serde_json::to_string(&vec![7u8; 31]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_commitment_parse_packed() {
+        assert!(Commitment::parse_packed(&[]).unwrap().is_empty());
+        assert!(Commitment::parse_packed(&[0u8; 33]).is_err());
+
+        let commitments = Commitment::parse_packed(&[[7u8; 32], [8u8; 32], [9u8; 32]].concat()).unwrap();
+        assert_eq!(commitments.len(), 3);
+        assert_eq!(commitments[2], Commitment::new([9u8; 32]));
+    }
+
+    #[test]
+    fn test_nullifier_hash_u128_pair() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        bytes[16] = 2;
+        let hash = NullifierHash::new(bytes);
+        assert_eq!(hash.to_u128_pair(), (1, 2));
+        let (low, high) = hash.to_u128_pair();
+        assert_eq!(NullifierHash::from_u128_pair(low, high), hash);
+    }
+
+    #[test]
+    fn test_pool_record_roundtrip() {
+        let record = PoolRecord {
+            asset_id: ZkAssetId { block: 2, tx: 1 },
+            denomination: 1000000,
+            pool_id: ZkAssetId { block: 6, tx: 42 },
+            deposit_count: 7,
+            created_block: 840000,
+            template_version: 2,
+            circuit_version: CIRCUIT_VERSION,
+        };
+        let bytes = record.to_bytes();
+        assert_eq!(bytes.len(), PoolRecord::SIZE);
+        assert_eq!(PoolRecord::from_bytes(&bytes).unwrap(), record);
+        assert!(PoolRecord::from_bytes(&bytes[1..]).is_err());
+
+        // Records from before templates have no versions
+        let legacy = PoolRecord::from_bytes(&bytes[..PoolRecord::LEGACY_SIZE]).unwrap();
+        assert_eq!((legacy.template_version, legacy.circuit_version), (0, 0));
+        assert_eq!(legacy.created_block, record.created_block);
+
+        let mut page = 5u128.to_le_bytes().to_vec();
+        page.extend_from_slice(&bytes);
+        page.extend_from_slice(&bytes);
+        let (total, records) = PoolRecord::parse_page(&page).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(records, vec![record, record]);
+
+        page.pop();
+        assert!(PoolRecord::parse_page(&page).is_err());
+    }
+
+    #[test]
+    fn test_pool_template_roundtrip() {
+        let template = PoolTemplate {
+            template_id: ZkAssetId { block: 4, tx: 0x7a6b },
+            circuit_version: 2,
+            registered_block: 840000,
+        };
+        let bytes = template.to_bytes();
+        assert_eq!(bytes.len(), PoolTemplate::SIZE);
+        assert_eq!(PoolTemplate::from_bytes(&bytes).unwrap(), template);
+        assert!(PoolTemplate::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_pool_record_circuit_version() {
+        let mut record = PoolRecord {
+            asset_id: ZkAssetId { block: 2, tx: 1 },
+            denomination: 1000000,
+            pool_id: ZkAssetId { block: 6, tx: 42 },
+            deposit_count: 0,
+            created_block: 840000,
+            template_version: 1,
+            circuit_version: 2,
+        };
+        assert!(record.check_circuit_version(2).is_ok());
+        assert!(matches!(record.check_circuit_version(1), Err(ZKaneError::UnsupportedCircuitVersion(2))));
+
+        record.circuit_version = 0;
+        assert!(record.check_circuit_version(1).is_ok());
+    }
+
+    #[test]
+    fn test_asset_id_parse_display() {
+        let id: ZkAssetId = "2:1".parse().unwrap();
+        assert_eq!(id, ZkAssetId::new(2, 1));
+        assert_eq!(id.to_string(), "2:1");
+        assert!("2".parse::<ZkAssetId>().is_err());
+        assert!("2:x".parse::<ZkAssetId>().is_err());
+        assert_eq!(id.to_bech32().parse::<ZkAssetId>().unwrap(), id);
+
+        // Serialized as its fields, as stored notes have always been
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#"{"block":2,"tx":1}"#);
+        assert_eq!(serde_json::from_str::<ZkAssetId>(&json).unwrap(), id);
+        assert_eq!(ZkAssetId::from(AlkaneId::from(id)), id);
+    }
+
+    #[test]
+    fn test_derive_pool_id() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let pool_id = derive_pool_id(&asset_id, 1000000);
+        assert_eq!(pool_id.block, ZKANE_INSTANCE_BLOCK);
+        // Pinned so the frontend's copy of the derivation can be checked against it
+        assert_eq!(pool_id.tx, 105333083908969836867177120801899420502);
+
+        // Inputs that collide under XOR folding must not collide here
+        let swapped = ZkAssetId { block: 1, tx: 2 };
+        assert_ne!(derive_pool_id(&swapped, 1000000), pool_id);
+        let shifted = ZkAssetId { block: 2 ^ 1000000, tx: 1 };
+        assert_ne!(derive_pool_id(&shifted, 0), pool_id);
+    }
+
+    #[test]
+    fn test_calculate_outputs_hash() {
+        use bitcoin::{Amount, ScriptBuf, TxOut};
+
+        let recipient = TxOut {
+            value: Amount::from_sat(546),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x00, 0x14, 0xab]),
+        };
+        let relayer = TxOut {
+            value: Amount::from_sat(1000),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+        };
+        let protostone = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return([0x5d; 8]),
+        };
+
+        // Same encoding as the frontend: value LE followed by the script hex
+        let mut input = Vec::new();
+        input.extend_from_slice(&546u64.to_le_bytes());
+        input.extend_from_slice(b"0014ab");
+        input.extend_from_slice(&1000u64.to_le_bytes());
+        input.extend_from_slice(b"51");
+        let expected: [u8; 32] = Sha256::digest(&input).into();
+
+        let outputs = [recipient.clone(), protostone, relayer.clone()];
+        assert_eq!(calculate_outputs_hash(&outputs), expected);
+        // Outputs are ordered
+        assert_ne!(calculate_outputs_hash(&[relayer.clone(), recipient.clone()]), expected);
+
+        // Each withdrawal of a batch is bound to its own outputs
+        let batch = [relayer.clone(), recipient.clone(), relayer.clone()];
+        assert_eq!(find_outputs_window(&batch, &expected), Some(1..3));
+        let other: [u8; 32] = calculate_outputs_hash(&[relayer, recipient.clone(), recipient]);
+        assert_eq!(find_outputs_window(&batch, &other), None);
+    }
+
     #[test]
     fn test_secret_random() {
         let secret1 = Secret::random();
@@ -822,6 +2426,30 @@ mod tests {
         assert_ne!(nullifier1, nullifier2);
     }
 
+    #[test]
+    fn test_secret_constant_time_eq_and_zeroize() {
+        let secret = Secret::new([7u8; 32]);
+        assert!(bool::from(secret.ct_eq(&Secret::new([7u8; 32]))));
+        assert!(!bool::from(secret.ct_eq(&Secret::new([8u8; 32]))));
+
+        let mut nullifier = Nullifier::new([9u8; 32]);
+        nullifier.zeroize();
+        assert_eq!(nullifier.as_bytes(), &[0u8; 32]);
+
+        let mut note = DepositNote::new(
+            Secret::new([1u8; 32]),
+            Nullifier::new([2u8; 32]),
+            Commitment::new([3u8; 32]),
+            ZkAssetId { block: 2, tx: 1 },
+            1000,
+            0,
+        );
+        note.zeroize();
+        assert_eq!(note.secret.as_bytes(), &[0u8; 32]);
+        assert_eq!(note.nullifier.as_bytes(), &[0u8; 32]);
+        assert_eq!(note.commitment, Commitment::new([3u8; 32]));
+    }
+
     #[test]
     fn test_merkle_path_validation() {
         let elements = vec![[1u8; 32], [2u8; 32]];
@@ -839,12 +2467,31 @@ mod tests {
     #[test]
     fn test_zkane_config_max_deposits() {
         let config = ZKaneConfig::new(
-            SerializableAlkaneId { block: 1, tx: 1 },
+            ZkAssetId { block: 1, tx: 1 },
             1000,
             10,
             vec![],
         );
         assert_eq!(config.max_deposits(), 1024); // 2^10
+
+        let config = ZKaneConfig::new(ZkAssetId { block: 1, tx: 1 }, 1000, 64, vec![]);
+        assert_eq!(config.max_deposits(), u64::MAX);
+    }
+
+    #[test]
+    fn test_zkane_config_tree_height_bounds() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        for height in [MIN_TREE_HEIGHT, 20, MAX_TREE_HEIGHT] {
+            let config = ZKaneConfig::try_new(asset_id, 1000, height, vec![]).unwrap();
+            assert_eq!(config.tree_height, height);
+        }
+        for height in [0, 3, MIN_TREE_HEIGHT - 1, MAX_TREE_HEIGHT + 1, 64, u32::MAX] {
+            let err = ZKaneConfig::try_new(asset_id, 1000, height, vec![]).unwrap_err();
+            assert!(matches!(err, ZKaneError::InvalidTreeHeight { height: found, .. } if found == u128::from(height)));
+            assert_eq!(err.code(), 3007);
+        }
+        assert_eq!(validate_tree_height(20).unwrap(), 20);
+        assert!(validate_tree_height(u128::from(u32::MAX) + 20).is_err());
     }
 
     #[test]
@@ -852,7 +2499,7 @@ mod tests {
         let secret = Secret::random();
         let nullifier = Nullifier::random();
         let commitment = Commitment::new([42u8; 32]);
-        let asset_id = SerializableAlkaneId { block: 2, tx: 1 };
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
 
         let note = DepositNote::new(
             secret,
@@ -868,18 +2515,40 @@ mod tests {
         assert_eq!(note.leaf_index, 5);
     }
 
+    #[test]
+    fn test_deposit_note_versions() {
+        let mut note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 1000);
+        assert_eq!(note.version, DEPOSIT_NOTE_VERSION);
+        assert!(!serde_json::to_string(&note).unwrap().contains("extensions"));
+        note.set_extension("pool_id", "2:7");
+        let decoded: DepositNote = serde_json::from_str(&serde_json::to_string(&note).unwrap()).unwrap();
+        assert_eq!(decoded.extension("pool_id"), Some("2:7"));
+        assert_eq!(decoded.extension("insertion_height"), None);
+
+        let mut legacy = serde_json::to_value(&note).unwrap();
+        legacy.as_object_mut().unwrap().remove("version");
+        let mut legacy: DepositNote = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.version, 1);
+        legacy.upgrade().unwrap();
+        assert_eq!(legacy.version, DEPOSIT_NOTE_VERSION);
+
+        legacy.version = DEPOSIT_NOTE_VERSION + 1;
+        let err = legacy.check_version().unwrap_err();
+        assert_eq!(err.code(), 1012);
+    }
+
     #[test]
     fn test_withdrawal_proof_creation() {
         let proof_bytes = vec![1, 2, 3, 4];
         let merkle_root = [42u8; 32];
         let nullifier_hash = NullifierHash::new([1u8; 32]);
-        let recipient = 12345u128;
+        let recipient = Recipient::new(bitcoin::ScriptBuf::from_bytes(vec![0x51]));
 
         let proof = WithdrawalProof::new(
             proof_bytes.clone(),
             merkle_root,
             nullifier_hash,
-            recipient,
+            recipient.clone(),
         );
 
         assert_eq!(proof.proof, proof_bytes);
@@ -888,4 +2557,171 @@ mod tests {
         assert_eq!(proof.recipient, recipient);
         assert_eq!(proof.proof_size(), 4);
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_withdrawal_proof_relayer_fee() {
+        let proof = WithdrawalProof::new(vec![], [0u8; 32], NullifierHash::new([1u8; 32]), Recipient::default());
+        assert!(!proof.is_relayed());
+        assert_eq!(proof.recipient_amount(1000).unwrap(), 1000);
+
+        let relayed = proof.clone().with_relayer([7u8; 32], 100);
+        assert!(relayed.is_relayed());
+        assert_eq!(relayed.recipient_amount(1000).unwrap(), 900);
+
+        // Fee larger than the denomination
+        assert!(relayed.validate_fee(99).is_err());
+
+        // Fee without a relayer output
+        let no_output = proof.with_relayer([0u8; 32], 100);
+        assert!(no_output.validate_fee(1000).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_proof_expiry() {
+        let proof = WithdrawalProof::new(vec![], [0u8; 32], NullifierHash::new([1u8; 32]), Recipient::default());
+        assert!(!proof.expires());
+        assert!(proof.validate_expiry(u64::MAX).is_ok());
+
+        let expiring = proof.with_expiry(100);
+        assert!(expiring.validate_expiry(100).is_ok());
+        let err = expiring.validate_expiry(101).unwrap_err();
+        assert!(matches!(err, ZKaneError::ProofExpired { not_after_height: 100, height: 101 }));
+        assert_eq!(err.code(), 2008);
+    }
+
+    #[test]
+    fn test_protocol_fee_conserves_denomination() {
+        let collector = ZkAssetId { block: 2, tx: 9 };
+        assert!(ProtocolFee::new(MAX_PROTOCOL_FEE_BPS + 1, collector).is_err());
+
+        let fee = ProtocolFee::new(25, collector).unwrap();
+        assert_eq!(fee.amount(1000000), 2500);
+        // Rounds down
+        assert_eq!(fee.amount(399), 0);
+        assert_eq!(fee.amount(u128::MAX), u128::MAX / 10_000 * 25 + u128::MAX % 10_000 * 25 / 10_000);
+
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let no_fee = ZKaneConfig::new(asset_id, 1000000, 20, vec![]);
+        assert_eq!(no_fee.protocol_fee_amount(), 0);
+
+        for denomination in [1u128, 399, 10_001, 1000000, u128::MAX] {
+            let config = ZKaneConfig::new(asset_id, denomination, 20, vec![]).with_protocol_fee(fee);
+            for relayer_fee in [0, 1, denomination / 2] {
+                let amounts = config.withdrawal_amounts(relayer_fee).unwrap();
+                assert_eq!(amounts.total(), denomination);
+                assert_eq!(amounts.relayer, relayer_fee);
+                assert_eq!(amounts.protocol, fee.amount(denomination));
+            }
+        }
+
+        // The relayer can't be paid out of the protocol's share
+        let config = ZKaneConfig::new(asset_id, 1000000, 20, vec![]).with_protocol_fee(fee);
+        assert!(config.withdrawal_amounts(997500).is_ok());
+        assert!(config.withdrawal_amounts(997501).is_err());
+
+        // Splits pay the protocol fee on the public amount only
+        let amounts = config.split_amounts(400000, 1000).unwrap();
+        assert_eq!((amounts.protocol, amounts.relayer, amounts.total()), (1000, 1000, 400000));
+        assert!(matches!(config.split_amounts(1000001, 0), Err(ZKaneError::InvalidDenomination)));
+
+        assert_eq!(ProtocolFee::from_bytes(&fee.to_bytes()).unwrap(), Some(fee));
+        assert_eq!(ProtocolFee::from_bytes(&[0u8; ProtocolFee::SIZE]).unwrap(), None);
+
+        // Configs stored before protocol fees existed still parse
+        let legacy = r#"{"asset_id":{"block":2,"tx":1},"denomination":5,"tree_height":20,"verifier_key":[]}"#;
+        let config: ZKaneConfig = serde_json::from_str(legacy).unwrap();
+        assert!(config.protocol_fee.is_none());
+    }
+
+    #[test]
+    fn test_pool_modes() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let fixed = ZKaneConfig::new(asset_id, 1000, 20, vec![]);
+        assert!(fixed.check_deposit_amount(1000).is_ok());
+        assert!(matches!(fixed.check_deposit_amount(999), Err(ZKaneError::InvalidDenomination)));
+
+        // Any non-zero amount, and splits of any size, in a variable pool
+        let fee = ProtocolFee::new(25, ZkAssetId { block: 2, tx: 9 }).unwrap();
+        let variable = ZKaneConfig::variable(asset_id, 20, vec![]).with_protocol_fee(fee);
+        assert_eq!(variable.denomination, 0);
+        assert!(variable.check_deposit_amount(1).is_ok());
+        assert!(variable.check_deposit_amount(7_777_777).is_ok());
+        assert!(variable.check_deposit_amount(0).is_err());
+        assert_eq!(variable.split_amounts(400_000, 0).unwrap().protocol, 1000);
+
+        // Whole-note withdrawals need a fixed value
+        let err = variable.withdrawal_amounts(0).unwrap_err();
+        assert_eq!(err.code(), 4009);
+        assert_eq!(err.to_string(), "Operation not supported by a variable pool");
+
+        // Configs stored before pool modes existed are fixed
+        let json = serde_json::to_string(&variable).unwrap();
+        assert!(json.contains(r#""mode":"variable""#));
+        let legacy = r#"{"asset_id":{"block":2,"tx":1},"denomination":5,"tree_height":20,"verifier_key":[]}"#;
+        assert_eq!(serde_json::from_str::<ZKaneConfig>(legacy).unwrap().mode, PoolMode::Fixed);
+    }
+
+    #[test]
+    fn test_circuit_version_check() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let config = ZKaneConfig::new(asset_id, 1000, 20, vec![]).with_verifier_key(2, vec![1, 2, 3]);
+        assert_eq!(config.verifier_key, vec![1, 2, 3]);
+
+        let proof = WithdrawalProof::new(vec![1], [0u8; 32], NullifierHash::new([0u8; 32]), Recipient::default());
+        assert!(matches!(
+            config.check_circuit_version(&proof),
+            Err(ZKaneError::UnsupportedCircuitVersion(CIRCUIT_VERSION))
+        ));
+        assert!(config.check_circuit_version(&proof.with_circuit_version(2)).is_ok());
+
+        // Configs stored before circuit versioning use the first circuit
+        let legacy = r#"{"asset_id":{"block":2,"tx":1},"denomination":5,"tree_height":20,"verifier_key":[]}"#;
+        let config: ZKaneConfig = serde_json::from_str(legacy).unwrap();
+        assert_eq!(config.circuit_version, CIRCUIT_VERSION);
+    }
+
+    #[test]
+    fn test_tree_hash_config() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let config = ZKaneConfig::new(asset_id, 1000, 20, vec![]).with_tree_hash(TreeHash::Sha256);
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""tree_hash":"sha256""#));
+        assert_eq!(serde_json::from_str::<ZKaneConfig>(&json).unwrap().tree_hash, TreeHash::Sha256);
+
+        // Configs stored before the hash was configurable use Blake2s
+        let legacy = r#"{"asset_id":{"block":2,"tx":1},"denomination":5,"tree_height":20,"verifier_key":[]}"#;
+        let config: ZKaneConfig = serde_json::from_str(legacy).unwrap();
+        assert_eq!(config.tree_hash, TreeHash::Blake2s);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let errors = [
+            ZKaneError::InvalidCommitment(String::new()),
+            ZKaneError::NullifierAlreadySpent,
+            ZKaneError::TreeFull,
+            ZKaneError::DepositsPaused,
+            ZKaneError::TransactionBuildFailed(String::new()),
+            ZKaneError::OutputsMismatch,
+        ];
+        let codes: Vec<u16> = errors.iter().map(ZKaneError::code).collect();
+        assert_eq!(codes, vec![1001, 2002, 3003, 4004, 5003, 2010]);
+
+        let message = ZKaneError::NullifierAlreadySpent.coded_message();
+        assert_eq!(message, "ZK2002: Nullifier already spent");
+        assert_eq!(ZKaneError::code_in(&format!("ALKANES: revert: {}", message)), Some(2002));
+        assert_eq!(ZKaneError::code_in("ZKane Error: ZK12: no code"), None);
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        let error: ZKaneError = anyhow::anyhow!("bad field element").into();
+        assert!(matches!(error, ZKaneError::CryptoError(ref message) if message == "bad field element"));
+
+        // Errors passed through anyhow keep their variant
+        let error: ZKaneError = anyhow::Error::from(ZKaneError::TreeFull).into();
+        assert!(matches!(error, ZKaneError::TreeFull));
+
+        let error: ZKaneError = serde_json::from_str::<ZKaneConfig>("{").unwrap_err().into();
+        assert_eq!(error.code(), 1009);
+    }
+}
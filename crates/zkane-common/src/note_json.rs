@@ -0,0 +1,243 @@
+//! # Note JSON Schema
+//!
+//! The versioned JSON schema of deposit note files, shared by the CLI, the
+//! WASM bindings and every frontend so a note saved by one opens in the
+//! others. Version 2 is:
+//!
+//! | Field | Format |
+//! |-------|--------|
+//! | `version` | number, [`NOTE_JSON_VERSION`] |
+//! | `secret` | 64 lowercase hex characters |
+//! | `nullifier` | 64 lowercase hex characters |
+//! | `commitment` | 64 lowercase hex characters |
+//! | `asset` | object with `block` and `tx`, decimal strings |
+//! | `denomination` | decimal string |
+//! | `leaf_index` | number |
+//! | `extensions` | object of strings, omitted if empty |
+//! | `checksum` | 8 hex characters, see below |
+//!
+//! The `u128` fields are decimal strings, as JavaScript numbers can't hold
+//! them. The checksum is the start of the SHA-256 of the secret, nullifier
+//! and commitment bytes, followed by the asset block, asset tx and
+//! denomination as 16-byte and the leaf index as 4-byte little-endian
+//! integers, then the key and value of each extension, each prefixed with its
+//! 4-byte little-endian length. It catches notes damaged by hand, not
+//! tampering; the commitment check of the note itself does that. Unknown
+//! fields are ignored, an unknown version is rejected.
+//!
+//! Version 1 is version 2 without `extensions`, and decodes into a note of
+//! version 1.
+//!
+//! ```rust
+//! use zkane_common::{DepositNote, ZkAssetId};
+//!
+//! let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 100_000);
+//! let json = note.to_json();
+//! assert!(json.contains("\"denomination\":\"100000\""));
+//! assert_eq!(DepositNote::from_json(&json)?.commitment, note.commitment);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{Commitment, DepositNote, Nullifier, Secret, ZKaneError, ZKaneResult, ZkAssetId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Current version of the note JSON schema
+pub const NOTE_JSON_VERSION: u32 = 2;
+
+const CHECKSUM_LEN: usize = 4;
+
+#[derive(Deserialize)]
+struct NoteJsonVersion {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AssetJson {
+    block: String,
+    tx: String,
+}
+
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct NoteJson {
+    version: u32,
+    secret: String,
+    nullifier: String,
+    commitment: String,
+    #[zeroize(skip)]
+    asset: AssetJson,
+    denomination: String,
+    leaf_index: u32,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[zeroize(skip)]
+    extensions: BTreeMap<String, String>,
+    checksum: String,
+}
+
+fn invalid(message: impl std::fmt::Display) -> ZKaneError {
+    ZKaneError::SerializationError(format!("invalid note JSON: {}", message))
+}
+
+fn checksum(note: &DepositNote) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(note.secret.as_bytes());
+    hasher.update(note.nullifier.as_bytes());
+    hasher.update(note.commitment.as_bytes());
+    hasher.update(note.asset_id.block.to_le_bytes());
+    hasher.update(note.asset_id.tx.to_le_bytes());
+    hasher.update(note.denomination.to_le_bytes());
+    hasher.update(note.leaf_index.to_le_bytes());
+    for (key, value) in &note.extensions {
+        for field in [key, value] {
+            hasher.update((field.len() as u32).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    hex::encode(&hasher.finalize()[..CHECKSUM_LEN])
+}
+
+fn parse_u128(field: &str, value: &str) -> ZKaneResult<u128> {
+    value.parse().map_err(|_| invalid(format!("{} must be a decimal string, got {:?}", field, value)))
+}
+
+impl DepositNote {
+    /// Encode the note in the current note JSON schema.
+    ///
+    /// The note is written as the current [`DEPOSIT_NOTE_VERSION`](crate::DEPOSIT_NOTE_VERSION),
+    /// which every older version migrates to unchanged.
+    pub fn to_json(&self) -> String {
+        let json = NoteJson {
+            version: NOTE_JSON_VERSION,
+            secret: self.secret.to_hex(),
+            nullifier: self.nullifier.to_hex(),
+            commitment: self.commitment.to_hex(),
+            asset: AssetJson { block: self.asset_id.block.to_string(), tx: self.asset_id.tx.to_string() },
+            denomination: self.denomination.to_string(),
+            leaf_index: self.leaf_index,
+            extensions: self.extensions.clone(),
+            checksum: checksum(self),
+        };
+        serde_json::to_string(&json).expect("a note always encodes")
+    }
+
+    /// Decode a note written by [`Self::to_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the JSON is malformed,
+    /// its version is newer than [`NOTE_JSON_VERSION`] or its checksum
+    /// doesn't match.
+    pub fn from_json(json: &str) -> ZKaneResult<Self> {
+        let NoteJsonVersion { version } = serde_json::from_str(json).map_err(invalid)?;
+        if !(1..=NOTE_JSON_VERSION).contains(&version) {
+            return Err(invalid(format!(
+                "unsupported version {}, expected at most {}",
+                version, NOTE_JSON_VERSION
+            )));
+        }
+        let json: NoteJson = serde_json::from_str(json).map_err(invalid)?;
+        let secret = Secret::from_hex(&json.secret).map_err(|e| invalid(format!("secret: {}", e)))?;
+        let nullifier = Nullifier::from_hex(&json.nullifier).map_err(|e| invalid(format!("nullifier: {}", e)))?;
+        let commitment = Commitment::from_hex(&json.commitment).map_err(|e| invalid(format!("commitment: {}", e)))?;
+        let asset_id = ZkAssetId {
+            block: parse_u128("asset block", &json.asset.block)?,
+            tx: parse_u128("asset tx", &json.asset.tx)?,
+        };
+        let denomination = parse_u128("denomination", &json.denomination)?;
+        let mut note = DepositNote::new(secret, nullifier, commitment, asset_id, denomination, json.leaf_index);
+        note.version = version;
+        if version > 1 {
+            note.extensions = json.extensions.clone();
+        }
+        if !json.checksum.eq_ignore_ascii_case(&checksum(&note)) {
+            return Err(invalid("checksum mismatch"));
+        }
+        Ok(note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_note_json_roundtrip() {
+        let note = DepositNote::random(ZkAssetId { block: 2, tx: u128::MAX }, u128::MAX);
+        let json = note.to_json();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], NOTE_JSON_VERSION);
+        assert_eq!(value["secret"], note.secret.to_hex());
+        assert_eq!(value["asset"]["tx"], u128::MAX.to_string());
+
+        let decoded = DepositNote::from_json(&json).unwrap();
+        assert_eq!(decoded.secret, note.secret);
+        assert_eq!(decoded.nullifier, note.nullifier);
+        assert_eq!(decoded.commitment, note.commitment);
+        assert_eq!(decoded.asset_id, note.asset_id);
+        assert_eq!(decoded.denomination, note.denomination);
+    }
+
+    #[test]
+    fn test_note_json_rejects() {
+        let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 1000);
+        let value: Value = serde_json::from_str(&note.to_json()).unwrap();
+
+        let mut future = value.clone();
+        future["version"] = (NOTE_JSON_VERSION + 1).into();
+        let err = DepositNote::from_json(&future.to_string()).unwrap_err();
+        assert!(err.to_string().contains(&format!("unsupported version {}", NOTE_JSON_VERSION + 1)));
+
+        let mut damaged = value.clone();
+        damaged["leaf_index"] = 1.into();
+        assert!(DepositNote::from_json(&damaged.to_string()).is_err());
+
+        let mut numeric = value;
+        numeric["denomination"] = 1000.into();
+        assert!(matches!(
+            DepositNote::from_json(&numeric.to_string()),
+            Err(ZKaneError::SerializationError(_))
+        ));
+
+        // The serde encoding of a note isn't a note file
+        assert!(DepositNote::from_json(&serde_json::to_string(&note).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_note_json_extensions() {
+        let mut note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 1000);
+        note.set_extension("insertion_height", "840000");
+        let json = note.to_json();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["extensions"]["insertion_height"], "840000");
+
+        let decoded = DepositNote::from_json(&json).unwrap();
+        assert_eq!(decoded.version, crate::DEPOSIT_NOTE_VERSION);
+        assert_eq!(decoded.extension("insertion_height"), Some("840000"));
+
+        // The checksum covers the extensions
+        let mut damaged = value;
+        damaged["extensions"]["insertion_height"] = "840001".into();
+        assert!(DepositNote::from_json(&damaged.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_note_json_reads_version_1() {
+        let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 1000);
+        let mut value: Value = serde_json::from_str(&note.to_json()).unwrap();
+        // A version 1 file has no extensions, and the same checksum
+        value["version"] = 1.into();
+        assert!(value.get("extensions").is_none());
+
+        let decoded = DepositNote::from_json(&value.to_string()).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert_eq!(decoded.commitment, note.commitment);
+        assert!(decoded.extensions.is_empty());
+
+        // Re-encoding migrates it to the current version
+        let reencoded: Value = serde_json::from_str(&decoded.to_json()).unwrap();
+        assert_eq!(reencoded["version"], NOTE_JSON_VERSION);
+    }
+}
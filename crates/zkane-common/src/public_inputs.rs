@@ -0,0 +1,272 @@
+//! # Withdrawal Public Inputs
+//!
+//! The public inputs of the Noir withdrawal circuit, in the order `main`
+//! declares them:
+//!
+//! | Index | Input |
+//! |-------|-------|
+//! | 0 | merkle_root |
+//! | 1 | nullifier_hash |
+//! | 2 | outputs_hash |
+//! | 3 | recipients_hash |
+//! | 4 | relayer_output_hash |
+//! | 5 | fee |
+//! | 6 | not_after_height |
+//!
+//! Each input is a BN254 field element, encoded as 32 big-endian bytes.
+//! Hashes are read as big-endian integers and reduced modulo the field order,
+//! as the circuit does when it is given a hash that doesn't fit; the fee and
+//! the expiry height are left-padded with zeros. [`PublicInputs`] holds the reduced values, so the
+//! prover and the verifier compare and encode exactly what the circuit sees.
+
+use crate::{WithdrawalWitness, ZKaneError, ZKaneResult};
+use serde::{Deserialize, Serialize};
+
+/// Number of public inputs of the withdrawal circuit
+pub const PUBLIC_INPUT_COUNT: usize = 7;
+
+/// Order of the BN254 scalar field, big-endian
+pub const BN254_FIELD_ORDER: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Public inputs of a withdrawal proof, as the circuit sees them.
+///
+/// # Example
+///
+/// ```rust
+/// use zkane_common::{PublicInputs, BN254_FIELD_ORDER};
+///
+/// // A hash at the field order is the zero element
+/// let inputs = PublicInputs::new([1u8; 32], BN254_FIELD_ORDER, [0u8; 32], [0u8; 32], [0u8; 32], 500, 0);
+/// assert_eq!(inputs.nullifier_hash, [0u8; 32]);
+///
+/// let fields = inputs.to_fields();
+/// assert_eq!(fields[5][31], 0xf4);
+/// assert_eq!(PublicInputs::from_bytes(&inputs.to_bytes())?, inputs);
+/// # Ok::<(), zkane_common::ZKaneError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PublicInputs {
+    /// Merkle root the note is proven against
+    pub merkle_root: [u8; 32],
+    /// Hash of the spent note's nullifier
+    pub nullifier_hash: [u8; 32],
+    /// Hash of the withdrawal transaction outputs
+    pub outputs_hash: [u8; 32],
+    /// Hash of the recipient outputs (zero if unbound)
+    pub recipients_hash: [u8; 32],
+    /// Hash of the relayer's fee output (zero if self-relayed)
+    pub relayer_output_hash: [u8; 32],
+    /// Fee paid to the relayer
+    pub fee: u128,
+    /// Last block height the proof may be spent at (0 if it never expires)
+    #[serde(default)]
+    pub not_after_height: u64,
+}
+
+impl PublicInputs {
+    /// Collect the public inputs, reducing each hash into the field.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        merkle_root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        outputs_hash: [u8; 32],
+        recipients_hash: [u8; 32],
+        relayer_output_hash: [u8; 32],
+        fee: u128,
+        not_after_height: u64,
+    ) -> Self {
+        Self {
+            merkle_root: reduce(merkle_root),
+            nullifier_hash: reduce(nullifier_hash),
+            outputs_hash: reduce(outputs_hash),
+            recipients_hash: reduce(recipients_hash),
+            relayer_output_hash: reduce(relayer_output_hash),
+            fee,
+            not_after_height,
+        }
+    }
+
+    /// Collect the public inputs of a withdrawal witness.
+    pub fn from_witness(witness: &WithdrawalWitness) -> Self {
+        let proof = &witness.proof;
+        Self::new(
+            proof.merkle_root,
+            *proof.nullifier_hash.as_bytes(),
+            witness.outputs_hash,
+            proof.recipients_hash,
+            proof.relayer_output_hash,
+            proof.fee,
+            proof.not_after_height,
+        )
+    }
+
+    /// Get the inputs as field elements, in circuit order.
+    pub fn to_fields(&self) -> [[u8; 32]; PUBLIC_INPUT_COUNT] {
+        let mut fee = [0u8; 32];
+        fee[16..].copy_from_slice(&self.fee.to_be_bytes());
+        let mut not_after_height = [0u8; 32];
+        not_after_height[24..].copy_from_slice(&self.not_after_height.to_be_bytes());
+        [
+            self.merkle_root,
+            self.nullifier_hash,
+            self.outputs_hash,
+            self.recipients_hash,
+            self.relayer_output_hash,
+            fee,
+            not_after_height,
+        ]
+    }
+
+    /// Read the inputs from field elements in circuit order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if an element isn't reduced, the
+    /// fee doesn't fit in a `u128` or the expiry height in a `u64`.
+    pub fn from_fields(fields: &[[u8; 32]; PUBLIC_INPUT_COUNT]) -> ZKaneResult<Self> {
+        if let Some(index) = fields.iter().position(|field| !is_canonical(field)) {
+            return Err(ZKaneError::InvalidProof(format!("public input {} is not a field element", index)));
+        }
+        let [merkle_root, nullifier_hash, outputs_hash, recipients_hash, relayer_output_hash, fee, not_after_height] =
+            *fields;
+        let (high, low) = fee.split_at(16);
+        if high.iter().any(|&byte| byte != 0) {
+            return Err(ZKaneError::InvalidProof("fee public input exceeds 128 bits".to_string()));
+        }
+        let (height_high, height_low) = not_after_height.split_at(24);
+        if height_high.iter().any(|&byte| byte != 0) {
+            return Err(ZKaneError::InvalidProof("expiry height public input exceeds 64 bits".to_string()));
+        }
+        Ok(Self {
+            merkle_root,
+            nullifier_hash,
+            outputs_hash,
+            recipients_hash,
+            relayer_output_hash,
+            fee: u128::from_be_bytes(low.try_into().unwrap()),
+            not_after_height: u64::from_be_bytes(height_low.try_into().unwrap()),
+        })
+    }
+
+    /// Encode the inputs as their concatenated field elements.
+    ///
+    /// This is the layout of the public inputs file written by the prover.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_fields().concat()
+    }
+
+    /// Decode inputs encoded by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        if data.len() != PUBLIC_INPUT_COUNT * 32 {
+            return Err(ZKaneError::InvalidProof(format!(
+                "expected {} bytes of public inputs, got {}",
+                PUBLIC_INPUT_COUNT * 32,
+                data.len()
+            )));
+        }
+        let mut fields = [[0u8; 32]; PUBLIC_INPUT_COUNT];
+        for (field, chunk) in fields.iter_mut().zip(data.chunks_exact(32)) {
+            field.copy_from_slice(chunk);
+        }
+        Self::from_fields(&fields)
+    }
+
+    /// Get the inputs as `0x`-prefixed hex field elements, as Noir's
+    /// `Prover.toml` and the frontend take them.
+    pub fn to_hex_fields(&self) -> Vec<String> {
+        self.to_fields()
+            .iter()
+            .map(|field| format!("0x{}", hex::encode(field)))
+            .collect()
+    }
+}
+
+/// Check whether 32 big-endian bytes are below the field order.
+fn is_canonical(value: &[u8; 32]) -> bool {
+    value < &BN254_FIELD_ORDER
+}
+
+/// Reduce 32 big-endian bytes modulo the field order.
+///
+/// The order is above 2^253, so at most five subtractions are needed.
+fn reduce(mut value: [u8; 32]) -> [u8; 32] {
+    while !is_canonical(&value) {
+        let mut borrow = 0u16;
+        for (byte, order) in value.iter_mut().zip(BN254_FIELD_ORDER.iter()).rev() {
+            let difference = (*byte as u16).wrapping_sub(*order as u16).wrapping_sub(borrow);
+            *byte = difference as u8;
+            borrow = (difference >> 8) & 1;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Commitment, MerklePath, NullifierHash, WithdrawalProof};
+
+    #[test]
+    fn test_reduction() {
+        assert_eq!(reduce([0u8; 32]), [0u8; 32]);
+        assert_eq!(reduce(BN254_FIELD_ORDER), [0u8; 32]);
+
+        let mut one_above = BN254_FIELD_ORDER;
+        one_above[31] += 1;
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(reduce(one_above), one);
+
+        // 2^256 - 1 mod r, computed independently
+        let expected = hex::decode("0e0a77c19a07df2f666ea36f7879462e36fc76959f60cd29ac96341c4ffffffa").unwrap();
+        assert_eq!(reduce([0xff; 32]).to_vec(), expected);
+    }
+
+    #[test]
+    fn test_field_order_and_encoding() {
+        let proof = WithdrawalProof::new(vec![1, 2, 3], [1u8; 32], NullifierHash::new([2u8; 32]), Default::default())
+            .with_relayer([4u8; 32], 1000)
+            .with_expiry(850_000);
+        let witness = WithdrawalWitness {
+            proof,
+            path: MerklePath::new(vec![[0u8; 32]], vec![false]).unwrap(),
+            leaf_index: 0,
+            commitment: Commitment::new([9u8; 32]),
+            outputs_hash: [3u8; 32],
+        };
+
+        let inputs = PublicInputs::from_witness(&witness);
+        let fields = inputs.to_fields();
+        assert_eq!(fields[0], [1u8; 32]);
+        assert_eq!(fields[1], [2u8; 32]);
+        assert_eq!(fields[2], [3u8; 32]);
+        assert_eq!(fields[3], [0u8; 32]);
+        assert_eq!(fields[4], [4u8; 32]);
+        assert_eq!(&fields[5][..30], &[0u8; 30]);
+        assert_eq!(fields[5][30..], 1000u16.to_be_bytes());
+        assert_eq!(fields[6][24..], 850_000u64.to_be_bytes());
+
+        assert_eq!(inputs.to_bytes().len(), PUBLIC_INPUT_COUNT * 32);
+        assert_eq!(PublicInputs::from_bytes(&inputs.to_bytes()).unwrap(), inputs);
+        assert_eq!(inputs.to_hex_fields()[2], format!("0x{}", "03".repeat(32)));
+    }
+
+    #[test]
+    fn test_non_canonical_fields_are_rejected() {
+        let mut fields = PublicInputs::new([0u8; 32], [0u8; 32], [0u8; 32], [0u8; 32], [0u8; 32], 0, 0).to_fields();
+        fields[2] = [0xff; 32];
+        assert!(PublicInputs::from_fields(&fields).is_err());
+
+        fields[2] = [0u8; 32];
+        fields[5][0] = 1;
+        assert!(PublicInputs::from_fields(&fields).is_err());
+
+        fields[5][0] = 0;
+        fields[6][23] = 1;
+        assert!(PublicInputs::from_fields(&fields).is_err());
+        assert!(PublicInputs::from_bytes(&[0u8; 64]).is_err());
+    }
+}
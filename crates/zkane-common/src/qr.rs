@@ -0,0 +1,459 @@
+//! # QR Encoding
+//!
+//! Compact text encoding of deposit notes for QR codes, so notes can move
+//! between devices, e.g. an air-gapped signer and a phone, without typing
+//! hex.
+//!
+//! A note is encoded as:
+//!
+//! | Field | Size |
+//! |-------|------|
+//! | version | 1 |
+//! | secret | 32 |
+//! | nullifier | 32 |
+//! | commitment | 32 |
+//! | asset block, asset tx, denomination | LEB128 varints |
+//! | leaf index | 4 |
+//! | extension count, then each key and value | LEB128 varints, the strings length-prefixed |
+//! | has Merkle path | 1 |
+//! | Merkle path | rest, as in [`MerklePath::to_bytes`] |
+//! | checksum | 4, the start of the SHA-256 of the preceding bytes |
+//!
+//! Version 1 has no extensions, and decodes into a note of version 1.
+//!
+//! [`DepositNote::to_qr_payload`] writes `zkane:` followed by the base64url
+//! encoding, about 200 characters, which fits a single QR code. A note with
+//! its Merkle path can exceed what a phone reliably scans, so
+//! [`DepositNote::to_qr_frames`] splits the encoding into frames shown as an
+//! animated QR code, `zkane:<index>/<count>:<base64url chunk>`, which a
+//! [`QrFrameDecoder`] collects in any order.
+//!
+//! ```rust
+//! use zkane_common::{DepositNote, ZkAssetId};
+//!
+//! let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 100_000);
+//! let payload = note.to_qr_payload();
+//! assert!(payload.len() < 300);
+//! assert_eq!(DepositNote::from_qr_payload(&payload)?.commitment, note.commitment);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{Commitment, DepositNote, MerklePath, Nullifier, Secret, ZKaneError, ZKaneResult, ZkAssetId};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// Prefix of QR payloads and frames
+pub const QR_PAYLOAD_PREFIX: &str = "zkane:";
+
+/// Current version of the QR note encoding
+pub const QR_NOTE_VERSION: u8 = 2;
+
+/// Default number of encoded bytes per animated QR frame
+pub const DEFAULT_QR_FRAME_SIZE: usize = 120;
+
+/// Maximum number of frames of an animated QR note
+pub const MAX_QR_FRAMES: usize = 64;
+
+const CHECKSUM_LEN: usize = 4;
+
+fn invalid(message: impl std::fmt::Display) -> ZKaneError {
+    ZKaneError::SerializationError(format!("invalid QR note: {}", message))
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    Sha256::digest(data)[..CHECKSUM_LEN].try_into().unwrap()
+}
+
+/// Write `value` as an LEB128 varint.
+pub(crate) fn write_varint(data: &mut Vec<u8>, mut value: u128) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+/// Read an LEB128 varint, or `None` if it's truncated or overflows.
+pub(crate) fn read_varint(data: &mut &[u8]) -> Option<u128> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        let bits = u128::from(byte & 0x7f);
+        if shift == 126 && bits > 0x03 {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn varint(data: &mut &[u8]) -> ZKaneResult<u128> {
+    read_varint(data).ok_or_else(|| invalid("truncated or overflowing varint"))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> ZKaneResult<&'a [u8]> {
+    if data.len() < len {
+        return Err(invalid("truncated"));
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn take32(data: &mut &[u8]) -> ZKaneResult<[u8; 32]> {
+    Ok(take(data, 32)?.try_into().unwrap())
+}
+
+fn write_string(data: &mut Vec<u8>, value: &str) {
+    write_varint(data, value.len() as u128);
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn take_string(data: &mut &[u8]) -> ZKaneResult<String> {
+    let len = usize::try_from(varint(data)?).map_err(|_| invalid("string too long"))?;
+    String::from_utf8(take(data, len)?.to_vec()).map_err(|_| invalid("extension is not UTF-8"))
+}
+
+/// Encode a note and optional Merkle path, with their checksum.
+fn encode_note(note: &DepositNote, path: Option<&MerklePath>) -> ZKaneResult<Zeroizing<Vec<u8>>> {
+    let mut data = Zeroizing::new(Vec::with_capacity(160));
+    data.push(QR_NOTE_VERSION);
+    data.extend_from_slice(note.secret.as_bytes());
+    data.extend_from_slice(note.nullifier.as_bytes());
+    data.extend_from_slice(note.commitment.as_bytes());
+    write_varint(&mut data, note.asset_id.block);
+    write_varint(&mut data, note.asset_id.tx);
+    write_varint(&mut data, note.denomination);
+    data.extend_from_slice(&note.leaf_index.to_le_bytes());
+    write_varint(&mut data, note.extensions.len() as u128);
+    for (key, value) in &note.extensions {
+        write_string(&mut data, key);
+        write_string(&mut data, value);
+    }
+    match path {
+        Some(path) => {
+            data.push(1);
+            data.extend_from_slice(&path.to_bytes()?);
+        }
+        None => data.push(0),
+    }
+    let sum = checksum(&data);
+    data.extend_from_slice(&sum);
+    Ok(data)
+}
+
+/// Decode what [`encode_note`] wrote.
+fn decode_note(data: &[u8]) -> ZKaneResult<(DepositNote, Option<MerklePath>)> {
+    if data.len() < CHECKSUM_LEN {
+        return Err(invalid("truncated"));
+    }
+    let (mut data, sum) = data.split_at(data.len() - CHECKSUM_LEN);
+    if checksum(data) != sum {
+        return Err(invalid("checksum mismatch"));
+    }
+    let version = take(&mut data, 1)?[0];
+    if !(1..=QR_NOTE_VERSION).contains(&version) {
+        return Err(invalid(format!("unsupported version {}", version)));
+    }
+    let secret = Secret::new(take32(&mut data)?);
+    let nullifier = Nullifier::new(take32(&mut data)?);
+    let commitment = Commitment::new(take32(&mut data)?);
+    let asset_id = ZkAssetId {
+        block: varint(&mut data)?,
+        tx: varint(&mut data)?,
+    };
+    let denomination = varint(&mut data)?;
+    let leaf_index = u32::from_le_bytes(take(&mut data, 4)?.try_into().unwrap());
+    let mut extensions = BTreeMap::new();
+    if version > 1 {
+        for _ in 0..varint(&mut data)? {
+            let key = take_string(&mut data)?;
+            extensions.insert(key, take_string(&mut data)?);
+        }
+    }
+    let path = match take(&mut data, 1)?[0] {
+        0 if data.is_empty() => None,
+        0 => return Err(invalid(format!("{} trailing bytes", data.len()))),
+        1 => Some(MerklePath::from_bytes(data)?),
+        flag => return Err(invalid(format!("unknown path flag {}", flag))),
+    };
+    let mut note = DepositNote::new(secret, nullifier, commitment, asset_id, denomination, leaf_index);
+    note.version = u32::from(version);
+    note.extensions = extensions;
+    Ok((note, path))
+}
+
+impl DepositNote {
+    /// Encode the note as a QR payload, `zkane:` followed by base64url.
+    pub fn to_qr_payload(&self) -> String {
+        let data = encode_note(self, None).expect("a note without a path always encodes");
+        format!("{}{}", QR_PAYLOAD_PREFIX, URL_SAFE_NO_PAD.encode(&*data))
+    }
+
+    /// Decode a note from a QR payload written by [`Self::to_qr_payload`].
+    ///
+    /// Surrounding whitespace is ignored, and so is a Merkle path, if the
+    /// payload carries one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the payload is
+    /// malformed or its checksum doesn't match.
+    pub fn from_qr_payload(payload: &str) -> ZKaneResult<Self> {
+        let encoded = payload
+            .trim()
+            .strip_prefix(QR_PAYLOAD_PREFIX)
+            .ok_or_else(|| invalid(format!("missing {} prefix", QR_PAYLOAD_PREFIX)))?;
+        let data = Zeroizing::new(URL_SAFE_NO_PAD.decode(encoded).map_err(invalid)?);
+        Ok(decode_note(&data)?.0)
+    }
+
+    /// Encode the note, and optionally its Merkle path, as animated QR frames.
+    ///
+    /// Each frame carries at most `frame_size` encoded bytes; see
+    /// [`DEFAULT_QR_FRAME_SIZE`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if `frame_size` is zero or
+    /// the encoding needs more than [`MAX_QR_FRAMES`] frames, and
+    /// [`ZKaneError::InvalidMerklePath`] if the path can't be encoded.
+    pub fn to_qr_frames(&self, path: Option<&MerklePath>, frame_size: usize) -> ZKaneResult<Vec<String>> {
+        if frame_size == 0 {
+            return Err(ZKaneError::SerializationError("QR frame size must not be zero".to_string()));
+        }
+        let data = encode_note(self, path)?;
+        let count = data.len().div_ceil(frame_size);
+        if count > MAX_QR_FRAMES {
+            return Err(ZKaneError::SerializationError(format!(
+                "note needs {} QR frames, at most {} are allowed",
+                count, MAX_QR_FRAMES
+            )));
+        }
+        Ok(data
+            .chunks(frame_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                format!("{}{}/{}:{}", QR_PAYLOAD_PREFIX, index, count, URL_SAFE_NO_PAD.encode(chunk))
+            })
+            .collect())
+    }
+}
+
+/// Collects the frames of an animated QR note, in any order.
+///
+/// ```rust
+/// use zkane_common::{DepositNote, QrFrameDecoder, ZkAssetId};
+///
+/// let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 100_000);
+/// let mut decoder = QrFrameDecoder::new();
+/// for frame in note.to_qr_frames(None, 40)?.iter().rev() {
+///     decoder.push(frame)?;
+/// }
+/// let (decoded, path) = decoder.finish()?;
+/// assert_eq!(decoded.commitment, note.commitment);
+/// assert!(path.is_none());
+/// # Ok::<(), zkane_common::ZKaneError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct QrFrameDecoder {
+    frames: Vec<Option<Zeroizing<Vec<u8>>>>,
+}
+
+impl QrFrameDecoder {
+    /// Create an empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a scanned frame. Frames seen before are ignored.
+    ///
+    /// # Returns
+    ///
+    /// Whether all frames have been collected.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the frame is malformed
+    /// or its frame count differs from earlier frames'.
+    pub fn push(&mut self, frame: &str) -> ZKaneResult<bool> {
+        let rest = frame
+            .trim()
+            .strip_prefix(QR_PAYLOAD_PREFIX)
+            .ok_or_else(|| invalid(format!("missing {} prefix", QR_PAYLOAD_PREFIX)))?;
+        let (position, encoded) = rest.split_once(':').ok_or_else(|| invalid("not an animated QR frame"))?;
+        let (index, count) = position.split_once('/').ok_or_else(|| invalid("missing frame count"))?;
+        let index: usize = index.parse().map_err(|_| invalid("invalid frame index"))?;
+        let count: usize = count.parse().map_err(|_| invalid("invalid frame count"))?;
+        if count == 0 || count > MAX_QR_FRAMES || index >= count {
+            return Err(invalid(format!("frame {} of {} is out of range", index, count)));
+        }
+        if self.frames.is_empty() {
+            self.frames.resize_with(count, || None);
+        } else if self.frames.len() != count {
+            return Err(invalid(format!(
+                "frame count {} differs from earlier frames' {}",
+                count,
+                self.frames.len()
+            )));
+        }
+        if self.frames[index].is_none() {
+            self.frames[index] = Some(Zeroizing::new(URL_SAFE_NO_PAD.decode(encoded).map_err(invalid)?));
+        }
+        Ok(self.is_complete())
+    }
+
+    /// Whether all frames have been collected.
+    pub fn is_complete(&self) -> bool {
+        !self.frames.is_empty() && self.frames.iter().all(Option::is_some)
+    }
+
+    /// Get the number of frames collected and expected, for progress display.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.frames.iter().filter(|frame| frame.is_some()).count(), self.frames.len())
+    }
+
+    /// Decode the collected note and Merkle path.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if frames are missing, or
+    /// the frames don't belong to the same note and the checksum fails.
+    pub fn finish(self) -> ZKaneResult<(DepositNote, Option<MerklePath>)> {
+        if !self.is_complete() {
+            let (collected, count) = self.progress();
+            return Err(invalid(format!("{} of {} frames collected", collected, count)));
+        }
+        let mut data = Zeroizing::new(Vec::new());
+        for frame in self.frames.iter().flatten() {
+            data.extend_from_slice(frame);
+        }
+        decode_note(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note() -> DepositNote {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let mut note = DepositNote::random(asset_id, u128::MAX);
+        note.leaf_index = 7;
+        note
+    }
+
+    #[test]
+    fn test_qr_payload_roundtrip() {
+        let note = note();
+        let payload = note.to_qr_payload();
+        assert!(payload.starts_with(QR_PAYLOAD_PREFIX));
+
+        let decoded = DepositNote::from_qr_payload(&format!(" {}\n", payload)).unwrap();
+        assert_eq!(decoded.secret.as_bytes(), note.secret.as_bytes());
+        assert_eq!(decoded.nullifier.as_bytes(), note.nullifier.as_bytes());
+        assert_eq!(decoded.commitment, note.commitment);
+        assert_eq!(decoded.asset_id, note.asset_id);
+        assert_eq!(decoded.denomination, u128::MAX);
+        assert_eq!(decoded.leaf_index, 7);
+    }
+
+    #[test]
+    fn test_qr_payload_rejects_corruption() {
+        let payload = note().to_qr_payload();
+        let mut corrupted = payload.clone().into_bytes();
+        let last = corrupted.len() - 10;
+        corrupted[last] = if corrupted[last] == b'A' { b'B' } else { b'A' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+
+        for payload in [&corrupted, &payload[1..], &payload[..payload.len() - 8], "zkane:"] {
+            assert!(matches!(DepositNote::from_qr_payload(payload), Err(ZKaneError::SerializationError(_))));
+        }
+    }
+
+    #[test]
+    fn test_qr_frames_roundtrip() {
+        let note = note();
+        let path = MerklePath::new(vec![[3u8; 32]; 20], vec![true; 20]).unwrap();
+        let frames = note.to_qr_frames(Some(&path), DEFAULT_QR_FRAME_SIZE).unwrap();
+        assert!(frames.len() > 1);
+
+        let mut decoder = QrFrameDecoder::new();
+        assert!(!decoder.push(&frames[1]).unwrap());
+        // Repeated frames are ignored
+        assert!(!decoder.push(&frames[1]).unwrap());
+        assert_eq!(decoder.progress(), (1, frames.len()));
+        for frame in &frames {
+            decoder.push(frame).unwrap();
+        }
+        assert!(decoder.is_complete());
+
+        let (decoded, decoded_path) = decoder.finish().unwrap();
+        assert_eq!(decoded.commitment, note.commitment);
+        assert_eq!(decoded_path.unwrap().to_bytes().unwrap(), path.to_bytes().unwrap());
+
+        // A single payload can't be pushed as a frame, and frames of another
+        // length are rejected
+        let mut decoder = QrFrameDecoder::new();
+        assert!(decoder.push(&note.to_qr_payload()).is_err());
+        decoder.push(&frames[0]).unwrap();
+        assert!(decoder.push(&note.to_qr_frames(None, 10).unwrap()[0]).is_err());
+        assert!(decoder.finish().is_err());
+
+        assert!(note.to_qr_frames(None, 0).is_err());
+        assert!(note.to_qr_frames(Some(&path), 1).is_err());
+    }
+
+    #[test]
+    fn test_qr_payload_versions() {
+        let mut note = note();
+        note.set_extension("pool_id", "2:7");
+        let decoded = DepositNote::from_qr_payload(&note.to_qr_payload()).unwrap();
+        assert_eq!(decoded.version, crate::DEPOSIT_NOTE_VERSION);
+        assert_eq!(decoded.extension("pool_id"), Some("2:7"));
+
+        // A version 1 payload is the version 2 one without the extension count
+        note.extensions.clear();
+        let encoded = encode_note(&note, None).unwrap();
+        let with_version = |version: u8| {
+            let mut data = encoded[..encoded.len() - CHECKSUM_LEN].to_vec();
+            data[0] = version;
+            data.remove(data.len() - 2);
+            let sum = checksum(&data);
+            data.extend_from_slice(&sum);
+            data
+        };
+        let (decoded, path) = decode_note(&with_version(1)).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert_eq!(decoded.commitment, note.commitment);
+        assert!(decoded.extensions.is_empty());
+        assert!(path.is_none());
+
+        let err = decode_note(&with_version(QR_NOTE_VERSION + 1)).unwrap_err();
+        assert!(err.to_string().contains("unsupported version"));
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u64::MAX as u128, u128::MAX] {
+            let mut data = Vec::new();
+            write_varint(&mut data, value);
+            let mut slice = data.as_slice();
+            assert_eq!(read_varint(&mut slice), Some(value));
+            assert!(slice.is_empty());
+        }
+        let mut overflowing: &[u8] = &[0xff; 20];
+        assert_eq!(read_varint(&mut overflowing), None);
+    }
+}
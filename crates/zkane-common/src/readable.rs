@@ -0,0 +1,169 @@
+//! # Human-Readable Encodings
+//!
+//! Bech32m encodings of the values users copy around, with a checksum that
+//! catches typos and a prefix that tells them apart:
+//!
+//! | Value | HRP | Data |
+//! |-------|-----|------|
+//! | [`Commitment`] | `zkc` | 32 bytes |
+//! | [`NullifierHash`] | `zkn` | 32 bytes |
+//! | Pool ID | `zkp` | block and tx as LEB128 varints |
+//!
+//! ```rust
+//! use zkane_common::{Commitment, ZkAssetId};
+//!
+//! let commitment = Commitment::new([7u8; 32]);
+//! let encoded = commitment.to_bech32();
+//! assert!(encoded.starts_with("zkc1"));
+//! assert_eq!(Commitment::from_bech32(&encoded)?, commitment);
+//!
+//! let pool_id = ZkAssetId { block: 6, tx: 1 };
+//! assert_eq!(ZkAssetId::from_bech32(&pool_id.to_bech32())?, pool_id);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::qr::{read_varint, write_varint};
+use crate::{Commitment, NullifierHash, ZKaneError, ZKaneResult, ZkAssetId};
+use bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use bitcoin::bech32::{Bech32m, Hrp};
+
+/// Human-readable part of bech32m commitments
+pub const COMMITMENT_HRP: &str = "zkc";
+
+/// Human-readable part of bech32m nullifier hashes
+pub const NULLIFIER_HASH_HRP: &str = "zkn";
+
+/// Human-readable part of bech32m pool IDs
+pub const POOL_ID_HRP: &str = "zkp";
+
+fn encode(hrp: &str, data: &[u8]) -> String {
+    let hrp = Hrp::parse(hrp).expect("valid human-readable part");
+    bitcoin::bech32::encode::<Bech32m>(hrp, data).expect("data fits a bech32m string")
+}
+
+/// Decode a bech32m string, checking its checksum and human-readable part.
+///
+/// Strings are accepted in either case, as long as they aren't mixed.
+fn decode(hrp: &str, encoded: &str) -> ZKaneResult<Vec<u8>> {
+    let invalid = |message: String| ZKaneError::SerializationError(format!("invalid {} string: {}", hrp, message));
+    let checked = CheckedHrpstring::new::<Bech32m>(encoded.trim()).map_err(|e| invalid(e.to_string()))?;
+    if !checked.hrp().as_str().eq_ignore_ascii_case(hrp) {
+        return Err(invalid(format!("expected prefix {}1, got {}1", hrp, checked.hrp())));
+    }
+    Ok(checked.byte_iter().collect())
+}
+
+fn decode32(hrp: &str, encoded: &str) -> ZKaneResult<[u8; 32]> {
+    let data = decode(hrp, encoded)?;
+    data.as_slice().try_into().map_err(|_| {
+        ZKaneError::SerializationError(format!("invalid {} string: expected 32 bytes, got {}", hrp, data.len()))
+    })
+}
+
+impl Commitment {
+    /// Encode the commitment as bech32m, prefixed `zkc1`.
+    pub fn to_bech32(&self) -> String {
+        encode(COMMITMENT_HRP, &self.0)
+    }
+
+    /// Parse a commitment encoded by [`Self::to_bech32`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the checksum fails, the
+    /// prefix isn't `zkc` or the data isn't 32 bytes.
+    pub fn from_bech32(encoded: &str) -> ZKaneResult<Self> {
+        decode32(COMMITMENT_HRP, encoded).map(Self)
+    }
+}
+
+impl NullifierHash {
+    /// Encode the nullifier hash as bech32m, prefixed `zkn1`.
+    pub fn to_bech32(&self) -> String {
+        encode(NULLIFIER_HASH_HRP, &self.0)
+    }
+
+    /// Parse a nullifier hash encoded by [`Self::to_bech32`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the checksum fails, the
+    /// prefix isn't `zkn` or the data isn't 32 bytes.
+    pub fn from_bech32(encoded: &str) -> ZKaneResult<Self> {
+        decode32(NULLIFIER_HASH_HRP, encoded).map(Self)
+    }
+}
+
+impl ZkAssetId {
+    /// Encode the ID as a bech32m pool ID, prefixed `zkp1`.
+    pub fn to_bech32(&self) -> String {
+        let mut data = Vec::with_capacity(8);
+        write_varint(&mut data, self.block);
+        write_varint(&mut data, self.tx);
+        encode(POOL_ID_HRP, &data)
+    }
+
+    /// Parse a pool ID encoded by [`Self::to_bech32`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the checksum fails, the
+    /// prefix isn't `zkp` or the data isn't two varints.
+    pub fn from_bech32(encoded: &str) -> ZKaneResult<Self> {
+        let data = decode(POOL_ID_HRP, encoded)?;
+        let mut reader = data.as_slice();
+        match (read_varint(&mut reader), read_varint(&mut reader)) {
+            (Some(block), Some(tx)) if reader.is_empty() => Ok(Self { block, tx }),
+            _ => Err(ZKaneError::SerializationError(format!(
+                "invalid {} string: malformed pool ID",
+                POOL_ID_HRP
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bech32_roundtrip() {
+        let commitment = Commitment::new([0xab; 32]);
+        let encoded = commitment.to_bech32();
+        assert!(encoded.starts_with("zkc1"));
+        assert_eq!(Commitment::from_bech32(&encoded).unwrap(), commitment);
+        assert_eq!(Commitment::from_bech32(&encoded.to_uppercase()).unwrap(), commitment);
+
+        let nullifier_hash = NullifierHash::new([0x01; 32]);
+        assert_eq!(NullifierHash::from_bech32(&nullifier_hash.to_bech32()).unwrap(), nullifier_hash);
+
+        for pool_id in [
+            ZkAssetId { block: 2, tx: 1 },
+            ZkAssetId { block: u128::MAX, tx: 0 },
+        ] {
+            assert_eq!(ZkAssetId::from_bech32(&pool_id.to_bech32()).unwrap(), pool_id);
+        }
+    }
+
+    #[test]
+    fn test_bech32_rejects_invalid() {
+        let encoded = Commitment::new([0xab; 32]).to_bech32();
+
+        // A typo breaks the checksum
+        let mut typo = encoded.clone().into_bytes();
+        typo[10] = if typo[10] == b'q' { b'p' } else { b'q' };
+        assert!(Commitment::from_bech32(std::str::from_utf8(&typo).unwrap()).is_err());
+
+        // Values can't be mistaken for one another
+        assert!(NullifierHash::from_bech32(&encoded).is_err());
+        assert!(ZkAssetId::from_bech32(&encoded).is_err());
+        assert!(Commitment::from_bech32(&ZkAssetId { block: 2, tx: 1 }.to_bech32()).is_err());
+
+        // Bech32 checksums aren't accepted for bech32m
+        let hrp = Hrp::parse(COMMITMENT_HRP).unwrap();
+        let bech32 = bitcoin::bech32::encode::<bitcoin::bech32::Bech32>(hrp, &[0xab; 32]).unwrap();
+        assert!(Commitment::from_bech32(&bech32).is_err());
+
+        assert!(Commitment::from_bech32(&encode(COMMITMENT_HRP, &[0xab; 31])).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! # Withdrawal Recipients
+//!
+//! A withdrawal pays out to a Bitcoin output, so its recipient is a
+//! scriptPubKey. A [`Recipient`] names the script a proof pays; it is bound
+//! to the proof through the proof's recipient outputs, whose hash is the
+//! `recipients_hash` public input of the withdrawal circuit.
+//! [`WithdrawalProof::bind_recipient`] commits to a single output paying the
+//! recipient, and [`validate_recipient`] checks that a transaction's
+//! recipient outputs start with it.
+//!
+//! ```rust
+//! use bitcoin::{Amount, Network};
+//! use zkane_common::{NullifierHash, Recipient, WithdrawalProof};
+//!
+//! let recipient = Recipient::parse("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080", Network::Regtest)?;
+//! let proof = WithdrawalProof::new(vec![0u8; 192], [1u8; 32], NullifierHash::new([2u8; 32]), recipient.clone())
+//!     .bind_recipient(Amount::from_sat(546));
+//! assert!(proof.validate_recipient(&[recipient.output(Amount::from_sat(546))]).is_ok());
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+//!
+//! [`WithdrawalProof::bind_recipient`]: crate::WithdrawalProof::bind_recipient
+
+use crate::{find_outputs_window, ZKaneError, ZKaneResult};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::{Address, Amount, Network, Script, ScriptBuf, TxOut};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Maximum size of a recipient scriptPubKey, the consensus script size limit
+pub const MAX_RECIPIENT_SCRIPT_SIZE: usize = 10_000;
+
+/// The scriptPubKey a withdrawal pays.
+///
+/// The empty script means the proof names no recipient. Serialized as the
+/// script's hex; the numeric recipients of earlier proofs deserialize as no
+/// recipient.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Recipient(ScriptBuf);
+
+impl Recipient {
+    /// Create a recipient from its scriptPubKey.
+    pub fn new(script_pubkey: ScriptBuf) -> Self {
+        Self(script_pubkey)
+    }
+
+    /// Create a recipient paying an address.
+    pub fn from_address(address: &Address) -> Self {
+        Self(address.script_pubkey())
+    }
+
+    /// Parse an address for a network into a recipient.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidRecipient`] if the address is malformed
+    /// or belongs to another network.
+    pub fn parse(address: &str, network: Network) -> ZKaneResult<Self> {
+        let address = address
+            .parse::<Address<NetworkUnchecked>>()
+            .map_err(|e| ZKaneError::InvalidRecipient(format!("invalid address {}: {}", address, e)))?
+            .require_network(network)
+            .map_err(|e| ZKaneError::InvalidRecipient(format!("invalid address {}: {}", address, e)))?;
+        Ok(Self::from_address(&address))
+    }
+
+    /// Create a recipient from raw scriptPubKey bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidRecipient`] if the script is larger than
+    /// [`MAX_RECIPIENT_SCRIPT_SIZE`].
+    pub fn from_bytes(bytes: Vec<u8>) -> ZKaneResult<Self> {
+        if bytes.len() > MAX_RECIPIENT_SCRIPT_SIZE {
+            return Err(ZKaneError::InvalidRecipient(format!(
+                "script of {} bytes exceeds {} bytes",
+                bytes.len(),
+                MAX_RECIPIENT_SCRIPT_SIZE
+            )));
+        }
+        Ok(Self(ScriptBuf::from_bytes(bytes)))
+    }
+
+    /// Parse a recipient from its scriptPubKey hex.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidRecipient`] if the string isn't hex or
+    /// the script is too large.
+    pub fn from_hex(hex_str: &str) -> ZKaneResult<Self> {
+        let bytes = hex::decode(hex_str).map_err(|e| ZKaneError::InvalidRecipient(format!("invalid script hex: {}", e)))?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Get the scriptPubKey hex.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_bytes())
+    }
+
+    /// Get the scriptPubKey.
+    pub fn script_pubkey(&self) -> &Script {
+        &self.0
+    }
+
+    /// Check if the recipient is unset.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the address of the recipient on a network, if its script has one.
+    pub fn to_address(&self, network: Network) -> Option<Address> {
+        Address::from_script(&self.0, network).ok()
+    }
+
+    /// Get an output paying `value` to the recipient.
+    pub fn output(&self, value: Amount) -> TxOut {
+        TxOut { value, script_pubkey: self.0.clone() }
+    }
+}
+
+impl From<ScriptBuf> for Recipient {
+    fn from(script_pubkey: ScriptBuf) -> Self {
+        Self(script_pubkey)
+    }
+}
+
+impl From<&Address> for Recipient {
+    fn from(address: &Address) -> Self {
+        Self::from_address(address)
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl Serialize for Recipient {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Recipient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RecipientVisitor;
+
+        impl Visitor<'_> for RecipientVisitor {
+            type Value = Recipient;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a scriptPubKey hex string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Recipient, E> {
+                Recipient::from_hex(value).map_err(E::custom)
+            }
+
+            // Proofs used to carry the recipient as a number, which named no script
+            fn visit_u64<E: de::Error>(self, _value: u64) -> Result<Recipient, E> {
+                Ok(Recipient::default())
+            }
+
+            fn visit_u128<E: de::Error>(self, _value: u128) -> Result<Recipient, E> {
+                Ok(Recipient::default())
+            }
+        }
+
+        deserializer.deserialize_any(RecipientVisitor)
+    }
+}
+
+/// Check that the recipient outputs of a withdrawal start with its recipient.
+///
+/// The outputs committed to by `recipients_hash` must appear as one
+/// contiguous run of `outputs`, and the first of them that isn't an
+/// OP_RETURN must pay `recipient`. A proof without a recipient set may not
+/// name a recipient, as nothing would bind it.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidRecipient`] if the recipient isn't bound or
+/// isn't paid.
+pub fn validate_recipient(outputs: &[TxOut], recipients_hash: &[u8; 32], recipient: &Recipient) -> ZKaneResult<()> {
+    if *recipients_hash == [0u8; 32] {
+        if recipient.is_empty() {
+            return Ok(());
+        }
+        return Err(ZKaneError::InvalidRecipient(
+            "recipient is not bound to a recipient set".to_string(),
+        ));
+    }
+    let window = find_outputs_window(outputs, recipients_hash).ok_or_else(|| {
+        ZKaneError::InvalidRecipient("transaction does not pay the proof's recipients".to_string())
+    })?;
+    if recipient.is_empty() {
+        return Ok(());
+    }
+    let first = outputs[window].iter().find(|output| !output.script_pubkey.is_op_return());
+    if first.map(|output| output.script_pubkey.as_script()) != Some(recipient.script_pubkey()) {
+        return Err(ZKaneError::InvalidRecipient(
+            "recipient outputs don't start with the recipient".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_outputs_hash;
+
+    const ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    #[test]
+    fn test_recipient_from_address() {
+        let recipient = Recipient::parse(ADDRESS, Network::Regtest).unwrap();
+        assert_eq!(recipient.to_address(Network::Regtest).unwrap().to_string(), ADDRESS);
+        assert_eq!(Recipient::from_hex(&recipient.to_hex()).unwrap(), recipient);
+        assert!(matches!(Recipient::parse(ADDRESS, Network::Bitcoin), Err(ZKaneError::InvalidRecipient(_))));
+        assert!(Recipient::parse("not an address", Network::Regtest).is_err());
+        assert!(Recipient::from_bytes(vec![0x51; MAX_RECIPIENT_SCRIPT_SIZE + 1]).is_err());
+
+        let json = serde_json::to_string(&recipient).unwrap();
+        assert_eq!(json, format!("\"{}\"", recipient.to_hex()));
+        assert_eq!(serde_json::from_str::<Recipient>(&json).unwrap(), recipient);
+        // Numeric recipients of earlier proofs name no script
+        assert!(serde_json::from_str::<Recipient>("12345").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_recipient() {
+        let recipient = Recipient::new(ScriptBuf::from_bytes(vec![0x51]));
+        let other = Recipient::new(ScriptBuf::from_bytes(vec![0x52]));
+        let outputs = [recipient.output(Amount::from_sat(546)), other.output(Amount::from_sat(600))];
+        let recipients_hash = calculate_outputs_hash(&outputs);
+
+        assert!(validate_recipient(&outputs, &recipients_hash, &recipient).is_ok());
+        assert!(validate_recipient(&outputs, &recipients_hash, &Recipient::default()).is_ok());
+        assert!(validate_recipient(&outputs, &recipients_hash, &other).is_err());
+        assert!(validate_recipient(&outputs[..1], &recipients_hash, &recipient).is_err());
+
+        // An unbound recipient is rejected, an unbound proof without one isn't
+        assert!(validate_recipient(&outputs, &[0u8; 32], &recipient).is_err());
+        assert!(validate_recipient(&[], &[0u8; 32], &Recipient::default()).is_ok());
+    }
+}
@@ -0,0 +1,177 @@
+//! # Pool Reserves
+//!
+//! A pool counts the value of the notes it holds, its liabilities: each
+//! deposit adds the amount deposited, and each withdrawal or split takes
+//! the public amount it pays out. The notes a split leaves in the pool carry
+//! the rest of the spent note's value, so a solvent pool holds at least its
+//! liabilities of its asset whatever its notes are worth. The pool's
+//! `GetReserves` opcode returns what it holds, its note counts and its
+//! liabilities as [`PoolReserves`], which monitoring services and operators
+//! compare with [`PoolReserves::expected`].
+//!
+//! Pools upgraded from a version that didn't count withdrawals and
+//! liabilities only count those made since, so their counts are marked
+//! incomplete and give no expected balance.
+//!
+//! ```rust
+//! use zkane_common::PoolReserves;
+//!
+//! let reserves =
+//!     PoolReserves { held: 3_000, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+//! assert_eq!(reserves.expected(), Some(3_000));
+//! assert_eq!(reserves.discrepancy(), Some(0));
+//! assert_eq!(PoolReserves::from_bytes(&reserves.to_bytes())?, reserves);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{ZKaneError, ZKaneResult};
+use serde::{Deserialize, Serialize};
+
+/// What a pool holds of its asset, and its note counts.
+///
+/// Returned by the pool's `GetReserves` opcode as four 16-byte
+/// little-endian integers, the held balance, the deposit count, the
+/// withdrawal count and the liabilities, followed by a byte set to 1 if the
+/// counts are complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolReserves {
+    /// Balance of the pool's asset held for notes, excluding reward alkanes
+    /// of the same asset
+    pub held: u128,
+    /// Leaves inserted in the pool's tree
+    pub deposit_count: u32,
+    /// Notes spent from the pool
+    pub withdrawal_count: u32,
+    /// Value of the notes the pool holds
+    pub liabilities: u128,
+    /// Whether the withdrawal count and the liabilities cover every note of
+    /// the pool
+    pub counts_complete: bool,
+}
+
+impl PoolReserves {
+    /// Size of encoded reserves in bytes
+    pub const SIZE: usize = 16 * 4 + 1;
+
+    /// Encode the reserves in the layout of the `GetReserves` opcode.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&self.held.to_le_bytes());
+        data.extend_from_slice(&(self.deposit_count as u128).to_le_bytes());
+        data.extend_from_slice(&(self.withdrawal_count as u128).to_le_bytes());
+        data.extend_from_slice(&self.liabilities.to_le_bytes());
+        data.push(self.counts_complete as u8);
+        data
+    }
+
+    /// Decode reserves returned by the `GetReserves` opcode.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the data isn't exactly
+    /// [`PoolReserves::SIZE`] bytes or a count is out of range.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Self> {
+        if data.len() != Self::SIZE || data[Self::SIZE - 1] > 1 {
+            return Err(ZKaneError::SerializationError(format!(
+                "reserves are {} bytes, expected {}",
+                data.len(),
+                Self::SIZE
+            )));
+        }
+        let u128_at = |i: usize| u128::from_le_bytes(data[i * 16..(i + 1) * 16].try_into().unwrap());
+        let count_at = |i: usize| {
+            u32::try_from(u128_at(i)).map_err(|_| ZKaneError::SerializationError("note count out of range".to_string()))
+        };
+        Ok(Self {
+            held: u128_at(0),
+            deposit_count: count_at(1)?,
+            withdrawal_count: count_at(2)?,
+            liabilities: u128_at(3),
+            counts_complete: data[Self::SIZE - 1] == 1,
+        })
+    }
+
+    /// Notes the pool holds the value of.
+    ///
+    /// Notes left by splits count like deposited ones, whatever their value.
+    pub fn outstanding_notes(&self) -> u32 {
+        self.deposit_count.saturating_sub(self.withdrawal_count)
+    }
+
+    /// Balance the pool must hold for its notes.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the counts are incomplete.
+    pub fn expected(&self) -> Option<u128> {
+        self.counts_complete.then_some(self.liabilities)
+    }
+
+    /// Difference between the held and the expected balance: negative for
+    /// a shortfall, positive for a surplus.
+    ///
+    /// # Returns
+    ///
+    /// `None` if there is no expected balance, or the difference doesn't fit
+    /// an `i128`.
+    pub fn discrepancy(&self) -> Option<i128> {
+        let expected = self.expected()?;
+        if self.held >= expected {
+            i128::try_from(self.held - expected).ok()
+        } else {
+            i128::try_from(expected - self.held).ok().map(|shortfall| -shortfall)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserves_discrepancy() {
+        let reserves =
+            PoolReserves { held: 2_500, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+        assert_eq!(reserves.outstanding_notes(), 3);
+        assert_eq!(reserves.expected(), Some(3_000));
+        assert_eq!(reserves.discrepancy(), Some(-500));
+        assert_eq!(PoolReserves { held: 4_000, ..reserves }.discrepancy(), Some(1_000));
+        // Incomplete counts have no expected balance
+        let upgraded = PoolReserves { counts_complete: false, ..reserves };
+        assert_eq!(upgraded.discrepancy(), None);
+    }
+
+    #[test]
+    fn test_reserves_after_split() {
+        // Two deposits of 1_000, then one note split paying out 400 into
+        // two notes holding the other 600 between them
+        let reserves =
+            PoolReserves { held: 1_600, deposit_count: 4, withdrawal_count: 1, liabilities: 1_600, counts_complete: true };
+        assert_eq!(reserves.outstanding_notes(), 3);
+        // Counting every outstanding note at the denomination would report a
+        // 1_400 shortfall
+        assert_eq!(reserves.discrepancy(), Some(0));
+        assert_eq!(PoolReserves { held: 1_500, ..reserves }.discrepancy(), Some(-100));
+    }
+
+    #[test]
+    fn test_reserves_bytes() {
+        let reserves = PoolReserves {
+            held: u128::MAX,
+            deposit_count: 7,
+            withdrawal_count: 3,
+            liabilities: 5_000,
+            counts_complete: false,
+        };
+        let mut bytes = reserves.to_bytes();
+        assert_eq!(bytes.len(), PoolReserves::SIZE);
+        assert_eq!(PoolReserves::from_bytes(&bytes).unwrap(), reserves);
+
+        assert!(PoolReserves::from_bytes(&bytes[1..]).is_err());
+        bytes[PoolReserves::SIZE - 1] = 2;
+        assert!(PoolReserves::from_bytes(&bytes).is_err());
+        bytes[PoolReserves::SIZE - 1] = 1;
+        bytes[16..32].copy_from_slice(&(u32::MAX as u128 + 1).to_le_bytes());
+        assert!(PoolReserves::from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! # Anonymity Mining
+//!
+//! Pools can pay reward points to notes that stay in the pool, to encourage
+//! deposits to wait and grow the anonymity set. Rewards are optional: the
+//! factory admin sets a [`RewardProgram`] for a pool, naming the alkane the
+//! points are paid in and how many accrue per block.
+//!
+//! The pool records the height each leaf is inserted at. When a note is
+//! withdrawn, the points for the blocks between its deposit and the
+//! withdrawal are recorded against its nullifier hash. Claiming them is a
+//! separate, later step, so it needn't be linked to the withdrawal by time:
+//!
+//! 1. `RegisterRewardClaim` records the [`reward_claim_hash`] of the note's
+//!    nullifier and the outputs of the claim transaction.
+//! 2. `ClaimRewards`, in a later block, reveals the nullifier. The pool checks
+//!    it hashes to a nullifier hash with points, that the claim was
+//!    registered for the transaction's outputs, and pays the points out of
+//!    the reward alkanes funded to it.
+//!
+//! Revealing the nullifier only shows what the withdrawal already did, and a
+//! copy of the reveal can't redirect the points, as the registered hash
+//! binds the outputs paid.
+//!
+//! The points of a note are a function of how long it stayed in the pool,
+//! which the withdrawal reveals to anyone reading the pool's storage. Pools
+//! that reward deposits trade that for the larger anonymity set.
+//!
+//! ```rust
+//! use zkane_common::{RewardProgram, ZkAssetId};
+//!
+//! let program = RewardProgram::new(ZkAssetId::new(2, 50), 10, 1_000)?;
+//! assert_eq!(program.points(100, 150), 500);
+//! // Accrual stops after max_blocks
+//! assert_eq!(program.points(100, 5_000), 10_000);
+//! assert_eq!(RewardProgram::from_bytes(&program.to_bytes())?, Some(program));
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{Nullifier, ZKaneError, ZKaneResult, ZkAssetId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Domain separator of reward claim hashes
+const REWARD_CLAIM_DOMAIN: &[u8] = b"zkane/reward-claim";
+
+/// Reward points paid to notes of a pool for the blocks they stay in it.
+///
+/// Returned by the factory's `GetRewardProgram` opcode as four 16-byte
+/// little-endian integers: token block, token tx, points per block and
+/// maximum blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardProgram {
+    /// The alkane the points are paid in
+    pub token: ZkAssetId,
+    /// Points accrued by a note for each block it stays in the pool
+    pub points_per_block: u128,
+    /// Blocks after which a note stops accruing points (zero for no limit)
+    pub max_blocks: u64,
+}
+
+impl RewardProgram {
+    /// Size of an encoded program in bytes
+    pub const SIZE: usize = 16 * 4;
+
+    /// Create a reward program.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidAmount`] if `points_per_block` is zero;
+    /// a pool without rewards has no program.
+    pub fn new(token: ZkAssetId, points_per_block: u128, max_blocks: u64) -> ZKaneResult<Self> {
+        if points_per_block == 0 {
+            return Err(ZKaneError::InvalidAmount("reward programs pay at least one point per block".to_string()));
+        }
+        Ok(Self {
+            token,
+            points_per_block,
+            max_blocks,
+        })
+    }
+
+    /// Encode the program in the layout of the `GetRewardProgram` opcode.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SIZE);
+        data.extend_from_slice(&self.token.block.to_le_bytes());
+        data.extend_from_slice(&self.token.tx.to_le_bytes());
+        data.extend_from_slice(&self.points_per_block.to_le_bytes());
+        data.extend_from_slice(&(self.max_blocks as u128).to_le_bytes());
+        data
+    }
+
+    /// Decode a program returned by the `GetRewardProgram` opcode.
+    ///
+    /// # Returns
+    ///
+    /// `None` for the empty response of a pool without rewards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the data is neither
+    /// empty nor exactly [`RewardProgram::SIZE`] bytes, or is out of range.
+    pub fn from_bytes(data: &[u8]) -> ZKaneResult<Option<Self>> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        if data.len() != Self::SIZE {
+            return Err(ZKaneError::SerializationError(format!(
+                "reward program is {} bytes, expected {}",
+                data.len(),
+                Self::SIZE
+            )));
+        }
+        let u128_at = |i: usize| u128::from_le_bytes(data[i * 16..(i + 1) * 16].try_into().unwrap());
+        let max_blocks = u64::try_from(u128_at(3))
+            .map_err(|_| ZKaneError::SerializationError("reward max blocks out of range".to_string()))?;
+        let token = ZkAssetId {
+            block: u128_at(0),
+            tx: u128_at(1),
+        };
+        Self::new(token, u128_at(2), max_blocks).map(Some)
+    }
+
+    /// Points accrued by a note inserted at `deposit_height` and withdrawn
+    /// at `withdrawal_height`.
+    ///
+    /// Leaves inserted before the pool recorded heights have a height of
+    /// zero and accrue nothing.
+    pub fn points(&self, deposit_height: u64, withdrawal_height: u64) -> u128 {
+        if deposit_height == 0 {
+            return 0;
+        }
+        let mut blocks = withdrawal_height.saturating_sub(deposit_height);
+        if self.max_blocks != 0 {
+            blocks = blocks.min(self.max_blocks);
+        }
+        self.points_per_block.saturating_mul(blocks as u128)
+    }
+}
+
+/// Hash registered ahead of a reward claim.
+///
+/// Binds the note's nullifier to the outputs of the claim transaction, as
+/// hashed by [`calculate_outputs_hash`](crate::calculate_outputs_hash), so
+/// the claim can't be redirected once the nullifier is revealed.
+pub fn reward_claim_hash(nullifier: &Nullifier, outputs_hash: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(REWARD_CLAIM_DOMAIN);
+    hasher.update(nullifier.as_bytes());
+    hasher.update(outputs_hash);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reward_points() {
+        let program = RewardProgram::new(ZkAssetId::new(2, 50), 3, 0).unwrap();
+        assert_eq!(program.points(10, 20), 30);
+        // Unknown insertion heights and reorged heights accrue nothing
+        assert_eq!(program.points(0, 20), 0);
+        assert_eq!(program.points(20, 10), 0);
+
+        let capped = RewardProgram { max_blocks: 5, ..program };
+        assert_eq!(capped.points(10, 20), 15);
+        let large = RewardProgram { points_per_block: u128::MAX, ..program };
+        assert_eq!(large.points(1, 3), u128::MAX);
+
+        assert!(RewardProgram::new(ZkAssetId::new(2, 50), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_reward_program_bytes() {
+        let program = RewardProgram::new(ZkAssetId::new(2, 50), 7, 144).unwrap();
+        let bytes = program.to_bytes();
+        assert_eq!(bytes.len(), RewardProgram::SIZE);
+        assert_eq!(RewardProgram::from_bytes(&bytes).unwrap(), Some(program));
+        assert_eq!(RewardProgram::from_bytes(&[]).unwrap(), None);
+        assert!(RewardProgram::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_reward_claim_hash() {
+        let nullifier = Nullifier::new([1u8; 32]);
+        let hash = reward_claim_hash(&nullifier, &[2u8; 32]);
+        assert_ne!(hash, reward_claim_hash(&nullifier, &[3u8; 32]));
+        assert_ne!(hash, reward_claim_hash(&Nullifier::new([4u8; 32]), &[2u8; 32]));
+    }
+}
@@ -0,0 +1,100 @@
+//! # Contract Storage Schema Versions
+//!
+//! The pool and factory contracts record the version of their storage
+//! layout at [`SCHEMA_VERSION_KEY`], as a little-endian `u32`. Contracts
+//! deployed before versioning have no marker and are at version 0.
+//!
+//! When a contract is upgraded, the first state-changing call runs the
+//! migrations from the stored version up to the contract's own, one version
+//! at a time, recording each version as it is reached. A contract refuses
+//! storage written by a newer version of itself rather than misreading it.
+//!
+//! ```rust
+//! use zkane_common::{decode_schema_version, pending_migrations, POOL_SCHEMA_VERSION};
+//!
+//! // An unversioned pool migrates through every version
+//! let stored = decode_schema_version(&[])?;
+//! assert_eq!(pending_migrations(stored, POOL_SCHEMA_VERSION)?, 1..POOL_SCHEMA_VERSION + 1);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::{ZKaneError, ZKaneResult};
+use std::ops::Range;
+
+/// Storage key of the schema version of a contract
+pub const SCHEMA_VERSION_KEY: &str = "/schema_version";
+
+/// Current storage schema version of the pool contract
+///
+/// | Version | Change |
+/// |---------|--------|
+/// | 0 | Unversioned layout |
+/// | 1 | Schema version marker |
+/// | 2 | Merkle tree nodes replace the placeholder root |
+/// | 3 | Nullifier tree of spent nullifier hashes |
+/// | 4 | Insertion height of each leaf, and reward claims |
+/// | 5 | Withdrawal count and note liabilities, for reserve audits |
+pub const POOL_SCHEMA_VERSION: u32 = 5;
+
+/// Current storage schema version of the factory contract
+///
+/// | Version | Change |
+/// |---------|--------|
+/// | 0 | Unversioned layout |
+/// | 1 | Schema version marker |
+/// | 2 | Asset list and mirrored deposit totals |
+pub const FACTORY_SCHEMA_VERSION: u32 = 2;
+
+/// Decode a stored schema version, 0 if none was stored.
+pub fn decode_schema_version(data: &[u8]) -> ZKaneResult<u32> {
+    match data.len() {
+        0 => Ok(0),
+        4 => Ok(u32::from_le_bytes(data.try_into().unwrap())),
+        len => Err(ZKaneError::SerializationError(format!(
+            "schema version must be 4 bytes, got {}",
+            len
+        ))),
+    }
+}
+
+/// Encode a schema version for storage.
+pub fn encode_schema_version(version: u32) -> Vec<u8> {
+    version.to_le_bytes().to_vec()
+}
+
+/// Get the versions to migrate storage at `stored` through, in order, to
+/// reach `current`.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::UnsupportedSchemaVersion`] if the storage was
+/// written by a newer contract than `current`.
+pub fn pending_migrations(stored: u32, current: u32) -> ZKaneResult<Range<u32>> {
+    if stored > current {
+        return Err(ZKaneError::UnsupportedSchemaVersion { stored, supported: current });
+    }
+    Ok(stored + 1..current + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version_encoding() {
+        assert_eq!(decode_schema_version(&[]).unwrap(), 0);
+        assert_eq!(decode_schema_version(&encode_schema_version(7)).unwrap(), 7);
+        assert!(matches!(decode_schema_version(&[1, 0]), Err(ZKaneError::SerializationError(_))));
+    }
+
+    #[test]
+    fn test_pending_migrations() {
+        assert_eq!(pending_migrations(0, 3).unwrap().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(pending_migrations(2, 3).unwrap().collect::<Vec<_>>(), vec![3]);
+        assert!(pending_migrations(3, 3).unwrap().is_empty());
+
+        let err = pending_migrations(4, 3).unwrap_err();
+        assert_eq!(err.code(), 4010);
+        assert!(matches!(err, ZKaneError::UnsupportedSchemaVersion { stored: 4, supported: 3 }));
+    }
+}
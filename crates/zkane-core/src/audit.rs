@@ -0,0 +1,263 @@
+//! # Proof of Reserves
+//!
+//! A [`ReserveReport`] records, at a block height, what a pool holds of its
+//! asset against the liabilities its notes are worth, with its Merkle root so the report can be matched to the state it
+//! was taken from. Operators sign reports with a schnorr key, and anyone with
+//! the [`SignedReserveReport`] JSON can check it with
+//! [`SignedReserveReport::verify`].
+//!
+//! With the `deezel` feature, [`audit_pool`] takes the report from a
+//! deployed pool, as `zkane-cli pool audit` does; monitoring services call it
+//! on a schedule and alert on [`ReserveReport::is_solvent`].
+//!
+//! ```rust
+//! use bitcoin::secp256k1::{Keypair, Secp256k1, SecretKey};
+//! use zkane_common::{PoolReserves, ZkAssetId};
+//! use zkane_core::{ReserveReport, SignedReserveReport};
+//!
+//! let reserves =
+//!     PoolReserves { held: 3_000, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+//! let report = ReserveReport::new(ZkAssetId { block: 6, tx: 7 }, 1_000, 840_000, [0xab; 32], reserves);
+//! assert_eq!(report.is_solvent(), Some(true));
+//!
+//! let secp = Secp256k1::new();
+//! let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+//! let signed = report.sign(&keypair)?;
+//! SignedReserveReport::from_json(&signed.to_json()?)?.verify()?;
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use zkane_common::{PoolReserves, ZKaneError, ZKaneResult, ZkAssetId};
+#[cfg(feature = "deezel")]
+use crate::provider::PoolProvider;
+#[cfg(feature = "deezel")]
+use crate::PoolClient;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::DeezelProvider;
+#[cfg(feature = "deezel")]
+use std::sync::Arc;
+
+/// Version of the reserve report format
+pub const RESERVE_REPORT_VERSION: u8 = 1;
+
+/// Domain separator of the digest signed over a report
+const RESERVE_REPORT_DOMAIN: &[u8] = b"zkane/reserve-report";
+
+/// Attempts at reading a pool's state within a single block
+#[cfg(feature = "deezel")]
+const AUDIT_ATTEMPTS: usize = 3;
+
+/// A pool's reserves checked against its outstanding notes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveReport {
+    /// Format version, [`RESERVE_REPORT_VERSION`]
+    pub version: u8,
+    /// The audited pool
+    pub pool_id: ZkAssetId,
+    /// Amount of every note (zero for a variable-amount pool)
+    pub denomination: u128,
+    /// Chain tip height the pool's state was read at
+    pub height: u64,
+    /// Merkle root of the pool at `height`, hex encoded
+    pub merkle_root: String,
+    /// What the pool holds and its note counts
+    pub reserves: PoolReserves,
+    /// Balance the outstanding notes are worth, if it can be computed
+    pub expected: Option<u128>,
+    /// Held minus expected balance: negative for a shortfall
+    pub discrepancy: Option<i128>,
+}
+
+impl ReserveReport {
+    /// Create the report of a pool's reserves read at `height`.
+    pub fn new(pool_id: ZkAssetId, denomination: u128, height: u64, merkle_root: [u8; 32], reserves: PoolReserves) -> Self {
+        Self {
+            version: RESERVE_REPORT_VERSION,
+            pool_id,
+            denomination,
+            height,
+            merkle_root: hex::encode(merkle_root),
+            reserves,
+            expected: reserves.expected(),
+            discrepancy: reserves.discrepancy(),
+        }
+    }
+
+    /// Whether the pool holds at least what its notes are worth.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the report has no expected balance.
+    pub fn is_solvent(&self) -> Option<bool> {
+        self.expected.map(|expected| self.reserves.held >= expected)
+    }
+
+    /// Get the digest a signature over the report signs.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the report can't be
+    /// serialized.
+    pub fn digest(&self) -> ZKaneResult<[u8; 32]> {
+        let json = serde_json::to_vec(self)?;
+        Ok(sha256::Hash::hash(&[RESERVE_REPORT_DOMAIN, &json].concat()).to_byte_array())
+    }
+
+    /// Sign the report with an operator key.
+    ///
+    /// Signatures are deterministic, so signing a report twice gives the
+    /// same signature.
+    pub fn sign(self, keypair: &Keypair) -> ZKaneResult<SignedReserveReport> {
+        let message = Message::from_digest(self.digest()?);
+        let signature = Secp256k1::signing_only().sign_schnorr_no_aux_rand(&message, keypair);
+        Ok(SignedReserveReport {
+            report: self,
+            public_key: hex::encode(keypair.x_only_public_key().0.serialize()),
+            signature: hex::encode(signature.serialize()),
+        })
+    }
+}
+
+/// A reserve report with the operator's schnorr signature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReserveReport {
+    /// The signed report
+    pub report: ReserveReport,
+    /// X-only public key of the operator, hex encoded
+    pub public_key: String,
+    /// BIP-340 signature over the report's digest, hex encoded
+    pub signature: String,
+}
+
+impl SignedReserveReport {
+    /// Serialize the signed report to JSON.
+    pub fn to_json(&self) -> ZKaneResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse a signed report from JSON.
+    pub fn from_json(json: &str) -> ZKaneResult<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Check the signature against the report and its public key.
+    ///
+    /// Callers still check the key is the operator's they expect.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidSignature`] if the key or signature is
+    /// malformed, or the signature doesn't sign the report.
+    pub fn verify(&self) -> ZKaneResult<()> {
+        let invalid = |e: &dyn std::fmt::Display| ZKaneError::InvalidSignature(e.to_string());
+        let public_key = hex::decode(&self.public_key)
+            .map_err(|e| invalid(&e))
+            .and_then(|key| XOnlyPublicKey::from_slice(&key).map_err(|e| invalid(&e)))?;
+        let signature = hex::decode(&self.signature)
+            .map_err(|e| invalid(&e))
+            .and_then(|signature| schnorr::Signature::from_slice(&signature).map_err(|e| invalid(&e)))?;
+        let message = Message::from_digest(self.report.digest()?);
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &message, &public_key)
+            .map_err(|e| invalid(&e))
+    }
+}
+
+/// Take the reserve report of a deployed pool.
+///
+/// The pool's state is read between two reads of the chain tip, and read
+/// again if a block arrived in between, so the report holds the state at
+/// its height.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::PoolQueryFailed`] if a query fails, or the chain
+/// kept advancing while the state was read.
+#[cfg(feature = "deezel")]
+pub async fn audit_pool<P: DeezelProvider>(provider: Arc<P>, pool_id: ZkAssetId) -> ZKaneResult<ReserveReport> {
+    let client = PoolClient::new(provider.clone(), pool_id);
+    let denomination = client.denomination().await?;
+    for _ in 0..AUDIT_ATTEMPTS {
+        let height = PoolProvider::get_tip_height(provider.as_ref()).await?;
+        let merkle_root = client.merkle_root().await?;
+        let reserves = client.reserves().await?;
+        if PoolProvider::get_tip_height(provider.as_ref()).await? == height {
+            return Ok(ReserveReport::new(pool_id, denomination, height, merkle_root, reserves));
+        }
+    }
+    Err(ZKaneError::PoolQueryFailed(format!(
+        "chain advanced during {} attempts to audit pool {}",
+        AUDIT_ATTEMPTS, pool_id
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::SecretKey;
+
+    fn keypair(byte: u8) -> Keypair {
+        Keypair::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[byte; 32]).unwrap())
+    }
+
+    fn report(held: u128) -> ReserveReport {
+        let reserves =
+            PoolReserves { held, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+        ReserveReport::new(ZkAssetId { block: 6, tx: 7 }, 1_000, 840_000, [0xab; 32], reserves)
+    }
+
+    #[test]
+    fn test_report_discrepancy() {
+        let short = report(2_500);
+        assert_eq!(short.expected, Some(3_000));
+        assert_eq!(short.discrepancy, Some(-500));
+        assert_eq!(short.is_solvent(), Some(false));
+        assert_eq!(report(3_000).is_solvent(), Some(true));
+
+        // Variable-amount pools are audited against their liabilities too
+        let variable = ReserveReport::new(ZkAssetId { block: 6, tx: 8 }, 0, 1, [0u8; 32], short.reserves);
+        assert_eq!(variable.is_solvent(), Some(false));
+        let upgraded = PoolReserves { counts_complete: false, ..short.reserves };
+        assert_eq!(ReserveReport::new(ZkAssetId { block: 6, tx: 7 }, 1_000, 1, [0u8; 32], upgraded).is_solvent(), None);
+    }
+
+    #[test]
+    fn test_signed_report() {
+        let signed = report(3_000).sign(&keypair(1)).unwrap();
+        signed.verify().unwrap();
+        assert_eq!(signed, report(3_000).sign(&keypair(1)).unwrap());
+        assert_eq!(SignedReserveReport::from_json(&signed.to_json().unwrap()).unwrap(), signed);
+
+        // Any change to the report breaks the signature
+        let mut tampered = signed.clone();
+        tampered.report.reserves.held += 1;
+        assert!(matches!(tampered.verify(), Err(ZKaneError::InvalidSignature(_))));
+
+        let mut rekeyed = signed.clone();
+        rekeyed.public_key = hex::encode(keypair(2).x_only_public_key().0.serialize());
+        assert!(matches!(rekeyed.verify(), Err(ZKaneError::InvalidSignature(_))));
+
+        let mut malformed = signed;
+        malformed.signature = "00".to_string();
+        assert!(matches!(malformed.verify(), Err(ZKaneError::InvalidSignature(_))));
+    }
+
+    #[cfg(feature = "deezel")]
+    #[tokio::test]
+    async fn test_audit_pool() {
+        let provider = crate::mock_provider::MockProvider::new(bitcoin::Network::Regtest);
+        provider.mine_empty_blocks(3);
+        let reserves =
+            PoolReserves { held: 3_000, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+        provider.add_simulation_data("6:7", "14", &1_000u128.to_le_bytes());
+        provider.add_simulation_data("6:7", "10", &[0xab; 32]);
+        provider.add_simulation_data("6:7", "29", &reserves.to_bytes());
+
+        let report = audit_pool(Arc::new(provider), ZkAssetId { block: 6, tx: 7 }).await.unwrap();
+        assert_eq!(report, ReserveReport::new(ZkAssetId { block: 6, tx: 7 }, 1_000, 3, [0xab; 32], reserves));
+        assert_eq!(report.discrepancy, Some(0));
+    }
+}
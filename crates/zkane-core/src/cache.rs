@@ -0,0 +1,403 @@
+//! # Provider Caching
+//!
+//! A [`CachingProvider`] keeps the transactions and blocks a
+//! [`PoolSyncer`](crate::PoolSyncer) fetches in an in-memory LRU cache, so
+//! syncing the same blocks again, as after a restart before checkpoints were
+//! flushed, doesn't spend another request per transaction:
+//!
+//! - Only confirmed transactions are cached, as a mempool transaction's
+//!   status changes once it is mined. Blocks are cached by hash.
+//! - The chain tip and broadcasts always go to the provider.
+//! - With a [`CacheConfig::directory`], entries evicted from memory or left
+//!   by an earlier run are read back from disk before asking the provider.
+//!   Files are only written for 64-character hex txids and block hashes.
+//! - After a reorg, [`CachingProvider::forget_above`] drops the transactions
+//!   and blocks of the orphaned heights, so they are fetched again with their
+//!   status on the new chain.
+//!
+//! [`CacheStats`] counts the hits and misses, to size the cache.
+
+use crate::provider::PoolProvider;
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use zkane_common::ZKaneResult;
+
+/// Default number of transactions, and of blocks, kept in memory
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// Size and location of a [`CachingProvider`]'s caches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Transactions, and blocks, kept in memory
+    pub capacity: usize,
+    /// Directory persisting cached entries across runs
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CACHE_CAPACITY,
+            directory: None,
+        }
+    }
+}
+
+/// Hits and misses of a [`CachingProvider`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from memory
+    pub hits: u64,
+    /// Lookups answered from the disk cache
+    pub disk_hits: u64,
+    /// Lookups sent to the provider
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Get the share of lookups answered without the provider.
+    ///
+    /// # Returns
+    ///
+    /// `None` before any lookup.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.disk_hits + self.misses;
+        (lookups > 0).then(|| (self.hits + self.disk_hits) as f64 / lookups as f64)
+    }
+}
+
+/// A map evicting its least recently used entry when full.
+struct Lru {
+    capacity: usize,
+    /// Entries and the tick they were last used at
+    entries: HashMap<String, (JsonValue, u64)>,
+    /// Keys by the tick they were last used at
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<JsonValue> {
+        self.tick += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        *used = self.tick;
+        self.order.insert(self.tick, key.to_string());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: &str, value: JsonValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.insert(key.to_string(), (value, self.tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, key.to_string());
+        while self.entries.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn retain(&mut self, keep: impl Fn(&JsonValue) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|_, (value, used)| {
+            let kept = keep(value);
+            if !kept {
+                order.remove(used);
+            }
+            kept
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// A pool provider caching the transactions and blocks it fetches.
+pub struct CachingProvider<P> {
+    provider: P,
+    config: CacheConfig,
+    txs: Mutex<Lru>,
+    blocks: Mutex<Lru>,
+    stats: Mutex<CacheStats>,
+}
+
+impl<P: PoolProvider> CachingProvider<P> {
+    /// Wrap a provider in caches of the configured size.
+    pub fn new(provider: P, config: CacheConfig) -> Self {
+        Self {
+            provider,
+            txs: Mutex::new(Lru::new(config.capacity)),
+            blocks: Mutex::new(Lru::new(config.capacity)),
+            stats: Mutex::new(CacheStats::default()),
+            config,
+        }
+    }
+
+    /// Get the wrapped provider.
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Get the cache settings.
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Get the hits and misses so far.
+    pub fn stats(&self) -> CacheStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Drop the transactions confirmed, and the blocks, above `height`.
+    ///
+    /// Call it with the height a reorg rolled back to, so syncing again
+    /// fetches the orphaned transactions with their new status.
+    pub fn forget_above(&self, height: u64) {
+        let tx_above = |tx: &JsonValue| tx["status"]["block_height"].as_u64().is_some_and(|h| h > height);
+        let block_above = |block: &JsonValue| block["height"].as_u64().is_some_and(|h| h > height);
+
+        self.txs.lock().unwrap().retain(|tx| !tx_above(tx));
+        self.blocks.lock().unwrap().retain(|block| !block_above(block));
+        self.remove_files("tx", tx_above);
+        self.remove_files("block", block_above);
+    }
+
+    /// Drop every cached transaction and block, in memory and on disk.
+    pub fn clear(&self) {
+        self.txs.lock().unwrap().clear();
+        self.blocks.lock().unwrap().clear();
+        self.remove_files("tx", |_| true);
+        self.remove_files("block", |_| true);
+    }
+
+    /// Look a key up in memory, then on disk, then fetch it and cache it if
+    /// `cacheable`.
+    async fn lookup<F>(
+        &self,
+        kind: &str,
+        key: &str,
+        cache: &Mutex<Lru>,
+        fetch: F,
+        cacheable: fn(&JsonValue) -> bool,
+    ) -> ZKaneResult<JsonValue>
+    where
+        F: std::future::Future<Output = ZKaneResult<JsonValue>>,
+    {
+        if let Some(value) = cache.lock().unwrap().get(key) {
+            self.stats.lock().unwrap().hits += 1;
+            return Ok(value);
+        }
+        if let Some(value) = self.read_file(kind, key) {
+            cache.lock().unwrap().insert(key, value.clone());
+            self.stats.lock().unwrap().disk_hits += 1;
+            return Ok(value);
+        }
+
+        self.stats.lock().unwrap().misses += 1;
+        let value = fetch.await?;
+        if cacheable(&value) {
+            cache.lock().unwrap().insert(key, value.clone());
+            self.write_file(kind, key, &value);
+        }
+        Ok(value)
+    }
+
+    /// Get the file of a cached entry, if it may be cached on disk.
+    fn file(&self, kind: &str, key: &str) -> Option<PathBuf> {
+        let is_hash = key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit());
+        let directory = self.config.directory.as_ref().filter(|_| is_hash)?;
+        Some(directory.join(kind).join(format!("{}.json", key.to_ascii_lowercase())))
+    }
+
+    fn read_file(&self, kind: &str, key: &str) -> Option<JsonValue> {
+        let json = std::fs::read(self.file(kind, key)?).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Write an entry to disk. The disk cache is best effort, so failures
+    /// only cost a fetch on the next run.
+    fn write_file(&self, kind: &str, key: &str, value: &JsonValue) {
+        if let Some(path) = self.file(kind, key) {
+            let written = path.parent().map(std::fs::create_dir_all).transpose().and_then(|_| {
+                std::fs::write(&path, serde_json::to_vec(value).unwrap_or_default())
+            });
+            if written.is_err() {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    fn remove_files(&self, kind: &str, remove: impl Fn(&JsonValue) -> bool) {
+        let Some(directory) = &self.config.directory else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(directory.join(kind)) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let value = std::fs::read(&path).ok().and_then(|json| serde_json::from_slice(&json).ok());
+            if value.as_ref().is_none_or(&remove) {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+/// Whether a transaction is confirmed, so its JSON won't change.
+fn is_confirmed(tx: &JsonValue) -> bool {
+    tx["status"]["confirmed"].as_bool() == Some(true)
+}
+
+#[async_trait(?Send)]
+impl<P: PoolProvider> PoolProvider for CachingProvider<P> {
+    async fn get_tx(&self, txid: &str) -> ZKaneResult<JsonValue> {
+        self.lookup("tx", txid, &self.txs, self.provider.get_tx(txid), is_confirmed)
+            .await
+    }
+
+    async fn get_block(&self, hash: &str) -> ZKaneResult<JsonValue> {
+        self.lookup("block", hash, &self.blocks, self.provider.get_block(hash), |_| true)
+            .await
+    }
+
+    async fn get_tip_height(&self) -> ZKaneResult<u64> {
+        self.provider.get_tip_height().await
+    }
+
+    async fn broadcast(&self, tx_hex: &str) -> ZKaneResult<String> {
+        self.provider.broadcast(tx_hex).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", JsonValue::from(1));
+        lru.insert("b", JsonValue::from(2));
+        assert!(lru.get("a").is_some());
+        lru.insert("c", JsonValue::from(3));
+
+        // "b" was used least recently
+        assert!(lru.get("b").is_none());
+        assert_eq!(lru.get("a"), Some(JsonValue::from(1)));
+        assert_eq!(lru.get("c"), Some(JsonValue::from(3)));
+
+        lru.insert("a", JsonValue::from(4));
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(lru.order.len(), 2);
+        lru.retain(|value| value.as_u64() != Some(4));
+        assert!(lru.get("a").is_none());
+        assert_eq!(lru.order.len(), 1);
+
+        let mut disabled = Lru::new(0);
+        disabled.insert("a", JsonValue::from(1));
+        assert!(disabled.get("a").is_none());
+    }
+
+    #[test]
+    fn test_hit_rate() {
+        assert_eq!(CacheStats::default().hit_rate(), None);
+        let stats = CacheStats { hits: 2, disk_hits: 1, misses: 1 };
+        assert_eq!(stats.hit_rate(), Some(0.75));
+    }
+
+    #[cfg(feature = "deezel")]
+    mod provider {
+        use super::super::*;
+        use crate::mock_provider::{MockFailure, MockProvider};
+
+        fn deposit(n: u8) -> JsonValue {
+            serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ] })
+        }
+
+        #[tokio::test]
+        async fn test_caches_confirmed_transactions() {
+            let provider = MockProvider::new(bitcoin::Network::Regtest);
+            provider.mine_block(vec![("tx_a", deposit(1))]);
+            provider.add_mempool_tx("tx_b", deposit(2));
+            let cache = CachingProvider::new(provider.clone(), CacheConfig::default());
+
+            let tx = cache.get_tx("tx_a").await.unwrap();
+            provider.inject_failure("get_tx", MockFailure::Timeout);
+            assert_eq!(cache.get_tx("tx_a").await.unwrap(), tx);
+            assert_eq!(cache.stats(), CacheStats { hits: 1, disk_hits: 0, misses: 1 });
+
+            // Unconfirmed transactions go to the provider every time, the
+            // first getting the failure the hit never reached
+            assert!(cache.get_tx("tx_b").await.is_err());
+            cache.get_tx("tx_b").await.unwrap();
+            cache.get_tx("tx_b").await.unwrap();
+            assert_eq!(cache.stats().misses, 4);
+
+            let hash = tx["status"]["block_hash"].as_str().unwrap().to_string();
+            cache.get_block(&hash).await.unwrap();
+            provider.inject_failure("get_block", MockFailure::Timeout);
+            assert_eq!(cache.get_block(&hash).await.unwrap()["height"], 1);
+            assert_eq!(cache.stats().hits, 2);
+        }
+
+        #[tokio::test]
+        async fn test_forget_after_reorg() {
+            let provider = MockProvider::new(bitcoin::Network::Regtest);
+            provider.mine_block(vec![("tx_a", deposit(1))]);
+            provider.mine_block(vec![("tx_b", deposit(2))]);
+            let cache = CachingProvider::new(provider.clone(), CacheConfig::default());
+            cache.get_tx("tx_a").await.unwrap();
+            cache.get_tx("tx_b").await.unwrap();
+
+            provider.reorg(1);
+            cache.forget_above(1);
+            assert_eq!(cache.get_tx("tx_b").await.unwrap()["status"]["confirmed"], false);
+            cache.get_tx("tx_a").await.unwrap();
+            assert_eq!(cache.stats(), CacheStats { hits: 1, disk_hits: 0, misses: 3 });
+        }
+
+        #[tokio::test]
+        async fn test_disk_cache() {
+            let directory = std::env::temp_dir().join(format!("zkane-cache-{}", std::process::id()));
+            let config = CacheConfig { capacity: 1, directory: Some(directory.clone()) };
+            let txid = "ab".repeat(32);
+            let provider = MockProvider::new(bitcoin::Network::Regtest);
+            provider.mine_block(vec![(&txid, deposit(1)), ("tx_b", deposit(2))]);
+
+            let cache = CachingProvider::new(provider.clone(), config.clone());
+            let tx = cache.get_tx(&txid).await.unwrap();
+            cache.get_tx("tx_b").await.unwrap();
+            assert!(directory.join("tx").join(format!("{}.json", txid)).exists());
+
+            // A later run reads the transaction back without the provider
+            let restarted = CachingProvider::new(provider.clone(), config);
+            provider.inject_failure("get_tx", MockFailure::Timeout);
+            assert_eq!(restarted.get_tx(&txid).await.unwrap(), tx);
+            assert_eq!(restarted.stats(), CacheStats { hits: 0, disk_hits: 1, misses: 0 });
+
+            restarted.forget_above(0);
+            assert!(!directory.join("tx").join(format!("{}.json", txid)).exists());
+            restarted.clear();
+            let _ = std::fs::remove_dir_all(&directory);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! # Consistency Checks
+//!
+//! Leaves are inserted into a [`PrivacyPool`] in the order the contract
+//! assigned them, so a synced pool's tree is a prefix of the contract's. The
+//! [`ConsistencyChecker`] compares the two through the pool's view opcodes and
+//! reports a [`ZKaneError::StateDivergence`] naming the first leaf they
+//! disagree on, instead of users finding out when their proofs are rejected.
+//!
+//! ```rust
+//! use zkane_core::{mock_provider::MockProvider, ConsistencyChecker, ConsistencyStatus, PoolClient, PrivacyPool};
+//! use zkane_common::{ZKaneConfig, ZkAssetId};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> zkane_common::ZKaneResult<()> {
+//! let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+//! let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]);
+//! let pool = PrivacyPool::new(config, provider.clone())?;
+//! provider.add_simulation_data("6:7", "10", &pool.merkle_root());
+//! provider.add_simulation_data("6:7", "11", &0u128.to_le_bytes());
+//!
+//! let checker = ConsistencyChecker::new(PoolClient::new(provider, ZkAssetId { block: 6, tx: 7 }));
+//! assert_eq!(checker.check(&pool).await?, ConsistencyStatus::InSync);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::pool_client::{PoolClient, COMMITMENT_PAGE_SIZE};
+use crate::PrivacyPool;
+use deezel_common::traits::DeezelProvider;
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// Outcome of a consistency check that found no divergence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsistencyStatus {
+    /// The local tree has every leaf of the contract's and the same root
+    InSync,
+    /// The local tree is a prefix of the contract's, missing this many leaves
+    Behind(u64),
+}
+
+/// Compares a local pool with its contract.
+pub struct ConsistencyChecker<P: DeezelProvider> {
+    client: PoolClient<P>,
+    /// Blocks between periodic checks
+    interval: u64,
+    /// Height of the last periodic check
+    last_checked: Option<u64>,
+}
+
+impl<P: DeezelProvider> ConsistencyChecker<P> {
+    /// Create a checker querying the pool contract through a client.
+    ///
+    /// Periodic checks run every block unless set with
+    /// [`with_interval`](Self::with_interval).
+    pub fn new(client: PoolClient<P>) -> Self {
+        Self {
+            client,
+            interval: 1,
+            last_checked: None,
+        }
+    }
+
+    /// Run periodic checks every `blocks` blocks.
+    pub fn with_interval(mut self, blocks: u64) -> Self {
+        self.interval = blocks.max(1);
+        self
+    }
+
+    /// Check the pool if a periodic check is due at `height`.
+    ///
+    /// # Returns
+    ///
+    /// The status, or `None` if no check was due.
+    pub async fn check_at(&mut self, pool: &PrivacyPool<P>, height: u64) -> ZKaneResult<Option<ConsistencyStatus>> {
+        if matches!(self.last_checked, Some(last) if height < last + self.interval) {
+            return Ok(None);
+        }
+        let status = self.check(pool).await?;
+        self.last_checked = Some(height);
+        Ok(Some(status))
+    }
+
+    /// Compare the pool with the contract.
+    ///
+    /// Matching leaf counts and roots are in sync without fetching any leaf.
+    /// Otherwise the contract's leaves are paged through to find the first
+    /// one the pool disagrees on.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::StateDivergence`] if a local leaf differs from
+    /// the contract's, the pool has leaves the contract doesn't, or both
+    /// have the same leaves but different roots.
+    pub async fn check(&self, pool: &PrivacyPool<P>) -> ZKaneResult<ConsistencyStatus> {
+        let chain_count = self.client.deposit_count().await?;
+        let local_count = pool.commitment_count();
+        if chain_count == local_count && self.client.merkle_root().await? == pool.merkle_root() {
+            return Ok(ConsistencyStatus::InSync);
+        }
+
+        let local = pool.commitments();
+        let overlap = local_count.min(chain_count);
+        let mut start = 0u64;
+        while start < overlap {
+            let page = self.client.commitments(start as u32, COMMITMENT_PAGE_SIZE).await?;
+            if page.is_empty() {
+                return Err(ZKaneError::StateDivergence {
+                    leaf_index: start,
+                    reason: format!("contract reports {} leaves but returned none from here", chain_count),
+                });
+            }
+            for (offset, leaf) in page.iter().enumerate() {
+                let index = start + offset as u64;
+                if index >= overlap {
+                    break;
+                }
+                if local[index as usize] != *leaf {
+                    return Err(ZKaneError::StateDivergence {
+                        leaf_index: index,
+                        reason: format!("local leaf is {}, contract leaf is {}", local[index as usize].to_hex(), leaf.to_hex()),
+                    });
+                }
+            }
+            start += page.len() as u64;
+        }
+
+        if local_count > chain_count {
+            return Err(ZKaneError::StateDivergence {
+                leaf_index: chain_count,
+                reason: format!("local tree has {} leaves, contract has {}", local_count, chain_count),
+            });
+        }
+        if local_count < chain_count {
+            return Ok(ConsistencyStatus::Behind(chain_count - local_count));
+        }
+        Err(ZKaneError::StateDivergence {
+            leaf_index: local_count,
+            reason: "every leaf matches but the roots differ; check the pool's tree hash".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use std::sync::Arc;
+    use zkane_common::{ContractEvent, ZKaneConfig, ZkAssetId};
+
+    const POOL: &str = "6:7";
+
+    fn create_pool(leaves: &[u8]) -> (MockProvider, PrivacyPool<MockProvider>) {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let mut pool = PrivacyPool::new(config, Arc::new(provider.clone())).unwrap();
+        for (index, n) in leaves.iter().enumerate() {
+            let event = ContractEvent::Deposit {
+                commitment: zkane_common::Commitment::new([*n; 32]),
+                leaf_index: index as u32,
+                root: [0u8; 32],
+            };
+            pool.apply_contract_event(&event, None).unwrap();
+        }
+        (provider, pool)
+    }
+
+    fn script_contract(provider: &MockProvider, leaves: &[u8], root: [u8; 32]) {
+        provider.add_simulation_data(POOL, "10", &root);
+        provider.add_simulation_data(POOL, "11", &(leaves.len() as u128).to_le_bytes());
+        let packed: Vec<u8> = leaves.iter().flat_map(|n| [*n; 32]).collect();
+        provider.add_simulation_data(POOL, &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &packed);
+    }
+
+    fn checker(provider: &MockProvider) -> ConsistencyChecker<MockProvider> {
+        ConsistencyChecker::new(PoolClient::new(Arc::new(provider.clone()), ZkAssetId { block: 6, tx: 7 }))
+    }
+
+    #[tokio::test]
+    async fn test_in_sync_and_behind() {
+        let (provider, pool) = create_pool(&[1, 2]);
+        script_contract(&provider, &[1, 2], pool.merkle_root());
+        assert_eq!(checker(&provider).check(&pool).await.unwrap(), ConsistencyStatus::InSync);
+
+        script_contract(&provider, &[1, 2, 3], [0u8; 32]);
+        assert_eq!(checker(&provider).check(&pool).await.unwrap(), ConsistencyStatus::Behind(1));
+    }
+
+    #[tokio::test]
+    async fn test_divergence() {
+        let (provider, pool) = create_pool(&[1, 2, 3]);
+        let divergence = |result: ZKaneResult<ConsistencyStatus>| match result {
+            Err(ZKaneError::StateDivergence { leaf_index, .. }) => leaf_index,
+            other => panic!("expected a divergence, got {:?}", other),
+        };
+
+        // The leaves were inserted out of order
+        script_contract(&provider, &[1, 3, 2, 4], [0u8; 32]);
+        assert_eq!(divergence(checker(&provider).check(&pool).await), 1);
+
+        // The local tree has a leaf the contract never saw
+        script_contract(&provider, &[1, 2], [0u8; 32]);
+        assert_eq!(divergence(checker(&provider).check(&pool).await), 2);
+
+        // Same leaves, different roots
+        script_contract(&provider, &[1, 2, 3], [0u8; 32]);
+        let err = checker(&provider).check(&pool).await.unwrap_err();
+        assert_eq!(err.code(), 3005);
+        assert_eq!(divergence(Err(err)), 3);
+    }
+
+    #[tokio::test]
+    async fn test_periodic_checks() {
+        let (provider, pool) = create_pool(&[1]);
+        script_contract(&provider, &[1], pool.merkle_root());
+        let mut checker = checker(&provider).with_interval(10);
+
+        assert_eq!(checker.check_at(&pool, 100).await.unwrap(), Some(ConsistencyStatus::InSync));
+        assert_eq!(checker.check_at(&pool, 109).await.unwrap(), None);
+        assert!(checker.check_at(&pool, 110).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,487 @@
+//! # Deposit Builder
+//!
+//! Assembles the Bitcoin transaction of a deposit. The commitment is revealed
+//! in the witness envelope of the first input, and a protostone calls the
+//! pool's `Deposit` opcode with the alkanes of the inputs. The result is an
+//! unsigned PSBT for a [`TxSigner`](crate::signer::TxSigner).
+//!
+//! ## Front-Run Protection
+//!
+//! A deposit exposes its commitment in the mempool, where it can be copied
+//! into a competing deposit. To prevent that, deposit in two phases: first
+//! confirm the transaction of [`DepositBuilder::build_registration`], which
+//! only carries the commitment's
+//! [`registration_hash`](zkane_common::Commitment::registration_hash), then
+//! reveal the commitment with the funds in a later block through a builder
+//! set to [`pre_registered`](DepositBuilder::pre_registered). The pool refuses
+//! plain deposits of a registered commitment.
+//!
+//! ## Variable Amounts
+//!
+//! A variable-amount pool takes deposits of any amount, of notes created with
+//! [`generate_circuit_note`](crate::generate_circuit_note). Their envelope
+//! carries an [`AmountWitness`] proving the commitment opens to the amount
+//! deposited, set with [`DepositBuilder::amount_witness`].
+//!
+//! ## Without a Provider
+//!
+//! Wallets that fund and sign deposits themselves lay them out with
+//! [`build_deposit_request`], which only needs the note and the UTXOs. The
+//! [`DepositPlan`] it returns holds the runestone, the envelope and the
+//! change of the transaction; the CLI and the WASM bindings build on it.
+//!
+//! ```rust
+//! use bitcoin::hashes::Hash;
+//! use bitcoin::{Amount, OutPoint, ScriptBuf, TxOut};
+//! use zkane_common::{Commitment, ZkAssetId};
+//! use zkane_core::{mock_provider::MockProvider, DepositBuilder, FundingUtxo};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> zkane_common::ZKaneResult<()> {
+//! let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+//! let utxo = FundingUtxo {
+//!     outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), 0),
+//!     txout: TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) },
+//! };
+//!
+//! let deposit = DepositBuilder::new(provider, ZkAssetId { block: 2, tx: 1 }, Commitment::new([1u8; 32]))
+//!     .utxos(vec![utxo])
+//!     .change_address("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+//!     .build()
+//!     .await?;
+//! assert_eq!(deposit.psbt.unsigned_tx.output.len(), 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::withdrawal::{call_protostone, estimate_vsize, FundingUtxo, DUST_LIMIT};
+#[cfg(feature = "deezel")]
+use crate::withdrawal::{parse_address, unsigned_inputs, DEFAULT_FEE_RATE};
+#[cfg(feature = "deezel")]
+use bitcoin::absolute::LockTime;
+use bitcoin::psbt::Psbt;
+#[cfg(feature = "deezel")]
+use bitcoin::transaction::Version;
+#[cfg(feature = "deezel")]
+use bitcoin::Transaction;
+use bitcoin::{Amount, FeeRate, ScriptBuf, TxOut};
+#[cfg(feature = "deezel")]
+use deezel_common::traits::{DeezelProvider, WalletProvider};
+#[cfg(feature = "deezel")]
+use std::sync::Arc;
+use zkane_abi::pool;
+#[cfg(feature = "deezel")]
+use zkane_abi::split_hash;
+use zkane_common::{derive_pool_id, DepositNote, ZKaneError, ZKaneResult, ZkAssetId};
+#[cfg(feature = "deezel")]
+use zkane_common::{AmountWitness, Commitment};
+
+/// Size of a taproot output script, which change is assumed to go to when
+/// planning without a change address
+const P2TR_SCRIPT_SIZE: usize = 34;
+
+/// A deposit laid out without a provider.
+///
+/// The transaction spends the UTXOs it was planned for, the envelope commit
+/// output first, and has the outputs of [`outputs`](Self::outputs): the
+/// change, then the runestone. The runestone's pointer sends the alkanes of
+/// the inputs to its protostone, which deposits them into the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositPlan {
+    /// The pool the note is deposited into
+    pub pool_id: ZkAssetId,
+    /// The runestone output script, its protostone calling the pool's
+    /// `Deposit` opcode
+    pub runestone: ScriptBuf,
+    /// The witness envelope, to be revealed by the first input
+    pub envelope: Vec<u8>,
+    /// Value of the change output
+    pub change: Amount,
+    /// Estimated size of the signed transaction, in vbytes
+    pub vsize: u64,
+    /// Fee paid by the transaction
+    pub fee: Amount,
+}
+
+impl DepositPlan {
+    /// Get the outputs of the deposit, paying change to `change_script`.
+    ///
+    /// The fee was set for a taproot change output; other scripts change the
+    /// size, and so the fee rate, slightly.
+    pub fn outputs(&self, change_script: ScriptBuf) -> Vec<TxOut> {
+        vec![
+            TxOut {
+                value: self.change,
+                script_pubkey: change_script,
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: self.runestone.clone(),
+            },
+        ]
+    }
+}
+
+/// Lay out the deposit of a note into its pool, funded by `utxos`.
+///
+/// The UTXOs must hold exactly the note's denomination of its asset, all of
+/// which is deposited, and the first must be the envelope commit output. The
+/// pool is the one [`derive_pool_id`] gives for the note's asset and
+/// denomination.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::TransactionBuildFailed`] if no UTXOs are given or
+/// they don't cover the fee and a change output.
+///
+/// # Example
+///
+/// ```rust
+/// use bitcoin::hashes::Hash;
+/// use bitcoin::{Amount, FeeRate, OutPoint, ScriptBuf, TxOut};
+/// use zkane_common::ZkAssetId;
+/// use zkane_core::{build_deposit_request, generate_deposit_note, FundingUtxo};
+///
+/// let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000)?;
+/// let utxo = FundingUtxo {
+///     outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), 0),
+///     txout: TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) },
+/// };
+///
+/// let plan = build_deposit_request(&note, &[utxo], FeeRate::from_sat_per_vb(2).unwrap())?;
+/// assert_eq!(plan.envelope, note.commitment.as_bytes().to_vec());
+/// assert_eq!(plan.change + plan.fee, Amount::from_sat(10_000));
+/// # Ok::<(), zkane_common::ZKaneError>(())
+/// ```
+pub fn build_deposit_request(note: &DepositNote, utxos: &[FundingUtxo], fee_rate: FeeRate) -> ZKaneResult<DepositPlan> {
+    let pool_id = derive_pool_id(&note.asset_id, note.denomination);
+    let runestone = deposit_runestone(&pool_id, pool::DEPOSIT)?;
+    let envelope = note.commitment.as_bytes().to_vec();
+    let mut plan = DepositPlan {
+        pool_id,
+        runestone,
+        envelope,
+        change: Amount::ZERO,
+        vsize: 0,
+        fee: Amount::ZERO,
+    };
+    let mut outputs = plan.outputs(ScriptBuf::from_bytes(vec![0u8; P2TR_SCRIPT_SIZE]));
+    (plan.vsize, plan.fee) = fund(utxos, &mut outputs, plan.envelope.len(), fee_rate)?;
+    plan.change = outputs[0].value;
+    Ok(plan)
+}
+
+/// Build the runestone calling a pool's deposit `opcode`.
+fn deposit_runestone(pool_id: &ZkAssetId, opcode: u128) -> ZKaneResult<ScriptBuf> {
+    // The alkanes of the inputs go to the protostone, the first virtual
+    // output after the change and OP_RETURN outputs
+    call_protostone(pool_id, vec![opcode], 0, Some(3))
+}
+
+/// Set the change, the first of `outputs`, to what the UTXOs leave after
+/// the fee, and get the estimated size and the fee.
+fn fund(
+    utxos: &[FundingUtxo],
+    outputs: &mut [TxOut],
+    envelope_len: usize,
+    fee_rate: FeeRate,
+) -> ZKaneResult<(u64, Amount)> {
+    if utxos.is_empty() {
+        return Err(ZKaneError::TransactionBuildFailed("no funding utxos".to_string()));
+    }
+    let vsize = estimate_vsize(utxos.len(), outputs, envelope_len);
+    let fee = fee_rate
+        .fee_vb(vsize)
+        .ok_or_else(|| ZKaneError::TransactionBuildFailed("fee overflow".to_string()))?;
+
+    let available = utxos.iter().map(|utxo| utxo.txout.value).sum::<Amount>();
+    outputs[0].value = available
+        .checked_sub(fee)
+        .filter(|change| change.to_sat() >= DUST_LIMIT)
+        .ok_or_else(|| {
+            ZKaneError::TransactionBuildFailed(format!(
+                "insufficient funds: need {}, have {}",
+                fee + Amount::from_sat(DUST_LIMIT),
+                available
+            ))
+        })?;
+    Ok((vsize, fee))
+}
+
+/// An assembled, unsigned deposit transaction.
+#[derive(Debug, Clone)]
+pub struct DepositTransaction {
+    /// The unsigned transaction, with the spent outputs of its inputs
+    pub psbt: Psbt,
+    /// The witness envelope, to be revealed by the first input (empty for a
+    /// registration)
+    pub envelope: Vec<u8>,
+    /// Estimated size of the signed transaction, in vbytes
+    pub vsize: u64,
+    /// Fee paid by the transaction
+    pub fee: Amount,
+}
+
+/// Builds deposit transactions for a pool.
+///
+/// The transaction has a change output, which also receives any alkanes the
+/// pool refunds, followed by the protostone. All alkanes of the inputs are
+/// sent to the pool, so the inputs should hold exactly the denomination, or
+/// the amount of the [`AmountWitness`] for a variable-amount pool.
+#[cfg(feature = "deezel")]
+pub struct DepositBuilder<P: DeezelProvider> {
+    provider: Arc<P>,
+    pool_id: ZkAssetId,
+    commitment: Commitment,
+    utxos: Vec<FundingUtxo>,
+    change_address: Option<String>,
+    fee_rate: FeeRate,
+    pre_registered: bool,
+    amount_witness: Option<AmountWitness>,
+}
+
+#[cfg(feature = "deezel")]
+impl<P: DeezelProvider> DepositBuilder<P> {
+    /// Create a builder for a deposit of `commitment` into a pool.
+    pub fn new(provider: Arc<P>, pool_id: ZkAssetId, commitment: Commitment) -> Self {
+        Self {
+            provider,
+            pool_id,
+            commitment,
+            utxos: Vec::new(),
+            change_address: None,
+            fee_rate: DEFAULT_FEE_RATE,
+            pre_registered: false,
+            amount_witness: None,
+        }
+    }
+
+    /// Set the UTXOs to spend, the envelope commit output first.
+    pub fn utxos(mut self, utxos: Vec<FundingUtxo>) -> Self {
+        self.utxos = utxos;
+        self
+    }
+
+    /// Send change to `address` instead of the provider's wallet address.
+    pub fn change_address(mut self, address: impl Into<String>) -> Self {
+        self.change_address = Some(address.into());
+        self
+    }
+
+    /// Set the fee rate.
+    pub fn fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Reveal a commitment registered with
+    /// [`build_registration`](Self::build_registration) instead of making a
+    /// plain deposit.
+    ///
+    /// The registration must have confirmed in an earlier block.
+    pub fn pre_registered(mut self) -> Self {
+        self.pre_registered = true;
+        self
+    }
+
+    /// Deposit into a variable-amount pool, revealing the witness's
+    /// commitment with its amount proof.
+    ///
+    /// The witness's commitment replaces the builder's.
+    pub fn amount_witness(mut self, witness: AmountWitness) -> Self {
+        self.commitment = witness.commitment;
+        self.amount_witness = Some(witness);
+        self
+    }
+
+    /// Assemble the deposit transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no UTXOs were set, the change address is invalid,
+    /// or the UTXOs don't cover the fee and a change output.
+    pub async fn build(&self) -> ZKaneResult<DepositTransaction> {
+        let opcode = if self.pre_registered {
+            pool::DEPOSIT_REGISTERED
+        } else {
+            pool::DEPOSIT
+        };
+        let protostone = deposit_runestone(&self.pool_id, opcode)?;
+        let envelope = match &self.amount_witness {
+            Some(witness) => witness.to_bytes()?,
+            None => self.commitment.as_bytes().to_vec(),
+        };
+        self.assemble(protostone, envelope).await
+    }
+
+    /// Assemble the transaction registering the commitment, the first phase
+    /// of a front-run-protected deposit.
+    ///
+    /// The transaction carries no envelope and sends no alkanes to the pool;
+    /// any alkanes of the inputs go to the change output.
+    ///
+    /// # Errors
+    ///
+    /// As for [`build`](Self::build).
+    pub async fn build_registration(&self) -> ZKaneResult<DepositTransaction> {
+        let [low, high] = split_hash(&self.commitment.registration_hash());
+        let inputs = vec![pool::REGISTER_COMMITMENT, low, high];
+        let protostone = call_protostone(&self.pool_id, inputs, 0, None)?;
+        self.assemble(protostone, Vec::new()).await
+    }
+
+    /// Assemble a transaction paying change and calling the pool through
+    /// `protostone`, with `envelope` revealed by the first input.
+    async fn assemble(&self, protostone: ScriptBuf, envelope: Vec<u8>) -> ZKaneResult<DepositTransaction> {
+        let change_address = match &self.change_address {
+            Some(address) => address.clone(),
+            None => WalletProvider::get_address(&*self.provider).await?,
+        };
+        let change_script = parse_address(&change_address, self.provider.get_network())?;
+
+        let mut outputs = vec![
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: change_script,
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: protostone,
+            },
+        ];
+        let (vsize, fee) = fund(&self.utxos, &mut outputs, envelope.len(), self.fee_rate)?;
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: unsigned_inputs(&self.utxos),
+            output: outputs,
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx)
+            .map_err(|e| ZKaneError::TransactionBuildFailed(e.to_string()))?;
+        for (input, utxo) in psbt.inputs.iter_mut().zip(&self.utxos) {
+            input.witness_utxo = Some(utxo.txout.clone());
+        }
+
+        Ok(DepositTransaction {
+            psbt,
+            envelope,
+            vsize,
+            fee,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "deezel"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf};
+
+    const CHANGE: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn utxo(sats: u64) -> FundingUtxo {
+        FundingUtxo {
+            outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), 0),
+            txout: TxOut {
+                value: Amount::from_sat(sats),
+                script_pubkey: ScriptBuf::from_bytes([vec![0x51, 0x20], vec![0x22; 32]].concat()),
+            },
+        }
+    }
+
+    fn create_builder() -> DepositBuilder<MockProvider> {
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        DepositBuilder::new(provider, ZkAssetId { block: 2, tx: 1 }, Commitment::new([4u8; 32]))
+            .change_address(CHANGE)
+            .fee_rate(FeeRate::from_sat_per_vb(3).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_build_deposit() {
+        let deposit = create_builder().utxos(vec![utxo(10_000)]).build().await.unwrap();
+
+        let tx = &deposit.psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(deposit.psbt.inputs[0].witness_utxo, Some(utxo(10_000).txout));
+        assert!(tx.output[1].script_pubkey.is_op_return());
+        assert_eq!(deposit.envelope, vec![4u8; 32]);
+        assert_eq!(deposit.fee, Amount::from_sat(deposit.vsize * 3));
+        assert_eq!(tx.output[0].value + deposit.fee, Amount::from_sat(10_000));
+    }
+
+    #[tokio::test]
+    async fn test_build_registered_deposit() {
+        let builder = create_builder().utxos(vec![utxo(10_000)]);
+        let registration = builder.build_registration().await.unwrap();
+        let plain = builder.build().await.unwrap();
+        let reveal = builder.pre_registered().build().await.unwrap();
+
+        // The registration hides the commitment
+        let commitment = Commitment::new([4u8; 32]);
+        assert!(registration.envelope.is_empty());
+        let protostone = registration.psbt.unsigned_tx.output[1].script_pubkey.as_bytes().to_vec();
+        assert!(!protostone.windows(32).any(|window| window == commitment.as_bytes()));
+
+        // The reveal carries the commitment and calls another opcode
+        assert_eq!(reveal.envelope, plain.envelope);
+        assert_eq!(reveal.fee, plain.fee);
+        assert_ne!(reveal.psbt.unsigned_tx.output[1], plain.psbt.unsigned_tx.output[1]);
+    }
+
+    #[tokio::test]
+    async fn test_build_variable_deposit() {
+        let witness = AmountWitness { commitment: Commitment::new([6u8; 32]), amount: 1234, proof: vec![1u8; 48] };
+        let builder = create_builder().utxos(vec![utxo(10_000)]).amount_witness(witness.clone());
+        let deposit = builder.build().await.unwrap();
+        assert_eq!(AmountWitness::from_envelope(&deposit.envelope).unwrap(), witness);
+
+        // The witness's commitment is the one registered
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        let expected = DepositBuilder::new(provider, ZkAssetId { block: 2, tx: 1 }, witness.commitment)
+            .change_address(CHANGE)
+            .utxos(vec![utxo(10_000)])
+            .build_registration()
+            .await
+            .unwrap();
+        let registration = builder.build_registration().await.unwrap();
+        assert_eq!(registration.psbt.unsigned_tx.output[1], expected.psbt.unsigned_tx.output[1]);
+    }
+
+    #[tokio::test]
+    async fn test_build_deposit_request() {
+        let note = crate::generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(3).unwrap();
+        let plan = build_deposit_request(&note, &[utxo(10_000)], fee_rate).unwrap();
+        assert_eq!(plan.pool_id, derive_pool_id(&note.asset_id, 1000));
+        assert_eq!(plan.envelope, note.commitment.as_bytes().to_vec());
+        assert_eq!(plan.change + plan.fee, Amount::from_sat(10_000));
+
+        // The plan matches the builder's deposit to a taproot change address
+        let taproot = bitcoin::Address::from_script(&utxo(0).txout.script_pubkey, bitcoin::Network::Regtest).unwrap();
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        let deposit = DepositBuilder::new(provider, plan.pool_id, note.commitment)
+            .change_address(taproot.to_string())
+            .fee_rate(fee_rate)
+            .utxos(vec![utxo(10_000)])
+            .build()
+            .await
+            .unwrap();
+        let change_script = deposit.psbt.unsigned_tx.output[0].script_pubkey.clone();
+        assert_eq!(deposit.psbt.unsigned_tx.output, plan.outputs(change_script));
+        assert_eq!((deposit.vsize, deposit.fee), (plan.vsize, plan.fee));
+
+        assert!(build_deposit_request(&note, &[], fee_rate).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_deposit_errors() {
+        let result = create_builder().build().await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+
+        // The change output can't be dust
+        let result = create_builder().utxos(vec![utxo(600)]).build().await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+    }
+}
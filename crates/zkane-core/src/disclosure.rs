@@ -0,0 +1,218 @@
+//! # Selective Disclosure
+//!
+//! Deposits stay private by default, but a depositor may choose to prove the
+//! source of their funds, e.g. to an exchange or an auditor. A
+//! [`DisclosurePackage`] links one deposit to its withdrawal by revealing the
+//! note, its leaf in the tree and the nullifier preimage. Anyone can check it
+//! with [`verify_disclosure`], and a pool indexer can also check it against
+//! its own state with [`PrivacyPool::check_disclosure`].
+//!
+//! Disclosure reveals only the one note: the other deposits of the pool stay
+//! unlinkable. The note can still be spent by whoever holds the package, so
+//! packages should be shared only after the withdrawal.
+//!
+//! ```rust
+//! use zkane_core::{generate_deposit_note, mock_provider::MockProvider, verify_disclosure, PrivacyPool};
+//! use zkane_common::ZKaneConfig;
+//! use zkane_common::ZkAssetId;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000)?;
+//! let mut provider = MockProvider::new(bitcoin::Network::Regtest);
+//! provider.add_response(
+//!     "deposit_txid",
+//!     serde_json::json!({ "vout": [{ "scriptpubkey": format!("6a{}", note.commitment.to_hex()), "value": 0 }] }),
+//! );
+//! let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]);
+//! let mut pool = PrivacyPool::new(config, Arc::new(provider))?;
+//! pool.add_commitment("deposit_txid").await?;
+//!
+//! let package = pool.disclose(&note, None)?;
+//! let report = verify_disclosure(&package)?;
+//! assert_eq!(report.commitment, note.commitment);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`PrivacyPool::check_disclosure`]: crate::PrivacyPool::check_disclosure
+
+use crate::verify_deposit_note;
+use serde::{Deserialize, Serialize};
+use zkane_common::{
+    Commitment, DepositNote, MerklePath, NullifierHash, TreeHash, ZKaneError, ZKaneResult, ZkAssetId,
+};
+use zkane_crypto::{generate_nullifier_hash, verify_merkle_path_with};
+
+/// Version of the disclosure package format
+pub const DISCLOSURE_VERSION: u8 = 1;
+
+/// The withdrawal a disclosure links the deposit to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisclosedWithdrawal {
+    /// Txid of the withdrawal transaction
+    pub txid: String,
+    /// Height of the block containing the withdrawal, if known
+    pub block: Option<u64>,
+}
+
+/// A verifiable link between a deposit and its withdrawal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisclosurePackage {
+    /// Format version, [`DISCLOSURE_VERSION`]
+    pub version: u8,
+    /// The disclosed note, including its secret and nullifier
+    pub note: DepositNote,
+    /// Leaf index of the note's commitment
+    pub leaf_index: u64,
+    /// Merkle path from the commitment to `root`
+    pub path: MerklePath,
+    /// A root of the pool's tree containing the commitment
+    pub root: [u8; 32],
+    /// How the pool's tree is hashed
+    pub tree_hash: TreeHash,
+    /// Height of the block containing the deposit, if known
+    pub deposit_block: Option<u64>,
+    /// The withdrawal spending the note, if it has been withdrawn
+    pub withdrawal: Option<DisclosedWithdrawal>,
+}
+
+impl DisclosurePackage {
+    /// Encode the package as JSON.
+    pub fn to_json(&self) -> ZKaneResult<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Decode a package encoded by [`to_json`](Self::to_json).
+    pub fn from_json(json: &str) -> ZKaneResult<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// The facts established by a valid disclosure.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisclosureReport {
+    /// The deposited commitment
+    pub commitment: Commitment,
+    /// Asset of the deposit
+    pub asset_id: ZkAssetId,
+    /// Denomination of the deposit
+    pub denomination: u128,
+    /// Leaf index of the commitment
+    pub leaf_index: u64,
+    /// The root the commitment was proven against
+    pub root: [u8; 32],
+    /// Nullifier hash published by the note's withdrawal
+    pub nullifier_hash: NullifierHash,
+    /// The claimed withdrawal
+    pub withdrawal: Option<DisclosedWithdrawal>,
+}
+
+/// Verify a disclosure package on its own.
+///
+/// Checks that the note's commitment opens to its secret, nullifier, asset
+/// and denomination, and that the commitment is at the leaf index under the
+/// package's root. The caller still has to check that the root is one of the
+/// pool's, and that the withdrawal transaction published the report's
+/// nullifier hash; [`PrivacyPool::check_disclosure`] does both for an
+/// indexed pool.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidCommitment`] if the note doesn't open its
+/// commitment, and [`ZKaneError::InvalidMerklePath`] if the path doesn't
+/// lead from the commitment to the root.
+///
+/// [`PrivacyPool::check_disclosure`]: crate::PrivacyPool::check_disclosure
+pub fn verify_disclosure(package: &DisclosurePackage) -> ZKaneResult<DisclosureReport> {
+    if package.version != DISCLOSURE_VERSION {
+        return Err(ZKaneError::SerializationError(format!(
+            "unsupported disclosure version {}",
+            package.version
+        )));
+    }
+    let note = &package.note;
+    if !verify_deposit_note(note)? {
+        return Err(ZKaneError::InvalidCommitment(
+            "note doesn't open its commitment".to_string(),
+        ));
+    }
+
+    let leaf_index = u32::try_from(package.leaf_index).map_err(|_| ZKaneError::InvalidMerklePath)?;
+    let tree_height = package.path.len() as u32;
+    if !verify_merkle_path_with(
+        &package.tree_hash,
+        &note.commitment,
+        leaf_index,
+        &package.path,
+        &package.root,
+        tree_height,
+    )? {
+        return Err(ZKaneError::InvalidMerklePath);
+    }
+
+    Ok(DisclosureReport {
+        commitment: note.commitment,
+        asset_id: note.asset_id,
+        denomination: note.denomination,
+        leaf_index: package.leaf_index,
+        root: package.root,
+        nullifier_hash: generate_nullifier_hash(&note.nullifier)?,
+        withdrawal: package.withdrawal.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_deposit_note;
+    use zkane_crypto::MerkleTree;
+
+    fn create_package() -> DisclosurePackage {
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        let tree = MerkleTree::from_leaves(4, &[Commitment::new([9u8; 32]), note.commitment]).unwrap();
+        DisclosurePackage {
+            version: DISCLOSURE_VERSION,
+            path: tree.generate_path(1).unwrap(),
+            note,
+            leaf_index: 1,
+            root: tree.root(),
+            tree_hash: TreeHash::Blake2s,
+            deposit_block: Some(100),
+            withdrawal: Some(DisclosedWithdrawal {
+                txid: "aa".repeat(32),
+                block: Some(200),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_verify_disclosure() {
+        let package = create_package();
+        let report = verify_disclosure(&DisclosurePackage::from_json(&package.to_json().unwrap()).unwrap()).unwrap();
+
+        assert_eq!(report.commitment, package.note.commitment);
+        assert_eq!(report.leaf_index, 1);
+        assert_eq!(report.nullifier_hash, generate_nullifier_hash(&package.note.nullifier).unwrap());
+        assert_eq!(report.withdrawal, package.withdrawal);
+    }
+
+    #[test]
+    fn test_verify_disclosure_rejects_tampering() {
+        let mut package = create_package();
+        package.note.denomination = 2000;
+        assert!(matches!(verify_disclosure(&package), Err(ZKaneError::InvalidCommitment(_))));
+
+        let mut package = create_package();
+        package.leaf_index = 0;
+        assert!(matches!(verify_disclosure(&package), Err(ZKaneError::InvalidMerklePath)));
+
+        let mut package = create_package();
+        package.tree_hash = TreeHash::Sha256;
+        assert!(matches!(verify_disclosure(&package), Err(ZKaneError::InvalidMerklePath)));
+
+        let mut package = create_package();
+        package.version = 2;
+        assert!(matches!(verify_disclosure(&package), Err(ZKaneError::SerializationError(_))));
+    }
+}
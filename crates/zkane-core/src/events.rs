@@ -0,0 +1,126 @@
+//! # Pool Events
+//!
+//! State changes of a [`PrivacyPool`](crate::PrivacyPool) are published as
+//! [`PoolEvent`]s so the frontend and relayer can react to new deposits,
+//! withdrawals and roots without polling.
+//!
+//! Subscribers receive every event published after they subscribed, in order.
+//! Dropping the stream unsubscribes.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use zkane_common::{Commitment, NullifierHash};
+
+/// A change to the state of a privacy pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoolEvent {
+    /// A commitment was inserted into the Merkle tree
+    DepositAdded {
+        /// Leaf index of the commitment
+        leaf: u64,
+        /// The deposited commitment
+        commitment: Commitment,
+        /// Height of the block containing the deposit, if confirmed
+        block: Option<u64>,
+    },
+    /// A nullifier was marked as spent
+    WithdrawalProcessed {
+        /// The spent nullifier hash
+        nullifier_hash: NullifierHash,
+        /// Height of the block containing the withdrawal, if known
+        block: Option<u64>,
+    },
+    /// The Merkle root changed
+    RootUpdated {
+        /// The new root
+        root: [u8; 32],
+        /// Number of leaves in the tree under the new root
+        leaf_count: u64,
+    },
+    /// Deposits and withdrawals confirmed above a height were undone
+    RolledBack {
+        /// Height of the last block still applied
+        height: u64,
+        /// Number of leaves left in the tree
+        leaf_count: u64,
+    },
+}
+
+/// Fans pool events out to any number of subscribers.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<UnboundedSender<PoolEvent>>>,
+}
+
+impl EventBus {
+    /// Create an event bus without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to all events published from now on.
+    pub fn subscribe(&self) -> UnboundedReceiver<PoolEvent> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Publish an event to all subscribers.
+    ///
+    /// Subscribers whose stream has been dropped are removed.
+    pub fn publish(&self, event: PoolEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Get the number of live subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        subscribers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn root_event(n: u8) -> PoolEvent {
+        PoolEvent::RootUpdated {
+            root: [n; 32],
+            leaf_count: n as u64,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_bus_fan_out() {
+        let bus = EventBus::new();
+        bus.publish(root_event(0));
+
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+        bus.publish(root_event(1));
+        bus.publish(root_event(2));
+
+        // Events published before subscribing are not delivered
+        assert_eq!(first.next().await, Some(root_event(1)));
+        assert_eq!(first.next().await, Some(root_event(2)));
+        assert_eq!(second.next().await, Some(root_event(1)));
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_removed() {
+        let bus = EventBus::new();
+        let first = bus.subscribe();
+        let _second = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 2);
+
+        drop(first);
+        bus.publish(root_event(1));
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+}
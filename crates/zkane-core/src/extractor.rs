@@ -0,0 +1,279 @@
+//! # Deposit Extraction
+//!
+//! A deposit transaction carries its commitment in one of several
+//! [`CommitmentEncoding`]s. The [`DepositExtractor`] is the one place that
+//! knows them, shared by the pool contract, [`PrivacyPool`](crate::PrivacyPool)
+//! and the WASM deposit scanner, so the indexers can't accept a deposit the
+//! contract rejects or the other way around.
+//!
+//! ```rust
+//! use bitcoin::{absolute::LockTime, transaction::Version, Amount, ScriptBuf, Transaction, TxOut};
+//! use zkane_common::Commitment;
+//! use zkane_core::{CommitmentEncoding, DepositExtractor};
+//!
+//! let tx = Transaction {
+//!     version: Version::TWO,
+//!     lock_time: LockTime::ZERO,
+//!     input: vec![],
+//!     output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([7u8; 32]) }],
+//! };
+//! let extracted = DepositExtractor::default().extract(&tx).unwrap();
+//! assert_eq!(extracted.commitment, Commitment::new([7u8; 32]));
+//! assert_eq!(extracted.encoding, CommitmentEncoding::OpReturn);
+//! ```
+
+use alkanes_support::witness::find_witness_payload;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+use zkane_common::{AmountWitness, Commitment, ZKaneError, ZKaneResult};
+
+/// First byte of a taproot annex
+const TAPROOT_ANNEX_TAG: u8 = 0x50;
+
+/// Where a deposit transaction carries its commitment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CommitmentEncoding {
+    /// The witness envelope of the first input, whose payload is the 32
+    /// bytes, or the [`AmountWitness`] of a variable-amount deposit
+    WitnessEnvelope,
+    /// An OP_RETURN output holding the 32 bytes, bare or as a single push
+    OpReturn,
+    /// The annex of the first input, `0x50` followed by the 32 bytes.
+    ///
+    /// Annexes are not relayed by default, so this is off unless enabled
+    /// with [`DepositExtractor::with_encodings`].
+    TaprootAnnex,
+}
+
+/// A commitment found in a deposit transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractedCommitment {
+    /// The deposited commitment
+    pub commitment: Commitment,
+    /// How the transaction carried it
+    pub encoding: CommitmentEncoding,
+}
+
+/// Finds the commitment of deposit transactions.
+///
+/// Encodings are tried in order, and the first one present wins. The default
+/// extractor prefers the witness envelope and falls back to OP_RETURN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositExtractor {
+    encodings: Vec<CommitmentEncoding>,
+}
+
+impl Default for DepositExtractor {
+    fn default() -> Self {
+        Self::with_encodings(vec![CommitmentEncoding::WitnessEnvelope, CommitmentEncoding::OpReturn])
+    }
+}
+
+impl DepositExtractor {
+    /// Create an extractor accepting `encodings`, in order of preference.
+    pub fn with_encodings(encodings: Vec<CommitmentEncoding>) -> Self {
+        Self { encodings }
+    }
+
+    /// Get the accepted encodings, in order of preference.
+    pub fn encodings(&self) -> &[CommitmentEncoding] {
+        &self.encodings
+    }
+
+    /// Find the commitment of a transaction.
+    pub fn extract(&self, tx: &Transaction) -> Option<ExtractedCommitment> {
+        self.encodings.iter().find_map(|&encoding| {
+            let commitment = match encoding {
+                CommitmentEncoding::WitnessEnvelope => from_witness_envelope(tx),
+                CommitmentEncoding::OpReturn => from_op_return(tx),
+                CommitmentEncoding::TaprootAnnex => from_taproot_annex(tx),
+            }?;
+            Some(ExtractedCommitment { commitment, encoding })
+        })
+    }
+
+    /// Find the commitment of a transaction in its Esplora JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::TransactionParseError`] if the JSON is malformed,
+    /// or [`ZKaneError::CommitmentNotFound`] if no accepted encoding is present.
+    pub fn extract_esplora(&self, tx: &JsonValue) -> ZKaneResult<ExtractedCommitment> {
+        self.extract(&transaction_from_esplora(tx)?)
+            .ok_or(ZKaneError::CommitmentNotFound)
+    }
+}
+
+fn from_witness_envelope(tx: &Transaction) -> Option<Commitment> {
+    let payload = find_witness_payload(tx, 0)?;
+    match <[u8; 32]>::try_from(payload.as_slice()) {
+        Ok(commitment) => Some(Commitment::new(commitment)),
+        Err(_) => AmountWitness::from_envelope(&payload).ok().map(|witness| witness.commitment),
+    }
+}
+
+fn from_op_return(tx: &Transaction) -> Option<Commitment> {
+    tx.output.iter().find_map(|output| {
+        let data = match output.script_pubkey.as_bytes() {
+            [0x6a, 0x20, data @ ..] if data.len() == 32 => data,
+            [0x6a, data @ ..] => data,
+            _ => return None,
+        };
+        <[u8; 32]>::try_from(data).ok().map(Commitment::new)
+    })
+}
+
+fn from_taproot_annex(tx: &Transaction) -> Option<Commitment> {
+    let annex = tx.input.first()?.witness.taproot_annex()?;
+    match annex {
+        [TAPROOT_ANNEX_TAG, data @ ..] => <[u8; 32]>::try_from(data).ok().map(Commitment::new),
+        _ => None,
+    }
+}
+
+/// Rebuild a transaction from its Esplora JSON representation.
+///
+/// A missing `vin` is read as a transaction without inputs.
+pub fn transaction_from_esplora(tx: &JsonValue) -> ZKaneResult<Transaction> {
+    let parse_hex = |value: &JsonValue| -> ZKaneResult<Vec<u8>> {
+        hex::decode(value.as_str().unwrap_or_default()).map_err(|_| ZKaneError::TransactionParseError)
+    };
+
+    let input = tx["vin"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|vin| {
+            let previous_output = if vin["is_coinbase"].as_bool().unwrap_or(false) {
+                OutPoint::null()
+            } else {
+                OutPoint {
+                    txid: Txid::from_str(vin["txid"].as_str().unwrap_or_default())
+                        .map_err(|_| ZKaneError::TransactionParseError)?,
+                    vout: vin["vout"].as_u64().ok_or(ZKaneError::TransactionParseError)? as u32,
+                }
+            };
+            let witness = match vin["witness"].as_array() {
+                Some(items) => Witness::from_slice(&items.iter().map(parse_hex).collect::<ZKaneResult<Vec<_>>>()?),
+                None => Witness::new(),
+            };
+            Ok(TxIn {
+                previous_output,
+                script_sig: ScriptBuf::from_bytes(parse_hex(&vin["scriptsig"])?),
+                sequence: Sequence(vin["sequence"].as_u64().unwrap_or(u32::MAX as u64) as u32),
+                witness,
+            })
+        })
+        .collect::<ZKaneResult<Vec<_>>>()?;
+
+    let output = tx["vout"]
+        .as_array()
+        .ok_or(ZKaneError::TransactionParseError)?
+        .iter()
+        .map(|vout| {
+            Ok(TxOut {
+                value: Amount::from_sat(vout["value"].as_u64().ok_or(ZKaneError::TransactionParseError)?),
+                script_pubkey: ScriptBuf::from_bytes(parse_hex(&vout["scriptpubkey"])?),
+            })
+        })
+        .collect::<ZKaneResult<Vec<_>>>()?;
+
+    Ok(Transaction {
+        version: bitcoin::transaction::Version(tx["version"].as_i64().unwrap_or(2) as i32),
+        lock_time: bitcoin::absolute::LockTime::from_consensus(tx["locktime"].as_u64().unwrap_or(0) as u32),
+        input,
+        output,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::transaction::Version;
+
+    fn tx(witness: Witness, scripts: Vec<ScriptBuf>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness,
+            }],
+            output: scripts
+                .into_iter()
+                .map(|script_pubkey| TxOut { value: Amount::ZERO, script_pubkey })
+                .collect(),
+        }
+    }
+
+    fn bare_op_return(data: &[u8]) -> ScriptBuf {
+        ScriptBuf::from_bytes([&[0x6a], data].concat())
+    }
+
+    #[test]
+    fn test_op_return_encodings() {
+        let extractor = DepositExtractor::default();
+
+        let pushed = tx(Witness::new(), vec![ScriptBuf::new_op_return([7u8; 32])]);
+        let bare = tx(Witness::new(), vec![bare_op_return(&[7u8; 32])]);
+        for tx in [pushed, bare] {
+            let extracted = extractor.extract(&tx).unwrap();
+            assert_eq!(extracted.commitment, Commitment::new([7u8; 32]));
+            assert_eq!(extracted.encoding, CommitmentEncoding::OpReturn);
+        }
+
+        // Other OP_RETURN outputs, e.g. runestones, are skipped
+        let runestone = tx(Witness::new(), vec![bare_op_return(&[0x5d, 1, 2]), bare_op_return(&[8u8; 32])]);
+        assert_eq!(extractor.extract(&runestone).unwrap().commitment, Commitment::new([8u8; 32]));
+        assert_eq!(extractor.extract(&tx(Witness::new(), vec![bare_op_return(&[1, 2])])), None);
+    }
+
+    #[test]
+    fn test_taproot_annex_is_opt_in() {
+        let annex = [vec![TAPROOT_ANNEX_TAG], vec![9u8; 32]].concat();
+        let tx = tx(Witness::from_slice(&[vec![1u8; 64], annex]), vec![]);
+        assert_eq!(DepositExtractor::default().extract(&tx), None);
+
+        let extractor = DepositExtractor::with_encodings(vec![CommitmentEncoding::TaprootAnnex]);
+        let extracted = extractor.extract(&tx).unwrap();
+        assert_eq!(extracted.commitment, Commitment::new([9u8; 32]));
+        assert_eq!(extracted.encoding, CommitmentEncoding::TaprootAnnex);
+    }
+
+    #[test]
+    fn test_encoding_preference() {
+        let annex = [vec![TAPROOT_ANNEX_TAG], vec![9u8; 32]].concat();
+        let tx = tx(Witness::from_slice(&[vec![1u8; 64], annex]), vec![bare_op_return(&[7u8; 32])]);
+
+        let extractor =
+            DepositExtractor::with_encodings(vec![CommitmentEncoding::TaprootAnnex, CommitmentEncoding::OpReturn]);
+        assert_eq!(extractor.extract(&tx).unwrap().encoding, CommitmentEncoding::TaprootAnnex);
+        let extractor =
+            DepositExtractor::with_encodings(vec![CommitmentEncoding::OpReturn, CommitmentEncoding::TaprootAnnex]);
+        assert_eq!(extractor.extract(&tx).unwrap().encoding, CommitmentEncoding::OpReturn);
+    }
+
+    #[test]
+    fn test_extract_esplora() {
+        let extractor = DepositExtractor::default();
+        let json = serde_json::json!({
+            "vin": [{ "txid": "11".repeat(32), "vout": 0, "scriptsig": "", "witness": ["aa"] }],
+            "vout": [{ "scriptpubkey": format!("6a20{}", hex::encode([7u8; 32])), "value": 0 }],
+        });
+        assert_eq!(extractor.extract_esplora(&json).unwrap().commitment, Commitment::new([7u8; 32]));
+
+        let json = serde_json::json!({ "vout": [{ "scriptpubkey": "6a0102", "value": 0 }] });
+        assert!(matches!(extractor.extract_esplora(&json), Err(ZKaneError::CommitmentNotFound)));
+        assert!(matches!(
+            extractor.extract_esplora(&serde_json::json!({})),
+            Err(ZKaneError::TransactionParseError)
+        ));
+    }
+}
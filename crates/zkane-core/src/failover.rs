@@ -0,0 +1,344 @@
+//! # Provider Failover
+//!
+//! A [`FailoverProvider`] serves [`PoolProvider`] requests from a prioritized
+//! list of providers, so a [`PoolSyncer`](crate::PoolSyncer) keeps syncing
+//! when an endpoint goes down:
+//!
+//! - Requests go to the first healthy provider and fail over down the list
+//!   on errors. A provider that fails is marked unhealthy, and is only tried
+//!   again once the healthy ones have failed too, or after a
+//!   [`health_check`](FailoverProvider::health_check) finds it healthy.
+//! - A health check asks every provider for its tip height and times the
+//!   answer. A provider is healthy if it answers within
+//!   [`FailoverConfig::max_latency`] and its tip is at most
+//!   [`FailoverConfig::max_tip_lag`] blocks behind the highest one.
+//! - In paranoid mode every transaction is fetched from two providers, and is
+//!   only returned if both agree on its inputs, outputs and block. A single
+//!   lying endpoint then can't slip a forged deposit into the pool.
+//!
+//! Native only, as health checks are timed with [`Instant`].
+
+use crate::provider::PoolProvider;
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// Health and cross-checking settings of a [`FailoverProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverConfig {
+    /// Fetch every transaction from two providers and compare them
+    pub paranoid: bool,
+    /// Blocks a healthy provider may lag behind the highest tip
+    pub max_tip_lag: u64,
+    /// Slowest health check answer of a healthy provider
+    pub max_latency: Duration,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            paranoid: false,
+            max_tip_lag: 2,
+            max_latency: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The outcome of a health check of one provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderHealth {
+    /// Name the provider was registered with
+    pub name: String,
+    /// Tip height reported, if the provider answered
+    pub tip_height: Option<u64>,
+    /// Time the provider took to answer or fail
+    pub latency: Duration,
+    /// Why the provider is unhealthy
+    pub error: Option<String>,
+}
+
+impl ProviderHealth {
+    /// Check whether the provider passed the health check.
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// A pool provider failing over between prioritized providers.
+pub struct FailoverProvider<P> {
+    providers: Vec<(String, P)>,
+    config: FailoverConfig,
+    /// Health of each provider, by priority
+    healthy: Mutex<Vec<bool>>,
+}
+
+impl<P: PoolProvider> FailoverProvider<P> {
+    /// Create a provider from named providers, highest priority first.
+    ///
+    /// All providers start out healthy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::ProviderUnavailable`] if there is no provider,
+    /// or only one in paranoid mode.
+    pub fn new(providers: Vec<(String, P)>, config: FailoverConfig) -> ZKaneResult<Self> {
+        let required = if config.paranoid { 2 } else { 1 };
+        if providers.len() < required {
+            return Err(ZKaneError::ProviderUnavailable(format!(
+                "{} provider(s) configured, {} required",
+                providers.len(),
+                required
+            )));
+        }
+        let healthy = Mutex::new(vec![true; providers.len()]);
+        Ok(Self { providers, config, healthy })
+    }
+
+    /// Get the failover settings.
+    pub fn config(&self) -> &FailoverConfig {
+        &self.config
+    }
+
+    /// Get the names of the providers, by priority.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.providers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Get the name of the provider requests currently go to first.
+    pub fn active(&self) -> &str {
+        let index = self.candidates(None)[0];
+        &self.providers[index].0
+    }
+
+    /// Check the health of every provider, and route requests to the
+    /// healthy ones first.
+    ///
+    /// # Returns
+    ///
+    /// The health of each provider, by priority.
+    pub async fn health_check(&self) -> Vec<ProviderHealth> {
+        let mut answers = Vec::with_capacity(self.providers.len());
+        for (_, provider) in &self.providers {
+            let started = Instant::now();
+            let tip_height = provider.get_tip_height().await;
+            answers.push((tip_height, started.elapsed()));
+        }
+        let best_tip = answers.iter().filter_map(|(tip, _)| tip.as_ref().ok().copied()).max();
+
+        let report: Vec<ProviderHealth> = self
+            .providers
+            .iter()
+            .zip(answers)
+            .map(|((name, _), (tip_height, latency))| {
+                let error = match &tip_height {
+                    Err(e) => Some(e.to_string()),
+                    Ok(_) if latency > self.config.max_latency => {
+                        Some(format!("answered in {} ms", latency.as_millis()))
+                    }
+                    Ok(tip) => best_tip
+                        .filter(|best| best.saturating_sub(*tip) > self.config.max_tip_lag)
+                        .map(|best| format!("tip {} is {} blocks behind", tip, best - tip)),
+                };
+                ProviderHealth { name: name.clone(), tip_height: tip_height.ok(), latency, error }
+            })
+            .collect();
+
+        *self.healthy.lock().unwrap() = report.iter().map(ProviderHealth::is_healthy).collect();
+        report
+    }
+
+    /// Get the providers to try, healthy ones first, each by priority.
+    fn candidates(&self, skip: Option<usize>) -> Vec<usize> {
+        let healthy = self.healthy.lock().unwrap();
+        let mut candidates: Vec<usize> = (0..self.providers.len()).filter(|&index| Some(index) != skip).collect();
+        candidates.sort_by_key(|&index| !healthy[index]);
+        candidates
+    }
+
+    /// Send a request to each candidate provider in turn, until one serves it.
+    ///
+    /// # Returns
+    ///
+    /// The index of the provider that served the request, and its answer.
+    async fn request<'a, T, F, Fut>(&'a self, skip: Option<usize>, send: F) -> ZKaneResult<(usize, T)>
+    where
+        F: Fn(&'a P) -> Fut,
+        Fut: Future<Output = ZKaneResult<T>>,
+    {
+        let mut last_error = None;
+        for index in self.candidates(skip) {
+            let result = send(&self.providers[index].1).await;
+            self.healthy.lock().unwrap()[index] = result.is_ok();
+            match result {
+                Ok(answer) => return Ok((index, answer)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ZKaneError::ProviderUnavailable("no other provider to ask".to_string())))
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: PoolProvider> PoolProvider for FailoverProvider<P> {
+    async fn get_tx(&self, txid: &str) -> ZKaneResult<JsonValue> {
+        let (index, tx) = self.request(None, |provider| provider.get_tx(txid)).await?;
+        if !self.config.paranoid {
+            return Ok(tx);
+        }
+
+        let (other, check) = self
+            .request(Some(index), |provider| provider.get_tx(txid))
+            .await
+            .map_err(|e| ZKaneError::ProviderUnavailable(format!("can't cross-check {}: {}", txid, e)))?;
+        match transaction_mismatch(&tx, &check) {
+            Some(field) => Err(ZKaneError::ProviderMismatch(format!(
+                "{} and {} return different {} for {}",
+                self.providers[index].0, self.providers[other].0, field, txid
+            ))),
+            None => Ok(tx),
+        }
+    }
+
+    async fn get_block(&self, hash: &str) -> ZKaneResult<JsonValue> {
+        Ok(self.request(None, |provider| provider.get_block(hash)).await?.1)
+    }
+
+    async fn get_tip_height(&self) -> ZKaneResult<u64> {
+        Ok(self.request(None, |provider| provider.get_tip_height()).await?.1)
+    }
+
+    async fn broadcast(&self, tx_hex: &str) -> ZKaneResult<String> {
+        Ok(self.request(None, |provider| provider.broadcast(tx_hex)).await?.1)
+    }
+}
+
+/// Find the part of a transaction's deposit data two providers disagree on.
+///
+/// The block is only compared if both providers report the transaction
+/// confirmed, so a provider that hasn't seen the block yet isn't a mismatch.
+fn transaction_mismatch(tx: &JsonValue, check: &JsonValue) -> Option<&'static str> {
+    if tx["vin"] != check["vin"] {
+        return Some("inputs");
+    }
+    if tx["vout"] != check["vout"] {
+        return Some("outputs");
+    }
+    let (block, check_block) = (&tx["status"]["block_hash"], &check["status"]["block_hash"]);
+    if !block.is_null() && !check_block.is_null() && block != check_block {
+        return Some("blocks");
+    }
+    None
+}
+
+#[cfg(all(test, feature = "deezel"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::{MockFailure, MockProvider};
+
+    fn deposit(n: u8) -> JsonValue {
+        serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ] })
+    }
+
+    fn chain(blocks: u64) -> MockProvider {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        provider.mine_block(vec![("tx_a", deposit(1))]);
+        for _ in 1..blocks {
+            provider.mine_block(vec![]);
+        }
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_errors() {
+        let (primary, backup) = (chain(1), chain(1));
+        let failover = FailoverProvider::new(
+            vec![("primary".to_string(), primary.clone()), ("backup".to_string(), backup.clone())],
+            FailoverConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(failover.active(), "primary");
+
+        primary.inject_failure("get_tx", MockFailure::Timeout);
+        assert_eq!(failover.get_tx("tx_a").await.unwrap()["vout"], deposit(1)["vout"]);
+        assert_eq!(failover.active(), "backup");
+
+        // The primary is skipped until a health check clears it
+        primary.inject_failure("get_tx", MockFailure::Timeout);
+        assert!(failover.get_tx("tx_a").await.is_ok());
+        failover.health_check().await;
+        assert_eq!(failover.active(), "primary");
+
+        primary.inject_failure("get_tx", MockFailure::Timeout);
+        backup.inject_failure("get_tx", MockFailure::Timeout);
+        assert!(failover.get_tx("tx_a").await.is_err());
+        assert!(failover.get_tx("tx_missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let (lagging, synced, down) = (chain(1), chain(4), chain(4));
+        let failover = FailoverProvider::new(
+            vec![
+                ("lagging".to_string(), lagging),
+                ("down".to_string(), down.clone()),
+                ("synced".to_string(), synced),
+            ],
+            FailoverConfig::default(),
+        )
+        .unwrap();
+
+        down.inject_failure("get_blocks_tip_height", MockFailure::Timeout);
+        let report = failover.health_check().await;
+        let healthy: Vec<bool> = report.iter().map(ProviderHealth::is_healthy).collect();
+        assert_eq!(healthy, vec![false, false, true]);
+        assert_eq!(report[0].tip_height, Some(1));
+        assert_eq!(report[1].tip_height, None);
+        assert_eq!(failover.active(), "synced");
+        assert_eq!(failover.get_tip_height().await.unwrap(), 4);
+
+        // Any answer taking time is too slow for a zero latency limit
+        let strict = FailoverProvider::new(
+            vec![("synced".to_string(), chain(1))],
+            FailoverConfig { max_latency: Duration::ZERO, ..FailoverConfig::default() },
+        )
+        .unwrap();
+        let health = &strict.health_check().await[0];
+        assert!(health.latency.is_zero() || !health.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_paranoid_cross_check() {
+        let config = FailoverConfig { paranoid: true, ..FailoverConfig::default() };
+        let (honest, mut liar) = (chain(1), chain(1));
+        liar.add_response("tx_a", deposit(9));
+
+        let failover = FailoverProvider::new(
+            vec![("honest".to_string(), honest.clone()), ("liar".to_string(), liar.clone())],
+            config.clone(),
+        )
+        .unwrap();
+        assert!(matches!(failover.get_tx("tx_a").await, Err(ZKaneError::ProviderMismatch(_))));
+
+        let failover = FailoverProvider::new(
+            vec![("honest".to_string(), honest.clone()), ("backup".to_string(), chain(1))],
+            config.clone(),
+        )
+        .unwrap();
+        assert_eq!(failover.get_tx("tx_a").await.unwrap()["vout"], deposit(1)["vout"]);
+
+        // Without a second answer there is nothing to cross-check against
+        honest.inject_failure("get_tx", MockFailure::Timeout);
+        let failover = FailoverProvider::new(
+            vec![("liar".to_string(), liar), ("honest".to_string(), honest)],
+            config.clone(),
+        )
+        .unwrap();
+        assert!(matches!(failover.get_tx("tx_a").await, Err(ZKaneError::ProviderUnavailable(_))));
+
+        assert!(FailoverProvider::new(vec![("only".to_string(), chain(1))], config).is_err());
+        assert!(FailoverProvider::<MockProvider>::new(vec![], FailoverConfig::default()).is_err());
+    }
+}
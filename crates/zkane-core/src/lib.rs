@@ -21,6 +21,12 @@
 //! - A set of spent nullifiers for double-spending prevention
 //! - Configuration parameters for the specific asset and denomination
 //!
+//! The pool reaches the chain through a [`PoolProvider`]. The default `deezel`
+//! feature makes every deezel provider one, and adds the pool client,
+//! deposit and withdrawal builders and SPV verification on top. Embedders
+//! that bring their own chain client disable default features and implement
+//! [`PoolProvider`] themselves.
+//!
 //! ## Usage Patterns
 //!
 //! ### Basic Pool Operations
@@ -28,12 +34,12 @@
 //! ```rust
 //! use zkane_core::{PrivacyPool, mock_provider::MockProvider};
 //! use zkane_common::ZKaneConfig;
-//! use alkanes_support::id::AlkaneId;
+//! use zkane_common::ZkAssetId;
 //! use std::sync::Arc;
 //!
 //! // Create a new privacy pool
 //! let config = ZKaneConfig::new(
-//!     AlkaneId { block: 2, tx: 1 }.into(),  // Asset ID
+//!     ZkAssetId { block: 2, tx: 1 },  // Asset ID
 //!     1000000,                              // Denomination
 //!     20,                                   // Tree height
 //!     vec![],                               // Verifier key
@@ -51,10 +57,10 @@
 //!
 //! ```rust
 //! use zkane_core::generate_deposit_note;
-//! use alkanes_support::id::AlkaneId;
+//! use zkane_common::ZkAssetId;
 //!
 //! // Generate a complete deposit note
-//! let asset_id = AlkaneId { block: 2, tx: 1 }.into();
+//! let asset_id = ZkAssetId { block: 2, tx: 1 };
 //! let denomination = 1000000u128;
 //! let deposit_note = generate_deposit_note(asset_id, denomination)?;
 //!
@@ -83,16 +89,85 @@
 //! - Configuration mismatches
 
 use zkane_common::{
-    Secret, Nullifier, Commitment, NullifierHash, DepositNote, WithdrawalProof,
-    ZKaneConfig, MerklePath, ZKaneError, ZKaneResult,
+    Secret, Nullifier, Commitment, NullifierHash, DepositNote, WithdrawalProof, SplitWitness, ContractEvent,
+    ZKaneConfig, MerklePath, TreeHash, ZkAssetId, ZKaneError, ZKaneResult, check_entropy, EntropyRng, Recipient,
 };
-use zkane_crypto::{generate_commitment, MerkleTree};
-use alkanes_support::id::AlkaneId;
-use std::collections::HashSet;
+use zkane_crypto::{generate_asset_commitment, MerkleTree};
+use std::collections::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+#[cfg(feature = "deezel")]
 use deezel_common::traits::DeezelProvider;
 use std::sync::Arc;
+use futures::Stream;
+use bitcoin::TxOut;
  
+pub mod audit;
+pub mod cache;
+#[cfg(feature = "deezel")]
+pub mod consistency;
+pub mod deposit;
+pub mod disclosure;
+pub mod events;
+pub mod extractor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod failover;
+#[cfg(feature = "deezel")]
 pub mod mock_provider;
+pub mod nullifier_index;
+#[cfg(feature = "deezel")]
+pub mod pool_client;
+pub mod provider;
+#[cfg(feature = "deezel")]
+pub mod recovery;
+pub mod rewards;
+pub mod signer;
+pub mod simulation;
+pub mod split;
+#[cfg(feature = "deezel")]
+pub mod spv;
+pub mod sync;
+pub mod verifier_keys;
+pub mod view;
+pub mod wallet;
+pub mod withdrawal;
+
+#[cfg(feature = "deezel")]
+pub use audit::audit_pool;
+pub use audit::{ReserveReport, SignedReserveReport};
+pub use cache::{CacheConfig, CacheStats, CachingProvider};
+#[cfg(feature = "deezel")]
+pub use consistency::{ConsistencyChecker, ConsistencyStatus};
+#[cfg(feature = "deezel")]
+pub use deposit::DepositBuilder;
+pub use deposit::{build_deposit_request, DepositPlan, DepositTransaction};
+pub use disclosure::{verify_disclosure, DisclosedWithdrawal, DisclosurePackage, DisclosureReport};
+pub use events::{EventBus, PoolEvent};
+pub use extractor::{CommitmentEncoding, DepositExtractor, ExtractedCommitment};
+#[cfg(not(target_arch = "wasm32"))]
+pub use failover::{FailoverConfig, FailoverProvider, ProviderHealth};
+pub use nullifier_index::NullifierIndex;
+#[cfg(feature = "deezel")]
+pub use pool_client::{FactoryClient, PoolClient, PoolInfo};
+pub use provider::PoolProvider;
+#[cfg(feature = "deezel")]
+pub use recovery::{NoteRecovery, RecoveredNote};
+pub use rewards::{plan_reward_claim, RewardClaimPlan};
+#[cfg(feature = "deezel")]
+pub use signer::ProviderSigner;
+pub use signer::TxSigner;
+pub use simulation::{CheckOutcome, PoolState, SimulationCheck, SimulationReport, SimulationStep};
+pub use split::{generate_circuit_note, plan_split, SplitPlan};
+#[cfg(feature = "deezel")]
+pub use spv::{InclusionProof, SpvVerifier, VerifiedTransaction};
+pub use sync::PoolSyncer;
+pub use verifier_keys::VerifierKeyRegistry;
+pub use view::{NoteStatus, ViewOnlyWallet, ViewingNote};
+pub use wallet::{PoolKey, WalletNote, ZkaneWallet};
+#[cfg(feature = "deezel")]
+pub use withdrawal::WithdrawalBuilder;
+pub use withdrawal::{batched_withdrawal_protostone, estimate_withdrawal_fee, WithdrawalFeeEstimate};
+pub use withdrawal::{EnvelopeCommit, FundingStrategy, FundingUtxo, WithdrawalTransaction};
 
 /// A privacy pool for a specific asset and denomination.
 ///
@@ -105,14 +180,14 @@ pub mod mock_provider;
 /// ```rust
 /// use zkane_core::{PrivacyPool, mock_provider::MockProvider};
 /// use zkane_common::ZKaneConfig;
-/// use alkanes_support::id::AlkaneId;
+/// use zkane_common::ZkAssetId;
 /// use deezel_common::traits::DeezelProvider;
 /// use std::sync::Arc;
 ///
 /// # fn test() -> Result<(), Box<dyn std::error::Error>> {
 /// let provider = MockProvider::new(bitcoin::Network::Regtest);
 /// let config = ZKaneConfig::new(
-///     AlkaneId { block: 2, tx: 1 }.into(),
+///     ZkAssetId { block: 2, tx: 1 },
 ///     1000000,
 ///     20,
 ///     vec![],
@@ -125,18 +200,26 @@ pub mod mock_provider;
 /// # Ok(())
 /// # }
 /// ```
-pub struct PrivacyPool<P: DeezelProvider> {
+pub struct PrivacyPool<P: PoolProvider> {
     /// Configuration for this pool
     config: ZKaneConfig,
-    /// Merkle tree storing commitments
-    merkle_tree: MerkleTree,
+    /// Merkle tree storing commitments, hashed as the config says
+    merkle_tree: MerkleTree<TreeHash>,
+    /// Leaf index of each commitment in the tree
+    commitment_index: HashMap<Commitment, u64>,
+    /// Height of the block containing each leaf, if confirmed
+    leaf_blocks: Vec<Option<u64>>,
     /// Set of spent nullifier hashes
     spent_nullifiers: HashSet<[u8; 32]>,
+    /// Height of the block containing each withdrawal, if known
+    nullifier_blocks: HashMap<[u8; 32], u64>,
     /// Provider for interacting with the Bitcoin network
     provider: Arc<P>,
+    /// Subscribers to state changes
+    events: EventBus,
 }
 
-impl<P: DeezelProvider> PrivacyPool<P> {
+impl<P: PoolProvider> PrivacyPool<P> {
     /// Create a new privacy pool with the given configuration.
     ///
     /// # Arguments
@@ -153,14 +236,14 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// ```rust
     /// use zkane_core::{PrivacyPool, mock_provider::MockProvider};
     /// use zkane_common::ZKaneConfig;
-    /// use alkanes_support::id::AlkaneId;
+    /// use zkane_common::ZkAssetId;
     /// use deezel_common::traits::DeezelProvider;
     /// use std::sync::Arc;
     ///
     /// # fn test() -> Result<(), Box<dyn std::error::Error>> {
     /// let provider = MockProvider::new(bitcoin::Network::Regtest);
     /// let config = ZKaneConfig::new(
-    ///     AlkaneId { block: 2, tx: 1 }.into(),
+    ///     ZkAssetId { block: 2, tx: 1 },
     ///     1000000,
     ///     20,
     ///     vec![],
@@ -170,13 +253,17 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// # }
     /// ```
     pub fn new(config: ZKaneConfig, provider: Arc<P>) -> ZKaneResult<Self> {
-        let merkle_tree = MerkleTree::new(config.tree_height);
+        let merkle_tree = MerkleTree::try_with_hasher(config.tree_height, config.tree_hash)?;
         
         Ok(Self {
             config,
             merkle_tree,
+            commitment_index: HashMap::new(),
+            leaf_blocks: Vec::new(),
             spent_nullifiers: HashSet::new(),
+            nullifier_blocks: HashMap::new(),
             provider,
+            events: EventBus::new(),
         })
     }
 
@@ -185,6 +272,20 @@ impl<P: DeezelProvider> PrivacyPool<P> {
         &self.config
     }
 
+    /// Get the provider the pool reads the chain through.
+    pub fn provider(&self) -> &Arc<P> {
+        &self.provider
+    }
+
+    /// Subscribe to state changes of this pool.
+    ///
+    /// The stream yields a [`PoolEvent`] for every deposit added, withdrawal
+    /// processed and root update from now on, in the order they happened.
+    /// Dropping the stream unsubscribes.
+    pub fn subscribe(&self) -> impl Stream<Item = PoolEvent> {
+        self.events.subscribe()
+    }
+
     /// Get the current Merkle root of the commitment tree.
     ///
     /// The Merkle root represents the current state of all commitments in the pool
@@ -199,13 +300,13 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// ```rust
     /// # use zkane_core::{PrivacyPool, mock_provider::MockProvider};
     /// # use zkane_common::ZKaneConfig;
-    /// # use alkanes_support::id::AlkaneId;
+    /// # use zkane_common::ZkAssetId;
     /// # use std::sync::Arc;
     /// #
     /// # fn test() -> Result<(), Box<dyn std::error::Error>> {
     /// # let provider = MockProvider::new(bitcoin::Network::Regtest);
     /// # let config = ZKaneConfig::new(
-    /// #     AlkaneId { block: 2, tx: 1 }.into(), 1000000, 20, vec![]
+    /// #     ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]
     /// # );
     /// # let pool = PrivacyPool::new(config, Arc::new(provider))?;
     /// let root = pool.merkle_root();
@@ -226,6 +327,28 @@ impl<P: DeezelProvider> PrivacyPool<P> {
         self.merkle_tree.leaf_count().into()
     }
 
+    /// Get the commitments in the pool, in leaf order.
+    pub fn commitments(&self) -> Vec<Commitment> {
+        let mut leaves: Vec<(u64, Commitment)> = self
+            .commitment_index
+            .iter()
+            .map(|(commitment, leaf)| (*leaf, *commitment))
+            .collect();
+        leaves.sort_unstable_by_key(|(leaf, _)| *leaf);
+        leaves.into_iter().map(|(_, commitment)| commitment).collect()
+    }
+
+    /// Get the leaf index of a commitment in the pool.
+    ///
+    /// Wallets use this to recover which leaf their note occupies.
+    ///
+    /// # Returns
+    ///
+    /// The leaf index, or `None` if the commitment hasn't been deposited.
+    pub fn leaf_index_of(&self, commitment: &Commitment) -> Option<u64> {
+        self.commitment_index.get(commitment).copied()
+    }
+
     /// Check if a nullifier hash has been spent.
     ///
     /// # Arguments
@@ -241,13 +364,13 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// ```rust
     /// # use zkane_core::{PrivacyPool, mock_provider::MockProvider};
     /// # use zkane_common::ZKaneConfig;
-    /// # use alkanes_support::id::AlkaneId;
+    /// # use zkane_common::ZkAssetId;
     /// # use std::sync::Arc;
     /// #
     /// # fn test() -> Result<(), Box<dyn std::error::Error>> {
     /// # let provider = MockProvider::new(bitcoin::Network::Regtest);
     /// # let config = ZKaneConfig::new(
-    /// #     AlkaneId { block: 2, tx: 1 }.into(), 1000000, 20, vec![]
+    /// #     ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]
     /// # );
     /// # let pool = PrivacyPool::new(config, Arc::new(provider))?;
     ///
@@ -263,12 +386,14 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// Add a commitment to the pool.
     ///
     /// This method adds a new commitment to the Merkle tree, representing a new deposit.
-    /// The commitment should be generated using [`generate_commitment`] from a secret
-    /// and nullifier pair.
+    /// The commitment should be generated using [`generate_asset_commitment`] from a
+    /// secret and nullifier pair and the pool's asset ID and denomination.
+    /// It is read from the deposit transaction with the default
+    /// [`DepositExtractor`], the same way the pool contract reads it.
     ///
     /// # Arguments
     ///
-    /// * `commitment` - The commitment to add
+    /// * `txid` - The deposit transaction
     ///
     /// # Returns
     ///
@@ -276,20 +401,22 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     ///
     /// # Errors
     ///
-    /// Returns an error if the tree is full or if there's a cryptographic error.
+    /// Returns [`ZKaneError::DuplicateCommitment`] if the commitment is already
+    /// in the pool, or an error if the tree is full or if there's a
+    /// cryptographic error.
     ///
     /// # Example
     ///
     /// ```rust
     /// # use zkane_core::{PrivacyPool, mock_provider::MockProvider};
     /// # use zkane_common::{ZKaneConfig, Commitment};
-    /// # use alkanes_support::id::AlkaneId;
+    /// # use zkane_common::ZkAssetId;
     /// # use std::sync::Arc;
     /// #
     /// # async fn test() -> Result<(), Box<dyn std::error::Error>> {
     /// # let mut provider = MockProvider::new(bitcoin::Network::Regtest);
     /// # let config = ZKaneConfig::new(
-    /// #     AlkaneId { block: 2, tx: 1 }.into(), 1000000, 20, vec![]
+    /// #     ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]
     /// # );
     ///
     /// let txid = "mock_txid";
@@ -312,29 +439,36 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// ```
     pub async fn add_commitment(&mut self, txid: &str) -> ZKaneResult<u64> {
         let tx_info = self.provider.get_tx(txid).await?;
-        
-        let vout = tx_info["vout"].as_array().ok_or(ZKaneError::TransactionParseError)?;
-        
-        let commitment = vout.iter()
-            .find_map(|output| {
-                let script_pubkey = output["scriptpubkey"].as_str()?;
-                if script_pubkey.starts_with("6a") { // OP_RETURN
-                    let data = hex::decode(&script_pubkey[2..]).ok()?;
-                    if data.len() == 32 {
-                        let mut commitment_bytes = [0u8; 32];
-                        commitment_bytes.copy_from_slice(&data);
-                        Some(Commitment::new(commitment_bytes))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .ok_or(ZKaneError::CommitmentNotFound)?;
+        let commitment = DepositExtractor::default().extract_esplora(&tx_info)?.commitment;
+
+        // The contract rejects repeated commitments, so the tree must too
+        if let Some(existing) = self.leaf_index_of(&commitment) {
+            return Err(ZKaneError::DuplicateCommitment(format!(
+                "{} is already at leaf {}",
+                commitment.to_hex(),
+                existing
+            )));
+        }
+
+        self.insert_commitment(commitment, tx_info["status"]["block_height"].as_u64())
+    }
+
+    /// Insert a commitment into the tree and publish the new leaf and root.
+    fn insert_commitment(&mut self, commitment: Commitment, block: Option<u64>) -> ZKaneResult<u64> {
+        let leaf_index = self.merkle_tree.insert(&commitment)?;
+        self.commitment_index.insert(commitment, leaf_index.into());
+        self.leaf_blocks.push(block);
+
+        self.events.publish(PoolEvent::DepositAdded {
+            leaf: leaf_index.into(),
+            commitment,
+            block,
+        });
+        self.events.publish(PoolEvent::RootUpdated {
+            root: self.merkle_root(),
+            leaf_count: self.commitment_count(),
+        });
 
-        let leaf_index = self.merkle_tree.insert(&commitment)
-            .map_err(|e| ZKaneError::CryptoError(e.to_string()))?;
         Ok(leaf_index.into())
     }
 
@@ -358,6 +492,171 @@ impl<P: DeezelProvider> PrivacyPool<P> {
         self.merkle_tree.generate_path(leaf_index as u32)
     }
 
+    /// Create a disclosure package linking a deposited note to its withdrawal.
+    ///
+    /// The package proves the note's commitment against the current root.
+    /// Pass the txid of the withdrawal to link it; the withdrawal's block is
+    /// filled in if the pool has seen the note's nullifier spent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnknownCommitment`] if the note hasn't been
+    /// deposited in the pool, and [`ZKaneError::InvalidNullifier`] if a
+    /// withdrawal is given but the note's nullifier isn't spent.
+    pub fn disclose(&self, note: &DepositNote, withdrawal_txid: Option<String>) -> ZKaneResult<DisclosurePackage> {
+        let leaf_index = self.leaf_index_of(&note.commitment).ok_or(ZKaneError::UnknownCommitment)?;
+        let withdrawal = match withdrawal_txid {
+            Some(txid) => {
+                let nullifier_hash = zkane_crypto::generate_nullifier_hash(&note.nullifier)?;
+                if !self.is_nullifier_spent(nullifier_hash.as_bytes()) {
+                    return Err(ZKaneError::InvalidNullifier("note has not been withdrawn".to_string()));
+                }
+                Some(DisclosedWithdrawal {
+                    txid,
+                    block: self.nullifier_blocks.get(nullifier_hash.as_bytes()).copied(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(DisclosurePackage {
+            version: disclosure::DISCLOSURE_VERSION,
+            note: note.clone(),
+            leaf_index,
+            path: self.generate_merkle_proof(leaf_index)?,
+            root: self.merkle_root(),
+            tree_hash: self.config.tree_hash,
+            deposit_block: self.leaf_blocks.get(leaf_index as usize).copied().flatten(),
+            withdrawal,
+        })
+    }
+
+    /// Verify a disclosure package against the pool's state.
+    ///
+    /// On top of [`verify_disclosure`], checks that the note is for this
+    /// pool, that the root is one of the pool's recent roots, and that a
+    /// claimed withdrawal has spent the note's nullifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`verify_disclosure`],
+    /// [`ZKaneError::InvalidDenomination`] if the note is for another pool,
+    /// [`ZKaneError::InvalidMerkleRoot`] if the root is unknown, and
+    /// [`ZKaneError::InvalidNullifier`] if the claimed withdrawal hasn't
+    /// happened.
+    pub fn check_disclosure(&self, package: &DisclosurePackage) -> ZKaneResult<DisclosureReport> {
+        let report = verify_disclosure(package)?;
+        if report.asset_id != self.config.asset_id || report.denomination != self.config.denomination {
+            return Err(ZKaneError::InvalidDenomination);
+        }
+        if package.tree_hash != self.config.tree_hash || !self.merkle_tree.is_known_root(&report.root) {
+            return Err(ZKaneError::InvalidMerkleRoot);
+        }
+        if report.withdrawal.is_some() && !self.is_nullifier_spent(report.nullifier_hash.as_bytes()) {
+            return Err(ZKaneError::InvalidNullifier("note has not been withdrawn".to_string()));
+        }
+        Ok(report)
+    }
+
+    /// Process a split withdrawal seen on chain.
+    ///
+    /// Marks the spent note's nullifier as spent and inserts the fresh
+    /// commitments, in order, as the pool contract does.
+    ///
+    /// # Returns
+    ///
+    /// The leaf indices of the fresh commitments.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::NullifierAlreadySpent`] if the note was already
+    /// spent, [`ZKaneError::DuplicateCommitment`] if a fresh commitment is
+    /// already in the pool, and [`ZKaneError::TreeFull`] if they don't fit.
+    /// Nothing is changed on error.
+    pub fn process_split(&mut self, witness: &SplitWitness, block_height: Option<u64>) -> ZKaneResult<Vec<u64>> {
+        self.apply_split(
+            witness.withdrawal.proof.nullifier_hash.as_bytes(),
+            &witness.output_commitments,
+            block_height,
+        )
+    }
+
+    /// Apply an event emitted by the pool contract.
+    ///
+    /// Deposits and splits must insert their commitments at the leaf index
+    /// the contract reports, so a missed or repeated event is caught instead
+    /// of shifting every later leaf.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidCommitment`] if the event's leaf index
+    /// isn't the next leaf, and the errors of
+    /// [`process_split`](Self::process_split) and
+    /// [`process_withdrawal`](Self::process_withdrawal). Nothing is changed on
+    /// error.
+    pub fn apply_contract_event(&mut self, event: &ContractEvent, block_height: Option<u64>) -> ZKaneResult<()> {
+        match event {
+            ContractEvent::Deposit { commitment, leaf_index, .. } => {
+                self.check_next_leaf(*leaf_index)?;
+                if let Some(existing) = self.leaf_index_of(commitment) {
+                    return Err(ZKaneError::DuplicateCommitment(format!(
+                        "{} is already at leaf {}",
+                        commitment.to_hex(),
+                        existing
+                    )));
+                }
+                self.insert_commitment(*commitment, block_height).map(|_| ())
+            }
+            ContractEvent::Withdrawal(spend) => self.spend_nullifier(spend.nullifier_hash.as_bytes(), block_height),
+            ContractEvent::Split {
+                spend,
+                first_leaf_index,
+                commitments,
+                ..
+            } => {
+                self.check_next_leaf(*first_leaf_index)?;
+                self.apply_split(spend.nullifier_hash.as_bytes(), commitments, block_height)
+                    .map(|_| ())
+            }
+        }
+    }
+
+    fn check_next_leaf(&self, leaf_index: u32) -> ZKaneResult<()> {
+        if u64::from(leaf_index) != self.commitment_count() {
+            return Err(ZKaneError::InvalidCommitment(format!(
+                "event inserts at leaf {}, but the next leaf is {}",
+                leaf_index,
+                self.commitment_count()
+            )));
+        }
+        Ok(())
+    }
+
+    fn apply_split(
+        &mut self,
+        nullifier_hash: &[u8; 32],
+        outputs: &[Commitment],
+        block_height: Option<u64>,
+    ) -> ZKaneResult<Vec<u64>> {
+        if self.is_nullifier_spent(nullifier_hash) {
+            return Err(ZKaneError::NullifierAlreadySpent);
+        }
+        for (index, commitment) in outputs.iter().enumerate() {
+            if self.commitment_index.contains_key(commitment) || outputs[..index].contains(commitment) {
+                return Err(ZKaneError::DuplicateCommitment(commitment.to_hex()));
+            }
+        }
+        if self.commitment_count() + outputs.len() as u64 > self.max_capacity() {
+            return Err(ZKaneError::TreeFull);
+        }
+
+        self.spend_nullifier(nullifier_hash, block_height)?;
+        outputs
+            .iter()
+            .map(|commitment| self.insert_commitment(*commitment, block_height))
+            .collect()
+    }
+
     /// Process a withdrawal by marking the nullifier as spent.
     ///
     /// This method should be called after verifying a withdrawal proof to prevent
@@ -380,13 +679,13 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// ```rust
     /// # use zkane_core::{PrivacyPool, mock_provider::MockProvider};
     /// # use zkane_common::ZKaneConfig;
-    /// # use alkanes_support::id::AlkaneId;
+    /// # use zkane_common::ZkAssetId;
     /// # use std::sync::Arc;
     /// #
     /// # fn test() -> Result<(), Box<dyn std::error::Error>> {
     /// # let provider = MockProvider::new(bitcoin::Network::Regtest);
     /// # let config = ZKaneConfig::new(
-    /// #     AlkaneId { block: 2, tx: 1 }.into(), 1000000, 20, vec![]
+    /// #     ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]
     /// # );
     /// # let mut pool = PrivacyPool::new(config, Arc::new(provider))?;
     ///
@@ -402,14 +701,84 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     /// # }
     /// ```
     pub fn process_withdrawal(&mut self, nullifier_hash: &[u8; 32]) -> ZKaneResult<()> {
+        self.spend_nullifier(nullifier_hash, None)
+    }
+
+    /// Process a withdrawal confirmed in the block at `block_height`.
+    ///
+    /// Same as [`process_withdrawal`](Self::process_withdrawal), but the
+    /// published [`PoolEvent::WithdrawalProcessed`] carries the block height.
+    pub fn process_withdrawal_in_block(&mut self, nullifier_hash: &[u8; 32], block_height: u64) -> ZKaneResult<()> {
+        self.spend_nullifier(nullifier_hash, Some(block_height))
+    }
+
+    fn spend_nullifier(&mut self, nullifier_hash: &[u8; 32], block: Option<u64>) -> ZKaneResult<()> {
         if self.spent_nullifiers.contains(nullifier_hash) {
             return Err(ZKaneError::NullifierAlreadySpent);
         }
         
         self.spent_nullifiers.insert(*nullifier_hash);
+        if let Some(height) = block {
+            self.nullifier_blocks.insert(*nullifier_hash, height);
+        }
+        self.events.publish(PoolEvent::WithdrawalProcessed {
+            nullifier_hash: NullifierHash::new(*nullifier_hash),
+            block,
+        });
         Ok(())
     }
 
+    /// Undo every deposit and withdrawal confirmed above `height`.
+    ///
+    /// Call this when the provider reports a reorg, so the pool never serves
+    /// roots from orphaned blocks. The Merkle tree is truncated at the first
+    /// leaf confirmed above `height`; leaves after it are removed as well,
+    /// since the tree is append-only. Withdrawals processed without a block
+    /// height are kept.
+    ///
+    /// Publishes [`PoolEvent::RolledBack`], followed by
+    /// [`PoolEvent::RootUpdated`] if any leaf was removed.
+    ///
+    /// # Returns
+    ///
+    /// The number of leaves removed.
+    pub fn rollback_to_height(&mut self, height: u64) -> ZKaneResult<u64> {
+        let leaf_count = self
+            .leaf_blocks
+            .iter()
+            .position(|block| matches!(block, Some(block) if *block > height))
+            .unwrap_or(self.leaf_blocks.len());
+        let removed = (self.leaf_blocks.len() - leaf_count) as u64;
+
+        self.merkle_tree.truncate(leaf_count as u32)?;
+        self.leaf_blocks.truncate(leaf_count);
+        self.commitment_index.retain(|_, leaf| *leaf < leaf_count as u64);
+
+        let orphaned: Vec<[u8; 32]> = self
+            .nullifier_blocks
+            .iter()
+            .filter(|(_, block)| **block > height)
+            .map(|(nullifier_hash, _)| *nullifier_hash)
+            .collect();
+        for nullifier_hash in &orphaned {
+            self.nullifier_blocks.remove(nullifier_hash);
+            self.spent_nullifiers.remove(nullifier_hash);
+        }
+
+        self.events.publish(PoolEvent::RolledBack {
+            height,
+            leaf_count: self.commitment_count(),
+        });
+        if removed > 0 {
+            self.events.publish(PoolEvent::RootUpdated {
+                root: self.merkle_root(),
+                leaf_count: self.commitment_count(),
+            });
+        }
+
+        Ok(removed)
+    }
+
     /// Verify a withdrawal proof against the current pool state.
     ///
     /// This method performs all the cryptographic verification needed to validate
@@ -427,6 +796,8 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     ///
     /// This method only verifies the proof; it does not mark the nullifier as spent.
     /// Call [`process_withdrawal`] after successful verification to update the state.
+    /// [`simulate_withdrawal`](Self::simulate_withdrawal) reports which check
+    /// fails instead.
     pub fn verify_withdrawal_proof(&self, proof: &WithdrawalProof) -> bool {
         // Check if nullifier is already spent
         if self.is_nullifier_spent(proof.nullifier_hash.as_bytes()) {
@@ -438,11 +809,50 @@ impl<P: DeezelProvider> PrivacyPool<P> {
             return false;
         }
 
+        // Notes of a variable pool are only withdrawn with split withdrawals
+        if self.config.is_variable() {
+            return false;
+        }
+
+        // Check that the proof was generated for this pool's circuit
+        if self.config.check_circuit_version(proof).is_err() {
+            return false;
+        }
+
+        // Check that the relayer fee can be paid out of the denomination
+        if proof.validate_fee(self.config.denomination).is_err() {
+            return false;
+        }
+
         // In a full implementation, this would verify the zero-knowledge proof
         // For now, we assume the proof is valid if basic checks pass
         true
     }
 
+    /// Simulate a withdrawal against the current pool state.
+    ///
+    /// Runs every check the contract makes, including proof verification if
+    /// the config has a verifier key, and reports the outcome of each. The
+    /// transaction outputs aren't checked; see
+    /// [`simulate_withdrawal_with_outputs`](Self::simulate_withdrawal_with_outputs).
+    pub fn simulate_withdrawal(&self, proof: &WithdrawalProof) -> SimulationReport {
+        simulation::simulate_withdrawal(&self.simulation_state(proof), proof, None)
+    }
+
+    /// Simulate a withdrawal paying the given transaction outputs.
+    pub fn simulate_withdrawal_with_outputs(&self, proof: &WithdrawalProof, outputs: &[TxOut]) -> SimulationReport {
+        simulation::simulate_withdrawal(&self.simulation_state(proof), proof, Some(outputs))
+    }
+
+    fn simulation_state(&self, proof: &WithdrawalProof) -> PoolState<'_> {
+        PoolState {
+            config: &self.config,
+            merkle_root: self.merkle_root(),
+            past_root: self.merkle_tree.is_known_root(&proof.merkle_root),
+            nullifier_spent: self.is_nullifier_spent(proof.nullifier_hash.as_bytes()),
+        }
+    }
+
     /// Get the maximum capacity of this pool.
     ///
     /// # Returns
@@ -475,10 +885,45 @@ impl<P: DeezelProvider> PrivacyPool<P> {
     }
 }
 
+#[cfg(feature = "deezel")]
+impl<P: DeezelProvider> PrivacyPool<P> {
+    /// Add a commitment from a deposit transaction proven to be in the chain.
+    ///
+    /// Like [`add_commitment`](Self::add_commitment), but the commitment is
+    /// read from the raw transaction after an [`SpvVerifier`] has checked it
+    /// against its block's header, so a malicious provider can't insert
+    /// commitments that were never deposited.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidInclusionProof`] if the transaction can't
+    /// be proven to be in a block, in addition to the errors of
+    /// [`add_commitment`](Self::add_commitment).
+    pub async fn add_verified_commitment(&mut self, txid: &str) -> ZKaneResult<u64> {
+        let verified = SpvVerifier::new(self.provider.clone()).verified_transaction(txid).await?;
+        let commitment = DepositExtractor::default()
+            .extract(&verified.tx)
+            .ok_or(ZKaneError::CommitmentNotFound)?
+            .commitment;
+
+        if let Some(existing) = self.leaf_index_of(&commitment) {
+            return Err(ZKaneError::DuplicateCommitment(format!(
+                "{} is already at leaf {}",
+                commitment.to_hex(),
+                existing
+            )));
+        }
+
+        self.insert_commitment(commitment, Some(verified.block_height))
+    }
+}
+
 /// Generate a complete deposit note for the given asset and denomination.
 ///
 /// This function creates all the cryptographic material needed for a deposit,
-/// including the secret, nullifier, and commitment. The resulting deposit note
+/// including the secret, nullifier, and commitment. The commitment is bound to
+/// the asset and denomination (see [`generate_asset_commitment`]), so the note
+/// is only valid for pools of that asset and denomination. The resulting deposit note
 /// contains everything a user needs to later withdraw their funds.
 ///
 /// # Arguments
@@ -494,9 +939,9 @@ impl<P: DeezelProvider> PrivacyPool<P> {
 ///
 /// ```rust
 /// use zkane_core::generate_deposit_note;
-/// use alkanes_support::id::AlkaneId;
+/// use zkane_common::ZkAssetId;
 ///
-/// let asset_id = AlkaneId { block: 2, tx: 1 }.into();
+/// let asset_id = ZkAssetId { block: 2, tx: 1 };
 /// let denomination = 1000000u128;
 /// let deposit_note = generate_deposit_note(asset_id, denomination)?;
 ///
@@ -509,29 +954,56 @@ impl<P: DeezelProvider> PrivacyPool<P> {
 ///
 /// # Security Notes
 ///
-/// - The secret and nullifier are generated using secure randomness
+/// - The secret and nullifier are drawn from the target's `SystemEntropy`,
+///   which is self-tested first
 /// - The deposit note should be stored securely by the user
 /// - Loss of the deposit note makes withdrawal impossible
-pub fn generate_deposit_note(asset_id: AlkaneId, denomination: u128) -> ZKaneResult<DepositNote> {
-    let secret = Secret::random();
-    let nullifier = Nullifier::random();
-    let commitment = generate_commitment(&nullifier, &secret)
-        .map_err(|e| ZKaneError::CryptoError(e.to_string()))?;
+pub fn generate_deposit_note(asset_id: ZkAssetId, denomination: u128) -> ZKaneResult<DepositNote> {
+    check_entropy()?;
+    generate_deposit_note_with_rng(asset_id, denomination, &mut EntropyRng::system())
+}
+
+/// Generate a deposit note with the given random number generator.
+///
+/// Like [`generate_deposit_note`], but the secret and nullifier are drawn
+/// from `rng`, so tests and fuzzing can use a seeded generator from
+/// [`note_rng`] and reproduce their notes.
+pub fn generate_deposit_note_with_rng<R: RngCore + CryptoRng>(
+    asset_id: ZkAssetId,
+    denomination: u128,
+    rng: &mut R,
+) -> ZKaneResult<DepositNote> {
+    let secret = Secret::random_with_rng(rng);
+    let nullifier = Nullifier::random_with_rng(rng);
+    let commitment = generate_asset_commitment(&nullifier, &secret, &asset_id, denomination)?;
 
     Ok(DepositNote::new(
         secret,
         nullifier,
         commitment,
-        asset_id.into(),
+        asset_id,
         denomination,
         0, // Leaf index will be set when deposited
     ))
 }
 
+/// Get a random number generator for notes, seeded if `seed` is given.
+///
+/// Without a seed the generator is seeded from the operating system, which
+/// is what production code should use. A fixed seed yields the same notes on
+/// every run and is only meant for tests.
+pub fn note_rng(seed: Option<u64>) -> StdRng {
+    seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+}
+
 /// Verify the integrity of a deposit note.
 ///
 /// This function checks that the commitment in a deposit note was correctly
-/// generated from the secret and nullifier.
+/// generated from the secret and nullifier, and that it is bound to the note's
+/// asset ID and denomination.
+///
+/// The commitment of a version 1 note is computed as that of the current
+/// version, so old notes verify unchanged; extensions don't enter it.
 ///
 /// # Arguments
 ///
@@ -541,13 +1013,18 @@ pub fn generate_deposit_note(asset_id: AlkaneId, denomination: u128) -> ZKaneRes
 ///
 /// A `Result` containing `true` if the note is valid, `false` otherwise.
 ///
+/// # Errors
+///
+/// Returns [`ZKaneError::UnsupportedNoteVersion`] if the note was written by
+/// a newer release.
+///
 /// # Example
 ///
 /// ```rust
 /// use zkane_core::{generate_deposit_note, verify_deposit_note};
-/// use alkanes_support::id::AlkaneId;
+/// use zkane_common::ZkAssetId;
 ///
-/// let asset_id = AlkaneId { block: 2, tx: 1 }.into();
+/// let asset_id = ZkAssetId { block: 2, tx: 1 };
 /// let note = generate_deposit_note(asset_id, 1000000)?;
 ///
 /// // Valid note should verify
@@ -555,8 +1032,9 @@ pub fn generate_deposit_note(asset_id: AlkaneId, denomination: u128) -> ZKaneRes
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn verify_deposit_note(note: &DepositNote) -> ZKaneResult<bool> {
-    let computed_commitment = generate_commitment(&note.nullifier, &note.secret)
-        .map_err(|e| ZKaneError::CryptoError(e.to_string()))?;
+    note.check_version()?;
+    let computed_commitment =
+        generate_asset_commitment(&note.nullifier, &note.secret, &note.asset_id, note.denomination)?;
     
     Ok(computed_commitment == note.commitment)
 }
@@ -571,7 +1049,7 @@ pub fn verify_deposit_note(note: &DepositNote) -> ZKaneResult<bool> {
 /// * `proof_bytes` - The zero-knowledge proof data
 /// * `merkle_root` - The Merkle root at time of proof generation
 /// * `nullifier_hash` - The nullifier hash being revealed
-/// * `recipient` - The recipient address
+/// * `recipient` - The recipient scriptPubKey, see [`Recipient::from_address`]
 ///
 /// # Returns
 ///
@@ -586,7 +1064,7 @@ pub fn create_withdrawal_proof(
     proof_bytes: Vec<u8>,
     merkle_root: [u8; 32],
     nullifier_hash: NullifierHash,
-    recipient: u128,
+    recipient: Recipient,
 ) -> WithdrawalProof {
     WithdrawalProof::new(proof_bytes, merkle_root, nullifier_hash, recipient)
 }
@@ -599,7 +1077,7 @@ mod tests {
 
     fn create_test_pool() -> PrivacyPool<MockProvider> {
         let config = ZKaneConfig::new(
-            alkanes_support::id::AlkaneId { block: 2, tx: 1 }.into(),
+            ZkAssetId { block: 2, tx: 1 },
             1000000,
             4, // Small tree for testing
             vec![],
@@ -644,6 +1122,111 @@ mod tests {
         assert_eq!(pool.commitment_count(), 1);
     }
 
+    #[tokio::test]
+    async fn test_disclosure() {
+        let mut pool = create_test_pool();
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        assert!(matches!(pool.disclose(&note, None), Err(ZKaneError::UnknownCommitment)));
+
+        pool.provider.responses.lock().unwrap().insert(
+            "mock_txid".to_string(),
+            serde_json::json!({
+                "vout": [ { "scriptpubkey": format!("6a{}", note.commitment.to_hex()), "value": 0 } ],
+                "status": { "block_height": 100 },
+            }),
+        );
+        pool.add_commitment("mock_txid").await.unwrap();
+
+        let package = pool.disclose(&note, None).unwrap();
+        assert_eq!(package.deposit_block, Some(100));
+        assert_eq!(pool.check_disclosure(&package).unwrap().leaf_index, 0);
+
+        // A withdrawal can only be claimed once the nullifier is spent
+        let claimed = DisclosurePackage {
+            withdrawal: Some(DisclosedWithdrawal { txid: "withdrawal_txid".to_string(), block: None }),
+            ..package.clone()
+        };
+        assert!(matches!(pool.check_disclosure(&claimed), Err(ZKaneError::InvalidNullifier(_))));
+        assert!(pool.disclose(&note, Some("withdrawal_txid".to_string())).is_err());
+
+        let nullifier_hash = zkane_crypto::generate_nullifier_hash(&note.nullifier).unwrap();
+        pool.process_withdrawal_in_block(nullifier_hash.as_bytes(), 200).unwrap();
+        let package = pool.disclose(&note, Some("withdrawal_txid".to_string())).unwrap();
+        assert_eq!(package.withdrawal.as_ref().unwrap().block, Some(200));
+        assert_eq!(pool.check_disclosure(&package).unwrap().nullifier_hash, nullifier_hash);
+
+        // Other pools' notes and unknown roots are rejected
+        let other = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 5000).unwrap();
+        let foreign = DisclosurePackage { note: other, ..package.clone() };
+        assert!(pool.check_disclosure(&foreign).is_err());
+        let unknown_root = DisclosurePackage { root: [0u8; 32], ..package };
+        assert!(pool.check_disclosure(&unknown_root).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_commitment_rejected() {
+        let mut pool = create_test_pool();
+        let commitment_hex = "0000000000000000000000000000000000000000000000000000000000000042";
+        for txid in ["mock_txid_first", "mock_txid_repeat"] {
+            pool.provider.responses.lock().unwrap().insert(
+                txid.to_string(),
+                serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", commitment_hex), "value": 0 } ] }),
+            );
+        }
+
+        let commitment = Commitment::from_hex(commitment_hex).unwrap();
+        assert_eq!(pool.leaf_index_of(&commitment), None);
+        assert_eq!(pool.add_commitment("mock_txid_first").await.unwrap(), 0);
+        assert_eq!(pool.leaf_index_of(&commitment), Some(0));
+
+        let root = pool.merkle_root();
+        assert!(matches!(
+            pool.add_commitment("mock_txid_repeat").await,
+            Err(ZKaneError::DuplicateCommitment(_))
+        ));
+        assert_eq!(pool.commitment_count(), 1);
+        assert_eq!(pool.merkle_root(), root);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to_height() {
+        let mut pool = create_test_pool();
+        let mut roots = vec![pool.merkle_root()];
+        for (n, height) in [(1u8, 100u64), (2, 101), (3, 102)] {
+            let txid = format!("mock_txid_rollback_{}", n);
+            pool.provider.responses.lock().unwrap().insert(
+                txid.clone(),
+                serde_json::json!({
+                    "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ],
+                    "status": { "confirmed": true, "block_height": height }
+                }),
+            );
+            pool.add_commitment(&txid).await.unwrap();
+            roots.push(pool.merkle_root());
+        }
+        pool.process_withdrawal_in_block(&[7u8; 32], 101).unwrap();
+        pool.process_withdrawal_in_block(&[8u8; 32], 102).unwrap();
+        pool.process_withdrawal(&[9u8; 32]).unwrap();
+
+        let mut events = pool.subscribe();
+        assert_eq!(pool.rollback_to_height(100).unwrap(), 2);
+        assert_eq!(pool.commitment_count(), 1);
+        assert_eq!(pool.merkle_root(), roots[1]);
+        assert_eq!(pool.leaf_index_of(&Commitment::new([2u8; 32])), None);
+        assert!(!pool.is_nullifier_spent(&[7u8; 32]));
+        assert!(!pool.is_nullifier_spent(&[8u8; 32]));
+        // Withdrawals without a known block are kept
+        assert!(pool.is_nullifier_spent(&[9u8; 32]));
+
+        use futures::StreamExt;
+        assert_eq!(events.next().await, Some(PoolEvent::RolledBack { height: 100, leaf_count: 1 }));
+        assert_eq!(events.next().await, Some(PoolEvent::RootUpdated { root: roots[1], leaf_count: 1 }));
+
+        // Nothing is confirmed above the tip
+        assert_eq!(pool.rollback_to_height(100).unwrap(), 0);
+        assert_eq!(pool.merkle_root(), roots[1]);
+    }
+
     #[test]
     fn test_nullifier_spending() {
         let mut pool = create_test_pool();
@@ -688,16 +1271,87 @@ mod tests {
         assert_eq!(proof.len(), 4); // Tree height
     }
 
+    #[tokio::test]
+    async fn test_configured_tree_hash() {
+        let config = ZKaneConfig::new(
+            ZkAssetId { block: 2, tx: 1 },
+            1000000,
+            4,
+            vec![],
+        )
+        .with_tree_hash(TreeHash::Sha256);
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        let mut pool = PrivacyPool::new(config, provider).unwrap();
+
+        let commitment = Commitment::new([0x42; 32]);
+        pool.provider.responses.lock().unwrap().insert(
+            "mock_txid_sha256".to_string(),
+            serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", commitment.to_hex()), "value": 0 } ] }),
+        );
+        pool.add_commitment("mock_txid_sha256").await.unwrap();
+
+        let expected = MerkleTree::from_leaves_with_hasher(4, &[commitment], zkane_crypto::Sha256Hash).unwrap();
+        assert_eq!(pool.merkle_root(), expected.root());
+        assert_ne!(pool.merkle_root(), MerkleTree::from_leaves(4, &[commitment]).unwrap().root());
+    }
+
     #[test]
     fn test_deposit_note_generation() {
-        let asset_id = AlkaneId { block: 2, tx: 1 };
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
         let denomination = 1000000u128;
         
         let note = generate_deposit_note(asset_id, denomination).unwrap();
         
-        assert_eq!(note.asset_id, asset_id.into());
+        assert_eq!(note.asset_id, asset_id);
         assert_eq!(note.denomination, denomination);
         assert!(verify_deposit_note(&note).unwrap());
+
+        // The commitment doesn't hold for another pool's asset or denomination
+        let mut other_pool = note.clone();
+        other_pool.denomination = denomination * 10;
+        assert!(!verify_deposit_note(&other_pool).unwrap());
+        other_pool.denomination = denomination;
+        other_pool.asset_id = ZkAssetId { block: 2, tx: 7 };
+        assert!(!verify_deposit_note(&other_pool).unwrap());
+    }
+
+    #[test]
+    fn test_verify_versioned_deposit_notes() {
+        let mut note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        note.set_extension("circuit_version", "1");
+        assert!(verify_deposit_note(&note).unwrap());
+
+        // A version 1 note, saved before notes had a version, still verifies
+        let mut value: serde_json::Value = serde_json::to_value(&note).unwrap();
+        value.as_object_mut().unwrap().remove("version");
+        value.as_object_mut().unwrap().remove("extensions");
+        let mut legacy: DepositNote = serde_json::from_value(value).unwrap();
+        assert_eq!(legacy.version, 1);
+        assert!(verify_deposit_note(&legacy).unwrap());
+        legacy.upgrade().unwrap();
+        assert_eq!(legacy.version, zkane_common::DEPOSIT_NOTE_VERSION);
+
+        note.version = zkane_common::DEPOSIT_NOTE_VERSION + 1;
+        assert!(matches!(
+            verify_deposit_note(&note),
+            Err(ZKaneError::UnsupportedNoteVersion { .. })
+        ));
+        assert!(note.upgrade().is_err());
+    }
+
+    #[test]
+    fn test_seeded_deposit_note_generation() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let note = generate_deposit_note_with_rng(asset_id, 1000, &mut note_rng(Some(42))).unwrap();
+        let again = generate_deposit_note_with_rng(asset_id, 1000, &mut note_rng(Some(42))).unwrap();
+        assert_eq!(note.commitment, again.commitment);
+        assert_eq!(note.secret, again.secret);
+        assert!(verify_deposit_note(&note).unwrap());
+
+        let other = generate_deposit_note_with_rng(asset_id, 1000, &mut note_rng(Some(43))).unwrap();
+        assert_ne!(note.commitment, other.commitment);
+        let unseeded = generate_deposit_note_with_rng(asset_id, 1000, &mut note_rng(None)).unwrap();
+        assert_ne!(note.commitment, unseeded.commitment);
     }
 
     #[tokio::test]
@@ -727,12 +1381,18 @@ mod tests {
             vec![0u8; 256],
             pool.merkle_root(),
             nullifier_hash,
-            12345,
+            Recipient::default(),
         );
         
         // Should verify with correct merkle root
         assert!(pool.verify_withdrawal_proof(&proof));
         
+        // Relayed withdrawals are accepted while the fee fits the denomination
+        let relayed = proof.clone().with_relayer([9u8; 32], 100);
+        assert!(pool.verify_withdrawal_proof(&relayed));
+        let overpaid = proof.clone().with_relayer([9u8; 32], pool.config().denomination + 1);
+        assert!(!pool.verify_withdrawal_proof(&overpaid));
+        
         // Should fail after nullifier is spent
         pool.process_withdrawal(nullifier_hash.as_bytes()).unwrap();
         assert!(!pool.verify_withdrawal_proof(&proof));
@@ -784,6 +1444,50 @@ mod tests {
         assert!(pool.add_commitment(txid).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_pool_events() {
+        use futures::StreamExt;
+
+        let mut pool = create_test_pool();
+        let mut events = pool.subscribe();
+
+        let txid = "mock_txid_events";
+        let commitment_hex = "0000000000000000000000000000000000000000000000000000000000000042";
+        let mock_response = serde_json::json!({
+            "vout": [ { "scriptpubkey": format!("6a{}", commitment_hex), "value": 0 } ],
+            "status": { "confirmed": true, "block_height": 840000 }
+        });
+        pool.provider
+            .responses
+            .lock()
+            .unwrap()
+            .insert(txid.to_string(), mock_response);
+        pool.add_commitment(txid).await.unwrap();
+        pool.process_withdrawal_in_block(&[7u8; 32], 840001).unwrap();
+
+        let commitment = Commitment::from_hex(commitment_hex).unwrap();
+        assert_eq!(
+            events.next().await,
+            Some(PoolEvent::DepositAdded { leaf: 0, commitment, block: Some(840000) })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(PoolEvent::RootUpdated { root: pool.merkle_root(), leaf_count: 1 })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(PoolEvent::WithdrawalProcessed {
+                nullifier_hash: NullifierHash::new([7u8; 32]),
+                block: Some(840001),
+            })
+        );
+
+        // A rejected double spend publishes nothing
+        assert!(pool.process_withdrawal(&[7u8; 32]).is_err());
+        drop(pool);
+        assert_eq!(events.next().await, None);
+    }
+
     #[tokio::test]
     async fn test_pool_stats() {
         let mut pool = create_test_pool();
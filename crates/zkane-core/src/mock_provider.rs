@@ -15,17 +15,75 @@ use deezel_common::{
 use alkanes_support::proto::alkanes as alkanes_pb;
 use async_trait::async_trait;
 use bitcoin::{
-    secp256k1::{schnorr, All, Secp256k1},
-    Network, OutPoint, Transaction, TxOut,
+    block::Header,
+    hashes::{sha256d, Hash},
+    secp256k1::{schnorr, All, Keypair, Message, Secp256k1, SecretKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{self, TapLeafHash},
+    BlockHash, CompactTarget, Network, OutPoint, Transaction, TxMerkleNode, TxOut,
 };
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use protorune_support::proto::protorune as protorune_pb;
 
+/// Difficulty bits of mock blocks, the regtest minimum
+const MOCK_BLOCK_BITS: u32 = 0x207f_ffff;
+
+/// Secret key of the mock wallet, its internal key
+const MOCK_WALLET_SECRET: [u8; 32] = [1u8; 32];
+
+/// A failure injected into the next call of a mock provider method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockFailure {
+    /// The call returns an error with the given message
+    Error(String),
+    /// The call times out
+    Timeout,
+}
+
+/// A block of the scripted mock chain.
+#[derive(Debug, Clone)]
+struct MockBlock {
+    hash: String,
+    txids: Vec<String>,
+    /// Header of blocks of real transactions
+    header: Option<Header>,
+}
+
+/// Scripted chain state shared by clones of a [`MockProvider`].
+#[derive(Debug, Default)]
+struct MockChain {
+    /// Blocks by height, starting at height 1
+    blocks: Vec<MockBlock>,
+    /// Number of reorgs so far, mixed into block hashes
+    reorgs: u64,
+    /// Pending outcomes of calls by method name, `None` for a call that
+    /// succeeds
+    failures: HashMap<String, VecDeque<Option<MockFailure>>>,
+    /// Simulation responses by contract id and params
+    simulations: HashMap<(String, String), JsonValue>,
+    /// Hex of the transactions broadcast so far
+    broadcasts: Vec<String>,
+    /// Hex of the real transactions served, by txid
+    raw_txs: HashMap<String, String>,
+}
+
+/// Mock provider for deterministic tests.
+///
+/// Transactions can be registered directly with [`MockProvider::add_response`],
+/// or scripted into a chain with [`MockProvider::mine_block`], which keeps
+/// their `status` in sync with the chain through [`MockProvider::reorg`].
+/// Failures are injected per method with [`MockProvider::inject_failure`],
+/// and contract views are scripted with [`MockProvider::add_simulation`].
+///
+/// Clones share their state, so a test can keep scripting the chain after
+/// handing the provider to a pool.
 #[derive(Clone)]
 pub struct MockProvider {
     pub responses: Arc<Mutex<HashMap<String, JsonValue>>>,
+    chain: Arc<Mutex<MockChain>>,
     secp: Secp256k1<All>,
     network: Network,
 }
@@ -34,6 +92,7 @@ impl MockProvider {
     pub fn new(network: Network) -> Self {
         Self {
             responses: Arc::new(Mutex::new(HashMap::new())),
+            chain: Arc::new(Mutex::new(MockChain::default())),
             secp: Secp256k1::new(),
             network,
         }
@@ -42,6 +101,282 @@ impl MockProvider {
     pub fn add_response(&mut self, txid: &str, response: JsonValue) {
         self.responses.lock().unwrap().insert(txid.to_string(), response);
     }
+
+    /// Add an unconfirmed transaction.
+    pub fn add_mempool_tx(&self, txid: &str, mut tx: JsonValue) {
+        tx["status"] = serde_json::json!({ "confirmed": false });
+        self.responses.lock().unwrap().insert(txid.to_string(), tx);
+    }
+
+    /// Mine a block containing the given transactions on top of the chain.
+    ///
+    /// The `status` of each transaction is set to confirmed in the new block.
+    ///
+    /// # Returns
+    ///
+    /// The height of the new block.
+    pub fn mine_block(&self, txs: Vec<(&str, JsonValue)>) -> u64 {
+        let mut chain = self.chain.lock().unwrap();
+        let height = chain.blocks.len() as u64 + 1;
+        let hash = format!("{:032x}{:032x}", chain.reorgs, height);
+
+        let mut responses = self.responses.lock().unwrap();
+        let mut txids = Vec::with_capacity(txs.len());
+        for (txid, mut tx) in txs {
+            tx["status"] = serde_json::json!({
+                "confirmed": true,
+                "block_height": height,
+                "block_hash": hash,
+            });
+            responses.insert(txid.to_string(), tx);
+            txids.push(txid.to_string());
+        }
+
+        chain.blocks.push(MockBlock { hash, txids, header: None });
+        height
+    }
+
+    /// Mine a block of real transactions on top of the chain.
+    ///
+    /// Unlike [`MockProvider::mine_block`], the block has a valid regtest
+    /// header, and the provider serves the raw transactions and their merkle
+    /// inclusion proofs, so they pass SPV verification. Their Esplora JSON is
+    /// registered as well.
+    ///
+    /// # Returns
+    ///
+    /// The height of the new block.
+    pub fn mine_transactions(&self, txs: Vec<Transaction>) -> u64 {
+        let mut chain = self.chain.lock().unwrap();
+        let height = chain.blocks.len() as u64 + 1;
+        let prev_blockhash = chain
+            .blocks
+            .last()
+            .and_then(|block| block.header)
+            .map_or(BlockHash::all_zeros(), |header| header.block_hash());
+        let merkle_root = bitcoin::merkle_tree::calculate_root(
+            txs.iter().map(|tx| TxMerkleNode::from_raw_hash(tx.compute_txid().to_raw_hash())),
+        )
+        .unwrap_or_else(TxMerkleNode::all_zeros);
+
+        let mut header = Header {
+            version: bitcoin::block::Version::ONE,
+            prev_blockhash,
+            merkle_root,
+            time: height as u32,
+            bits: CompactTarget::from_consensus(MOCK_BLOCK_BITS),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        let hash = header.block_hash().to_string();
+
+        let mut responses = self.responses.lock().unwrap();
+        let mut txids = Vec::with_capacity(txs.len());
+        for tx in &txs {
+            let txid = tx.compute_txid().to_string();
+            let mut json = esplora_json(tx);
+            json["status"] = serde_json::json!({
+                "confirmed": true,
+                "block_height": height,
+                "block_hash": hash,
+            });
+            responses.insert(txid.clone(), json);
+            chain.raw_txs.insert(txid.clone(), bitcoin::consensus::encode::serialize_hex(tx));
+            txids.push(txid);
+        }
+
+        chain.blocks.push(MockBlock { hash, txids, header: Some(header) });
+        height
+    }
+
+    /// Serve the raw bytes of a transaction without mining it.
+    pub fn add_raw_tx(&self, tx: &Transaction) {
+        self.chain
+            .lock()
+            .unwrap()
+            .raw_txs
+            .insert(tx.compute_txid().to_string(), bitcoin::consensus::encode::serialize_hex(tx));
+    }
+
+    /// Mine `count` empty blocks.
+    ///
+    /// # Returns
+    ///
+    /// The new tip height.
+    pub fn mine_empty_blocks(&self, count: u64) -> u64 {
+        let mut height = self.tip_height();
+        for _ in 0..count {
+            height = self.mine_block(Vec::new());
+        }
+        height
+    }
+
+    /// Disconnect the top `depth` blocks.
+    ///
+    /// Their transactions return to the mempool; mine new blocks afterwards to
+    /// build the competing chain.
+    ///
+    /// # Returns
+    ///
+    /// The txids of the disconnected transactions, in chain order.
+    pub fn reorg(&self, depth: usize) -> Vec<String> {
+        let mut chain = self.chain.lock().unwrap();
+        let keep = chain.blocks.len().saturating_sub(depth);
+        let disconnected: Vec<String> = chain.blocks.drain(keep..).flat_map(|block| block.txids).collect();
+        chain.reorgs += 1;
+
+        let mut responses = self.responses.lock().unwrap();
+        for txid in &disconnected {
+            if let Some(tx) = responses.get_mut(txid) {
+                tx["status"] = serde_json::json!({ "confirmed": false });
+            }
+        }
+        disconnected
+    }
+
+    /// Get the height of the chain tip, zero before any block is mined.
+    pub fn tip_height(&self) -> u64 {
+        self.chain.lock().unwrap().blocks.len() as u64
+    }
+
+    /// Get the txids of the block at a height.
+    pub fn block_txids(&self, height: u64) -> Option<Vec<String>> {
+        self.block_at(height).map(|block| block.txids)
+    }
+
+    /// Make the next call of a provider method fail.
+    ///
+    /// Failures queue up, so injecting twice fails the next two calls.
+    /// `method` is the name of the trait method, e.g. `"get_tx"`.
+    pub fn inject_failure(&self, method: &str, failure: MockFailure) {
+        self.inject_failure_after(method, 0, failure);
+    }
+
+    /// Make a call of a provider method fail once `calls` more calls of it
+    /// succeeded, e.g. the second of two broadcasts.
+    pub fn inject_failure_after(&self, method: &str, calls: usize, failure: MockFailure) {
+        let mut chain = self.chain.lock().unwrap();
+        let failures = chain.failures.entry(method.to_string()).or_default();
+        failures.extend(std::iter::repeat_n(None, calls));
+        failures.push_back(Some(failure));
+    }
+
+    /// Set the response of simulating a contract call.
+    ///
+    /// `contract_id` is `block:tx` and `params` the comma-separated opcode and
+    /// inputs, as passed to [`AlkanesProvider::simulate`]. A shorthand for
+    /// successful calls is [`MockProvider::add_simulation_data`].
+    pub fn add_simulation(&self, contract_id: &str, params: &str, response: JsonValue) {
+        self.chain
+            .lock()
+            .unwrap()
+            .simulations
+            .insert((contract_id.to_string(), params.to_string()), response);
+    }
+
+    /// Make simulating a contract call return the given data.
+    pub fn add_simulation_data(&self, contract_id: &str, params: &str, data: &[u8]) {
+        let response = serde_json::json!({
+            "status": 0,
+            "execution": { "data": format!("0x{}", hex::encode(data)), "error": null },
+        });
+        self.add_simulation(contract_id, params, response);
+    }
+
+    /// Get the keypair of the mock wallet's internal key.
+    fn keypair(&self) -> Keypair {
+        Keypair::from_secret_key(&self.secp, &SecretKey::from_slice(&MOCK_WALLET_SECRET).unwrap())
+    }
+
+    /// Get the hex of the transactions broadcast so far.
+    pub fn broadcasts(&self) -> Vec<String> {
+        self.chain.lock().unwrap().broadcasts.clone()
+    }
+
+    /// Fail the call if a failure was injected for the method.
+    fn check_failure(&self, method: &str) -> Result<()> {
+        let failure = self
+            .chain
+            .lock()
+            .unwrap()
+            .failures
+            .get_mut(method)
+            .and_then(|failures| failures.pop_front())
+            .flatten();
+        match failure {
+            None => Ok(()),
+            Some(MockFailure::Error(message)) => Err(DeezelError::JsonRpc(message)),
+            Some(MockFailure::Timeout) => Err(DeezelError::JsonRpc(format!("{}: request timed out", method))),
+        }
+    }
+
+    fn block_at(&self, height: u64) -> Option<MockBlock> {
+        let index = usize::try_from(height).ok()?.checked_sub(1)?;
+        self.chain.lock().unwrap().blocks.get(index).cloned()
+    }
+
+    fn block_by_hash(&self, hash: &str) -> Option<(u64, MockBlock)> {
+        let chain = self.chain.lock().unwrap();
+        chain
+            .blocks
+            .iter()
+            .position(|block| block.hash == hash)
+            .map(|index| (index as u64 + 1, chain.blocks[index].clone()))
+    }
+
+    fn block_json(&self, hash: &str) -> Result<JsonValue> {
+        let (height, block) = self
+            .block_by_hash(hash)
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock block with hash: {}", hash)))?;
+        Ok(serde_json::json!({
+            "id": block.hash,
+            "height": height,
+            "tx_count": block.txids.len(),
+        }))
+    }
+
+    /// Build the Esplora merkle proof of a transaction from its block.
+    fn merkle_proof(&self, txid: &str) -> Result<JsonValue> {
+        let chain = self.chain.lock().unwrap();
+        let (index, pos) = chain
+            .blocks
+            .iter()
+            .enumerate()
+            .find_map(|(index, block)| Some((index, block.txids.iter().position(|id| id == txid)?)))
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock block contains txid: {}", txid)))?;
+
+        let mut level = chain.blocks[index]
+            .txids
+            .iter()
+            .map(|id| TxMerkleNode::from_str(id))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| DeezelError::JsonRpc(e.to_string()))?;
+        let mut merkle = Vec::new();
+        let mut position = pos;
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().unwrap());
+            }
+            merkle.push(level[position ^ 1].to_string());
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    let data = [pair[0].to_byte_array(), pair[1].to_byte_array()].concat();
+                    TxMerkleNode::from_raw_hash(sha256d::Hash::hash(&data))
+                })
+                .collect();
+            position /= 2;
+        }
+        Ok(serde_json::json!({ "block_height": index + 1, "merkle": merkle, "pos": pos }))
+    }
+
+    fn block_hash_at(&self, height: u64) -> Result<String> {
+        self.block_at(height)
+            .map(|block| block.hash)
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock block at height: {}", height)))
+    }
 }
 
 #[async_trait(?Send)]
@@ -279,11 +614,17 @@ impl WalletProvider for MockProvider {
     async fn create_transaction(&self, _params: SendParams) -> Result<String> {
         unimplemented!()
     }
-    async fn sign_transaction(&self, _tx_hex: String) -> Result<String> {
-        unimplemented!()
+    async fn sign_transaction(&self, tx_hex: String) -> Result<String> {
+        // The mock wallet holds no coins, so the transaction is returned as is
+        self.check_failure("sign_transaction")?;
+        Ok(tx_hex)
     }
-    async fn broadcast_transaction(&self, _tx_hex: String) -> Result<String> {
-        unimplemented!()
+    async fn broadcast_transaction(&self, tx_hex: String) -> Result<String> {
+        self.check_failure("broadcast_transaction")?;
+        let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_hex)
+            .map_err(|e| DeezelError::JsonRpc(e.to_string()))?;
+        self.chain.lock().unwrap().broadcasts.push(tx_hex);
+        Ok(tx.compute_txid().to_string())
     }
     async fn estimate_fee(&self, _target: u32) -> Result<FeeEstimate> {
         unimplemented!()
@@ -304,13 +645,41 @@ impl WalletProvider for MockProvider {
         self.network
     }
     async fn get_internal_key(&self) -> Result<bitcoin::XOnlyPublicKey> {
-        unimplemented!()
-    }
-    async fn sign_psbt(&self, _psbt: &bitcoin::psbt::Psbt) -> Result<bitcoin::psbt::Psbt> {
-        unimplemented!()
+        Ok(self.keypair().x_only_public_key().0)
+    }
+    async fn sign_psbt(&self, psbt: &bitcoin::psbt::Psbt) -> Result<bitcoin::psbt::Psbt> {
+        self.check_failure("sign_psbt")?;
+        // The wallet only signs script-path spends locked to its internal key,
+        // such as envelope reveals, and needs every spent output to do so
+        let mut psbt = psbt.clone();
+        let prevouts: Option<Vec<TxOut>> = psbt.inputs.iter().map(|input| input.witness_utxo.clone()).collect();
+        let Some(prevouts) = prevouts else {
+            return Ok(psbt);
+        };
+        let keypair = self.keypair();
+        let key = keypair.x_only_public_key().0;
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            for (control_block, (script, version)) in &input.tap_scripts {
+                if control_block.internal_key != key {
+                    continue;
+                }
+                let leaf_hash = TapLeafHash::from_script(script, *version);
+                let sighash = cache
+                    .taproot_script_spend_signature_hash(index, &Prevouts::All(&prevouts), leaf_hash, TapSighashType::Default)
+                    .map_err(|e| DeezelError::JsonRpc(e.to_string()))?;
+                let signature = self.secp.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair);
+                let signature = taproot::Signature {
+                    signature,
+                    sighash_type: TapSighashType::Default,
+                };
+                input.tap_script_sigs.insert((key, leaf_hash), signature);
+            }
+        }
+        Ok(psbt)
     }
     async fn get_keypair(&self) -> Result<bitcoin::secp256k1::Keypair> {
-        unimplemented!()
+        Ok(self.keypair())
     }
     fn set_passphrase(&mut self, _passphrase: Option<String>) {
         unimplemented!()
@@ -336,7 +705,8 @@ impl AddressResolver for MockProvider {
 #[async_trait(?Send)]
 impl BitcoinRpcProvider for MockProvider {
     async fn get_block_count(&self) -> Result<u64> {
-        Ok(0)
+        self.check_failure("get_block_count")?;
+        Ok(self.tip_height())
     }
     async fn generate_to_address(&self, _nblocks: u32, _address: &str) -> Result<JsonValue> {
         Ok(JsonValue::Null)
@@ -347,11 +717,13 @@ impl BitcoinRpcProvider for MockProvider {
     async fn get_transaction_hex(&self, _txid: &str) -> Result<String> {
         Ok(String::new())
     }
-    async fn get_block(&self, _hash: &str) -> Result<JsonValue> {
-        Ok(JsonValue::Null)
+    async fn get_block(&self, hash: &str) -> Result<JsonValue> {
+        self.check_failure("get_block")?;
+        self.block_json(hash)
     }
-    async fn get_block_hash(&self, _height: u64) -> Result<String> {
-        Ok(String::new())
+    async fn get_block_hash(&self, height: u64) -> Result<String> {
+        self.check_failure("get_block_hash")?;
+        self.block_hash_at(height)
     }
     async fn send_raw_transaction(&self, _tx_hex: &str) -> Result<String> {
         Ok(String::new())
@@ -363,7 +735,8 @@ impl BitcoinRpcProvider for MockProvider {
         Ok(JsonValue::Null)
     }
     async fn get_esplora_blocks_tip_height(&self) -> Result<u64> {
-        Ok(0)
+        self.check_failure("get_esplora_blocks_tip_height")?;
+        Ok(self.tip_height())
     }
     async fn trace_transaction(
         &self,
@@ -379,7 +752,8 @@ impl BitcoinRpcProvider for MockProvider {
 #[async_trait(?Send)]
 impl MetashrewRpcProvider for MockProvider {
     async fn get_metashrew_height(&self) -> Result<u64> {
-        Ok(0)
+        self.check_failure("get_metashrew_height")?;
+        Ok(self.tip_height())
     }
     async fn get_contract_meta(&self, _block: &str, _tx: &str) -> Result<JsonValue> {
         Ok(JsonValue::Null)
@@ -401,28 +775,40 @@ impl MetashrewRpcProvider for MockProvider {
 #[async_trait(?Send)]
 impl EsploraProvider for MockProvider {
     async fn get_blocks_tip_hash(&self) -> Result<String> {
-        Ok(String::new())
+        self.check_failure("get_blocks_tip_hash")?;
+        self.block_hash_at(self.tip_height())
     }
     async fn get_blocks_tip_height(&self) -> Result<u64> {
-        Ok(0)
+        self.check_failure("get_blocks_tip_height")?;
+        Ok(self.tip_height())
     }
     async fn get_blocks(&self, _start_height: Option<u64>) -> Result<JsonValue> {
         Ok(JsonValue::Null)
     }
-    async fn get_block_by_height(&self, _height: u64) -> Result<String> {
-        Ok(String::new())
+    async fn get_block_by_height(&self, height: u64) -> Result<String> {
+        self.check_failure("get_block_by_height")?;
+        self.block_hash_at(height)
     }
-    async fn get_block(&self, _hash: &str) -> Result<JsonValue> {
-        Ok(JsonValue::Null)
+    async fn get_block(&self, hash: &str) -> Result<JsonValue> {
+        self.check_failure("get_block")?;
+        self.block_json(hash)
     }
     async fn get_block_status(&self, _hash: &str) -> Result<JsonValue> {
         Ok(JsonValue::Null)
     }
-    async fn get_block_txids(&self, _hash: &str) -> Result<JsonValue> {
-        Ok(JsonValue::Null)
+    async fn get_block_txids(&self, hash: &str) -> Result<JsonValue> {
+        self.check_failure("get_block_txids")?;
+        let (_, block) = self
+            .block_by_hash(hash)
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock block with hash: {}", hash)))?;
+        Ok(serde_json::json!(block.txids))
     }
-    async fn get_block_header(&self, _hash: &str) -> Result<String> {
-        Ok(String::new())
+    async fn get_block_header(&self, hash: &str) -> Result<String> {
+        self.check_failure("get_block_header")?;
+        self.block_by_hash(hash)
+            .and_then(|(_, block)| block.header)
+            .map(|header| bitcoin::consensus::encode::serialize_hex(&header))
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock header for block: {}", hash)))
     }
     async fn get_block_raw(&self, _hash: &str) -> Result<String> {
         Ok(String::new())
@@ -459,23 +845,37 @@ impl EsploraProvider for MockProvider {
         Ok(JsonValue::Null)
     }
     async fn get_tx(&self, txid: &str) -> Result<JsonValue> {
+        self.check_failure("get_tx")?;
         let responses = self.responses.lock().unwrap();
         responses
             .get(txid)
             .cloned()
             .ok_or_else(|| DeezelError::JsonRpc(format!("No mock response for txid: {}", txid)))
     }
-    async fn get_tx_hex(&self, _txid: &str) -> Result<String> {
-        Ok(String::new())
+    async fn get_tx_hex(&self, txid: &str) -> Result<String> {
+        self.check_failure("get_tx_hex")?;
+        self.chain
+            .lock()
+            .unwrap()
+            .raw_txs
+            .get(txid)
+            .cloned()
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock raw transaction for txid: {}", txid)))
     }
     async fn get_tx_raw(&self, _txid: &str) -> Result<String> {
         Ok(String::new())
     }
-    async fn get_tx_status(&self, _txid: &str) -> Result<JsonValue> {
-        Ok(JsonValue::Null)
+    async fn get_tx_status(&self, txid: &str) -> Result<JsonValue> {
+        self.check_failure("get_tx_status")?;
+        let responses = self.responses.lock().unwrap();
+        responses
+            .get(txid)
+            .map(|tx| tx["status"].clone())
+            .ok_or_else(|| DeezelError::JsonRpc(format!("No mock response for txid: {}", txid)))
     }
-    async fn get_tx_merkle_proof(&self, _txid: &str) -> Result<JsonValue> {
-        Ok(JsonValue::Null)
+    async fn get_tx_merkle_proof(&self, txid: &str) -> Result<JsonValue> {
+        self.check_failure("get_tx_merkle_proof")?;
+        self.merkle_proof(txid)
     }
     async fn get_tx_merkleblock_proof(&self, _txid: &str) -> Result<String> {
         Ok(String::new())
@@ -591,8 +991,12 @@ impl AlkanesProvider for MockProvider {
     ) -> Result<protorune_pb::OutpointResponse> {
         unimplemented!()
     }
-    async fn simulate(&self, _contract_id: &str, _params: Option<&str>) -> Result<JsonValue> {
-        unimplemented!()
+    async fn simulate(&self, contract_id: &str, params: Option<&str>) -> Result<JsonValue> {
+        self.check_failure("simulate")?;
+        let key = (contract_id.to_string(), params.unwrap_or_default().to_string());
+        self.chain.lock().unwrap().simulations.get(&key).cloned().ok_or_else(|| {
+            DeezelError::JsonRpc(format!("No mock simulation for {} with params {}", key.0, key.1))
+        })
     }
     async fn trace(&self, _outpoint: &str) -> Result<alkanes_pb::Trace> {
         unimplemented!()
@@ -693,4 +1097,102 @@ impl DeezelProvider for MockProvider {
     ) -> Result<schnorr::Signature> {
         unimplemented!()
     }
-}
\ No newline at end of file
+}
+/// Build the Esplora JSON of a transaction.
+fn esplora_json(tx: &Transaction) -> JsonValue {
+    let vin: Vec<JsonValue> = tx
+        .input
+        .iter()
+        .map(|input| {
+            serde_json::json!({
+                "txid": input.previous_output.txid.to_string(),
+                "vout": input.previous_output.vout,
+                "is_coinbase": input.previous_output.is_null(),
+                "scriptsig": hex::encode(input.script_sig.as_bytes()),
+                "witness": input.witness.iter().map(hex::encode).collect::<Vec<_>>(),
+                "sequence": input.sequence.0,
+            })
+        })
+        .collect();
+    let vout: Vec<JsonValue> = tx
+        .output
+        .iter()
+        .map(|output| {
+            serde_json::json!({
+                "scriptpubkey": hex::encode(output.script_pubkey.as_bytes()),
+                "value": output.value.to_sat(),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "txid": tx.compute_txid().to_string(),
+        "version": tx.version.0,
+        "locktime": tx.lock_time.to_consensus_u32(),
+        "vin": vin,
+        "vout": vout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit_tx(n: u8) -> JsonValue {
+        serde_json::json!({
+            "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_mined_blocks() {
+        let provider = MockProvider::new(Network::Regtest);
+        assert_eq!(provider.get_blocks_tip_height().await.unwrap(), 0);
+
+        assert_eq!(provider.mine_block(vec![("tx_a", deposit_tx(1)), ("tx_b", deposit_tx(2))]), 1);
+        assert_eq!(provider.mine_empty_blocks(2), 3);
+        assert_eq!(provider.get_blocks_tip_height().await.unwrap(), 3);
+
+        let hash = provider.get_block_hash(1).await.unwrap();
+        assert_eq!(provider.get_block_txids(&hash).await.unwrap(), serde_json::json!(["tx_a", "tx_b"]));
+        let status = provider.get_tx_status("tx_b").await.unwrap();
+        assert_eq!(status["block_height"], 1);
+        assert_eq!(status["block_hash"], hash);
+        assert!(provider.get_block_hash(4).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_unconfirms_transactions() {
+        let provider = MockProvider::new(Network::Regtest);
+        provider.mine_block(vec![("tx_a", deposit_tx(1))]);
+        provider.mine_block(vec![("tx_b", deposit_tx(2))]);
+        let orphaned = provider.get_block_hash(2).await.unwrap();
+
+        assert_eq!(provider.reorg(1), vec!["tx_b".to_string()]);
+        assert_eq!(provider.tip_height(), 1);
+        assert_eq!(provider.get_tx_status("tx_b").await.unwrap()["confirmed"], false);
+
+        // The competing block has a different hash at the same height
+        provider.mine_block(vec![("tx_c", deposit_tx(3))]);
+        assert_ne!(provider.get_block_hash(2).await.unwrap(), orphaned);
+        assert!(provider.get_block_txids(&orphaned).await.is_err());
+        assert_eq!(provider.block_txids(2), Some(vec!["tx_c".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_injected_failures() {
+        let provider = MockProvider::new(Network::Regtest);
+        provider.mine_block(vec![("tx_a", deposit_tx(1))]);
+        provider.inject_failure("get_tx", MockFailure::Timeout);
+        provider.inject_failure("get_tx", MockFailure::Error("connection refused".to_string()));
+
+        assert!(provider.get_tx("tx_a").await.unwrap_err().to_string().contains("timed out"));
+        assert!(provider.get_tx("tx_a").await.unwrap_err().to_string().contains("connection refused"));
+        assert!(provider.get_tx("tx_a").await.is_ok());
+        provider.inject_failure_after("get_tx", 1, MockFailure::Timeout);
+        assert!(provider.get_tx("tx_a").await.is_ok());
+        assert!(provider.get_tx("tx_a").await.is_err());
+        assert!(provider.get_tx("tx_a").await.is_ok());
+        // Other methods are unaffected
+        assert_eq!(provider.get_blocks_tip_height().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! # Nullifier Index
+//!
+//! Checking whether a note is spent takes its nullifier hash, a Poseidon
+//! hash that is slow to recompute for every note on every scan. The
+//! [`NullifierIndex`] computes each note's hash once, keyed by commitment,
+//! and is saved with the notes so later scans only hash new ones. Its hashes
+//! are checked against the pool in batches with
+//! [`NullifierIndex::check_spent`].
+//!
+//! ```rust
+//! use zkane_core::{generate_deposit_note, NullifierIndex};
+//! use zkane_common::ZkAssetId;
+//!
+//! # fn example() -> zkane_common::ZKaneResult<()> {
+//! let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000)?;
+//! let mut index = NullifierIndex::new();
+//! let nullifier_hash = index.insert(&note)?;
+//!
+//! // Saved and restored without hashing again
+//! let restored: NullifierIndex = serde_json::from_str(&serde_json::to_string(&index)?)?;
+//! assert_eq!(restored.get(&note.commitment), Some(nullifier_hash));
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "deezel")]
+use crate::pool_client::PoolClient;
+use crate::view::ViewingNote;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::DeezelProvider;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zkane_common::{Commitment, DepositNote, NullifierHash, ZKaneResult};
+
+/// Nullifier hashes of a wallet's notes, keyed by commitment.
+///
+/// Serialized as the list of the notes' viewing data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<ViewingNote>", into = "Vec<ViewingNote>")]
+pub struct NullifierIndex {
+    /// Notes in the order they were added
+    notes: Vec<ViewingNote>,
+    by_commitment: HashMap<Commitment, usize>,
+}
+
+impl NullifierIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an index of the given notes.
+    pub fn from_notes<'a>(notes: impl IntoIterator<Item = &'a DepositNote>) -> ZKaneResult<Self> {
+        let mut index = Self::new();
+        for note in notes {
+            index.insert(note)?;
+        }
+        Ok(index)
+    }
+
+    /// Get a note's nullifier hash, computing it if the note isn't indexed yet.
+    pub fn insert(&mut self, note: &DepositNote) -> ZKaneResult<NullifierHash> {
+        if let Some(nullifier_hash) = self.get(&note.commitment) {
+            return Ok(nullifier_hash);
+        }
+        let viewing = ViewingNote::from_deposit_note(note)?;
+        self.push(viewing);
+        Ok(viewing.nullifier_hash)
+    }
+
+    /// Get the nullifier hash of the note with a commitment, if indexed.
+    pub fn get(&self, commitment: &Commitment) -> Option<NullifierHash> {
+        self.by_commitment
+            .get(commitment)
+            .map(|&index| self.notes[index].nullifier_hash)
+    }
+
+    /// Drop the notes whose commitment isn't kept.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Commitment) -> bool) {
+        self.notes.retain(|note| keep(&note.commitment));
+        self.reindex();
+    }
+
+    /// Get the indexed notes, in the order they were added.
+    pub fn notes(&self) -> &[ViewingNote] {
+        &self.notes
+    }
+
+    /// Get the number of indexed notes.
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Check whether no note is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Check which of the given notes have been spent in a pool.
+    ///
+    /// The hashes are checked in bulk with [`PoolClient::check_spent`].
+    ///
+    /// # Returns
+    ///
+    /// The spent status of each commitment, in the order given, or `None`
+    /// for commitments that aren't indexed.
+    #[cfg(feature = "deezel")]
+    pub async fn check_spent<P: DeezelProvider>(
+        &self,
+        client: &PoolClient<P>,
+        commitments: &[Commitment],
+    ) -> ZKaneResult<Vec<Option<bool>>> {
+        let hashes: Vec<Option<NullifierHash>> = commitments.iter().map(|commitment| self.get(commitment)).collect();
+        let known: Vec<NullifierHash> = hashes.iter().flatten().copied().collect();
+        let mut spent = client.check_spent(&known).await?.into_iter();
+        Ok(hashes
+            .iter()
+            .map(|hash| hash.and_then(|_| spent.next()))
+            .collect())
+    }
+
+    fn push(&mut self, note: ViewingNote) {
+        self.by_commitment.insert(note.commitment, self.notes.len());
+        self.notes.push(note);
+    }
+
+    fn reindex(&mut self) {
+        self.by_commitment = self
+            .notes
+            .iter()
+            .enumerate()
+            .map(|(index, note)| (note.commitment, index))
+            .collect();
+    }
+}
+
+impl From<Vec<ViewingNote>> for NullifierIndex {
+    fn from(notes: Vec<ViewingNote>) -> Self {
+        let mut index = Self::new();
+        for note in notes {
+            if !index.by_commitment.contains_key(&note.commitment) {
+                index.push(note);
+            }
+        }
+        index
+    }
+}
+
+impl From<NullifierIndex> for Vec<ViewingNote> {
+    fn from(index: NullifierIndex) -> Self {
+        index.notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use crate::{generate_deposit_note_with_rng, note_rng};
+    use std::sync::Arc;
+    use zkane_common::ZkAssetId;
+    use zkane_crypto::generate_nullifier_hash;
+
+    fn create_notes(count: usize) -> Vec<DepositNote> {
+        let mut rng = note_rng(Some(3));
+        (0..count)
+            .map(|_| generate_deposit_note_with_rng(ZkAssetId { block: 2, tx: 1 }, 1000, &mut rng).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_index_and_persistence() {
+        let notes = create_notes(3);
+        let mut index = NullifierIndex::from_notes(&notes[..2]).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.insert(&notes[0]).unwrap(), generate_nullifier_hash(&notes[0].nullifier).unwrap());
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get(&notes[2].commitment), None);
+
+        let json = serde_json::to_string(&index).unwrap();
+        let mut restored: NullifierIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, index);
+
+        restored.retain(|commitment| *commitment != notes[0].commitment);
+        assert_eq!(restored.get(&notes[0].commitment), None);
+        assert_eq!(restored.get(&notes[1].commitment), index.get(&notes[1].commitment));
+    }
+
+    #[tokio::test]
+    async fn test_check_spent() {
+        let notes = create_notes(2);
+        let index = NullifierIndex::from_notes(&notes).unwrap();
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let mut params = vec!["18".to_string(), "2".to_string()];
+        for note in index.notes() {
+            let (low, high) = note.nullifier_hash.to_u128_pair();
+            params.extend([low.to_string(), high.to_string()]);
+        }
+        provider.add_simulation_data("6:7", &params.join(","), &[0, 1]);
+        let client = PoolClient::new(Arc::new(provider), ZkAssetId { block: 6, tx: 7 });
+
+        let unknown = Commitment::new([9u8; 32]);
+        let spent = index
+            .check_spent(&client, &[notes[0].commitment, unknown, notes[1].commitment])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![Some(false), None, Some(true)]);
+    }
+}
@@ -0,0 +1,889 @@
+//! # Pool Client
+//!
+//! Read-only access to deployed pool and factory contracts. The
+//! [`PoolClient`] and [`FactoryClient`] simulate calls to the contracts' view
+//! opcodes through the provider, so wallets and operators can check pool and
+//! note status without syncing the whole pool.
+//!
+//! ```rust
+//! use zkane_core::{mock_provider::MockProvider, PoolClient};
+//! use zkane_common::ZkAssetId;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> zkane_common::ZKaneResult<()> {
+//! let provider = MockProvider::new(bitcoin::Network::Regtest);
+//! provider.add_simulation_data("6:7", "11", &3u128.to_le_bytes());
+//!
+//! let client = PoolClient::new(Arc::new(provider), ZkAssetId { block: 6, tx: 7 });
+//! assert_eq!(client.deposit_count().await?, 3);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::simulation::{simulate_withdrawal, PoolState, SimulationReport};
+use bitcoin::TxOut;
+use deezel_common::traits::DeezelProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use zkane_abi::{decode_alkane_id, decode_hash, decode_u128, factory, pool, split_hash, AbiError};
+use zkane_common::{
+    derive_pool_id, Commitment, DenominationSpec, DepositNote, GlobalStats, MerklePath, NullifierHash, PoolRecord,
+    PoolReserves, PoolTemplate, ProtocolFee, RewardProgram, WithdrawalProof, ZKaneConfig, ZKaneError, ZKaneResult, ZkAssetId,
+};
+use zkane_crypto::{MerkleTree, NullifierTreeProof};
+
+/// Number of nullifier hashes checked per call, the pool's own limit
+pub const NULLIFIER_BATCH_SIZE: usize = 100;
+
+/// Most pool generations followed for one asset/denomination pair
+pub const MAX_POOL_GENERATIONS: usize = 256;
+
+/// Number of pool records requested per page, the factory's own limit
+pub const POOLS_PAGE_SIZE: u128 = 100;
+
+/// Number of commitments requested per page when scanning the pool.
+///
+/// Matches the pool's own limit on a single `GetCommitmentRange` call.
+pub const COMMITMENT_PAGE_SIZE: u32 = 1000;
+
+/// A snapshot of a pool's public state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolInfo {
+    /// The pool contract
+    pub pool_id: ZkAssetId,
+    /// Amount of every deposit
+    pub denomination: u128,
+    /// Number of deposits, the size of the anonymity set
+    pub deposit_count: u64,
+    /// Current Merkle root, hex encoded
+    pub merkle_root: String,
+    /// Version of the withdrawal circuit the pool accepts
+    pub circuit_version: u32,
+    /// Fee taken from withdrawals, if any
+    pub protocol_fee: Option<ProtocolFee>,
+}
+
+/// Read-only client for a deployed pool contract.
+pub struct PoolClient<P: DeezelProvider> {
+    provider: Arc<P>,
+    pool_id: ZkAssetId,
+}
+
+impl<P: DeezelProvider> PoolClient<P> {
+    /// Create a client for the pool with the given alkane ID.
+    pub fn new(provider: Arc<P>, pool_id: ZkAssetId) -> Self {
+        Self { provider, pool_id }
+    }
+
+    /// Get the alkane ID of the pool.
+    pub fn pool_id(&self) -> ZkAssetId {
+        self.pool_id
+    }
+
+    /// Get the number of deposits made to the pool.
+    ///
+    /// This is also the size of the anonymity set of every note in the pool.
+    pub async fn deposit_count(&self) -> ZKaneResult<u64> {
+        let count = self.call_u128(&[pool::GET_DEPOSIT_COUNT]).await?;
+        u64::try_from(count).map_err(|_| ZKaneError::PoolQueryFailed(format!("deposit count out of range: {}", count)))
+    }
+
+    /// Get the current Merkle root of the pool.
+    pub async fn merkle_root(&self) -> ZKaneResult<[u8; 32]> {
+        let data = self.call(&[pool::GET_ROOT]).await?;
+        decode_hash(&data).map_err(query_failed)
+    }
+
+    /// Get the denomination of the pool.
+    pub async fn denomination(&self) -> ZKaneResult<u128> {
+        self.call_u128(&[pool::GET_DENOMINATION]).await
+    }
+
+    /// Get the version of the withdrawal circuit the pool accepts.
+    pub async fn circuit_version(&self) -> ZKaneResult<u32> {
+        let version = self.call_u128(&[pool::GET_CIRCUIT_VERSION]).await?;
+        u32::try_from(version)
+            .map_err(|_| ZKaneError::PoolQueryFailed(format!("circuit version out of range: {}", version)))
+    }
+
+    /// Get the fee the pool takes from withdrawals.
+    pub async fn protocol_fee(&self) -> ZKaneResult<Option<ProtocolFee>> {
+        let data = self.call(&[pool::GET_PROTOCOL_FEE]).await?;
+        ProtocolFee::from_bytes(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the storage schema version recorded by the pool.
+    ///
+    /// Pools upgraded from an earlier schema report it until their first
+    /// state-changing call migrates them. Pools deployed before versioning
+    /// don't have the `GetConfig` opcode, and fail the query.
+    pub async fn schema_version(&self) -> ZKaneResult<u32> {
+        self.config_info().await?["schema_version"]
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| ZKaneError::PoolQueryFailed("pool config has no schema version".to_string()))
+    }
+
+    /// Get the pool's configuration, including its verifier key.
+    ///
+    /// Like [`schema_version`](Self::schema_version), only available from
+    /// pools with the `GetConfig` opcode.
+    pub async fn config(&self) -> ZKaneResult<ZKaneConfig> {
+        serde_json::from_value(self.config_info().await?["config"].take())
+            .map_err(|e| ZKaneError::PoolQueryFailed(format!("malformed pool config: {}", e)))
+    }
+
+    /// Simulate a withdrawal against the pool contract's current state.
+    ///
+    /// Like [`PrivacyPool::simulate_withdrawal`](crate::PrivacyPool::simulate_withdrawal),
+    /// except that the contract keeps no root history, so a stale root is
+    /// reported as unknown. The outputs are checked if given.
+    pub async fn simulate_withdrawal(
+        &self,
+        proof: &WithdrawalProof,
+        outputs: Option<&[TxOut]>,
+    ) -> ZKaneResult<SimulationReport> {
+        let config = self.config().await?;
+        let state = PoolState {
+            config: &config,
+            merkle_root: self.merkle_root().await?,
+            past_root: false,
+            nullifier_spent: self.is_spent(&proof.nullifier_hash).await?,
+        };
+        Ok(simulate_withdrawal(&state, proof, outputs))
+    }
+
+    /// Get a snapshot of the pool's public state.
+    pub async fn info(&self) -> ZKaneResult<PoolInfo> {
+        Ok(PoolInfo {
+            pool_id: self.pool_id,
+            denomination: self.denomination().await?,
+            deposit_count: self.deposit_count().await?,
+            merkle_root: hex::encode(self.merkle_root().await?),
+            circuit_version: self.circuit_version().await?,
+            protocol_fee: self.protocol_fee().await?,
+        })
+    }
+
+    /// Get the JSON answer of the pool's `GetConfig` opcode.
+    async fn config_info(&self) -> ZKaneResult<JsonValue> {
+        let data = self.call(&[pool::GET_CONFIG]).await?;
+        serde_json::from_slice(&data).map_err(|e| ZKaneError::PoolQueryFailed(format!("malformed pool config: {}", e)))
+    }
+
+    /// Get up to `count` consecutive commitments starting at leaf `start`.
+    ///
+    /// Fewer commitments are returned at the end of the tree.
+    pub async fn commitments(&self, start: u32, count: u32) -> ZKaneResult<Vec<Commitment>> {
+        let data = self
+            .call(&[pool::GET_COMMITMENT_RANGE, start as u128, count as u128])
+            .await?;
+        Commitment::parse_packed(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Find the leaf index of a commitment by paging through the pool.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the commitment hasn't been deposited.
+    pub async fn find_commitment(&self, commitment: &Commitment) -> ZKaneResult<Option<u32>> {
+        let mut start = 0u32;
+        loop {
+            let page = self.commitments(start, COMMITMENT_PAGE_SIZE).await?;
+            if let Some(offset) = page.iter().position(|leaf| leaf == commitment) {
+                return Ok(Some(start + offset as u32));
+            }
+            if page.len() < COMMITMENT_PAGE_SIZE as usize {
+                return Ok(None);
+            }
+            start += COMMITMENT_PAGE_SIZE;
+        }
+    }
+
+    /// Get the leaf index and Merkle path of a deposited commitment.
+    ///
+    /// Pages through all of the pool's commitments to rebuild its tree, so
+    /// the path leads to the pool's current root. Like
+    /// [`config`](Self::config), only available from pools with the
+    /// `GetConfig` opcode.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the commitment hasn't been deposited.
+    pub async fn merkle_path(&self, commitment: &Commitment) -> ZKaneResult<Option<(u32, MerklePath)>> {
+        let height = self.config().await?.tree_height;
+        let mut leaves = Vec::new();
+        loop {
+            let page = self.commitments(leaves.len() as u32, COMMITMENT_PAGE_SIZE).await?;
+            let last = page.len() < COMMITMENT_PAGE_SIZE as usize;
+            leaves.extend(page);
+            if last {
+                break;
+            }
+        }
+
+        let Some(leaf_index) = leaves.iter().position(|leaf| leaf == commitment) else {
+            return Ok(None);
+        };
+        let tree = MerkleTree::from_leaves(height, &leaves)?;
+        Ok(Some((leaf_index as u32, tree.generate_path(leaf_index as u32)?)))
+    }
+
+    /// Check whether a nullifier hash has been spent.
+    pub async fn is_spent(&self, nullifier_hash: &NullifierHash) -> ZKaneResult<bool> {
+        let (low, high) = nullifier_hash.to_u128_pair();
+        Ok(self.call_u128(&[pool::IS_NULLIFIER_SPENT, low, high]).await? != 0)
+    }
+
+    /// Check whether each of a list of nullifier hashes has been spent.
+    ///
+    /// The hashes are checked [`NULLIFIER_BATCH_SIZE`] at a time, so a wallet
+    /// scanning its notes makes one query per batch instead of one per note.
+    ///
+    /// # Returns
+    ///
+    /// The spent status of each hash, in the order given.
+    pub async fn check_spent(&self, nullifier_hashes: &[NullifierHash]) -> ZKaneResult<Vec<bool>> {
+        let mut spent = Vec::with_capacity(nullifier_hashes.len());
+        for batch in nullifier_hashes.chunks(NULLIFIER_BATCH_SIZE) {
+            let mut inputs = vec![pool::ARE_NULLIFIERS_SPENT, batch.len() as u128];
+            for nullifier_hash in batch {
+                let (low, high) = nullifier_hash.to_u128_pair();
+                inputs.extend([low, high]);
+            }
+            let data = self.call(&inputs).await?;
+            if data.len() != batch.len() || data.iter().any(|&flag| flag > 1) {
+                return Err(ZKaneError::PoolQueryFailed(format!(
+                    "expected {} spent flags, got {} bytes",
+                    batch.len(),
+                    data.len()
+                )));
+            }
+            spent.extend(data.iter().map(|&flag| flag == 1));
+        }
+        Ok(spent)
+    }
+
+    /// Get the root of the pool's nullifier tree.
+    ///
+    /// # Returns
+    ///
+    /// The root, and whether the tree holds every spent nullifier. A pool
+    /// upgraded to the tree only holds the nullifiers spent since, so its
+    /// proofs of absence don't show a nullifier is unspent.
+    pub async fn nullifier_root(&self) -> ZKaneResult<([u8; 32], bool)> {
+        let data = self.call(&[pool::GET_NULLIFIER_ROOT]).await?;
+        match data.as_slice() {
+            [root @ .., complete] if root.len() == 32 && *complete <= 1 => {
+                Ok((root.try_into().unwrap(), *complete == 1))
+            }
+            _ => Err(ZKaneError::PoolQueryFailed(format!(
+                "expected a nullifier root and flag, got {} bytes",
+                data.len()
+            ))),
+        }
+    }
+
+    /// Get the pool's proof of whether a nullifier hash is in its nullifier
+    /// tree.
+    ///
+    /// The proof is checked against a root from [`Self::nullifier_root`] or
+    /// any other source trusted by the caller, so the pool doesn't have to
+    /// be.
+    pub async fn nullifier_proof(&self, nullifier_hash: &NullifierHash) -> ZKaneResult<NullifierTreeProof> {
+        let (low, high) = nullifier_hash.to_u128_pair();
+        let data = self.call(&[pool::GET_NULLIFIER_PROOF, low, high]).await?;
+        NullifierTreeProof::from_bytes(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the height a commitment was pre-registered at for a
+    /// front-run-protected deposit.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the commitment isn't registered, or was already revealed.
+    pub async fn registration_height(&self, commitment: &Commitment) -> ZKaneResult<Option<u64>> {
+        let hash = commitment.registration_hash();
+        let [low, high] = split_hash(&hash);
+        let height = self.call_u128(&[pool::GET_REGISTRATION, low, high]).await?;
+        Ok(Some(height as u64).filter(|&height| height != 0))
+    }
+
+    /// Get the height a leaf was inserted at.
+    ///
+    /// # Returns
+    ///
+    /// `None` for leaves inserted before the pool recorded heights.
+    pub async fn leaf_height(&self, leaf_index: u32) -> ZKaneResult<Option<u64>> {
+        let height = self.call_u128(&[pool::GET_LEAF_HEIGHT, leaf_index as u128]).await?;
+        Ok(Some(height as u64).filter(|&height| height != 0))
+    }
+
+    /// Get the reward points a withdrawn note can claim, 0 if it has none
+    /// or they were claimed.
+    pub async fn reward_claim(&self, nullifier_hash: &NullifierHash) -> ZKaneResult<u128> {
+        let (low, high) = nullifier_hash.to_u128_pair();
+        self.call_u128(&[pool::GET_REWARD_CLAIM, low, high]).await
+    }
+
+    /// Get the reward alkanes the pool holds to pay claims.
+    pub async fn reward_balance(&self) -> ZKaneResult<u128> {
+        self.call_u128(&[pool::GET_REWARD_BALANCE]).await
+    }
+
+    /// Get the balance of its asset the pool holds for notes, and its
+    /// deposit and withdrawal counts.
+    pub async fn reserves(&self) -> ZKaneResult<PoolReserves> {
+        let data = self.call(&[pool::GET_RESERVES]).await?;
+        PoolReserves::from_bytes(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Simulate a call to the pool and return its response data.
+    async fn call(&self, inputs: &[u128]) -> ZKaneResult<Vec<u8>> {
+        simulate_call(self.provider.as_ref(), self.pool_id, inputs).await
+    }
+
+    /// Simulate a call returning a little-endian `u128`.
+    async fn call_u128(&self, inputs: &[u128]) -> ZKaneResult<u128> {
+        let data = self.call(inputs).await?;
+        decode_u128(&data).map_err(query_failed)
+    }
+}
+
+/// Read-only client for the pool factory contract.
+pub struct FactoryClient<P: DeezelProvider> {
+    provider: Arc<P>,
+    factory_id: ZkAssetId,
+}
+
+impl<P: DeezelProvider> FactoryClient<P> {
+    /// Create a client for the factory with the given alkane ID.
+    pub fn new(provider: Arc<P>, factory_id: ZkAssetId) -> Self {
+        Self { provider, factory_id }
+    }
+
+    /// Get a page of pools in creation order.
+    ///
+    /// # Returns
+    ///
+    /// The total number of pools and the records of the page.
+    pub async fn pools_page(&self, offset: u128, limit: u128) -> ZKaneResult<(u128, Vec<PoolRecord>)> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_POOLS_PAGE, offset, limit],
+        )
+        .await?;
+        PoolRecord::parse_page(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the records of every pool, paging through the factory.
+    pub async fn pools(&self) -> ZKaneResult<Vec<PoolRecord>> {
+        let mut pools = Vec::new();
+        loop {
+            let (total, page) = self.pools_page(pools.len() as u128, POOLS_PAGE_SIZE).await?;
+            let done = page.is_empty();
+            pools.extend(page);
+            if done || pools.len() as u128 >= total {
+                return Ok(pools);
+            }
+        }
+    }
+
+    /// Get pool and deposit totals across all pools and per asset.
+    pub async fn global_stats(&self) -> ZKaneResult<GlobalStats> {
+        let data = simulate_call(self.provider.as_ref(), self.factory_id, &[factory::GET_GLOBAL_STATS]).await?;
+        serde_json::from_slice(&data).map_err(|e| ZKaneError::PoolQueryFailed(format!("invalid factory stats: {}", e)))
+    }
+
+    /// Get the version of the template new pools are created from, 0 if
+    /// the factory has no template.
+    pub async fn template_version(&self) -> ZKaneResult<u32> {
+        let data =
+            simulate_call(self.provider.as_ref(), self.factory_id, &[factory::GET_TEMPLATE_VERSION]).await?;
+        u32::try_from(decode_u128(&data).map_err(query_failed)?)
+            .map_err(|_| ZKaneError::PoolQueryFailed("template version out of range".to_string()))
+    }
+
+    /// Get a registered pool template, `None` if the version isn't registered.
+    pub async fn template(&self, template_version: u32) -> ZKaneResult<Option<PoolTemplate>> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_TEMPLATE, template_version as u128],
+        )
+        .await?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        PoolTemplate::from_bytes(&data)
+            .map(Some)
+            .map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the registered denomination specs of an asset, in registration
+    /// order. Empty if the factory admin registered none.
+    pub async fn denomination_specs(&self, asset_id: &ZkAssetId) -> ZKaneResult<Vec<DenominationSpec>> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_DENOMINATION_SPECS, asset_id.block, asset_id.tx],
+        )
+        .await?;
+        DenominationSpec::parse_list(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the reward program of a pool, `None` if it doesn't reward deposits.
+    pub async fn reward_program(&self, pool_id: &ZkAssetId) -> ZKaneResult<Option<RewardProgram>> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_REWARD_PROGRAM, pool_id.block, pool_id.tx],
+        )
+        .await?;
+        RewardProgram::from_bytes(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the records of the pools of an asset, in creation order.
+    pub async fn asset_pools(&self, asset_id: &ZkAssetId) -> ZKaneResult<Vec<PoolRecord>> {
+        let mut pools = self.pools().await?;
+        pools.retain(|pool| pool.asset_id == *asset_id);
+        Ok(pools)
+    }
+
+    /// Get the record of a pool.
+    pub async fn pool(&self, pool_id: &ZkAssetId) -> ZKaneResult<PoolRecord> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_POOL_METADATA, pool_id.block, pool_id.tx],
+        )
+        .await?;
+        PoolRecord::from_bytes(&data).map_err(|e| ZKaneError::PoolQueryFailed(e.to_string()))
+    }
+
+    /// Get the pool taking deposits for an asset and denomination.
+    ///
+    /// Once a pool is full and retired, this is its successor. Returns
+    /// `None` if the factory has no pool for the pair.
+    pub async fn active_pool(
+        &self,
+        asset_id: &ZkAssetId,
+        denomination: u128,
+    ) -> ZKaneResult<Option<ZkAssetId>> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_ACTIVE_POOL, asset_id.block, asset_id.tx, denomination],
+        )
+        .await?;
+        parse_pool_id(&data)
+    }
+
+    /// Get the successor of a retired pool, `None` if the pool is active.
+    pub async fn successor(&self, pool_id: &ZkAssetId) -> ZKaneResult<Option<ZkAssetId>> {
+        let data = simulate_call(
+            self.provider.as_ref(),
+            self.factory_id,
+            &[factory::GET_SUCCESSOR, pool_id.block, pool_id.tx],
+        )
+        .await?;
+        parse_pool_id(&data)
+    }
+
+    /// Get every generation of the pool of an asset and denomination, from
+    /// the first pool to the active one.
+    ///
+    /// Notes stay withdrawable from the pool they were deposited in after it
+    /// is retired, so wallets look for them across all generations.
+    pub async fn pool_generations(
+        &self,
+        asset_id: &ZkAssetId,
+        denomination: u128,
+    ) -> ZKaneResult<Vec<ZkAssetId>> {
+        let mut pools = vec![derive_pool_id(asset_id, denomination)];
+        while let Some(successor) = self.successor(pools.last().unwrap()).await? {
+            if pools.len() >= MAX_POOL_GENERATIONS || pools.contains(&successor) {
+                return Err(ZKaneError::PoolQueryFailed(format!(
+                    "pool {} has too many generations",
+                    pools[0]
+                )));
+            }
+            pools.push(successor);
+        }
+        Ok(pools)
+    }
+
+    /// Find the pool generation a note was deposited in.
+    ///
+    /// # Returns
+    ///
+    /// The pool and the note's leaf index, or `None` if no generation holds
+    /// the note's commitment.
+    pub async fn locate_note(&self, note: &DepositNote) -> ZKaneResult<Option<(ZkAssetId, u32)>> {
+        // Recent generations are the likeliest to hold the note
+        for pool_id in self.pool_generations(&note.asset_id, note.denomination).await?.into_iter().rev() {
+            let client = PoolClient::new(self.provider.clone(), pool_id);
+            if let Some(leaf_index) = client.find_commitment(&note.commitment).await? {
+                return Ok(Some((pool_id, leaf_index)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Decode a pool ID returned by the factory, empty if there is none.
+fn parse_pool_id(data: &[u8]) -> ZKaneResult<Option<ZkAssetId>> {
+    let id = decode_alkane_id(data).map_err(query_failed)?;
+    Ok(id.map(|(block, tx)| ZkAssetId { block, tx }))
+}
+
+/// Report a response that doesn't decode as a failed query.
+fn query_failed(e: AbiError) -> ZKaneError {
+    ZKaneError::PoolQueryFailed(e.to_string())
+}
+
+/// Simulate a call to a contract and return its response data.
+async fn simulate_call<P: DeezelProvider>(
+    provider: &P,
+    contract_id: ZkAssetId,
+    inputs: &[u128],
+) -> ZKaneResult<Vec<u8>> {
+    let params = inputs.iter().map(u128::to_string).collect::<Vec<_>>().join(",");
+    let response = provider.simulate(&contract_id.to_string(), Some(&params)).await?;
+    parse_simulation(&response)
+}
+
+/// Extract the response data of a simulated call.
+fn parse_simulation(response: &JsonValue) -> ZKaneResult<Vec<u8>> {
+    let execution = &response["execution"];
+    if let Some(error) = execution["error"].as_str().filter(|error| !error.is_empty()) {
+        return Err(ZKaneError::PoolQueryFailed(error.to_string()));
+    }
+    let data = execution["data"]
+        .as_str()
+        .ok_or_else(|| ZKaneError::PoolQueryFailed("simulation returned no data".to_string()))?;
+    hex::decode(data.strip_prefix("0x").unwrap_or(data))
+        .map_err(|e| ZKaneError::PoolQueryFailed(format!("invalid response data: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use zkane_common::Recipient;
+
+    const POOL: &str = "6:7";
+
+    fn create_client() -> (MockProvider, PoolClient<MockProvider>) {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let client = PoolClient::new(Arc::new(provider.clone()), ZkAssetId { block: 6, tx: 7 });
+        (provider, client)
+    }
+
+    #[tokio::test]
+    async fn test_pool_views() {
+        let (provider, client) = create_client();
+        provider.add_simulation_data(POOL, "10", &[0xab; 32]);
+        provider.add_simulation_data(POOL, "14", &1000000u128.to_le_bytes());
+
+        let nullifier_hash = NullifierHash::new([3u8; 32]);
+        let (low, high) = nullifier_hash.to_u128_pair();
+        provider.add_simulation_data(POOL, &format!("17,{},{}", low, high), &1u128.to_le_bytes());
+
+        assert_eq!(client.merkle_root().await.unwrap(), [0xab; 32]);
+        assert_eq!(client.denomination().await.unwrap(), 1000000);
+        assert!(client.is_spent(&nullifier_hash).await.unwrap());
+        // Nothing scripted for this nullifier
+        assert!(client.is_spent(&NullifierHash::new([4u8; 32])).await.is_err());
+
+        let commitment = Commitment::new([5u8; 32]);
+        let hash = commitment.registration_hash();
+        let [low, high] = split_hash(&hash);
+        let params = format!("{},{},{}", pool::GET_REGISTRATION, low, high);
+        provider.add_simulation_data(POOL, &params, &840_000u128.to_le_bytes());
+        assert_eq!(client.registration_height(&commitment).await.unwrap(), Some(840_000));
+        provider.add_simulation_data(POOL, &params, &0u128.to_le_bytes());
+        assert_eq!(client.registration_height(&commitment).await.unwrap(), None);
+
+        provider.add_simulation_data(POOL, "20", br#"{"config":{},"schema_version":1,"supported_schema_version":1}"#);
+        assert_eq!(client.schema_version().await.unwrap(), 1);
+        provider.add_simulation_data(POOL, "20", b"{}");
+        assert!(matches!(client.schema_version().await, Err(ZKaneError::PoolQueryFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_nullifier_tree_views() {
+        let (provider, client) = create_client();
+        let spent = NullifierHash::new([3u8; 32]);
+        let unspent = NullifierHash::new([4u8; 32]);
+        let mut tree = zkane_crypto::NullifierTree::new();
+        tree.insert(&spent).unwrap();
+
+        provider.add_simulation_data(POOL, "21", &[tree.root().to_vec(), vec![1]].concat());
+        let (root, complete) = client.nullifier_root().await.unwrap();
+        assert_eq!(root, tree.root());
+        assert!(complete);
+
+        let (low, high) = unspent.to_u128_pair();
+        let params = format!("22,{},{}", low, high);
+        provider.add_simulation_data(POOL, &params, &tree.prove(&unspent).unwrap().to_bytes());
+        let proof = client.nullifier_proof(&unspent).await.unwrap();
+        assert!(proof.verify_non_membership(&root, &unspent, tree.hasher()));
+
+        provider.add_simulation_data(POOL, "21", &[0u8; 32]);
+        assert!(matches!(client.nullifier_root().await, Err(ZKaneError::PoolQueryFailed(_))));
+        provider.add_simulation_data(POOL, &params, &[1]);
+        assert!(matches!(client.nullifier_proof(&unspent).await, Err(ZKaneError::PoolQueryFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_check_spent() {
+        let (provider, client) = create_client();
+        let hashes: Vec<NullifierHash> = (0..NULLIFIER_BATCH_SIZE as u8 + 2).map(|n| NullifierHash::new([n; 32])).collect();
+        let params = |batch: &[NullifierHash]| {
+            let halves = batch.iter().flat_map(|hash| {
+                let (low, high) = hash.to_u128_pair();
+                [low, high]
+            });
+            [18, batch.len() as u128].into_iter().chain(halves).map(|n| n.to_string()).collect::<Vec<_>>().join(",")
+        };
+        // Every third note of the first batch is spent, and the last note
+        let first: Vec<u8> = (0..NULLIFIER_BATCH_SIZE).map(|n| (n % 3 == 0) as u8).collect();
+        provider.add_simulation_data(POOL, &params(&hashes[..NULLIFIER_BATCH_SIZE]), &first);
+        provider.add_simulation_data(POOL, &params(&hashes[NULLIFIER_BATCH_SIZE..]), &[0, 1]);
+
+        let spent = client.check_spent(&hashes).await.unwrap();
+        assert_eq!(spent.len(), hashes.len());
+        assert!(spent[0] && !spent[1] && spent[99]);
+        assert!(!spent[100] && spent[101]);
+        assert!(client.check_spent(&[]).await.unwrap().is_empty());
+
+        // A response that doesn't cover the batch is rejected
+        provider.add_simulation_data(POOL, &params(&hashes[..1]), &[1, 0]);
+        assert!(client.check_spent(&hashes[..1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_commitment() {
+        let (provider, client) = create_client();
+        let leaves = [[1u8; 32], [2u8; 32], [3u8; 32]].concat();
+        provider.add_simulation_data(POOL, &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &leaves);
+
+        assert_eq!(client.find_commitment(&Commitment::new([3u8; 32])).await.unwrap(), Some(2));
+        assert_eq!(client.find_commitment(&Commitment::new([9u8; 32])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_merkle_path() {
+        let (provider, client) = create_client();
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let info = serde_json::json!({ "config": config, "schema_version": 1, "supported_schema_version": 1 });
+        provider.add_simulation_data(POOL, "20", info.to_string().as_bytes());
+        let leaves: Vec<Commitment> = (1..=3).map(|n| Commitment::new([n; 32])).collect();
+        let packed: Vec<u8> = leaves.iter().flat_map(|leaf| *leaf.as_bytes()).collect();
+        provider.add_simulation_data(POOL, &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &packed);
+
+        let (leaf_index, path) = client.merkle_path(&leaves[1]).await.unwrap().unwrap();
+        assert_eq!(leaf_index, 1);
+        let tree = MerkleTree::from_leaves(4, &leaves).unwrap();
+        assert!(tree.verify_path(&leaves[1], 1, &path, &tree.root()).unwrap());
+        assert!(client.merkle_path(&Commitment::new([9u8; 32])).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pool_info() {
+        let (provider, client) = create_client();
+        provider.add_simulation_data(POOL, "10", &[0xab; 32]);
+        provider.add_simulation_data(POOL, "11", &4u128.to_le_bytes());
+        provider.add_simulation_data(POOL, "14", &1000000u128.to_le_bytes());
+        let fee = ProtocolFee::new(30, ZkAssetId { block: 2, tx: 9 }).unwrap();
+        provider.add_simulation_data(POOL, "15", &fee.to_bytes());
+        provider.add_simulation_data(POOL, "16", &1u128.to_le_bytes());
+
+        let info = client.info().await.unwrap();
+        assert_eq!(info.deposit_count, 4);
+        assert_eq!(info.merkle_root, "ab".repeat(32));
+        assert_eq!(info.circuit_version, 1);
+        assert_eq!(info.protocol_fee, Some(fee));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_withdrawal() {
+        let (provider, client) = create_client();
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]);
+        let info = serde_json::json!({ "config": config, "schema_version": 1, "supported_schema_version": 1 });
+        provider.add_simulation_data(POOL, "20", info.to_string().as_bytes());
+        provider.add_simulation_data(POOL, "10", &[0xab; 32]);
+        let nullifier_hash = NullifierHash::new([3u8; 32]);
+        let (low, high) = nullifier_hash.to_u128_pair();
+        provider.add_simulation_data(POOL, &format!("17,{},{}", low, high), &1u128.to_le_bytes());
+
+        assert_eq!(client.config().await.unwrap().denomination, 1000000);
+        let proof = WithdrawalProof::new(vec![1; 8], [0xab; 32], nullifier_hash, Recipient::default());
+        let report = client.simulate_withdrawal(&proof, None).await.unwrap();
+        assert_eq!(report.first_failure().unwrap().step, crate::SimulationStep::Nullifier);
+    }
+
+    #[tokio::test]
+    async fn test_factory_pools() {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let record = |asset_tx: u128, pool_tx: u128| PoolRecord {
+            asset_id: ZkAssetId { block: 2, tx: asset_tx },
+            denomination: 1000000,
+            pool_id: ZkAssetId { block: 6, tx: pool_tx },
+            deposit_count: 0,
+            created_block: 100,
+            template_version: 1,
+            circuit_version: 1,
+        };
+        let page = |records: &[PoolRecord]| {
+            let mut data = 3u128.to_le_bytes().to_vec();
+            records.iter().for_each(|record| data.extend_from_slice(&record.to_bytes()));
+            data
+        };
+        // The factory returns fewer records than requested, so the client pages
+        provider.add_simulation_data("4:1", "6,0,100", &page(&[record(1, 10), record(5, 11)]));
+        provider.add_simulation_data("4:1", "6,2,100", &page(&[record(1, 12)]));
+        provider.add_simulation_data("4:1", "7,6,11", &record(5, 11).to_bytes());
+
+        assert_eq!(factory.pools().await.unwrap().len(), 3);
+        let pools = factory.asset_pools(&ZkAssetId { block: 2, tx: 1 }).await.unwrap();
+        assert_eq!(pools.iter().map(|pool| pool.pool_id.tx).collect::<Vec<_>>(), vec![10, 12]);
+        assert_eq!(factory.pool(&ZkAssetId { block: 6, tx: 11 }).await.unwrap(), record(5, 11));
+    }
+
+    #[tokio::test]
+    async fn test_factory_templates() {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let template = PoolTemplate {
+            template_id: ZkAssetId { block: 4, tx: 0x7a6b },
+            circuit_version: 2,
+            registered_block: 840000,
+        };
+        provider.add_simulation_data("4:1", "25", &2u128.to_le_bytes());
+        provider.add_simulation_data("4:1", "24,2", &template.to_bytes());
+        provider.add_simulation_data("4:1", "24,3", &[]);
+
+        assert_eq!(factory.template_version().await.unwrap(), 2);
+        assert_eq!(factory.template(2).await.unwrap(), Some(template));
+        assert_eq!(factory.template(3).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_factory_denomination_specs() {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let specs = vec![
+            DenominationSpec::new(10_000_000, 8, "ZKN").unwrap(),
+            DenominationSpec::new(100_000_000, 8, "ZKN").unwrap(),
+        ];
+        let data: Vec<u8> = specs.iter().flat_map(DenominationSpec::to_bytes).collect();
+        provider.add_simulation_data("4:1", "27,2,1", &data);
+        provider.add_simulation_data("4:1", "27,2,5", &[]);
+        provider.add_simulation_data("4:1", "27,2,6", &data[1..]);
+
+        assert_eq!(factory.denomination_specs(&ZkAssetId { block: 2, tx: 1 }).await.unwrap(), specs);
+        assert!(factory.denomination_specs(&ZkAssetId { block: 2, tx: 5 }).await.unwrap().is_empty());
+        assert!(factory.denomination_specs(&ZkAssetId { block: 2, tx: 6 }).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reward_views() {
+        let (provider, client) = create_client();
+        let nullifier_hash = NullifierHash::new([3u8; 32]);
+        let (low, high) = nullifier_hash.to_u128_pair();
+        provider.add_simulation_data(POOL, "23,4", &840_000u128.to_le_bytes());
+        provider.add_simulation_data(POOL, "23,0", &0u128.to_le_bytes());
+        provider.add_simulation_data(POOL, &format!("27,{},{}", low, high), &500u128.to_le_bytes());
+        provider.add_simulation_data(POOL, "28", &10_000u128.to_le_bytes());
+
+        assert_eq!(client.leaf_height(4).await.unwrap(), Some(840_000));
+        assert_eq!(client.leaf_height(0).await.unwrap(), None);
+        assert_eq!(client.reward_claim(&nullifier_hash).await.unwrap(), 500);
+        assert_eq!(client.reward_balance().await.unwrap(), 10_000);
+
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let program = RewardProgram::new(ZkAssetId { block: 2, tx: 50 }, 10, 0).unwrap();
+        provider.add_simulation_data("4:1", "29,6,7", &program.to_bytes());
+        provider.add_simulation_data("4:1", "29,6,8", &[]);
+        assert_eq!(factory.reward_program(&ZkAssetId { block: 6, tx: 7 }).await.unwrap(), Some(program));
+        assert_eq!(factory.reward_program(&ZkAssetId { block: 6, tx: 8 }).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_reserves_view() {
+        let (provider, client) = create_client();
+        let reserves =
+            PoolReserves { held: 3_000, deposit_count: 5, withdrawal_count: 2, liabilities: 3_000, counts_complete: true };
+        provider.add_simulation_data(POOL, "29", &reserves.to_bytes());
+        assert_eq!(client.reserves().await.unwrap(), reserves);
+
+        provider.add_simulation_data(POOL, "29", &[0u8; 16]);
+        assert!(matches!(client.reserves().await, Err(ZKaneError::PoolQueryFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_factory_global_stats() {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let stats = GlobalStats {
+            total_pools: 3,
+            total_deposits: 12,
+            assets: vec![
+                zkane_common::AssetStats { asset_id: ZkAssetId { block: 2, tx: 1 }, pools: 2, deposits: 10 },
+                zkane_common::AssetStats { asset_id: ZkAssetId { block: 2, tx: 5 }, pools: 1, deposits: 2 },
+            ],
+        };
+        provider.add_simulation_data("4:1", "21", &serde_json::to_vec(&stats).unwrap());
+        assert_eq!(factory.global_stats().await.unwrap(), stats);
+
+        provider.add_simulation_data("4:1", "21", b"{}");
+        assert!(matches!(factory.global_stats().await, Err(ZKaneError::PoolQueryFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_pool_rollover() {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let factory = FactoryClient::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        let note = crate::generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        let first = derive_pool_id(&note.asset_id, note.denomination);
+        let second = zkane_common::derive_pool_id_at(&note.asset_id, note.denomination, 1);
+        let encode = |id: ZkAssetId| [id.block.to_le_bytes(), id.tx.to_le_bytes()].concat();
+
+        // The first pool was retired, so deposits go to its successor
+        provider.add_simulation_data("4:1", "19,2,1,1000000", &encode(second));
+        provider.add_simulation_data("4:1", &format!("20,{},{}", first.block, first.tx), &encode(second));
+        provider.add_simulation_data("4:1", &format!("20,{},{}", second.block, second.tx), &[]);
+        assert_eq!(factory.active_pool(&note.asset_id, note.denomination).await.unwrap(), Some(second));
+        assert_eq!(factory.successor(&second).await.unwrap(), None);
+        assert_eq!(factory.pool_generations(&note.asset_id, 1000000).await.unwrap(), vec![first, second]);
+
+        // A note deposited before the rollover is found in the retired pool
+        let leaves = |commitments: &[[u8; 32]]| commitments.concat();
+        provider.add_simulation_data(&second.to_string(), &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &leaves(&[[9u8; 32]]));
+        provider.add_simulation_data(
+            &first.to_string(),
+            &format!("13,0,{}", COMMITMENT_PAGE_SIZE),
+            &leaves(&[[8u8; 32], note.commitment.0]),
+        );
+        assert_eq!(factory.locate_note(&note).await.unwrap(), Some((first, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_simulation_errors() {
+        let (provider, client) = create_client();
+        provider.add_simulation(
+            POOL,
+            "11",
+            serde_json::json!({ "execution": { "data": "0x", "error": "ALKANES: revert" } }),
+        );
+        assert!(matches!(client.deposit_count().await, Err(ZKaneError::PoolQueryFailed(_))));
+
+        provider.add_simulation_data(POOL, "10", &[0u8; 16]);
+        assert!(client.merkle_root().await.is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! # Pool Provider
+//!
+//! [`PrivacyPool`](crate::PrivacyPool) and [`PoolSyncer`](crate::PoolSyncer)
+//! only need to fetch transactions, blocks and the chain tip and to
+//! broadcast, which the [`PoolProvider`] trait covers. Embedders that don't
+//! use deezel implement it on their own chain client and build zkane-core
+//! without default features, leaving out `deezel-common`.
+//!
+//! With the `deezel` feature, every `DeezelProvider` is a [`PoolProvider`],
+//! reading from its Esplora backend and broadcasting through its wallet.
+
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use zkane_common::ZKaneResult;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::{DeezelProvider, EsploraProvider, WalletProvider};
+
+/// The chain access a privacy pool needs.
+#[async_trait(?Send)]
+pub trait PoolProvider {
+    /// Get a transaction, in the Esplora JSON format.
+    async fn get_tx(&self, txid: &str) -> ZKaneResult<JsonValue>;
+
+    /// Get a block by hash, in the Esplora JSON format.
+    async fn get_block(&self, hash: &str) -> ZKaneResult<JsonValue>;
+
+    /// Get the height of the provider's chain tip.
+    async fn get_tip_height(&self) -> ZKaneResult<u64>;
+
+    /// Broadcast a hex encoded transaction.
+    ///
+    /// # Returns
+    ///
+    /// The txid of the broadcast transaction.
+    async fn broadcast(&self, tx_hex: &str) -> ZKaneResult<String>;
+}
+
+#[cfg(feature = "deezel")]
+#[async_trait(?Send)]
+impl<P: DeezelProvider> PoolProvider for P {
+    async fn get_tx(&self, txid: &str) -> ZKaneResult<JsonValue> {
+        Ok(EsploraProvider::get_tx(self, txid).await?)
+    }
+
+    async fn get_block(&self, hash: &str) -> ZKaneResult<JsonValue> {
+        Ok(EsploraProvider::get_block(self, hash).await?)
+    }
+
+    async fn get_tip_height(&self) -> ZKaneResult<u64> {
+        Ok(EsploraProvider::get_blocks_tip_height(self).await?)
+    }
+
+    async fn broadcast(&self, tx_hex: &str) -> ZKaneResult<String> {
+        Ok(WalletProvider::broadcast_transaction(self, tx_hex.to_string()).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+
+    #[tokio::test]
+    async fn test_deezel_provider_is_pool_provider() {
+        let mut provider = MockProvider::new(bitcoin::Network::Regtest);
+        provider.add_response("tx_a", serde_json::json!({ "txid": "tx_a" }));
+        assert_eq!(PoolProvider::get_tx(&provider, "tx_a").await.unwrap()["txid"], "tx_a");
+
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        };
+        let tx_hex = bitcoin::consensus::encode::serialize_hex(&tx);
+        let txid = PoolProvider::broadcast(&provider, &tx_hex).await.unwrap();
+        assert_eq!(txid, tx.compute_txid().to_string());
+        assert_eq!(provider.broadcasts(), vec![tx_hex]);
+    }
+}
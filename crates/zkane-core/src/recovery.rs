@@ -0,0 +1,186 @@
+//! # Note Recovery
+//!
+//! A deposit can only be withdrawn with its full note, but the secret and
+//! nullifier are the only parts that can't be looked up again: the pool,
+//! denomination and leaf index all follow from them and the chain.
+//! [`NoteRecovery`] rebuilds a note whose metadata was lost by recomputing
+//! its commitment for each of the factory's pools and searching the pool's
+//! leaves for it.
+//!
+//! ```rust
+//! use zkane_core::{mock_provider::MockProvider, NoteRecovery};
+//! use zkane_common::{Nullifier, Secret, ZkAssetId};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> zkane_common::ZKaneResult<()> {
+//! let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+//! let recovery = NoteRecovery::new(provider, ZkAssetId { block: 4, tx: 1 })
+//!     .with_asset(ZkAssetId { block: 2, tx: 1 });
+//!
+//! let (secret, nullifier) = (Secret::random(), Nullifier::random());
+//! if let Some(recovered) = recovery.recover(&secret, &nullifier).await? {
+//!     println!("note {} found at leaf {}", recovered.note.commitment.to_hex(), recovered.note.leaf_index);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::pool_client::{FactoryClient, PoolClient};
+use deezel_common::traits::DeezelProvider;
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use zkane_common::{Commitment, DepositNote, Nullifier, Secret, ZKaneResult, ZkAssetId};
+use zkane_crypto::{generate_asset_commitment, generate_nullifier_hash};
+
+/// A note rebuilt from its secret and nullifier.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveredNote {
+    /// The complete note, with its leaf index
+    pub note: DepositNote,
+    /// The pool holding the deposit
+    pub pool_id: ZkAssetId,
+    /// Whether the note has been withdrawn
+    pub spent: bool,
+    /// Number of deposits in the pool
+    pub anonymity_set: u64,
+}
+
+/// Searches a factory's pools for the deposit of a secret and nullifier.
+pub struct NoteRecovery<P: DeezelProvider> {
+    provider: Arc<P>,
+    factory: FactoryClient<P>,
+    asset_id: Option<ZkAssetId>,
+}
+
+impl<P: DeezelProvider> NoteRecovery<P> {
+    /// Search every pool of a factory.
+    pub fn new(provider: Arc<P>, factory_id: ZkAssetId) -> Self {
+        let factory = FactoryClient::new(provider.clone(), factory_id);
+        Self { provider, factory, asset_id: None }
+    }
+
+    /// Only search the pools of an asset.
+    pub fn with_asset(mut self, asset_id: ZkAssetId) -> Self {
+        self.asset_id = Some(asset_id);
+        self
+    }
+
+    /// Find the deposit of a secret and nullifier and rebuild its note.
+    ///
+    /// The commitment is recomputed for each pool's asset and denomination
+    /// and looked up among the pool's leaves, newest pools first. Empty pools
+    /// are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The recovered note, or `None` if no pool holds its commitment.
+    pub async fn recover(&self, secret: &Secret, nullifier: &Nullifier) -> ZKaneResult<Option<RecoveredNote>> {
+        let pools = match &self.asset_id {
+            Some(asset_id) => self.factory.asset_pools(asset_id).await?,
+            None => self.factory.pools().await?,
+        };
+
+        // Pool generations share an asset and denomination, and so a commitment
+        let mut commitments: HashMap<(ZkAssetId, u128), Commitment> = HashMap::new();
+        for record in pools.iter().rev().filter(|record| record.deposit_count > 0) {
+            let key = (record.asset_id, record.denomination);
+            let commitment = match commitments.entry(key) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    *entry.insert(generate_asset_commitment(nullifier, secret, &record.asset_id, record.denomination)?)
+                }
+            };
+
+            let client = PoolClient::new(self.provider.clone(), record.pool_id);
+            let Some(leaf_index) = client.find_commitment(&commitment).await? else {
+                continue;
+            };
+            let note = DepositNote::new(
+                secret.clone(),
+                nullifier.clone(),
+                commitment,
+                record.asset_id,
+                record.denomination,
+                leaf_index,
+            );
+            return Ok(Some(RecoveredNote {
+                spent: client.is_spent(&generate_nullifier_hash(nullifier)?).await?,
+                anonymity_set: client.deposit_count().await?,
+                pool_id: record.pool_id,
+                note,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use crate::pool_client::COMMITMENT_PAGE_SIZE;
+    use zkane_common::PoolRecord;
+
+    const FACTORY: &str = "4:1";
+    const ASSET: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+    fn record(denomination: u128, pool_tx: u128, deposit_count: u128) -> PoolRecord {
+        PoolRecord {
+            asset_id: ASSET,
+            denomination,
+            pool_id: ZkAssetId { block: 6, tx: pool_tx },
+            deposit_count,
+            created_block: 100,
+            template_version: 0,
+            circuit_version: 0,
+        }
+    }
+
+    fn setup(records: &[PoolRecord]) -> (MockProvider, NoteRecovery<MockProvider>) {
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let mut page = (records.len() as u128).to_le_bytes().to_vec();
+        records.iter().for_each(|record| page.extend_from_slice(&record.to_bytes()));
+        provider.add_simulation_data(FACTORY, "6,0,100", &page);
+        let recovery = NoteRecovery::new(Arc::new(provider.clone()), ZkAssetId { block: 4, tx: 1 });
+        (provider, recovery)
+    }
+
+    #[tokio::test]
+    async fn test_recover_note() {
+        // The empty pool has nothing scripted, so querying it would fail
+        let (provider, recovery) = setup(&[record(1000, 10, 2), record(5000, 11, 3), record(1000, 12, 0)]);
+        let original = crate::generate_deposit_note(ASSET, 5000).unwrap();
+        let leaves = |commitments: &[[u8; 32]]| commitments.concat();
+        provider.add_simulation_data("6:10", &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &leaves(&[[1u8; 32], [2u8; 32]]));
+        provider.add_simulation_data(
+            "6:11",
+            &format!("13,0,{}", COMMITMENT_PAGE_SIZE),
+            &leaves(&[[3u8; 32], [4u8; 32], original.commitment.0]),
+        );
+        let (low, high) = generate_nullifier_hash(&original.nullifier).unwrap().to_u128_pair();
+        provider.add_simulation_data("6:11", &format!("17,{},{}", low, high), &1u128.to_le_bytes());
+        provider.add_simulation_data("6:11", "11", &3u128.to_le_bytes());
+
+        let recovered = recovery.recover(&original.secret, &original.nullifier).await.unwrap().unwrap();
+        assert_eq!(recovered.pool_id, ZkAssetId { block: 6, tx: 11 });
+        assert_eq!(recovered.note.commitment, original.commitment);
+        assert_eq!(recovered.note.denomination, 5000);
+        assert_eq!(recovered.note.leaf_index, 2);
+        assert!(recovered.spent);
+        assert_eq!(recovered.anonymity_set, 3);
+    }
+
+    #[tokio::test]
+    async fn test_recover_missing_note() {
+        let (provider, recovery) = setup(&[record(1000, 10, 1)]);
+        provider.add_simulation_data("6:10", &format!("13,0,{}", COMMITMENT_PAGE_SIZE), &[1u8; 32]);
+
+        let note = crate::generate_deposit_note(ASSET, 1000).unwrap();
+        assert!(recovery.recover(&note.secret, &note.nullifier).await.unwrap().is_none());
+        // No pool of another asset is searched
+        let recovery = recovery.with_asset(ZkAssetId { block: 2, tx: 9 });
+        assert!(recovery.recover(&note.secret, &note.nullifier).await.unwrap().is_none());
+    }
+}
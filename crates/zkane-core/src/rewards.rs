@@ -0,0 +1,125 @@
+//! # Reward Claims
+//!
+//! Lays out the two transactions claiming the reward points of a withdrawn
+//! note from a pool with a [`RewardProgram`](zkane_common::RewardProgram).
+//! The registration carries the [`reward_claim_hash`] of the note's nullifier
+//! and the claim's outputs; the claim, confirmed in a later block, reveals
+//! the nullifier and is paid the points in its first output.
+//!
+//! The claim hash covers every output of the claim transaction, the runestone
+//! last, so the outputs passed to [`plan_reward_claim`] must be exactly those
+//! the claim is sent with, change included.
+//!
+//! ```rust
+//! use bitcoin::{Amount, ScriptBuf, TxOut};
+//! use zkane_common::{Nullifier, ZkAssetId};
+//! use zkane_core::plan_reward_claim;
+//!
+//! let outputs = vec![TxOut { value: Amount::from_sat(546), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) }];
+//! let plan = plan_reward_claim(ZkAssetId { block: 2, tx: 1 }, &Nullifier::new([1u8; 32]), outputs)?;
+//! assert_eq!(plan.claim_outputs().len(), 2);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::withdrawal::call_protostone;
+use bitcoin::{Amount, ScriptBuf, TxOut};
+use zkane_abi::{pool, split_hash};
+use zkane_common::{calculate_outputs_hash, reward_claim_hash, Nullifier, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// A reward claim laid out without a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewardClaimPlan {
+    /// The pool paying the rewards
+    pub pool_id: ZkAssetId,
+    /// Hash registered ahead of the claim
+    pub claim_hash: [u8; 32],
+    /// Runestone of the registration transaction, calling the pool's
+    /// `RegisterRewardClaim` opcode
+    pub register_runestone: ScriptBuf,
+    /// Runestone of the claim transaction, calling the pool's `ClaimRewards`
+    /// opcode
+    pub claim_runestone: ScriptBuf,
+    /// Outputs of the claim transaction before its runestone, the first
+    /// receiving the points
+    pub outputs: Vec<TxOut>,
+}
+
+impl RewardClaimPlan {
+    /// Get the outputs of the claim transaction, its runestone last.
+    pub fn claim_outputs(&self) -> Vec<TxOut> {
+        let mut outputs = self.outputs.clone();
+        outputs.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: self.claim_runestone.clone(),
+        });
+        outputs
+    }
+}
+
+/// Lay out the claim of a withdrawn note's reward points from `pool_id`.
+///
+/// `outputs` are the outputs of the claim transaction before its runestone;
+/// the first receives the reward alkanes.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::TransactionBuildFailed`] if no outputs are given.
+pub fn plan_reward_claim(pool_id: ZkAssetId, nullifier: &Nullifier, outputs: Vec<TxOut>) -> ZKaneResult<RewardClaimPlan> {
+    if outputs.is_empty() {
+        return Err(ZKaneError::TransactionBuildFailed(
+            "a reward claim needs an output to pay".to_string(),
+        ));
+    }
+
+    let [nullifier_low, nullifier_high] = split_hash(nullifier.as_bytes());
+    let claim_runestone = call_protostone(&pool_id, vec![pool::CLAIM_REWARDS, nullifier_low, nullifier_high], 0, None)?;
+
+    let mut plan = RewardClaimPlan {
+        pool_id,
+        claim_hash: [0u8; 32],
+        register_runestone: ScriptBuf::new(),
+        claim_runestone,
+        outputs,
+    };
+    plan.claim_hash = reward_claim_hash(nullifier, &calculate_outputs_hash(&plan.claim_outputs()));
+
+    let [claim_low, claim_high] = split_hash(&plan.claim_hash);
+    plan.register_runestone = call_protostone(&pool_id, vec![pool::REGISTER_REWARD_CLAIM, claim_low, claim_high], 0, None)?;
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(value: u64) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+        }
+    }
+
+    #[test]
+    fn test_plan_reward_claim() {
+        let pool_id = ZkAssetId { block: 2, tx: 7 };
+        let nullifier = Nullifier::new([9u8; 32]);
+        let plan = plan_reward_claim(pool_id, &nullifier, vec![output(546), output(10_000)]).unwrap();
+
+        let claim_outputs = plan.claim_outputs();
+        assert_eq!(claim_outputs.len(), 3);
+        assert_eq!(claim_outputs[2].script_pubkey, plan.claim_runestone);
+        assert_eq!(
+            plan.claim_hash,
+            reward_claim_hash(&nullifier, &calculate_outputs_hash(&claim_outputs))
+        );
+        assert!(plan.register_runestone.is_op_return());
+        assert!(plan.claim_runestone.is_op_return());
+
+        // The hash binds the outputs paid
+        let other = plan_reward_claim(pool_id, &nullifier, vec![output(547), output(10_000)]).unwrap();
+        assert_ne!(other.claim_hash, plan.claim_hash);
+        assert_eq!(other.claim_runestone, plan.claim_runestone);
+
+        assert!(plan_reward_claim(pool_id, &nullifier, vec![]).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! # Transaction Signing
+//!
+//! Deposits and withdrawals are assembled as unsigned PSBTs, handed to a
+//! [`TxSigner`], finalized and broadcast through the provider. The signer is a
+//! trait so hardware wallets and other external signers can take the place of
+//! the provider's own wallet, which [`ProviderSigner`] wraps.
+//!
+//! ```rust,no_run
+//! use zkane_core::mock_provider::MockProvider;
+//! use zkane_core::signer::{sign_and_broadcast, ProviderSigner};
+//! use std::sync::Arc;
+//!
+//! # async fn example(psbt: bitcoin::psbt::Psbt) -> zkane_common::ZKaneResult<()> {
+//! let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+//! let signer = ProviderSigner::new(provider.clone());
+//! let txid = sign_and_broadcast(provider.as_ref(), &signer, psbt).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+#[cfg(feature = "deezel")]
+use bitcoin::consensus::encode::deserialize_hex;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::psbt::Psbt;
+use bitcoin::{ScriptBuf, Transaction, Witness};
+use crate::provider::PoolProvider;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::DeezelProvider;
+#[cfg(feature = "deezel")]
+use std::sync::Arc;
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// Signs the inputs of a PSBT it controls.
+#[async_trait(?Send)]
+pub trait TxSigner {
+    /// Sign a PSBT.
+    ///
+    /// Signatures may be added as partial signatures, to be finalized by
+    /// [`finalize_psbt`], or as final witnesses.
+    async fn sign_psbt(&self, psbt: Psbt) -> ZKaneResult<Psbt>;
+}
+
+/// Signs with the provider's wallet.
+///
+/// A PSBT without inputs, such as a relayed withdrawal, is handed to the
+/// wallet as a raw transaction instead, so the wallet funds it before
+/// signing.
+#[cfg(feature = "deezel")]
+pub struct ProviderSigner<P: DeezelProvider> {
+    provider: Arc<P>,
+}
+
+#[cfg(feature = "deezel")]
+impl<P: DeezelProvider> ProviderSigner<P> {
+    /// Create a signer for the provider's wallet.
+    pub fn new(provider: Arc<P>) -> Self {
+        Self { provider }
+    }
+}
+
+#[cfg(feature = "deezel")]
+#[async_trait(?Send)]
+impl<P: DeezelProvider> TxSigner for ProviderSigner<P> {
+    async fn sign_psbt(&self, psbt: Psbt) -> ZKaneResult<Psbt> {
+        if psbt.unsigned_tx.input.is_empty() {
+            let signed = self.provider.sign_transaction(serialize_hex(&psbt.unsigned_tx)).await?;
+            let tx: Transaction = deserialize_hex(&signed)
+                .map_err(|e| ZKaneError::TransactionBuildFailed(format!("invalid signed transaction: {}", e)))?;
+            return psbt_from_signed(tx);
+        }
+        Ok(self.provider.sign_psbt(&psbt).await?)
+    }
+}
+
+/// Turn a signed transaction back into a finalized PSBT.
+pub fn psbt_from_signed(mut tx: Transaction) -> ZKaneResult<Psbt> {
+    let finals: Vec<(ScriptBuf, Witness)> = tx
+        .input
+        .iter_mut()
+        .map(|input| (std::mem::take(&mut input.script_sig), std::mem::take(&mut input.witness)))
+        .collect();
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| ZKaneError::TransactionBuildFailed(e.to_string()))?;
+    for (input, (script_sig, witness)) in psbt.inputs.iter_mut().zip(finals) {
+        input.final_script_sig = (!script_sig.is_empty()).then_some(script_sig);
+        input.final_script_witness = (!witness.is_empty()).then_some(witness);
+    }
+    Ok(psbt)
+}
+
+/// Finalize a signed PSBT and extract the transaction.
+///
+/// Inputs that already have a final witness are kept as they are. Otherwise
+/// the witness is built from a taproot key-path signature, a single taproot
+/// script-path signature, such as the reveal of a witness envelope, or a
+/// single segwit v0 signature.
+///
+/// # Errors
+///
+/// Returns an error if an input is not signed or can't be finalized.
+pub fn finalize_psbt(mut psbt: Psbt) -> ZKaneResult<Transaction> {
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        if input.final_script_witness.is_some() || input.final_script_sig.is_some() {
+            continue;
+        }
+
+        let witness = if let Some(signature) = input.tap_key_sig {
+            Witness::p2tr_key_spend(&signature)
+        } else if let ([(_, signature)], [(control_block, (script, _))]) = (
+            input.tap_script_sigs.iter().collect::<Vec<_>>().as_slice(),
+            input.tap_scripts.iter().collect::<Vec<_>>().as_slice(),
+        ) {
+            Witness::from_slice(&[signature.to_vec(), script.to_bytes(), control_block.serialize()])
+        } else if let [(public_key, signature)] = input.partial_sigs.iter().collect::<Vec<_>>().as_slice() {
+            Witness::p2wpkh(signature, &public_key.inner)
+        } else {
+            return Err(ZKaneError::TransactionBuildFailed(format!(
+                "input {} can't be finalized",
+                index
+            )));
+        };
+
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.tap_key_sig = None;
+        input.tap_script_sigs.clear();
+        input.tap_scripts.clear();
+        input.tap_key_origins.clear();
+        input.bip32_derivation.clear();
+    }
+
+    // Relayed withdrawals are funded by the signer, so the PSBT may not know
+    // the spent outputs needed to check the fee rate
+    Ok(psbt.extract_tx_unchecked_fee_rate())
+}
+
+/// Sign a PSBT, finalize it and broadcast the transaction.
+///
+/// # Returns
+///
+/// The txid of the broadcast transaction.
+pub async fn sign_and_broadcast<P: PoolProvider + ?Sized, S: TxSigner + ?Sized>(
+    provider: &P,
+    signer: &S,
+    psbt: Psbt,
+) -> ZKaneResult<String> {
+    let signed = signer.sign_psbt(psbt).await?;
+    let tx = finalize_psbt(signed)?;
+    provider.broadcast(&serialize_hex(&tx)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::{MockFailure, MockProvider};
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+    use bitcoin::transaction::Version;
+    use bitcoin::{taproot, Amount, OutPoint, Sequence, TxIn, TxOut, Txid};
+
+    fn unsigned_tx(inputs: u32) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: (0..inputs)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint::new(Txid::all_zeros(), vout),
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+            }],
+        }
+    }
+
+    fn signature() -> taproot::Signature {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest([2u8; 32]), &keypair);
+        taproot::Signature::from_slice(signature.as_ref()).unwrap()
+    }
+
+    /// Signs every input with a taproot key-path signature
+    struct KeySpendSigner;
+
+    #[async_trait(?Send)]
+    impl TxSigner for KeySpendSigner {
+        async fn sign_psbt(&self, mut psbt: Psbt) -> ZKaneResult<Psbt> {
+            for input in psbt.inputs.iter_mut() {
+                input.tap_key_sig = Some(signature());
+            }
+            Ok(psbt)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_external_signer() {
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx(2)).unwrap();
+        let tx = finalize_psbt(KeySpendSigner.sign_psbt(psbt).await.unwrap()).unwrap();
+
+        assert_eq!(tx.input.len(), 2);
+        assert!(tx.input.iter().all(|input| input.witness.len() == 1));
+        assert_eq!(tx.input[0].witness.nth(0).unwrap(), signature().to_vec().as_slice());
+    }
+
+    #[test]
+    fn test_finalize_unsigned_input() {
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx(1)).unwrap();
+        assert!(matches!(finalize_psbt(psbt), Err(ZKaneError::TransactionBuildFailed(_))));
+
+        // Nothing to finalize without inputs
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx(0)).unwrap();
+        assert_eq!(finalize_psbt(psbt).unwrap(), unsigned_tx(0));
+    }
+
+    #[test]
+    fn test_psbt_from_signed() {
+        let mut tx = unsigned_tx(1);
+        tx.input[0].witness = Witness::from_slice(&[vec![1u8; 64]]);
+
+        let psbt = psbt_from_signed(tx.clone()).unwrap();
+        assert_eq!(psbt.unsigned_tx, unsigned_tx(1));
+        assert_eq!(psbt.inputs[0].final_script_sig, None);
+        assert_eq!(finalize_psbt(psbt).unwrap(), tx);
+    }
+
+    #[tokio::test]
+    async fn test_provider_signer_broadcast() {
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        let signer = ProviderSigner::new(provider.clone());
+
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx(0)).unwrap();
+        let txid = sign_and_broadcast(provider.as_ref(), &signer, psbt).await.unwrap();
+        assert_eq!(txid, unsigned_tx(0).compute_txid().to_string());
+        assert_eq!(provider.broadcasts(), vec![serialize_hex(&unsigned_tx(0))]);
+    }
+
+    #[tokio::test]
+    async fn test_provider_signer_errors() {
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        provider.inject_failure("sign_transaction", MockFailure::Error("locked".to_string()));
+        let signer = ProviderSigner::new(provider.clone());
+
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx(0)).unwrap();
+        assert!(matches!(
+            sign_and_broadcast(provider.as_ref(), &signer, psbt).await,
+            Err(ZKaneError::DeezelError(_))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+//! # Withdrawal Simulation
+//!
+//! A dry run of a withdrawal, reporting every check the pool contract would
+//! make and which of them would fail, instead of the single bool of
+//! [`PrivacyPool::verify_withdrawal_proof`]. Wallets and relayers run it
+//! before paying to broadcast, so a bad withdrawal is rejected with the step
+//! and error code to fix.
+//!
+//! [`PrivacyPool::simulate_withdrawal`] checks against a locally synced
+//! pool, [`PoolClient::simulate_withdrawal`] against the contract's state.
+//! Both build on [`simulate_withdrawal`].
+//!
+//! ```rust
+//! use zkane_core::{mock_provider::MockProvider, PrivacyPool, SimulationStep};
+//! use zkane_common::{NullifierHash, Recipient, WithdrawalProof, ZKaneConfig, ZkAssetId};
+//! use std::sync::Arc;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]);
+//! let pool = PrivacyPool::new(config, Arc::new(MockProvider::new(bitcoin::Network::Regtest)))?;
+//!
+//! let proof = WithdrawalProof::new(vec![1; 192], [9u8; 32], NullifierHash::new([3u8; 32]), Recipient::default());
+//! let report = pool.simulate_withdrawal(&proof);
+//! assert!(!report.would_succeed());
+//! assert_eq!(report.first_failure().unwrap().step, SimulationStep::MerkleRoot);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`PrivacyPool::verify_withdrawal_proof`]: crate::PrivacyPool::verify_withdrawal_proof
+//! [`PrivacyPool::simulate_withdrawal`]: crate::PrivacyPool::simulate_withdrawal
+//! [`PoolClient::simulate_withdrawal`]: crate::PoolClient::simulate_withdrawal
+
+use bitcoin::TxOut;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zkane_common::{calculate_outputs_hash, WithdrawalProof, ZKaneConfig, ZKaneError, ZKaneResult};
+use zkane_crypto::zkp::verify_withdrawal;
+
+/// A check made on a withdrawal, in the order the contract makes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationStep {
+    /// The pool takes whole-note withdrawals
+    PoolMode,
+    /// The proof is for the pool's circuit version
+    CircuitVersion,
+    /// The transaction pays the proof's recipients and relayer
+    Outputs,
+    /// The relayer fee fits in the denomination after the protocol fee
+    Fee,
+    /// The nullifier hasn't been spent
+    Nullifier,
+    /// The proof is against the pool's current Merkle root
+    MerkleRoot,
+    /// The zero-knowledge proof verifies against its public inputs
+    Proof,
+}
+
+impl fmt::Display for SimulationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SimulationStep::PoolMode => "pool mode",
+            SimulationStep::CircuitVersion => "circuit version",
+            SimulationStep::Outputs => "outputs",
+            SimulationStep::Fee => "fee",
+            SimulationStep::Nullifier => "nullifier",
+            SimulationStep::MerkleRoot => "merkle root",
+            SimulationStep::Proof => "proof",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The outcome of one check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum CheckOutcome {
+    /// The check passed
+    Passed,
+    /// The check couldn't be made, e.g. without the transaction outputs
+    Skipped {
+        /// Why the check wasn't made
+        reason: String,
+    },
+    /// The contract would revert at this check
+    Failed {
+        /// The [`ZKaneError::code`] the contract reverts with, if it has one
+        code: Option<u16>,
+        /// What is wrong
+        reason: String,
+    },
+}
+
+impl CheckOutcome {
+    fn failed(error: ZKaneError) -> Self {
+        CheckOutcome::Failed {
+            code: Some(error.code()),
+            reason: error.to_string(),
+        }
+    }
+
+    fn skipped(reason: &str) -> Self {
+        CheckOutcome::Skipped {
+            reason: reason.to_string(),
+        }
+    }
+
+    fn from_result(result: ZKaneResult<()>) -> Self {
+        result.map_or_else(Self::failed, |_| CheckOutcome::Passed)
+    }
+}
+
+/// One check of a simulation and its outcome.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationCheck {
+    pub step: SimulationStep,
+    #[serde(flatten)]
+    pub outcome: CheckOutcome,
+}
+
+/// Every check of a simulated withdrawal, in the contract's order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub checks: Vec<SimulationCheck>,
+}
+
+impl SimulationReport {
+    /// Check whether no check failed.
+    ///
+    /// Skipped checks don't count, so a withdrawal simulated without its
+    /// outputs or a verifier key may still fail on chain.
+    pub fn would_succeed(&self) -> bool {
+        self.first_failure().is_none()
+    }
+
+    /// Get the first failed check, the one the contract would revert at.
+    pub fn first_failure(&self) -> Option<&SimulationCheck> {
+        self.checks
+            .iter()
+            .find(|check| matches!(check.outcome, CheckOutcome::Failed { .. }))
+    }
+
+    /// Get the outcome of a step.
+    pub fn outcome(&self, step: SimulationStep) -> Option<&CheckOutcome> {
+        self.checks.iter().find(|check| check.step == step).map(|check| &check.outcome)
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let step = check.step.to_string();
+            match &check.outcome {
+                CheckOutcome::Passed => writeln!(f, "{:<16} passed", step)?,
+                CheckOutcome::Skipped { reason } => writeln!(f, "{:<16} skipped: {}", step, reason)?,
+                CheckOutcome::Failed { code: Some(code), reason } => {
+                    writeln!(f, "{:<16} FAILED: ZK{}: {}", step, code, reason)?
+                }
+                CheckOutcome::Failed { code: None, reason } => writeln!(f, "{:<16} FAILED: {}", step, reason)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The pool state a withdrawal is simulated against.
+#[derive(Debug, Clone)]
+pub struct PoolState<'a> {
+    /// Configuration of the pool
+    pub config: &'a ZKaneConfig,
+    /// Current Merkle root of the pool
+    pub merkle_root: [u8; 32],
+    /// Whether the proof's root is one of the pool's past roots
+    pub past_root: bool,
+    /// Whether the proof's nullifier hash has been spent
+    pub nullifier_spent: bool,
+}
+
+/// Run every check of a withdrawal against a pool's state.
+///
+/// Without `outputs`, the transaction outputs aren't checked. Without a
+/// verifier key in the config, neither is the proof.
+pub fn simulate_withdrawal(state: &PoolState, proof: &WithdrawalProof, outputs: Option<&[TxOut]>) -> SimulationReport {
+    let config = state.config;
+    let mut checks = Vec::new();
+    let mut check = |step, outcome| checks.push(SimulationCheck { step, outcome });
+
+    check(
+        SimulationStep::PoolMode,
+        if config.is_variable() {
+            CheckOutcome::failed(ZKaneError::WrongPoolMode(config.mode))
+        } else {
+            CheckOutcome::Passed
+        },
+    );
+    check(
+        SimulationStep::CircuitVersion,
+        CheckOutcome::from_result(config.check_circuit_version(proof)),
+    );
+    check(SimulationStep::Outputs, check_outputs(proof, outputs));
+    check(
+        SimulationStep::Fee,
+        if config.is_variable() {
+            CheckOutcome::skipped("variable pools take split withdrawals")
+        } else {
+            CheckOutcome::from_result(
+                proof
+                    .validate_fee(config.denomination)
+                    .and_then(|_| config.withdrawal_amounts(proof.fee).map(drop)),
+            )
+        },
+    );
+    check(
+        SimulationStep::Nullifier,
+        if state.nullifier_spent {
+            CheckOutcome::failed(ZKaneError::NullifierAlreadySpent)
+        } else {
+            CheckOutcome::Passed
+        },
+    );
+    check(
+        SimulationStep::MerkleRoot,
+        if proof.merkle_root == state.merkle_root {
+            CheckOutcome::Passed
+        } else if state.past_root {
+            CheckOutcome::Failed {
+                code: Some(ZKaneError::InvalidMerkleRoot.code()),
+                reason: "proof is against a past root; regenerate it against the current root".to_string(),
+            }
+        } else {
+            CheckOutcome::failed(ZKaneError::InvalidMerkleRoot)
+        },
+    );
+    check(SimulationStep::Proof, check_proof(config, proof));
+
+    SimulationReport { checks }
+}
+
+/// Check that the outputs pay the proof's recipients and relayer.
+fn check_outputs(proof: &WithdrawalProof, outputs: Option<&[TxOut]>) -> CheckOutcome {
+    if !proof.has_recipients() && !proof.is_relayed() && proof.recipient.is_empty() {
+        return CheckOutcome::Passed;
+    }
+    let Some(outputs) = outputs else {
+        return CheckOutcome::skipped("no transaction outputs given");
+    };
+    if let Err(err) = proof.validate_recipient(outputs) {
+        return CheckOutcome::failed(err);
+    }
+    let pays_relayer = outputs
+        .iter()
+        .any(|output| calculate_outputs_hash(std::slice::from_ref(output)) == proof.relayer_output_hash);
+    if proof.is_relayed() && !pays_relayer {
+        return CheckOutcome::Failed {
+            code: None,
+            reason: "relayer output not found in transaction".to_string(),
+        };
+    }
+    CheckOutcome::Passed
+}
+
+/// Verify the proof against the pool's verifier key.
+fn check_proof(config: &ZKaneConfig, proof: &WithdrawalProof) -> CheckOutcome {
+    if proof.proof.is_empty() {
+        return CheckOutcome::failed(ZKaneError::InvalidProof("empty proof".to_string()));
+    }
+    if config.verifier_key.is_empty() {
+        return CheckOutcome::skipped("pool has no verifier key");
+    }
+    match verify_withdrawal(&config.verifier_key, proof, &config.asset_id, config.denomination) {
+        Ok(true) => CheckOutcome::Passed,
+        Ok(false) => CheckOutcome::failed(ZKaneError::InvalidProof(
+            "proof doesn't verify against its public inputs".to_string(),
+        )),
+        Err(e) => CheckOutcome::failed(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Amount, ScriptBuf};
+    use zkane_common::{NullifierHash, PoolMode, Recipient, ZkAssetId};
+    use zkane_crypto::zkp::split::circuit_nullifier_hash;
+    use zkane_crypto::zkp::{proof_to_bytes, prove, setup, verifying_key_to_bytes, WithdrawalCircuit};
+
+    fn config() -> ZKaneConfig {
+        ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000, 20, vec![])
+    }
+
+    fn state(config: &ZKaneConfig) -> PoolState<'_> {
+        PoolState {
+            config,
+            merkle_root: [7u8; 32],
+            past_root: false,
+            nullifier_spent: false,
+        }
+    }
+
+    fn output(byte: u8) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(546),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51, byte]),
+        }
+    }
+
+    #[test]
+    fn test_simulate_withdrawal_steps() {
+        let config = config();
+        let proof = WithdrawalProof::new(vec![1; 8], [7u8; 32], NullifierHash::new([3u8; 32]), Recipient::default())
+            .with_recipients(&[output(1)])
+            .with_relayer(calculate_outputs_hash(&[output(2)]), 10);
+
+        let report = simulate_withdrawal(&state(&config), &proof, Some(&[output(1), output(2)]));
+        assert!(report.would_succeed());
+        assert_eq!(report.checks.len(), 7);
+        assert!(matches!(report.outcome(SimulationStep::Proof), Some(CheckOutcome::Skipped { .. })));
+
+        // Without outputs they are skipped, not failed
+        let report = simulate_withdrawal(&state(&config), &proof, None);
+        assert!(matches!(report.outcome(SimulationStep::Outputs), Some(CheckOutcome::Skipped { .. })));
+
+        // Every failure is reported, the first being the contract's revert
+        let spent = PoolState { nullifier_spent: true, merkle_root: [8u8; 32], past_root: true, ..state(&config) };
+        let report = simulate_withdrawal(&spent, &WithdrawalProof { fee: 2000, ..proof.clone() }, Some(&[output(2)]));
+        let failed: Vec<_> = report
+            .checks
+            .iter()
+            .filter(|check| matches!(check.outcome, CheckOutcome::Failed { .. }))
+            .map(|check| check.step)
+            .collect();
+        assert_eq!(
+            failed,
+            vec![SimulationStep::Outputs, SimulationStep::Fee, SimulationStep::Nullifier, SimulationStep::MerkleRoot]
+        );
+        assert_eq!(report.first_failure().unwrap().step, SimulationStep::Outputs);
+        assert!(matches!(
+            report.outcome(SimulationStep::Nullifier),
+            Some(CheckOutcome::Failed { code: Some(2002), .. })
+        ));
+        assert!(report.to_string().contains("merkle root      FAILED: ZK3001: proof is against a past root"));
+
+        let variable = ZKaneConfig { mode: PoolMode::Variable, ..config.clone() };
+        let report = simulate_withdrawal(&state(&variable), &proof, None);
+        assert_eq!(report.first_failure().unwrap().step, SimulationStep::PoolMode);
+        assert!(matches!(report.outcome(SimulationStep::Fee), Some(CheckOutcome::Skipped { .. })));
+    }
+
+    #[test]
+    fn test_simulate_withdrawal_verifies_proof() {
+        let (pk, vk) = setup();
+        let config = config().with_verifier_key(1, verifying_key_to_bytes(&vk).unwrap());
+        let circuit =
+            WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &config.asset_id, 1000, &[0u8; 32], &[0u8; 32], 0, 0)
+                .unwrap();
+        let proof = WithdrawalProof::new(
+            proof_to_bytes(&prove(&pk, circuit)).unwrap(),
+            [7u8; 32],
+            NullifierHash::new(circuit_nullifier_hash(&[2u8; 32]).unwrap()),
+            Recipient::default(),
+        );
+
+        assert!(simulate_withdrawal(&state(&config), &proof, None).would_succeed());
+
+        let forged = WithdrawalProof { nullifier_hash: NullifierHash::new([3u8; 32]), ..proof };
+        let report = simulate_withdrawal(&state(&config), &forged, None);
+        assert_eq!(report.first_failure().unwrap().step, SimulationStep::Proof);
+        assert!(matches!(report.outcome(SimulationStep::Proof), Some(CheckOutcome::Failed { code: Some(2001), .. })));
+    }
+}
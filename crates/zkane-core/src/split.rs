@@ -0,0 +1,187 @@
+//! # Split Withdrawals
+//!
+//! Plans the withdrawal of part of a note: the public amount leaves the pool
+//! and the rest is kept in fresh change notes, deposited back in the same
+//! transaction. [`plan_split`] creates the change notes and the circuit to
+//! prove; the proof goes into a [`SplitWitness`] the pool contract checks.
+//!
+//! Split notes are committed with the circuit's Poseidon, so only notes
+//! created with [`generate_circuit_note`], or as change of an earlier split,
+//! can be split.
+//!
+//! ```rust
+//! use zkane_core::{generate_circuit_note, plan_split};
+//! use zkane_common::ZkAssetId;
+//!
+//! let note = generate_circuit_note(ZkAssetId { block: 2, tx: 1 }, 1000)?;
+//! let plan = plan_split(&note, 400, &[600], ([0u8; 32], 0))?;
+//! assert_eq!(plan.change[0].denomination, 600);
+//! assert_eq!(plan.output_commitments.len(), 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use zkane_common::{
+    Commitment, DepositNote, Nullifier, NullifierHash, Secret, ZKaneError, ZKaneResult, ZkAssetId,
+    SPLIT_OUTPUTS,
+};
+#[cfg(doc)]
+use zkane_common::SplitWitness;
+use zkane_crypto::zkp::receipt::circuit_commitment;
+use zkane_crypto::zkp::split::{circuit_nullifier_hash, SplitCircuit, SplitOutputNote};
+
+/// A planned split withdrawal.
+#[derive(Clone)]
+pub struct SplitPlan {
+    /// The circuit to prove
+    pub circuit: SplitCircuit,
+    /// The change notes to keep, one per non-zero change amount
+    pub change: Vec<DepositNote>,
+    /// The fresh commitments, including zero-value padding
+    pub output_commitments: Vec<Commitment>,
+    /// The spent note's nullifier hash, as the circuit derives it
+    pub nullifier_hash: NullifierHash,
+    /// Amount paid out of the pool, including the relayer fee
+    pub public_amount: u128,
+}
+
+/// Generate a note committed with the circuit's Poseidon, so it can be split.
+///
+/// The note's denomination is its value.
+pub fn generate_circuit_note(asset_id: ZkAssetId, amount: u128) -> ZKaneResult<DepositNote> {
+    let secret = Secret::random();
+    let nullifier = Nullifier::random();
+    let commitment = circuit_commitment(nullifier.as_bytes(), secret.as_bytes(), &asset_id, amount)?;
+    Ok(DepositNote::new(secret, nullifier, Commitment::new(commitment), asset_id, amount, 0))
+}
+
+/// Plan the split of a note into a public amount and change notes.
+///
+/// `relayer` is the relayer output hash and fee, paid out of the public
+/// amount. Up to [`SPLIT_OUTPUTS`] change notes are created; missing outputs
+/// are padded with zero-value notes.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidCommitment`] if the note isn't committed with
+/// the circuit's Poseidon, [`ZKaneError::InvalidDenomination`] if there are
+/// too many change amounts or the amounts don't add up to the note's value,
+/// and [`ZKaneError::InvalidFee`] if the fee exceeds the public amount.
+pub fn plan_split(
+    note: &DepositNote,
+    public_amount: u128,
+    change: &[u128],
+    relayer: ([u8; 32], u128),
+) -> ZKaneResult<SplitPlan> {
+    let (secret, nullifier) = (note.secret.as_bytes(), note.nullifier.as_bytes());
+    if circuit_commitment(nullifier, secret, &note.asset_id, note.denomination)? != *note.commitment.as_bytes() {
+        return Err(ZKaneError::InvalidCommitment(
+            "note is not committed with the circuit's Poseidon".to_string(),
+        ));
+    }
+    let total = change
+        .iter()
+        .try_fold(public_amount, |total, amount| total.checked_add(*amount));
+    if change.len() > SPLIT_OUTPUTS || total != Some(note.denomination) {
+        return Err(ZKaneError::InvalidDenomination);
+    }
+    if relayer.1 > public_amount {
+        return Err(ZKaneError::InvalidFee(format!(
+            "fee {} exceeds the public amount {}",
+            relayer.1, public_amount
+        )));
+    }
+
+    let mut notes = Vec::with_capacity(SPLIT_OUTPUTS);
+    let mut outputs = [SplitOutputNote { secret: [0u8; 32], nullifier: [0u8; 32], amount: 0 }; SPLIT_OUTPUTS];
+    for (index, output) in outputs.iter_mut().enumerate() {
+        let note = generate_circuit_note(note.asset_id, change.get(index).copied().unwrap_or(0))?;
+        *output = SplitOutputNote {
+            secret: *note.secret.as_bytes(),
+            nullifier: *note.nullifier.as_bytes(),
+            amount: note.denomination,
+        };
+        notes.push(note);
+    }
+
+    Ok(SplitPlan {
+        circuit: SplitCircuit::from_notes(
+            (secret, nullifier, note.denomination),
+            &note.asset_id,
+            public_amount,
+            (&relayer.0, relayer.1),
+            &outputs,
+        )?,
+        output_commitments: notes.iter().map(|note| note.commitment).collect(),
+        change: notes.into_iter().take(change.len()).collect(),
+        nullifier_hash: NullifierHash::new(circuit_nullifier_hash(nullifier)?),
+        public_amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use crate::PrivacyPool;
+    use std::sync::Arc;
+    use zkane_common::{MerklePath, Recipient, SplitWitness, WithdrawalProof, WithdrawalWitness, ZKaneConfig};
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+    #[test]
+    fn test_plan_split() {
+        let note = generate_circuit_note(ASSET_ID, 1000).unwrap();
+        let plan = plan_split(&note, 400, &[500, 100], ([7u8; 32], 10)).unwrap();
+        assert_eq!(plan.change.iter().map(|note| note.denomination).collect::<Vec<_>>(), vec![500, 100]);
+        assert_eq!(plan.output_commitments[0], plan.change[0].commitment);
+
+        // Unused outputs are padded with zero-value notes
+        let plan = plan_split(&note, 1000, &[], ([0u8; 32], 0)).unwrap();
+        assert!(plan.change.is_empty());
+        assert_eq!(plan.output_commitments.len(), SPLIT_OUTPUTS);
+        assert_ne!(plan.output_commitments[0], plan.output_commitments[1]);
+
+        assert!(matches!(plan_split(&note, 400, &[500], ([0u8; 32], 0)), Err(ZKaneError::InvalidDenomination)));
+        assert!(matches!(
+            plan_split(&note, 400, &[200, 200, 200], ([0u8; 32], 0)),
+            Err(ZKaneError::InvalidDenomination)
+        ));
+        assert!(matches!(plan_split(&note, 5, &[995], ([0u8; 32], 10)), Err(ZKaneError::InvalidFee(_))));
+
+        let native = crate::generate_deposit_note(ASSET_ID, 1000).unwrap();
+        assert!(matches!(plan_split(&native, 1000, &[], ([0u8; 32], 0)), Err(ZKaneError::InvalidCommitment(_))));
+    }
+
+    #[test]
+    fn test_process_split() {
+        let config = ZKaneConfig::new(ASSET_ID, 1000, 4, vec![]);
+        let mut pool = PrivacyPool::new(config, Arc::new(MockProvider::new(bitcoin::Network::Regtest))).unwrap();
+
+        let note = generate_circuit_note(ASSET_ID, 1000).unwrap();
+        let plan = plan_split(&note, 400, &[600], ([0u8; 32], 0)).unwrap();
+        let witness = SplitWitness {
+            withdrawal: WithdrawalWitness {
+                proof: WithdrawalProof::new(vec![1u8], pool.merkle_root(), plan.nullifier_hash, Recipient::default()),
+                path: MerklePath::new(vec![], vec![]).unwrap(),
+                leaf_index: 0,
+                commitment: note.commitment,
+                outputs_hash: [0u8; 32],
+            },
+            public_amount: plan.public_amount,
+            output_commitments: plan.output_commitments.clone(),
+        };
+
+        assert_eq!(pool.process_split(&witness, Some(10)).unwrap(), vec![0, 1]);
+        assert_eq!(pool.leaf_index_of(&plan.change[0].commitment), Some(0));
+        assert!(pool.is_nullifier_spent(plan.nullifier_hash.as_bytes()));
+
+        // A spent note can't be split again, and nothing is inserted
+        assert!(matches!(pool.process_split(&witness, None), Err(ZKaneError::NullifierAlreadySpent)));
+        assert_eq!(pool.commitment_count(), 2);
+
+        let mut repeated = witness;
+        repeated.withdrawal.proof.nullifier_hash = NullifierHash::new([9u8; 32]);
+        assert!(matches!(pool.process_split(&repeated, None), Err(ZKaneError::DuplicateCommitment(_))));
+        assert!(!pool.is_nullifier_spent(&[9u8; 32]));
+    }
+}
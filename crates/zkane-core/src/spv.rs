@@ -0,0 +1,242 @@
+//! # SPV Verification
+//!
+//! The [`SpvVerifier`] checks that a transaction served by the provider is
+//! actually in the chain, before the pool trusts the commitment it carries.
+//! It fetches the transaction's raw bytes, the Esplora merkle inclusion proof
+//! and the header of the block the proof points at, and checks that:
+//!
+//! - the raw transaction hashes to the requested txid,
+//! - the header hashes to the block hash the provider reports at its height,
+//!   and meets its own target, which must be attainable on the network,
+//! - the merkle branch links the txid to the header's merkle root.
+//!
+//! Headers are checked one at a time, not as a chain, so the verifier proves
+//! that a block of real work commits to the transaction. An endpoint lying
+//! about the transaction's content, or about it being in a block, is caught;
+//! an endpoint able to mine a competing block at full difficulty is not.
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::deserialize_hex;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::params::Params;
+use bitcoin::{BlockHash, Transaction, TxMerkleNode, Txid};
+use deezel_common::traits::DeezelProvider;
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+use std::sync::Arc;
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// An Esplora merkle inclusion proof of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    /// Height of the block containing the transaction
+    pub block_height: u64,
+    /// Sibling hashes from the transaction up to the merkle root
+    pub merkle: Vec<TxMerkleNode>,
+    /// Position of the transaction in the block
+    pub pos: u64,
+}
+
+impl InclusionProof {
+    /// Parse the `/tx/:txid/merkle-proof` response of an Esplora endpoint.
+    pub fn from_esplora(proof: &JsonValue) -> ZKaneResult<Self> {
+        let invalid = |reason: &str| ZKaneError::InvalidInclusionProof(format!("malformed merkle proof: {}", reason));
+        let block_height = proof["block_height"].as_u64().ok_or_else(|| invalid("missing block_height"))?;
+        let pos = proof["pos"].as_u64().ok_or_else(|| invalid("missing pos"))?;
+        let merkle = proof["merkle"]
+            .as_array()
+            .ok_or_else(|| invalid("missing merkle"))?
+            .iter()
+            .map(|node| {
+                node.as_str()
+                    .and_then(|node| TxMerkleNode::from_str(node).ok())
+                    .ok_or_else(|| invalid("bad merkle node"))
+            })
+            .collect::<ZKaneResult<_>>()?;
+        Ok(Self { block_height, merkle, pos })
+    }
+
+    /// Compute the merkle root the proof links `txid` to.
+    ///
+    /// Returns `None` if the position doesn't fit the branch.
+    pub fn merkle_root(&self, txid: Txid) -> Option<TxMerkleNode> {
+        if self.merkle.len() < 64 && self.pos >> self.merkle.len() != 0 {
+            return None;
+        }
+        let mut node = txid.to_raw_hash();
+        for (level, sibling) in self.merkle.iter().enumerate() {
+            let sibling = sibling.to_raw_hash();
+            let (left, right) = if self.pos >> level & 1 == 0 { (node, sibling) } else { (sibling, node) };
+            node = sha256d::Hash::hash(&[left.as_byte_array().as_slice(), right.as_byte_array()].concat());
+        }
+        Some(TxMerkleNode::from_raw_hash(node))
+    }
+}
+
+/// A transaction proven to be in a block.
+#[derive(Debug, Clone)]
+pub struct VerifiedTransaction {
+    /// The transaction
+    pub tx: Transaction,
+    /// Height of the block containing it
+    pub block_height: u64,
+    /// Header of the block containing it
+    pub header: Header,
+}
+
+/// Verifies transactions served by a provider against block headers.
+pub struct SpvVerifier<P: DeezelProvider> {
+    provider: Arc<P>,
+}
+
+impl<P: DeezelProvider> SpvVerifier<P> {
+    /// Create a verifier fetching from the given provider.
+    pub fn new(provider: Arc<P>) -> Self {
+        Self { provider }
+    }
+
+    /// Fetch a block header and check its proof of work.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidInclusionProof`] if the header doesn't
+    /// hash to `hash` or doesn't meet a target attainable on the network.
+    pub async fn block_header(&self, hash: &str) -> ZKaneResult<Header> {
+        let expected = BlockHash::from_str(hash)
+            .map_err(|e| ZKaneError::InvalidInclusionProof(format!("bad block hash {}: {}", hash, e)))?;
+        let header: Header = deserialize_hex(&self.provider.get_block_header(hash).await?)
+            .map_err(|e| ZKaneError::InvalidInclusionProof(format!("bad header of block {}: {}", hash, e)))?;
+
+        if header.block_hash() != expected {
+            return Err(ZKaneError::InvalidInclusionProof(format!(
+                "header of block {} hashes to {}",
+                hash,
+                header.block_hash()
+            )));
+        }
+        let max_target = Params::new(self.provider.get_network()).max_attainable_target;
+        if header.target() > max_target || header.validate_pow(header.target()).is_err() {
+            return Err(ZKaneError::InvalidInclusionProof(format!(
+                "header of block {} lacks proof of work",
+                hash
+            )));
+        }
+        Ok(header)
+    }
+
+    /// Fetch the merkle inclusion proof of a transaction.
+    pub async fn inclusion_proof(&self, txid: &str) -> ZKaneResult<InclusionProof> {
+        InclusionProof::from_esplora(&self.provider.get_tx_merkle_proof(txid).await?)
+    }
+
+    /// Fetch a transaction and prove it is in the block the provider places it in.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidInclusionProof`] if any check fails, see
+    /// the [module documentation](self).
+    pub async fn verified_transaction(&self, txid: &str) -> ZKaneResult<VerifiedTransaction> {
+        let expected = Txid::from_str(txid)
+            .map_err(|e| ZKaneError::InvalidInclusionProof(format!("bad txid {}: {}", txid, e)))?;
+        let tx: Transaction = deserialize_hex(&self.provider.get_tx_hex(txid).await?)
+            .map_err(|e| ZKaneError::InvalidInclusionProof(format!("bad transaction {}: {}", txid, e)))?;
+        if tx.compute_txid() != expected {
+            return Err(ZKaneError::InvalidInclusionProof(format!(
+                "transaction served for {} hashes to {}",
+                txid,
+                tx.compute_txid()
+            )));
+        }
+        // A 64-byte transaction can pass for an inner merkle node
+        if tx.base_size() == 64 {
+            return Err(ZKaneError::InvalidInclusionProof(format!("transaction {} is 64 bytes", txid)));
+        }
+
+        let proof = self.inclusion_proof(txid).await?;
+        let hash = self.provider.get_block_by_height(proof.block_height).await?;
+        let header = self.block_header(&hash).await?;
+        if proof.merkle_root(expected) != Some(header.merkle_root) {
+            return Err(ZKaneError::InvalidInclusionProof(format!(
+                "transaction {} is not in block {}",
+                txid, hash
+            )));
+        }
+
+        Ok(VerifiedTransaction { tx, block_height: proof.block_height, header })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use bitcoin::{Amount, Network, OutPoint, ScriptBuf, TxIn, TxOut};
+
+    fn deposit_tx(n: u8) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::all_zeros(), n.into()), ..Default::default() }],
+            output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([n; 32]) }],
+        }
+    }
+
+    fn node(txid: Txid) -> TxMerkleNode {
+        TxMerkleNode::from_raw_hash(txid.to_raw_hash())
+    }
+
+    #[tokio::test]
+    async fn test_verified_transaction() {
+        let provider = MockProvider::new(Network::Regtest);
+        let txs: Vec<Transaction> = (1..=3).map(deposit_tx).collect();
+        provider.mine_transactions(txs.clone());
+        let verifier = SpvVerifier::new(Arc::new(provider));
+
+        for tx in &txs {
+            let verified = verifier.verified_transaction(&tx.compute_txid().to_string()).await.unwrap();
+            assert_eq!(&verified.tx, tx);
+            assert_eq!(verified.block_height, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_transaction_is_rejected() {
+        let provider = MockProvider::new(Network::Regtest);
+        provider.mine_transactions(vec![deposit_tx(1)]);
+        // Served by the endpoint, but in no block
+        let unmined = deposit_tx(2);
+        provider.add_raw_tx(&unmined);
+
+        let verifier = SpvVerifier::new(Arc::new(provider));
+        assert!(verifier.verified_transaction(&unmined.compute_txid().to_string()).await.is_err());
+    }
+
+    #[test]
+    fn test_forged_branch_is_rejected() {
+        let txs: Vec<Txid> = (1..=3).map(|n| deposit_tx(n).compute_txid()).collect();
+        let root = bitcoin::merkle_tree::calculate_root(txs.iter().copied().map(node)).unwrap();
+        // The odd last transaction is paired with itself
+        let right = bitcoin::merkle_tree::calculate_root([node(txs[2]), node(txs[2])].into_iter()).unwrap();
+        let proof = InclusionProof { block_height: 1, merkle: vec![node(txs[0]), right], pos: 1 };
+        assert_eq!(proof.merkle_root(txs[1]), Some(root));
+
+        // A transaction outside the block, or at the wrong position, misses the root
+        assert_ne!(proof.merkle_root(deposit_tx(4).compute_txid()), Some(root));
+        assert_ne!(InclusionProof { pos: 0, ..proof.clone() }.merkle_root(txs[1]), Some(root));
+        assert_eq!(InclusionProof { pos: 4, ..proof }.merkle_root(txs[1]), None);
+    }
+
+    #[test]
+    fn test_parse_esplora_proof() {
+        let node = "14e5eb4b4e8b7a0b5e22c1e2a3e5d0b9a6c1b8f1f8e2d0c3b4a5968778695a4b";
+        let proof = InclusionProof::from_esplora(&serde_json::json!({
+            "block_height": 7, "merkle": [node], "pos": 1,
+        }))
+        .unwrap();
+        assert_eq!(proof.block_height, 7);
+        assert_eq!(proof.merkle, vec![TxMerkleNode::from_str(node).unwrap()]);
+
+        let err = InclusionProof::from_esplora(&serde_json::json!({ "merkle": [], "pos": 0 })).unwrap_err();
+        assert_eq!(err.code(), 5004);
+    }
+}
@@ -0,0 +1,483 @@
+//! # Pool Synchronization
+//!
+//! The [`PoolSyncer`] keeps a [`PrivacyPool`] in step with the deposits and
+//! withdrawals seen on chain. It skips deposits that were already synced and
+//! feeds the resulting [`PoolEvent`]s to an optional [`ViewOnlyWallet`], so
+//! watched notes are recognized as they are synced.
+//!
+//! The syncer works on any [`PoolProvider`]. On a deezel provider,
+//! [`PoolSyncer::with_inclusion_proofs`] only syncs deposits once their
+//! transactions are proven to be in a block, see [`crate::spv`].
+//!
+//! When the provider reports a reorg, [`PoolSyncer::rollback_to_height`]
+//! undoes the orphaned deposits and withdrawals so their transactions can be
+//! synced again from the new chain.
+//!
+//! To keep syncing when an endpoint goes down, sync through a
+//! [`FailoverProvider`](crate::FailoverProvider) over several providers, and
+//! run [`PoolSyncer::health_check`] between syncs to move away from stale or
+//! slow ones.
+//!
+//! To avoid refetching transactions already seen, as when a restart syncs
+//! blocks again, sync through a [`CachingProvider`] and read its hit rate
+//! with [`PoolSyncer::cache_stats`]. After a rollback, have the cache
+//! [`forget_above`](CachingProvider::forget_above) the same height.
+//!
+//! Deposit events report the contract's root after the deposit, which
+//! [`PoolSyncer::sync_event`] compares with the pool's. This catches a
+//! diverging tree on the deposit that caused it, without the round trips of a
+//! [`ConsistencyChecker`](crate::ConsistencyChecker).
+
+use crate::cache::{CacheStats, CachingProvider};
+use crate::events::PoolEvent;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::failover::{FailoverProvider, ProviderHealth};
+use crate::view::ViewOnlyWallet;
+use crate::PrivacyPool;
+use crate::provider::PoolProvider;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::DeezelProvider;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::future::LocalBoxFuture;
+use futures::{FutureExt, StreamExt};
+use std::collections::HashSet;
+use zkane_common::{ContractEvent, ZKaneError, ZKaneResult};
+
+/// Adds a deposit transaction's commitment to a pool.
+type AddDeposit<P> = for<'a> fn(&'a mut PrivacyPool<P>, &'a str) -> LocalBoxFuture<'a, ZKaneResult<u64>>;
+
+/// Synchronizes a privacy pool with on-chain deposits and withdrawals.
+pub struct PoolSyncer<P: PoolProvider> {
+    pool: PrivacyPool<P>,
+    events: UnboundedReceiver<PoolEvent>,
+    synced_deposits: HashSet<String>,
+    /// Txids of the synced deposits, in leaf order
+    deposit_txids: Vec<String>,
+    view_only: Option<ViewOnlyWallet>,
+    /// Adds synced deposits, checking their inclusion proofs if required
+    add_deposit: AddDeposit<P>,
+}
+
+impl<P: PoolProvider> PoolSyncer<P> {
+    /// Create a syncer for a pool.
+    pub fn new(pool: PrivacyPool<P>) -> Self {
+        let events = pool.events.subscribe();
+        Self {
+            pool,
+            events,
+            synced_deposits: HashSet::new(),
+            deposit_txids: Vec::new(),
+            view_only: None,
+            add_deposit: |pool, txid| pool.add_commitment(txid).boxed_local(),
+        }
+    }
+
+    /// Mark which synced commitments belong to the notes of a view-only wallet.
+    pub fn with_view_only(mut self, wallet: ViewOnlyWallet) -> Self {
+        self.view_only = Some(wallet);
+        self
+    }
+
+    /// Get the synced pool.
+    pub fn pool(&self) -> &PrivacyPool<P> {
+        &self.pool
+    }
+
+    /// Consume the syncer, returning the synced pool.
+    pub fn into_pool(self) -> PrivacyPool<P> {
+        self.pool
+    }
+
+    /// Get the view-only wallet, if any.
+    pub fn view_only(&self) -> Option<&ViewOnlyWallet> {
+        self.view_only.as_ref()
+    }
+
+    /// Sync deposit transactions, in chain order.
+    ///
+    /// Transactions that were already synced are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of new deposits added to the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a transaction can't be fetched, fails verification
+    /// or doesn't contain a commitment. Deposits before the failing one remain
+    /// synced.
+    pub async fn sync_deposits(&mut self, txids: &[&str]) -> ZKaneResult<usize> {
+        let mut added = 0;
+        for txid in txids {
+            if self.synced_deposits.contains(*txid) {
+                continue;
+            }
+            let result = (self.add_deposit)(&mut self.pool, txid).await;
+            self.drain_events();
+            result?;
+            self.synced_deposits.insert(txid.to_string());
+            self.deposit_txids.push(txid.to_string());
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Sync a withdrawal seen on chain.
+    pub fn sync_withdrawal(&mut self, nullifier_hash: &[u8; 32], block_height: Option<u64>) -> ZKaneResult<()> {
+        let result = match block_height {
+            Some(height) => self.pool.process_withdrawal_in_block(nullifier_hash, height),
+            None => self.pool.process_withdrawal(nullifier_hash),
+        };
+        self.drain_events();
+        result
+    }
+
+    /// Sync an event the pool contract emitted in transaction `txid`.
+    ///
+    /// `data` is the response data of the contract call, encoded as a
+    /// [`ContractEvent`]. Deposit events of already synced transactions are
+    /// skipped, as in [`sync_deposits`](Self::sync_deposits). A deposit
+    /// reporting a root is checked against the pool's root once applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data isn't a valid event or the pool rejects
+    /// it; see [`PrivacyPool::apply_contract_event`]. Returns
+    /// [`ZKaneError::StateDivergence`] if the pool's root after a deposit
+    /// isn't the reported one; the deposit stays synced, so the divergence
+    /// can be located with a [`ConsistencyChecker`](crate::ConsistencyChecker)
+    /// or undone with [`rollback_to_height`](Self::rollback_to_height).
+    pub fn sync_event(&mut self, txid: &str, data: &[u8], block_height: Option<u64>) -> ZKaneResult<()> {
+        let event = ContractEvent::from_bytes(data)?;
+        let is_deposit = matches!(event, ContractEvent::Deposit { .. });
+        if is_deposit && self.synced_deposits.contains(txid) {
+            return Ok(());
+        }
+
+        let leaf_count = self.pool.commitment_count();
+        let result = self.pool.apply_contract_event(&event, block_height);
+        self.drain_events();
+        result?;
+
+        // Keep the txids in leaf order for rollbacks
+        for _ in leaf_count..self.pool.commitment_count() {
+            self.deposit_txids.push(txid.to_string());
+        }
+        if is_deposit {
+            self.synced_deposits.insert(txid.to_string());
+        }
+
+        if let ContractEvent::Deposit { leaf_index, root, .. } = event {
+            let local_root = self.pool.merkle_root();
+            if root != [0u8; 32] && root != local_root {
+                return Err(ZKaneError::StateDivergence {
+                    leaf_index: leaf_index.into(),
+                    reason: format!(
+                        "local root after the deposit is {}, contract root is {}",
+                        hex::encode(local_root),
+                        hex::encode(root)
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the deposits and withdrawals confirmed above `height` after a reorg.
+    ///
+    /// The removed deposits are forgotten, so syncing their transactions again
+    /// re-adds them at their position in the new chain.
+    ///
+    /// # Returns
+    ///
+    /// The txids of the removed deposits, in leaf order.
+    pub fn rollback_to_height(&mut self, height: u64) -> ZKaneResult<Vec<String>> {
+        let result = self.pool.rollback_to_height(height);
+        self.drain_events();
+        result?;
+
+        let leaf_count = self.pool.commitment_count() as usize;
+        let removed = self.deposit_txids.split_off(leaf_count.min(self.deposit_txids.len()));
+        for txid in &removed {
+            self.synced_deposits.remove(txid);
+        }
+        Ok(removed)
+    }
+
+    fn drain_events(&mut self) {
+        // The pool publishes synchronously, so every event is already queued
+        while let Some(Some(event)) = self.events.next().now_or_never() {
+            if let Some(wallet) = &mut self.view_only {
+                wallet.apply(&event);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deezel")]
+impl<P: DeezelProvider> PoolSyncer<P> {
+    /// Verify that each deposit transaction is in a block before syncing it.
+    ///
+    /// See [`PrivacyPool::add_verified_commitment`].
+    pub fn with_inclusion_proofs(mut self) -> Self {
+        self.add_deposit = |pool, txid| pool.add_verified_commitment(txid).boxed_local();
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<P: PoolProvider> PoolSyncer<FailoverProvider<P>> {
+    /// Check the health of the pool's providers, see
+    /// [`FailoverProvider::health_check`].
+    pub async fn health_check(&self) -> Vec<ProviderHealth> {
+        self.pool.provider().health_check().await
+    }
+}
+
+impl<P: PoolProvider> PoolSyncer<CachingProvider<P>> {
+    /// Get the hits and misses of the pool's provider cache, see
+    /// [`CachingProvider::stats`].
+    pub fn cache_stats(&self) -> CacheStats {
+        self.pool.provider().stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::{MockFailure, MockProvider};
+    use crate::view::ViewingNote;
+    use std::sync::Arc;
+    use zkane_common::{Commitment, NullifierHash, SpendEvent, ZKaneConfig, ZkAssetId};
+
+    fn create_syncer(wallet: ViewOnlyWallet) -> PoolSyncer<MockProvider> {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let mut provider = MockProvider::new(bitcoin::Network::Regtest);
+        for (txid, n) in [("tx_a", 1u8), ("tx_b", 2), ("tx_c", 3)] {
+            provider.add_response(
+                txid,
+                serde_json::json!({
+                    "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ],
+                    "status": { "confirmed": true, "block_height": 100 + n as u64 }
+                }),
+            );
+        }
+        let pool = PrivacyPool::new(config, Arc::new(provider)).unwrap();
+        PoolSyncer::new(pool).with_view_only(wallet)
+    }
+
+    #[tokio::test]
+    async fn test_sync_skips_known_deposits() {
+        let mut syncer = create_syncer(ViewOnlyWallet::default());
+        assert_eq!(syncer.sync_deposits(&["tx_a", "tx_b"]).await.unwrap(), 2);
+        assert_eq!(syncer.sync_deposits(&["tx_a", "tx_b", "tx_c"]).await.unwrap(), 1);
+        assert_eq!(syncer.pool().commitment_count(), 3);
+
+        assert!(syncer.sync_deposits(&["tx_missing"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_scripted_chain() {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let deposit = |n: u8| serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ] });
+        provider.mine_block(vec![("tx_a", deposit(1)), ("tx_b", deposit(2))]);
+        provider.mine_block(vec![("tx_c", deposit(3))]);
+
+        let mut syncer = PoolSyncer::new(PrivacyPool::new(config, Arc::new(provider.clone())).unwrap());
+
+        // A block that failed to sync is retried once the provider recovers
+        for height in 1..=provider.tip_height() {
+            provider.inject_failure("get_tx", MockFailure::Timeout);
+            let txids = provider.block_txids(height).unwrap();
+            let txids: Vec<&str> = txids.iter().map(String::as_str).collect();
+            assert!(syncer.sync_deposits(&txids).await.is_err());
+            assert_eq!(syncer.sync_deposits(&txids).await.unwrap(), txids.len());
+        }
+        assert_eq!(syncer.pool().commitment_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sync_with_inclusion_proofs() {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let mut provider = MockProvider::new(bitcoin::Network::Regtest);
+        let deposit = |n: u8| bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::ZERO,
+                script_pubkey: bitcoin::ScriptBuf::new_op_return([n; 32]),
+            }],
+        };
+        let (tx_a, tx_b) = (deposit(1), deposit(2));
+        provider.mine_transactions(vec![tx_a.clone(), tx_b.clone()]);
+        let (txid_a, txid_b) = (tx_a.compute_txid().to_string(), tx_b.compute_txid().to_string());
+
+        // The endpoint lies about the content of tx_a and claims a deposit
+        // that was never mined
+        provider.add_response(
+            &txid_a,
+            serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([9u8; 32])), "value": 0 } ] }),
+        );
+        let forged = deposit(3);
+        provider.add_raw_tx(&forged);
+        provider.add_response(
+            &forged.compute_txid().to_string(),
+            serde_json::json!({
+                "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([3u8; 32])), "value": 0 } ],
+                "status": { "confirmed": true, "block_height": 1 }
+            }),
+        );
+
+        let pool = PrivacyPool::new(config, Arc::new(provider)).unwrap();
+        let mut syncer = PoolSyncer::new(pool).with_inclusion_proofs();
+        assert_eq!(syncer.sync_deposits(&[&txid_a, &txid_b]).await.unwrap(), 2);
+        assert_eq!(syncer.pool().leaf_index_of(&Commitment::new([1u8; 32])), Some(0));
+        assert_eq!(syncer.pool().leaf_index_of(&Commitment::new([9u8; 32])), None);
+
+        assert!(syncer.sync_deposits(&[&forged.compute_txid().to_string()]).await.is_err());
+        assert_eq!(syncer.pool().commitment_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_reorg() {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let deposit = |n: u8| serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ] });
+        let note = ViewingNote::new(Commitment::new([2u8; 32]), NullifierHash::new([102u8; 32]));
+
+        let pool = PrivacyPool::new(config, Arc::new(provider.clone())).unwrap();
+        let mut syncer = PoolSyncer::new(pool).with_view_only(ViewOnlyWallet::new([note]));
+        provider.mine_block(vec![("tx_a", deposit(1))]);
+        provider.mine_block(vec![("tx_b", deposit(2))]);
+        syncer.sync_deposits(&["tx_a", "tx_b"]).await.unwrap();
+        syncer.sync_withdrawal(&[102u8; 32], Some(2)).unwrap();
+
+        // Block 2 is orphaned and tx_b is mined again after tx_c
+        let orphaned = provider.reorg(1);
+        assert_eq!(syncer.rollback_to_height(provider.tip_height()).unwrap(), orphaned);
+        assert_eq!(syncer.pool().commitment_count(), 1);
+        assert!(!syncer.pool().is_nullifier_spent(&[102u8; 32]));
+        let status = syncer.view_only().unwrap().status(&note.commitment).unwrap();
+        assert_eq!((status.leaf_index, status.spent), (None, false));
+
+        provider.mine_block(vec![("tx_c", deposit(3)), ("tx_b", deposit(2))]);
+        let txids = provider.block_txids(2).unwrap();
+        let txids: Vec<&str> = txids.iter().map(String::as_str).collect();
+        assert_eq!(syncer.sync_deposits(&txids).await.unwrap(), 2);
+        let status = syncer.view_only().unwrap().status(&note.commitment).unwrap();
+        assert_eq!(status.leaf_index, Some(2));
+        assert_eq!(syncer.pool().leaf_index_of(&note.commitment), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_sync_through_cache() {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let provider = MockProvider::new(bitcoin::Network::Regtest);
+        let deposit = |n: u8| serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", hex::encode([n; 32])), "value": 0 } ] });
+        provider.mine_block(vec![("tx_a", deposit(1)), ("tx_b", deposit(2))]);
+        let cache = Arc::new(CachingProvider::new(provider, crate::CacheConfig::default()));
+
+        let mut syncer = PoolSyncer::new(PrivacyPool::new(config.clone(), cache.clone()).unwrap());
+        assert_eq!(syncer.sync_deposits(&["tx_a", "tx_b"]).await.unwrap(), 2);
+
+        // A restarted syncer gets the transactions from the cache
+        let mut restarted = PoolSyncer::new(PrivacyPool::new(config, cache).unwrap());
+        assert_eq!(restarted.sync_deposits(&["tx_a", "tx_b"]).await.unwrap(), 2);
+        assert_eq!(restarted.pool().merkle_root(), syncer.pool().merkle_root());
+        let stats = restarted.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(stats.hit_rate(), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_view_only_scanning() {
+        let mine = ViewingNote::new(Commitment::new([2u8; 32]), NullifierHash::new([42u8; 32]));
+        let mut syncer = create_syncer(ViewOnlyWallet::new([mine]));
+
+        syncer.sync_deposits(&["tx_a", "tx_b", "tx_c"]).await.unwrap();
+        let status = syncer.view_only().unwrap().status(&mine.commitment).unwrap().clone();
+        assert_eq!(status.leaf_index, Some(1));
+        assert_eq!(status.deposit_block, Some(102));
+        assert!(!status.spent);
+
+        syncer.sync_withdrawal(&[7u8; 32], Some(104)).unwrap();
+        syncer.sync_withdrawal(&[42u8; 32], Some(105)).unwrap();
+        let status = syncer.view_only().unwrap().status(&mine.commitment).unwrap();
+        assert!(status.spent);
+        assert_eq!(status.withdrawal_block, Some(105));
+        assert_eq!(syncer.view_only().unwrap().unspent().count(), 0);
+    }
+
+    #[test]
+    fn test_sync_contract_events() {
+        let mine = ViewingNote::new(Commitment::new([2u8; 32]), NullifierHash::new([42u8; 32]));
+        let mut syncer = create_syncer(ViewOnlyWallet::new([mine]));
+        let deposit = |n: u8, leaf_index| {
+            ContractEvent::Deposit { commitment: Commitment::new([n; 32]), leaf_index, root: [0u8; 32] }.to_bytes()
+        };
+
+        syncer.sync_event("tx_a", &deposit(1, 0), Some(101)).unwrap();
+        syncer.sync_event("tx_b", &deposit(2, 1), Some(102)).unwrap();
+        // Resyncing a deposit is a no-op
+        syncer.sync_event("tx_b", &deposit(2, 1), Some(102)).unwrap();
+        assert_eq!(syncer.pool().commitment_count(), 2);
+
+        // A missed deposit is caught by its leaf index
+        assert!(matches!(
+            syncer.sync_event("tx_d", &deposit(4, 3), Some(104)),
+            Err(ZKaneError::InvalidCommitment(_))
+        ));
+        assert!(syncer.sync_event("tx_d", br#"{"type":"deposit"}"#, Some(104)).is_err());
+        assert_eq!(syncer.pool().commitment_count(), 2);
+
+        let spend = SpendEvent {
+            nullifier_hash: NullifierHash::new([42u8; 32]),
+            outputs_hash: [0u8; 32],
+            relayer_output_hash: [0u8; 32],
+            fee: 0,
+            protocol_fee: 0,
+        };
+        let split = ContractEvent::Split {
+            spend,
+            public_amount: 400000,
+            first_leaf_index: 2,
+            commitments: vec![Commitment::new([5u8; 32]), Commitment::new([6u8; 32])],
+        };
+        syncer.sync_event("tx_e", &split.to_bytes(), Some(105)).unwrap();
+        assert_eq!(syncer.pool().commitment_count(), 4);
+        let status = syncer.view_only().unwrap().status(&mine.commitment).unwrap();
+        assert_eq!(status.withdrawal_block, Some(105));
+
+        let withdrawal = ContractEvent::Withdrawal(spend).to_bytes();
+        assert!(matches!(
+            syncer.sync_event("tx_f", &withdrawal, Some(106)),
+            Err(ZKaneError::NullifierAlreadySpent)
+        ));
+
+        // Both leaves of the split are rolled back with its transaction
+        assert_eq!(syncer.rollback_to_height(102).unwrap(), vec!["tx_e", "tx_e"]);
+        assert_eq!(syncer.pool().commitment_count(), 2);
+    }
+
+    #[test]
+    fn test_sync_deposit_roots() {
+        let deposit = |n: u8, leaf_index, root| ContractEvent::Deposit { commitment: Commitment::new([n; 32]), leaf_index, root }.to_bytes();
+        let mut reference = create_syncer(ViewOnlyWallet::default());
+        reference.sync_event("tx_a", &deposit(1, 0, [0u8; 32]), Some(101)).unwrap();
+        let root_a = reference.pool().merkle_root();
+        reference.sync_event("tx_b", &deposit(2, 1, [0u8; 32]), Some(102)).unwrap();
+        let root_b = reference.pool().merkle_root();
+
+        let mut syncer = create_syncer(ViewOnlyWallet::default());
+        syncer.sync_event("tx_a", &deposit(1, 0, root_a), Some(101)).unwrap();
+
+        // A wrong root is reported at the deposit, which stays synced
+        let err = syncer.sync_event("tx_b", &deposit(2, 1, root_a), Some(102)).unwrap_err();
+        assert!(matches!(err, ZKaneError::StateDivergence { leaf_index: 1, .. }));
+        assert_eq!(syncer.pool().merkle_root(), root_b);
+        syncer.sync_event("tx_b", &deposit(2, 1, root_a), Some(102)).unwrap();
+        assert_eq!(syncer.rollback_to_height(101).unwrap(), vec!["tx_b"]);
+    }
+}
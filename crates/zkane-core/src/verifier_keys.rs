@@ -0,0 +1,267 @@
+//! # Verifier Key Management
+//!
+//! Pools verify withdrawal proofs with the verification key of a specific
+//! version of the withdrawal circuit. The [`VerifierKeyRegistry`] holds the
+//! keys of every supported circuit version, loaded from artifacts embedded in
+//! the binary or from key files on disk, and stamps the active key into new
+//! pool configurations.
+//!
+//! ```rust
+//! use zkane_core::VerifierKeyRegistry;
+//! use zkane_common::ZKaneConfig;
+//! use zkane_common::ZkAssetId;
+//!
+//! // Typically `&[(1, include_bytes!("withdraw_v1.vk"))]`
+//! let registry = VerifierKeyRegistry::from_embedded(&[(1, &[0xab; 48])])?;
+//!
+//! let config = registry.stamp(ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![]))?;
+//! assert_eq!(config.circuit_version, 1);
+//! assert_eq!(config.verifier_key, vec![0xab; 48]);
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use zkane_common::{WithdrawalProof, ZKaneConfig, ZKaneError, ZKaneResult};
+
+/// Extension of verifier key files loaded by [`VerifierKeyRegistry::load_dir`]
+pub const VERIFIER_KEY_EXTENSION: &str = "vk";
+
+/// Verification keys of the supported withdrawal circuit versions.
+///
+/// The active version is the one stamped into new pools. It defaults to the
+/// highest registered version and can be pinned with
+/// [`VerifierKeyRegistry::set_active`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifierKeyRegistry {
+    keys: BTreeMap<u32, Vec<u8>>,
+    active: Option<u32>,
+}
+
+impl VerifierKeyRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry from keys embedded in the binary.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key is empty or a version is listed twice with
+    /// different keys.
+    pub fn from_embedded(artifacts: &[(u32, &[u8])]) -> ZKaneResult<Self> {
+        let mut registry = Self::new();
+        for (circuit_version, key) in artifacts {
+            registry.register(*circuit_version, key.to_vec())?;
+        }
+        Ok(registry)
+    }
+
+    /// Register the verification key of a circuit version.
+    ///
+    /// Registering the same key twice is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidVerifierKey`] if the key is empty or a
+    /// different key is already registered for the version.
+    pub fn register(&mut self, circuit_version: u32, key: Vec<u8>) -> ZKaneResult<()> {
+        if key.is_empty() {
+            return Err(ZKaneError::InvalidVerifierKey(format!(
+                "empty key for circuit version {}",
+                circuit_version
+            )));
+        }
+        match self.keys.get(&circuit_version) {
+            Some(existing) if *existing != key => Err(ZKaneError::InvalidVerifierKey(format!(
+                "conflicting keys for circuit version {}",
+                circuit_version
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                self.keys.insert(circuit_version, key);
+                Ok(())
+            }
+        }
+    }
+
+    /// Load the verification key of a circuit version from a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or the key is rejected by
+    /// [`VerifierKeyRegistry::register`].
+    pub fn load_file(&mut self, circuit_version: u32, path: impl AsRef<Path>) -> ZKaneResult<()> {
+        let path = path.as_ref();
+        let key = std::fs::read(path)
+            .map_err(|e| ZKaneError::InvalidVerifierKey(format!("{}: {}", path.display(), e)))?;
+        self.register(circuit_version, key)
+    }
+
+    /// Load every key file in a directory.
+    ///
+    /// Key files are named after their circuit version, e.g. `v2.vk`; other
+    /// files are ignored.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys loaded.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> ZKaneResult<usize> {
+        let dir = dir.as_ref();
+        let entries =
+            std::fs::read_dir(dir).map_err(|e| ZKaneError::InvalidVerifierKey(format!("{}: {}", dir.display(), e)))?;
+
+        let mut loaded = 0;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| ZKaneError::InvalidVerifierKey(format!("{}: {}", dir.display(), e)))?
+                .path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(VERIFIER_KEY_EXTENSION) {
+                continue;
+            }
+            let version = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix('v'))
+                .and_then(|version| version.parse::<u32>().ok());
+            if let Some(version) = version {
+                self.load_file(version, &path)?;
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Pin the circuit version stamped into new pools.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnsupportedCircuitVersion`] if no key is
+    /// registered for the version.
+    pub fn set_active(&mut self, circuit_version: u32) -> ZKaneResult<()> {
+        if !self.is_supported(circuit_version) {
+            return Err(ZKaneError::UnsupportedCircuitVersion(circuit_version));
+        }
+        self.active = Some(circuit_version);
+        Ok(())
+    }
+
+    /// Get the circuit version stamped into new pools, if any key is registered.
+    pub fn active_version(&self) -> Option<u32> {
+        self.active.or_else(|| self.keys.keys().next_back().copied())
+    }
+
+    /// Get the verification key of a circuit version.
+    pub fn get(&self, circuit_version: u32) -> Option<&[u8]> {
+        self.keys.get(&circuit_version).map(Vec::as_slice)
+    }
+
+    /// Check whether a key is registered for a circuit version.
+    pub fn is_supported(&self, circuit_version: u32) -> bool {
+        self.keys.contains_key(&circuit_version)
+    }
+
+    /// Get the registered circuit versions in ascending order.
+    pub fn versions(&self) -> impl Iterator<Item = u32> + '_ {
+        self.keys.keys().copied()
+    }
+
+    /// Stamp the active key and circuit version into a pool configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidVerifierKey`] if the registry is empty.
+    pub fn stamp(&self, config: ZKaneConfig) -> ZKaneResult<ZKaneConfig> {
+        let version = self
+            .active_version()
+            .ok_or_else(|| ZKaneError::InvalidVerifierKey("no verifier key registered".to_string()))?;
+        Ok(config.with_verifier_key(version, self.keys[&version].clone()))
+    }
+
+    /// Check that a withdrawal proof references a supported circuit version
+    /// matching the pool's verifier key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::UnsupportedCircuitVersion`] if the version is
+    /// unknown or differs from the pool's, and
+    /// [`ZKaneError::InvalidVerifierKey`] if the pool's key isn't the
+    /// registered key of its version.
+    pub fn check_proof(&self, config: &ZKaneConfig, proof: &WithdrawalProof) -> ZKaneResult<()> {
+        let key = self
+            .get(proof.circuit_version)
+            .ok_or(ZKaneError::UnsupportedCircuitVersion(proof.circuit_version))?;
+        config.check_circuit_version(proof)?;
+        if config.verifier_key != key {
+            return Err(ZKaneError::InvalidVerifierKey(format!(
+                "pool key doesn't match circuit version {}",
+                config.circuit_version
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::ZkAssetId;
+    use zkane_common::{NullifierHash, Recipient};
+
+    fn test_config() -> ZKaneConfig {
+        ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 20, vec![])
+    }
+
+    #[test]
+    fn test_active_version() {
+        let mut registry = VerifierKeyRegistry::from_embedded(&[(1, &[1u8; 4]), (3, &[3u8; 4])]).unwrap();
+        assert_eq!(registry.active_version(), Some(3));
+        assert_eq!(registry.versions().collect::<Vec<_>>(), vec![1, 3]);
+
+        registry.set_active(1).unwrap();
+        let config = registry.stamp(test_config()).unwrap();
+        assert_eq!((config.circuit_version, config.verifier_key), (1, vec![1u8; 4]));
+
+        assert!(registry.set_active(2).is_err());
+        assert!(registry.register(3, vec![9u8; 4]).is_err());
+        assert!(registry.register(3, vec![3u8; 4]).is_ok());
+        assert!(registry.register(4, vec![]).is_err());
+        assert!(VerifierKeyRegistry::new().stamp(test_config()).is_err());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("zkane-vk-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("v1.vk"), [1u8; 8]).unwrap();
+        std::fs::write(dir.join("v2.vk"), [2u8; 8]).unwrap();
+        std::fs::write(dir.join("README"), b"not a key").unwrap();
+
+        let mut registry = VerifierKeyRegistry::new();
+        let loaded = registry.load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.unwrap(), 2);
+        assert_eq!(registry.get(2), Some(&[2u8; 8][..]));
+        assert!(registry.load_file(5, dir.join("missing.vk")).is_err());
+    }
+
+    #[test]
+    fn test_check_proof() {
+        let registry = VerifierKeyRegistry::from_embedded(&[(1, &[1u8; 4]), (2, &[2u8; 4])]).unwrap();
+        let config = registry.stamp(test_config()).unwrap();
+        let proof = WithdrawalProof::new(vec![1], [0u8; 32], NullifierHash::new([0u8; 32]), Recipient::default());
+
+        assert!(registry.check_proof(&config, &proof.clone().with_circuit_version(2)).is_ok());
+        // Supported by the registry, but not by this pool
+        assert!(registry.check_proof(&config, &proof.clone().with_circuit_version(1)).is_err());
+        assert!(matches!(
+            registry.check_proof(&config, &proof.clone().with_circuit_version(9)),
+            Err(ZKaneError::UnsupportedCircuitVersion(9))
+        ));
+
+        let tampered = config.with_verifier_key(2, vec![7u8; 4]);
+        assert!(registry.check_proof(&tampered, &proof.with_circuit_version(2)).is_err());
+    }
+}
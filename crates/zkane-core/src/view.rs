@@ -0,0 +1,234 @@
+//! # View-Only Scanning
+//!
+//! A view-only wallet recognizes a user's deposits and withdrawals on chain
+//! from public viewing data alone: the commitment and nullifier hash of each
+//! note. The secrets and nullifiers needed to spend are never loaded, which
+//! makes it suitable for audits and for watching notes kept in cold storage.
+//!
+//! Viewing data is exported once from the full notes with
+//! [`ViewingNote::from_deposit_note`] and fed to a [`PoolSyncer`] through
+//! [`PoolSyncer::with_view_only`].
+//!
+//! [`PoolSyncer`]: crate::sync::PoolSyncer
+//! [`PoolSyncer::with_view_only`]: crate::sync::PoolSyncer::with_view_only
+
+use crate::events::PoolEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zkane_common::{Commitment, DepositNote, NullifierHash, ZKaneError, ZKaneResult};
+use zkane_crypto::generate_nullifier_hash;
+
+/// The public data needed to recognize a note, without its spending secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ViewingNote {
+    /// The note's commitment, published on deposit
+    pub commitment: Commitment,
+    /// The note's nullifier hash, published on withdrawal
+    pub nullifier_hash: NullifierHash,
+}
+
+impl ViewingNote {
+    /// Create viewing data from a commitment and nullifier hash.
+    pub fn new(commitment: Commitment, nullifier_hash: NullifierHash) -> Self {
+        Self {
+            commitment,
+            nullifier_hash,
+        }
+    }
+
+    /// Derive the viewing data of a full deposit note.
+    pub fn from_deposit_note(note: &DepositNote) -> ZKaneResult<Self> {
+        let nullifier_hash = generate_nullifier_hash(&note.nullifier)?;
+        Ok(Self::new(note.commitment, nullifier_hash))
+    }
+}
+
+/// What the scanner has seen of a watched note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteStatus {
+    /// The watched note
+    pub note: ViewingNote,
+    /// Leaf index of the deposit, once it has been seen
+    pub leaf_index: Option<u64>,
+    /// Height of the block containing the deposit, if known
+    pub deposit_block: Option<u64>,
+    /// Whether the note's nullifier has been spent
+    pub spent: bool,
+    /// Height of the block containing the withdrawal, if known
+    pub withdrawal_block: Option<u64>,
+}
+
+impl NoteStatus {
+    /// Check whether the note is deposited and not yet withdrawn.
+    pub fn is_unspent(&self) -> bool {
+        self.leaf_index.is_some() && !self.spent
+    }
+}
+
+/// Tracks the on-chain status of a set of notes from their viewing data.
+#[derive(Debug, Clone, Default)]
+pub struct ViewOnlyWallet {
+    notes: Vec<NoteStatus>,
+    by_commitment: HashMap<Commitment, usize>,
+    by_nullifier_hash: HashMap<NullifierHash, usize>,
+}
+
+impl ViewOnlyWallet {
+    /// Create a wallet watching the given notes.
+    pub fn new(notes: impl IntoIterator<Item = ViewingNote>) -> Self {
+        let mut wallet = Self::default();
+        for note in notes {
+            wallet.watch(note);
+        }
+        wallet
+    }
+
+    /// Parse a wallet from a JSON list of viewing notes.
+    pub fn from_json(json: &str) -> ZKaneResult<Self> {
+        let notes: Vec<ViewingNote> =
+            serde_json::from_str(json).map_err(|e| ZKaneError::InvalidCommitment(e.to_string()))?;
+        Ok(Self::new(notes))
+    }
+
+    /// Start watching a note. Watching the same note twice has no effect.
+    pub fn watch(&mut self, note: ViewingNote) {
+        if self.by_commitment.contains_key(&note.commitment) {
+            return;
+        }
+        let index = self.notes.len();
+        self.by_commitment.insert(note.commitment, index);
+        self.by_nullifier_hash.insert(note.nullifier_hash, index);
+        self.notes.push(NoteStatus {
+            note,
+            leaf_index: None,
+            deposit_block: None,
+            spent: false,
+            withdrawal_block: None,
+        });
+    }
+
+    /// Update the watched notes from a pool event.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the event concerned one of the watched notes.
+    pub fn apply(&mut self, event: &PoolEvent) -> bool {
+        match event {
+            PoolEvent::DepositAdded { leaf, commitment, block } => match self.by_commitment.get(commitment) {
+                Some(&index) => {
+                    let status = &mut self.notes[index];
+                    status.leaf_index = Some(*leaf);
+                    status.deposit_block = *block;
+                    true
+                }
+                None => false,
+            },
+            PoolEvent::WithdrawalProcessed { nullifier_hash, block } => {
+                match self.by_nullifier_hash.get(nullifier_hash) {
+                    Some(&index) => {
+                        let status = &mut self.notes[index];
+                        status.spent = true;
+                        status.withdrawal_block = *block;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            PoolEvent::RootUpdated { .. } => false,
+            PoolEvent::RolledBack { height, leaf_count } => {
+                let mut changed = false;
+                for status in &mut self.notes {
+                    if status.leaf_index.is_some_and(|leaf| leaf >= *leaf_count) {
+                        status.leaf_index = None;
+                        status.deposit_block = None;
+                        changed = true;
+                    }
+                    if status.withdrawal_block.is_some_and(|block| block > *height) {
+                        status.spent = false;
+                        status.withdrawal_block = None;
+                        changed = true;
+                    }
+                }
+                changed
+            }
+        }
+    }
+
+    /// Replace the status of a watched note with one tracked elsewhere.
+    pub(crate) fn update_status(&mut self, status: &NoteStatus) {
+        if let Some(&index) = self.by_commitment.get(&status.note.commitment) {
+            self.notes[index] = status.clone();
+        }
+    }
+
+    /// Get the status of every watched note, in the order they were added.
+    pub fn notes(&self) -> &[NoteStatus] {
+        &self.notes
+    }
+
+    /// Get the status of a watched note by commitment.
+    pub fn status(&self, commitment: &Commitment) -> Option<&NoteStatus> {
+        self.by_commitment.get(commitment).map(|&index| &self.notes[index])
+    }
+
+    /// Iterate over the notes that are deposited and not yet withdrawn.
+    pub fn unspent(&self) -> impl Iterator<Item = &NoteStatus> {
+        self.notes.iter().filter(|status| status.is_unspent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewing_note(n: u8) -> ViewingNote {
+        ViewingNote::new(Commitment::new([n; 32]), NullifierHash::new([n + 100; 32]))
+    }
+
+    #[test]
+    fn test_view_only_wallet_tracks_notes() {
+        let mut wallet = ViewOnlyWallet::new([viewing_note(1), viewing_note(2)]);
+
+        // Someone else's deposit
+        assert!(!wallet.apply(&PoolEvent::DepositAdded {
+            leaf: 0,
+            commitment: Commitment::new([9u8; 32]),
+            block: Some(10),
+        }));
+        assert!(wallet.apply(&PoolEvent::DepositAdded {
+            leaf: 1,
+            commitment: Commitment::new([1u8; 32]),
+            block: Some(11),
+        }));
+        assert!(wallet.apply(&PoolEvent::DepositAdded {
+            leaf: 2,
+            commitment: Commitment::new([2u8; 32]),
+            block: Some(12),
+        }));
+        assert!(wallet.apply(&PoolEvent::WithdrawalProcessed {
+            nullifier_hash: NullifierHash::new([101u8; 32]),
+            block: Some(13),
+        }));
+
+        let first = wallet.status(&Commitment::new([1u8; 32])).unwrap();
+        assert_eq!(first.leaf_index, Some(1));
+        assert!(first.spent);
+        assert_eq!(first.withdrawal_block, Some(13));
+
+        let unspent: Vec<_> = wallet.unspent().map(|s| s.note).collect();
+        assert_eq!(unspent, vec![viewing_note(2)]);
+    }
+
+    #[test]
+    fn test_viewing_note_from_deposit_note() {
+        let note = crate::generate_deposit_note(zkane_common::ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        let viewing = ViewingNote::from_deposit_note(&note).unwrap();
+        assert_eq!(viewing.commitment, note.commitment);
+        assert_eq!(viewing.nullifier_hash, generate_nullifier_hash(&note.nullifier).unwrap());
+
+        // Viewing data contains nothing but the public values
+        let json = serde_json::to_string(&vec![viewing]).unwrap();
+        let wallet = ViewOnlyWallet::from_json(&json).unwrap();
+        assert_eq!(wallet.notes()[0].note, viewing);
+    }
+}
@@ -0,0 +1,309 @@
+//! # Multi-Pool Wallet
+//!
+//! A [`ZkaneWallet`] owns a user's deposit notes across every pool they use,
+//! keeps track of which are deposited and which are spent, and picks the
+//! notes to withdraw for a requested amount. Each pool is identified by its
+//! [`PoolKey`], the asset and denomination of its notes.
+//!
+//! Status comes from pool events: feed the wallet the events of each pool
+//! with [`ZkaneWallet::apply`], or hand [`ZkaneWallet::view_only`] to that
+//! pool's [`PoolSyncer`] and copy the result back with
+//! [`ZkaneWallet::update_from`].
+//!
+//! ```rust
+//! use zkane_core::{generate_deposit_note, PoolEvent, ZkaneWallet};
+//! use zkane_common::ZkAssetId;
+//!
+//! # fn example() -> zkane_common::ZKaneResult<()> {
+//! let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000)?;
+//! let mut wallet = ZkaneWallet::new();
+//! let pool = wallet.add_note(note.clone())?;
+//!
+//! wallet.apply(&pool, &PoolEvent::DepositAdded { leaf: 0, commitment: note.commitment, block: Some(100) });
+//! assert_eq!(wallet.balance(&ZkAssetId { block: 2, tx: 1 }), 1000);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`PoolSyncer`]: crate::sync::PoolSyncer
+
+use crate::events::PoolEvent;
+use crate::provider::PoolProvider;
+use crate::sync::PoolSyncer;
+use crate::view::{NoteStatus, ViewOnlyWallet, ViewingNote};
+use crate::verify_deposit_note;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zkane_common::{DepositNote, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// Identifies a pool by the asset and denomination of its notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PoolKey {
+    /// The pool's asset
+    pub asset_id: ZkAssetId,
+    /// The pool's denomination
+    pub denomination: u128,
+}
+
+impl PoolKey {
+    /// Get the key of the pool a note is deposited in.
+    pub fn of(note: &DepositNote) -> Self {
+        Self {
+            asset_id: note.asset_id,
+            denomination: note.denomination,
+        }
+    }
+}
+
+/// A wallet note and its on-chain status.
+#[derive(Debug, Clone)]
+pub struct WalletNote<'a> {
+    /// The note, with its spending secrets
+    pub note: &'a DepositNote,
+    /// What has been seen of the note on chain
+    pub status: &'a NoteStatus,
+}
+
+/// The notes of one pool.
+#[derive(Debug, Clone, Default)]
+struct PoolNotes {
+    /// Notes in the order they were added
+    notes: Vec<DepositNote>,
+    /// Their status, in the same order
+    view: ViewOnlyWallet,
+}
+
+/// Owns deposit notes across pools and tracks their status.
+#[derive(Debug, Clone, Default)]
+pub struct ZkaneWallet {
+    pools: HashMap<PoolKey, PoolNotes>,
+}
+
+impl ZkaneWallet {
+    /// Create an empty wallet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a note to the wallet. Adding the same note twice has no effect.
+    ///
+    /// # Returns
+    ///
+    /// The key of the note's pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidCommitment`] if the note doesn't open its
+    /// commitment.
+    pub fn add_note(&mut self, note: DepositNote) -> ZKaneResult<PoolKey> {
+        if !verify_deposit_note(&note)? {
+            return Err(ZKaneError::InvalidCommitment(
+                "note doesn't open its commitment".to_string(),
+            ));
+        }
+        let key = PoolKey::of(&note);
+        let pool = self.pools.entry(key).or_default();
+        if pool.view.status(&note.commitment).is_none() {
+            pool.view.watch(ViewingNote::from_deposit_note(&note)?);
+            pool.notes.push(note);
+        }
+        Ok(key)
+    }
+
+    /// Update the notes of a pool from one of its events.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the event concerned one of the wallet's notes.
+    pub fn apply(&mut self, pool: &PoolKey, event: &PoolEvent) -> bool {
+        self.pools.get_mut(pool).is_some_and(|pool| pool.view.apply(event))
+    }
+
+    /// Get the viewing data of a pool's notes, to sync with a [`PoolSyncer`].
+    pub fn view_only(&self, pool: &PoolKey) -> ViewOnlyWallet {
+        self.pools.get(pool).map(|pool| pool.view.clone()).unwrap_or_default()
+    }
+
+    /// Copy the status of the notes from a syncer created with
+    /// [`view_only`](Self::view_only).
+    ///
+    /// The syncer's pool determines which of the wallet's pools is updated.
+    pub fn update_from<P: PoolProvider>(&mut self, syncer: &PoolSyncer<P>) {
+        let config = syncer.pool().config();
+        let key = PoolKey {
+            asset_id: config.asset_id,
+            denomination: config.denomination,
+        };
+        let (Some(pool), Some(synced)) = (self.pools.get_mut(&key), syncer.view_only()) else {
+            return;
+        };
+        for status in synced.notes() {
+            pool.view.update_status(status);
+        }
+    }
+
+    /// Iterate over the pools the wallet has notes in.
+    pub fn pools(&self) -> impl Iterator<Item = &PoolKey> {
+        self.pools.keys()
+    }
+
+    /// Iterate over the notes of a pool, in the order they were added.
+    pub fn notes(&self, pool: &PoolKey) -> impl Iterator<Item = WalletNote<'_>> {
+        self.pools
+            .get(pool)
+            .into_iter()
+            .flat_map(|pool| pool.notes.iter().zip(pool.view.notes()))
+            .map(|(note, status)| WalletNote { note, status })
+    }
+
+    /// Iterate over the deposited, unspent notes of an asset across pools.
+    pub fn unspent(&self, asset_id: &ZkAssetId) -> impl Iterator<Item = WalletNote<'_>> {
+        let asset_id = *asset_id;
+        self.pools
+            .iter()
+            .filter(move |(key, _)| key.asset_id == asset_id)
+            .flat_map(|(key, _)| self.notes(key))
+            .filter(|note| note.status.is_unspent())
+    }
+
+    /// Get the total of the unspent notes of an asset.
+    pub fn balance(&self, asset_id: &ZkAssetId) -> u128 {
+        self.unspent(asset_id).map(|note| note.note.denomination).sum()
+    }
+
+    /// Get the total of the unspent notes of every asset.
+    pub fn balances(&self) -> HashMap<ZkAssetId, u128> {
+        let mut balances = HashMap::new();
+        for key in self.pools.keys() {
+            let total: u128 = self
+                .notes(key)
+                .filter(|note| note.status.is_unspent())
+                .map(|note| note.note.denomination)
+                .sum();
+            *balances.entry(key.asset_id).or_default() += total;
+        }
+        balances
+    }
+
+    /// Select unspent notes adding up to exactly `amount` of an asset.
+    ///
+    /// Each note is withdrawn whole, so notes of the largest denominations
+    /// that fit are taken first, and the oldest deposits of a denomination
+    /// before newer ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidDenomination`] if the unspent notes can't
+    /// make up the amount.
+    pub fn select_notes(&self, asset_id: &ZkAssetId, amount: u128) -> ZKaneResult<Vec<&DepositNote>> {
+        let mut candidates: Vec<WalletNote<'_>> = self.unspent(asset_id).collect();
+        candidates.sort_by_key(|note| (std::cmp::Reverse(note.note.denomination), note.status.leaf_index));
+
+        let mut remaining = amount;
+        let mut selected = Vec::new();
+        for candidate in candidates {
+            if candidate.note.denomination <= remaining {
+                remaining -= candidate.note.denomination;
+                selected.push(candidate.note);
+            }
+        }
+        if remaining != 0 || amount == 0 {
+            return Err(ZKaneError::InvalidDenomination);
+        }
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use crate::{generate_deposit_note, generate_deposit_note_with_rng, note_rng, PrivacyPool};
+    use std::sync::Arc;
+    use zkane_common::ZKaneConfig;
+    use zkane_crypto::generate_nullifier_hash;
+
+    const ASSET: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+    /// A wallet with deposited notes of the given denominations
+    fn create_wallet(denominations: &[u128]) -> (ZkaneWallet, Vec<DepositNote>) {
+        let mut wallet = ZkaneWallet::new();
+        let mut notes = Vec::new();
+        let mut rng = note_rng(Some(0));
+        for (leaf, &denomination) in denominations.iter().enumerate() {
+            let note = generate_deposit_note_with_rng(ZkAssetId { block: 2, tx: 1 }, denomination, &mut rng).unwrap();
+            let pool = wallet.add_note(note.clone()).unwrap();
+            assert!(wallet.apply(&pool, &PoolEvent::DepositAdded {
+                leaf: leaf as u64,
+                commitment: note.commitment,
+                block: Some(100),
+            }));
+            notes.push(note);
+        }
+        (wallet, notes)
+    }
+
+    #[test]
+    fn test_wallet_balances() {
+        let (mut wallet, notes) = create_wallet(&[1000, 1000, 100]);
+        let other = generate_deposit_note(ZkAssetId { block: 3, tx: 7 }, 50).unwrap();
+        wallet.add_note(other.clone()).unwrap();
+        wallet.add_note(notes[0].clone()).unwrap();
+
+        // Notes count once they are deposited
+        assert_eq!(wallet.pools().count(), 3);
+        assert_eq!(wallet.balance(&ASSET), 2100);
+        assert_eq!(wallet.balance(&other.asset_id), 0);
+
+        let pool = PoolKey::of(&notes[0]);
+        let nullifier_hash = generate_nullifier_hash(&notes[0].nullifier).unwrap();
+        assert!(wallet.apply(&pool, &PoolEvent::WithdrawalProcessed { nullifier_hash, block: Some(110) }));
+        assert!(!wallet.apply(&PoolKey::of(&other), &PoolEvent::WithdrawalProcessed { nullifier_hash, block: None }));
+
+        assert_eq!(wallet.balances(), HashMap::from([(ASSET, 1100), (other.asset_id, 0)]));
+        assert_eq!(wallet.notes(&pool).filter(|note| note.status.spent).count(), 1);
+    }
+
+    #[test]
+    fn test_select_notes() {
+        let (wallet, notes) = create_wallet(&[100, 1000, 100, 1000]);
+
+        let selected = wallet.select_notes(&ASSET, 1100).unwrap();
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].commitment, notes[1].commitment);
+        assert_eq!(selected[1].commitment, notes[0].commitment);
+
+        assert_eq!(wallet.select_notes(&ASSET, 2200).unwrap().len(), 4);
+        assert!(matches!(wallet.select_notes(&ASSET, 150), Err(ZKaneError::InvalidDenomination)));
+        assert!(matches!(wallet.select_notes(&ASSET, 3000), Err(ZKaneError::InvalidDenomination)));
+        assert!(wallet.select_notes(&ASSET, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_from_syncer() {
+        let note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000000).unwrap();
+        let mut wallet = ZkaneWallet::new();
+        let pool = wallet.add_note(note.clone()).unwrap();
+
+        let mut provider = MockProvider::new(bitcoin::Network::Regtest);
+        provider.add_response(
+            "tx_a",
+            serde_json::json!({ "vout": [ { "scriptpubkey": format!("6a{}", note.commitment.to_hex()), "value": 0 } ] }),
+        );
+        let config = ZKaneConfig::new(pool.asset_id, pool.denomination, 4, vec![]);
+        let privacy_pool = PrivacyPool::new(config, Arc::new(provider)).unwrap();
+        let mut syncer = PoolSyncer::new(privacy_pool).with_view_only(wallet.view_only(&pool));
+        syncer.sync_deposits(&["tx_a"]).await.unwrap();
+
+        assert_eq!(wallet.balance(&ASSET), 0);
+        wallet.update_from(&syncer);
+        assert_eq!(wallet.balance(&ASSET), 1000000);
+    }
+
+    #[test]
+    fn test_add_invalid_note() {
+        let mut note = generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        note.denomination = 2000;
+        assert!(matches!(ZkaneWallet::new().add_note(note), Err(ZKaneError::InvalidCommitment(_))));
+    }
+}
@@ -0,0 +1,973 @@
+//! # Withdrawal Builder
+//!
+//! Assembles the Bitcoin transaction of a withdrawal. The [`WithdrawalBuilder`]
+//! lays out the outputs, computes the outputs hash the pool checks, encodes
+//! and sizes the [`WithdrawalWitness`] envelope, estimates the fee at a given
+//! rate and returns an unsigned PSBT.
+//!
+//! Withdrawals are funded in one of two ways:
+//!
+//! - [`FundingStrategy::SelfFunded`]: the withdrawer spends their own UTXOs.
+//!   The first UTXO must be the commit output of the envelope script, so its
+//!   script-path spend carries the witness envelope. Change goes back to the
+//!   withdrawer's wallet.
+//! - [`FundingStrategy::Relayer`]: the PSBT only holds the outputs. The relayer
+//!   adds its own inputs, including the envelope input, and is paid through
+//!   its fee output. [`EnvelopeCommit`] builds the commit output and the
+//!   input revealing the envelope from it. Relayed withdrawals may be
+//!   [batched](WithdrawalBuilder::batched) into one transaction, whose
+//!   runestone is built by [`batched_withdrawal_protostone`].
+//!
+//! ```rust
+//! use bitcoin::{Amount, FeeRate, ScriptBuf, TxOut};
+//! use zkane_common::{Commitment, MerklePath, NullifierHash, Recipient, WithdrawalProof, ZkAssetId};
+//! use zkane_core::{mock_provider::MockProvider, WithdrawalBuilder};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> zkane_common::ZKaneResult<()> {
+//! let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+//! let fee_output = TxOut { value: Amount::from_sat(546), script_pubkey: ScriptBuf::from_bytes(vec![0x51]) };
+//!
+//! let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+//! let builder = WithdrawalBuilder::new(provider, ZkAssetId { block: 2, tx: 1 })
+//!     .recipient(address, Amount::from_sat(546))
+//!     .relayer(fee_output)
+//!     .fee_rate(FeeRate::from_sat_per_vb(2).unwrap());
+//!
+//! let recipient = Recipient::parse(address, bitcoin::Network::Regtest)?;
+//! let proof = WithdrawalProof::new(vec![0u8; 192], [0u8; 32], NullifierHash::new([1u8; 32]), recipient)
+//!     .bind_recipient(Amount::from_sat(546))
+//!     .with_relayer(builder.relayer_output_hash(), 100);
+//! let withdrawal = builder.build(proof, MerklePath { elements: vec![], indices: vec![] }, 0, Commitment::new([2u8; 32])).await?;
+//! assert!(withdrawal.psbt.unsigned_tx.input.is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use alkanes_support::cellpack::Cellpack;
+use bitcoin::absolute::LockTime;
+use bitcoin::blockdata::opcodes::{all::*, OP_FALSE};
+use bitcoin::psbt::{self, Psbt};
+use bitcoin::script::{Builder, PushBytes};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::transaction::Version;
+use bitcoin::{
+    Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness, XOnlyPublicKey,
+};
+#[cfg(feature = "deezel")]
+use bitcoin::Address;
+#[cfg(feature = "deezel")]
+use deezel_common::traits::{DeezelProvider, WalletProvider};
+use ordinals::Runestone;
+use protorune_support::protostone::{Protostone, Protostones};
+#[cfg(feature = "deezel")]
+use std::str::FromStr;
+#[cfg(feature = "deezel")]
+use std::sync::Arc;
+use zkane_abi::pool;
+use zkane_common::{
+    Commitment, EnvelopeFormat, MerklePath, NullifierHash, Recipient, WithdrawalProof, WithdrawalWitness, ZkAssetId,
+    ZKaneError, ZKaneResult,
+};
+#[cfg(feature = "deezel")]
+use zkane_common::calculate_outputs_hash;
+
+/// Protocol tag of alkanes protostones
+pub const ALKANES_PROTOCOL_TAG: u128 = 1;
+
+/// Smallest change output worth creating, in sats
+pub const DUST_LIMIT: u64 = 546;
+
+/// Fee rate used when none is set, 1 sat/vB
+pub const DEFAULT_FEE_RATE: FeeRate = FeeRate::from_sat_per_kwu(250);
+
+/// Largest push allowed in a tapscript
+const MAX_SCRIPT_PUSH: usize = 520;
+
+/// Protocol id pushed at the start of an alkanes envelope
+const ENVELOPE_PROTOCOL_ID: &[u8; 3] = b"BIN";
+
+/// Size of a schnorr signature with the default sighash
+const SCHNORR_SIGNATURE_SIZE: usize = 64;
+
+/// Size of the control block of a script-path spend with no sibling leaves
+const CONTROL_BLOCK_SIZE: usize = 33;
+
+/// Size of a taproot output script, the largest of the standard outputs
+/// paying a single key
+const P2TR_SCRIPT_SIZE: usize = 34;
+
+/// A UTXO spent by a self-funded withdrawal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingUtxo {
+    /// The spent output
+    pub outpoint: OutPoint,
+    /// Value and script of the spent output
+    pub txout: TxOut,
+}
+
+/// Who pays the Bitcoin fee of a withdrawal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FundingStrategy {
+    /// The withdrawer spends their own UTXOs.
+    ///
+    /// The first UTXO carries the witness envelope. Change goes to
+    /// `change_address`, or to the provider's wallet address if unset.
+    SelfFunded {
+        /// UTXOs to spend, the envelope commit output first
+        utxos: Vec<FundingUtxo>,
+        /// Where the change goes
+        change_address: Option<String>,
+    },
+    /// A relayer funds the transaction and is paid through `fee_output`.
+    Relayer {
+        /// The output paying the relayer, as committed to by the proof
+        fee_output: TxOut,
+    },
+}
+
+/// An assembled, unsigned withdrawal transaction.
+#[derive(Debug, Clone)]
+pub struct WithdrawalTransaction {
+    /// The unsigned transaction, with the spent outputs of self-funded inputs
+    pub psbt: Psbt,
+    /// The encoded witness envelope, to be revealed by the first input
+    pub envelope: Vec<u8>,
+    /// Hash of the transaction outputs, as checked by the pool
+    pub outputs_hash: [u8; 32],
+    /// Estimated size of the signed transaction the fee was set for, in vbytes
+    pub vsize: u64,
+    /// Fee paid by the transaction
+    pub fee: Amount,
+}
+
+/// Builds withdrawal transactions for a pool.
+///
+/// Outputs are laid out as the recipients in the order they were added, then
+/// the relayer fee output or the change output, then the protostone calling
+/// the pool's `Withdraw` opcode. Withdrawn alkanes go to the first recipient.
+#[cfg(feature = "deezel")]
+pub struct WithdrawalBuilder<P: DeezelProvider> {
+    provider: Arc<P>,
+    pool_id: ZkAssetId,
+    recipients: Vec<(String, Amount)>,
+    funding: Option<FundingStrategy>,
+    fee_rate: FeeRate,
+    envelope_format: EnvelopeFormat,
+    batched: bool,
+}
+
+#[cfg(feature = "deezel")]
+impl<P: DeezelProvider> WithdrawalBuilder<P> {
+    /// Create a builder for withdrawals from a pool.
+    pub fn new(provider: Arc<P>, pool_id: ZkAssetId) -> Self {
+        Self {
+            provider,
+            pool_id,
+            recipients: Vec::new(),
+            funding: None,
+            fee_rate: DEFAULT_FEE_RATE,
+            envelope_format: EnvelopeFormat::Compressed,
+            batched: false,
+        }
+    }
+
+    /// Add an output paying `value` to `address`.
+    pub fn recipient(mut self, address: impl Into<String>, value: Amount) -> Self {
+        self.recipients.push((address.into(), value));
+        self
+    }
+
+    /// Fund the withdrawal from the withdrawer's own UTXOs.
+    ///
+    /// The first UTXO must be the commit output of the envelope script.
+    pub fn self_funded(mut self, utxos: Vec<FundingUtxo>, change_address: Option<String>) -> Self {
+        self.funding = Some(FundingStrategy::SelfFunded { utxos, change_address });
+        self
+    }
+
+    /// Have a relayer fund the withdrawal, paid through `fee_output`.
+    pub fn relayer(mut self, fee_output: TxOut) -> Self {
+        self.funding = Some(FundingStrategy::Relayer { fee_output });
+        self
+    }
+
+    /// Set the fee rate.
+    pub fn fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Set the encoding of the witness envelope, compressed by default.
+    pub fn envelope_format(mut self, format: EnvelopeFormat) -> Self {
+        self.envelope_format = format;
+        self
+    }
+
+    /// Build the withdrawal as one of a relayed batch.
+    ///
+    /// The withdrawal gets no protostone of its own, and its outputs hash
+    /// only covers its recipients and the relayer fee output, the window the
+    /// pool's `WithdrawBatched` looks for. The batch transaction calls the
+    /// pool once per withdrawal, see [`batched_withdrawal_protostone`].
+    pub fn batched(mut self) -> Self {
+        self.batched = true;
+        self
+    }
+
+    /// Get the relayer output hash the withdrawal proof must commit to.
+    ///
+    /// All zeros unless the withdrawal is relayed.
+    pub fn relayer_output_hash(&self) -> [u8; 32] {
+        match &self.funding {
+            Some(FundingStrategy::Relayer { fee_output }) => calculate_outputs_hash(std::slice::from_ref(fee_output)),
+            _ => [0u8; 32],
+        }
+    }
+
+    /// Get the recipients hash the withdrawal proof may commit to.
+    ///
+    /// This is the hash of the recipient outputs, in the order they were
+    /// added. Proving against it binds a single withdrawal to all of its
+    /// recipients, for example a merchant and change back to the withdrawer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a recipient address is invalid.
+    pub fn recipients_hash(&self) -> ZKaneResult<[u8; 32]> {
+        Ok(calculate_outputs_hash(&self.recipient_outputs()?))
+    }
+
+    /// Assemble the withdrawal transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The withdrawal proof
+    /// * `path` - Merkle path of the withdrawn commitment
+    /// * `leaf_index` - Leaf index of the withdrawn commitment
+    /// * `commitment` - The withdrawn commitment
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no recipient or funding strategy was set, an
+    /// address is invalid, the proof commits to a different relayer output or
+    /// recipient set, names a recipient other than the first, the funding
+    /// UTXOs don't cover the outputs and fee, or a batched withdrawal is not
+    /// relayed.
+    pub async fn build(
+        &self,
+        proof: WithdrawalProof,
+        path: MerklePath,
+        leaf_index: u32,
+        commitment: Commitment,
+    ) -> ZKaneResult<WithdrawalTransaction> {
+        let funding = self
+            .funding
+            .as_ref()
+            .ok_or_else(|| ZKaneError::TransactionBuildFailed("no funding strategy".to_string()))?;
+        if self.recipients.is_empty() {
+            return Err(ZKaneError::TransactionBuildFailed("no recipients".to_string()));
+        }
+        if self.batched && !matches!(funding, FundingStrategy::Relayer { .. }) {
+            return Err(ZKaneError::TransactionBuildFailed(
+                "only relayed withdrawals are batched".to_string(),
+            ));
+        }
+        if proof.relayer_output_hash != self.relayer_output_hash() {
+            return Err(ZKaneError::InvalidProof(
+                "proof commits to a different relayer output".to_string(),
+            ));
+        }
+
+        let mut outputs = self.recipient_outputs()?;
+        if proof.has_recipients() && proof.recipients_hash != calculate_outputs_hash(&outputs) {
+            return Err(ZKaneError::InvalidProof(
+                "proof commits to a different recipient set".to_string(),
+            ));
+        }
+        proof.validate_recipient(&outputs)?;
+        let protostone = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: withdrawal_protostone(&self.pool_id, 0)?,
+        };
+
+        // The envelope is sized before the outputs are final. Compression
+        // never makes it larger, and an all-255 hash is the longest in JSON,
+        // so this is an upper bound on the envelope size
+        let mut witness = WithdrawalWitness {
+            proof,
+            path,
+            leaf_index,
+            commitment,
+            outputs_hash: [u8::MAX; 32],
+        };
+        let sizing_format = match self.envelope_format {
+            EnvelopeFormat::Compressed => EnvelopeFormat::Binary,
+            format => format,
+        };
+        let envelope_len = witness.to_envelope(sizing_format)?.len();
+
+        let (inputs, fee) = match funding {
+            FundingStrategy::Relayer { fee_output } => {
+                outputs.push(fee_output.clone());
+                outputs.push(protostone);
+                // Quote the fee for the envelope input the relayer will add.
+                // A batched withdrawal still pays for a protostone, as the
+                // batch carries one for it
+                let vsize = estimate_vsize(1, &outputs, envelope_len);
+                if self.batched {
+                    outputs.pop();
+                }
+                (Vec::new(), self.fee_for(vsize)?)
+            }
+            FundingStrategy::SelfFunded { utxos, change_address } => {
+                if utxos.is_empty() {
+                    return Err(ZKaneError::TransactionBuildFailed("no funding utxos".to_string()));
+                }
+                let change_address = match change_address {
+                    Some(address) => address.clone(),
+                    None => WalletProvider::get_address(&*self.provider).await?,
+                };
+                let change_script = self.parse_address(&change_address)?;
+
+                let available = utxos.iter().map(|utxo| utxo.txout.value).sum::<Amount>();
+                let spent = outputs.iter().map(|output| output.value).sum::<Amount>();
+
+                let mut with_change = outputs.clone();
+                with_change.push(TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: change_script,
+                });
+                with_change.push(protostone.clone());
+                let fee = self.fee_for(estimate_vsize(utxos.len(), &with_change, envelope_len))?;
+
+                match available.checked_sub(spent + fee) {
+                    Some(change) if change.to_sat() >= DUST_LIMIT => {
+                        let change_index = with_change.len() - 2;
+                        with_change[change_index].value = change;
+                        outputs = with_change;
+                        (utxos.clone(), fee)
+                    }
+                    _ => {
+                        // Change would be dust, so it goes to the fee instead
+                        outputs.push(protostone);
+                        let fee = self.fee_for(estimate_vsize(utxos.len(), &outputs, envelope_len))?;
+                        if available < spent + fee {
+                            return Err(ZKaneError::TransactionBuildFailed(format!(
+                                "insufficient funds: need {}, have {}",
+                                spent + fee,
+                                available
+                            )));
+                        }
+                        (utxos.clone(), available - spent)
+                    }
+                }
+            }
+        };
+
+        witness.outputs_hash = calculate_outputs_hash(&outputs);
+        let envelope = witness.to_envelope(self.envelope_format)?;
+        let vsize = estimate_vsize(inputs.len().max(1), &outputs, envelope_len);
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: unsigned_inputs(&inputs),
+            output: outputs,
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx)
+            .map_err(|e| ZKaneError::TransactionBuildFailed(e.to_string()))?;
+        for (input, utxo) in psbt.inputs.iter_mut().zip(&inputs) {
+            input.witness_utxo = Some(utxo.txout.clone());
+        }
+
+        Ok(WithdrawalTransaction {
+            psbt,
+            envelope,
+            outputs_hash: witness.outputs_hash,
+            vsize,
+            fee,
+        })
+    }
+
+    fn recipient_outputs(&self) -> ZKaneResult<Vec<TxOut>> {
+        self.recipients
+            .iter()
+            .map(|(address, value)| {
+                Ok(TxOut {
+                    value: *value,
+                    script_pubkey: self.parse_address(address)?,
+                })
+            })
+            .collect()
+    }
+
+    fn parse_address(&self, address: &str) -> ZKaneResult<ScriptBuf> {
+        parse_address(address, self.provider.get_network())
+    }
+
+    fn fee_for(&self, vsize: u64) -> ZKaneResult<Amount> {
+        self.fee_rate
+            .fee_vb(vsize)
+            .ok_or_else(|| ZKaneError::TransactionBuildFailed("fee overflow".to_string()))
+    }
+}
+
+/// Build the protostone output script calling a pool's `Withdraw` opcode.
+///
+/// # Arguments
+///
+/// * `pool_id` - The pool contract
+/// * `pointer` - Index of the output receiving the withdrawn alkanes
+pub fn withdrawal_protostone(pool_id: &ZkAssetId, pointer: u32) -> ZKaneResult<ScriptBuf> {
+    call_protostone(pool_id, vec![pool::WITHDRAW], pointer, None)
+}
+
+/// Build the protostone output script of a batch of withdrawals.
+///
+/// The runestone holds one protostone per withdrawal, in order, each calling
+/// the pool's `WithdrawBatched` with the index of the input revealing that
+/// withdrawal's envelope. Withdrawal `i` is paid out to output `pointers[i]`,
+/// the first of its outputs.
+pub fn batched_withdrawal_protostone(pool_id: &ZkAssetId, pointers: &[u32]) -> ZKaneResult<ScriptBuf> {
+    let protostones = pointers
+        .iter()
+        .enumerate()
+        .map(|(input, &pointer)| pool_call(pool_id, vec![pool::WITHDRAW_BATCHED, input as u128], pointer))
+        .collect();
+    runestone(protostones, None)
+}
+
+/// Build a runestone with a single protostone calling a pool with `inputs`,
+/// the opcode first.
+///
+/// `pointer` receives whatever the call returns or refunds. With
+/// `runestone_pointer` set, the alkanes of the inputs go to that output
+/// instead of the first one.
+pub(crate) fn call_protostone(
+    pool_id: &ZkAssetId,
+    inputs: Vec<u128>,
+    pointer: u32,
+    runestone_pointer: Option<u32>,
+) -> ZKaneResult<ScriptBuf> {
+    runestone(vec![pool_call(pool_id, inputs, pointer)], runestone_pointer)
+}
+
+/// Build a protostone calling a pool with `inputs`, returning and refunding
+/// to `pointer`.
+fn pool_call(pool_id: &ZkAssetId, inputs: Vec<u128>, pointer: u32) -> Protostone {
+    let cellpack = Cellpack {
+        target: (*pool_id).into(),
+        inputs,
+    };
+    Protostone {
+        burn: None,
+        message: cellpack.encipher(),
+        edicts: vec![],
+        refund: Some(pointer),
+        pointer: Some(pointer),
+        from: None,
+        protocol_tag: ALKANES_PROTOCOL_TAG,
+    }
+}
+
+/// Build the output script of a runestone carrying `protostones`.
+fn runestone(protostones: Vec<Protostone>, pointer: Option<u32>) -> ZKaneResult<ScriptBuf> {
+    let protocol = protostones
+        .encipher()
+        .map_err(|e| ZKaneError::TransactionBuildFailed(e.to_string()))?;
+    Ok(Runestone {
+        pointer,
+        protocol: Some(protocol),
+        ..Default::default()
+    }
+    .encipher())
+}
+
+/// Parse an address for the network and get its script.
+#[cfg(feature = "deezel")]
+pub(crate) fn parse_address(address: &str, network: bitcoin::Network) -> ZKaneResult<ScriptBuf> {
+    let parsed = Address::from_str(address)
+        .and_then(|parsed| parsed.require_network(network))
+        .map_err(|e| ZKaneError::TransactionBuildFailed(format!("invalid address {}: {}", address, e)))?;
+    Ok(parsed.script_pubkey())
+}
+
+/// Build the inputs of an unsigned transaction spending the UTXOs.
+#[cfg(feature = "deezel")]
+pub(crate) fn unsigned_inputs(utxos: &[FundingUtxo]) -> Vec<TxIn> {
+    utxos
+        .iter()
+        .map(|utxo| TxIn {
+            previous_output: utxo.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        })
+        .collect()
+}
+
+/// Build the envelope script revealing `payload`, locked to `key`.
+///
+/// The script is laid out as sized by [`envelope_script_size`].
+pub fn envelope_script(key: &XOnlyPublicKey, payload: &[u8]) -> ScriptBuf {
+    let mut builder = Builder::new()
+        .push_x_only_key(key)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF)
+        .push_slice(ENVELOPE_PROTOCOL_ID)
+        .push_opcode(OP_PUSHBYTES_0);
+    for chunk in payload.chunks(MAX_SCRIPT_PUSH) {
+        // Chunks are no longer than a push may be
+        builder = builder.push_slice(<&PushBytes>::try_from(chunk).unwrap());
+    }
+    builder.push_opcode(OP_ENDIF).into_script()
+}
+
+/// The taproot output committing to an envelope script.
+///
+/// A relayed withdrawal reveals its witness envelope by spending this output
+/// through the script path. The relayer first pays the output in a commit
+/// transaction, then adds [`EnvelopeCommit::reveal_input`] to the withdrawal.
+#[derive(Debug, Clone)]
+pub struct EnvelopeCommit {
+    /// The envelope script
+    pub script: ScriptBuf,
+    /// Script of the commit output
+    pub script_pubkey: ScriptBuf,
+    /// Control block of the script-path spend
+    pub control_block: ControlBlock,
+    spend_info: TaprootSpendInfo,
+}
+
+impl EnvelopeCommit {
+    /// Commit to the envelope revealing `payload`, spendable by `key`.
+    ///
+    /// `key` is both the internal key of the output and the key the envelope
+    /// script checks the reveal's signature against.
+    pub fn new(key: XOnlyPublicKey, payload: &[u8]) -> ZKaneResult<Self> {
+        let script = envelope_script(&key, payload);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, script.clone())
+            .map_err(|e| ZKaneError::TransactionBuildFailed(e.to_string()))?
+            .finalize(&Secp256k1::verification_only(), key)
+            .map_err(|_| ZKaneError::TransactionBuildFailed("incomplete envelope tree".to_string()))?;
+        let control_block = spend_info
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .ok_or_else(|| ZKaneError::TransactionBuildFailed("envelope script not in tree".to_string()))?;
+        Ok(Self {
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            script,
+            control_block,
+            spend_info,
+        })
+    }
+
+    /// Build the input revealing the envelope by spending the commit output
+    /// `utxo`.
+    ///
+    /// The PSBT input carries the script and control block, so a signer adds
+    /// a script-path signature that [`finalize_psbt`](crate::signer::finalize_psbt)
+    /// turns into the envelope witness.
+    pub fn reveal_input(&self, utxo: &FundingUtxo) -> (TxIn, psbt::Input) {
+        let mut input = psbt::Input {
+            witness_utxo: Some(utxo.txout.clone()),
+            tap_internal_key: Some(self.spend_info.internal_key()),
+            tap_merkle_root: self.spend_info.merkle_root(),
+            ..Default::default()
+        };
+        input
+            .tap_scripts
+            .insert(self.control_block.clone(), (self.script.clone(), LeafVersion::TapScript));
+        let txin = TxIn {
+            previous_output: utxo.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        };
+        (txin, input)
+    }
+}
+
+/// Size in bytes of the envelope script revealing a payload.
+///
+/// The script is `<pubkey> OP_CHECKSIG OP_FALSE OP_IF "BIN" OP_0 <payload
+/// chunks> OP_ENDIF`, with the payload split into pushes of at most 520 bytes.
+pub fn envelope_script_size(payload_len: usize) -> usize {
+    let chunks = payload_len.div_ceil(MAX_SCRIPT_PUSH);
+    let payload = (0..chunks)
+        .map(|i| push_size(MAX_SCRIPT_PUSH.min(payload_len - i * MAX_SCRIPT_PUSH)))
+        .sum::<usize>();
+    push_size(32) + 1 + 1 + 1 + push_size(ENVELOPE_PROTOCOL_ID.len()) + 1 + payload + 1
+}
+
+/// Estimate the size in vbytes of a signed withdrawal transaction.
+///
+/// The first input is a script-path spend revealing an envelope of
+/// `envelope_len` bytes; any further inputs are taproot key-path spends.
+pub fn estimate_vsize(inputs: usize, outputs: &[TxOut], envelope_len: usize) -> u64 {
+    estimate_weight(inputs, outputs, envelope_len).to_vbytes_ceil()
+}
+
+/// Estimate the weight of a signed withdrawal transaction, laid out as for
+/// [`estimate_vsize`].
+pub fn estimate_weight(inputs: usize, outputs: &[TxOut], envelope_len: usize) -> Weight {
+    let input = (0..inputs)
+        .map(|i| {
+            let witness = if i == 0 {
+                Witness::from_slice(&[
+                    vec![0u8; SCHNORR_SIGNATURE_SIZE],
+                    vec![0u8; envelope_script_size(envelope_len)],
+                    vec![0u8; CONTROL_BLOCK_SIZE],
+                ])
+            } else {
+                Witness::from_slice(&[vec![0u8; SCHNORR_SIGNATURE_SIZE]])
+            };
+            TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness,
+            }
+        })
+        .collect();
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input,
+        output: outputs.to_vec(),
+    };
+    tx.weight()
+}
+
+/// The modelled size and fee of a withdrawal transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawalFeeEstimate {
+    /// Size of the binary witness envelope, in bytes
+    pub envelope_len: usize,
+    /// Weight of the signed transaction
+    pub weight: Weight,
+    /// Size of the signed transaction, in vbytes
+    pub vsize: u64,
+    /// Fee at the requested rate
+    pub fee: Amount,
+}
+
+/// Estimate the fee of a withdrawal before its proof exists.
+///
+/// Models the whole signed transaction: the script-path input revealing the
+/// witness envelope of a `proof_size`-byte proof with a `path_len`-high Merkle
+/// path, `n_outputs` taproot outputs and the withdrawal protostone. Count the
+/// recipients, the relayer fee output and any change in `n_outputs`. The
+/// envelope is sized in the binary format, which compression never makes
+/// larger, and the protostone for a pool ID of up to 64-bit parts, so the
+/// estimate doesn't fall short of what [`WithdrawalBuilder`] builds with one
+/// funding input. Each further key-path input adds 57.5 vbytes.
+///
+/// # Errors
+///
+/// Returns an error if `path_len` is higher than
+/// [`MAX_ENCODED_PATH_HEIGHT`](zkane_common::MAX_ENCODED_PATH_HEIGHT) or the
+/// fee overflows.
+pub fn estimate_withdrawal_fee(
+    proof_size: usize,
+    path_len: usize,
+    n_outputs: usize,
+    fee_rate: FeeRate,
+) -> ZKaneResult<WithdrawalFeeEstimate> {
+    let script_pubkey = ScriptBuf::from_bytes(vec![0u8; P2TR_SCRIPT_SIZE]);
+    let witness = WithdrawalWitness {
+        proof: WithdrawalProof::new(
+            vec![0u8; proof_size],
+            [0u8; 32],
+            NullifierHash::new([0u8; 32]),
+            Recipient::new(script_pubkey.clone()),
+        ),
+        path: MerklePath {
+            elements: vec![[0u8; 32]; path_len],
+            indices: vec![false; path_len],
+        },
+        leaf_index: 0,
+        commitment: Commitment::new([0u8; 32]),
+        outputs_hash: [0u8; 32],
+    };
+    let envelope_len = witness.to_envelope(EnvelopeFormat::Binary)?.len();
+
+    let largest_pool = ZkAssetId {
+        block: u64::MAX.into(),
+        tx: u64::MAX.into(),
+    };
+    let mut outputs = vec![
+        TxOut {
+            value: Amount::ZERO,
+            script_pubkey,
+        };
+        n_outputs
+    ];
+    outputs.push(TxOut {
+        value: Amount::ZERO,
+        script_pubkey: withdrawal_protostone(&largest_pool, 0)?,
+    });
+
+    let weight = estimate_weight(1, &outputs, envelope_len);
+    let vsize = weight.to_vbytes_ceil();
+    let fee = fee_rate
+        .fee_vb(vsize)
+        .ok_or_else(|| ZKaneError::TransactionBuildFailed("fee overflow".to_string()))?;
+    Ok(WithdrawalFeeEstimate {
+        envelope_len,
+        weight,
+        vsize,
+        fee,
+    })
+}
+
+fn push_size(len: usize) -> usize {
+    let prefix = match len {
+        0..=75 => 1,
+        76..=255 => 2,
+        _ => 3,
+    };
+    prefix + len
+}
+
+#[cfg(all(test, feature = "deezel"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use bitcoin::hashes::Hash;
+    use zkane_common::MAX_ENCODED_PATH_HEIGHT;
+
+    const RECIPIENT: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn create_builder() -> WithdrawalBuilder<MockProvider> {
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        WithdrawalBuilder::new(provider, ZkAssetId { block: 2, tx: 1 })
+            .recipient(RECIPIENT, Amount::from_sat(546))
+            .fee_rate(FeeRate::from_sat_per_vb(2).unwrap())
+    }
+
+    fn change_address() -> String {
+        Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest).to_string()
+    }
+
+    fn fee_output() -> TxOut {
+        TxOut {
+            value: Amount::from_sat(546),
+            script_pubkey: ScriptBuf::from_bytes([vec![0x00, 0x14], vec![0x11; 20]].concat()),
+        }
+    }
+
+    fn proof() -> WithdrawalProof {
+        WithdrawalProof::new(vec![7u8; 192], [3u8; 32], NullifierHash::new([1u8; 32]), Recipient::default())
+    }
+
+    fn path() -> MerklePath {
+        MerklePath::new(vec![[5u8; 32]; 20], vec![false; 20]).unwrap()
+    }
+
+    fn utxo(vout: u32, sats: u64) -> FundingUtxo {
+        FundingUtxo {
+            outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), vout),
+            txout: TxOut {
+                value: Amount::from_sat(sats),
+                script_pubkey: ScriptBuf::from_bytes([vec![0x51, 0x20], vec![0x22; 32]].concat()),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relayed_withdrawal() {
+        let builder = create_builder().relayer(fee_output());
+        let proof = proof().with_relayer(builder.relayer_output_hash(), 100);
+        let withdrawal = builder.build(proof, path(), 9, Commitment::new([2u8; 32])).await.unwrap();
+
+        let tx = &withdrawal.psbt.unsigned_tx;
+        assert!(tx.input.is_empty());
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(tx.output[1], fee_output());
+        assert!(tx.output[2].script_pubkey.is_op_return());
+        assert_eq!(withdrawal.outputs_hash, calculate_outputs_hash(&tx.output));
+
+        let witness = WithdrawalWitness::from_envelope(&withdrawal.envelope).unwrap();
+        assert_eq!(witness.outputs_hash, withdrawal.outputs_hash);
+        assert_eq!(witness.leaf_index, 9);
+        assert_eq!(withdrawal.fee, Amount::from_sat(withdrawal.vsize * 2));
+    }
+
+    #[tokio::test]
+    async fn test_batched_withdrawal() {
+        let builder = create_builder().relayer(fee_output()).batched();
+        let proof = proof().with_relayer(builder.relayer_output_hash(), 100);
+        let withdrawal = builder.build(proof.clone(), path(), 9, Commitment::new([2u8; 32])).await.unwrap();
+
+        // The recipient and the relayer fee, the batch adds the protostone
+        let tx = &withdrawal.psbt.unsigned_tx;
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[1], fee_output());
+        assert_eq!(withdrawal.outputs_hash, calculate_outputs_hash(&tx.output));
+        let witness = WithdrawalWitness::from_envelope(&withdrawal.envelope).unwrap();
+        assert_eq!(witness.outputs_hash, withdrawal.outputs_hash);
+
+        // Quoted as if it had its own protostone
+        let single = create_builder().relayer(fee_output());
+        let single = single.build(proof.clone(), path(), 9, Commitment::new([2u8; 32])).await.unwrap();
+        assert_eq!(withdrawal.fee, single.fee);
+
+        let builder = create_builder().self_funded(vec![utxo(0, 20_000)], Some(change_address())).batched();
+        let result = builder.build(proof, path(), 9, Commitment::new([2u8; 32])).await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_self_funded_withdrawal() {
+        let builder = create_builder().self_funded(vec![utxo(0, 20_000), utxo(1, 5_000)], Some(change_address()));
+        let withdrawal = builder.build(proof(), path(), 0, Commitment::new([2u8; 32])).await.unwrap();
+
+        let tx = &withdrawal.psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(withdrawal.psbt.inputs[1].witness_utxo, Some(utxo(1, 5_000).txout));
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(tx.output[2].value, Amount::ZERO);
+
+        // Everything not sent to the recipient is change or fee
+        let change = tx.output[1].value;
+        assert_eq!(Amount::from_sat(546) + change + withdrawal.fee, Amount::from_sat(25_000));
+        assert_eq!(withdrawal.fee, Amount::from_sat(withdrawal.vsize * 2));
+        assert_eq!(withdrawal.outputs_hash, calculate_outputs_hash(&tx.output));
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_to_multiple_recipients() {
+        let builder = create_builder()
+            .recipient(change_address(), Amount::from_sat(1_000))
+            .relayer(fee_output());
+        let proof = proof()
+            .with_relayer(builder.relayer_output_hash(), 100)
+            .with_recipients(&builder.recipient_outputs().unwrap());
+        assert_eq!(proof.recipients_hash, builder.recipients_hash().unwrap());
+
+        let withdrawal = builder.build(proof.clone(), path(), 0, Commitment::new([2u8; 32])).await.unwrap();
+        let tx = &withdrawal.psbt.unsigned_tx;
+        assert_eq!(tx.output.len(), 4);
+        assert!(proof.pays_recipients(&tx.output));
+
+        // The recipients can't be changed after proving
+        let other = create_builder().recipient(RECIPIENT, Amount::from_sat(1_000)).relayer(fee_output());
+        let result = other.build(proof, path(), 0, Commitment::new([2u8; 32])).await;
+        assert!(matches!(result, Err(ZKaneError::InvalidProof(_))));
+    }
+
+    #[tokio::test]
+    async fn test_dust_change_goes_to_fee() {
+        let envelope_len = WithdrawalWitness {
+            proof: proof(),
+            path: path(),
+            leaf_index: 0,
+            commitment: Commitment::new([2u8; 32]),
+            outputs_hash: [0u8; 32],
+        }
+        .to_envelope(EnvelopeFormat::Binary)
+        .unwrap()
+        .len();
+        let builder = create_builder().envelope_format(EnvelopeFormat::Binary);
+        let outputs = [
+            TxOut { value: Amount::from_sat(546), script_pubkey: builder.parse_address(RECIPIENT).unwrap() },
+            TxOut { value: Amount::ZERO, script_pubkey: withdrawal_protostone(&builder.pool_id, 0).unwrap() },
+        ];
+        let fee = estimate_vsize(1, &outputs, envelope_len) * 2;
+
+        let builder = builder.self_funded(vec![utxo(0, 546 + fee + 100)], Some(change_address()));
+        let withdrawal = builder.build(proof(), path(), 0, Commitment::new([2u8; 32])).await.unwrap();
+        assert_eq!(withdrawal.psbt.unsigned_tx.output.len(), 2);
+        assert_eq!(withdrawal.fee, Amount::from_sat(fee + 100));
+    }
+
+    #[tokio::test]
+    async fn test_build_errors() {
+        let commitment = Commitment::new([2u8; 32]);
+
+        let result = create_builder().build(proof(), path(), 0, commitment).await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+
+        // The proof must pay the relayer the builder pays
+        let result = create_builder().relayer(fee_output()).build(proof(), path(), 0, commitment).await;
+        assert!(matches!(result, Err(ZKaneError::InvalidProof(_))));
+
+        let builder = create_builder().self_funded(vec![utxo(0, 1_000)], Some(change_address()));
+        let result = builder.build(proof(), path(), 0, commitment).await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+
+        let builder = create_builder()
+            .recipient("not an address", Amount::from_sat(546))
+            .self_funded(vec![utxo(0, 20_000)], Some(change_address()));
+        let result = builder.build(proof(), path(), 0, commitment).await;
+        assert!(matches!(result, Err(ZKaneError::TransactionBuildFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_fee_estimate() {
+        let builder = create_builder().relayer(fee_output());
+        let proof = proof().with_relayer(builder.relayer_output_hash(), 100);
+        let withdrawal = builder.build(proof, path(), 0, Commitment::new([2u8; 32])).await.unwrap();
+
+        // The recipient and the relayer fee output
+        let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+        let estimate = estimate_withdrawal_fee(192, 20, 2, fee_rate).unwrap();
+        assert_eq!(estimate.vsize, estimate.weight.to_vbytes_ceil());
+        assert_eq!(estimate.fee, Amount::from_sat(estimate.vsize * 2));
+        // Never short of the built transaction, and not far above it
+        assert!(estimate.vsize >= withdrawal.vsize);
+        assert!(estimate.vsize - withdrawal.vsize < 64);
+
+        // Each output and proof byte is paid for
+        let more_outputs = estimate_withdrawal_fee(192, 20, 3, fee_rate).unwrap();
+        assert_eq!(more_outputs.weight - estimate.weight, Weight::from_non_witness_data_size(43));
+        let larger_proof = estimate_withdrawal_fee(192 + 520, 20, 2, fee_rate).unwrap();
+        assert_eq!(larger_proof.envelope_len, estimate.envelope_len + 520);
+
+        assert!(estimate_withdrawal_fee(192, MAX_ENCODED_PATH_HEIGHT + 1, 2, fee_rate).is_err());
+    }
+
+    #[test]
+    fn test_envelope_commit() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let secret = bitcoin::secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let (key, _) = secret.x_only_public_key(&secp);
+
+        for payload_len in [0, 32, 520, 1041] {
+            let payload = vec![7u8; payload_len];
+            let commit = EnvelopeCommit::new(key, &payload).unwrap();
+            assert_eq!(commit.script.len(), envelope_script_size(payload_len));
+            assert_eq!(commit.control_block.size(), CONTROL_BLOCK_SIZE);
+            assert!(commit.script_pubkey.is_p2tr());
+        }
+
+        let commit = EnvelopeCommit::new(key, &[7u8; 100]).unwrap();
+        let (txin, input) = commit.reveal_input(&utxo(3, 10_000));
+        assert_eq!(txin.previous_output, utxo(3, 10_000).outpoint);
+        assert_eq!(input.tap_internal_key, Some(key));
+        assert_eq!(input.tap_scripts.get(&commit.control_block), Some(&(commit.script.clone(), LeafVersion::TapScript)));
+        // The commit output is spendable through the envelope script
+        let output_key = XOnlyPublicKey::from_slice(&commit.script_pubkey.as_bytes()[2..]).unwrap();
+        assert!(commit.control_block.verify_taproot_commitment(&secp, output_key, &commit.script));
+    }
+
+    #[test]
+    fn test_envelope_sizing() {
+        // Key, OP_CHECKSIG, OP_FALSE, OP_IF, "BIN", OP_0, OP_ENDIF around the payload
+        assert_eq!(envelope_script_size(0), 33 + 1 + 1 + 1 + 4 + 1 + 1);
+        assert_eq!(envelope_script_size(32), envelope_script_size(0) + 33);
+        // Each 520-byte chunk takes an OP_PUSHDATA2 push
+        assert_eq!(envelope_script_size(1040), envelope_script_size(0) + 2 * 523);
+        assert_eq!(envelope_script_size(1041), envelope_script_size(0) + 2 * 523 + 2);
+
+        // Witness data is discounted, so a bigger envelope adds a quarter of its size
+        let outputs = [fee_output()];
+        let small = estimate_vsize(1, &outputs, 520);
+        let large = estimate_vsize(1, &outputs, 520 * 5);
+        assert!((522..=524).contains(&(large - small)));
+    }
+}
@@ -0,0 +1,123 @@
+//! Benchmarks for the ZKane cryptographic primitives
+//!
+//! Run with `cargo bench -p zkane-crypto`; add `--features parallel` to
+//! measure bulk tree construction on the rayon thread pool.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use zkane_common::{Commitment, Nullifier, Secret, ZkAssetId};
+use zkane_crypto::zkp::{self, WithdrawalCircuit};
+use zkane_crypto::{generate_commitment, poseidon_hash_two, MerkleTree};
+
+/// Deterministic, distinct commitments for tree benchmarks
+fn commitments(count: usize) -> Vec<Commitment> {
+    (0..count as u32)
+        .map(|i| {
+            let mut bytes = [0u8; 32];
+            bytes[..4].copy_from_slice(&i.to_le_bytes());
+            Commitment::new(bytes)
+        })
+        .collect()
+}
+
+fn bench_poseidon(c: &mut Criterion) {
+    let left = [1u8; 32];
+    let right = [2u8; 32];
+    c.bench_function("poseidon_hash_two", |b| {
+        b.iter(|| poseidon_hash_two(black_box(&left), black_box(&right)).unwrap())
+    });
+}
+
+fn bench_tree_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_construction");
+    group.sample_size(10);
+    for count in [1_000usize, 10_000] {
+        let leaves = commitments(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("insert", count), &leaves, |b, leaves| {
+            b.iter(|| {
+                let mut tree = MerkleTree::new(20);
+                for commitment in leaves {
+                    tree.insert(commitment).unwrap();
+                }
+                tree
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("from_leaves", count), &leaves, |b, leaves| {
+            b.iter(|| MerkleTree::from_leaves(20, leaves).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_single_insert(c: &mut Criterion) {
+    let tree = MerkleTree::from_leaves(20, &commitments(1_000)).unwrap();
+    let commitment = Commitment::new([0xff; 32]);
+    c.bench_function("tree_insert_single", |b| {
+        b.iter_batched(
+            || tree.clone(),
+            |mut tree| tree.insert(black_box(&commitment)).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_path_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_path");
+    let leaves = commitments(1_024);
+    for height in [16u32, 20, 24] {
+        let tree = MerkleTree::from_leaves(height, &leaves).unwrap();
+        let root = tree.root();
+        group.bench_with_input(BenchmarkId::new("generate", height), &tree, |b, tree| {
+            b.iter(|| tree.generate_path(black_box(517)).unwrap())
+        });
+        let path = tree.generate_path(517).unwrap();
+        group.bench_with_input(BenchmarkId::new("verify", height), &tree, |b, tree| {
+            b.iter(|| tree.verify_path(&leaves[517], 517, black_box(&path), &root).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_withdrawal_proof(c: &mut Criterion) {
+    let (pk, _) = zkp::setup();
+    let secret = [3u8; 32];
+    let nullifier = [4u8; 32];
+    let recipients_hash = [6u8; 32];
+    let relayer_output_hash = [5u8; 32];
+    let asset_id = ZkAssetId { block: 2, tx: 1 };
+
+    let mut group = c.benchmark_group("withdrawal_proof");
+    group.sample_size(10);
+    group.bench_function("prove", |b| {
+        b.iter_batched(
+            || WithdrawalCircuit::from_note(&secret, &nullifier, &asset_id, 100_000, &recipients_hash, &relayer_output_hash, 1_000, 0).unwrap(),
+            |circuit| zkp::prove(&pk, circuit),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_commitment_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("commitment_generation");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("generate_commitment", |b| {
+        b.iter_batched(
+            || (Nullifier::random(), Secret::random()),
+            |(nullifier, secret)| generate_commitment(&nullifier, &secret).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_poseidon,
+    bench_tree_construction,
+    bench_single_insert,
+    bench_path_generation,
+    bench_withdrawal_proof,
+    bench_commitment_generation,
+);
+criterion_main!(benches);
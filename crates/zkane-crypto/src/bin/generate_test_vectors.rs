@@ -0,0 +1,42 @@
+//! Generates the Rust <-> Noir hash test vectors.
+//!
+//! Usage: `generate-test-vectors [--seed N] [--count N] [--json PATH] [--noir PATH]`
+//!
+//! Without `--json` the JSON vectors are printed to stdout.
+
+use anyhow::{anyhow, Result};
+use zkane_crypto::test_vectors::{TestVectors, DEFAULT_COUNT, DEFAULT_SEED};
+
+fn main() -> Result<()> {
+    let mut seed = DEFAULT_SEED;
+    let mut count = DEFAULT_COUNT;
+    let mut json_path = None;
+    let mut noir_path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("missing value for {}", arg));
+        match arg.as_str() {
+            "--seed" => seed = value()?.parse()?,
+            "--count" => count = value()?.parse()?,
+            "--json" => json_path = Some(value()?),
+            "--noir" => noir_path = Some(value()?),
+            _ => return Err(anyhow!("unknown argument {}", arg)),
+        }
+    }
+
+    let vectors = TestVectors::generate(seed, count)?;
+    vectors.verify()?;
+
+    let json = vectors.to_json()? + "\n";
+    match json_path {
+        Some(path) => std::fs::write(path, json)?,
+        None => print!("{}", json),
+    }
+
+    if let Some(path) = noir_path {
+        std::fs::write(path, vectors.to_noir())?;
+    }
+
+    Ok(())
+}
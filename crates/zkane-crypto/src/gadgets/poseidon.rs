@@ -37,4 +37,14 @@ impl PoseidonGadget {
         let input = &[input.clone()];
         CRHGadget::evaluate(params, input)
     }
-}
\ No newline at end of file
+
+    /// Hashes four field elements.
+    pub fn hash_four<F: PrimeField + Absorb>(
+        _cs: ConstraintSystemRef<F>,
+        params: &CRHParametersVar<F>,
+        inputs: [&FpVar<F>; 4],
+    ) -> Result<FpVar<F>, SynthesisError> {
+        let input = inputs.map(|input| input.clone());
+        CRHGadget::evaluate(params, &input)
+    }
+}
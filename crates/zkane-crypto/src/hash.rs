@@ -2,6 +2,8 @@
 
 use sha2::{Digest, Sha256};
 use blake2::{Blake2b512, Blake2s256};
+use zkane_common::TreeHash;
+use crate::poseidon::{poseidon_hash_single, poseidon_hash_two, PoseidonConfig};
 
 /// SHA-256 hash function
 pub fn sha256(input: &[u8]) -> [u8; 32] {
@@ -51,6 +53,139 @@ pub fn hash_internal(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
     blake2s(&input)
 }
 
+/// Hash function of a [`MerkleTree`](crate::MerkleTree).
+///
+/// Leaves and internal nodes are hashed differently, so a leaf can't be
+/// passed off as an internal node. [`TreeHash`] itself implements the trait,
+/// dispatching to the backend it names, for trees whose hash is only known
+/// from a pool's configuration.
+pub trait HashFunction: Clone + Send + Sync {
+    /// The configuration value naming this backend
+    fn kind(&self) -> TreeHash;
+
+    /// Hash a leaf value
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32];
+
+    /// Hash two child nodes into their parent
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32];
+}
+
+/// Blake2s tree hash, see [`hash_leaf`] and [`hash_internal`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Blake2sHash;
+
+impl HashFunction for Blake2sHash {
+    fn kind(&self) -> TreeHash {
+        TreeHash::Blake2s
+    }
+
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        hash_leaf(leaf)
+    }
+
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        hash_internal(left, right)
+    }
+}
+
+/// Poseidon tree hash, matching Noir's `hash_1` for leaves and `hash_2` for
+/// internal nodes
+///
+/// The arity separates leaves from internal nodes. Much slower than the
+/// other backends outside of circuits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoseidonHash;
+
+impl HashFunction for PoseidonHash {
+    fn kind(&self) -> TreeHash {
+        TreeHash::Poseidon
+    }
+
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        poseidon_hash_single(leaf).expect("arity 1 is supported")
+    }
+
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        poseidon_hash_two(left, right).expect("arity 2 is supported")
+    }
+}
+
+/// Poseidon tree hash over BLS12-381, with the parameters of the arkworks
+/// circuits in [`crate::zkp`]
+///
+/// Like [`PoseidonHash`], leaves are hashed with arity 1 and internal nodes
+/// with arity 2. Trees hashed this way can be proven against in a circuit,
+/// as deposit receipts do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoseidonBls12Hash;
+
+impl HashFunction for PoseidonBls12Hash {
+    fn kind(&self) -> TreeHash {
+        TreeHash::PoseidonBls12_381
+    }
+
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        PoseidonConfig::bls12_381(1)
+            .and_then(|config| config.hash(&[*leaf]))
+            .expect("arity 1 is supported")
+    }
+
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        PoseidonConfig::bls12_381(2)
+            .and_then(|config| config.hash(&[*left, *right]))
+            .expect("arity 2 is supported")
+    }
+}
+
+/// SHA-256 tree hash, with the same 0x00/0x01 prefixes as [`Blake2sHash`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hash;
+
+impl HashFunction for Sha256Hash {
+    fn kind(&self) -> TreeHash {
+        TreeHash::Sha256
+    }
+
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update([0x00]);
+        hasher.update(leaf);
+        hasher.finalize().into()
+    }
+
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update([0x01]);
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().into()
+    }
+}
+
+impl HashFunction for TreeHash {
+    fn kind(&self) -> TreeHash {
+        *self
+    }
+
+    fn hash_leaf(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        match self {
+            TreeHash::Blake2s => Blake2sHash.hash_leaf(leaf),
+            TreeHash::Poseidon => PoseidonHash.hash_leaf(leaf),
+            TreeHash::Sha256 => Sha256Hash.hash_leaf(leaf),
+            TreeHash::PoseidonBls12_381 => PoseidonBls12Hash.hash_leaf(leaf),
+        }
+    }
+
+    fn hash_internal(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        match self {
+            TreeHash::Blake2s => Blake2sHash.hash_internal(left, right),
+            TreeHash::Poseidon => PoseidonHash.hash_internal(left, right),
+            TreeHash::Sha256 => Sha256Hash.hash_internal(left, right),
+            TreeHash::PoseidonBls12_381 => PoseidonBls12Hash.hash_internal(left, right),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +240,20 @@ mod tests {
         // Leaf and internal hashes should be different even with same input
         assert_ne!(leaf_hash, internal_hash);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tree_hash_backends() {
+        let leaf = [1u8; 32];
+        assert_eq!(Blake2sHash.hash_leaf(&leaf), hash_leaf(&leaf));
+        assert_eq!(Sha256Hash.hash_leaf(&leaf), sha256(&[&[0x00][..], &leaf[..]].concat()));
+        assert_eq!(PoseidonHash.hash_internal(&leaf, &leaf), poseidon_hash_two(&leaf, &leaf).unwrap());
+
+        let backends = [TreeHash::Blake2s, TreeHash::Poseidon, TreeHash::Sha256, TreeHash::PoseidonBls12_381];
+        for backend in backends {
+            assert_eq!(backend.kind(), backend);
+            assert_ne!(backend.hash_leaf(&leaf), backend.hash_internal(&leaf, &leaf));
+        }
+        assert_eq!(TreeHash::Sha256.hash_leaf(&leaf), Sha256Hash.hash_leaf(&leaf));
+        assert_ne!(TreeHash::Sha256.hash_leaf(&leaf), TreeHash::Blake2s.hash_leaf(&leaf));
+    }
+}
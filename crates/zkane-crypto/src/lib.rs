@@ -10,7 +10,10 @@
 //!
 //! - **Poseidon Hash Function**: A zero-knowledge friendly hash function optimized for
 //!   use in arithmetic circuits
-//! - **Merkle Trees**: Binary trees for efficient commitment storage and inclusion proofs
+//! - **Merkle Trees**: Binary trees for efficient commitment storage and inclusion proofs,
+//!   generic over a [`HashFunction`] so pools without proofs can use SHA-256
+//! - **Nullifier Tree**: A sparse Merkle tree of spent nullifier hashes, whose
+//!   non-membership proofs show a nullifier is unspent as of a root
 //! - **Commitment Scheme**: Cryptographic commitments that hide secrets while enabling
 //!   zero-knowledge proofs
 //!
@@ -52,7 +55,8 @@
 //! - **Poseidon Hashing**: Optimized for zero-knowledge circuits but slower than SHA-256
 //!   for general use
 //! - **Merkle Tree Operations**: O(log n) insertion and proof generation
-//! - **Memory Usage**: Trees store all intermediate nodes for efficient proof generation
+//! - **Memory Usage**: Trees store all intermediate nodes for efficient proof generation,
+//!   in memory or in any other [`NodeStore`]
 //!
 //! ## Zero-Knowledge Compatibility
 //!
@@ -63,15 +67,20 @@
 pub mod hash;
 pub mod poseidon;
 pub mod merkle;
+pub mod node_store;
+pub mod nullifier_tree;
 pub mod zkp;
 pub mod gadgets;
+pub mod test_vectors;
 
 use anyhow::Result;
-use zkane_common::{Secret, Nullifier, Commitment, NullifierHash};
+use zkane_common::{Secret, Nullifier, Commitment, NullifierHash, ZkAssetId};
 
 pub use hash::*;
 pub use poseidon::*;
 pub use merkle::*;
+pub use node_store::*;
+pub use nullifier_tree::*;
 
 /// Generate a commitment from a nullifier and secret.
 ///
@@ -120,6 +129,60 @@ pub fn generate_commitment(nullifier: &Nullifier, secret: &Secret) -> Result<Com
     Ok(Commitment::new(hash_result))
 }
 
+/// Hash an asset ID into a single field element.
+///
+/// The block and transaction numbers are encoded as big-endian field elements
+/// and combined with [`poseidon_hash_two`].
+pub fn asset_id_hash(asset_id: &ZkAssetId) -> Result<[u8; 32]> {
+    poseidon_hash_two(&u128_to_field_bytes(asset_id.block), &u128_to_field_bytes(asset_id.tx))
+}
+
+/// Generate a commitment bound to the pool's asset and denomination.
+///
+/// Unlike [`generate_commitment`], the commitment also covers the asset ID and
+/// denomination of the pool the note is deposited into, so a note created for
+/// one pool cannot be presented as a note for another.
+///
+/// The commitment is `poseidon(nullifier, secret, asset_id_hash, denomination)`
+/// where `asset_id_hash` is computed with [`asset_id_hash`].
+///
+/// # Example
+///
+/// ```rust
+/// use zkane_crypto::{generate_asset_commitment, verify_asset_commitment};
+/// use zkane_common::{Nullifier, Secret, ZkAssetId};
+///
+/// let secret = Secret::random();
+/// let nullifier = Nullifier::random();
+/// let asset_id = ZkAssetId { block: 2, tx: 1 };
+/// let commitment = generate_asset_commitment(&nullifier, &secret, &asset_id, 1000000)?;
+///
+/// assert!(verify_asset_commitment(&commitment, &nullifier, &secret, &asset_id, 1000000)?);
+/// assert!(!verify_asset_commitment(&commitment, &nullifier, &secret, &asset_id, 500000)?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn generate_asset_commitment(
+    nullifier: &Nullifier,
+    secret: &Secret,
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> Result<Commitment> {
+    let hash_result = PoseidonConfig::bn254(4)?.hash(&[
+        *nullifier.as_bytes(),
+        *secret.as_bytes(),
+        asset_id_hash(asset_id)?,
+        u128_to_field_bytes(denomination),
+    ])?;
+    Ok(Commitment::new(hash_result))
+}
+
+/// Encode a `u128` as a big-endian 32-byte field element.
+fn u128_to_field_bytes(value: u128) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[16..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
 /// Generate a nullifier hash from a nullifier.
 ///
 /// This function creates a one-way hash of a nullifier that can be safely published
@@ -206,6 +269,21 @@ pub fn verify_commitment(
     Ok(commitment == &computed_commitment)
 }
 
+/// Verify that an asset-bound commitment was generated from a note's secret,
+/// nullifier, asset ID and denomination.
+///
+/// See [`generate_asset_commitment`].
+pub fn verify_asset_commitment(
+    commitment: &Commitment,
+    nullifier: &Nullifier,
+    secret: &Secret,
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> Result<bool> {
+    let computed_commitment = generate_asset_commitment(nullifier, secret, asset_id, denomination)?;
+    Ok(commitment == &computed_commitment)
+}
+
 /// Verify that a nullifier hash was correctly generated from a nullifier.
 ///
 /// This function verifies the integrity of a nullifier hash by recomputing it
@@ -279,6 +357,25 @@ mod tests {
         assert!(!verify_commitment(&commitment, &wrong_nullifier, &secret).unwrap());
     }
 
+    #[test]
+    fn test_asset_commitment_binds_asset_and_denomination() {
+        let secret = Secret::random();
+        let nullifier = Nullifier::random();
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let commitment = generate_asset_commitment(&nullifier, &secret, &asset_id, 1000).unwrap();
+
+        assert!(verify_asset_commitment(&commitment, &nullifier, &secret, &asset_id, 1000).unwrap());
+        assert_ne!(commitment, generate_commitment(&nullifier, &secret).unwrap());
+
+        // Another pool's asset or denomination gives another commitment
+        let other_asset = ZkAssetId { block: 2, tx: 2 };
+        let swapped_asset = ZkAssetId { block: 1, tx: 2 };
+        assert!(!verify_asset_commitment(&commitment, &nullifier, &secret, &other_asset, 1000).unwrap());
+        assert!(!verify_asset_commitment(&commitment, &nullifier, &secret, &swapped_asset, 1000).unwrap());
+        assert!(!verify_asset_commitment(&commitment, &nullifier, &secret, &asset_id, 1001).unwrap());
+        assert!(!verify_asset_commitment(&commitment, &nullifier, &Secret::random(), &asset_id, 1000).unwrap());
+    }
+
     #[test]
     fn test_nullifier_hash_generation() {
         let nullifier = Nullifier::random();
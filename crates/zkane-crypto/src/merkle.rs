@@ -1,48 +1,308 @@
 //! Merkle tree implementation for ZKane privacy pools
 
-use zkane_common::{Commitment, MerklePath, ZKaneError, ZKaneResult};
-use crate::hash::{hash_leaf, hash_internal};
-use std::collections::HashMap;
+use zkane_common::{Commitment, MerklePath, ZKaneError, ZKaneResult, MAX_TREE_HEIGHT};
+use crate::hash::{Blake2sHash, HashFunction};
+use crate::node_store::{MemoryNodeStore, NodeStore};
+use std::collections::VecDeque;
+
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+
+/// Number of recent roots remembered by the tree
+pub const ROOT_HISTORY_SIZE: usize = 30;
+
+/// Magic bytes at the start of an encoded tree snapshot
+const SNAPSHOT_MAGIC: &[u8; 4] = b"ZKMT";
+
+/// Current version of the snapshot encoding
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Magic bytes at the start of an encoded tree delta
+const DELTA_MAGIC: &[u8; 4] = b"ZKMD";
+
+/// Current version of the delta encoding
+pub const DELTA_VERSION: u8 = 1;
 
 /// A sparse Merkle tree for storing commitments
+///
+/// The hash function is a type parameter, Blake2s unless another
+/// [`HashFunction`] is chosen with [`MerkleTree::with_hasher`]. The nodes are
+/// kept in a [`NodeStore`], in memory unless the tree is opened on another
+/// store with [`MerkleTree::open`]; only the frontier of the next insertion
+/// and the root history are held by the tree itself.
 #[derive(Debug, Clone)]
-pub struct MerkleTree {
+pub struct MerkleTree<H: HashFunction = Blake2sHash, S: NodeStore = MemoryNodeStore> {
     /// The height of the tree (number of levels)
     height: u32,
     /// The current number of leaves
     leaf_count: u32,
-    /// Cache of computed hashes at each level
-    /// Key: (level, index), Value: hash
-    cache: HashMap<(u32, u32), [u8; 32]>,
+    /// Storage of the computed hashes at each level
+    store: S,
     /// The zero hashes for each level (for sparse tree optimization)
     zero_hashes: Vec<[u8; 32]>,
+    /// Last left node hashed at each level, the sibling of the next insertion
+    /// wherever it is a right child
+    filled_subtrees: Vec<[u8; 32]>,
+    /// The current root
+    root: [u8; 32],
+    /// Most recent roots with the leaf count of the tree at each, oldest first
+    root_history: VecDeque<(u32, [u8; 32])>,
+    /// First leaf whose path can be generated (non-zero for restored trees)
+    first_provable_leaf: u32,
+    /// Hash function of the leaves and internal nodes
+    hasher: H,
 }
 
 impl MerkleTree {
     /// Create a new merkle tree with the given height
+    ///
+    /// # Panics
+    ///
+    /// Panics if the height is zero or exceeds [`MAX_TREE_HEIGHT`]; see
+    /// [`MerkleTree::try_new`].
     pub fn new(height: u32) -> Self {
-        let zero_hashes = Self::compute_zero_hashes(height);
-        
+        Self::with_hasher(height, Blake2sHash)
+    }
+
+    /// Create a new merkle tree with the given height
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidTreeHeight`] if the height is zero or
+    /// exceeds [`MAX_TREE_HEIGHT`].
+    pub fn try_new(height: u32) -> ZKaneResult<Self> {
+        Self::try_with_hasher(height, Blake2sHash)
+    }
+
+    /// Build a tree of the given height from a batch of commitments.
+    ///
+    /// See [`MerkleTree::from_leaves_with_hasher`].
+    pub fn from_leaves(height: u32, commitments: &[Commitment]) -> ZKaneResult<Self> {
+        Self::from_leaves_with_hasher(height, commitments, Blake2sHash)
+    }
+
+    /// Restore a tree from a snapshot produced by [`MerkleTree::to_snapshot`].
+    ///
+    /// See [`MerkleTree::from_snapshot_with_hasher`].
+    pub fn from_snapshot(bytes: &[u8]) -> ZKaneResult<Self> {
+        Self::from_snapshot_with_hasher(bytes, Blake2sHash)
+    }
+}
+
+impl<H: HashFunction> MerkleTree<H> {
+    /// Create a new merkle tree with the given height and hash function
+    ///
+    /// # Panics
+    ///
+    /// Panics if the height is zero or exceeds [`MAX_TREE_HEIGHT`]; see
+    /// [`MerkleTree::try_with_hasher`].
+    pub fn with_hasher(height: u32, hasher: H) -> Self {
+        Self::try_with_hasher(height, hasher).expect("invalid tree height")
+    }
+
+    /// Create a new merkle tree with the given height and hash function
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidTreeHeight`] if the height is zero or
+    /// exceeds [`MAX_TREE_HEIGHT`].
+    pub fn try_with_hasher(height: u32, hasher: H) -> ZKaneResult<Self> {
+        check_height(height)?;
+        Ok(Self::empty(height, hasher, MemoryNodeStore::default()))
+    }
+
+    /// Build a tree of the given height from a batch of commitments.
+    ///
+    /// Produces the same tree, including the root history, as inserting the
+    /// commitments one by one, but hashes each level in a single pass. With
+    /// the `parallel` feature the levels are hashed on the rayon thread pool;
+    /// wasm32 builds always hash on the calling thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidTreeHeight`] if the height is zero or
+    /// exceeds [`MAX_TREE_HEIGHT`], and [`ZKaneError::TreeFull`] if there are
+    /// more commitments than the tree has leaves.
+    pub fn from_leaves_with_hasher(height: u32, commitments: &[Commitment], hasher: H) -> ZKaneResult<Self> {
+        check_height(height)?;
+        if commitments.len() as u64 > 1u64 << height {
+            return Err(ZKaneError::TreeFull);
+        }
+
+        let mut tree = Self::with_hasher(height, hasher);
+
+        // The trailing leaves are inserted one by one so that the root
+        // history holds the same roots as a sequentially built tree
+        let bulk = commitments.len().saturating_sub(ROOT_HISTORY_SIZE);
+        let (head, tail) = commitments.split_at(bulk);
+
+        let mut level_hashes = hash_leaves(&tree.hasher, head);
+        for level in 0..=height {
+            for (index, hash) in level_hashes.iter().enumerate() {
+                tree.store.set(level, index as u32, *hash)?;
+            }
+            if level == height {
+                break;
+            }
+            let zero = tree.zero_hashes[level as usize];
+            level_hashes = hash_level(&tree.hasher, &level_hashes, &zero);
+        }
+        tree.leaf_count = head.len() as u32;
+        tree.load_frontier()?;
+        if !head.is_empty() {
+            tree.push_root(tree.leaf_count, tree.root());
+        }
+
+        for commitment in tail {
+            tree.insert(commitment)?;
+        }
+        Ok(tree)
+    }
+
+    /// Restore a tree from a snapshot produced by [`MerkleTree::to_snapshot`].
+    ///
+    /// Snapshots don't record the hash function; restoring a non-empty tree
+    /// with the wrong one fails because its filled subtrees don't hash to its
+    /// latest root.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidSnapshot`] if the snapshot is truncated, has
+    /// an unknown version, or its filled subtrees don't match its latest root.
+    pub fn from_snapshot_with_hasher(bytes: &[u8], hasher: H) -> ZKaneResult<Self> {
+        let mut reader = SnapshotReader { bytes };
+
+        if reader.take(4)? != SNAPSHOT_MAGIC {
+            return Err(ZKaneError::InvalidSnapshot("bad magic".to_string()));
+        }
+        let version = reader.take(1)?[0];
+        if version != SNAPSHOT_VERSION {
+            return Err(ZKaneError::InvalidSnapshot(format!("unsupported version {}", version)));
+        }
+
+        let height = reader.u32()?;
+        if height == 0 || height > MAX_TREE_HEIGHT {
+            return Err(ZKaneError::InvalidSnapshot(format!("invalid height {}", height)));
+        }
+        let leaf_count = reader.u32()?;
+        if leaf_count > (1u32 << height) {
+            return Err(ZKaneError::InvalidSnapshot("leaf count exceeds capacity".to_string()));
+        }
+
+        let filled_subtrees = (0..height)
+            .map(|_| reader.hash())
+            .collect::<ZKaneResult<Vec<_>>>()?;
+
+        let root_count = reader.u32()? as usize;
+        if root_count > ROOT_HISTORY_SIZE {
+            return Err(ZKaneError::InvalidSnapshot("root history too long".to_string()));
+        }
+        let roots = (0..root_count)
+            .map(|_| reader.hash())
+            .collect::<ZKaneResult<Vec<_>>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(ZKaneError::InvalidSnapshot("trailing bytes".to_string()));
+        }
+
+        let mut tree = Self::with_hasher(height, hasher);
+        let root = tree.root_from_filled_subtrees(&filled_subtrees, leaf_count);
+        if leaf_count > 0 && roots.last() != Some(&root) {
+            return Err(ZKaneError::InvalidSnapshot(
+                "filled subtrees do not match latest root".to_string(),
+            ));
+        }
+
+        for level in 0..height {
+            let index = leaf_count >> level;
+            if index % 2 == 1 {
+                tree.store.set(level, index - 1, filled_subtrees[level as usize])?;
+            }
+        }
+        if leaf_count > 0 {
+            tree.store.set(height, 0, root)?;
+        }
+        tree.leaf_count = leaf_count;
+        tree.load_frontier()?;
+        tree.first_provable_leaf = leaf_count;
+        // The restored roots all predate the first provable leaf, so their
+        // leaf counts never matter and are taken as the restore point
+        tree.root_history = roots.into_iter().map(|root| (leaf_count, root)).collect();
+
+        Ok(tree)
+    }
+}
+
+impl<H: HashFunction, S: NodeStore> MerkleTree<H, S> {
+    /// Open a tree of `leaf_count` leaves whose nodes are in `store`.
+    ///
+    /// The store must hold the nodes this tree type wrote for exactly the
+    /// first `leaf_count` leaves, or nothing for a new tree. Only the current
+    /// root is known; the root history starts with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidTreeHeight`] if the height is zero or
+    /// exceeds [`MAX_TREE_HEIGHT`], [`ZKaneError::TreeFull`] if `leaf_count`
+    /// exceeds the capacity of the tree, and the errors of the store.
+    pub fn open(height: u32, hasher: H, store: S, leaf_count: u32) -> ZKaneResult<Self> {
+        check_height(height)?;
+        if u64::from(leaf_count) > 1u64 << height {
+            return Err(ZKaneError::TreeFull);
+        }
+        let mut tree = Self::empty(height, hasher, store);
+        tree.leaf_count = leaf_count;
+        tree.load_frontier()?;
+        if leaf_count > 0 {
+            tree.push_root(leaf_count, tree.root);
+        }
+        Ok(tree)
+    }
+
+    /// Create a tree without leaves on a store
+    fn empty(height: u32, hasher: H, store: S) -> Self {
+        let zero_hashes = Self::compute_zero_hashes(height, &hasher);
+
         Self {
             height,
             leaf_count: 0,
-            cache: HashMap::new(),
+            store,
+            filled_subtrees: zero_hashes[..height as usize].to_vec(),
+            root: zero_hashes[height as usize],
             zero_hashes,
+            root_history: VecDeque::with_capacity(ROOT_HISTORY_SIZE),
+            first_provable_leaf: 0,
+            hasher,
         }
     }
 
+    /// Get the hash function of the tree
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Get the node store of the tree
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Consume the tree, returning its node store
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
     /// Compute the zero hashes for each level of the tree
-    fn compute_zero_hashes(height: u32) -> Vec<[u8; 32]> {
+    fn compute_zero_hashes(height: u32, hasher: &H) -> Vec<[u8; 32]> {
         let mut zero_hashes = Vec::with_capacity(height as usize + 1);
         
         // Level 0 (leaves): hash of zero
         let zero_leaf = [0u8; 32];
-        zero_hashes.push(hash_leaf(&zero_leaf));
+        zero_hashes.push(hasher.hash_leaf(&zero_leaf));
         
         // Higher levels: hash of two zero hashes from previous level
         for i in 1..=height {
             let prev_zero = zero_hashes[(i - 1) as usize];
-            let current_zero = hash_internal(&prev_zero, &prev_zero);
+            let current_zero = hasher.hash_internal(&prev_zero, &prev_zero);
             zero_hashes.push(current_zero);
         }
         
@@ -50,73 +310,104 @@ impl MerkleTree {
     }
 
     /// Insert a commitment into the tree and return its leaf index
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::TreeFull`] if the tree is full, and the errors
+    /// of the store. A store error leaves the tree unchanged, but the store
+    /// may hold some of the new nodes.
     pub fn insert(&mut self, commitment: &Commitment) -> ZKaneResult<u32> {
-        if self.leaf_count >= (1u32 << self.height) {
+        if self.is_full() {
             return Err(ZKaneError::TreeFull);
         }
 
         let leaf_index = self.leaf_count;
-        let leaf_hash = hash_leaf(commitment.as_bytes());
+        let leaf_hash = self.hasher.hash_leaf(commitment.as_bytes());
         
         // Store the leaf
-        self.cache.insert((0, leaf_index), leaf_hash);
+        self.store.set(0, leaf_index, leaf_hash)?;
         
         // Update the tree by recomputing hashes up to the root
-        self.update_path(leaf_index, leaf_hash);
+        self.update_path(leaf_index, leaf_hash)?;
         
         self.leaf_count += 1;
+        self.push_root(self.leaf_count, self.root);
         Ok(leaf_index)
     }
 
-    /// Update the tree along the path from a leaf to the root
-    fn update_path(&mut self, leaf_index: u32, leaf_hash: [u8; 32]) {
+    /// Record the root of the tree at `leaf_count` leaves in the bounded root history
+    fn push_root(&mut self, leaf_count: u32, root: [u8; 32]) {
+        if self.root_history.len() == ROOT_HISTORY_SIZE {
+            self.root_history.pop_front();
+        }
+        self.root_history.push_back((leaf_count, root));
+    }
+
+    /// Update the tree along the path from a new last leaf to the root
+    ///
+    /// Every node right of the path is empty, so the siblings are the filled
+    /// subtrees on the left and zero hashes on the right.
+    fn update_path(&mut self, leaf_index: u32, leaf_hash: [u8; 32]) -> ZKaneResult<()> {
+        let mut filled_subtrees = self.filled_subtrees.clone();
         let mut current_hash = leaf_hash;
         let mut current_index = leaf_index;
         
-        for level in 1..=self.height {
-            let parent_index = current_index / 2;
+        for level in 0..self.height {
             let is_right_child = current_index % 2 == 1;
             
-            let sibling_hash = if is_right_child {
-                // We are the right child, get left sibling
-                let sibling_index = current_index - 1;
-                self.get_hash(level - 1, sibling_index)
+            current_hash = if is_right_child {
+                self.hasher.hash_internal(&filled_subtrees[level as usize], &current_hash)
             } else {
-                // We are the left child, get right sibling
-                let sibling_index = current_index + 1;
-                self.get_hash(level - 1, sibling_index)
+                filled_subtrees[level as usize] = current_hash;
+                self.hasher.hash_internal(&current_hash, &self.zero_hashes[level as usize])
             };
+            current_index /= 2;
             
-            let parent_hash = if is_right_child {
-                hash_internal(&sibling_hash, &current_hash)
+            self.store.set(level + 1, current_index, current_hash)?;
+        }
+
+        self.filled_subtrees = filled_subtrees;
+        self.root = current_hash;
+        Ok(())
+    }
+
+    /// Load the frontier of the next insertion and the root from the store
+    fn load_frontier(&mut self) -> ZKaneResult<()> {
+        for level in 0..self.height {
+            let index = self.leaf_count >> level;
+            self.filled_subtrees[level as usize] = if index % 2 == 1 {
+                self.get_hash(level, index - 1)?
             } else {
-                hash_internal(&current_hash, &sibling_hash)
+                self.zero_hashes[level as usize]
             };
-            
-            self.cache.insert((level, parent_index), parent_hash);
-            
-            current_hash = parent_hash;
-            current_index = parent_index;
         }
+        self.root = if self.leaf_count == 0 {
+            self.zero_hashes[self.height as usize]
+        } else {
+            self.get_hash(self.height, 0)?
+        };
+        Ok(())
     }
 
     /// Get the hash at a specific level and index
-    fn get_hash(&self, level: u32, index: u32) -> [u8; 32] {
-        if let Some(&hash) = self.cache.get(&(level, index)) {
-            hash
-        } else {
-            // Return the appropriate zero hash for this level
-            self.zero_hashes[level as usize]
-        }
+    fn get_hash(&self, level: u32, index: u32) -> ZKaneResult<[u8; 32]> {
+        // Missing nodes are the appropriate zero hash for this level
+        Ok(self.store.get(level, index)?.unwrap_or(self.zero_hashes[level as usize]))
     }
 
     /// Get the current root hash of the tree
     pub fn root(&self) -> [u8; 32] {
-        if self.leaf_count == 0 {
-            return self.zero_hashes[self.height as usize];
-        }
-        
-        self.get_hash(self.height, 0)
+        self.root
+    }
+
+    /// Check if a root is the current root or one of the recent roots
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        *root == self.root() || self.root_history.iter().any(|(_, known)| known == root)
+    }
+
+    /// Get the recent roots, oldest first
+    pub fn root_history(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.root_history.iter().map(|(_, root)| root)
     }
 
     /// Generate a merkle path for the given leaf index
@@ -124,6 +415,11 @@ impl MerkleTree {
         if leaf_index >= self.leaf_count {
             return Err(ZKaneError::InvalidCommitment("Leaf index out of bounds".to_string()));
         }
+        if leaf_index < self.first_provable_leaf {
+            return Err(ZKaneError::InvalidCommitment(
+                "Leaf was inserted before the tree was restored from a snapshot".to_string(),
+            ));
+        }
 
         let mut elements = Vec::new();
         let mut indices = Vec::new();
@@ -137,7 +433,7 @@ impl MerkleTree {
                 current_index + 1
             };
             
-            let sibling_hash = self.get_hash(level, sibling_index);
+            let sibling_hash = self.get_hash(level, sibling_index)?;
             elements.push(sibling_hash);
             indices.push(is_right_child);
             
@@ -155,31 +451,55 @@ impl MerkleTree {
         path: &MerklePath,
         expected_root: &[u8; 32],
     ) -> ZKaneResult<bool> {
-        if path.len() != self.height as usize {
-            return Ok(false);
+        verify_merkle_path_with(&self.hasher, commitment, leaf_index, path, expected_root, self.height)
+    }
+
+    /// Remove every leaf from `leaf_count` onwards.
+    ///
+    /// The tree ends up in the same state as if only the first `leaf_count`
+    /// leaves had been inserted, except that the root history loses the
+    /// roots of the removed leaves without regaining older ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `leaf_count` exceeds the current leaf count, or if
+    /// the tree was restored from a snapshot and `leaf_count` is before the
+    /// restore point, and the errors of the store.
+    pub fn truncate(&mut self, leaf_count: u32) -> ZKaneResult<()> {
+        if leaf_count > self.leaf_count {
+            return Err(ZKaneError::InvalidCommitment("Truncation beyond the last leaf".to_string()));
+        }
+        if leaf_count < self.first_provable_leaf {
+            return Err(ZKaneError::InvalidCommitment(
+                "Truncation before the point the tree was restored from a snapshot".to_string(),
+            ));
         }
 
-        let mut current_hash = hash_leaf(commitment.as_bytes());
-        let mut current_index = leaf_index;
-        
-        for (_level, (&sibling_hash, &is_right_child)) in
-            path.elements.iter().zip(path.indices.iter()).enumerate() {
-            
-            // Verify the index matches the path
-            if (current_index % 2 == 1) != is_right_child {
-                return Ok(false);
+        let end = leaf_count as u64;
+        let old_end = self.leaf_count as u64;
+
+        // Drop every node covering a removed leaf
+        for level in 0..=self.height {
+            let last = (old_end + (1u64 << level) - 1) >> level;
+            for index in end >> level..last {
+                self.store.remove(level, index as u32)?;
             }
-            
-            current_hash = if is_right_child {
-                hash_internal(&sibling_hash, &current_hash)
-            } else {
-                hash_internal(&current_hash, &sibling_hash)
-            };
-            
-            current_index /= 2;
         }
-        
-        Ok(&current_hash == expected_root)
+
+        // Recompute the nodes that still cover some remaining leaves
+        for level in 1..=self.height {
+            let index = (end >> level) as u32;
+            if (index as u64) << level < end {
+                let left = self.get_hash(level - 1, index * 2)?;
+                let right = self.get_hash(level - 1, index * 2 + 1)?;
+                self.store.set(level, index, self.hasher.hash_internal(&left, &right))?;
+            }
+        }
+
+        self.leaf_count = leaf_count;
+        self.load_frontier()?;
+        self.root_history.retain(|(count, _)| *count <= leaf_count);
+        Ok(())
     }
 
     /// Get the current number of leaves in the tree
@@ -194,8 +514,437 @@ impl MerkleTree {
 
     /// Check if the tree is full
     pub fn is_full(&self) -> bool {
-        self.leaf_count >= (1u32 << self.height)
+        u64::from(self.leaf_count) >= 1u64 << self.height
+    }
+
+    /// Get the filled subtree hashes along the path of the next insertion.
+    ///
+    /// Entry `level` is the hash of the completed left subtree at that level,
+    /// or the zero hash if the next leaf is a left child at that level.
+    fn next_filled_subtrees(&self) -> Vec<[u8; 32]> {
+        (0..self.height)
+            .map(|level| {
+                if (self.leaf_count >> level) % 2 == 1 {
+                    self.filled_subtrees[level as usize]
+                } else {
+                    self.zero_hashes[level as usize]
+                }
+            })
+            .collect()
     }
+
+    /// Compute the root from filled subtree hashes for a tree with `leaf_count` leaves
+    fn root_from_filled_subtrees(&self, filled_subtrees: &[[u8; 32]], leaf_count: u32) -> [u8; 32] {
+        let mut current = self.zero_hashes[0];
+        for level in 0..self.height {
+            current = if (leaf_count >> level) % 2 == 1 {
+                self.hasher.hash_internal(&filled_subtrees[level as usize], &current)
+            } else {
+                self.hasher.hash_internal(&current, &self.zero_hashes[level as usize])
+            };
+        }
+        current
+    }
+
+    /// Encode the tree state as a compact binary snapshot.
+    ///
+    /// The snapshot contains only the filled subtrees, the leaf count and the
+    /// root history, so its size depends on the tree height rather than the
+    /// number of leaves. Layout (integers little-endian):
+    ///
+    /// ```text
+    /// magic "ZKMT" | version u8 | height u32 | leaf_count u32
+    /// | filled_subtrees [32; height] | root_count u32 | roots [32; root_count]
+    /// ```
+    ///
+    /// A tree restored with [`MerkleTree::from_snapshot`] accepts new leaves and
+    /// produces the same roots as the original, but can only generate paths for
+    /// leaves inserted after the restore.
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        let filled_subtrees = self.next_filled_subtrees();
+        let mut out = Vec::with_capacity(
+            4 + 1 + 4 + 4 + 32 * filled_subtrees.len() + 4 + 32 * self.root_history.len(),
+        );
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        out.push(SNAPSHOT_VERSION);
+        out.extend_from_slice(&self.height.to_le_bytes());
+        out.extend_from_slice(&self.leaf_count.to_le_bytes());
+        for hash in &filled_subtrees {
+            out.extend_from_slice(hash);
+        }
+        out.extend_from_slice(&(self.root_history.len() as u32).to_le_bytes());
+        for (_, root) in &self.root_history {
+            out.extend_from_slice(root);
+        }
+        out
+    }
+
+    /// Get the nodes added or changed since the tree had `leaf_count` leaves.
+    ///
+    /// These are the new leaves and, at each level above, the nodes from the
+    /// one covering the first new leaf to the one covering the last, so the
+    /// delta grows with the number of new leaves plus the tree height.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `leaf_count` exceeds the current leaf count, or if
+    /// the tree was restored from a snapshot and `leaf_count` is before the
+    /// restore point, and the errors of the store.
+    pub fn diff_since(&self, leaf_count: u32) -> ZKaneResult<TreeDelta> {
+        if leaf_count > self.leaf_count {
+            return Err(ZKaneError::InvalidCommitment("Delta from beyond the last leaf".to_string()));
+        }
+        if leaf_count < self.first_provable_leaf {
+            return Err(ZKaneError::InvalidCommitment(
+                "Delta from before the point the tree was restored from a snapshot".to_string(),
+            ));
+        }
+
+        let mut delta = TreeDelta {
+            height: self.height,
+            from_leaf_count: leaf_count,
+            leaves: Vec::new(),
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        };
+        if leaf_count == self.leaf_count {
+            return Ok(delta);
+        }
+
+        let level_nodes = |level: u32| {
+            let first = leaf_count >> level;
+            let len = TreeDelta::level_len(leaf_count, self.leaf_count, level) as u32;
+            (first..first + len)
+                .map(|index| self.get_hash(level, index))
+                .collect::<ZKaneResult<Vec<_>>>()
+        };
+        delta.leaves = level_nodes(0)?;
+        delta.nodes = (1..=self.height).map(level_nodes).collect::<ZKaneResult<_>>()?;
+
+        delta.roots = self
+            .root_history
+            .iter()
+            .filter(|(count, _)| *count > leaf_count)
+            .copied()
+            .collect();
+        Ok(delta)
+    }
+
+    /// Bring the tree up to date with a delta from [`MerkleTree::diff_since`].
+    ///
+    /// The tree must have the delta's height and starting leaf count. The
+    /// path of the last new leaf is rehashed and checked against the delta's
+    /// nodes before the store is touched; the other nodes are taken as they
+    /// are, so the resulting root should still be checked against the pool's.
+    /// The delta's roots are added to the root history, followed by the new
+    /// root unless the delta ends with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidSnapshot`] if the delta doesn't fit the
+    /// tree or its last path doesn't hash to its root, [`ZKaneError::TreeFull`]
+    /// if it adds more leaves than fit, and the errors of the store. A store
+    /// error may leave some of the new nodes in the store.
+    pub fn apply_delta(&mut self, delta: &TreeDelta) -> ZKaneResult<()> {
+        let invalid = |reason: String| ZKaneError::InvalidSnapshot(format!("delta {}", reason));
+        if delta.height != self.height {
+            return Err(invalid(format!(
+                "of a height-{} tree applied to a height-{} tree",
+                delta.height, self.height
+            )));
+        }
+        let from = delta.from_leaf_count;
+        if from != self.leaf_count {
+            return Err(invalid(format!(
+                "from leaf {} applied to a tree of {} leaves",
+                from, self.leaf_count
+            )));
+        }
+        if u64::from(from) + delta.leaves.len() as u64 > 1u64 << self.height {
+            return Err(ZKaneError::TreeFull);
+        }
+        if delta.is_empty() {
+            if delta.nodes.iter().any(|nodes| !nodes.is_empty()) || !delta.roots.is_empty() {
+                return Err(invalid("has nodes but no leaves".to_string()));
+            }
+            return Ok(());
+        }
+
+        let to = delta.leaf_count();
+        if delta.nodes.len() != self.height as usize {
+            return Err(invalid(format!("has {} levels, expected {}", delta.nodes.len(), self.height)));
+        }
+        for level in 1..=self.height {
+            let expected = TreeDelta::level_len(from, to, level);
+            if delta.level(level).len() != expected {
+                return Err(invalid(format!(
+                    "has {} nodes at level {}, expected {}",
+                    delta.level(level).len(),
+                    level,
+                    expected
+                )));
+            }
+        }
+
+        // Rehash the path of the last new leaf; everything right of it is empty
+        let node = |level: u32, index: u32| -> ZKaneResult<[u8; 32]> {
+            let first = from >> level;
+            if index >= first {
+                Ok(delta.level(level)[(index - first) as usize])
+            } else {
+                self.get_hash(level, index)
+            }
+        };
+        let mut index = to - 1;
+        let mut current = node(0, index)?;
+        for level in 0..self.height {
+            current = if index % 2 == 1 {
+                self.hasher.hash_internal(&node(level, index - 1)?, &current)
+            } else {
+                self.hasher.hash_internal(&current, &self.zero_hashes[level as usize])
+            };
+            index /= 2;
+            if current != node(level + 1, index)? {
+                return Err(invalid(format!("node at level {} doesn't hash from its children", level + 1)));
+            }
+        }
+        let mut previous = from;
+        for &(count, root) in &delta.roots {
+            if count <= previous || count > to {
+                return Err(invalid(format!("has a root at leaf count {} out of order", count)));
+            }
+            if count == to && root != current {
+                return Err(invalid("has a different root at its last leaf".to_string()));
+            }
+            previous = count;
+        }
+
+        for level in 0..=self.height {
+            let first = from >> level;
+            for (offset, hash) in delta.level(level).iter().enumerate() {
+                self.store.set(level, first + offset as u32, *hash)?;
+            }
+        }
+        self.leaf_count = to;
+        self.load_frontier()?;
+        for &(count, root) in &delta.roots {
+            self.push_root(count, root);
+        }
+        if previous != to {
+            self.push_root(to, self.root);
+        }
+        Ok(())
+    }
+}
+
+/// The nodes a tree gained since an earlier leaf count, from
+/// [`MerkleTree::diff_since`].
+///
+/// Applied with [`MerkleTree::apply_delta`] to a tree at that leaf count, it
+/// brings the tree up to date without the commitments or any hashing beyond
+/// one path, so a light client downloads a few kilobytes per sync instead of
+/// the whole tree. Positions are implied: the nodes of each level start at
+/// the one covering the first new leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDelta {
+    /// Height of the tree
+    pub height: u32,
+    /// Leaf count of the tree the delta applies to
+    pub from_leaf_count: u32,
+    /// Hashes of the new leaves
+    pub leaves: Vec<[u8; 32]>,
+    /// New and changed internal nodes, entry `level - 1` holding those of
+    /// `level`, up to the root
+    pub nodes: Vec<Vec<[u8; 32]>>,
+    /// Roots recorded for the new leaves with the leaf count of the tree at
+    /// each, oldest first, as far as the tree remembers them
+    pub roots: Vec<(u32, [u8; 32])>,
+}
+
+impl TreeDelta {
+    /// Get the leaf count of the tree after the delta.
+    pub fn leaf_count(&self) -> u32 {
+        self.from_leaf_count + self.leaves.len() as u32
+    }
+
+    /// Check if the delta adds no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Get the nodes of a level, the leaves at level 0
+    fn level(&self, level: u32) -> &[[u8; 32]] {
+        match level {
+            0 => &self.leaves,
+            _ => &self.nodes[level as usize - 1],
+        }
+    }
+
+    /// Number of nodes at `level` covering the leaves from `from` to `to`
+    fn level_len(from: u32, to: u32, level: u32) -> usize {
+        if to == from {
+            0
+        } else {
+            (((to - 1) >> level) - (from >> level)) as usize + 1
+        }
+    }
+
+    /// Encode the delta. Layout (integers little-endian):
+    ///
+    /// ```text
+    /// magic "ZKMD" | version u8 | height u32 | from_leaf_count u32 | leaf_count u32
+    /// | leaves and nodes [32; ...], level by level from the leaves
+    /// | root_count u32 | (leaf_count u32 | root [32]) * root_count
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let node_count = self.leaves.len() + self.nodes.iter().map(Vec::len).sum::<usize>();
+        let mut out = Vec::with_capacity(4 + 1 + 4 + 4 + 4 + 32 * node_count + 4 + 36 * self.roots.len());
+        out.extend_from_slice(DELTA_MAGIC);
+        out.push(DELTA_VERSION);
+        out.extend_from_slice(&self.height.to_le_bytes());
+        out.extend_from_slice(&self.from_leaf_count.to_le_bytes());
+        out.extend_from_slice(&self.leaf_count().to_le_bytes());
+        for hash in self.leaves.iter().chain(self.nodes.iter().flatten()) {
+            out.extend_from_slice(hash);
+        }
+        out.extend_from_slice(&(self.roots.len() as u32).to_le_bytes());
+        for (count, root) in &self.roots {
+            out.extend_from_slice(&count.to_le_bytes());
+            out.extend_from_slice(root);
+        }
+        out
+    }
+
+    /// Decode a delta written by [`TreeDelta::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidSnapshot`] if the bytes are truncated,
+    /// have an unknown version or don't describe a tree.
+    pub fn from_bytes(bytes: &[u8]) -> ZKaneResult<Self> {
+        let invalid = |reason: &str| ZKaneError::InvalidSnapshot(format!("delta {}", reason));
+        let mut reader = SnapshotReader { bytes };
+
+        if reader.take(4)? != DELTA_MAGIC {
+            return Err(invalid("has bad magic"));
+        }
+        let version = reader.take(1)?[0];
+        if version != DELTA_VERSION {
+            return Err(invalid(&format!("has unsupported version {}", version)));
+        }
+
+        let height = reader.u32()?;
+        if height == 0 || height > MAX_TREE_HEIGHT {
+            return Err(invalid(&format!("has invalid height {}", height)));
+        }
+        let from = reader.u32()?;
+        let to = reader.u32()?;
+        if from > to || to > (1u32 << height) {
+            return Err(invalid(&format!("from leaf {} to {} doesn't fit the tree", from, to)));
+        }
+
+        let mut levels = (0..=height)
+            .map(|level| {
+                (0..Self::level_len(from, to, level))
+                    .map(|_| reader.hash())
+                    .collect::<ZKaneResult<Vec<_>>>()
+            })
+            .collect::<ZKaneResult<Vec<_>>>()?;
+        let leaves = levels.remove(0);
+        if leaves.is_empty() {
+            levels.clear();
+        }
+
+        let root_count = reader.u32()? as usize;
+        if root_count > ROOT_HISTORY_SIZE {
+            return Err(invalid("root history too long"));
+        }
+        let roots = (0..root_count)
+            .map(|_| Ok((reader.u32()?, reader.hash()?)))
+            .collect::<ZKaneResult<Vec<_>>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(invalid("has trailing bytes"));
+        }
+
+        Ok(Self {
+            height,
+            from_leaf_count: from,
+            leaves,
+            nodes: levels,
+            roots,
+        })
+    }
+}
+
+/// Cursor over snapshot bytes
+struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, len: usize) -> ZKaneResult<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(ZKaneError::InvalidSnapshot("unexpected end of snapshot".to_string()));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> ZKaneResult<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn hash(&mut self) -> ZKaneResult<[u8; 32]> {
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(self.take(32)?);
+        Ok(buf)
+    }
+}
+
+/// Check that a tree of `height` levels can be built
+fn check_height(height: u32) -> ZKaneResult<()> {
+    if height == 0 || height > MAX_TREE_HEIGHT {
+        return Err(ZKaneError::InvalidTreeHeight {
+            height: height.into(),
+            min: 1,
+            max: MAX_TREE_HEIGHT,
+        });
+    }
+    Ok(())
+}
+
+/// Hash a batch of commitments into leaf nodes
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+fn hash_leaves<H: HashFunction>(hasher: &H, commitments: &[Commitment]) -> Vec<[u8; 32]> {
+    commitments.par_iter().map(|c| hasher.hash_leaf(c.as_bytes())).collect()
+}
+
+/// Hash a batch of commitments into leaf nodes
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+fn hash_leaves<H: HashFunction>(hasher: &H, commitments: &[Commitment]) -> Vec<[u8; 32]> {
+    commitments.iter().map(|c| hasher.hash_leaf(c.as_bytes())).collect()
+}
+
+/// Hash the nodes of one level into their parents, padding with `zero`
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+fn hash_level<H: HashFunction>(hasher: &H, nodes: &[[u8; 32]], zero: &[u8; 32]) -> Vec<[u8; 32]> {
+    nodes
+        .par_chunks(2)
+        .map(|pair| hasher.hash_internal(&pair[0], pair.get(1).unwrap_or(zero)))
+        .collect()
+}
+
+/// Hash the nodes of one level into their parents, padding with `zero`
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+fn hash_level<H: HashFunction>(hasher: &H, nodes: &[[u8; 32]], zero: &[u8; 32]) -> Vec<[u8; 32]> {
+    nodes
+        .chunks(2)
+        .map(|pair| hasher.hash_internal(&pair[0], pair.get(1).unwrap_or(zero)))
+        .collect()
 }
 
 /// Verify a merkle path without needing the full tree
@@ -205,32 +954,83 @@ pub fn verify_merkle_path(
     path: &MerklePath,
     root: &[u8; 32],
     tree_height: u32,
+) -> ZKaneResult<bool> {
+    verify_merkle_path_with(&Blake2sHash, commitment, leaf_index, path, root, tree_height)
+}
+
+/// Verify a merkle path of a tree built with `hasher`
+pub fn verify_merkle_path_with<H: HashFunction>(
+    hasher: &H,
+    commitment: &Commitment,
+    leaf_index: u32,
+    path: &MerklePath,
+    root: &[u8; 32],
+    tree_height: u32,
 ) -> ZKaneResult<bool> {
     if path.len() != tree_height as usize {
         return Ok(false);
     }
+    Ok(compute_root_from_path_with(hasher, commitment, leaf_index, path).is_ok_and(|computed| &computed == root))
+}
 
-    let mut current_hash = hash_leaf(commitment.as_bytes());
+/// Compute the root a merkle path leads to from a commitment
+///
+/// See [`compute_root_from_path_with`].
+pub fn compute_root_from_path(commitment: &Commitment, leaf_index: u32, path: &MerklePath) -> ZKaneResult<[u8; 32]> {
+    compute_root_from_path_with(&Blake2sHash, commitment, leaf_index, path)
+}
+
+/// Compute the root a merkle path of a tree built with `hasher` leads to
+///
+/// Unlike [`verify_merkle_path_with`], this doesn't need the expected root,
+/// so callers can look the result up in a root history, and learn where a
+/// path is wrong rather than only that it is.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::MerklePathMismatch`] naming the first level whose
+/// direction doesn't match `leaf_index`, or the path's height if the index
+/// doesn't fit in a tree of that height.
+pub fn compute_root_from_path_with<H: HashFunction>(
+    hasher: &H,
+    commitment: &Commitment,
+    leaf_index: u32,
+    path: &MerklePath,
+) -> ZKaneResult<[u8; 32]> {
+    let mut current_hash = hasher.hash_leaf(commitment.as_bytes());
     let mut current_index = leaf_index;
-    
-    for (&sibling_hash, &is_right_child) in 
-        path.elements.iter().zip(path.indices.iter()) {
-        
-        // Verify the index matches the path
+
+    for (level, (&sibling_hash, &is_right_child)) in path.elements.iter().zip(path.indices.iter()).enumerate() {
+        // The path's directions must be the bits of the index
         if (current_index % 2 == 1) != is_right_child {
-            return Ok(false);
+            let side = |right: bool| if right { "right" } else { "left" };
+            return Err(ZKaneError::MerklePathMismatch {
+                level: level as u32,
+                reason: format!(
+                    "leaf {} is on the {} but the path goes {}",
+                    leaf_index,
+                    side(current_index % 2 == 1),
+                    side(is_right_child)
+                ),
+            });
         }
-        
+
         current_hash = if is_right_child {
-            hash_internal(&sibling_hash, &current_hash)
+            hasher.hash_internal(&sibling_hash, &current_hash)
         } else {
-            hash_internal(&current_hash, &sibling_hash)
+            hasher.hash_internal(&current_hash, &sibling_hash)
         };
-        
+
         current_index /= 2;
     }
-    
-    Ok(&current_hash == root)
+
+    if current_index != 0 {
+        return Err(ZKaneError::MerklePathMismatch {
+            level: path.len() as u32,
+            reason: format!("leaf {} is beyond a tree of height {}", leaf_index, path.len()),
+        });
+    }
+    Ok(current_hash)
 }
 
 #[cfg(test)]
@@ -250,6 +1050,21 @@ mod tests {
         assert_eq!(root, tree.zero_hashes[4]);
     }
 
+    #[test]
+    fn test_tree_height_bounds() {
+        assert_eq!(MerkleTree::try_new(1).unwrap().height(), 1);
+        assert_eq!(MerkleTree::try_new(MAX_TREE_HEIGHT).unwrap().height(), MAX_TREE_HEIGHT);
+        for height in [0, MAX_TREE_HEIGHT + 1, 64, u32::MAX] {
+            assert!(matches!(MerkleTree::try_new(height), Err(ZKaneError::InvalidTreeHeight { .. })));
+            assert!(matches!(MerkleTree::from_leaves(height, &[]), Err(ZKaneError::InvalidTreeHeight { .. })));
+            assert!(matches!(
+                MerkleTree::open(height, Blake2sHash, MemoryNodeStore::default(), 0),
+                Err(ZKaneError::InvalidTreeHeight { .. })
+            ));
+        }
+        assert!(std::panic::catch_unwind(|| MerkleTree::new(64)).is_err());
+    }
+
     #[test]
     fn test_single_insertion() {
         let mut tree = MerkleTree::new(4);
@@ -348,4 +1163,469 @@ mod tests {
         
         assert!(!tree.verify_path(&commitment, leaf_index, &path, &root).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_root_history() {
+        let mut tree = MerkleTree::new(4);
+        let mut roots = Vec::new();
+        for i in 0..3 {
+            tree.insert(&Commitment::new([i as u8; 32])).unwrap();
+            roots.push(tree.root());
+        }
+
+        for root in &roots {
+            assert!(tree.is_known_root(root));
+        }
+        assert!(!tree.is_known_root(&[9u8; 32]));
+    }
+
+    #[test]
+    fn test_compute_root_from_path() {
+        let mut tree = MerkleTree::new(4);
+        let first = Commitment::new([1u8; 32]);
+        tree.insert(&first).unwrap();
+        tree.insert(&Commitment::new([2u8; 32])).unwrap();
+        let path = tree.generate_path(0).unwrap();
+        let old_root = tree.root();
+        assert_eq!(compute_root_from_path(&first, 0, &path).unwrap(), old_root);
+
+        // A path to an older root still leads to it, which is in the history
+        tree.insert(&Commitment::new([3u8; 32])).unwrap();
+        let computed = compute_root_from_path(&first, 0, &path).unwrap();
+        assert_ne!(computed, tree.root());
+        assert!(tree.is_known_root(&computed));
+
+        // The first level whose direction contradicts the index is reported
+        let mismatch = |result: ZKaneResult<[u8; 32]>| match result {
+            Err(ZKaneError::MerklePathMismatch { level, .. }) => level,
+            other => panic!("expected a mismatch, got {:?}", other),
+        };
+        assert_eq!(mismatch(compute_root_from_path(&first, 4, &path)), 2);
+        let err = compute_root_from_path(&first, 16, &path).unwrap_err();
+        assert_eq!(err.code(), 3006);
+        assert_eq!(mismatch(Err(err)), 4);
+        assert!(!verify_merkle_path(&first, 16, &path, &old_root, 4).unwrap());
+    }
+
+    #[test]
+    fn test_from_leaves_matches_insert() {
+        for count in [0usize, 1, 5, 16, 45, 64] {
+            let commitments: Vec<Commitment> = (0..count)
+                .map(|i| Commitment::new([(i % 251) as u8 + 1; 32]))
+                .collect();
+
+            let mut expected = MerkleTree::new(6);
+            for commitment in &commitments {
+                expected.insert(commitment).unwrap();
+            }
+            let tree = MerkleTree::from_leaves(6, &commitments).unwrap();
+
+            assert_eq!(tree.leaf_count(), expected.leaf_count());
+            assert_eq!(tree.root(), expected.root());
+            assert!(tree.root_history().eq(expected.root_history()));
+            for i in 0..count as u32 {
+                let (path, expected_path) = (tree.generate_path(i).unwrap(), expected.generate_path(i).unwrap());
+                assert_eq!(path.elements, expected_path.elements);
+                assert_eq!(path.indices, expected_path.indices);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_leaves_tree_full() {
+        let commitments: Vec<Commitment> = (0..5u8).map(|i| Commitment::new([i; 32])).collect();
+        assert!(MerkleTree::from_leaves(2, &commitments[..4]).is_ok());
+        assert!(matches!(MerkleTree::from_leaves(2, &commitments), Err(ZKaneError::TreeFull)));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let commitments: Vec<Commitment> = (1..=7u8).map(|i| Commitment::new([i; 32])).collect();
+        let mut tree = MerkleTree::new(4);
+        let mut roots = vec![tree.root()];
+        for commitment in &commitments {
+            tree.insert(commitment).unwrap();
+            roots.push(tree.root());
+        }
+
+        for leaf_count in (0..=7u32).rev() {
+            let mut truncated = tree.clone();
+            truncated.truncate(leaf_count).unwrap();
+            assert_eq!(truncated.root(), roots[leaf_count as usize]);
+            assert_eq!(truncated.leaf_count(), leaf_count);
+            assert_eq!(truncated.root_history().count(), leaf_count as usize);
+
+            // The tree keeps growing as if the removed leaves never existed
+            truncated.insert(&Commitment::new([99u8; 32])).unwrap();
+            let mut expected = MerkleTree::new(4);
+            for commitment in &commitments[..leaf_count as usize] {
+                expected.insert(commitment).unwrap();
+            }
+            expected.insert(&Commitment::new([99u8; 32])).unwrap();
+            assert_eq!(truncated.root(), expected.root());
+            let path = truncated.generate_path(leaf_count).unwrap();
+            assert!(truncated
+                .verify_path(&Commitment::new([99u8; 32]), leaf_count, &path, &truncated.root())
+                .unwrap());
+        }
+
+        assert!(tree.truncate(8).is_err());
+        let mut restored = MerkleTree::from_snapshot(&tree.to_snapshot()).unwrap();
+        assert!(restored.truncate(6).is_err());
+        restored.insert(&Commitment::new([8u8; 32])).unwrap();
+        restored.truncate(7).unwrap();
+        assert_eq!(restored.root(), roots[7]);
+    }
+
+    #[test]
+    fn test_open_store() {
+        let commitments: Vec<Commitment> = (1..=9u8).map(|i| Commitment::new([i; 32])).collect();
+        let mut tree = MerkleTree::from_leaves(4, &commitments[..6]).unwrap();
+
+        // A tree reopened on the nodes of another continues where it stopped
+        let mut reopened = MerkleTree::open(4, Blake2sHash, tree.store().clone(), 6).unwrap();
+        assert_eq!(reopened.root(), tree.root());
+        assert!(reopened.root_history().eq([tree.root()].iter()));
+        for commitment in &commitments[6..] {
+            tree.insert(commitment).unwrap();
+            reopened.insert(commitment).unwrap();
+        }
+        assert_eq!(reopened.root(), tree.root());
+        for i in 0..9 {
+            assert_eq!(reopened.generate_path(i).unwrap().elements, tree.generate_path(i).unwrap().elements);
+        }
+
+        // Truncation removes the nodes of the removed leaves from the store
+        let nodes = MerkleTree::from_leaves(4, &commitments[..3]).unwrap().store().len();
+        reopened.truncate(3).unwrap();
+        assert_eq!(reopened.store().len(), nodes);
+        let empty = MerkleTree::open(4, Blake2sHash, MemoryNodeStore::default(), 0).unwrap();
+        assert_eq!(empty.root(), MerkleTree::new(4).root());
+        assert!(matches!(MerkleTree::open(4, Blake2sHash, MemoryNodeStore::default(), 17), Err(ZKaneError::TreeFull)));
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut tree = MerkleTree::new(5);
+        for i in 0..11 {
+            tree.insert(&Commitment::new([i as u8; 32])).unwrap();
+        }
+
+        let snapshot = tree.to_snapshot();
+        let mut restored = MerkleTree::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.root(), tree.root());
+        assert_eq!(restored.leaf_count(), tree.leaf_count());
+        assert_eq!(restored.to_snapshot(), snapshot);
+
+        // Both trees must evolve identically after the restore
+        for i in 11..20 {
+            let commitment = Commitment::new([i as u8; 32]);
+            tree.insert(&commitment).unwrap();
+            let leaf_index = restored.insert(&commitment).unwrap();
+            assert_eq!(restored.root(), tree.root());
+
+            let path = restored.generate_path(leaf_index).unwrap();
+            assert_eq!(path.elements, tree.generate_path(leaf_index).unwrap().elements);
+        }
+
+        // Paths for leaves before the snapshot are not available
+        assert!(restored.generate_path(0).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_empty_tree() {
+        let tree = MerkleTree::new(20);
+        let restored = MerkleTree::from_snapshot(&tree.to_snapshot()).unwrap();
+        assert_eq!(restored.root(), tree.root());
+        assert_eq!(restored.leaf_count(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_invalid_input() {
+        let mut tree = MerkleTree::new(4);
+        tree.insert(&Commitment::new([1u8; 32])).unwrap();
+        let snapshot = tree.to_snapshot();
+
+        // Truncated
+        assert!(MerkleTree::from_snapshot(&snapshot[..snapshot.len() - 1]).is_err());
+
+        // Unknown version
+        let mut bad_version = snapshot.clone();
+        bad_version[4] = SNAPSHOT_VERSION + 1;
+        assert!(MerkleTree::from_snapshot(&bad_version).is_err());
+
+        // Filled subtrees inconsistent with the latest root
+        let mut tampered = snapshot.clone();
+        tampered[13] ^= 1;
+        assert!(MerkleTree::from_snapshot(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_tree_delta() {
+        let commitments: Vec<Commitment> = (1..=16u8).map(|i| Commitment::new([i; 32])).collect();
+        let mut server = MerkleTree::from_leaves(4, &commitments[..5]).unwrap();
+        let mut client = server.clone();
+        for commitment in &commitments[5..11] {
+            server.insert(commitment).unwrap();
+        }
+
+        let delta = server.diff_since(5).unwrap();
+        assert_eq!(delta.leaf_count(), 11);
+        assert_eq!(delta.leaves.len(), 6);
+        // Level 4 is the root, level 3 the nodes covering leaves 0-7 and 8-15
+        assert_eq!(delta.nodes[3].len(), 1);
+        assert_eq!(delta.nodes[2].len(), 2);
+        assert_eq!(TreeDelta::from_bytes(&delta.to_bytes()).unwrap(), delta);
+
+        client.apply_delta(&delta).unwrap();
+        assert_eq!(client.root(), server.root());
+        assert_eq!(client.leaf_count(), 11);
+        assert!(client.root_history().eq(server.root_history()));
+        for i in 0..11 {
+            assert_eq!(client.generate_path(i).unwrap().elements, server.generate_path(i).unwrap().elements);
+        }
+        assert_eq!(client.store().len(), server.store().len());
+
+        // A delta applies only on the leaf count it was taken from
+        assert!(client.apply_delta(&delta).is_err());
+        assert!(server.diff_since(12).is_err());
+        let empty = server.diff_since(11).unwrap();
+        assert!(empty.is_empty());
+        client.apply_delta(&TreeDelta::from_bytes(&empty.to_bytes()).unwrap()).unwrap();
+        assert_eq!(client.root(), server.root());
+
+        // A client restored from a snapshot syncs up to a full tree
+        let mut restored = MerkleTree::from_snapshot(&server.to_snapshot()).unwrap();
+        for commitment in &commitments[11..] {
+            server.insert(commitment).unwrap();
+        }
+        restored.apply_delta(&server.diff_since(11).unwrap()).unwrap();
+        assert_eq!(restored.root(), server.root());
+        assert!(restored.is_full());
+        assert_eq!(restored.generate_path(15).unwrap().elements, server.generate_path(15).unwrap().elements);
+        assert!(restored.diff_since(10).is_err());
+    }
+
+    #[test]
+    fn test_tree_delta_across_batch() {
+        let commitments: Vec<Commitment> = (1..=12u8).map(|i| Commitment::new([i; 32])).collect();
+        let root_at = |count: usize| MerkleTree::from_leaves(4, &commitments[..count]).unwrap().root();
+        let server = MerkleTree::from_leaves(4, &commitments).unwrap();
+
+        // A delta without roots records a single root for its whole batch
+        let mut batched = MerkleTree::from_leaves(4, &commitments[..2]).unwrap();
+        let mut delta = server.diff_since(2).unwrap();
+        delta.roots.clear();
+        batched.apply_delta(&delta).unwrap();
+        assert!(batched.root_history().eq([root_at(1), root_at(2), root_at(12)].iter()));
+
+        // Diffing into the batch ships only the roots after the diff point
+        let delta = batched.diff_since(5).unwrap();
+        assert_eq!(delta.roots, vec![(12, root_at(12))]);
+        let mut client = MerkleTree::from_leaves(4, &commitments[..5]).unwrap();
+        client.apply_delta(&TreeDelta::from_bytes(&delta.to_bytes()).unwrap()).unwrap();
+        assert_eq!(client.root(), server.root());
+        let expected: Vec<_> = [1, 2, 3, 4, 5, 12].into_iter().map(root_at).collect();
+        assert!(client.root_history().eq(expected.iter()));
+
+        // Truncating into the batch drops its root but keeps the earlier ones
+        batched.truncate(7).unwrap();
+        assert_eq!(batched.root(), root_at(7));
+        assert!(batched.root_history().eq([root_at(1), root_at(2)].iter()));
+    }
+
+    #[test]
+    fn test_tree_delta_rejects_invalid_input() {
+        let commitments: Vec<Commitment> = (1..=9u8).map(|i| Commitment::new([i; 32])).collect();
+        let mut server = MerkleTree::from_leaves(4, &commitments[..3]).unwrap();
+        let client = server.clone();
+        for commitment in &commitments[3..] {
+            server.insert(commitment).unwrap();
+        }
+        let delta = server.diff_since(3).unwrap();
+
+        // A tampered node on the last path is caught before the store changes
+        let mut tampered = delta.clone();
+        tampered.leaves[5][0] ^= 1;
+        let mut target = client.clone();
+        assert!(matches!(target.apply_delta(&tampered), Err(ZKaneError::InvalidSnapshot(_))));
+        assert_eq!(target.root(), client.root());
+        assert_eq!(target.store().len(), client.store().len());
+
+        let mut missing = delta.clone();
+        missing.nodes[0].pop();
+        assert!(target.apply_delta(&missing).is_err());
+
+        let mut taller = delta.clone();
+        taller.height = 5;
+        assert!(target.apply_delta(&taller).is_err());
+
+        let mut reordered = delta.clone();
+        reordered.roots.swap(0, 1);
+        assert!(target.apply_delta(&reordered).is_err());
+        let mut wrong_root = delta.clone();
+        wrong_root.roots.last_mut().unwrap().1 = client.root();
+        assert!(target.apply_delta(&wrong_root).is_err());
+
+        let bytes = delta.to_bytes();
+        assert!(TreeDelta::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad_version = bytes.clone();
+        bad_version[4] = DELTA_VERSION + 1;
+        assert!(TreeDelta::from_bytes(&bad_version).is_err());
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(TreeDelta::from_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_tree_hash_backends() {
+        use crate::hash::Sha256Hash;
+        use zkane_common::TreeHash;
+
+        let commitments: Vec<_> = (0..5).map(|i| Commitment::new([i as u8; 32])).collect();
+        let blake2s = MerkleTree::from_leaves(4, &commitments).unwrap();
+        let sha256 = MerkleTree::from_leaves_with_hasher(4, &commitments, Sha256Hash).unwrap();
+        assert_ne!(sha256.root(), blake2s.root());
+
+        // A tree whose hash comes from the pool configuration matches the
+        // statically typed tree of the same backend
+        let mut configured = MerkleTree::with_hasher(4, TreeHash::Sha256);
+        for commitment in &commitments {
+            configured.insert(commitment).unwrap();
+        }
+        assert_eq!(configured.root(), sha256.root());
+
+        // Paths only verify with the hash the tree was built with
+        let path = sha256.generate_path(3).unwrap();
+        let root = sha256.root();
+        assert!(verify_merkle_path_with(&Sha256Hash, &commitments[3], 3, &path, &root, 4).unwrap());
+        assert!(!verify_merkle_path(&commitments[3], 3, &path, &root, 4).unwrap());
+
+        // So do snapshots
+        let snapshot = sha256.to_snapshot();
+        assert!(MerkleTree::from_snapshot(&snapshot).is_err());
+        let restored = MerkleTree::from_snapshot_with_hasher(&snapshot, Sha256Hash).unwrap();
+        assert_eq!(restored.root(), root);
+    }
+}
+
+#[cfg(test)]
+mod prop_tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const HEIGHT: u32 = 4;
+
+    /// Between one leaf and a full tree of `HEIGHT`
+    fn leaves() -> impl Strategy<Value = Vec<Commitment>> {
+        vec(any::<[u8; 32]>().prop_map(Commitment::new), 1..=1usize << HEIGHT)
+    }
+
+    fn build(leaves: &[Commitment]) -> MerkleTree {
+        let mut tree = MerkleTree::new(HEIGHT);
+        for leaf in leaves {
+            tree.insert(leaf).unwrap();
+        }
+        tree
+    }
+
+    proptest! {
+        #[test]
+        fn every_path_verifies(leaves in leaves()) {
+            let tree = build(&leaves);
+            let root = tree.root();
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = tree.generate_path(index as u32).unwrap();
+                prop_assert!(tree.verify_path(leaf, index as u32, &path, &root).unwrap());
+                prop_assert!(verify_merkle_path(leaf, index as u32, &path, &root, HEIGHT).unwrap());
+                prop_assert_eq!(compute_root_from_path(leaf, index as u32, &path).unwrap(), root);
+            }
+        }
+
+        #[test]
+        fn mutated_element_fails(
+            leaves in leaves(),
+            index in any::<prop::sample::Index>(),
+            level in 0..HEIGHT as usize,
+            byte in 0..32usize,
+            bit in 0..8u8,
+        ) {
+            let tree = build(&leaves);
+            let index = index.index(leaves.len());
+            let mut path = tree.generate_path(index as u32).unwrap();
+            path.elements[level][byte] ^= 1 << bit;
+            prop_assert!(!tree.verify_path(&leaves[index], index as u32, &path, &tree.root()).unwrap());
+        }
+
+        #[test]
+        fn mutated_index_fails(
+            leaves in leaves(),
+            index in any::<prop::sample::Index>(),
+            level in 0..HEIGHT as usize,
+        ) {
+            let tree = build(&leaves);
+            let index = index.index(leaves.len());
+            let mut path = tree.generate_path(index as u32).unwrap();
+            path.indices[level] = !path.indices[level];
+            prop_assert!(!tree.verify_path(&leaves[index], index as u32, &path, &tree.root()).unwrap());
+            prop_assert!(!verify_merkle_path(&leaves[index], index as u32, &path, &tree.root(), HEIGHT).unwrap());
+            let is_mismatch_at_level = matches!(
+                compute_root_from_path(&leaves[index], index as u32, &path),
+                Err(ZKaneError::MerklePathMismatch { level: found, .. }) if found as usize == level
+            );
+            prop_assert!(is_mismatch_at_level);
+        }
+
+        #[test]
+        fn insertion_is_deterministic(leaves in leaves()) {
+            let tree = build(&leaves);
+            prop_assert_eq!(tree.root(), build(&leaves).root());
+            prop_assert_eq!(tree.root(), MerkleTree::from_leaves(HEIGHT, &leaves).unwrap().root());
+            prop_assert_eq!(tree.leaf_count() as usize, leaves.len());
+
+            // Each leaf only verifies at the index it was inserted at
+            let root = tree.root();
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = tree.generate_path(index as u32).unwrap();
+                for (other, other_leaf) in leaves.iter().enumerate() {
+                    if other_leaf != leaf {
+                        prop_assert!(!tree.verify_path(other_leaf, index as u32, &path, &root).unwrap());
+                    }
+                    if other != index {
+                        prop_assert!(!tree.verify_path(leaf, other as u32, &path, &root).unwrap());
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn different_leaves_give_different_roots(a in leaves(), b in leaves()) {
+            prop_assume!(a != b);
+            prop_assert_ne!(build(&a).root(), build(&b).root());
+        }
+
+        #[test]
+        fn delta_matches_insertion(leaves in leaves(), split in any::<prop::sample::Index>()) {
+            let tree = build(&leaves);
+            let split = split.index(leaves.len() + 1);
+            let mut client = build(&leaves[..split]);
+            let delta = TreeDelta::from_bytes(&tree.diff_since(split as u32).unwrap().to_bytes()).unwrap();
+            client.apply_delta(&delta).unwrap();
+            prop_assert_eq!(client.root(), tree.root());
+            prop_assert!(client.root_history().eq(tree.root_history()));
+            prop_assert_eq!(client.store().len(), tree.store().len());
+        }
+
+        #[test]
+        fn full_tree_rejects_insert(leaves in vec(any::<[u8; 32]>().prop_map(Commitment::new), 1usize << HEIGHT)) {
+            let mut tree = build(&leaves);
+            prop_assert!(tree.is_full());
+            prop_assert!(matches!(tree.insert(&leaves[0]), Err(ZKaneError::TreeFull)));
+            let last = (leaves.len() - 1) as u32;
+            let path = tree.generate_path(last).unwrap();
+            prop_assert!(path.indices.iter().all(|&right| right));
+            prop_assert!(tree.verify_path(&leaves[last as usize], last, &path, &tree.root()).unwrap());
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! # Merkle Tree Node Storage
+//!
+//! A [`MerkleTree`](crate::MerkleTree) keeps only its frontier (the filled
+//! subtrees along the next insertion, the root and the root history) in
+//! memory, and stores its nodes in a [`NodeStore`]. The same tree logic then
+//! runs wherever the nodes have to live:
+//!
+//! - [`MemoryNodeStore`], the default, keeps every node in a hash map.
+//! - `SledNodeStore` (feature `sled`, native only) keeps them in a sled tree,
+//!   for indexers that can't hold a height-20 tree in memory.
+//! - The pool contract implements the trait over its storage pointers.
+//!
+//! Nodes are addressed by level, counted from the leaves, and index within
+//! the level. Stores that need a flat key use [`node_key`].
+
+use std::collections::HashMap;
+use zkane_common::ZKaneResult;
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+use zkane_common::ZKaneError;
+
+/// Storage of the nodes of a Merkle tree.
+///
+/// A missing node is the zero hash of its level; the tree only stores the
+/// nodes covering inserted leaves.
+pub trait NodeStore {
+    /// Get the node at `index` of `level`, if stored.
+    fn get(&self, level: u32, index: u32) -> ZKaneResult<Option<[u8; 32]>>;
+
+    /// Store the node at `index` of `level`.
+    fn set(&mut self, level: u32, index: u32, hash: [u8; 32]) -> ZKaneResult<()>;
+
+    /// Remove the node at `index` of `level`, if stored.
+    fn remove(&mut self, level: u32, index: u32) -> ZKaneResult<()>;
+}
+
+/// Get the flat key of a node: its level then its index, big-endian, so
+/// the nodes of a level are contiguous and in order.
+pub fn node_key(level: u32, index: u32) -> [u8; 8] {
+    let mut key = [0u8; 8];
+    key[..4].copy_from_slice(&level.to_be_bytes());
+    key[4..].copy_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// Node store keeping every node in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNodeStore {
+    nodes: HashMap<(u32, u32), [u8; 32]>,
+}
+
+impl MemoryNodeStore {
+    /// Get the number of stored nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if no node is stored.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl NodeStore for MemoryNodeStore {
+    fn get(&self, level: u32, index: u32) -> ZKaneResult<Option<[u8; 32]>> {
+        Ok(self.nodes.get(&(level, index)).copied())
+    }
+
+    fn set(&mut self, level: u32, index: u32, hash: [u8; 32]) -> ZKaneResult<()> {
+        self.nodes.insert((level, index), hash);
+        Ok(())
+    }
+
+    fn remove(&mut self, level: u32, index: u32) -> ZKaneResult<()> {
+        self.nodes.remove(&(level, index));
+        Ok(())
+    }
+}
+
+/// Node store keeping the nodes in a sled tree, keyed by [`node_key`].
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct SledNodeStore {
+    tree: sled::Tree,
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+impl SledNodeStore {
+    /// Store nodes in a sled tree, which should hold nothing else.
+    pub fn new(tree: sled::Tree) -> Self {
+        Self { tree }
+    }
+
+    /// Get the sled tree.
+    pub fn tree(&self) -> &sled::Tree {
+        &self.tree
+    }
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+impl NodeStore for SledNodeStore {
+    fn get(&self, level: u32, index: u32) -> ZKaneResult<Option<[u8; 32]>> {
+        let Some(value) = self.tree.get(node_key(level, index)).map_err(sled_error)? else {
+            return Ok(None);
+        };
+        let hash = value.as_ref().try_into().map_err(|_| {
+            ZKaneError::InvalidSnapshot(format!("node {} of level {} is {} bytes", index, level, value.len()))
+        })?;
+        Ok(Some(hash))
+    }
+
+    fn set(&mut self, level: u32, index: u32, hash: [u8; 32]) -> ZKaneResult<()> {
+        self.tree.insert(node_key(level, index), &hash[..]).map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, level: u32, index: u32) -> ZKaneResult<()> {
+        self.tree.remove(node_key(level, index)).map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+fn sled_error(error: sled::Error) -> ZKaneError {
+    ZKaneError::CryptoError(format!("node store: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_node_store() {
+        let mut store = MemoryNodeStore::default();
+        assert!(store.is_empty());
+        store.set(2, 5, [7u8; 32]).unwrap();
+        assert_eq!(store.get(2, 5).unwrap(), Some([7u8; 32]));
+        assert_eq!(store.get(5, 2).unwrap(), None);
+        store.remove(2, 5).unwrap();
+        assert!(store.is_empty());
+
+        // Keys order by level, then index
+        assert!(node_key(1, u32::MAX) < node_key(2, 0));
+        assert!(node_key(2, 1) < node_key(2, 256));
+    }
+}
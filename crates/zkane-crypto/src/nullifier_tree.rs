@@ -0,0 +1,508 @@
+//! # Nullifier Tree
+//!
+//! A sparse Merkle tree of spent nullifier hashes, so a stateless client or
+//! relayer can check that a nullifier is unspent as of a root without
+//! downloading the whole nullifier set.
+//!
+//! The tree is keyed by the 256 bits of the nullifier hash, most significant
+//! first, but compressed: a leaf sits at the shallowest depth where it is
+//! alone in its subtree, so an insertion only touches the nodes down to
+//! where the new key parts from its nearest neighbour. An empty subtree
+//! hashes to zero, a leaf to the [`HashFunction`]'s leaf hash of its key and
+//! an internal node to the internal hash of its children.
+//!
+//! A [`NullifierTreeProof`] is the path from the root to where the key
+//! would be, ending in an empty subtree or a leaf of another key, which
+//! proves the key is absent. Ending in the key's own leaf proves it is
+//! present. Proofs only carry the siblings that aren't empty, so they are
+//! about as long as the tree is deep on average, `log2` of the spends.
+//!
+//! ```rust
+//! use zkane_common::NullifierHash;
+//! use zkane_crypto::NullifierTree;
+//!
+//! let mut tree = NullifierTree::new();
+//! tree.insert(&NullifierHash::new([1u8; 32]))?;
+//!
+//! let unspent = NullifierHash::new([2u8; 32]);
+//! let proof = tree.prove(&unspent)?;
+//! assert!(proof.verify_non_membership(&tree.root(), &unspent, tree.hasher()));
+//! # Ok::<(), zkane_common::ZKaneError>(())
+//! ```
+
+use crate::hash::{Blake2sHash, HashFunction};
+use std::collections::HashMap;
+use zkane_common::{NullifierHash, ZKaneError, ZKaneResult};
+
+/// Number of bits of a key, the greatest depth of a leaf
+pub const NULLIFIER_TREE_DEPTH: u32 = 256;
+
+/// Hash of an empty subtree
+const EMPTY_HASH: [u8; 32] = [0u8; 32];
+
+const INTERNAL_TAG: u8 = 0;
+const LEAF_TAG: u8 = 1;
+
+/// A stored node of a [`NullifierTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SparseNode {
+    /// An internal node, with its hash
+    Internal([u8; 32]),
+    /// A leaf, with its key
+    Leaf([u8; 32]),
+}
+
+impl SparseNode {
+    /// Encode the node as a tag byte followed by its hash or key.
+    pub fn to_bytes(&self) -> [u8; 33] {
+        let (tag, value) = match self {
+            SparseNode::Internal(hash) => (INTERNAL_TAG, hash),
+            SparseNode::Leaf(key) => (LEAF_TAG, key),
+        };
+        let mut bytes = [0u8; 33];
+        bytes[0] = tag;
+        bytes[1..].copy_from_slice(value);
+        bytes
+    }
+
+    /// Decode a node encoded with [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> ZKaneResult<Self> {
+        let invalid = || ZKaneError::InvalidSnapshot(format!("invalid nullifier tree node of {} bytes", bytes.len()));
+        let value: [u8; 32] = bytes.get(1..).and_then(|value| value.try_into().ok()).ok_or_else(invalid)?;
+        match bytes[0] {
+            INTERNAL_TAG => Ok(SparseNode::Internal(value)),
+            LEAF_TAG => Ok(SparseNode::Leaf(value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Storage of the nodes of a [`NullifierTree`].
+///
+/// A node is addressed by its depth from the root and its path: the key
+/// bits leading to it, with the bits from `depth` on cleared. A missing node
+/// is an empty subtree.
+pub trait SparseNodeStore {
+    /// Get the node at `path` of `depth`, if stored.
+    fn get(&self, depth: u32, path: &[u8; 32]) -> ZKaneResult<Option<SparseNode>>;
+
+    /// Store the node at `path` of `depth`.
+    fn set(&mut self, depth: u32, path: &[u8; 32], node: SparseNode) -> ZKaneResult<()>;
+}
+
+/// Get the flat key of a nullifier tree node: its depth, big-endian, then
+/// its path.
+pub fn sparse_node_key(depth: u32, path: &[u8; 32]) -> [u8; 34] {
+    let mut key = [0u8; 34];
+    key[..2].copy_from_slice(&(depth as u16).to_be_bytes());
+    key[2..].copy_from_slice(path);
+    key
+}
+
+/// Sparse node store keeping every node in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySparseNodeStore {
+    nodes: HashMap<(u32, [u8; 32]), SparseNode>,
+}
+
+impl MemorySparseNodeStore {
+    /// Get the number of stored nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if no node is stored.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl SparseNodeStore for MemorySparseNodeStore {
+    fn get(&self, depth: u32, path: &[u8; 32]) -> ZKaneResult<Option<SparseNode>> {
+        Ok(self.nodes.get(&(depth, *path)).copied())
+    }
+
+    fn set(&mut self, depth: u32, path: &[u8; 32], node: SparseNode) -> ZKaneResult<()> {
+        self.nodes.insert((depth, *path), node);
+        Ok(())
+    }
+}
+
+/// Get bit `index` of a key, counted from the most significant.
+fn bit(key: &[u8; 32], index: u32) -> bool {
+    (key[(index / 8) as usize] >> (7 - index % 8)) & 1 == 1
+}
+
+/// Get the path of the node at `depth` above a key.
+fn path_at(key: &[u8; 32], depth: u32) -> [u8; 32] {
+    let mut path = [0u8; 32];
+    let full_bytes = (depth / 8) as usize;
+    path[..full_bytes].copy_from_slice(&key[..full_bytes]);
+    let partial_bits = depth % 8;
+    if partial_bits > 0 {
+        path[full_bytes] = key[full_bytes] & !(0xffu8 >> partial_bits);
+    }
+    path
+}
+
+/// Get the path of the child of the node at `depth` on `path`.
+fn child_path(path: &[u8; 32], depth: u32, right: bool) -> [u8; 32] {
+    let mut child = *path;
+    if right {
+        child[(depth / 8) as usize] |= 0x80 >> (depth % 8);
+    }
+    child
+}
+
+/// A compressed sparse Merkle tree of spent nullifier hashes.
+///
+/// The hash function is a type parameter, Blake2s unless another
+/// [`HashFunction`] is chosen with [`NullifierTree::with_hasher`]. The nodes
+/// are kept in a [`SparseNodeStore`], in memory unless the tree is opened on
+/// another store with [`NullifierTree::open`].
+#[derive(Debug, Clone)]
+pub struct NullifierTree<H: HashFunction = Blake2sHash, S: SparseNodeStore = MemorySparseNodeStore> {
+    store: S,
+    hasher: H,
+}
+
+impl NullifierTree {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self::with_hasher(Blake2sHash)
+    }
+}
+
+impl Default for NullifierTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: HashFunction> NullifierTree<H> {
+    /// Create an empty tree with a hash function.
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::open(hasher, MemorySparseNodeStore::default())
+    }
+}
+
+impl<H: HashFunction, S: SparseNodeStore> NullifierTree<H, S> {
+    /// Open the tree whose nodes are in `store`, or an empty tree if it
+    /// holds none.
+    pub fn open(hasher: H, store: S) -> Self {
+        Self { store, hasher }
+    }
+
+    /// Get the hash function of the tree
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Get the node store of the tree
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Get the root of the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the store.
+    pub fn try_root(&self) -> ZKaneResult<[u8; 32]> {
+        self.node_hash(0, &EMPTY_HASH)
+    }
+
+    /// Check whether a nullifier hash is in the tree.
+    pub fn contains(&self, nullifier_hash: &NullifierHash) -> ZKaneResult<bool> {
+        Ok(self.prove(nullifier_hash)?.leaf == Some(nullifier_hash.0))
+    }
+
+    /// Insert a nullifier hash.
+    ///
+    /// # Returns
+    ///
+    /// `false` if it was already in the tree, which is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the store, which may then hold some of the new
+    /// nodes.
+    pub fn insert(&mut self, nullifier_hash: &NullifierHash) -> ZKaneResult<bool> {
+        let key = nullifier_hash.0;
+        let mut depth = 0;
+        let leaf_depth = loop {
+            let path = path_at(&key, depth);
+            match self.store.get(depth, &path)? {
+                None => {
+                    self.store.set(depth, &path, SparseNode::Leaf(key))?;
+                    break depth;
+                }
+                Some(SparseNode::Leaf(other)) if other == key => return Ok(false),
+                Some(SparseNode::Leaf(other)) => {
+                    // Both leaves move below the last bit the keys share
+                    let split = (depth..NULLIFIER_TREE_DEPTH)
+                        .find(|&index| bit(&key, index) != bit(&other, index))
+                        .expect("distinct keys differ in some bit");
+                    self.store.set(split + 1, &path_at(&other, split + 1), SparseNode::Leaf(other))?;
+                    self.store.set(split + 1, &path_at(&key, split + 1), SparseNode::Leaf(key))?;
+                    break split + 1;
+                }
+                Some(SparseNode::Internal(_)) => depth += 1,
+            }
+        };
+
+        for depth in (0..leaf_depth).rev() {
+            let path = path_at(&key, depth);
+            let left = self.node_hash(depth + 1, &child_path(&path, depth, false))?;
+            let right = self.node_hash(depth + 1, &child_path(&path, depth, true))?;
+            self.store.set(depth, &path, SparseNode::Internal(self.hasher.hash_internal(&left, &right)))?;
+        }
+        Ok(true)
+    }
+
+    /// Prove whether a nullifier hash is in the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the store.
+    pub fn prove(&self, nullifier_hash: &NullifierHash) -> ZKaneResult<NullifierTreeProof> {
+        let key = nullifier_hash.0;
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        loop {
+            let path = path_at(&key, depth);
+            match self.store.get(depth, &path)? {
+                None => return Ok(NullifierTreeProof { siblings, leaf: None }),
+                Some(SparseNode::Leaf(leaf)) => return Ok(NullifierTreeProof { siblings, leaf: Some(leaf) }),
+                Some(SparseNode::Internal(_)) => {
+                    let sibling = child_path(&path, depth, !bit(&key, depth));
+                    siblings.push(self.node_hash(depth + 1, &sibling)?);
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Get the hash of the node at `path` of `depth`
+    fn node_hash(&self, depth: u32, path: &[u8; 32]) -> ZKaneResult<[u8; 32]> {
+        Ok(match self.store.get(depth, path)? {
+            None => EMPTY_HASH,
+            Some(SparseNode::Leaf(key)) => self.hasher.hash_leaf(&key),
+            Some(SparseNode::Internal(hash)) => hash,
+        })
+    }
+}
+
+impl<H: HashFunction> NullifierTree<H> {
+    /// Get the root of the tree.
+    pub fn root(&self) -> [u8; 32] {
+        self.try_root().expect("the memory store can't fail")
+    }
+}
+
+/// Proof that a nullifier hash is or isn't in a [`NullifierTree`].
+///
+/// The path from the root follows the key's bits and ends at an empty
+/// subtree or at a leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullifierTreeProof {
+    /// Hashes of the siblings along the path, from the root down
+    pub siblings: Vec<[u8; 32]>,
+    /// Key of the leaf the path ends at, `None` for an empty subtree
+    pub leaf: Option<[u8; 32]>,
+}
+
+impl NullifierTreeProof {
+    /// Check that the proof shows `nullifier_hash` is absent from the tree
+    /// with `root`, so unspent as of that root.
+    pub fn verify_non_membership<H: HashFunction>(
+        &self,
+        root: &[u8; 32],
+        nullifier_hash: &NullifierHash,
+        hasher: &H,
+    ) -> bool {
+        self.leaf != Some(nullifier_hash.0) && self.verify(root, nullifier_hash, hasher)
+    }
+
+    /// Check that the proof shows `nullifier_hash` is in the tree with
+    /// `root`, so spent as of that root.
+    pub fn verify_membership<H: HashFunction>(&self, root: &[u8; 32], nullifier_hash: &NullifierHash, hasher: &H) -> bool {
+        self.leaf == Some(nullifier_hash.0) && self.verify(root, nullifier_hash, hasher)
+    }
+
+    /// Check that the path follows the key to `root`
+    fn verify<H: HashFunction>(&self, root: &[u8; 32], nullifier_hash: &NullifierHash, hasher: &H) -> bool {
+        let key = nullifier_hash.0;
+        let depth = self.siblings.len() as u32;
+        if depth > NULLIFIER_TREE_DEPTH {
+            return false;
+        }
+        let mut hash = match &self.leaf {
+            None => EMPTY_HASH,
+            // A leaf is alone in the subtree of its path
+            Some(leaf) if path_at(leaf, depth) != path_at(&key, depth) => return false,
+            Some(leaf) => hasher.hash_leaf(leaf),
+        };
+        for (index, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = if bit(&key, index as u32) {
+                hasher.hash_internal(sibling, &hash)
+            } else {
+                hasher.hash_internal(&hash, sibling)
+            };
+        }
+        hash == *root
+    }
+
+    /// Encode the proof.
+    ///
+    /// The encoding is the path length as a little-endian `u16`, a bitmap of
+    /// the siblings that aren't empty, one bit per sibling from the most
+    /// significant, those siblings, then a byte set to 1 if a leaf key
+    /// follows.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bitmap = vec![0u8; self.siblings.len().div_ceil(8)];
+        let mut hashes = Vec::new();
+        for (index, sibling) in self.siblings.iter().enumerate() {
+            if *sibling != EMPTY_HASH {
+                bitmap[index / 8] |= 0x80 >> (index % 8);
+                hashes.extend_from_slice(sibling);
+            }
+        }
+        let mut bytes = (self.siblings.len() as u16).to_le_bytes().to_vec();
+        bytes.extend(bitmap);
+        bytes.extend(hashes);
+        match &self.leaf {
+            Some(leaf) => {
+                bytes.push(1);
+                bytes.extend_from_slice(leaf);
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Decode a proof encoded with [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> ZKaneResult<Self> {
+        let mut rest = bytes;
+        let depth = u16::from_le_bytes(take(&mut rest, 2)?.try_into().unwrap()) as usize;
+        if depth > NULLIFIER_TREE_DEPTH as usize {
+            return Err(invalid_proof("path longer than the tree"));
+        }
+        let bitmap = take(&mut rest, depth.div_ceil(8))?;
+        let mut siblings = Vec::with_capacity(depth);
+        for index in 0..depth {
+            if bitmap[index / 8] & (0x80 >> (index % 8)) != 0 {
+                siblings.push(take(&mut rest, 32)?.try_into().unwrap());
+            } else {
+                siblings.push(EMPTY_HASH);
+            }
+        }
+        let leaf = match take(&mut rest, 1)?[0] {
+            0 => None,
+            1 => Some(take(&mut rest, 32)?.try_into().unwrap()),
+            _ => return Err(invalid_proof("bad leaf flag")),
+        };
+        if !rest.is_empty() {
+            return Err(invalid_proof("trailing bytes"));
+        }
+        Ok(Self { siblings, leaf })
+    }
+}
+
+fn invalid_proof(message: &str) -> ZKaneError {
+    ZKaneError::InvalidProof(format!("invalid nullifier tree proof: {}", message))
+}
+
+/// Split `len` bytes off the front of `rest`
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> ZKaneResult<&'a [u8]> {
+    if rest.len() < len {
+        return Err(invalid_proof("truncated"));
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::PoseidonHash;
+
+    fn hash(first: u8, last: u8) -> NullifierHash {
+        let mut bytes = [0u8; 32];
+        bytes[0] = first;
+        bytes[31] = last;
+        NullifierHash::new(bytes)
+    }
+
+    #[test]
+    fn test_nullifier_tree_proofs() {
+        let mut tree = NullifierTree::new();
+        let empty = tree.root();
+        assert_eq!(empty, EMPTY_HASH);
+        assert!(tree.prove(&hash(1, 0)).unwrap().verify_non_membership(&empty, &hash(1, 0), tree.hasher()));
+
+        // Keys sharing all but their last bit split at the bottom of the tree
+        let spent = [hash(0x80, 0), hash(0x00, 1), hash(0x00, 0), hash(0xff, 7)];
+        for nullifier_hash in &spent {
+            assert!(tree.insert(nullifier_hash).unwrap());
+        }
+        let root = tree.root();
+        assert!(!tree.insert(&spent[0]).unwrap());
+        assert_eq!(tree.root(), root);
+
+        for nullifier_hash in &spent {
+            let proof = tree.prove(nullifier_hash).unwrap();
+            assert!(proof.verify_membership(&root, nullifier_hash, tree.hasher()));
+            assert!(!proof.verify_non_membership(&root, nullifier_hash, tree.hasher()));
+            assert!(tree.contains(nullifier_hash).unwrap());
+        }
+        for unspent in [hash(0x00, 2), hash(0x40, 0), hash(0xff, 6)] {
+            let proof = tree.prove(&unspent).unwrap();
+            assert!(proof.verify_non_membership(&root, &unspent, tree.hasher()));
+            assert!(!proof.verify_non_membership(&empty, &unspent, tree.hasher()));
+            assert!(!tree.contains(&unspent).unwrap());
+        }
+
+        // A proof of one key doesn't carry over to another
+        let proof = tree.prove(&hash(0x00, 2)).unwrap();
+        assert!(!proof.verify_non_membership(&root, &hash(0x00, 1), tree.hasher()));
+    }
+
+    #[test]
+    fn test_nullifier_tree_order_independent() {
+        let spent: Vec<_> = (0..20u8).map(|i| NullifierHash::new(crate::hash::sha256(&[i]))).collect();
+        let mut forward = NullifierTree::with_hasher(PoseidonHash);
+        let mut backward = NullifierTree::with_hasher(PoseidonHash);
+        for (a, b) in spent.iter().zip(spent.iter().rev()) {
+            forward.insert(a).unwrap();
+            backward.insert(b).unwrap();
+        }
+        assert_eq!(forward.root(), backward.root());
+        assert_ne!(forward.root(), NullifierTree::new().root());
+    }
+
+    #[test]
+    fn test_nullifier_tree_proof_encoding() {
+        let mut tree = NullifierTree::new();
+        tree.insert(&hash(0x00, 0)).unwrap();
+        tree.insert(&hash(0x00, 1)).unwrap();
+
+        // Only the last of the 256 siblings isn't empty
+        let proof = tree.prove(&hash(0x00, 1)).unwrap();
+        assert_eq!(proof.siblings.len(), 256);
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 2 + 32 + 32 + 1 + 32);
+        assert_eq!(NullifierTreeProof::from_bytes(&bytes).unwrap(), proof);
+        assert!(NullifierTreeProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(NullifierTreeProof::from_bytes(&[bytes.clone(), vec![0]].concat()).is_err());
+
+        let proof = tree.prove(&hash(0x80, 0)).unwrap();
+        assert_eq!(NullifierTreeProof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+
+        let node = SparseNode::Leaf([5u8; 32]);
+        assert_eq!(SparseNode::from_bytes(&node.to_bytes()).unwrap(), node);
+        assert!(SparseNode::from_bytes(&[2u8; 33]).is_err());
+        assert!(sparse_node_key(1, &[0xff; 32]) < sparse_node_key(2, &[0; 32]));
+    }
+}
@@ -1,103 +1,335 @@
 //! Poseidon hash function implementation for ZKane
-//! 
-//! This is a simplified implementation for demonstration purposes.
-//! In production, you would use a proper Poseidon implementation
-//! that matches the one used in your Noir circuits.
+//!
+//! Poseidon is instantiated over two scalar fields:
+//!
+//! - **BN254**, using the circomlib parameters. These are the parameters of
+//!   Noir's `std::hash::poseidon::bn254` module, so hashes computed here match
+//!   the Noir circuits proven with Barretenberg exactly.
+//! - **BLS12-381**, using parameters derived with the Grain LFSR as in
+//!   arkworks. These are used by the arkworks Groth16 circuits in [`crate::zkp`].
+//!
+//! A [`PoseidonConfig`] selects the field and the arity (number of inputs).
+//! Field elements are encoded as 32-byte big-endian values, the same as
+//! Noir's `Field::to_be_bytes`. Inputs larger than the field modulus are
+//! reduced.
+//!
+//! Inputs of any length are hashed with the sponge [`PoseidonHasher`]:
+//!
+//! ```rust
+//! use zkane_crypto::{poseidon_sponge, PoseidonHasher};
+//!
+//! let mut hasher = PoseidonHasher::new(7);
+//! hasher.update(b"hello ")?;
+//! hasher.update(b"world")?;
+//! assert_eq!(hasher.finalize()?, poseidon_sponge(7, b"hello world")?);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
 
-use anyhow::Result;
-use ark_ff::{PrimeField, Field, Zero};
+use anyhow::{anyhow, Result};
+use ark_bls12_381::Fr as Bls12Fr;
 use ark_bn254::Fr as Bn254Fr;
+use ark_crypto_primitives::crh::{poseidon::CRH, CRHScheme};
+use ark_crypto_primitives::sponge::poseidon::{find_poseidon_ark_and_mds, PoseidonConfig as ArkPoseidonConfig};
+use ark_ff::{BigInteger, PrimeField, Zero};
 use ark_std::vec::Vec;
+use light_poseidon::{Poseidon, PoseidonHasher as _};
+use serde::{Deserialize, Serialize};
 
-/// Poseidon hash function using BN254 scalar field
-/// 
-/// Note: This is a placeholder implementation. In production, you should use
-/// a proper Poseidon implementation that matches your Noir circuit exactly.
+/// Smallest supported arity
+pub const MIN_ARITY: usize = 1;
+
+/// Largest supported arity
+pub const MAX_ARITY: usize = 4;
+
+/// Number of full rounds, for every width
+const FULL_ROUNDS: usize = 8;
+
+/// S-box exponent
+const ALPHA: u64 = 5;
+
+/// Number of field elements [`PoseidonHasher`] absorbs per permutation
+pub const SPONGE_RATE: usize = MAX_ARITY - 1;
+
+/// Number of input bytes packed into each absorbed field element, so every
+/// element is below the modulus of both fields
+pub const SPONGE_BYTES_PER_ELEMENT: usize = 31;
+
+/// Number of input bytes absorbed per permutation
+const SPONGE_BLOCK_SIZE: usize = SPONGE_RATE * SPONGE_BYTES_PER_ELEMENT;
+
+/// Number of partial rounds for widths 2 to 5 (arity 1 to 4), giving
+/// 128-bit security for 255-bit fields with x^5 S-boxes
+const PARTIAL_ROUNDS: [usize; MAX_ARITY] = [56, 57, 56, 60];
+
+/// The scalar field Poseidon is instantiated over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PoseidonCurve {
+    /// BN254 scalar field, used by Noir and Barretenberg
+    Bn254,
+    /// BLS12-381 scalar field, used by the arkworks Groth16 circuits
+    Bls12_381,
+}
+
+/// Selects the field and arity of a Poseidon instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PoseidonConfig {
+    curve: PoseidonCurve,
+    arity: usize,
+}
+
+impl PoseidonConfig {
+    /// Create a configuration for the given curve and arity.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the arity is not between [`MIN_ARITY`] and
+    /// [`MAX_ARITY`].
+    pub fn new(curve: PoseidonCurve, arity: usize) -> Result<Self> {
+        if !(MIN_ARITY..=MAX_ARITY).contains(&arity) {
+            return Err(anyhow!(
+                "Unsupported Poseidon arity {}, expected {} to {}",
+                arity,
+                MIN_ARITY,
+                MAX_ARITY
+            ));
+        }
+        Ok(Self { curve, arity })
+    }
+
+    /// BN254 configuration, matching Noir's `poseidon::bn254::hash_<arity>`.
+    pub fn bn254(arity: usize) -> Result<Self> {
+        Self::new(PoseidonCurve::Bn254, arity)
+    }
+
+    /// BLS12-381 configuration, matching the arkworks circuits.
+    pub fn bls12_381(arity: usize) -> Result<Self> {
+        Self::new(PoseidonCurve::Bls12_381, arity)
+    }
+
+    /// Get the curve
+    pub fn curve(&self) -> PoseidonCurve {
+        self.curve
+    }
+
+    /// Get the arity
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Hash exactly `arity` field elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of inputs doesn't match the arity.
+    pub fn hash(&self, inputs: &[[u8; 32]]) -> Result<[u8; 32]> {
+        if inputs.len() != self.arity {
+            return Err(anyhow!(
+                "Poseidon arity is {} but {} inputs were given",
+                self.arity,
+                inputs.len()
+            ));
+        }
+
+        match self.curve {
+            PoseidonCurve::Bn254 => {
+                let elements: Vec<Bn254Fr> = inputs.iter().map(|i| Bn254Fr::from_be_bytes_mod_order(i)).collect();
+                let mut hasher = Poseidon::<Bn254Fr>::new_circom(self.arity)
+                    .map_err(|e| anyhow!("Failed to create Poseidon hasher: {}", e))?;
+                let result = hasher
+                    .hash(&elements)
+                    .map_err(|e| anyhow!("Poseidon hash failed: {}", e))?;
+                Ok(field_element_to_bytes(&result))
+            }
+            PoseidonCurve::Bls12_381 => {
+                let elements: Vec<Bls12Fr> = inputs.iter().map(|i| Bls12Fr::from_be_bytes_mod_order(i)).collect();
+                let result = CRH::evaluate(&self.bls12_381_params()?, elements)
+                    .map_err(|e| anyhow!("Poseidon hash failed: {}", e))?;
+                Ok(field_element_to_bytes(&result))
+            }
+        }
+    }
+
+    /// Get the arkworks parameters of a BLS12-381 configuration, for use in
+    /// R1CS circuits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration is not over BLS12-381.
+    pub fn bls12_381_params(&self) -> Result<ArkPoseidonConfig<Bls12Fr>> {
+        if self.curve != PoseidonCurve::Bls12_381 {
+            return Err(anyhow!("Arkworks parameters are only available for BLS12-381"));
+        }
+
+        let partial_rounds = PARTIAL_ROUNDS[self.arity - 1];
+        let (ark, mds) = find_poseidon_ark_and_mds::<Bls12Fr>(
+            Bls12Fr::MODULUS_BIT_SIZE as u64,
+            self.arity,
+            FULL_ROUNDS as u64,
+            partial_rounds as u64,
+            0,
+        );
+        Ok(ArkPoseidonConfig::new(FULL_ROUNDS, partial_rounds, ALPHA, mds, ark, self.arity, 1))
+    }
+}
+
+/// Poseidon hash of arbitrary bytes over BN254
+///
+/// The input is split into 31-byte field elements which are folded together
+/// with [`poseidon_hash_two`]. A single element is hashed with
+/// [`poseidon_hash_single`].
 pub fn poseidon_hash(input: &[u8]) -> Result<[u8; 32]> {
-    // Convert bytes to field elements
     let field_elements = bytes_to_field_elements(input)?;
-    
-    // Apply Poseidon permutation (simplified)
-    let result = poseidon_permutation(&field_elements)?;
-    
-    // Convert back to bytes
-    field_element_to_bytes(&result)
+    let mut elements = field_elements.iter().map(field_element_to_bytes);
+
+    // There is always at least one element
+    let first = elements.next().unwrap_or_default();
+    match elements.next() {
+        None => poseidon_hash_single(&first),
+        Some(second) => {
+            let mut result = poseidon_hash_two(&first, &second)?;
+            for element in elements {
+                result = poseidon_hash_two(&result, &element)?;
+            }
+            Ok(result)
+        }
+    }
+}
+
+/// Sponge hasher for inputs of any length.
+///
+/// The state is a single field element, initialized to the domain tag. Input
+/// bytes are packed into big-endian 31-byte field elements and absorbed
+/// [`SPONGE_RATE`] at a time by hashing them with the state at arity
+/// [`MAX_ARITY`], i.e. `state = hash_4([state, e0, e1, e2])`, which is
+/// reproduced in Noir with `poseidon::bn254::hash_4`. On finalization the
+/// input is padded with a `0x01` byte and then zeros up to a whole block, so
+/// inputs differing only in trailing zeros hash differently, and the final
+/// state is the hash.
+///
+/// Hashes of different domains are independent: use a distinct tag for each
+/// kind of data hashed.
+#[derive(Debug, Clone)]
+pub struct PoseidonHasher {
+    config: PoseidonConfig,
+    state: [u8; 32],
+    buffer: Vec<u8>,
+}
+
+impl PoseidonHasher {
+    /// Create a BN254 hasher for a domain.
+    pub fn new(domain: u64) -> Self {
+        Self::with_curve(PoseidonCurve::Bn254, domain)
+    }
+
+    /// Create a hasher over a curve for a domain.
+    pub fn with_curve(curve: PoseidonCurve, domain: u64) -> Self {
+        let mut state = [0u8; 32];
+        state[24..].copy_from_slice(&domain.to_be_bytes());
+        Self {
+            config: PoseidonConfig { curve, arity: MAX_ARITY },
+            state,
+            buffer: Vec::with_capacity(SPONGE_BLOCK_SIZE),
+        }
+    }
+
+    /// Absorb input bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a permutation fails.
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        for byte in data {
+            self.buffer.push(*byte);
+            if self.buffer.len() == SPONGE_BLOCK_SIZE {
+                self.absorb_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pad the input and get the hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a permutation fails.
+    pub fn finalize(mut self) -> Result<[u8; 32]> {
+        self.buffer.push(0x01);
+        self.buffer.resize(SPONGE_BLOCK_SIZE, 0);
+        self.absorb_block()?;
+        Ok(self.state)
+    }
+
+    /// Absorb the full block in the buffer.
+    fn absorb_block(&mut self) -> Result<()> {
+        let mut inputs = [[0u8; 32]; MAX_ARITY];
+        inputs[0] = self.state;
+        for (input, chunk) in inputs[1..].iter_mut().zip(self.buffer.chunks(SPONGE_BYTES_PER_ELEMENT)) {
+            input[32 - chunk.len()..].copy_from_slice(chunk);
+        }
+        self.state = self.config.hash(&inputs)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Sponge hash of arbitrary bytes over BN254 in a domain, see
+/// [`PoseidonHasher`].
+pub fn poseidon_sponge(domain: u64, input: &[u8]) -> Result<[u8; 32]> {
+    let mut hasher = PoseidonHasher::new(domain);
+    hasher.update(input)?;
+    hasher.finalize()
 }
 
 /// Convert bytes to BN254 field elements
 fn bytes_to_field_elements(input: &[u8]) -> Result<Vec<Bn254Fr>> {
     let mut elements = Vec::new();
-    
+
     // Process input in 31-byte chunks (to stay within field size)
     for chunk in input.chunks(31) {
-        let mut bytes = [0u8; 32];
-        bytes[1..chunk.len() + 1].copy_from_slice(chunk);
-        
-        let element = Bn254Fr::from_le_bytes_mod_order(&bytes);
-        elements.push(element);
+        elements.push(Bn254Fr::from_be_bytes_mod_order(chunk));
     }
-    
+
     // Ensure we have at least one element
     if elements.is_empty() {
         elements.push(Bn254Fr::zero());
     }
-    
+
     Ok(elements)
 }
 
-/// Convert a field element back to bytes
-fn field_element_to_bytes(element: &Bn254Fr) -> Result<[u8; 32]> {
-    use ark_serialize::CanonicalSerialize;
-    
-    let mut bytes = Vec::new();
-    element.serialize_compressed(&mut bytes)?;
-    
-    // Pad or truncate to 32 bytes
-    let mut result = [0u8; 32];
-    let len = std::cmp::min(bytes.len(), 32);
-    result[..len].copy_from_slice(&bytes[..len]);
-    
-    Ok(result)
-}
+/// Convert a field element to 32 big-endian bytes
+fn field_element_to_bytes<F: PrimeField>(element: &F) -> [u8; 32] {
+    let bytes = element.into_bigint().to_bytes_be();
 
-/// Simplified Poseidon permutation
-/// 
-/// This is a placeholder implementation. In production, you need to use
-/// the exact same Poseidon parameters and implementation as your Noir circuit.
-fn poseidon_permutation(input: &[Bn254Fr]) -> Result<Bn254Fr> {
-    // For now, just sum all elements and square the result
-    // This is NOT a secure hash function - just a placeholder
-    let mut result = Bn254Fr::zero();
-    
-    for element in input {
-        result += element;
-    }
-    
-    // Apply some simple operations to mix the input
-    result = result.square();
-    result += Bn254Fr::from(1u64);
-    result = result.square();
-    
-    Ok(result)
+    let mut result = [0u8; 32];
+    result[32 - bytes.len()..].copy_from_slice(&bytes);
+    result
 }
 
 /// Poseidon hash for two field elements (common case)
+///
+/// Matches Noir's `poseidon::bn254::hash_2`.
 pub fn poseidon_hash_two(left: &[u8; 32], right: &[u8; 32]) -> Result<[u8; 32]> {
-    let mut input = Vec::with_capacity(64);
-    input.extend_from_slice(left);
-    input.extend_from_slice(right);
-    poseidon_hash(&input)
+    PoseidonConfig::bn254(2)?.hash(&[*left, *right])
 }
 
 /// Poseidon hash for a single 32-byte input
+///
+/// Matches Noir's `poseidon::bn254::hash_1`.
 pub fn poseidon_hash_single(input: &[u8; 32]) -> Result<[u8; 32]> {
-    poseidon_hash(input)
+    PoseidonConfig::bn254(1)?.hash(&[*input])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn field(n: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&n.to_be_bytes());
+        bytes
+    }
+
     #[test]
     fn test_poseidon_hash_deterministic() {
         let input = b"hello world";
@@ -110,10 +342,10 @@ mod tests {
     fn test_poseidon_hash_different_inputs() {
         let input1 = b"hello world";
         let input2 = b"hello world!";
-        
+
         let hash1 = poseidon_hash(input1).unwrap();
         let hash2 = poseidon_hash(input2).unwrap();
-        
+
         assert_ne!(hash1, hash2);
     }
 
@@ -121,20 +353,20 @@ mod tests {
     fn test_poseidon_hash_two() {
         let left = [1u8; 32];
         let right = [2u8; 32];
-        
+
         let hash1 = poseidon_hash_two(&left, &right).unwrap();
         let hash2 = poseidon_hash_two(&left, &right).unwrap();
-        
+
         assert_eq!(hash1, hash2);
     }
 
     #[test]
     fn test_poseidon_hash_single() {
         let input = [42u8; 32];
-        
+
         let hash1 = poseidon_hash_single(&input).unwrap();
         let hash2 = poseidon_hash_single(&input).unwrap();
-        
+
         assert_eq!(hash1, hash2);
     }
 
@@ -148,7 +380,80 @@ mod tests {
     #[test]
     fn test_field_element_to_bytes() {
         let element = Bn254Fr::from(42u64);
-        let bytes = field_element_to_bytes(&element).unwrap();
-        assert_eq!(bytes.len(), 32);
+        let bytes = field_element_to_bytes(&element);
+        assert_eq!(bytes, field(42));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_bn254_matches_noir() {
+        // Outputs of Noir's std::hash::poseidon::bn254::hash_1([1]) and hash_2([1, 2])
+        let expected_single = "29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133";
+        let expected_two = "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a";
+
+        assert_eq!(hex::encode(poseidon_hash_single(&field(1)).unwrap()), expected_single);
+        assert_eq!(hex::encode(poseidon_hash_two(&field(1), &field(2)).unwrap()), expected_two);
+    }
+
+    #[test]
+    fn test_config_arity() {
+        assert!(PoseidonConfig::bn254(0).is_err());
+        assert!(PoseidonConfig::bn254(5).is_err());
+
+        for arity in MIN_ARITY..=MAX_ARITY {
+            let inputs: Vec<[u8; 32]> = (1..=arity as u64).map(field).collect();
+            for config in [PoseidonConfig::bn254(arity).unwrap(), PoseidonConfig::bls12_381(arity).unwrap()] {
+                assert!(config.hash(&inputs).is_ok());
+                assert!(config.hash(&inputs[1..]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_curves_differ() {
+        let inputs = [field(1), field(2)];
+        let bn254 = PoseidonConfig::bn254(2).unwrap().hash(&inputs).unwrap();
+        let bls = PoseidonConfig::bls12_381(2).unwrap().hash(&inputs).unwrap();
+        assert_ne!(bn254, bls);
+    }
+
+    #[test]
+    fn test_sponge_streaming() {
+        let input: Vec<u8> = (0..=255u8).cycle().take(3 * SPONGE_BLOCK_SIZE + 5).collect();
+        let expected = poseidon_sponge(1, &input).unwrap();
+        for split in [0, 1, SPONGE_BLOCK_SIZE, SPONGE_BLOCK_SIZE + 1, input.len()] {
+            let mut hasher = PoseidonHasher::new(1);
+            hasher.update(&input[..split]).unwrap();
+            hasher.update(&input[split..]).unwrap();
+            assert_eq!(hasher.finalize().unwrap(), expected);
+        }
+
+        // A single block is hash_4 of the domain and the padded input
+        let mut block = [[0u8; 32]; MAX_ARITY];
+        block[0] = field(1);
+        block[1][1..4].copy_from_slice(&[0xab, 0xcd, 0x01]);
+        assert_eq!(poseidon_sponge(1, &[0xab, 0xcd]).unwrap(), PoseidonConfig::bn254(4).unwrap().hash(&block).unwrap());
+    }
+
+    #[test]
+    fn test_sponge_separation() {
+        let empty = poseidon_sponge(0, &[]).unwrap();
+        assert_ne!(empty, poseidon_sponge(0, &[0]).unwrap());
+        assert_ne!(poseidon_sponge(0, &[1]).unwrap(), poseidon_sponge(0, &[1, 0]).unwrap());
+        assert_ne!(poseidon_sponge(0, &[0; SPONGE_BLOCK_SIZE]).unwrap(), poseidon_sponge(0, &[0; SPONGE_BLOCK_SIZE - 1]).unwrap());
+        assert_ne!(empty, poseidon_sponge(1, &[]).unwrap());
+
+        let mut bls = PoseidonHasher::with_curve(PoseidonCurve::Bls12_381, 0);
+        bls.update(b"zkane").unwrap();
+        assert_ne!(bls.finalize().unwrap(), poseidon_sponge(0, b"zkane").unwrap());
+    }
+
+    #[test]
+    fn test_bls12_381_params() {
+        let config = PoseidonConfig::bls12_381(2).unwrap();
+        let params = config.bls12_381_params().unwrap();
+        assert_eq!(params.rate, 2);
+        assert_eq!(params.ark.len(), params.full_rounds + params.partial_rounds);
+
+        assert!(PoseidonConfig::bn254(2).unwrap().bls12_381_params().is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! # Rust <-> Noir Test Vectors
+//!
+//! Commitments and nullifier hashes are computed off-chain in Rust and checked
+//! in-circuit by the Noir withdrawal circuit, so the two implementations must
+//! agree bit for bit. This module generates JSON vectors from the Rust side and
+//! renders them as Noir tests.
+//!
+//! The checked-in vectors live in `test_vectors/hash_vectors.json` and the
+//! rendered Noir tests in `noir/withdraw/src/vectors.nr`. Both are produced by
+//! the `generate-test-vectors` binary:
+//!
+//! ```bash
+//! cargo run -p zkane-crypto --bin generate-test-vectors -- \
+//!     --json crates/zkane-crypto/test_vectors/hash_vectors.json \
+//!     --noir noir/withdraw/src/vectors.nr
+//! ```
+//!
+//! The Rust tests check that the JSON vectors still match the Rust hashes and
+//! that the Noir tests are in sync with the JSON; `nargo test` then checks the
+//! same vectors against the circuit.
+
+use crate::{generate_commitment, generate_nullifier_hash, poseidon_hash_single, poseidon_hash_two};
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use zkane_common::{Nullifier, Secret};
+
+/// Seed used for the checked-in vectors
+pub const DEFAULT_SEED: u64 = 2024;
+
+/// Number of vectors generated per function
+pub const DEFAULT_COUNT: usize = 4;
+
+/// Renders the Noir expression hashing the given inputs
+type NoirCall = fn(&[String]) -> String;
+
+/// A single hash test vector, with field elements as `0x`-prefixed big-endian hex.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashVector {
+    /// The inputs, in the order they are passed to the hash
+    pub inputs: Vec<String>,
+    /// The expected output
+    pub output: String,
+}
+
+/// Test vectors for every hash shared between Rust and Noir.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    /// `poseidon_hash_single(x)`, Noir `poseidon::bn254::hash_1([x])`
+    pub poseidon_hash_single: Vec<HashVector>,
+    /// `poseidon_hash_two(l, r)`, Noir `poseidon::bn254::hash_2([l, r])`
+    pub poseidon_hash_two: Vec<HashVector>,
+    /// `generate_commitment(nullifier, secret)`, inputs are `[nullifier, secret]`
+    pub commitment: Vec<HashVector>,
+    /// `generate_nullifier_hash(nullifier)`, inputs are `[nullifier]`
+    pub nullifier_hash: Vec<HashVector>,
+}
+
+impl TestVectors {
+    /// Generate `count` vectors per function from a seeded RNG.
+    ///
+    /// The inputs always include the edge cases zero and one, and random
+    /// values are kept below the BN254 modulus so Noir accepts them as
+    /// `Field` literals.
+    pub fn generate(seed: u64, count: usize) -> Result<Self> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut next = |i: usize| -> [u8; 32] {
+            match i {
+                0 => field_from_u64(0),
+                1 => field_from_u64(1),
+                _ => {
+                    let mut bytes = [0u8; 32];
+                    rng.fill_bytes(&mut bytes[1..]);
+                    bytes
+                }
+            }
+        };
+
+        let mut vectors = TestVectors {
+            poseidon_hash_single: Vec::with_capacity(count),
+            poseidon_hash_two: Vec::with_capacity(count),
+            commitment: Vec::with_capacity(count),
+            nullifier_hash: Vec::with_capacity(count),
+        };
+
+        for i in 0..count {
+            let x = next(i);
+            vectors.poseidon_hash_single.push(vector(&[x], poseidon_hash_single(&x)?));
+
+            let (left, right) = (next(i), next(i + 1));
+            vectors.poseidon_hash_two.push(vector(&[left, right], poseidon_hash_two(&left, &right)?));
+
+            let (nullifier, secret) = (next(i), next(i + 1));
+            let commitment = generate_commitment(&Nullifier::new(nullifier), &Secret::new(secret))?;
+            vectors.commitment.push(vector(&[nullifier, secret], *commitment.as_bytes()));
+
+            let nullifier = next(i);
+            let nullifier_hash = generate_nullifier_hash(&Nullifier::new(nullifier))?;
+            vectors.nullifier_hash.push(vector(&[nullifier], *nullifier_hash.as_bytes()));
+        }
+
+        Ok(vectors)
+    }
+
+    /// Parse vectors from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize the vectors as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Recompute every vector with the Rust implementation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first vector whose output doesn't match.
+    pub fn verify(&self) -> Result<()> {
+        for (i, v) in self.poseidon_hash_single.iter().enumerate() {
+            let [x] = parse_inputs::<1>(v)?;
+            check("poseidon_hash_single", i, v, poseidon_hash_single(&x)?)?;
+        }
+        for (i, v) in self.poseidon_hash_two.iter().enumerate() {
+            let [left, right] = parse_inputs::<2>(v)?;
+            check("poseidon_hash_two", i, v, poseidon_hash_two(&left, &right)?)?;
+        }
+        for (i, v) in self.commitment.iter().enumerate() {
+            let [nullifier, secret] = parse_inputs::<2>(v)?;
+            let commitment = generate_commitment(&Nullifier::new(nullifier), &Secret::new(secret))?;
+            check("commitment", i, v, *commitment.as_bytes())?;
+        }
+        for (i, v) in self.nullifier_hash.iter().enumerate() {
+            let [nullifier] = parse_inputs::<1>(v)?;
+            let nullifier_hash = generate_nullifier_hash(&Nullifier::new(nullifier))?;
+            check("nullifier_hash", i, v, *nullifier_hash.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Render the vectors as Noir tests against the withdrawal circuit's
+    /// hash helpers.
+    pub fn to_noir(&self) -> String {
+        let mut out = String::new();
+        out.push_str("// Rust <-> Noir hash test vectors\n");
+        out.push_str("// Generated by `generate-test-vectors` in zkane-crypto, do not edit\n\n");
+        out.push_str("use std::hash::poseidon;\n");
+        out.push_str("use crate::{compute_commitment, compute_nullifier_hash};\n");
+
+        let cases: [(&str, &[HashVector], NoirCall); 4] = [
+            ("poseidon_hash_single", &self.poseidon_hash_single, |i| {
+                format!("poseidon::bn254::hash_1([{}])", i[0])
+            }),
+            ("poseidon_hash_two", &self.poseidon_hash_two, |i| {
+                format!("poseidon::bn254::hash_2([{}, {}])", i[0], i[1])
+            }),
+            ("commitment", &self.commitment, |i| format!("compute_commitment({}, {})", i[0], i[1])),
+            ("nullifier_hash", &self.nullifier_hash, |i| format!("compute_nullifier_hash({})", i[0])),
+        ];
+
+        for (name, vectors, call) in cases {
+            for (i, v) in vectors.iter().enumerate() {
+                // Writing to a String never fails
+                let _ = write!(
+                    out,
+                    "\n#[test]\nfn test_vector_{}_{}() {{\n    assert({} == {});\n}}\n",
+                    name,
+                    i,
+                    call(&v.inputs),
+                    v.output
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn field_from_u64(n: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&n.to_be_bytes());
+    bytes
+}
+
+fn to_field_hex(bytes: &[u8; 32]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn vector(inputs: &[[u8; 32]], output: [u8; 32]) -> HashVector {
+    HashVector {
+        inputs: inputs.iter().map(to_field_hex).collect(),
+        output: to_field_hex(&output),
+    }
+}
+
+fn parse_field(s: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("Field element {} is not 32 bytes", s))
+}
+
+fn parse_inputs<const N: usize>(v: &HashVector) -> Result<[[u8; 32]; N]> {
+    if v.inputs.len() != N {
+        return Err(anyhow!("Expected {} inputs, got {}", N, v.inputs.len()));
+    }
+    let mut inputs = [[0u8; 32]; N];
+    for (input, s) in inputs.iter_mut().zip(&v.inputs) {
+        *input = parse_field(s)?;
+    }
+    Ok(inputs)
+}
+
+fn check(name: &str, index: usize, v: &HashVector, actual: [u8; 32]) -> Result<()> {
+    if parse_field(&v.output)? != actual {
+        return Err(anyhow!(
+            "{} vector {} mismatch: expected {}, got {}",
+            name,
+            index,
+            v.output,
+            to_field_hex(&actual)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON_VECTORS: &str = include_str!("../test_vectors/hash_vectors.json");
+    const NOIR_VECTORS: &str = include_str!("../../../noir/withdraw/src/vectors.nr");
+
+    #[test]
+    fn test_checked_in_vectors_match_rust() {
+        let vectors = TestVectors::from_json(JSON_VECTORS).unwrap();
+        assert_eq!(vectors.poseidon_hash_single.len(), DEFAULT_COUNT);
+        vectors.verify().unwrap();
+    }
+
+    #[test]
+    fn test_noir_vectors_in_sync() {
+        let vectors = TestVectors::from_json(JSON_VECTORS).unwrap();
+        assert_eq!(
+            vectors.to_noir(),
+            NOIR_VECTORS,
+            "noir/withdraw/src/vectors.nr is stale, rerun generate-test-vectors"
+        );
+    }
+
+    #[test]
+    fn test_generate_roundtrip() {
+        let vectors = TestVectors::generate(1, 3).unwrap();
+        let parsed = TestVectors::from_json(&vectors.to_json().unwrap()).unwrap();
+        assert_eq!(vectors, parsed);
+        parsed.verify().unwrap();
+    }
+
+    #[test]
+    fn test_verify_detects_mismatch() {
+        let mut vectors = TestVectors::generate(1, 2).unwrap();
+        vectors.commitment[1].output = to_field_hex(&[7u8; 32]);
+        let err = vectors.verify().unwrap_err();
+        assert!(err.to_string().contains("commitment vector 1"));
+    }
+}
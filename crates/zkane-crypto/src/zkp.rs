@@ -7,31 +7,69 @@
 //! ## Components
 //!
 //! - **WithdrawalCircuit**: The R1CS circuit for a withdrawal operation.
-//! - **Prover**: Functions for generating proofs.
+//! - **Prover**: Functions for generating proofs, with progress reporting and
+//!   cancellation through a [`prover::ProverHandle`].
 //! - **Verifier**: Functions for verifying proofs.
+//! - **Receipts**: Proofs that a note was deposited, without revealing it, in
+//!   [`receipt`].
+//! - **Splits**: Withdrawals of part of a note, with change kept in the pool,
+//!   in [`split`].
+//! - **Amounts**: Proofs that a variable-amount deposit's commitment opens to
+//!   the amount paid in, in [`amount`].
+//! - **Witness caching**: Retrying a failed proof without synthesizing its
+//!   witness again, in [`witness`].
 
+pub mod amount;
 pub mod poseidon_params;
+pub mod prover;
+pub mod receipt;
+pub mod split;
+pub mod witness;
+
+pub use prover::{prove_with_handle, prove_witness, synthesize_witness, ProofStage, ProverHandle};
+pub use witness::{prove_cached, MemoryWitnessStore, SynthesizedWitness, WitnessKey, WitnessStore};
 
 use crate::gadgets::poseidon::PoseidonGadget;
 use ark_bls12_381::{Bls12_381, Fr};
+use anyhow::{anyhow, Result};
 use ark_crypto_primitives::{
-    crh::poseidon::constraints::CRHParametersVar,
+    crh::{poseidon::{constraints::CRHParametersVar, CRH}, CRHScheme},
 };
+use ark_ff::PrimeField;
 use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey, PreparedVerifyingKey};
 use ark_r1cs_std::{prelude::*, fields::fp::FpVar};
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use ark_snark::SNARK;
+use zkane_common::{WithdrawalProof, ZKaneError, ZKaneResult, ZkAssetId};
 use ark_std::rand::rngs::StdRng;
 use ark_std::rand::SeedableRng;
 
 /// This circuit proves that a user knows a valid deposit note (secret and
 /// nullifier) corresponding to a commitment in the Merkle tree, without
-/// revealing the note itself.
+/// revealing the note itself. The commitment is bound to the pool's asset ID
+/// and denomination, which are public inputs, and the withdrawal to the hash
+/// of its recipient outputs and the height it expires after.
 #[derive(Clone)]
 pub struct WithdrawalCircuit {
     // --- Public Inputs ---
     /// The hash of the nullifier, used to prevent double-spending.
     pub nullifier_hash: Fr,
+    /// The hash of the recipient outputs (zero if not bound to a recipient set).
+    pub recipients_hash: Fr,
+    /// The hash of the relayer's fee output (zero for self-relayed withdrawals).
+    pub relayer_output_hash: Fr,
+    /// The fee paid to the relayer out of the denomination.
+    pub fee: Fr,
+    /// The last block height the withdrawal may be spent at (zero if it
+    /// never expires).
+    pub not_after_height: Fr,
+    /// The block number of the pool's asset ID.
+    pub asset_block: Fr,
+    /// The transaction number of the pool's asset ID.
+    pub asset_tx: Fr,
+    /// The pool's denomination.
+    pub denomination: Fr,
 
     // --- Private Witnesses ---
     /// The secret part of the deposit note.
@@ -40,25 +78,80 @@ pub struct WithdrawalCircuit {
     pub nullifier: Fr,
 }
 
+impl WithdrawalCircuit {
+    /// Build the circuit for a deposit note, deriving the nullifier hash.
+    ///
+    /// Byte values are read as big-endian field elements, reduced modulo the
+    /// field order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_note(
+        secret: &[u8; 32],
+        nullifier: &[u8; 32],
+        asset_id: &ZkAssetId,
+        denomination: u128,
+        recipients_hash: &[u8; 32],
+        relayer_output_hash: &[u8; 32],
+        fee: u128,
+        not_after_height: u64,
+    ) -> Result<Self> {
+        let nullifier = Fr::from_be_bytes_mod_order(nullifier);
+        let nullifier_hash = CRH::evaluate(&poseidon_params::for_arity(1), [nullifier])
+            .map_err(|e| anyhow!("failed to hash nullifier: {}", e))?;
+        Ok(Self {
+            nullifier_hash,
+            recipients_hash: Fr::from_be_bytes_mod_order(recipients_hash),
+            relayer_output_hash: Fr::from_be_bytes_mod_order(relayer_output_hash),
+            fee: Fr::from(fee),
+            not_after_height: Fr::from(not_after_height),
+            asset_block: Fr::from(asset_id.block),
+            asset_tx: Fr::from(asset_id.tx),
+            denomination: Fr::from(denomination),
+            secret: Fr::from_be_bytes_mod_order(secret),
+            nullifier,
+        })
+    }
+}
+
 impl ConstraintSynthesizer<Fr> for WithdrawalCircuit {
     fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
         // Allocate public inputs
         let nullifier_hash = FpVar::new_input(cs.clone(), || Ok(self.nullifier_hash))?;
+        let recipients_hash = FpVar::new_input(cs.clone(), || Ok(self.recipients_hash))?;
+        let relayer_output_hash = FpVar::new_input(cs.clone(), || Ok(self.relayer_output_hash))?;
+        let fee = FpVar::new_input(cs.clone(), || Ok(self.fee))?;
+        let not_after_height = FpVar::new_input(cs.clone(), || Ok(self.not_after_height))?;
+        let asset_block = FpVar::new_input(cs.clone(), || Ok(self.asset_block))?;
+        let asset_tx = FpVar::new_input(cs.clone(), || Ok(self.asset_tx))?;
+        let denomination = FpVar::new_input(cs.clone(), || Ok(self.denomination))?;
 
         // Allocate private witnesses
         let secret = FpVar::new_witness(cs.clone(), || Ok(self.secret))?;
         let nullifier = FpVar::new_witness(cs.clone(), || Ok(self.nullifier))?;
 
-        let poseidon_params = poseidon_params::new();
-        let params_var = CRHParametersVar::new_witness(cs.clone(), || Ok(poseidon_params.clone()))?;
+        let params_four = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(4))?;
+        let params_two = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(2))?;
+        let params_one = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(1))?;
 
-        // 1. Verify the commitment is correctly derived from the secret and nullifier.
-        let _commitment = PoseidonGadget::hash_two(cs.clone(), &params_var, &secret, &nullifier)?;
+        // 1. Verify the commitment is correctly derived from the nullifier and
+        //    secret, bound to the pool's asset ID and denomination.
+        let asset_id_hash = PoseidonGadget::hash_two(cs.clone(), &params_two, &asset_block, &asset_tx)?;
+        let _commitment = PoseidonGadget::hash_four(
+            cs.clone(),
+            &params_four,
+            [&nullifier, &secret, &asset_id_hash, &denomination],
+        )?;
 
         // 2. Verify the nullifier hash is correctly derived from the nullifier.
-        let computed_nullifier_hash = PoseidonGadget::hash_one(cs.clone(), &params_var, &nullifier)?;
+        let computed_nullifier_hash = PoseidonGadget::hash_one(cs.clone(), &params_one, &nullifier)?;
         computed_nullifier_hash.enforce_equal(&nullifier_hash)?;
 
+        // 3. Bind the recipients, relayer output, fee and expiry to the proof
+        //    so they cannot be altered by whoever broadcasts the withdrawal.
+        let _recipients_square = recipients_hash.square()?;
+        let _relayer_square = relayer_output_hash.square()?;
+        let _fee_square = fee.square()?;
+        let _expiry_square = not_after_height.square()?;
+
         Ok(())
     }
 }
@@ -67,6 +160,13 @@ pub fn setup() -> (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
     let mut rng = StdRng::seed_from_u64(0u64);
     let circuit = WithdrawalCircuit {
         nullifier_hash: Fr::default(),
+        recipients_hash: Fr::default(),
+        relayer_output_hash: Fr::default(),
+        fee: Fr::default(),
+        not_after_height: Fr::default(),
+        asset_block: Fr::default(),
+        asset_tx: Fr::default(),
+        denomination: Fr::default(),
         secret: Fr::default(),
         nullifier: Fr::default(),
     };
@@ -83,17 +183,107 @@ pub fn prove(
     Groth16::<Bls12_381>::prove(pk, circuit, &mut rng).unwrap()
 }
 
+/// Encode a proving key in compressed form.
+pub fn proving_key_to_bytes(pk: &ProvingKey<Bls12_381>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    pk.serialize_compressed(&mut bytes)
+        .map_err(|e| anyhow!("failed to encode proving key: {}", e))?;
+    Ok(bytes)
+}
+
+/// Decode a proving key encoded by [`proving_key_to_bytes`].
+pub fn proving_key_from_bytes(bytes: &[u8]) -> Result<ProvingKey<Bls12_381>> {
+    ProvingKey::deserialize_compressed(bytes).map_err(|e| anyhow!("invalid proving key: {}", e))
+}
+
+/// Encode a proof in compressed form.
+pub fn proof_to_bytes(proof: &Proof<Bls12_381>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    proof
+        .serialize_compressed(&mut bytes)
+        .map_err(|e| anyhow!("failed to encode proof: {}", e))?;
+    Ok(bytes)
+}
+
+/// Decode a proof encoded by [`proof_to_bytes`].
+pub fn proof_from_bytes(bytes: &[u8]) -> Result<Proof<Bls12_381>> {
+    Proof::deserialize_compressed(bytes).map_err(|e| anyhow!("invalid proof: {}", e))
+}
+
+/// Encode a verifying key in compressed form.
+pub fn verifying_key_to_bytes(vk: &VerifyingKey<Bls12_381>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    vk.serialize_compressed(&mut bytes)
+        .map_err(|e| anyhow!("failed to encode verifying key: {}", e))?;
+    Ok(bytes)
+}
+
+/// Decode a verifying key encoded by [`verifying_key_to_bytes`].
+pub fn verifying_key_from_bytes(bytes: &[u8]) -> Result<VerifyingKey<Bls12_381>> {
+    VerifyingKey::deserialize_compressed(bytes).map_err(|e| anyhow!("invalid verifying key: {}", e))
+}
+
 /// Verify a proof with the given verifying key and public inputs.
+///
+/// The asset ID and denomination are those of the pool the withdrawal is
+/// made from.
+#[allow(clippy::too_many_arguments)]
 pub fn verify(
     vk: &VerifyingKey<Bls12_381>,
     proof: &Proof<Bls12_381>,
     nullifier_hash: Fr,
+    recipients_hash: Fr,
+    relayer_output_hash: Fr,
+    fee: Fr,
+    not_after_height: Fr,
+    asset_id: &ZkAssetId,
+    denomination: u128,
 ) -> bool {
-    let public_inputs = &[nullifier_hash];
+    let public_inputs = &[
+        nullifier_hash,
+        recipients_hash,
+        relayer_output_hash,
+        fee,
+        not_after_height,
+        Fr::from(asset_id.block),
+        Fr::from(asset_id.tx),
+        Fr::from(denomination),
+    ];
     let pvk = PreparedVerifyingKey::from(vk.clone());
     Groth16::<Bls12_381>::verify_with_processed_vk(&pvk, public_inputs, proof).unwrap()
 }
 
+/// Verify a withdrawal proof against a pool's compressed verifying key.
+///
+/// The public inputs are read from the proof, hashes as big-endian field
+/// elements. The asset ID and denomination are those of the pool.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidVerifierKey`] if the key can't be decoded
+/// and [`ZKaneError::InvalidProof`] if the proof can't. A well-formed proof
+/// that doesn't verify gives `Ok(false)`.
+pub fn verify_withdrawal(
+    verifying_key: &[u8],
+    proof: &WithdrawalProof,
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> ZKaneResult<bool> {
+    let vk = verifying_key_from_bytes(verifying_key).map_err(|e| ZKaneError::InvalidVerifierKey(e.to_string()))?;
+    let groth16_proof = proof_from_bytes(&proof.proof).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+    Ok(verify(
+        &vk,
+        &groth16_proof,
+        Fr::from_be_bytes_mod_order(proof.nullifier_hash.as_bytes()),
+        Fr::from_be_bytes_mod_order(&proof.recipients_hash),
+        Fr::from_be_bytes_mod_order(&proof.relayer_output_hash),
+        Fr::from(proof.fee),
+        Fr::from(proof.not_after_height),
+        asset_id,
+        denomination,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,11 +301,25 @@ mod tests {
         let secret = Fr::rand(&mut rng);
         let nullifier = Fr::rand(&mut rng);
 
-        let poseidon_params = poseidon_params::new();
+        let poseidon_params = poseidon_params::for_arity(1);
         let nullifier_hash = CRH::evaluate(&poseidon_params, [nullifier]).unwrap();
 
+        let recipients_hash = Fr::rand(&mut rng);
+        let relayer_output_hash = Fr::rand(&mut rng);
+        let fee = Fr::from(1000u64);
+        let not_after_height = Fr::from(850_000u64);
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let denomination = 100_000u128;
+
         let circuit = WithdrawalCircuit {
             nullifier_hash,
+            recipients_hash,
+            relayer_output_hash,
+            fee,
+            not_after_height,
+            asset_block: Fr::from(asset_id.block),
+            asset_tx: Fr::from(asset_id.tx),
+            denomination: Fr::from(denomination),
             secret,
             nullifier,
         };
@@ -124,7 +328,56 @@ mod tests {
         let proof = prove(&pk, circuit);
 
         // 4. Verify proof
-        let is_valid = verify(&vk, &proof, nullifier_hash);
+        let is_valid = verify(&vk, &proof, nullifier_hash, recipients_hash, relayer_output_hash, fee, not_after_height, &asset_id, denomination);
         assert!(is_valid);
+
+        // 5. A tampered fee must not verify
+        let tampered_fee = Fr::from(2000u64);
+        assert!(!verify(&vk, &proof, nullifier_hash, recipients_hash, relayer_output_hash, tampered_fee, not_after_height, &asset_id, denomination));
+
+        // 6. Nor may the expiry be lifted
+        assert!(!verify(&vk, &proof, nullifier_hash, recipients_hash, relayer_output_hash, fee, Fr::from(0u64), &asset_id, denomination));
+
+        // 7. Nor may the recipients be swapped
+        let other_recipients = Fr::rand(&mut rng);
+        assert!(!verify(&vk, &proof, nullifier_hash, other_recipients, relayer_output_hash, fee, not_after_height, &asset_id, denomination));
+
+        // 8. Nor may the proof be used against another pool
+        let other_asset = ZkAssetId { block: 2, tx: 2 };
+        assert!(!verify(&vk, &proof, nullifier_hash, recipients_hash, relayer_output_hash, fee, not_after_height, &other_asset, denomination));
+        assert!(!verify(&vk, &proof, nullifier_hash, recipients_hash, relayer_output_hash, fee, not_after_height, &asset_id, denomination * 10));
+    }
+
+    #[test]
+    fn test_verify_withdrawal() {
+        let (pk, vk) = setup();
+        let vk_bytes = verifying_key_to_bytes(&vk).unwrap();
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let (recipients, relayer) = ([0x11u8; 32], [0x22u8; 32]);
+        let circuit = WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &asset_id, 1000, &recipients, &relayer, 5, 0)
+            .unwrap();
+        let proof = WithdrawalProof {
+            recipients_hash: recipients,
+            ..WithdrawalProof::new(
+                proof_to_bytes(&prove(&pk, circuit)).unwrap(),
+                [0u8; 32],
+                zkane_common::NullifierHash::new(split::circuit_nullifier_hash(&[2u8; 32]).unwrap()),
+                zkane_common::Recipient::default(),
+            )
+            .with_relayer(relayer, 5)
+        };
+
+        assert!(verify_withdrawal(&vk_bytes, &proof, &asset_id, 1000).unwrap());
+        assert!(!verify_withdrawal(&vk_bytes, &proof, &asset_id, 100).unwrap());
+        assert!(!verify_withdrawal(&vk_bytes, &WithdrawalProof { fee: 6, ..proof.clone() }, &asset_id, 1000).unwrap());
+        assert!(!verify_withdrawal(&vk_bytes, &proof.clone().with_expiry(100), &asset_id, 1000).unwrap());
+        assert!(matches!(
+            verify_withdrawal(&vk_bytes, &WithdrawalProof { proof: vec![1, 2, 3], ..proof.clone() }, &asset_id, 1000),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+        assert!(matches!(
+            verify_withdrawal(&[0u8; 8], &proof, &asset_id, 1000),
+            Err(ZKaneError::InvalidVerifierKey(_))
+        ));
     }
 }
\ No newline at end of file
@@ -0,0 +1,213 @@
+//! # Deposit Amounts
+//!
+//! Notes of a variable-amount pool commit to their value with
+//! [`circuit_commitment`], so the pool can't read a deposit's value from its
+//! commitment. Instead each deposit carries an amount proof that the
+//! commitment opens to a note of the amount paid in, without revealing the
+//! note's secret or nullifier. Without it a depositor could pay in a little
+//! and commit to a note worth a lot.
+//!
+//! Once deposited, the note's value only ever leaves the pool through split
+//! withdrawals, whose proof conserves it.
+//!
+//! ```rust,no_run
+//! use zkane_common::ZkAssetId;
+//! use zkane_crypto::zkp::amount::{prove_amount, setup_amount, verify_amount};
+//! use zkane_crypto::zkp::ProverHandle;
+//!
+//! let asset_id = ZkAssetId { block: 2, tx: 1 };
+//! let (pk, vk) = setup_amount();
+//! let witness = prove_amount(&pk, (&[1u8; 32], &[2u8; 32]), &asset_id, 12_345, &ProverHandle::new())?;
+//! assert!(verify_amount(&vk, &witness, &asset_id)?);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::poseidon_params;
+use super::prover::{prove_with_handle, ProverHandle};
+use super::receipt::circuit_commitment;
+use super::{proof_from_bytes, proof_to_bytes};
+use crate::gadgets::poseidon::PoseidonGadget;
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_crypto_primitives::crh::poseidon::constraints::CRHParametersVar;
+use ark_ff::PrimeField;
+use ark_groth16::{Groth16, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use zkane_common::{AmountWitness, Commitment, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// This circuit proves that the prover knows the note of the public
+/// commitment, and that the note is for the public asset ID and amount.
+#[derive(Clone)]
+pub struct AmountCircuit {
+    // --- Public Inputs ---
+    /// The deposited commitment.
+    pub commitment: Fr,
+    /// The block number of the pool's asset ID.
+    pub asset_block: Fr,
+    /// The transaction number of the pool's asset ID.
+    pub asset_tx: Fr,
+    /// The amount paid into the pool.
+    pub amount: Fr,
+
+    // --- Private Witnesses ---
+    /// The secret of the note.
+    pub secret: Fr,
+    /// The nullifier of the note.
+    pub nullifier: Fr,
+}
+
+impl AmountCircuit {
+    /// Build the circuit for a note's secret and nullifier, deriving its
+    /// commitment.
+    pub fn from_note(
+        note: (&[u8; 32], &[u8; 32]),
+        asset_id: &ZkAssetId,
+        amount: u128,
+    ) -> ZKaneResult<Self> {
+        let (secret, nullifier) = note;
+        Ok(Self {
+            commitment: Fr::from_be_bytes_mod_order(&circuit_commitment(nullifier, secret, asset_id, amount)?),
+            asset_block: Fr::from(asset_id.block),
+            asset_tx: Fr::from(asset_id.tx),
+            amount: Fr::from(amount),
+            secret: Fr::from_be_bytes_mod_order(secret),
+            nullifier: Fr::from_be_bytes_mod_order(nullifier),
+        })
+    }
+
+    /// A circuit with all values zero, for setup.
+    pub fn blank() -> Self {
+        Self {
+            commitment: Fr::default(),
+            asset_block: Fr::default(),
+            asset_tx: Fr::default(),
+            amount: Fr::default(),
+            secret: Fr::default(),
+            nullifier: Fr::default(),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for AmountCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate public inputs
+        let commitment = FpVar::new_input(cs.clone(), || Ok(self.commitment))?;
+        let asset_block = FpVar::new_input(cs.clone(), || Ok(self.asset_block))?;
+        let asset_tx = FpVar::new_input(cs.clone(), || Ok(self.asset_tx))?;
+        let amount = FpVar::new_input(cs.clone(), || Ok(self.amount))?;
+
+        // Allocate private witnesses
+        let secret = FpVar::new_witness(cs.clone(), || Ok(self.secret))?;
+        let nullifier = FpVar::new_witness(cs.clone(), || Ok(self.nullifier))?;
+
+        let params_four = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(4))?;
+        let params_two = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(2))?;
+
+        // Verify the commitment opens to a note of the asset ID and amount.
+        // The amount is a public `u128`, so it needs no range check here.
+        let asset_id_hash = PoseidonGadget::hash_two(cs.clone(), &params_two, &asset_block, &asset_tx)?;
+        let computed_commitment = PoseidonGadget::hash_four(
+            cs.clone(),
+            &params_four,
+            [&nullifier, &secret, &asset_id_hash, &amount],
+        )?;
+        computed_commitment.enforce_equal(&commitment)?;
+
+        Ok(())
+    }
+}
+
+/// Generate the keys of the amount circuit.
+pub fn setup_amount() -> (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
+    let mut rng = StdRng::seed_from_u64(0u64);
+    Groth16::<Bls12_381>::circuit_specific_setup(AmountCircuit::blank(), &mut rng).unwrap()
+}
+
+/// Prove that a note's commitment opens to `amount`, producing the witness
+/// of its deposit.
+///
+/// `note` is the note's secret and nullifier.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::ProofCancelled`] if the handle is cancelled.
+pub fn prove_amount(
+    pk: &ProvingKey<Bls12_381>,
+    note: (&[u8; 32], &[u8; 32]),
+    asset_id: &ZkAssetId,
+    amount: u128,
+    handle: &ProverHandle,
+) -> ZKaneResult<AmountWitness> {
+    let (secret, nullifier) = note;
+    let circuit = AmountCircuit::from_note(note, asset_id, amount)?;
+    let proof = prove_with_handle(pk, circuit, handle)?;
+    Ok(AmountWitness {
+        commitment: Commitment::new(circuit_commitment(nullifier, secret, asset_id, amount)?),
+        amount,
+        proof: proof_to_bytes(&proof)?,
+    })
+}
+
+/// Verify the amount proof of a deposit against its witness.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidProof`] if the proof can't be decoded.
+pub fn verify_amount(
+    vk: &VerifyingKey<Bls12_381>,
+    witness: &AmountWitness,
+    asset_id: &ZkAssetId,
+) -> ZKaneResult<bool> {
+    let proof = proof_from_bytes(&witness.proof).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+    let public_inputs = [
+        Fr::from_be_bytes_mod_order(witness.commitment.as_bytes()),
+        Fr::from(asset_id.block),
+        Fr::from(asset_id.tx),
+        Fr::from(witness.amount),
+    ];
+    let pvk = PreparedVerifyingKey::from(vk.clone());
+    Ok(Groth16::<Bls12_381>::verify_with_processed_vk(&pvk, &public_inputs, &proof).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+    const NOTE: (&[u8; 32], &[u8; 32]) = (&[1u8; 32], &[2u8; 32]);
+
+    #[test]
+    fn test_amount_proof() {
+        let (pk, vk) = setup_amount();
+        let witness = prove_amount(&pk, NOTE, &ASSET_ID, 12_345, &ProverHandle::new()).unwrap();
+        assert_eq!(witness.commitment.0, circuit_commitment(NOTE.1, NOTE.0, &ASSET_ID, 12_345).unwrap());
+        assert!(verify_amount(&vk, &witness, &ASSET_ID).unwrap());
+
+        // The amount, commitment and asset are bound to the proof
+        assert!(!verify_amount(&vk, &AmountWitness { amount: 12_346, ..witness.clone() }, &ASSET_ID).unwrap());
+        let other = circuit_commitment(NOTE.1, NOTE.0, &ASSET_ID, 1_000_000).unwrap();
+        let inflated = AmountWitness { commitment: Commitment::new(other), ..witness.clone() };
+        assert!(!verify_amount(&vk, &inflated, &ASSET_ID).unwrap());
+        assert!(!verify_amount(&vk, &witness, &ZkAssetId { block: 2, tx: 2 }).unwrap());
+
+        let garbled = AmountWitness { proof: vec![1, 2, 3], ..witness };
+        assert!(matches!(verify_amount(&vk, &garbled, &ASSET_ID), Err(ZKaneError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn test_commitment_must_open_to_amount() {
+        let (pk, _vk) = setup_amount();
+
+        // A note worth more than the amount paid in can't be proven
+        let mut circuit = AmountCircuit::from_note(NOTE, &ASSET_ID, 100).unwrap();
+        circuit.commitment =
+            Fr::from_be_bytes_mod_order(&circuit_commitment(NOTE.1, NOTE.0, &ASSET_ID, 1_000_000).unwrap());
+        assert!(matches!(
+            prove_with_handle(&pk, circuit, &ProverHandle::new()),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+    }
+}
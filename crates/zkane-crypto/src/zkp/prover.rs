@@ -0,0 +1,277 @@
+//! # Prover Handles
+//!
+//! Generating a withdrawal proof takes seconds natively and much longer in the
+//! browser. A [`ProverHandle`] reports which stage the prover is in and lets
+//! the caller cancel the proof, so interfaces don't appear frozen.
+//!
+//! Cancellation takes effect at the next stage boundary: a stage that has
+//! started runs to completion.
+//!
+//! ```rust,no_run
+//! use zkane_common::ZkAssetId;
+//! use zkane_crypto::zkp::{prove_with_handle, setup, ProverHandle, WithdrawalCircuit};
+//!
+//! let (pk, _vk) = setup();
+//! let asset_id = ZkAssetId { block: 2, tx: 1 };
+//! let circuit = WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &asset_id, 100000, &[0u8; 32], &[0u8; 32], 0, 0)?;
+//!
+//! let handle = ProverHandle::new();
+//! handle.on_progress(|stage| eprintln!("{} ({:.0}%)", stage, stage.progress() * 100.0));
+//! let proof = prove_with_handle(&pk, circuit, &handle)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_ff::UniformRand;
+use ark_groth16::{Groth16, Proof, ProvingKey};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, OptimizationGoal};
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use super::witness::SynthesizedWitness;
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// A stage of proof generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofStage {
+    /// Generating the circuit's constraints and witness
+    Synthesizing,
+    /// Computing the proof from the witness
+    Proving,
+    /// The proof is complete
+    Done,
+}
+
+impl ProofStage {
+    /// Approximate share of the total work completed when the stage starts.
+    pub fn progress(&self) -> f64 {
+        match self {
+            ProofStage::Synthesizing => 0.0,
+            ProofStage::Proving => 0.1,
+            ProofStage::Done => 1.0,
+        }
+    }
+
+    /// Get the name of the stage.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProofStage::Synthesizing => "synthesizing",
+            ProofStage::Proving => "proving",
+            ProofStage::Done => "done",
+        }
+    }
+}
+
+impl fmt::Display for ProofStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+type ProgressCallback = Box<dyn Fn(ProofStage) + Send + Sync>;
+
+/// Observes and cancels proof generation.
+///
+/// Clones share their state, so a clone can cancel a proof running on
+/// another thread.
+#[derive(Clone, Default)]
+pub struct ProverHandle {
+    cancelled: Arc<AtomicBool>,
+    callbacks: Arc<Mutex<Vec<ProgressCallback>>>,
+}
+
+impl ProverHandle {
+    /// Create a handle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `callback` whenever the prover enters a new stage.
+    pub fn on_progress(&self, callback: impl Fn(ProofStage) + Send + Sync + 'static) {
+        self.callbacks.lock().unwrap().push(Box::new(callback));
+    }
+
+    /// Cancel the proof at the next stage boundary.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether the proof has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Enter a stage, unless the proof has been cancelled.
+    fn enter(&self, stage: ProofStage) -> ZKaneResult<()> {
+        if self.is_cancelled() {
+            return Err(ZKaneError::ProofCancelled);
+        }
+        self.report(stage);
+        Ok(())
+    }
+
+    fn report(&self, stage: ProofStage) {
+        for callback in self.callbacks.lock().unwrap().iter() {
+            callback(stage);
+        }
+    }
+}
+
+impl fmt::Debug for ProverHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProverHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Generate a proof, reporting progress to and checking for cancellation
+/// through `handle`.
+///
+/// Produces the same proof as [`super::prove`] for a [`super::WithdrawalCircuit`].
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::ProofCancelled`] if the handle is cancelled before
+/// the proof completes, and [`ZKaneError::InvalidProof`] if the witness
+/// doesn't satisfy the circuit.
+pub fn prove_with_handle(
+    pk: &ProvingKey<Bls12_381>,
+    circuit: impl ConstraintSynthesizer<Fr>,
+    handle: &ProverHandle,
+) -> ZKaneResult<Proof<Bls12_381>> {
+    let witness = synthesize_witness(circuit, handle)?;
+    prove_witness(pk, &witness, handle)
+}
+
+/// Run the synthesizing stage of a proof: generate the circuit's constraints
+/// and the witness satisfying them.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::ProofCancelled`] if the handle is cancelled, and
+/// [`ZKaneError::InvalidProof`] if the witness doesn't satisfy the circuit.
+pub fn synthesize_witness(
+    circuit: impl ConstraintSynthesizer<Fr>,
+    handle: &ProverHandle,
+) -> ZKaneResult<SynthesizedWitness> {
+    handle.enter(ProofStage::Synthesizing)?;
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    circuit
+        .generate_constraints(cs.clone())
+        .map_err(|e| ZKaneError::CryptoError(e.to_string()))?;
+    if !cs.is_satisfied().map_err(|e| ZKaneError::CryptoError(e.to_string()))? {
+        return Err(ZKaneError::InvalidProof(
+            "witness doesn't satisfy the circuit".to_string(),
+        ));
+    }
+    cs.finalize();
+    let matrices = cs
+        .to_matrices()
+        .ok_or_else(|| ZKaneError::CryptoError("constraint system has no matrices".to_string()))?;
+    let assignment = {
+        let cs = cs
+            .borrow()
+            .ok_or_else(|| ZKaneError::CryptoError("constraint system has no assignment".to_string()))?;
+        [cs.instance_assignment.as_slice(), cs.witness_assignment.as_slice()].concat()
+    };
+    Ok(SynthesizedWitness { matrices, assignment })
+}
+
+/// Run the proving stage of a proof from a synthesized witness.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::ProofCancelled`] if the handle is cancelled.
+pub fn prove_witness(
+    pk: &ProvingKey<Bls12_381>,
+    witness: &SynthesizedWitness,
+    handle: &ProverHandle,
+) -> ZKaneResult<Proof<Bls12_381>> {
+    let mut rng = StdRng::seed_from_u64(0u64);
+    let r = Fr::rand(&mut rng);
+    let s = Fr::rand(&mut rng);
+
+    handle.enter(ProofStage::Proving)?;
+    let matrices = &witness.matrices;
+    let proof = Groth16::<Bls12_381>::create_proof_with_reduction_and_matrices(
+        pk,
+        r,
+        s,
+        matrices,
+        matrices.num_instance_variables,
+        matrices.num_constraints,
+        &witness.assignment,
+    )
+    .map_err(|e| ZKaneError::CryptoError(e.to_string()))?;
+
+    handle.report(ProofStage::Done);
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::{prove, setup, verify, WithdrawalCircuit};
+    use zkane_common::ZkAssetId;
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+    #[test]
+    fn test_prove_with_handle() {
+        let (pk, vk) = setup();
+        let circuit = WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &ASSET_ID, 100_000, &[6u8; 32], &[3u8; 32], 500, 0).unwrap();
+
+        let stages = Arc::new(Mutex::new(Vec::new()));
+        let handle = ProverHandle::new();
+        let observed = stages.clone();
+        handle.on_progress(move |stage| observed.lock().unwrap().push(stage));
+
+        let proof = prove_with_handle(&pk, circuit.clone(), &handle).unwrap();
+        assert_eq!(
+            *stages.lock().unwrap(),
+            vec![ProofStage::Synthesizing, ProofStage::Proving, ProofStage::Done]
+        );
+        assert_eq!(proof, prove(&pk, circuit.clone()));
+        assert!(verify(
+            &vk,
+            &proof,
+            circuit.nullifier_hash,
+            circuit.recipients_hash,
+            circuit.relayer_output_hash,
+            circuit.fee,
+            circuit.not_after_height,
+            &ASSET_ID,
+            100_000
+        ));
+    }
+
+    #[test]
+    fn test_cancel_at_stage_boundary() {
+        let (pk, _vk) = setup();
+        let circuit = WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &ASSET_ID, 100_000, &[6u8; 32], &[3u8; 32], 500, 0).unwrap();
+
+        // Cancelled from a callback during synthesis, stops before proving
+        let handle = ProverHandle::new();
+        let canceller = handle.clone();
+        handle.on_progress(move |stage| {
+            if stage == ProofStage::Synthesizing {
+                canceller.cancel();
+            }
+        });
+        assert!(matches!(
+            prove_with_handle(&pk, circuit.clone(), &handle),
+            Err(ZKaneError::ProofCancelled)
+        ));
+
+        let mut unsatisfied = circuit;
+        unsatisfied.nullifier_hash = Fr::from(1u64);
+        assert!(matches!(
+            prove_with_handle(&pk, unsatisfied, &ProverHandle::new()),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+    }
+}
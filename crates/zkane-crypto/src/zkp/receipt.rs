@@ -0,0 +1,339 @@
+//! # Deposit Receipts
+//!
+//! A deposit receipt proves that the prover owns a note for a given asset and
+//! denomination whose commitment is in a Merkle tree, without revealing the
+//! note or its leaf. It serves compliance-style attestations and proving to a
+//! counterparty that a note is held, while keeping the deposit unlinkable.
+//!
+//! The tree is hashed with [`PoseidonBls12Hash`] over the commitments derived
+//! by [`circuit_commitment`], so the circuit can recompute the path. To limit
+//! the claim to deposits made in a block range, the receipt tree holds only
+//! the commitments deposited in that range, and the verifier rebuilds its root
+//! from the pool's deposits before checking the proof. The counterparty's
+//! challenge is a public input, so a receipt can't be replayed to another
+//! counterparty.
+//!
+//! ```rust,no_run
+//! use zkane_common::{Commitment, ZkAssetId};
+//! use zkane_crypto::zkp::receipt::{circuit_commitment, setup_receipt, DepositReceipt};
+//! use zkane_crypto::zkp::ProverHandle;
+//! use zkane_crypto::{MerkleTree, PoseidonBls12Hash};
+//!
+//! let asset_id = ZkAssetId { block: 2, tx: 1 };
+//! let (secret, nullifier) = ([1u8; 32], [2u8; 32]);
+//! let commitment = circuit_commitment(&nullifier, &secret, &asset_id, 100_000)?;
+//!
+//! let tree = MerkleTree::from_leaves_with_hasher(4, &[Commitment::new(commitment)], PoseidonBls12Hash)?;
+//! let (pk, vk) = setup_receipt(4);
+//! let receipt = DepositReceipt::generate(
+//!     &pk,
+//!     (&secret, &nullifier),
+//!     (&asset_id, 100_000),
+//!     &tree.generate_path(0)?,
+//!     tree.root(),
+//!     (800_000, 800_100),
+//!     [7u8; 32],
+//!     &ProverHandle::new(),
+//! )?;
+//! assert!(receipt.verify(&vk)?);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::poseidon_params;
+use super::prover::{prove_with_handle, ProverHandle};
+use super::{proof_from_bytes, proof_to_bytes};
+use crate::gadgets::poseidon::PoseidonGadget;
+#[cfg(doc)]
+use crate::hash::PoseidonBls12Hash;
+use crate::poseidon::PoseidonConfig;
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_crypto_primitives::crh::poseidon::constraints::CRHParametersVar;
+use ark_ff::PrimeField;
+use ark_groth16::{Groth16, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use zkane_common::{MerklePath, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// Compute a note's commitment with the circuit's Poseidon.
+///
+/// The commitment is `poseidon(nullifier, secret, poseidon(block, tx),
+/// denomination)` over BLS12-381, as derived inside the withdrawal and
+/// receipt circuits.
+pub fn circuit_commitment(
+    nullifier: &[u8; 32],
+    secret: &[u8; 32],
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> ZKaneResult<[u8; 32]> {
+    let asset_id_hash = PoseidonConfig::bls12_381(2)?.hash(&[field_bytes(asset_id.block), field_bytes(asset_id.tx)])?;
+    Ok(PoseidonConfig::bls12_381(4)?.hash(&[*nullifier, *secret, asset_id_hash, field_bytes(denomination)])?)
+}
+
+/// Encode an integer as a big-endian field element
+fn field_bytes(value: u128) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[16..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// This circuit proves that the prover knows a deposit note for the public
+/// asset ID and denomination whose commitment is a leaf of the tree with the
+/// public root.
+#[derive(Clone)]
+pub struct DepositReceiptCircuit {
+    // --- Public Inputs ---
+    /// The root of the receipt tree.
+    pub root: Fr,
+    /// The block number of the note's asset ID.
+    pub asset_block: Fr,
+    /// The transaction number of the note's asset ID.
+    pub asset_tx: Fr,
+    /// The note's denomination.
+    pub denomination: Fr,
+    /// The counterparty's challenge the receipt is bound to.
+    pub challenge: Fr,
+
+    // --- Private Witnesses ---
+    /// The secret part of the deposit note.
+    pub secret: Fr,
+    /// The nullifier part of the deposit note.
+    pub nullifier: Fr,
+    /// The sibling hashes from the leaf up to the root.
+    pub path_elements: Vec<Fr>,
+    /// Whether the node is the right child, at each level.
+    pub path_indices: Vec<bool>,
+}
+
+impl DepositReceiptCircuit {
+    /// A circuit for trees of `height` with all values zero, for setup.
+    pub fn blank(height: u32) -> Self {
+        Self {
+            root: Fr::default(),
+            asset_block: Fr::default(),
+            asset_tx: Fr::default(),
+            denomination: Fr::default(),
+            challenge: Fr::default(),
+            secret: Fr::default(),
+            nullifier: Fr::default(),
+            path_elements: vec![Fr::default(); height as usize],
+            path_indices: vec![false; height as usize],
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for DepositReceiptCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate public inputs
+        let root = FpVar::new_input(cs.clone(), || Ok(self.root))?;
+        let asset_block = FpVar::new_input(cs.clone(), || Ok(self.asset_block))?;
+        let asset_tx = FpVar::new_input(cs.clone(), || Ok(self.asset_tx))?;
+        let denomination = FpVar::new_input(cs.clone(), || Ok(self.denomination))?;
+        let challenge = FpVar::new_input(cs.clone(), || Ok(self.challenge))?;
+
+        // Allocate private witnesses
+        let secret = FpVar::new_witness(cs.clone(), || Ok(self.secret))?;
+        let nullifier = FpVar::new_witness(cs.clone(), || Ok(self.nullifier))?;
+
+        let params_four = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(4))?;
+        let params_two = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(2))?;
+        let params_one = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(1))?;
+
+        // 1. Derive the commitment, bound to the asset ID and denomination.
+        let asset_id_hash = PoseidonGadget::hash_two(cs.clone(), &params_two, &asset_block, &asset_tx)?;
+        let commitment = PoseidonGadget::hash_four(
+            cs.clone(),
+            &params_four,
+            [&nullifier, &secret, &asset_id_hash, &denomination],
+        )?;
+
+        // 2. Walk the path from the commitment's leaf up to the root.
+        let mut node = PoseidonGadget::hash_one(cs.clone(), &params_one, &commitment)?;
+        for (element, is_right) in self.path_elements.into_iter().zip(self.path_indices) {
+            let sibling = FpVar::new_witness(cs.clone(), || Ok(element))?;
+            let is_right = Boolean::new_witness(cs.clone(), || Ok(is_right))?;
+            let left = is_right.select(&sibling, &node)?;
+            let right = is_right.select(&node, &sibling)?;
+            node = PoseidonGadget::hash_two(cs.clone(), &params_two, &left, &right)?;
+        }
+        node.enforce_equal(&root)?;
+
+        // 3. Bind the challenge to the proof.
+        let _challenge_square = challenge.square()?;
+
+        Ok(())
+    }
+}
+
+/// Generate the keys of the receipt circuit for trees of `height`.
+pub fn setup_receipt(height: u32) -> (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
+    let mut rng = StdRng::seed_from_u64(0u64);
+    Groth16::<Bls12_381>::circuit_specific_setup(DepositReceiptCircuit::blank(height), &mut rng).unwrap()
+}
+
+/// A proof that a note for an asset and denomination was deposited in a
+/// block range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositReceipt {
+    /// The compressed Groth16 proof
+    pub proof: Vec<u8>,
+    /// Root of the receipt tree over the deposits made in the block range
+    pub root: [u8; 32],
+    /// The note's asset ID
+    pub asset_id: ZkAssetId,
+    /// The note's denomination
+    pub denomination: u128,
+    /// First block of the range, inclusive
+    pub from_block: u64,
+    /// Last block of the range, inclusive
+    pub to_block: u64,
+    /// The counterparty's challenge
+    pub challenge: [u8; 32],
+}
+
+impl DepositReceipt {
+    /// Prove ownership of a note in the receipt tree with `root`.
+    ///
+    /// `note` is the note's secret and nullifier, `pool` its asset ID and
+    /// denomination, and `blocks` the inclusive block range the tree covers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the note's commitment is not
+    /// at the end of `path`, which must be as long as the tree height the
+    /// keys were set up for, and [`ZKaneError::ProofCancelled`] if the handle
+    /// is cancelled.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate(
+        pk: &ProvingKey<Bls12_381>,
+        note: (&[u8; 32], &[u8; 32]),
+        pool: (&ZkAssetId, u128),
+        path: &MerklePath,
+        root: [u8; 32],
+        blocks: (u64, u64),
+        challenge: [u8; 32],
+        handle: &ProverHandle,
+    ) -> ZKaneResult<Self> {
+        let ((secret, nullifier), (asset_id, denomination)) = (note, pool);
+        let circuit = DepositReceiptCircuit {
+            root: Fr::from_be_bytes_mod_order(&root),
+            asset_block: Fr::from(asset_id.block),
+            asset_tx: Fr::from(asset_id.tx),
+            denomination: Fr::from(denomination),
+            challenge: Fr::from_be_bytes_mod_order(&challenge),
+            secret: Fr::from_be_bytes_mod_order(secret),
+            nullifier: Fr::from_be_bytes_mod_order(nullifier),
+            path_elements: path.elements.iter().map(|element| Fr::from_be_bytes_mod_order(element)).collect(),
+            path_indices: path.indices.clone(),
+        };
+
+        let proof = prove_with_handle(pk, circuit, handle)?;
+        Ok(Self {
+            proof: proof_to_bytes(&proof)?,
+            root,
+            asset_id: *asset_id,
+            denomination,
+            from_block: blocks.0,
+            to_block: blocks.1,
+            challenge,
+        })
+    }
+
+    /// Verify the proof against the receipt's public inputs.
+    ///
+    /// The caller must also check that [`root`](Self::root) is the root of
+    /// the receipt tree over the pool's deposits from `from_block` to
+    /// `to_block`, and that the challenge is the one they issued.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::InvalidProof`] if the proof can't be decoded.
+    pub fn verify(&self, vk: &VerifyingKey<Bls12_381>) -> ZKaneResult<bool> {
+        let proof = proof_from_bytes(&self.proof).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+        let public_inputs = [
+            Fr::from_be_bytes_mod_order(&self.root),
+            Fr::from(self.asset_id.block),
+            Fr::from(self.asset_id.tx),
+            Fr::from(self.denomination),
+            Fr::from_be_bytes_mod_order(&self.challenge),
+        ];
+        let pvk = PreparedVerifyingKey::from(vk.clone());
+        Groth16::<Bls12_381>::verify_with_processed_vk(&pvk, &public_inputs, &proof)
+            .map_err(|e| ZKaneError::CryptoError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::PoseidonBls12Hash;
+    use crate::MerkleTree;
+    use zkane_common::Commitment;
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+    const HEIGHT: u32 = 3;
+
+    #[test]
+    fn test_deposit_receipt() {
+        let (pk, vk) = setup_receipt(HEIGHT);
+        let (secret, nullifier) = ([1u8; 32], [2u8; 32]);
+        let commitment = circuit_commitment(&nullifier, &secret, &ASSET_ID, 1000).unwrap();
+
+        // The note is hidden among other deposits of the range
+        let leaves = [Commitment::new([5u8; 32]), Commitment::new(commitment), Commitment::new([6u8; 32])];
+        let tree = MerkleTree::from_leaves_with_hasher(HEIGHT, &leaves, PoseidonBls12Hash).unwrap();
+        let path = tree.generate_path(1).unwrap();
+
+        let receipt = DepositReceipt::generate(
+            &pk,
+            (&secret, &nullifier),
+            (&ASSET_ID, 1000),
+            &path,
+            tree.root(),
+            (100, 200),
+            [9u8; 32],
+            &ProverHandle::new(),
+        )
+        .unwrap();
+        assert!(receipt.verify(&vk).unwrap());
+
+        // Every public input is bound to the proof
+        let tampered = [
+            DepositReceipt { root: [0u8; 32], ..receipt.clone() },
+            DepositReceipt { asset_id: ZkAssetId { block: 2, tx: 2 }, ..receipt.clone() },
+            DepositReceipt { denomination: 10_000, ..receipt.clone() },
+            DepositReceipt { challenge: [8u8; 32], ..receipt.clone() },
+        ];
+        for receipt in tampered {
+            assert!(!receipt.verify(&vk).unwrap());
+        }
+
+        let garbled = DepositReceipt { proof: vec![1, 2, 3], ..receipt };
+        assert!(matches!(garbled.verify(&vk), Err(ZKaneError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn test_receipt_requires_membership() {
+        let (pk, _vk) = setup_receipt(HEIGHT);
+        let (secret, nullifier) = ([1u8; 32], [2u8; 32]);
+
+        // A note for another denomination is not in the tree
+        let commitment = circuit_commitment(&nullifier, &secret, &ASSET_ID, 5000).unwrap();
+        let tree = MerkleTree::from_leaves_with_hasher(HEIGHT, &[Commitment::new(commitment)], PoseidonBls12Hash)
+            .unwrap();
+        let result = DepositReceipt::generate(
+            &pk,
+            (&secret, &nullifier),
+            (&ASSET_ID, 1000),
+            &tree.generate_path(0).unwrap(),
+            tree.root(),
+            (100, 200),
+            [9u8; 32],
+            &ProverHandle::new(),
+        );
+        assert!(matches!(result, Err(ZKaneError::InvalidProof(_))));
+    }
+}
@@ -0,0 +1,391 @@
+//! # Split Withdrawals
+//!
+//! A split withdrawal spends a note into a public amount, paid out of the
+//! pool like a withdrawal, and [`SPLIT_OUTPUTS`] fresh notes deposited back
+//! into the pool. This gives partial withdrawals and change without leaving
+//! the anonymity set: the values of the fresh notes stay private, and the
+//! circuit proves that they add up to the spent note's value together with
+//! the public amount.
+//!
+//! Notes are committed with [`circuit_commitment`], so a fresh note can be
+//! split again. A fresh note that isn't needed is given an amount of zero.
+//!
+//! ```rust,no_run
+//! use zkane_common::ZkAssetId;
+//! use zkane_crypto::zkp::split::{setup_split, SplitCircuit, SplitOutputNote};
+//! use zkane_crypto::zkp::{prove_with_handle, ProverHandle};
+//!
+//! let asset_id = ZkAssetId { block: 2, tx: 1 };
+//! let outputs = [
+//!     SplitOutputNote { secret: [3u8; 32], nullifier: [4u8; 32], amount: 600 },
+//!     SplitOutputNote { secret: [5u8; 32], nullifier: [6u8; 32], amount: 0 },
+//! ];
+//! let circuit = SplitCircuit::from_notes((&[1u8; 32], &[2u8; 32], 1000), &asset_id, 400, (&[0u8; 32], 0), &outputs)?;
+//!
+//! let (pk, _vk) = setup_split();
+//! let proof = prove_with_handle(&pk, circuit, &ProverHandle::new())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::poseidon_params;
+use super::receipt::circuit_commitment;
+use crate::gadgets::poseidon::PoseidonGadget;
+use crate::poseidon::PoseidonConfig;
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_crypto_primitives::crh::poseidon::constraints::CRHParametersVar;
+use ark_ff::PrimeField;
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, ProvingKey, VerifyingKey};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use zkane_common::{SplitWitness, ZKaneError, ZKaneResult, ZkAssetId, SPLIT_OUTPUTS};
+
+/// Number of bits amounts are range checked to, so sums can't wrap around
+/// the field
+const AMOUNT_BITS: usize = 128;
+
+/// Compute a nullifier's hash with the circuit's Poseidon.
+pub fn circuit_nullifier_hash(nullifier: &[u8; 32]) -> ZKaneResult<[u8; 32]> {
+    Ok(PoseidonConfig::bls12_381(1)?.hash(&[*nullifier])?)
+}
+
+/// A fresh note created by a split withdrawal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitOutputNote {
+    /// The note's secret
+    pub secret: [u8; 32],
+    /// The note's nullifier
+    pub nullifier: [u8; 32],
+    /// The note's value
+    pub amount: u128,
+}
+
+impl SplitOutputNote {
+    /// Compute the note's commitment for an asset.
+    pub fn commitment(&self, asset_id: &ZkAssetId) -> ZKaneResult<[u8; 32]> {
+        circuit_commitment(&self.nullifier, &self.secret, asset_id, self.amount)
+    }
+}
+
+/// This circuit proves that a user knows the note of the public commitment,
+/// and that its value equals the public amount plus the values of the notes
+/// of the public output commitments.
+#[derive(Clone)]
+pub struct SplitCircuit {
+    // --- Public Inputs ---
+    /// The commitment of the spent note.
+    pub commitment: Fr,
+    /// The hash of the spent note's nullifier.
+    pub nullifier_hash: Fr,
+    /// The hash of the relayer's fee output (zero for self-relayed splits).
+    pub relayer_output_hash: Fr,
+    /// The fee paid to the relayer out of the public amount.
+    pub fee: Fr,
+    /// The block number of the pool's asset ID.
+    pub asset_block: Fr,
+    /// The transaction number of the pool's asset ID.
+    pub asset_tx: Fr,
+    /// The amount paid out of the pool.
+    pub public_amount: Fr,
+    /// The commitments of the fresh notes.
+    pub output_commitments: [Fr; SPLIT_OUTPUTS],
+
+    // --- Private Witnesses ---
+    /// The secret of the spent note.
+    pub secret: Fr,
+    /// The nullifier of the spent note.
+    pub nullifier: Fr,
+    /// The value of the spent note.
+    pub amount: Fr,
+    /// The secrets, nullifiers and values of the fresh notes.
+    pub outputs: [(Fr, Fr, Fr); SPLIT_OUTPUTS],
+}
+
+impl SplitCircuit {
+    /// Build the circuit for splitting a note, deriving the public inputs.
+    ///
+    /// `note` is the spent note's secret, nullifier and value, and `relayer`
+    /// the relayer output hash and fee.
+    pub fn from_notes(
+        note: (&[u8; 32], &[u8; 32], u128),
+        asset_id: &ZkAssetId,
+        public_amount: u128,
+        relayer: (&[u8; 32], u128),
+        outputs: &[SplitOutputNote; SPLIT_OUTPUTS],
+    ) -> ZKaneResult<Self> {
+        let (secret, nullifier, amount) = note;
+        let mut output_commitments = [Fr::default(); SPLIT_OUTPUTS];
+        for (commitment, output) in output_commitments.iter_mut().zip(outputs) {
+            *commitment = Fr::from_be_bytes_mod_order(&output.commitment(asset_id)?);
+        }
+
+        Ok(Self {
+            commitment: Fr::from_be_bytes_mod_order(&circuit_commitment(nullifier, secret, asset_id, amount)?),
+            nullifier_hash: Fr::from_be_bytes_mod_order(&circuit_nullifier_hash(nullifier)?),
+            relayer_output_hash: Fr::from_be_bytes_mod_order(relayer.0),
+            fee: Fr::from(relayer.1),
+            asset_block: Fr::from(asset_id.block),
+            asset_tx: Fr::from(asset_id.tx),
+            public_amount: Fr::from(public_amount),
+            output_commitments,
+            secret: Fr::from_be_bytes_mod_order(secret),
+            nullifier: Fr::from_be_bytes_mod_order(nullifier),
+            amount: Fr::from(amount),
+            outputs: outputs.map(|output| {
+                (
+                    Fr::from_be_bytes_mod_order(&output.secret),
+                    Fr::from_be_bytes_mod_order(&output.nullifier),
+                    Fr::from(output.amount),
+                )
+            }),
+        })
+    }
+
+    /// A circuit with all values zero, for setup.
+    pub fn blank() -> Self {
+        Self {
+            commitment: Fr::default(),
+            nullifier_hash: Fr::default(),
+            relayer_output_hash: Fr::default(),
+            fee: Fr::default(),
+            asset_block: Fr::default(),
+            asset_tx: Fr::default(),
+            public_amount: Fr::default(),
+            output_commitments: [Fr::default(); SPLIT_OUTPUTS],
+            secret: Fr::default(),
+            nullifier: Fr::default(),
+            amount: Fr::default(),
+            outputs: [(Fr::default(), Fr::default(), Fr::default()); SPLIT_OUTPUTS],
+        }
+    }
+}
+
+/// Enforce that a value fits in [`AMOUNT_BITS`] bits.
+fn enforce_amount(value: &FpVar<Fr>) -> Result<(), SynthesisError> {
+    for bit in &value.to_bits_le()?[AMOUNT_BITS..] {
+        bit.enforce_equal(&Boolean::FALSE)?;
+    }
+    Ok(())
+}
+
+impl ConstraintSynthesizer<Fr> for SplitCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate public inputs
+        let commitment = FpVar::new_input(cs.clone(), || Ok(self.commitment))?;
+        let nullifier_hash = FpVar::new_input(cs.clone(), || Ok(self.nullifier_hash))?;
+        let relayer_output_hash = FpVar::new_input(cs.clone(), || Ok(self.relayer_output_hash))?;
+        let fee = FpVar::new_input(cs.clone(), || Ok(self.fee))?;
+        let asset_block = FpVar::new_input(cs.clone(), || Ok(self.asset_block))?;
+        let asset_tx = FpVar::new_input(cs.clone(), || Ok(self.asset_tx))?;
+        let public_amount = FpVar::new_input(cs.clone(), || Ok(self.public_amount))?;
+        let output_commitments = self
+            .output_commitments
+            .iter()
+            .map(|value| FpVar::new_input(cs.clone(), || Ok(*value)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Allocate private witnesses
+        let secret = FpVar::new_witness(cs.clone(), || Ok(self.secret))?;
+        let nullifier = FpVar::new_witness(cs.clone(), || Ok(self.nullifier))?;
+        let amount = FpVar::new_witness(cs.clone(), || Ok(self.amount))?;
+
+        let params_four = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(4))?;
+        let params_two = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(2))?;
+        let params_one = CRHParametersVar::new_constant(cs.clone(), poseidon_params::for_arity(1))?;
+
+        // 1. Verify the spent commitment opens to the note, bound to the
+        //    pool's asset ID and the note's value.
+        let asset_id_hash = PoseidonGadget::hash_two(cs.clone(), &params_two, &asset_block, &asset_tx)?;
+        let computed_commitment = PoseidonGadget::hash_four(
+            cs.clone(),
+            &params_four,
+            [&nullifier, &secret, &asset_id_hash, &amount],
+        )?;
+        computed_commitment.enforce_equal(&commitment)?;
+
+        // 2. Verify the nullifier hash is correctly derived from the nullifier.
+        let computed_nullifier_hash = PoseidonGadget::hash_one(cs.clone(), &params_one, &nullifier)?;
+        computed_nullifier_hash.enforce_equal(&nullifier_hash)?;
+
+        // 3. Verify each fresh commitment opens to a note of the same asset,
+        //    and that no value is created.
+        enforce_amount(&amount)?;
+        enforce_amount(&public_amount)?;
+        let mut total = public_amount;
+        for ((secret, nullifier, value), commitment) in self.outputs.into_iter().zip(&output_commitments) {
+            let secret = FpVar::new_witness(cs.clone(), || Ok(secret))?;
+            let nullifier = FpVar::new_witness(cs.clone(), || Ok(nullifier))?;
+            let value = FpVar::new_witness(cs.clone(), || Ok(value))?;
+            enforce_amount(&value)?;
+
+            let computed = PoseidonGadget::hash_four(
+                cs.clone(),
+                &params_four,
+                [&nullifier, &secret, &asset_id_hash, &value],
+            )?;
+            computed.enforce_equal(commitment)?;
+            total += value;
+        }
+        total.enforce_equal(&amount)?;
+
+        // 4. Bind the relayer output and fee to the proof so they cannot be
+        //    altered by whoever broadcasts the split.
+        let _relayer_square = relayer_output_hash.square()?;
+        let _fee_square = fee.square()?;
+
+        Ok(())
+    }
+}
+
+/// Generate the keys of the split circuit.
+pub fn setup_split() -> (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
+    let mut rng = StdRng::seed_from_u64(0u64);
+    Groth16::<Bls12_381>::circuit_specific_setup(SplitCircuit::blank(), &mut rng).unwrap()
+}
+
+/// Verify the proof of a split withdrawal against its witness.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::InvalidProof`] if the proof can't be decoded or the
+/// witness doesn't have [`SPLIT_OUTPUTS`] output commitments.
+pub fn verify_split(
+    vk: &VerifyingKey<Bls12_381>,
+    witness: &SplitWitness,
+    asset_id: &ZkAssetId,
+) -> ZKaneResult<bool> {
+    if witness.output_commitments.len() != SPLIT_OUTPUTS {
+        return Err(ZKaneError::InvalidProof(format!(
+            "expected {} output commitments, got {}",
+            SPLIT_OUTPUTS,
+            witness.output_commitments.len()
+        )));
+    }
+    let withdrawal = &witness.withdrawal;
+    let proof = super::proof_from_bytes(&withdrawal.proof.proof).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+
+    let mut public_inputs = vec![
+        Fr::from_be_bytes_mod_order(withdrawal.commitment.as_bytes()),
+        Fr::from_be_bytes_mod_order(withdrawal.proof.nullifier_hash.as_bytes()),
+        Fr::from_be_bytes_mod_order(&withdrawal.proof.relayer_output_hash),
+        Fr::from(withdrawal.proof.fee),
+        Fr::from(asset_id.block),
+        Fr::from(asset_id.tx),
+        Fr::from(witness.public_amount),
+    ];
+    public_inputs.extend(
+        witness
+            .output_commitments
+            .iter()
+            .map(|commitment| Fr::from_be_bytes_mod_order(commitment.as_bytes())),
+    );
+    Ok(verify_proof(vk, &proof, &public_inputs))
+}
+
+fn verify_proof(vk: &VerifyingKey<Bls12_381>, proof: &Proof<Bls12_381>, public_inputs: &[Fr]) -> bool {
+    let pvk = PreparedVerifyingKey::from(vk.clone());
+    Groth16::<Bls12_381>::verify_with_processed_vk(&pvk, public_inputs, proof).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::{proof_to_bytes, prove_with_handle, ProverHandle};
+    use ark_ff::BigInteger;
+    use zkane_common::{Commitment, MerklePath, NullifierHash, Recipient, WithdrawalProof, WithdrawalWitness};
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+    const NOTE: (&[u8; 32], &[u8; 32], u128) = (&[1u8; 32], &[2u8; 32], 1000);
+
+    fn field_bytes(value: u128) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[16..].copy_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    fn outputs(first: u128, second: u128) -> [SplitOutputNote; SPLIT_OUTPUTS] {
+        [
+            SplitOutputNote { secret: [3u8; 32], nullifier: [4u8; 32], amount: first },
+            SplitOutputNote { secret: [5u8; 32], nullifier: [6u8; 32], amount: second },
+        ]
+    }
+
+    fn witness(proof: Vec<u8>, public_amount: u128, outputs: &[SplitOutputNote]) -> SplitWitness {
+        let (secret, nullifier, amount) = NOTE;
+        let proof = WithdrawalProof::new(
+            proof,
+            [0u8; 32],
+            NullifierHash::new(circuit_nullifier_hash(nullifier).unwrap()),
+            Recipient::default(),
+        )
+        .with_relayer([7u8; 32], 10);
+        SplitWitness {
+            withdrawal: WithdrawalWitness {
+                proof,
+                path: MerklePath::new(vec![], vec![]).unwrap(),
+                leaf_index: 0,
+                commitment: Commitment::new(circuit_commitment(nullifier, secret, &ASSET_ID, amount).unwrap()),
+                outputs_hash: [0u8; 32],
+            },
+            public_amount,
+            output_commitments: outputs
+                .iter()
+                .map(|output| Commitment::new(output.commitment(&ASSET_ID).unwrap()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_split_withdrawal() {
+        let (pk, vk) = setup_split();
+        let outputs = outputs(500, 100);
+        let circuit = SplitCircuit::from_notes(NOTE, &ASSET_ID, 400, (&[7u8; 32], 10), &outputs).unwrap();
+        let proof = proof_to_bytes(&prove_with_handle(&pk, circuit, &ProverHandle::new()).unwrap()).unwrap();
+
+        let split = witness(proof, 400, &outputs);
+        assert!(verify_split(&vk, &split, &ASSET_ID).unwrap());
+
+        // The public amount, outputs and asset are bound to the proof
+        assert!(!verify_split(&vk, &SplitWitness { public_amount: 500, ..split.clone() }, &ASSET_ID).unwrap());
+        let mut swapped = split.clone();
+        swapped.output_commitments.reverse();
+        assert!(!verify_split(&vk, &swapped, &ASSET_ID).unwrap());
+        assert!(!verify_split(&vk, &split, &ZkAssetId { block: 2, tx: 2 }).unwrap());
+
+        let mut missing = split;
+        missing.output_commitments.pop();
+        assert!(verify_split(&vk, &missing, &ASSET_ID).is_err());
+    }
+
+    #[test]
+    fn test_split_conserves_value() {
+        let (pk, _vk) = setup_split();
+
+        // Outputs worth more than the note can't be proven
+        let circuit = SplitCircuit::from_notes(NOTE, &ASSET_ID, 400, (&[7u8; 32], 10), &outputs(500, 101)).unwrap();
+        assert!(matches!(
+            prove_with_handle(&pk, circuit, &ProverHandle::new()),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+
+        // Nor can a negative value that wraps around the field
+        let negative = -Fr::from(1000u64);
+        let mut negative_bytes = [0u8; 32];
+        negative_bytes.copy_from_slice(&negative.into_bigint().to_bytes_be());
+        let asset_id_hash = PoseidonConfig::bls12_381(2).unwrap().hash(&[field_bytes(2), field_bytes(1)]).unwrap();
+        let negative_commitment = PoseidonConfig::bls12_381(4)
+            .unwrap()
+            .hash(&[[6u8; 32], [5u8; 32], asset_id_hash, negative_bytes])
+            .unwrap();
+
+        let mut circuit = SplitCircuit::from_notes(NOTE, &ASSET_ID, 1400, (&[7u8; 32], 10), &outputs(600, 0)).unwrap();
+        circuit.outputs[1].2 = negative;
+        circuit.output_commitments[1] = Fr::from_be_bytes_mod_order(&negative_commitment);
+        assert!(matches!(
+            prove_with_handle(&pk, circuit, &ProverHandle::new()),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+    }
+}
@@ -0,0 +1,326 @@
+//! # Witness Caching
+//!
+//! Proving a withdrawal can fail partway, when a browser tab is closed or
+//! runs out of memory. A [`WitnessStore`] keeps the witness synthesized for a
+//! withdrawal, so a retry of the same withdrawal skips the synthesizing stage
+//! and goes straight to proving.
+//!
+//! Witnesses are keyed by a [`WitnessKey`], the hash of the withdrawal's
+//! public inputs: the note's nullifier hash, the outputs hash and the rest of
+//! what the proof commits to. A withdrawal to different outputs or with a
+//! different fee is synthesized afresh.
+//!
+//! A witness holds the note's secret and nullifier, so it is as sensitive as
+//! the note itself. [`prove_cached`] removes it once the proof is done, and
+//! [`WitnessStore::clear`] removes every cached witness, for users who don't
+//! want a witness left behind by a failed proof.
+
+use super::prover::{prove_witness, synthesize_witness};
+use super::{ProverHandle, WithdrawalCircuit};
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_groth16::{Proof, ProvingKey};
+use ark_relations::r1cs::{ConstraintMatrices, Matrix};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use zkane_common::{ZKaneError, ZKaneResult};
+
+/// Domain separator of witness keys
+const WITNESS_KEY_DOMAIN: &[u8] = b"zkane-witness-v1";
+
+/// Key of a cached witness: the hash of the public inputs of the proof it is
+/// synthesized for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WitnessKey(pub [u8; 32]);
+
+impl WitnessKey {
+    /// Get the key of the witness for a withdrawal circuit.
+    pub fn for_circuit(circuit: &WithdrawalCircuit) -> ZKaneResult<Self> {
+        let inputs = [
+            circuit.nullifier_hash,
+            circuit.recipients_hash,
+            circuit.relayer_output_hash,
+            circuit.fee,
+            circuit.not_after_height,
+            circuit.asset_block,
+            circuit.asset_tx,
+            circuit.denomination,
+        ];
+        let mut bytes = Vec::new();
+        inputs
+            .serialize_compressed(&mut bytes)
+            .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(WITNESS_KEY_DOMAIN);
+        hasher.update(&bytes);
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// Get the key as a hex string, for stores keyed by strings.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+impl fmt::Display for WitnessKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// The output of the synthesizing stage of a proof: the circuit's constraint
+/// matrices and the full variable assignment satisfying them.
+#[derive(Clone)]
+pub struct SynthesizedWitness {
+    /// The circuit's constraint matrices
+    pub matrices: ConstraintMatrices<Fr>,
+    /// The instance assignment followed by the witness assignment
+    pub assignment: Vec<Fr>,
+}
+
+impl SynthesizedWitness {
+    /// Encode the witness for a [`WitnessStore`].
+    pub fn to_bytes(&self) -> ZKaneResult<Vec<u8>> {
+        let m = &self.matrices;
+        let mut bytes = Vec::new();
+        let counts = (m.num_instance_variables, m.num_witness_variables, m.num_constraints);
+        let non_zero = (m.a_num_non_zero, m.b_num_non_zero, m.c_num_non_zero);
+        (|| {
+            (counts, non_zero).serialize_compressed(&mut bytes)?;
+            for matrix in [&m.a, &m.b, &m.c] {
+                matrix.serialize_compressed(&mut bytes)?;
+            }
+            self.assignment.serialize_compressed(&mut bytes)
+        })()
+        .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Decode a witness encoded with [`SynthesizedWitness::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::SerializationError`] if the bytes aren't a
+    /// well-formed witness.
+    pub fn from_bytes(bytes: &[u8]) -> ZKaneResult<Self> {
+        type Encoded = (
+            ((usize, usize, usize), (usize, usize, usize)),
+            (Matrix<Fr>, Matrix<Fr>, Matrix<Fr>),
+            Vec<Fr>,
+        );
+        let (
+            (
+                (num_instance_variables, num_witness_variables, num_constraints),
+                (a_num_non_zero, b_num_non_zero, c_num_non_zero),
+            ),
+            (a, b, c),
+            assignment,
+        ) = Encoded::deserialize_compressed(bytes).map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+
+        if assignment.len() != num_instance_variables + num_witness_variables
+            || [a.len(), b.len(), c.len()] != [num_constraints; 3]
+        {
+            return Err(ZKaneError::SerializationError(
+                "witness doesn't match its constraint matrices".to_string(),
+            ));
+        }
+        Ok(Self {
+            matrices: ConstraintMatrices {
+                num_instance_variables,
+                num_witness_variables,
+                num_constraints,
+                a_num_non_zero,
+                b_num_non_zero,
+                c_num_non_zero,
+                a,
+                b,
+                c,
+            },
+            assignment,
+        })
+    }
+}
+
+impl fmt::Debug for SynthesizedWitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SynthesizedWitness")
+            .field("num_constraints", &self.matrices.num_constraints)
+            .field("num_variables", &self.assignment.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Storage of encoded witnesses, by key.
+pub trait WitnessStore {
+    /// Get the witness stored under `key`, if any.
+    fn get(&self, key: &WitnessKey) -> ZKaneResult<Option<Vec<u8>>>;
+
+    /// Store a witness under `key`.
+    fn set(&mut self, key: &WitnessKey, witness: Vec<u8>) -> ZKaneResult<()>;
+
+    /// Remove the witness stored under `key`, if any.
+    fn remove(&mut self, key: &WitnessKey) -> ZKaneResult<()>;
+
+    /// Remove every stored witness.
+    fn clear(&mut self) -> ZKaneResult<()>;
+}
+
+/// Witness store keeping witnesses in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryWitnessStore {
+    witnesses: HashMap<WitnessKey, Vec<u8>>,
+}
+
+impl MemoryWitnessStore {
+    /// Get the number of stored witnesses.
+    pub fn len(&self) -> usize {
+        self.witnesses.len()
+    }
+
+    /// Check if no witness is stored.
+    pub fn is_empty(&self) -> bool {
+        self.witnesses.is_empty()
+    }
+}
+
+impl WitnessStore for MemoryWitnessStore {
+    fn get(&self, key: &WitnessKey) -> ZKaneResult<Option<Vec<u8>>> {
+        Ok(self.witnesses.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &WitnessKey, witness: Vec<u8>) -> ZKaneResult<()> {
+        self.witnesses.insert(*key, witness);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &WitnessKey) -> ZKaneResult<()> {
+        self.witnesses.remove(key);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> ZKaneResult<()> {
+        self.witnesses.clear();
+        Ok(())
+    }
+}
+
+/// Prove a withdrawal, reusing the witness cached in `store` by an earlier
+/// attempt if there is one.
+///
+/// A fresh witness is stored before the proving stage starts, so it survives
+/// a failure or cancellation there, and is removed once the proof is done.
+/// A cached witness that can't be decoded is synthesized again. A resumed
+/// proof reports no [`ProofStage::Synthesizing`](super::ProofStage::Synthesizing)
+/// stage.
+///
+/// # Errors
+///
+/// Returns [`ZKaneError::ProofCancelled`] if the handle is cancelled, and any
+/// error of `store`.
+pub fn prove_cached(
+    pk: &ProvingKey<Bls12_381>,
+    circuit: WithdrawalCircuit,
+    store: &mut impl WitnessStore,
+    handle: &ProverHandle,
+) -> ZKaneResult<Proof<Bls12_381>> {
+    let key = WitnessKey::for_circuit(&circuit)?;
+    let cached = store.get(&key)?.and_then(|bytes| SynthesizedWitness::from_bytes(&bytes).ok());
+    let witness = match cached {
+        Some(witness) => witness,
+        None => {
+            let witness = synthesize_witness(circuit, handle)?;
+            store.set(&key, witness.to_bytes()?)?;
+            witness
+        }
+    };
+
+    let proof = prove_witness(pk, &witness, handle)?;
+    store.remove(&key)?;
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::{prove, setup, ProofStage};
+    use std::sync::{Arc, Mutex};
+    use zkane_common::ZkAssetId;
+
+    const ASSET_ID: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+    fn circuit(fee: u128) -> WithdrawalCircuit {
+        WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &ASSET_ID, 100_000, &[6u8; 32], &[3u8; 32], fee, 0).unwrap()
+    }
+
+    #[test]
+    fn test_witness_key() {
+        assert_eq!(WitnessKey::for_circuit(&circuit(500)).unwrap(), WitnessKey::for_circuit(&circuit(500)).unwrap());
+        assert_ne!(WitnessKey::for_circuit(&circuit(500)).unwrap(), WitnessKey::for_circuit(&circuit(600)).unwrap());
+
+        let mut other_outputs = circuit(500);
+        other_outputs.recipients_hash = Fr::from(7u64);
+        assert_ne!(WitnessKey::for_circuit(&circuit(500)).unwrap(), WitnessKey::for_circuit(&other_outputs).unwrap());
+    }
+
+    #[test]
+    fn test_witness_roundtrip() {
+        let witness = synthesize_witness(circuit(500), &ProverHandle::new()).unwrap();
+        let bytes = witness.to_bytes().unwrap();
+        let decoded = SynthesizedWitness::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.assignment, witness.assignment);
+        assert_eq!(decoded.matrices, witness.matrices);
+
+        assert!(matches!(
+            SynthesizedWitness::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ZKaneError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_prove_cached_resumes_after_cancel() {
+        let (pk, _vk) = setup();
+        let mut store = MemoryWitnessStore::default();
+
+        // Cancelled once synthesis is done, the witness is kept
+        let handle = ProverHandle::new();
+        let canceller = handle.clone();
+        handle.on_progress(move |stage| {
+            if stage == ProofStage::Synthesizing {
+                canceller.cancel();
+            }
+        });
+        assert!(matches!(
+            prove_cached(&pk, circuit(500), &mut store, &handle),
+            Err(ZKaneError::ProofCancelled)
+        ));
+        assert_eq!(store.len(), 1);
+
+        // The retry skips synthesis, and the witness is removed once proven
+        let stages = Arc::new(Mutex::new(Vec::new()));
+        let handle = ProverHandle::new();
+        let observed = stages.clone();
+        handle.on_progress(move |stage| observed.lock().unwrap().push(stage));
+        let proof = prove_cached(&pk, circuit(500), &mut store, &handle).unwrap();
+        assert_eq!(*stages.lock().unwrap(), vec![ProofStage::Proving, ProofStage::Done]);
+        assert_eq!(proof, prove(&pk, circuit(500)));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_prove_cached_ignores_corrupt_witness() {
+        let (pk, _vk) = setup();
+        let mut store = MemoryWitnessStore::default();
+        let key = WitnessKey::for_circuit(&circuit(500)).unwrap();
+        store.set(&key, vec![1, 2, 3]).unwrap();
+        store.set(&WitnessKey([9u8; 32]), vec![4, 5, 6]).unwrap();
+
+        let proof = prove_cached(&pk, circuit(500), &mut store, &ProverHandle::new()).unwrap();
+        assert_eq!(proof, prove(&pk, circuit(500)));
+        assert_eq!(store.get(&key).unwrap(), None);
+
+        store.clear().unwrap();
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+// Withdrawal prover worker
+//
+// Runs the zkane-wasm prover off the page's thread, so the UI stays
+// responsive while a withdrawal is proven. See `src/prover_worker.rs` for
+// the page's side. The page posts one request per worker:
+//
+//   { provingKeyUrl, verifyingKeyUrl, note, outputs, relayerOutputHash, fee, witness }
+//
+// and the worker answers with
+//
+//   { type: "progress", stage, progress }
+//   { type: "witness", key, witness }         // hex encoded, before proving
+//   { type: "done", proof, recipientsHash }   // hex encoded
+//   { type: "error", message }
+//
+// `witness` is the { key, witness } of an earlier attempt, if the page kept
+// one. It is used instead of synthesizing the witness again if its key
+// matches this withdrawal's.
+//
+// Each proof is checked against the verifying key before it is handed back,
+// so the page never broadcasts or relays a proof the verifier would reject.
+//
+// The zkane-wasm package is served next to this file, built with
+//
+//   wasm-pack build crates/zkane-wasm --target web --out-dir ../zkane-frontend/zkane-wasm
+
+import init, {
+  circuitNullifierHash,
+  proveWithdrawalWitness,
+  recipientsHash,
+  synthesizeWithdrawalWitness,
+  withdrawalWitnessKey,
+  JsProverHandle,
+  JsWithdrawalVerifier,
+} from "./zkane-wasm/zkane_wasm.js";
+
+const ready = init();
+
+async function fetchKey(url, name) {
+  const response = await fetch(url);
+  if (!response.ok) {
+    throw new Error(`Failed to fetch ${name}: HTTP ${response.status}`);
+  }
+  return new Uint8Array(await response.arrayBuffer());
+}
+
+function toHex(bytes) {
+  return Array.from(bytes, (byte) => byte.toString(16).padStart(2, "0")).join("");
+}
+
+function fromHex(hex) {
+  return Uint8Array.from(hex.match(/../g) ?? [], (byte) => parseInt(byte, 16));
+}
+
+self.onmessage = async ({ data }) => {
+  try {
+    self.postMessage({ type: "progress", stage: "loading", progress: 0 });
+    await ready;
+    const [provingKey, verifyingKey] = await Promise.all([
+      fetchKey(data.provingKeyUrl, "proving key"),
+      fetchKey(data.verifyingKeyUrl, "verifying key"),
+    ]);
+
+    const handle = new JsProverHandle();
+    handle.onProgress((stage, progress) => self.postMessage({ type: "progress", stage, progress }));
+
+    const hash = recipientsHash(data.outputs);
+    const relayerOutputHash = data.relayerOutputHash.replace(/^0x/, "");
+    const fee = BigInt(data.fee);
+
+    const key = withdrawalWitnessKey(data.note, hash, relayerOutputHash, fee, 0n);
+    let witness = data.witness?.key === key ? fromHex(data.witness.witness) : null;
+    if (witness === null) {
+      witness = synthesizeWithdrawalWitness(data.note, hash, relayerOutputHash, fee, 0n, handle);
+      self.postMessage({ type: "witness", key, witness: toHex(witness) });
+    }
+    const proof = toHex(proveWithdrawalWitness(provingKey, witness, handle));
+
+    const note = JSON.parse(data.note);
+    const publicInputs = {
+      nullifier_hash: circuitNullifierHash(note.nullifier.replace(/^0x/, "")),
+      recipients_hash: hash,
+      relayer_output_hash: relayerOutputHash,
+      fee: Number(data.fee),
+      asset_id: note.asset_id,
+      denomination: note.denomination,
+    };
+    const verifier = new JsWithdrawalVerifier(verifyingKey);
+    if (!verifier.verifyWithdrawalProof(proof, JSON.stringify(publicInputs))) {
+      throw new Error("Generated proof failed verification");
+    }
+    self.postMessage({ type: "done", proof, recipientsHash: hash });
+  } catch (error) {
+    self.postMessage({ type: "error", message: String(error?.message ?? error) });
+  }
+};
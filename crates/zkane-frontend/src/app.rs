@@ -5,6 +5,7 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 use crate::components::*;
+use crate::provider::*;
 use crate::services::*;
 use crate::types::*;
 
@@ -15,9 +16,11 @@ pub fn App() -> impl IntoView {
     // Global services
     let notification_service = NotificationService::new();
     let storage_service = StorageService::new();
+    let secure_storage = SecureStorageService::new();
     let zkane_service = ZKaneService::new();
     let alkanes_service = AlkanesService::new();
     let wallet_service = WalletService::new();
+    let backup_service = BackupService::new();
 
     // Detect wallets on startup
     let wallet_service_clone = wallet_service.clone();
@@ -29,7 +32,9 @@ pub fn App() -> impl IntoView {
     });
 
     // Global state
-    let (app_config, _set_app_config) = create_signal(AppConfig::default());
+    let config = AppConfig::default();
+    let relayers = (config.relayers.clone(), config.relayer_list_url.clone());
+    let (app_config, _set_app_config) = create_signal(config);
     let (user_preferences, set_user_preferences) = create_signal(UserPreferences::default());
     
     // Load user preferences from storage
@@ -43,9 +48,17 @@ pub fn App() -> impl IntoView {
     // Provide services to child components
     provide_context(notification_service.clone());
     provide_context(storage_service);
-    provide_context(zkane_service);
-    provide_context(alkanes_service);
+    provide_context(secure_storage.clone());
+    provide_context(zkane_service.clone());
+    provide_context(alkanes_service.clone());
     provide_context(wallet_service.clone());
+    provide_frontend_provider(
+        WebProvider::new(zkane_service, alkanes_service, wallet_service.clone())
+            .with_witness_cache(secure_storage.clone())
+            .with_relayers(relayers.0)
+            .with_relayer_list(relayers.1),
+    );
+    provide_context(backup_service);
     provide_context(app_config);
     provide_context(user_preferences);
     provide_context(set_user_preferences);
@@ -67,7 +80,15 @@ pub fn App() -> impl IntoView {
         <Link rel="icon" type_="image/png" href="/assets/favicon.png"/>
 
         <Router>
-            <div class="app">
+            // Any interaction postpones the note vault's auto-lock
+            <div
+                class="app"
+                on:pointerdown={
+                    let secure_storage = secure_storage.clone();
+                    move |_| secure_storage.touch()
+                }
+                on:keydown=move |_| secure_storage.touch()
+            >
                 <Header/>
                 <NotificationContainer/>
                 
@@ -135,6 +156,7 @@ fn Header() -> impl IntoView {
                     <div class="action-button" title="Network Status">
                         <span class="action-icon">"🌐"</span>
                     </div>
+                    <NoteVaultLockButton/>
                     <ThemeToggle/>
                     <A href="/settings" class="action-button">
                         <span class="action-icon">"⚙️"</span>
@@ -324,20 +346,13 @@ fn FeatureCard(
 
 #[component]
 fn QuickStats() -> impl IntoView {
-    let alkanes_service = expect_context::<AlkanesService>();
+    let provider = use_frontend_provider();
     
     let pools_stats = Resource::new(
         || (),
         move |_| {
-            let alkanes_service = alkanes_service.clone();
-            let wallet_service = expect_context::<WalletService>();
-            async move {
-                if let Some(wallet_provider) = wallet_service.connected_wallet.get() {
-                    alkanes_service.get_privacy_pools(&wallet_provider).await
-                } else {
-                    Err(ZKaneError::WasmError("Wallet not connected".to_string()))
-                }
-            }
+            let provider = provider.clone();
+            async move { provider.get_privacy_pools().await }
         }
     );
 
@@ -454,8 +469,8 @@ fn HistoryPage() -> impl IntoView {
     view! {
         <div class="page history-page">
             <div class="page-header">
-                <h1>"Deposit Note Management"</h1>
-                <p>"Securely manage your deposit notes and track privacy pool participation history"</p>
+                <h1>"History"</h1>
+                <p>"Your deposits and withdrawals, and the deposit notes saved in this browser"</p>
             </div>
             <HistoryComponent/>
         </div>
@@ -1,6 +1,7 @@
 //! UI Components for ZKane Frontend application
 
 mod deposit;
+mod backup;
 mod withdraw;
 mod pool_list;
 mod history;
@@ -9,8 +10,10 @@ mod help;
 mod about;
 mod notifications;
 mod utils;
+mod vault;
 
 pub use deposit::*;
+pub use backup::*;
 pub use withdraw::*;
 pub use notifications::*;
 pub use utils::*;
@@ -19,9 +22,12 @@ pub use history::*;
 pub use settings::*;
 pub use help::*;
 pub use about::*;
+pub use vault::*;
 
+use std::rc::Rc;
 use leptos::*;
 use crate::types::*;
+use crate::provider::*;
 use crate::services::*;
 use deezel_web::wallet_provider::WalletInfo;
 
@@ -111,10 +117,10 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
  
  #[component]
  pub fn DepositComponent() -> impl IntoView {
-    let zkane_service = expect_context::<ZKaneService>();
-    let alkanes_service = expect_context::<AlkanesService>();
+    let provider = use_frontend_provider();
     let notification_service = expect_context::<NotificationService>();
     let storage_service = expect_context::<StorageService>();
+    let secure_storage = expect_context::<SecureStorageService>();
     
     // State
     let (selected_asset, set_selected_asset) = create_signal(None::<AssetBalance>);
@@ -123,35 +129,24 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
     let (created_note, set_created_note) = create_signal(None::<DepositNote>);
     
     // Load user assets
-    let alkanes_service_for_assets = alkanes_service.clone();
+    let provider_for_assets = provider.clone();
     let user_assets = Resource::new(
         || (),
         move |_| {
-            let alkanes_service = alkanes_service_for_assets.clone();
-            let wallet_service = expect_context::<WalletService>();
-            async move {
-                if let Some(wallet_provider) = wallet_service.connected_wallet.get() {
-                    alkanes_service.get_user_assets(&wallet_provider, "user_address").await
-                } else {
-                    Err(ZKaneError::WasmError("Wallet not connected".to_string()))
-                }
-            }
+            let provider = provider_for_assets.clone();
+            async move { provider.get_user_assets().await }
         },
     );
 
     // Deposit action
     let deposit_action = Action::new({
-        let zkane_service = zkane_service.clone();
-        let alkanes_service = alkanes_service.clone();
+        let provider = provider.clone();
         let notification_service = notification_service.clone();
-        let storage_service = storage_service.clone();
-        let wallet_service = expect_context::<WalletService>();
+        let secure_storage = secure_storage.clone();
         move |_: &()| {
-            let zkane_service = zkane_service.clone();
-            let alkanes_service = alkanes_service.clone();
+            let provider = provider.clone();
             let notification_service = notification_service.clone();
-            let storage_service = storage_service.clone();
-            let wallet_service = wallet_service.clone();
+            let secure_storage = secure_storage.clone();
             let selected_asset = selected_asset.get();
             let amount_str = deposit_amount.get();
             
@@ -177,31 +172,32 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
                     set_deposit_status.set(DepositStatus::CreatingNote);
                     
                     // Create deposit note
-                    if let Some(wallet_provider) = wallet_service.connected_wallet.get() {
-                        match zkane_service.create_deposit(asset.asset_id.clone(), amount).await {
-                            Ok(note) => {
-                                set_created_note.set(Some(note.clone()));
-                                set_deposit_status.set(DepositStatus::Complete(note.clone()));
-
-                                // Save note to storage if auto-save is enabled
-                                if let Err(e) = storage_service.save_deposit_note(&note) {
-                                    log::warn!("Failed to save deposit note: {:?}", e);
-                                }
-
-                                notification_service.success(
-                                    "Deposit Note Created",
-                                    "Your deposit note has been created successfully. Save it securely!"
-                                );
-                            },
-                            Err(e) => {
-                                let error_msg = format!("Failed to create deposit note: {:?}", e);
-                                set_deposit_status.set(DepositStatus::Error(error_msg.clone()));
-                                notification_service.error("Deposit Failed", &error_msg);
+                    match provider.create_deposit_note(asset.asset_id.clone(), amount).await {
+                        Ok(note) => {
+                            set_created_note.set(Some(note.clone()));
+                            // The deposit is broadcast once the note backup is verified
+                            set_deposit_status.set(DepositStatus::AwaitingBackup(note.clone()));
+
+                            // Save note to the vault if it is unlocked
+                            match secure_storage.save_deposit_note(&note).await {
+                                Ok(()) => {}
+                                Err(ZKaneError::StorageLocked) => notification_service.warning(
+                                    "Note Not Saved",
+                                    "Unlock your note vault on the history page to keep notes in this browser."
+                                ),
+                                Err(e) => log::warn!("Failed to save deposit note: {:?}", e),
                             }
+
+                            notification_service.info(
+                                "Deposit Note Created",
+                                "Back up your deposit note before the deposit is broadcast."
+                            );
+                        },
+                        Err(e) => {
+                            let error_msg = format!("Failed to create deposit note: {:?}", e);
+                            set_deposit_status.set(DepositStatus::Error(error_msg.clone()));
+                            notification_service.error("Deposit Failed", &error_msg);
                         }
-                    } else {
-                        set_deposit_status.set(DepositStatus::Error("Wallet not connected".to_string()));
-                        notification_service.error("Wallet Not Connected", "Please connect a wallet to create a deposit");
                     }
                 } else {
                     set_deposit_status.set(DepositStatus::Error("No asset selected".to_string()));
@@ -211,6 +207,57 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
         }
     });
 
+    // Broadcast action, dispatched once the note backup has been verified
+    let broadcast_action = Action::new({
+        let provider = provider.clone();
+        let notification_service = notification_service.clone();
+        let secure_storage = secure_storage.clone();
+        move |note: &DepositNote| {
+            let provider = provider.clone();
+            let notification_service = notification_service.clone();
+            let secure_storage = secure_storage.clone();
+            let note = note.clone();
+
+            async move {
+                set_deposit_status.set(DepositStatus::BuildingTransaction);
+                let result = async {
+                    let tx_request = provider.create_deposit_transaction(&note).await?;
+                    set_deposit_status.set(DepositStatus::WaitingForSignature);
+                    provider.broadcast_transaction(&tx_request).await
+                }.await;
+
+                match result {
+                    Ok(response) => {
+                        let record = TransactionRecord {
+                            kind: HistoryKind::Deposit,
+                            commitment: note.commitment.clone(),
+                            asset_id: note.asset_id.clone(),
+                            denomination: note.denomination,
+                            txid: Some(response.txid.clone()),
+                            relay_job: None,
+                            anonymity_set: None,
+                            created_at: js_sys::Date::now(),
+                        };
+                        // The history is only kept while the vault is unlocked
+                        if let Err(e) = secure_storage.record_transaction(&record).await {
+                            log::debug!("Deposit not recorded in history: {:?}", e);
+                        }
+                        set_deposit_status.set(DepositStatus::Complete(note));
+                        notification_service.success(
+                            "Deposit Broadcast",
+                            &format!("Your deposit was broadcast in transaction {}", response.txid)
+                        );
+                    },
+                    Err(e) => {
+                        let error_msg = format!("Failed to broadcast deposit: {:?}", e);
+                        set_deposit_status.set(DepositStatus::Error(error_msg.clone()));
+                        notification_service.error("Deposit Failed", &error_msg);
+                    }
+                }
+            }
+        }
+    });
+
     view! {
         <div class="deposit-component">
             <AssetSelector 
@@ -233,6 +280,19 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
                 amount=deposit_amount
             />
             
+            {move || match deposit_status.get() {
+                DepositStatus::AwaitingBackup(note) => {
+                    let note_to_broadcast = note.clone();
+                    Some(view! {
+                        <NoteBackup
+                            note=note
+                            on_verified=Callback::new(move |_| broadcast_action.dispatch(note_to_broadcast.clone()))
+                        />
+                    })
+                },
+                _ => None
+            }}
+
             <DepositResult
                 status=deposit_status
                 created_note=created_note
@@ -242,34 +302,41 @@ fn WalletSelectionModal(show_wallet_modal: RwSignal<bool>) -> impl IntoView {
     }
 }
 
+/// Multi-step withdrawal wizard.
+///
+/// The note is validated, its pool synced and the fee options fetched before
+/// anything is proven. The proof is generated in the provider's prover
+/// worker, and the withdrawal is only submitted after the user has reviewed
+/// it.
 #[component]
 pub fn WithdrawComponent() -> impl IntoView {
-    let zkane_service = expect_context::<ZKaneService>();
-    let _alkanes_service = expect_context::<AlkanesService>();
+    let provider = use_frontend_provider();
     let notification_service = expect_context::<NotificationService>();
-    
+    let secure_storage = expect_context::<SecureStorageService>();
+
     // State
+    let (step, set_step) = create_signal(WithdrawStep::Note);
     let (deposit_note_json, set_deposit_note_json) = create_signal(String::new());
     let (recipient_address, set_recipient_address) = create_signal(String::new());
-    let (withdrawal_status, set_withdrawal_status) = create_signal(WithdrawalStatus::Idle);
     let (parsed_note, set_parsed_note) = create_signal(None::<DepositNote>);
-    let (generated_proof, set_generated_proof) = create_signal(None::<WithdrawalProof>);
+    let (sync_state, set_sync_state) = create_signal(None::<PoolSyncState>);
+    let (fee_options, set_fee_options) = create_signal(Vec::<FeeOption>::new());
+    let (selected_fee, set_selected_fee) = create_signal(None::<usize>);
+    let (proof_progress, set_proof_progress) = create_signal(None::<ProofProgress>);
+    let (preview, set_preview) = create_signal(None::<WithdrawalPreview>);
+    let (submission, set_submission) = create_signal(None::<WithdrawalSubmission>);
+    let (error, set_error) = create_signal(None::<String>);
 
     // Clone services for different closures
     let notification_service_prefill = notification_service.clone();
     let notification_service_for_parse = notification_service.clone();
 
-    // Check for pre-filled note from session storage (from history page)
+    // Check for a note handed over by the history page
+    let secure_storage_prefill = secure_storage.clone();
     create_effect(move |_| {
-        if let Some(window) = web_sys::window() {
-            if let Ok(Some(storage)) = window.session_storage() {
-                if let Ok(Some(prefill_note)) = storage.get_item("zkane_prefill_note") {
-                    set_deposit_note_json.set(prefill_note);
-                    // Clear the session storage item after using it
-                    let _ = storage.remove_item("zkane_prefill_note");
-                    notification_service_prefill.info("Note Loaded", "Deposit note loaded from history");
-                }
-            }
+        if let Some(prefill_note) = secure_storage_prefill.take_prefill_note() {
+            set_deposit_note_json.set(serde_json::to_string_pretty(&prefill_note).unwrap_or_default());
+            notification_service_prefill.info("Note Loaded", "Deposit note loaded from history");
         }
     });
 
@@ -301,109 +368,337 @@ pub fn WithdrawComponent() -> impl IntoView {
         parse_note_effect();
     });
 
-    // Withdrawal action
-    let withdraw_action = Action::new(move |_: &()| {
-        let zkane_service = zkane_service.clone();
+    let recipient_script = move || crate::utils::address_script_hex(&recipient_address.get());
+    let can_continue = move || parsed_note.get().is_some() && recipient_script().is_some();
+
+    // Sync the pool and fetch the fee options
+    let sync_action = {
+        let provider = provider.clone();
         let notification_service = notification_service.clone();
-        let wallet_service = expect_context::<WalletService>();
-        let note_json = deposit_note_json.get();
-        let recipient = recipient_address.get();
-        
-        async move {
-            set_withdrawal_status.set(WithdrawalStatus::ParsingNote);
-            
-            // Parse and validate deposit note
-            let deposit_note = match serde_json::from_str::<DepositNote>(&note_json) {
-                Ok(note) => note,
-                Err(_) => {
-                    set_withdrawal_status.set(WithdrawalStatus::Error("Invalid deposit note".to_string()));
-                    notification_service.error("Invalid Note", "Failed to parse deposit note");
-                    return;
+        let secure_storage = secure_storage.clone();
+        Action::new(move |_: &()| {
+            let provider = provider.clone();
+            let notification_service = notification_service.clone();
+            let secure_storage = secure_storage.clone();
+            let note = parsed_note.get_untracked();
+
+            async move {
+                let Some(note) = note else { return };
+                set_error.set(None);
+                set_sync_state.set(None);
+
+                let state = match provider.sync_pool(&note).await {
+                    Ok(state) => {
+                        // Checkpoints are only kept while the vault is unlocked.
+                        // Spent ones are kept too, for the history
+                        if let Err(e) = secure_storage.save_sync_checkpoint(&note.commitment, &state).await {
+                            log::debug!("Sync checkpoint not saved: {:?}", e);
+                        }
+                        if state.nullifier_spent {
+                            set_error.set(Some("This note has already been withdrawn".to_string()));
+                            return;
+                        }
+                        state
+                    }
+                    Err(e) => match secure_storage.load_sync_checkpoint(&note.commitment).await {
+                        // A path to an earlier root still proves while the pool remembers the root
+                        Ok(Some(state)) if !state.nullifier_spent => {
+                            notification_service.warning(
+                                "Using Saved Pool State",
+                                &format!("Failed to sync the pool ({}), using the last saved checkpoint", e)
+                            );
+                            state
+                        }
+                        _ => {
+                            set_error.set(Some(format!("Failed to sync the pool: {}", e)));
+                            return;
+                        }
+                    },
+                };
+                set_sync_state.set(Some(state));
+
+                match provider.get_fee_options(&note).await {
+                    Ok(options) => {
+                        set_selected_fee.set(if options.is_empty() { None } else { Some(0) });
+                        set_fee_options.set(options);
+                    }
+                    Err(e) => set_error.set(Some(format!("Failed to estimate fees: {}", e))),
                 }
-            };
-            
-            set_withdrawal_status.set(WithdrawalStatus::ValidatingRecipient);
-            
-            // Validate recipient address
-            if !validate_bitcoin_address(&recipient) {
-                set_withdrawal_status.set(WithdrawalStatus::Error("Invalid recipient address".to_string()));
-                notification_service.error("Invalid Address", "Please enter a valid Bitcoin address");
-                return;
             }
-            
-            set_withdrawal_status.set(WithdrawalStatus::GeneratingProof);
-            
-            // Create transaction outputs
-            let outputs = vec![TxOutput {
-                value: deposit_note.denomination,
-                script_pubkey: recipient.clone(),
-            }];
-            
-            // Mock merkle path (in production, fetch from indexer)
-            let merkle_path = MerklePath {
-                root: "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef".to_string(),
-                elements: vec!["0xabcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890".to_string()],
-                indices: vec![false],
-                leaf_index: deposit_note.leaf_index,
-            };
-            
-            // Generate withdrawal proof
-            if let Some(wallet_provider) = wallet_service.connected_wallet.get() {
-                match zkane_service.generate_withdrawal_proof(&deposit_note, &outputs, &merkle_path).await {
+        })
+    };
+
+    // Prove the withdrawal in the prover worker, then prepare it for review
+    let prove_action = {
+        let provider = provider.clone();
+        let notification_service = notification_service.clone();
+        Action::new(move |_: &()| {
+            let provider = provider.clone();
+            let notification_service = notification_service.clone();
+            let note = parsed_note.get_untracked();
+            let recipient = recipient_address.get_untracked();
+            let script = crate::utils::address_script_hex(&recipient);
+            let merkle_path = sync_state.get_untracked().map(|state| state.merkle_path);
+            let fee = selected_fee
+                .get_untracked()
+                .and_then(|index| fee_options.get_untracked().get(index).cloned());
+
+            async move {
+                let (Some(note), Some(script), Some(merkle_path), Some(fee)) = (note, script, merkle_path, fee) else {
+                    return;
+                };
+                let Some(amount) = fee.recipient_amount(note.denomination) else {
+                    set_error.set(Some("The relayer fee exceeds the note amount".to_string()));
+                    return;
+                };
+                set_error.set(None);
+                set_proof_progress.set(None);
+                set_submission.set(None);
+
+                let outputs = vec![TxOutput {
+                    value: amount,
+                    script_pubkey: script,
+                }];
+                let on_progress = Rc::new(move |progress: ProofProgress| set_proof_progress.set(Some(progress)));
+                let result = match provider.prove_withdrawal(&note, &outputs, &merkle_path, &fee, on_progress).await {
                     Ok(proof) => {
-                        set_generated_proof.set(Some(proof.clone()));
-                        set_withdrawal_status.set(WithdrawalStatus::Complete(proof));
-                        notification_service.success(
-                            "Proof Generated",
-                            "Withdrawal proof generated successfully"
-                        );
-                    },
+                        provider
+                            .preview_withdrawal(&note, proof, &merkle_path, &recipient, outputs, fee)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(withdrawal) => {
+                        set_preview.set(Some(withdrawal));
+                        set_step.set(WithdrawStep::Review);
+                        notification_service.success("Proof Generated", "Review your withdrawal before submitting it");
+                    }
+                    // A cancelled proof already took the user back
+                    Err(_) if step.get_untracked() != WithdrawStep::Prove => {}
+                    Err(e) => set_error.set(Some(format!("Failed to generate proof: {}", e))),
+                }
+            }
+        })
+    };
+
+    // Broadcast the reviewed withdrawal or hand it to its relayer
+    let submit_action = {
+        let provider = provider.clone();
+        let notification_service = notification_service.clone();
+        let secure_storage = secure_storage.clone();
+        Action::new(move |_: &()| {
+            let provider = provider.clone();
+            let notification_service = notification_service.clone();
+            let secure_storage = secure_storage.clone();
+            let withdrawal = preview.get_untracked();
+            let note = parsed_note.get_untracked();
+            let anonymity_set = sync_state.get_untracked().map(|state| state.deposit_count);
+
+            async move {
+                let (Some(withdrawal), Some(note)) = (withdrawal, note) else { return };
+                set_error.set(None);
+                match provider.submit_withdrawal(&withdrawal).await {
+                    Ok(result) => {
+                        let (txid, relay_job) = match &result {
+                            WithdrawalSubmission::Broadcast(response) => (Some(response.txid.clone()), None),
+                            WithdrawalSubmission::Relayed { relayer, job_id } => (None, Some((relayer.clone(), *job_id))),
+                        };
+                        let record = TransactionRecord {
+                            kind: HistoryKind::Withdrawal,
+                            commitment: note.commitment.clone(),
+                            asset_id: note.asset_id.clone(),
+                            denomination: note.denomination,
+                            txid,
+                            relay_job,
+                            anonymity_set,
+                            created_at: js_sys::Date::now(),
+                        };
+                        if let Err(e) = secure_storage.record_transaction(&record).await {
+                            log::debug!("Withdrawal not recorded in history: {:?}", e);
+                        }
+                        set_submission.set(Some(result));
+                        notification_service.success("Withdrawal Submitted", "Your withdrawal is on its way");
+                    }
                     Err(e) => {
-                        let error_msg = format!("Failed to generate proof: {:?}", e);
-                        set_withdrawal_status.set(WithdrawalStatus::Error(error_msg.clone()));
-                        notification_service.error("Proof Generation Failed", &error_msg);
+                        let error_msg = format!("Failed to submit withdrawal: {}", e);
+                        notification_service.error("Withdrawal Failed", &error_msg);
+                        set_error.set(Some(error_msg));
                     }
                 }
-            } else {
-                set_withdrawal_status.set(WithdrawalStatus::Error("Wallet not connected".to_string()));
-                notification_service.error("Wallet Not Connected", "Please connect a wallet to generate a proof");
             }
+        })
+    };
+
+    let go_to = move |next: WithdrawStep| {
+        set_error.set(None);
+        set_step.set(next);
+        match next {
+            WithdrawStep::Sync => sync_action.dispatch(()),
+            WithdrawStep::Prove => prove_action.dispatch(()),
+            _ => {}
         }
-    });
+    };
+    let cancel_proof = {
+        let provider = provider.clone();
+        Callback::new(move |_| {
+            provider.cancel_withdrawal_proof();
+            set_step.set(WithdrawStep::Fees);
+        })
+    };
+    let denomination = move || parsed_note.get().map_or(0, |note| note.denomination);
 
     view! {
         <div class="withdraw-component">
-            <NoteInput 
-                note_json=deposit_note_json
-                set_note_json=set_deposit_note_json
-                parse_note=parse_note
-                parsed_note=parsed_note
-            />
-            
-            <RecipientInput 
-                recipient=recipient_address
-                set_recipient=set_recipient_address
-                disabled=Signal::derive(move || parsed_note.get().is_none())
-            />
-            
-            <WithdrawActions 
-                withdraw_action=withdraw_action
-                withdrawal_status=withdrawal_status
-                parsed_note=parsed_note
-                recipient=recipient_address
-            />
-            
-            <WithdrawResult 
-                status=withdrawal_status
-                generated_proof=generated_proof
-            />
+            <WithdrawStepIndicator step=step />
+
+            <Show when=move || step.get() == WithdrawStep::Note>
+                <NoteInput
+                    note_json=deposit_note_json
+                    set_note_json=set_deposit_note_json
+                    parse_note=parse_note.clone()
+                    parsed_note=parsed_note
+                />
+
+                <RecipientInput
+                    recipient=recipient_address
+                    set_recipient=set_recipient_address
+                    disabled=Signal::derive(move || parsed_note.get().is_none())
+                />
+
+                <div class="withdraw-actions">
+                    <button
+                        type="button"
+                        class="btn btn-primary btn-lg"
+                        prop:disabled=move || !can_continue()
+                        on:click=move |_| go_to(WithdrawStep::Sync)
+                    >
+                        "Continue"
+                    </button>
+                </div>
+            </Show>
+
+            <Show when=move || step.get() == WithdrawStep::Sync>
+                {move || match sync_state.get() {
+                    Some(state) => view! { <PoolSyncSummary state=state /> }.into_view(),
+                    None if sync_action.pending().get() => view! {
+                        <div class="progress-indicator">
+                            <div class="spinner"></div>
+                            <span>"Syncing pool state..."</span>
+                        </div>
+                    }.into_view(),
+                    None => ().into_view(),
+                }}
+                <div class="withdraw-actions">
+                    <button type="button" class="btn btn-secondary" on:click=move |_| go_to(WithdrawStep::Note)>
+                        "Back"
+                    </button>
+                    <button
+                        type="button"
+                        class="btn btn-secondary"
+                        prop:disabled=move || sync_action.pending().get()
+                        on:click=move |_| sync_action.dispatch(())
+                    >
+                        "Retry"
+                    </button>
+                    <button
+                        type="button"
+                        class="btn btn-primary"
+                        prop:disabled=move || sync_state.get().is_none() || fee_options.get().is_empty()
+                        on:click=move |_| go_to(WithdrawStep::Fees)
+                    >
+                        "Continue"
+                    </button>
+                </div>
+            </Show>
+
+            <Show when=move || step.get() == WithdrawStep::Fees>
+                {move || view! {
+                    <FeeOptionList
+                        options=fee_options
+                        selected=selected_fee
+                        set_selected=set_selected_fee
+                        denomination=denomination()
+                    />
+                }}
+                <div class="withdraw-actions">
+                    <button type="button" class="btn btn-secondary" on:click=move |_| go_to(WithdrawStep::Sync)>
+                        "Back"
+                    </button>
+                    <button
+                        type="button"
+                        class="btn btn-primary"
+                        prop:disabled=move || selected_fee.get().is_none()
+                        on:click=move |_| go_to(WithdrawStep::Prove)
+                    >
+                        "Generate Proof"
+                    </button>
+                </div>
+            </Show>
+
+            <Show when=move || step.get() == WithdrawStep::Prove>
+                <Show
+                    when=move || prove_action.pending().get()
+                    fallback=move || view! {
+                        <div class="withdraw-actions">
+                            <button type="button" class="btn btn-secondary" on:click=move |_| go_to(WithdrawStep::Fees)>
+                                "Back"
+                            </button>
+                            <button type="button" class="btn btn-primary" on:click=move |_| go_to(WithdrawStep::Prove)>
+                                "Try Again"
+                            </button>
+                        </div>
+                    }
+                >
+                    <ProofProgressPanel progress=proof_progress on_cancel=cancel_proof />
+                </Show>
+            </Show>
+
+            <Show when=move || step.get() == WithdrawStep::Review>
+                {move || preview.get().map(|withdrawal| view! {
+                    <WithdrawalReview
+                        preview=withdrawal
+                        recipient=recipient_address.get_untracked()
+                        denomination=denomination()
+                    />
+                })}
+                {move || match submission.get() {
+                    Some(result) => view! { <WithdrawalSubmitted submission=result /> }.into_view(),
+                    None => view! {
+                        <div class="withdraw-actions">
+                            <button type="button" class="btn btn-secondary" on:click=move |_| go_to(WithdrawStep::Fees)>
+                                "Back"
+                            </button>
+                            <button
+                                type="button"
+                                class="btn btn-primary btn-lg"
+                                prop:disabled=move || submit_action.pending().get()
+                                on:click=move |_| submit_action.dispatch(())
+                            >
+                                {move || if submit_action.pending().get() { "Submitting..." } else { "Submit Withdrawal" }}
+                            </button>
+                        </div>
+                    }.into_view(),
+                }}
+            </Show>
+
+            {move || error.get().map(|error| view! {
+                <div class="error-result">
+                    <div class="error-header">
+                        <span class="error-icon">"❌"</span>
+                        <h4>"Withdrawal Failed"</h4>
+                    </div>
+                    <p class="error-message">{error}</p>
+                </div>
+            })}
         </div>
     }
 }
 
 #[component]
 pub fn PoolListComponent() -> impl IntoView {
-    let alkanes_service = expect_context::<AlkanesService>();
+    let provider = use_frontend_provider();
     
     // State
     let (filter_asset, set_filter_asset) = create_signal(String::new());
@@ -414,15 +709,8 @@ pub fn PoolListComponent() -> impl IntoView {
     let pools = Resource::new(
         || (),
         move |_| {
-            let alkanes_service = alkanes_service.clone();
-            let wallet_service = expect_context::<WalletService>();
-            async move {
-                if let Some(wallet_provider) = wallet_service.connected_wallet.get() {
-                    alkanes_service.get_privacy_pools(&wallet_provider).await
-                } else {
-                    Err(ZKaneError::WasmError("Wallet not connected".to_string()))
-                }
-            }
+            let provider = provider.clone();
+            async move { provider.get_privacy_pools().await }
         },
     );
 
@@ -488,6 +776,21 @@ pub fn SettingsComponent() -> impl IntoView {
     let set_user_preferences = expect_context::<WriteSignal<UserPreferences>>();
     let storage_service = expect_context::<StorageService>();
     let notification_service = expect_context::<NotificationService>();
+    let secure_storage = expect_context::<SecureStorageService>();
+
+    let clear_witness = {
+        let notification_service = notification_service.clone();
+        move |_| {
+            let secure_storage = secure_storage.clone();
+            let notification_service = notification_service.clone();
+            spawn_local(async move {
+                match secure_storage.clear_witness().await {
+                    Ok(()) => notification_service.success("Proof Data Cleared", "No proof witness is kept in this browser"),
+                    Err(e) => notification_service.error("Clear Failed", &e.to_string()),
+                }
+            });
+        }
+    };
 
     let save_preferences = {
         let user_preferences = user_preferences;
@@ -522,7 +825,7 @@ pub fn SettingsComponent() -> impl IntoView {
                 <h3>"Privacy"</h3>
                 <ToggleSetting
                     label="Auto-save deposit notes"
-                    description="Automatically save deposit notes to local storage"
+                    description="Automatically save deposit notes to the encrypted note vault"
                     checked=Signal::derive(move || user_preferences.get().auto_save_notes)
                     on_change={
                         let save_preferences = save_preferences.clone();
@@ -532,6 +835,17 @@ pub fn SettingsComponent() -> impl IntoView {
                         }
                     }
                 />
+                <div class="toggle-setting">
+                    <div class="setting-info">
+                        <label class="setting-label">"Cached proof data"</label>
+                        <p class="setting-description">
+                            "A withdrawal's proof witness is kept in the note vault until its proof is done, so a failed proof can be retried faster. It contains your note's secrets."
+                        </p>
+                    </div>
+                    <div class="setting-control">
+                        <button class="btn btn-secondary" on:click=clear_witness>"Clear"</button>
+                    </div>
+                </div>
             </div>
             
             <div class="settings-section">
@@ -671,24 +985,17 @@ fn filter_and_sort_pools(
     pools
 }
 
+/// Parse an amount in whole units of an asset, exactly, into base units
 fn parse_amount(amount_str: &str, decimals: u8) -> Result<u128, String> {
     if amount_str.is_empty() {
         return Err("Amount cannot be empty".to_string());
     }
 
-    let parsed: f64 = amount_str.parse()
-        .map_err(|_| "Invalid amount format".to_string())?;
-    
-    if parsed <= 0.0 {
+    let amount = zkane_common::parse_units(amount_str.trim(), decimals).map_err(|e| e.to_string())?;
+
+    if amount == 0 {
         return Err("Amount must be greater than zero".to_string());
     }
-    
-    let multiplier = 10u128.pow(decimals as u32) as f64;
-    let amount = (parsed * multiplier) as u128;
-    
+
     Ok(amount)
 }
-
-fn validate_bitcoin_address(address: &str) -> bool {
-    !address.is_empty() && address.len() >= 26 && address.len() <= 62
-}
\ No newline at end of file
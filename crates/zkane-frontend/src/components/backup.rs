@@ -0,0 +1,209 @@
+//! Note backup component shown before a deposit is broadcast
+
+use leptos::*;
+use wasm_bindgen::JsCast;
+use gloo_file::callbacks::{read_as_text, FileReader};
+use crate::types::*;
+use crate::services::{BackupService, NotificationService, MIN_BACKUP_PASSWORD_LEN};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BackupStep {
+    /// Choose the backup password
+    Encrypt,
+    /// Download the backup file and scan the QR code
+    Save,
+    /// Re-import the backup to prove it can be restored
+    Verify,
+}
+
+/// Walk the user through an encrypted backup of a new deposit note.
+///
+/// The note is encrypted with a password and offered as a file download and a
+/// QR code. `on_verified` is only called once the user has re-imported the
+/// backup and decrypted it with the same password, so a deposit can't be
+/// broadcast for a note that was never saved.
+#[component]
+pub fn NoteBackup(note: DepositNote, on_verified: Callback<()>) -> impl IntoView {
+    let backup_service = expect_context::<BackupService>();
+    let notification_service = expect_context::<NotificationService>();
+    let note = store_value(note);
+
+    let (step, set_step) = create_signal(BackupStep::Encrypt);
+    let (password, set_password) = create_signal(String::new());
+    let (confirm_password, set_confirm_password) = create_signal(String::new());
+    let (backup, set_backup) = create_signal(None::<String>);
+    let (downloaded, set_downloaded) = create_signal(false);
+    let (import_json, set_import_json) = create_signal(String::new());
+    let (import_password, set_import_password) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let file_reader = store_value(None::<FileReader>);
+
+    let encrypt = {
+        let backup_service = backup_service.clone();
+        move |_| {
+            if password.get() != confirm_password.get() {
+                set_error.set(Some("Passwords do not match".to_string()));
+                return;
+            }
+            match note.with_value(|note| backup_service.encrypt_note(note, &password.get())) {
+                Ok(contents) => {
+                    // The password has to be typed again to verify the backup
+                    set_password.set(String::new());
+                    set_confirm_password.set(String::new());
+                    set_backup.set(Some(contents));
+                    set_error.set(None);
+                    set_step.set(BackupStep::Save);
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        }
+    };
+
+    let download = {
+        let backup_service = backup_service.clone();
+        move |_| {
+            let Some(contents) = backup.get() else { return };
+            let file_name = note.with_value(|note| backup_service.file_name(note));
+            match backup_service.download(&contents, &file_name) {
+                Ok(()) => set_downloaded.set(true),
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        }
+    };
+
+    let qr_code = {
+        let backup_service = backup_service.clone();
+        move || {
+            backup
+                .get()
+                .and_then(|contents| backup_service.qr_code_svg(&contents).ok())
+                .unwrap_or_default()
+        }
+    };
+
+    let verify = {
+        let backup_service = backup_service.clone();
+        move |_| {
+            let result = note.with_value(|note| {
+                backup_service.verify_backup(note, &import_json.get(), &import_password.get())
+            });
+            match result {
+                Ok(()) => {
+                    set_error.set(None);
+                    notification_service.success("Backup Verified", "Your note backup can be restored.");
+                    on_verified.call(());
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        }
+    };
+
+    view! {
+        <div class="note-backup">
+            <div class="backup-header">
+                <h4>"Back Up Your Deposit Note"</h4>
+                <p>"Your deposit will only be broadcast once you have saved an encrypted backup and shown that it can be restored."</p>
+            </div>
+
+            <Show when=move || step.get() == BackupStep::Encrypt>
+                <div class="backup-step">
+                    <label class="form-label">"Backup Password"</label>
+                    <input
+                        type="password"
+                        class="form-input"
+                        placeholder=format!("At least {} characters", MIN_BACKUP_PASSWORD_LEN)
+                        prop:value=password
+                        on:input=move |ev| set_password.set(event_target_value(&ev))
+                    />
+                    <input
+                        type="password"
+                        class="form-input"
+                        placeholder="Confirm password"
+                        prop:value=confirm_password
+                        on:input=move |ev| set_confirm_password.set(event_target_value(&ev))
+                    />
+                    <button
+                        type="button"
+                        class="btn btn-primary"
+                        prop:disabled=move || password.get().is_empty()
+                        on:click=encrypt.clone()
+                    >
+                        "Encrypt Backup"
+                    </button>
+                </div>
+            </Show>
+
+            <Show when=move || step.get() == BackupStep::Save>
+                <div class="backup-step">
+                    <div class="backup-qr" inner_html=qr_code.clone()></div>
+                    <p class="backup-hint">"Scan the QR code or download the backup file and keep it somewhere safe."</p>
+                    <div class="note-actions">
+                        <button type="button" class="btn btn-secondary" on:click=download.clone()>
+                            "Download Backup"
+                        </button>
+                        <button
+                            type="button"
+                            class="btn btn-primary"
+                            prop:disabled=move || !downloaded.get()
+                            on:click=move |_| set_step.set(BackupStep::Verify)
+                        >
+                            "I've Saved My Backup"
+                        </button>
+                    </div>
+                </div>
+            </Show>
+
+            <Show when=move || step.get() == BackupStep::Verify>
+                <div class="backup-step">
+                    <label class="form-label">"Re-import Your Backup"</label>
+                    <textarea
+                        class="form-textarea"
+                        placeholder="Paste the backup file contents or load the file..."
+                        rows="6"
+                        prop:value=import_json
+                        on:input=move |ev| set_import_json.set(event_target_value(&ev))
+                    ></textarea>
+                    <input
+                        type="file"
+                        accept=".json"
+                        on:change=move |ev| {
+                            if let Some(file) = ev.target().and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+                                .and_then(|input| input.files())
+                                .and_then(|files| files.get(0)) {
+
+                                let file_blob = gloo_file::Blob::from(file);
+                                // The read is aborted if the reader is dropped, so keep it
+                                file_reader.set_value(Some(read_as_text(&file_blob, move |result| {
+                                    if let Ok(text) = result {
+                                        set_import_json.set(text);
+                                    }
+                                })));
+                            }
+                        }
+                    />
+                    <input
+                        type="password"
+                        class="form-input"
+                        placeholder="Backup password"
+                        prop:value=import_password
+                        on:input=move |ev| set_import_password.set(event_target_value(&ev))
+                    />
+                    <button
+                        type="button"
+                        class="btn btn-primary"
+                        prop:disabled=move || import_json.get().is_empty() || import_password.get().is_empty()
+                        on:click=verify.clone()
+                    >
+                        "Verify Backup"
+                    </button>
+                </div>
+            </Show>
+
+            {move || error.get().map(|error| view! {
+                <div class="error-state">
+                    <p>{error}</p>
+                </div>
+            })}
+        </div>
+    }
+}
@@ -164,6 +164,7 @@ pub fn DepositActions(
                         DepositStatus::Idle => "Create Deposit Note",
                         DepositStatus::ValidatingAmount => "Validating...",
                         DepositStatus::CreatingNote => "Creating Note...",
+                        DepositStatus::AwaitingBackup(_) => "Back Up Your Note",
                         DepositStatus::BuildingTransaction => "Building Transaction...",
                         DepositStatus::WaitingForSignature => "Waiting for Signature...",
                         DepositStatus::Broadcasting => "Broadcasting...",
@@ -6,37 +6,39 @@ use wasm_bindgen_futures::spawn_local;
 use crate::types::*;
 use crate::services::*;
 use crate::components::utils::*;
+use crate::provider::use_frontend_provider;
 
 #[component]
 pub fn HistoryComponent() -> impl IntoView {
-    let storage_service = expect_context::<StorageService>();
+    let secure_storage = expect_context::<SecureStorageService>();
     let notification_service = expect_context::<NotificationService>();
+    let locked = secure_storage.locked();
     
     // State for managing notes
     let (refresh_trigger, set_refresh_trigger) = create_signal(0);
     
-    // Preload test deposit notes on component mount
-    let storage_service_for_preload = storage_service.clone();
-    let set_refresh_trigger_for_preload = set_refresh_trigger;
+    // Preload test deposit notes once the vault is unlocked
+    let secure_storage_for_preload = secure_storage.clone();
     create_effect(move |_| {
-        let storage_service = storage_service_for_preload.clone();
+        if locked.get() {
+            return;
+        }
+        let secure_storage = secure_storage_for_preload.clone();
         spawn_local(async move {
-            if let Err(e) = storage_service.preload_test_deposit_notes() {
+            if let Err(e) = secure_storage.preload_test_deposit_notes().await {
                 log::warn!("Failed to preload test deposit notes: {:?}", e);
-            } else {
-                // Trigger refresh after preloading
-                set_refresh_trigger_for_preload.update(|n| *n += 1);
             }
         });
     });
     
-    // Load saved deposit notes with refresh capability
+    // Load saved deposit notes, again whenever the vault changes
+    let revision = secure_storage.revision();
     let saved_notes = Resource::new(
-        move || refresh_trigger.get(),
+        move || (refresh_trigger.get(), revision.get()),
         move |_| {
-            let storage_service = storage_service.clone();
+            let secure_storage = secure_storage.clone();
             async move {
-                storage_service.load_deposit_notes()
+                secure_storage.load_deposit_notes().await
             }
         },
     );
@@ -58,49 +60,161 @@ pub fn HistoryComponent() -> impl IntoView {
                 </div>
             </div>
             
-            <Suspense fallback=|| view! { <LoadingSpinner message="Loading history..."/> }>
-                {move || {
-                    saved_notes.get().map(|result| -> leptos::View {
-                        match result {
-                            Ok(notes) => {
-                                if notes.is_empty() {
-                                    view! {
-                                        <EmptyState
-                                            icon="📜"
-                                            title="No Deposit Notes"
-                                            message="You haven't created any deposit notes yet. Create your first deposit to get started!"
-                                        />
-                                    }.into_view()
-                                } else {
-                                    view! {
-                                        <div class="notes-list">
-                                            <div class="notes-summary">
-                                                <p class="summary-text">
-                                                    {format!("You have {} saved deposit note{}", 
-                                                        notes.len(), 
-                                                        if notes.len() == 1 { "" } else { "s" }
-                                                    )}
-                                                </p>
-                                            </div>
-                                            
-                                            <div class="notes-grid">
-                                                {notes.into_iter().map(|note| {
-                                                    view! {
-                                                        <NoteCard note=note/>
-                                                    }
-                                                }).collect::<Vec<_>>()}
+            <Show when=move || locked.get()>
+                <NoteVaultUnlock/>
+            </Show>
+
+            <Show when=move || !locked.get()>
+                <TransactionHistory/>
+
+                <Suspense fallback=|| view! { <LoadingSpinner message="Loading history..."/> }>
+                    {move || {
+                        saved_notes.get().map(|result| -> leptos::View {
+                            match result {
+                                Ok(notes) => {
+                                    if notes.is_empty() {
+                                        view! {
+                                            <EmptyState
+                                                icon="📜"
+                                                title="No Deposit Notes"
+                                                message="You haven't created any deposit notes yet. Create your first deposit to get started!"
+                                            />
+                                        }.into_view()
+                                    } else {
+                                        view! {
+                                            <div class="notes-list">
+                                                <div class="notes-summary">
+                                                    <p class="summary-text">
+                                                        {format!("You have {} saved deposit note{}", 
+                                                            notes.len(), 
+                                                            if notes.len() == 1 { "" } else { "s" }
+                                                        )}
+                                                    </p>
+                                                </div>
+                                                
+                                                <div class="notes-grid">
+                                                    {notes.into_iter().map(|note| {
+                                                        view! {
+                                                            <NoteCard note=note/>
+                                                        }
+                                                    }).collect::<Vec<_>>()}
+                                                </div>
                                             </div>
-                                        </div>
-                                    }.into_view()
-                                }
-                            },
-                            Err(e) => view! {
-                                <ErrorState
-                                    title="Failed to Load History"
-                                    message=format!("Error loading deposit notes: {:?}", e)
-                                />
-                            }.into_view()
+                                        }.into_view()
+                                    }
+                                },
+                                Err(e) => view! {
+                                    <ErrorState
+                                        title="Failed to Load History"
+                                        message=format!("Error loading deposit notes: {:?}", e)
+                                    />
+                                }.into_view()
+                            }
+                        })
+                    }}
+                </Suspense>
+            </Show>
+        </div>
+    }
+}
+
+/// The user's deposits and withdrawals, with CSV export.
+///
+/// Entries come from the note vault only, see [`build_history`]. Their
+/// transactions are then looked up by ID for confirmations and block heights;
+/// nothing is ever looked up by address.
+#[component]
+pub fn TransactionHistory() -> impl IntoView {
+    let secure_storage = expect_context::<SecureStorageService>();
+    let storage_service = expect_context::<StorageService>();
+    let notification_service = expect_context::<NotificationService>();
+    let provider = use_frontend_provider();
+
+    let revision = secure_storage.revision();
+    let history = Resource::new(
+        move || revision.get(),
+        move |_| {
+            let secure_storage = secure_storage.clone();
+            let provider = provider.clone();
+            async move {
+                let notes = secure_storage.load_deposit_notes().await?;
+                let records = secure_storage.load_transactions().await?;
+                let checkpoints = secure_storage.load_sync_checkpoints().await?;
+                let mut entries = build_history(&notes, &records, &checkpoints);
+                for entry in &mut entries {
+                    let Some(txid) = entry.txid.clone() else { continue };
+                    match provider.get_transaction_status(&txid).await {
+                        // A pending lookup doesn't undo a confirmation seen by a sync
+                        Ok(response) if response.status != TransactionStatus::Pending => {
+                            entry.status = response.status;
+                            entry.block_height = response.block_height;
                         }
+                        Ok(_) => {}
+                        Err(e) => log::debug!("Status of {} not available: {}", txid, e),
+                    }
+                }
+                Ok::<_, ZKaneError>(entries)
+            }
+        },
+    );
+
+    let export_csv = move |_| {
+        let Some(Ok(entries)) = history.get_untracked() else { return };
+        match download_file(&history_csv(&entries), "zkane-history.csv", "text/csv") {
+            Ok(()) => notification_service.success("History Exported", "Your history was saved as CSV"),
+            Err(e) => notification_service.error("Export Failed", &e.to_string()),
+        }
+    };
+
+    view! {
+        <div class="transaction-history">
+            <div class="history-header">
+                <h3>"Deposits & Withdrawals"</h3>
+                <div class="history-actions">
+                    <button
+                        class="btn btn-secondary"
+                        prop:disabled=move || !matches!(history.get(), Some(Ok(entries)) if !entries.is_empty())
+                        on:click=export_csv
+                    >
+                        "⬇ Export CSV"
+                    </button>
+                </div>
+            </div>
+
+            <Suspense fallback=|| view! { <LoadingSpinner message="Loading transactions..."/> }>
+                {move || {
+                    let storage_service = storage_service.clone();
+                    history.get().map(|result| match result {
+                        Ok(entries) if entries.is_empty() => view! {
+                            <EmptyState
+                                icon="🧾"
+                                title="No Transactions"
+                                message="Deposits and withdrawals of your saved notes will appear here."
+                            />
+                        }.into_view(),
+                        Ok(entries) => view! {
+                            <table class="history-table">
+                                <thead>
+                                    <tr>
+                                        <th>"Transaction"</th>
+                                        <th>"Amount"</th>
+                                        <th>"Status"</th>
+                                        <th>"Block"</th>
+                                        <th>"Anonymity Set"</th>
+                                        <th>"Date"</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {entries.into_iter().map(|entry| {
+                                        let symbol = storage_service.get_asset_symbol(&entry.asset_id);
+                                        view! { <HistoryRow entry=entry asset_symbol=symbol/> }
+                                    }).collect::<Vec<_>>()}
+                                </tbody>
+                            </table>
+                        }.into_view(),
+                        Err(e) => view! {
+                            <ErrorState title="Failed to Load Transactions" message=e.to_string()/>
+                        }.into_view(),
                     })
                 }}
             </Suspense>
@@ -108,6 +222,36 @@ pub fn HistoryComponent() -> impl IntoView {
     }
 }
 
+#[component]
+fn HistoryRow(entry: HistoryEntry, asset_symbol: String) -> impl IntoView {
+    let (status, status_class) = match entry.status {
+        TransactionStatus::Confirmed => ("Confirmed", "status-confirmed"),
+        TransactionStatus::Pending if entry.relayer.is_some() && entry.txid.is_none() => ("Relaying", "status-pending"),
+        TransactionStatus::Pending => ("Pending", "status-pending"),
+        TransactionStatus::Failed => ("Failed", "status-failed"),
+    };
+    let anonymity = entry.anonymity_set.map(|size| {
+        let level = AnonymityLevel::for_set_size(size);
+        view! { <span class=level.css_class() title=level.as_str()>{size}</span> }
+    });
+
+    view! {
+        <tr class="history-row">
+            <td>
+                <span class="history-label">{entry.label()}</span>
+                {entry.txid.as_ref().map(|txid| view! {
+                    <span class="detail-value monospace" title=txid.clone()>{format!("{}...", &txid[..txid.len().min(16)])}</span>
+                })}
+            </td>
+            <td>{format!("{:.8} {}", entry.denomination as f64 / 100_000_000.0, asset_symbol)}</td>
+            <td><span class=format!("status-badge {}", status_class)>{status}</span></td>
+            <td>{entry.block_height.map_or("—".to_string(), |height| height.to_string())}</td>
+            <td>{anonymity.map_or_else(|| "—".into_view(), |view| view.into_view())}</td>
+            <td>{format_timestamp(entry.timestamp)}</td>
+        </tr>
+    }
+}
+
 #[component]
 pub fn EnhancedNoteCard(
     note: DepositNote,
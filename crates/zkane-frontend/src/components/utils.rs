@@ -224,7 +224,8 @@ pub fn NoteCard(
     // Clone notification service for each closure
     let notification_service_copy = notification_service.clone();
     let notification_service_delete = notification_service.clone();
-    let storage_service_delete = storage_service.clone();
+    let secure_storage = expect_context::<SecureStorageService>();
+    let secure_storage_withdraw = secure_storage.clone();
     
     view! {
         <div class="note-card">
@@ -283,15 +284,9 @@ pub fn NoteCard(
                         if let Some(callback) = &on_use_for_withdrawal {
                             callback(note_for_withdrawal.clone());
                         } else {
-                            // Default behavior: navigate to withdraw page
-                            let note_json = serde_json::to_string_pretty(&note_for_withdrawal).unwrap_or_default();
-                            // Store the note in session storage for the withdraw page to pick up
-                            if let Some(window) = web_sys::window() {
-                                if let Ok(Some(storage)) = window.session_storage() {
-                                    let _ = storage.set_item("zkane_prefill_note", &note_json);
-                                }
-                            }
-                            
+                            // Default behavior: hand the note to the withdraw page in memory
+                            secure_storage_withdraw.set_prefill_note(note_for_withdrawal.clone());
+
                             // Use Leptos router navigation
                             let navigate = leptos_router::use_navigate();
                             navigate("/withdraw", Default::default());
@@ -321,20 +316,20 @@ pub fn NoteCard(
                         } else {
                             // Default behavior: show confirmation and delete
                             if confirm_delete_note() {
-                                match storage_service_delete.delete_deposit_note(&note_for_delete.commitment) {
-                                    Ok(_) => {
-                                        notification_service_delete.success("Deleted", "Deposit note deleted successfully");
-                                        // Trigger page refresh safely
-                                        if let Some(window) = web_sys::window() {
-                                            if let Ok(_location) = window.location().href() {
-                                                let _ = window.location().reload();
-                                            }
+                                // The history reloads once the vault has been written
+                                let secure_storage = secure_storage.clone();
+                                let notification_service = notification_service_delete.clone();
+                                let commitment = note_for_delete.commitment.clone();
+                                spawn_local(async move {
+                                    match secure_storage.delete_deposit_note(&commitment).await {
+                                        Ok(_) => {
+                                            notification_service.success("Deleted", "Deposit note deleted successfully");
+                                        },
+                                        Err(e) => {
+                                            notification_service.error("Delete Failed", &format!("Failed to delete note: {:?}", e));
                                         }
-                                    },
-                                    Err(e) => {
-                                        notification_service_delete.error("Delete Failed", &format!("Failed to delete note: {:?}", e));
                                     }
-                                }
+                                });
                             }
                         }
                     }
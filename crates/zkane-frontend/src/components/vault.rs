@@ -0,0 +1,119 @@
+//! Lock and unlock controls for the encrypted note vault
+
+use leptos::*;
+use crate::services::{NotificationService, SecureStorageService, MIN_BACKUP_PASSWORD_LEN};
+
+/// Passphrase form unlocking the note vault, or creating it on first use.
+///
+/// `on_unlocked` is called once the vault is unlocked.
+#[component]
+pub fn NoteVaultUnlock(#[prop(optional, into)] on_unlocked: Option<Callback<()>>) -> impl IntoView {
+    let secure_storage = expect_context::<SecureStorageService>();
+    let notification_service = expect_context::<NotificationService>();
+
+    let (passphrase, set_passphrase) = create_signal(String::new());
+    let (confirm_passphrase, set_confirm_passphrase) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let vault_exists = {
+        let secure_storage = secure_storage.clone();
+        Resource::new(
+            || (),
+            move |_| {
+                let secure_storage = secure_storage.clone();
+                async move { secure_storage.has_vault().await.unwrap_or(false) }
+            },
+        )
+    };
+    let creating = move || vault_exists.get() == Some(false);
+
+    let unlock_action = Action::new(move |_: &()| {
+        let secure_storage = secure_storage.clone();
+        let notification_service = notification_service.clone();
+        let passphrase = passphrase.get_untracked();
+        let mismatch = creating() && passphrase != confirm_passphrase.get_untracked();
+
+        async move {
+            if mismatch {
+                set_error.set(Some("Passphrases do not match".to_string()));
+                return;
+            }
+            match secure_storage.unlock(&passphrase).await {
+                Ok(()) => {
+                    set_passphrase.set(String::new());
+                    set_confirm_passphrase.set(String::new());
+                    set_error.set(None);
+                    notification_service.success("Vault Unlocked", "Your saved deposit notes are available");
+                    if let Some(on_unlocked) = on_unlocked {
+                        on_unlocked.call(());
+                    }
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        }
+    });
+
+    view! {
+        <div class="note-vault-unlock">
+            <div class="vault-header">
+                <h4>{move || if creating() { "Create Your Note Vault" } else { "Unlock Your Note Vault" }}</h4>
+                <p>
+                    {move || if creating() {
+                        "Deposit notes are saved in this browser encrypted with a passphrase. It can't be recovered, so keep your note backups."
+                    } else {
+                        "Enter your passphrase to access the deposit notes saved in this browser."
+                    }}
+                </p>
+            </div>
+
+            <input
+                type="password"
+                class="form-input"
+                placeholder=format!("Passphrase (at least {} characters)", MIN_BACKUP_PASSWORD_LEN)
+                prop:value=passphrase
+                on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+            />
+            <Show when=creating>
+                <input
+                    type="password"
+                    class="form-input"
+                    placeholder="Confirm passphrase"
+                    prop:value=confirm_passphrase
+                    on:input=move |ev| set_confirm_passphrase.set(event_target_value(&ev))
+                />
+            </Show>
+            <button
+                type="button"
+                class="btn btn-primary"
+                prop:disabled=move || passphrase.get().is_empty() || unlock_action.pending().get()
+                on:click=move |_| unlock_action.dispatch(())
+            >
+                {move || match (unlock_action.pending().get(), creating()) {
+                    (true, _) => "Unlocking...",
+                    (false, true) => "Create Vault",
+                    (false, false) => "Unlock",
+                }}
+            </button>
+
+            {move || error.get().map(|error| view! { <p class="error-message">{error}</p> })}
+        </div>
+    }
+}
+
+/// Header button showing the vault state, locking it when unlocked
+#[component]
+pub fn NoteVaultLockButton() -> impl IntoView {
+    let secure_storage = expect_context::<SecureStorageService>();
+    let locked = secure_storage.locked();
+
+    view! {
+        <button
+            class="vault-lock action-button"
+            prop:disabled=locked
+            on:click=move |_| secure_storage.lock()
+            title=move || if locked.get() { "Note vault locked" } else { "Lock note vault" }
+        >
+            <span class="action-icon">{move || if locked.get() { "🔒" } else { "🔓" }}</span>
+        </button>
+    }
+}
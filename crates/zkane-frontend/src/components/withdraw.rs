@@ -1,10 +1,16 @@
 //! Withdraw component and related UI elements
 
+use std::rc::Rc;
 use leptos::*;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 use gloo_file::callbacks::read_as_text;
+use crate::provider::{use_frontend_provider, FrontendProvider};
 use crate::types::*;
 
+/// Milliseconds between checks of a relayed withdrawal's progress
+const RELAY_POLL_MS: u32 = 5_000;
+
 #[component]
 pub fn NoteInput(
     note_json: ReadSignal<String>,
@@ -97,7 +103,7 @@ pub fn NoteInput(
                                     </div>
                                     <div class="detail-row">
                                         <span class="detail-label">"Amount:"</span>
-                                        <span class="detail-value">{format!("{:.8}", note.denomination as f64 / 100_000_000.0)}</span>
+                                        <span class="detail-value">{format_amount(note.denomination)}</span>
                                     </div>
                                     <div class="detail-row">
                                         <span class="detail-label">"Commitment:"</span>
@@ -179,217 +185,360 @@ pub fn RecipientInput(
     }
 }
 
-#[component]
-pub fn WithdrawActions(
-    withdraw_action: Action<(), ()>,
-    withdrawal_status: ReadSignal<WithdrawalStatus>,
-    parsed_note: ReadSignal<Option<DepositNote>>,
-    recipient: ReadSignal<String>,
-) -> impl IntoView {
-    let can_withdraw = move || {
-        parsed_note.get().is_some() && 
-        !recipient.get().is_empty() && 
-        validate_bitcoin_address(&recipient.get()) &&
-        matches!(withdrawal_status.get(), WithdrawalStatus::Idle)
-    };
+/// Steps of the withdrawal wizard, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WithdrawStep {
+    /// Load the note and enter the recipient
+    Note,
+    /// Fetch the note's Merkle path and check it wasn't withdrawn
+    Sync,
+    /// Choose the network fee rate or a relayer
+    Fees,
+    /// Generate the proof in the prover worker
+    Prove,
+    /// Check the withdrawal and submit it
+    Review,
+}
+
+impl WithdrawStep {
+    pub const ALL: [WithdrawStep; 5] = [
+        WithdrawStep::Note,
+        WithdrawStep::Sync,
+        WithdrawStep::Fees,
+        WithdrawStep::Prove,
+        WithdrawStep::Review,
+    ];
 
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|step| step == self).unwrap_or(0)
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            WithdrawStep::Note => "Note",
+            WithdrawStep::Sync => "Sync Pool",
+            WithdrawStep::Fees => "Fees",
+            WithdrawStep::Prove => "Prove",
+            WithdrawStep::Review => "Review",
+        }
+    }
+}
+
+#[component]
+pub fn WithdrawStepIndicator(step: ReadSignal<WithdrawStep>) -> impl IntoView {
     view! {
-        <div class="withdraw-actions">
-            <button 
-                type="button"
-                class="btn btn-primary btn-lg"
-                prop:disabled=move || !can_withdraw()
-                on:click=move |_| {
-                    withdraw_action.dispatch(());
-                }
-            >
-                {move || {
-                    match withdrawal_status.get() {
-                        WithdrawalStatus::Idle => "Generate Withdrawal Proof",
-                        WithdrawalStatus::ParsingNote => "Parsing Note...",
-                        WithdrawalStatus::ValidatingRecipient => "Validating Address...",
-                        WithdrawalStatus::FetchingMerklePath => "Fetching Merkle Path...",
-                        WithdrawalStatus::GeneratingProof => "Generating Proof...",
-                        WithdrawalStatus::BuildingTransaction => "Building Transaction...",
-                        WithdrawalStatus::WaitingForSignature => "Waiting for Signature...",
-                        WithdrawalStatus::Broadcasting => "Broadcasting...",
-                        WithdrawalStatus::Complete(_) => "Proof Generated",
-                        WithdrawalStatus::Error(_) => "Try Again",
+        <ol class="withdraw-steps">
+            {WithdrawStep::ALL
+                .iter()
+                .map(|&item| {
+                    view! {
+                        <li
+                            class="withdraw-step"
+                            class:active=move || step.get() == item
+                            class:done=move || step.get().index() > item.index()
+                        >
+                            <span class="step-number">{item.index() + 1}</span>
+                            <span class="step-title">{item.title()}</span>
+                        </li>
                     }
-                }}
-            </button>
-            
-            {move || {
-                match withdrawal_status.get() {
-                    WithdrawalStatus::ParsingNote | 
-                    WithdrawalStatus::ValidatingRecipient | 
-                    WithdrawalStatus::GeneratingProof => {
-                        Some(view! {
-                            <div class="progress-indicator">
-                                <div class="spinner"></div>
-                                <span>
-                                    {match withdrawal_status.get() {
-                                        WithdrawalStatus::ParsingNote => "Parsing deposit note...",
-                                        WithdrawalStatus::ValidatingRecipient => "Validating recipient address...",
-                                        WithdrawalStatus::GeneratingProof => "Generating zero-knowledge proof...",
-                                        _ => ""
-                                    }}
-                                </span>
-                            </div>
-                        })
-                    },
-                    _ => None
-                }
-            }}
-            
-            {move || {
-                if matches!(withdrawal_status.get(), WithdrawalStatus::GeneratingProof) {
-                    Some(view! {
-                        <div class="proof-progress">
-                            <div class="progress-bar">
-                                <div class="progress-fill"></div>
-                            </div>
-                            <p class="progress-text">
-                                "This may take a few minutes. Please do not close this tab."
-                            </p>
-                        </div>
-                    })
-                } else {
-                    None
-                }
-            }}
+                })
+                .collect_view()}
+        </ol>
+    }
+}
+
+#[component]
+pub fn PoolSyncSummary(state: PoolSyncState) -> impl IntoView {
+    let root_preview = format!("{}...", &state.merkle_path.root[..state.merkle_path.root.len().min(18)]);
+
+    view! {
+        <div class="pool-sync-summary">
+            <div class="note-status success">
+                <span class="status-icon">"✅"</span>
+                <span>"Note found in the pool and not yet withdrawn"</span>
+            </div>
+            <div class="detail-grid">
+                <div class="detail-row">
+                    <span class="detail-label">"Anonymity Set:"</span>
+                    <span class="detail-value">{state.deposit_count}" deposits"</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Leaf Index:"</span>
+                    <span class="detail-value">{state.merkle_path.leaf_index}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Root:"</span>
+                    <span class="detail-value monospace">{root_preview}</span>
+                </div>
+            </div>
         </div>
     }
 }
 
 #[component]
-pub fn WithdrawResult(
-    status: ReadSignal<WithdrawalStatus>,
-    generated_proof: ReadSignal<Option<WithdrawalProof>>,
+pub fn FeeOptionList(
+    options: ReadSignal<Vec<FeeOption>>,
+    selected: ReadSignal<Option<usize>>,
+    set_selected: WriteSignal<Option<usize>>,
+    denomination: u128,
 ) -> impl IntoView {
     view! {
-        <div class="withdraw-result">
+        <div class="fee-options">
             {move || {
-                match status.get() {
-                    WithdrawalStatus::Complete(proof) => {
-                        let proof_clone1 = proof.clone();
-                        let proof_clone2 = proof.clone();
-                        let proof_clone3 = proof.clone();
-                        let nullifier_hash_preview = format!("{}...", &proof.nullifier_hash[..16]);
-                        let merkle_root_preview = format!("{}...", &proof.merkle_root[..16]);
-                        let proof_len = proof.proof.len();
-                        
-                        Some(view! {
-                            <div class="success-result">
-                                <div class="success-header">
-                                    <span class="success-icon">"✅"</span>
-                                    <h4>"Withdrawal Proof Generated Successfully"</h4>
-                                </div>
-                                
-                                <div class="proof-display">
-                                    <label>"Your Withdrawal Proof:"</label>
-                                    <textarea
-                                        class="proof-textarea"
-                                        readonly
-                                        prop:value=move || {
-                                            serde_json::to_string_pretty(&proof_clone1).unwrap_or_default()
-                                        }
-                                    ></textarea>
-                                </div>
-                                
-                                <div class="proof-actions">
-                                    <button
-                                        type="button"
-                                        class="btn btn-secondary"
-                                        on:click=move |_| {
-                                            let proof_json = serde_json::to_string_pretty(&proof_clone2).unwrap_or_default();
-                                            copy_to_clipboard(&proof_json);
-                                        }
-                                    >
-                                        "Copy Proof"
-                                    </button>
-                                    
-                                    <button
-                                        type="button"
-                                        class="btn btn-secondary"
-                                        on:click=move |_| {
-                                            let proof_json = serde_json::to_string_pretty(&proof_clone3).unwrap_or_default();
-                                            download_as_file(&proof_json, &format!("zkane-withdrawal-proof-{}.json", proof_clone3.nullifier_hash));
-                                        }
-                                    >
-                                        "Download Proof"
-                                    </button>
-                                    
-                                    <button
-                                        type="button"
-                                        class="btn btn-primary"
-                                        on:click=move |_| {
-                                            // In a real implementation, this would submit the transaction
-                                            log::info!("Submitting withdrawal transaction with proof");
-                                        }
-                                    >
-                                        "Submit Transaction"
-                                    </button>
-                                </div>
-                                
-                                <div class="proof-details">
-                                    <h5>"Proof Details:"</h5>
-                                    <div class="detail-grid">
-                                        <div class="detail-row">
-                                            <span class="detail-label">"Nullifier Hash:"</span>
-                                            <span class="detail-value monospace">{nullifier_hash_preview}</span>
-                                        </div>
-                                        <div class="detail-row">
-                                            <span class="detail-label">"Root:"</span>
-                                            <span class="detail-value monospace">{merkle_root_preview}</span>
-                                        </div>
-                                        <div class="detail-row">
-                                            <span class="detail-label">"Proof Size:"</span>
-                                            <span class="detail-value">{proof_len}" bytes"</span>
-                                        </div>
-                                    </div>
-                                </div>
-                                
-                                <div class="next-steps">
-                                    <h5>"Next Steps:"</h5>
-                                    <ol>
-                                        <li>"Copy or download your withdrawal proof"</li>
-                                        <li>"Submit the transaction to the Bitcoin network"</li>
-                                        <li>"Wait for confirmation (usually 10-60 minutes)"</li>
-                                        <li>"Your funds will be available at the recipient address"</li>
-                                    </ol>
-                                </div>
-                            </div>
-                        })
-                    },
-                    WithdrawalStatus::Error(error) => {
-                        Some(view! {
-                            <div class="error-result">
-                                <div class="error-header">
-                                    <span class="error-icon">"❌"</span>
-                                    <h4>"Withdrawal Failed"</h4>
-                                </div>
-                                <p class="error-message">{error}</p>
-                                
-                                <div class="error-help">
-                                    <h5>"Common Issues:"</h5>
-                                    <ul>
-                                        <li>"Invalid deposit note format"</li>
-                                        <li>"Invalid recipient address"</li>
-                                        <li>"Network connectivity issues"</li>
-                                        <li>"Note already used for withdrawal"</li>
-                                    </ul>
+                options
+                    .get()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, option)| {
+                        let network_fee = match option.relayer {
+                            Some(_) => "Network fee paid by the relayer".to_string(),
+                            None => format!("{} sat/vB network fee, paid from your wallet", option.fee_rate),
+                        };
+                        let receives = option
+                            .recipient_amount(denomination)
+                            .map(format_amount)
+                            .unwrap_or_else(|| "-".to_string());
+                        let relayer_terms = option.relayer.as_ref().map(|quote| {
+                            format!(
+                                "{}, {} job(s) queued",
+                                format_confirmation(quote.estimated_confirmation_secs),
+                                quote.pending_jobs
+                            )
+                        });
+
+                        view! {
+                            <label class="fee-option" class:selected=move || selected.get() == Some(index)>
+                                <input
+                                    type="radio"
+                                    name="withdraw-fee-option"
+                                    prop:checked=move || selected.get() == Some(index)
+                                    on:change=move |_| set_selected.set(Some(index))
+                                />
+                                <div class="fee-option-details">
+                                    <span class="fee-option-label">{option.label.clone()}</span>
+                                    <span class="fee-option-network">{network_fee}</span>
+                                    <span class="fee-option-relayer">
+                                        "Relayer fee: "{format_amount(option.relayer_fee())}
+                                    </span>
+                                    <span class="fee-option-receives">"Recipient receives: "{receives}</span>
+                                    {relayer_terms.map(|terms| view! {
+                                        <span class="fee-option-terms">{terms}</span>
+                                    })}
                                 </div>
-                            </div>
-                        })
-                    },
-                    _ => None
-                }
+                            </label>
+                        }
+                    })
+                    .collect_view()
             }}
         </div>
     }
 }
 
+#[component]
+pub fn ProofProgressPanel(progress: ReadSignal<Option<ProofProgress>>, on_cancel: Callback<()>) -> impl IntoView {
+    let percent = move || progress.get().map_or(0.0, |progress| progress.progress.clamp(0.0, 1.0) * 100.0);
+    let stage = move || {
+        match progress.get().as_ref().map(|progress| progress.stage.as_str()) {
+            None | Some("loading") => "Loading the prover and proving key...",
+            Some("synthesizing") => "Building the circuit...",
+            Some("proving") => "Generating zero-knowledge proof...",
+            Some(_) => "Finishing up...",
+        }
+    };
+
+    view! {
+        <div class="proof-progress">
+            <div class="progress-indicator">
+                <div class="spinner"></div>
+                <span>{stage}</span>
+            </div>
+            <div class="progress-bar">
+                <div class="progress-fill" style:width=move || format!("{:.0}%", percent())></div>
+            </div>
+            <p class="progress-text">
+                "The proof is generated in the background, so you can keep using this page. Please do not close this tab."
+            </p>
+            <button type="button" class="btn btn-secondary" on:click=move |_| on_cancel.call(())>
+                "Cancel"
+            </button>
+        </div>
+    }
+}
+
+#[component]
+pub fn WithdrawalReview(preview: WithdrawalPreview, recipient: String, denomination: u128) -> impl IntoView {
+    let proof = preview.proof.clone();
+    let proof_for_download = preview.proof.clone();
+    let nullifier_hash_preview = format!("{}...", &proof.nullifier_hash[..proof.nullifier_hash.len().min(18)]);
+    let proof_size = proof.proof.trim_start_matches("0x").len() / 2;
+    let relayer_fee = format_amount(preview.fee.relayer_fee());
+    let receives = preview
+        .fee
+        .recipient_amount(denomination)
+        .map(format_amount)
+        .unwrap_or_else(|| "-".to_string());
+    let submitted_by = match &preview.fee.relayer {
+        Some(relayer) => format!("Relayer {}", relayer.url),
+        None => format!("Your wallet, at {} sat/vB", preview.fee.fee_rate),
+    };
+
+    view! {
+        <div class="withdrawal-review">
+            <h4>"Review Withdrawal"</h4>
+            <div class="detail-grid">
+                <div class="detail-row">
+                    <span class="detail-label">"Recipient:"</span>
+                    <span class="detail-value monospace">{recipient}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Note Amount:"</span>
+                    <span class="detail-value">{format_amount(denomination)}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Relayer Fee:"</span>
+                    <span class="detail-value">{relayer_fee}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Recipient Receives:"</span>
+                    <span class="detail-value">{receives}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Submitted By:"</span>
+                    <span class="detail-value">{submitted_by}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Nullifier Hash:"</span>
+                    <span class="detail-value monospace">{nullifier_hash_preview}</span>
+                </div>
+                <div class="detail-row">
+                    <span class="detail-label">"Proof Size:"</span>
+                    <span class="detail-value">{proof_size}" bytes"</span>
+                </div>
+            </div>
+
+            {preview.transaction.map(|transaction| view! {
+                <div class="proof-display">
+                    <label>"Unsigned Transaction:"</label>
+                    <textarea class="proof-textarea" readonly prop:value=transaction.tx_hex></textarea>
+                </div>
+            })}
+
+            <div class="proof-actions">
+                <button
+                    type="button"
+                    class="btn btn-secondary"
+                    on:click=move |_| {
+                        let proof_json = serde_json::to_string_pretty(&proof).unwrap_or_default();
+                        copy_to_clipboard(&proof_json);
+                    }
+                >
+                    "Copy Proof"
+                </button>
+                <button
+                    type="button"
+                    class="btn btn-secondary"
+                    on:click=move |_| {
+                        let proof_json = serde_json::to_string_pretty(&proof_for_download).unwrap_or_default();
+                        download_as_file(&proof_json, &format!("zkane-withdrawal-proof-{}.json", proof_for_download.nullifier_hash));
+                    }
+                >
+                    "Download Proof"
+                </button>
+            </div>
+        </div>
+    }
+}
+
+/// The submitted withdrawal. A relayed one shows the relay job's progress
+/// until it is broadcast or fails.
+#[component]
+pub fn WithdrawalSubmitted(submission: WithdrawalSubmission) -> impl IntoView {
+    let (job_status, set_job_status) = create_signal(None::<RelayJobStatus>);
+    if let WithdrawalSubmission::Relayed { relayer, job_id } = &submission {
+        poll_relay_job(use_frontend_provider(), relayer.clone(), *job_id, set_job_status);
+    }
+
+    let (title, detail) = match submission {
+        WithdrawalSubmission::Broadcast(response) => (
+            "Withdrawal Broadcast",
+            format!("Transaction {} is waiting for confirmation.", response.txid),
+        ),
+        WithdrawalSubmission::Relayed { relayer, job_id } => (
+            "Withdrawal Sent to Relayer",
+            format!("{} queued it as job {} and will broadcast it shortly.", relayer, job_id),
+        ),
+    };
+
+    view! {
+        <div class="success-result">
+            <div class="success-header">
+                <span class="success-icon">"✅"</span>
+                <h4>{title}</h4>
+            </div>
+            <p>{detail}</p>
+            {move || job_status.get().map(|status| view! {
+                <p class="relay-job-status" class:failed=matches!(status, RelayJobStatus::Failed { .. })>
+                    {relay_job_label(&status)}
+                </p>
+            })}
+        </div>
+    }
+}
+
+/// Poll a relay job until it is broadcast or fails, or the component
+/// showing it is unmounted.
+fn poll_relay_job(
+    provider: Rc<dyn FrontendProvider>,
+    relayer: String,
+    job_id: u64,
+    set_status: WriteSignal<Option<RelayJobStatus>>,
+) {
+    spawn_local(async move {
+        loop {
+            match provider.get_relay_job(&relayer, job_id).await {
+                Ok(status) => {
+                    let done = status.is_final();
+                    // The signal is disposed once the component unmounts
+                    if set_status.try_set(Some(status)).is_some() || done {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    log::debug!("Relay job {} of {} not checked: {}", job_id, relayer, e);
+                    if set_status.try_update(|_| ()).is_none() {
+                        return;
+                    }
+                }
+            }
+            gloo_timers::future::TimeoutFuture::new(RELAY_POLL_MS).await;
+        }
+    });
+}
+
+/// Describe the progress of a relay job
+pub fn relay_job_label(status: &RelayJobStatus) -> String {
+    match status {
+        RelayJobStatus::Queued => "Waiting in the relayer's queue...".to_string(),
+        RelayJobStatus::Broadcasting => "The relayer is broadcasting the withdrawal...".to_string(),
+        RelayJobStatus::Broadcast { txid } => format!("Broadcast in transaction {}, waiting for the pool...", txid),
+        RelayJobStatus::Confirmed { txid } => format!("Withdrawn in transaction {}", txid),
+        RelayJobStatus::Failed { reason } => format!("The relayer failed the withdrawal: {}", reason),
+    }
+}
+
+/// Describe a quoted confirmation time
+pub fn format_confirmation(secs: u64) -> String {
+    match secs {
+        0 => "Confirmation time not quoted".to_string(),
+        secs => format!("Confirms in about {} min", secs.div_ceil(60)),
+    }
+}
+
+/// Format an amount of the pool asset, in whole units
+fn format_amount(amount: u128) -> String {
+    zkane_common::format_units(amount, 8)
+}
+
 // Utility functions
 fn validate_bitcoin_address(address: &str) -> bool {
     // Basic validation - in production, use a proper Bitcoin address validator
@@ -8,6 +8,8 @@ use wasm_bindgen::prelude::*;
 
 mod app;
 pub mod components;
+pub mod provider;
+pub mod prover_worker;
 pub mod services;
 pub mod types;
 mod utils;
@@ -20,6 +22,8 @@ mod lib_testable;
 // Export main modules
 pub use app::*;
 pub use components::*;
+pub use provider::*;
+pub use prover_worker::*;
 pub use services::*;
 pub use types::*;
 pub use utils::*;
@@ -36,6 +40,11 @@ pub fn main() {
     _ = console_log::init_with_level(log::Level::Debug);
     
     log::info!("🚀 ZKane Privacy Pool application starting...");
+
+    // Notes drawn from a broken random source could be guessed; don't start
+    if let Err(error) = zkane_common::check_entropy() {
+        panic!("{}", error.coded_message());
+    }
     
     mount_to_body(|| {
         view! { <App/> }
@@ -0,0 +1,253 @@
+//! Withdrawal proving in a web worker
+//!
+//! Proving a withdrawal takes long enough to freeze the page, so the
+//! [`ProofWorker`] runs it in `prover-worker.js`, a module worker wrapping
+//! the zkane-wasm prover, and relays the prover's progress back to the page.
+//!
+//! The worker checks each proof against the withdrawal circuit's verifying
+//! key before handing it back, so a bad proof is never broadcast or sent to a
+//! relayer.
+//!
+//! Each worker proves one withdrawal. Cancelling terminates the worker,
+//! since proving blocks the worker's thread until it is done.
+//!
+//! The worker hands back the witness it synthesizes before proving starts.
+//! The page keeps it as a [`CachedWitness`] in the note vault and passes it
+//! to the next worker, so a retry of a proof that failed partway skips
+//! synthesizing the witness again.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ErrorEvent, MessageEvent, Worker, WorkerOptions, WorkerType};
+use zeroize::{Zeroize, Zeroizing};
+use crate::types::{DepositNote, ProofProgress, TxOutput, ZKaneError};
+
+/// URL the worker script is served at
+pub const WORKER_SCRIPT_URL: &str = "/prover-worker.js";
+
+/// URL the withdrawal circuit's proving key is served at
+pub const PROVING_KEY_URL: &str = "/keys/withdrawal.pk";
+
+/// URL the withdrawal circuit's verifying key is served at
+pub const VERIFYING_KEY_URL: &str = "/keys/withdrawal.vk";
+
+/// Message a cancelled proof is rejected with, as the prover reports it
+const CANCELLED: &str = "Proof generation cancelled";
+
+/// A withdrawal to prove, as posted to the worker
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProveRequest {
+    pub proving_key_url: String,
+    pub verifying_key_url: String,
+    /// The note in the `zkane-common` JSON encoding, see [`prover_note_json`]
+    pub note: String,
+    /// Recipient outputs with hex scriptPubKeys, as JSON
+    pub outputs: String,
+    pub relayer_output_hash: String,
+    /// Relayer fee as a decimal string, since it may not fit a JS number
+    pub fee: String,
+    /// Witness cached by an earlier attempt, used if it is for this
+    /// withdrawal
+    pub witness: Option<CachedWitness>,
+}
+
+impl Drop for ProveRequest {
+    fn drop(&mut self) {
+        self.note.zeroize();
+    }
+}
+
+/// A witness synthesized by the worker, keyed by the hash of the public
+/// inputs of the withdrawal it is for.
+///
+/// The witness holds the note's secrets, so it is kept in the note vault and
+/// removed once its proof is done.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedWitness {
+    pub key: String,
+    /// The encoded witness, hex encoded
+    pub witness: String,
+}
+
+impl Drop for CachedWitness {
+    fn drop(&mut self) {
+        self.witness.zeroize();
+    }
+}
+
+/// A message posted back by the worker
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WorkerMessage {
+    Progress { stage: String, progress: f64 },
+    /// The witness synthesized, sent before proving starts
+    Witness(CachedWitness),
+    /// The hex proof and the recipients hash it commits to
+    #[serde(rename_all = "camelCase")]
+    Done { proof: String, recipients_hash: String },
+    Error { message: String },
+}
+
+/// A proof produced by the worker
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerProof {
+    pub proof: String,
+    pub recipients_hash: String,
+}
+
+/// Encode a note the way the zkane-wasm prover reads it, the serde JSON of
+/// a `zkane_common::DepositNote` with its 32-byte values as `0x` hex.
+pub fn prover_note_json(note: &DepositNote) -> Result<Zeroizing<String>, ZKaneError> {
+    #[derive(Serialize)]
+    struct ProverNote<'a> {
+        secret: &'a str,
+        nullifier: &'a str,
+        commitment: &'a str,
+        asset_id: &'a crate::types::AlkaneId,
+        denomination: u128,
+        leaf_index: u32,
+    }
+
+    let normalize = |value_hex: &str| -> Result<Zeroizing<String>, ZKaneError> {
+        let bytes = Zeroizing::new(
+            hex::decode(value_hex.trim_start_matches("0x")).map_err(|_| ZKaneError::InvalidDepositNote)?,
+        );
+        if bytes.len() != 32 {
+            return Err(ZKaneError::InvalidDepositNote);
+        }
+        Ok(Zeroizing::new(format!("0x{}", hex::encode(&*bytes))))
+    };
+    let (secret, nullifier, commitment) =
+        (normalize(&note.secret)?, normalize(&note.nullifier)?, normalize(&note.commitment)?);
+
+    serde_json::to_string(&ProverNote {
+        secret: &secret,
+        nullifier: &nullifier,
+        commitment: &commitment,
+        asset_id: &note.asset_id,
+        denomination: note.denomination,
+        leaf_index: note.leaf_index,
+    })
+    .map(Zeroizing::new)
+    .map_err(|e| ZKaneError::SerializationError(e.to_string()))
+}
+
+/// Encode recipient outputs for the worker's `recipientsHash`
+pub fn prover_outputs_json(outputs: &[TxOutput]) -> Result<String, ZKaneError> {
+    let outputs: Vec<_> = outputs
+        .iter()
+        .map(|output| serde_json::json!({ "value": output.value as u64, "script_pubkey": output.script_pubkey }))
+        .collect();
+    serde_json::to_string(&outputs).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+}
+
+type Handlers = (Closure<dyn FnMut(MessageEvent)>, Closure<dyn FnMut(ErrorEvent)>);
+
+/// A dedicated worker proving a withdrawal off the UI thread.
+pub struct ProofWorker {
+    worker: Worker,
+    /// Rejects the pending proof, so cancelling settles it
+    reject: RefCell<Option<js_sys::Function>>,
+    handlers: RefCell<Option<Handlers>>,
+}
+
+impl ProofWorker {
+    /// Start a worker running [`WORKER_SCRIPT_URL`].
+    pub fn new() -> Result<Self, ZKaneError> {
+        let options = WorkerOptions::new();
+        options.set_type(WorkerType::Module);
+        let worker = Worker::new_with_options(WORKER_SCRIPT_URL, &options)
+            .map_err(|e| ZKaneError::WasmError(format!("Failed to start prover worker: {:?}", e)))?;
+        Ok(Self {
+            worker,
+            reject: RefCell::new(None),
+            handlers: RefCell::new(None),
+        })
+    }
+
+    /// Prove a withdrawal, calling `on_progress` as the prover advances and
+    /// `on_witness` with the witness synthesized for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ZKaneError::ProofGenerationFailed`] if the worker fails or
+    /// the proof is cancelled.
+    pub async fn prove(
+        &self,
+        request: &ProveRequest,
+        on_progress: Rc<dyn Fn(ProofProgress)>,
+        on_witness: Rc<dyn Fn(CachedWitness)>,
+    ) -> Result<WorkerProof, ZKaneError> {
+        let message = serde_wasm_bindgen::to_value(request).map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            *self.reject.borrow_mut() = Some(reject.clone());
+
+            let on_progress = on_progress.clone();
+            let on_witness = on_witness.clone();
+            let reject_message = reject.clone();
+            let onmessage = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                match serde_wasm_bindgen::from_value::<WorkerMessage>(event.data()) {
+                    Ok(WorkerMessage::Progress { stage, progress }) => on_progress(ProofProgress { stage, progress }),
+                    Ok(WorkerMessage::Witness(witness)) => on_witness(witness),
+                    Ok(WorkerMessage::Done { proof, recipients_hash }) => {
+                        let result = js_sys::Array::of2(&JsValue::from_str(&proof), &JsValue::from_str(&recipients_hash));
+                        let _ = resolve.call1(&JsValue::NULL, &result);
+                    }
+                    Ok(WorkerMessage::Error { message }) => {
+                        let _ = reject_message.call1(&JsValue::NULL, &JsValue::from_str(&message));
+                    }
+                    Err(e) => {
+                        let message = format!("Unexpected prover worker message: {}", e);
+                        let _ = reject_message.call1(&JsValue::NULL, &JsValue::from_str(&message));
+                    }
+                }
+            });
+            let onerror = Closure::<dyn FnMut(ErrorEvent)>::new(move |event: ErrorEvent| {
+                let _ = reject.call1(&JsValue::NULL, &JsValue::from_str(&event.message()));
+            });
+
+            self.worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+            self.worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+            *self.handlers.borrow_mut() = Some((onmessage, onerror));
+        });
+
+        self.worker
+            .post_message(&message)
+            .map_err(|e| ZKaneError::WasmError(format!("Failed to post to prover worker: {:?}", e)))?;
+        let result = JsFuture::from(promise).await;
+
+        self.reject.borrow_mut().take();
+        self.worker.set_onmessage(None);
+        self.worker.set_onerror(None);
+        self.handlers.borrow_mut().take();
+
+        let result = result.map_err(|e| {
+            ZKaneError::ProofGenerationFailed(e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+        })?;
+        let result: js_sys::Array = result.unchecked_into();
+        Ok(WorkerProof {
+            proof: result.get(0).as_string().unwrap_or_default(),
+            recipients_hash: result.get(1).as_string().unwrap_or_default(),
+        })
+    }
+
+    /// Cancel the pending proof and stop the worker.
+    pub fn cancel(&self) {
+        self.worker.terminate();
+        if let Some(reject) = self.reject.borrow_mut().take() {
+            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str(CANCELLED));
+        }
+    }
+}
+
+impl Drop for ProofWorker {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
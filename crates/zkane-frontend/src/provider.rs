@@ -0,0 +1,620 @@
+//! Injectable backend for the frontend components
+//!
+//! Components reach the network and the prover through a [`FrontendProvider`]
+//! taken from the Leptos context, instead of calling the services directly.
+//! The app provides a [`WebProvider`], backed by the connected wallet's
+//! esplora/RPC endpoints and the WASM prover; component tests provide a
+//! [`MockProvider`] so they can render headless, without either.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use async_trait::async_trait;
+use leptos::*;
+use crate::prover_worker::{
+    prover_note_json, prover_outputs_json, CachedWitness, ProofWorker, ProveRequest, PROVING_KEY_URL,
+    VERIFYING_KEY_URL,
+};
+use crate::services::{AlkanesService, RelayerService, SecureStorageService, WalletService, ZKaneService};
+use crate::types::*;
+use crate::wasm_bindings::{
+    generate_nullifier_hash_from_nullifier, relayed_withdrawal_public_inputs, withdrawal_public_inputs,
+};
+
+/// The operations the deposit, withdraw and pool components need.
+#[async_trait(?Send)]
+pub trait FrontendProvider {
+    /// Get the connected user's alkane balances
+    async fn get_user_assets(&self) -> Result<Vec<AssetBalance>, ZKaneError>;
+
+    /// Get the known privacy pools
+    async fn get_privacy_pools(&self) -> Result<Vec<PoolInfo>, ZKaneError>;
+
+    /// Create a new deposit note
+    async fn create_deposit_note(&self, asset_id: AlkaneId, amount: u128) -> Result<DepositNote, ZKaneError>;
+
+    /// Get a note's Merkle path to its pool's current root, and whether the
+    /// note was already withdrawn
+    async fn sync_pool(&self, note: &DepositNote) -> Result<PoolSyncState, ZKaneError>;
+
+    /// Get the ways a note can be withdrawn: self-relayed at several network
+    /// fee rates, or through a relayer
+    async fn get_fee_options(&self, note: &DepositNote) -> Result<Vec<FeeOption>, ZKaneError>;
+
+    /// Prove a withdrawal of a note to `recipient_outputs`, calling
+    /// `on_progress` as the prover advances
+    async fn prove_withdrawal(
+        &self,
+        deposit_note: &DepositNote,
+        recipient_outputs: &[TxOutput],
+        merkle_path: &MerklePath,
+        fee: &FeeOption,
+        on_progress: Rc<dyn Fn(ProofProgress)>,
+    ) -> Result<WithdrawalProof, ZKaneError>;
+
+    /// Cancel the withdrawal proof in progress, if any
+    fn cancel_withdrawal_proof(&self);
+
+    /// Prepare a proven withdrawal of a note for review. Self-relayed
+    /// withdrawals get the wallet transaction paying `recipient`.
+    async fn preview_withdrawal(
+        &self,
+        deposit_note: &DepositNote,
+        proof: WithdrawalProof,
+        merkle_path: &MerklePath,
+        recipient: &str,
+        recipient_outputs: Vec<TxOutput>,
+        fee: FeeOption,
+    ) -> Result<WithdrawalPreview, ZKaneError>;
+
+    /// Broadcast a previewed withdrawal, or hand it to its relayer
+    async fn submit_withdrawal(&self, preview: &WithdrawalPreview) -> Result<WithdrawalSubmission, ZKaneError>;
+
+    /// Get the progress of a withdrawal handed to a relayer
+    async fn get_relay_job(&self, relayer: &str, job_id: u64) -> Result<RelayJobStatus, ZKaneError>;
+
+    /// Build the transaction depositing a note into its pool
+    async fn create_deposit_transaction(&self, note: &DepositNote) -> Result<TransactionRequest, ZKaneError>;
+
+    /// Sign and broadcast a transaction
+    async fn broadcast_transaction(&self, tx_request: &TransactionRequest) -> Result<TransactionResponse, ZKaneError>;
+
+    /// Get whether a transaction has confirmed, and in which block
+    async fn get_transaction_status(&self, txid: &str) -> Result<TransactionResponse, ZKaneError>;
+}
+
+/// The provider components use, as stored in the Leptos context.
+#[derive(Clone)]
+pub struct ProviderContext(pub Rc<dyn FrontendProvider>);
+
+/// Make `provider` available to child components.
+pub fn provide_frontend_provider(provider: impl FrontendProvider + 'static) {
+    provide_context(ProviderContext(Rc::new(provider)));
+}
+
+/// Get the provider from the context.
+///
+/// # Panics
+///
+/// Panics if no provider was provided, like [`expect_context`].
+pub fn use_frontend_provider() -> Rc<dyn FrontendProvider> {
+    expect_context::<ProviderContext>().0
+}
+
+fn wallet_not_connected() -> ZKaneError {
+    ZKaneError::WasmError("Wallet not connected".to_string())
+}
+
+/// Confirmation targets of the self-relayed fee options, in blocks
+const FEE_TARGETS: [(&str, u32); 3] = [("Fast", 1), ("Normal", 6), ("Slow", 144)];
+
+/// Provider backed by the connected browser wallet and the WASM prover.
+///
+/// Withdrawals are proven in a [`ProofWorker`], so the page stays
+/// responsive while proving. With a witness cache, the witness of a proof is
+/// kept in the note vault until the proof is done, so retrying a proof that
+/// failed partway doesn't synthesize it again.
+#[derive(Clone)]
+pub struct WebProvider {
+    zkane_service: ZKaneService,
+    alkanes_service: AlkanesService,
+    wallet_service: WalletService,
+    relayer_service: RelayerService,
+    relayers: Vec<String>,
+    relayer_list_url: Option<String>,
+    default_fee_rate: u64,
+    proof_worker: Rc<RefCell<Option<Rc<ProofWorker>>>>,
+    witness_cache: Option<SecureStorageService>,
+}
+
+impl WebProvider {
+    pub fn new(zkane_service: ZKaneService, alkanes_service: AlkanesService, wallet_service: WalletService) -> Self {
+        Self {
+            zkane_service,
+            alkanes_service,
+            wallet_service,
+            relayer_service: RelayerService::new(),
+            relayers: Vec::new(),
+            relayer_list_url: None,
+            default_fee_rate: AppConfig::default().default_fee_rate,
+            proof_worker: Rc::new(RefCell::new(None)),
+            witness_cache: None,
+        }
+    }
+
+    /// Keep the witnesses of proofs in progress in `secure_storage`.
+    pub fn with_witness_cache(mut self, secure_storage: SecureStorageService) -> Self {
+        self.witness_cache = Some(secure_storage);
+        self
+    }
+
+    /// Offer withdrawals through the relayers at `relayers`.
+    pub fn with_relayers(mut self, relayers: Vec<String>) -> Self {
+        self.relayers = relayers;
+        self
+    }
+
+    /// Also offer the relayers listed at `list_url`, fetched each time fees
+    /// are quoted.
+    pub fn with_relayer_list(mut self, list_url: Option<String>) -> Self {
+        self.relayer_list_url = list_url;
+        self
+    }
+
+    /// Get the configured relayers and those of the relayer list, without
+    /// duplicates. A list that can't be fetched leaves the configured ones.
+    async fn relayer_urls(&self) -> Vec<String> {
+        let mut urls = self.relayers.clone();
+        if let Some(list_url) = &self.relayer_list_url {
+            match self.relayer_service.get_relayers(list_url).await {
+                Ok(listed) => urls.extend(listed),
+                Err(e) => log::warn!("Relayer list {} unavailable: {}", list_url, e),
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        urls.retain(|url| seen.insert(url.trim_end_matches('/').to_string()));
+        urls
+    }
+
+    /// Use `fee_rate` when the network's fee estimates are unavailable.
+    pub fn with_default_fee_rate(mut self, fee_rate: u64) -> Self {
+        self.default_fee_rate = fee_rate;
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl FrontendProvider for WebProvider {
+    async fn get_user_assets(&self) -> Result<Vec<AssetBalance>, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        self.alkanes_service.get_user_assets(&wallet_provider, "user_address").await
+    }
+
+    async fn get_privacy_pools(&self) -> Result<Vec<PoolInfo>, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        self.alkanes_service.get_privacy_pools(&wallet_provider).await
+    }
+
+    async fn create_deposit_note(&self, asset_id: AlkaneId, amount: u128) -> Result<DepositNote, ZKaneError> {
+        if self.wallet_service.connected_wallet.get().is_none() {
+            return Err(wallet_not_connected());
+        }
+        self.zkane_service.create_deposit(asset_id, amount).await
+    }
+
+    async fn sync_pool(&self, note: &DepositNote) -> Result<PoolSyncState, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        let pool_id = self.zkane_service.generate_pool_id(&note.asset_id, note.denomination)?;
+        let nullifier_hash = generate_nullifier_hash_from_nullifier(note.nullifier.trim_start_matches("0x"))
+            .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+        self.alkanes_service
+            .get_pool_state(&wallet_provider, &pool_id, &note.commitment, &nullifier_hash)
+            .await
+    }
+
+    async fn get_fee_options(&self, note: &DepositNote) -> Result<Vec<FeeOption>, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+
+        // Without estimates the wallet can still broadcast at the default rate
+        let estimates = self.alkanes_service.get_fee_estimates(&wallet_provider).await.unwrap_or_default();
+        let rate_for = |target: u32| {
+            estimates
+                .iter()
+                .filter(|(estimate_target, _)| *estimate_target <= target)
+                .max_by_key(|(estimate_target, _)| *estimate_target)
+                .map(|(_, rate)| rate.ceil().max(1.0) as u64)
+                .unwrap_or(self.default_fee_rate)
+        };
+        let mut options: Vec<FeeOption> = FEE_TARGETS
+            .iter()
+            .map(|(label, target)| FeeOption {
+                label: label.to_string(),
+                fee_rate: rate_for(*target),
+                relayer: None,
+            })
+            .collect();
+        options.dedup_by_key(|option| option.fee_rate);
+
+        // Relayers that are down or serve another pool are left out
+        let mut quotes = Vec::new();
+        for url in self.relayer_urls().await {
+            match self.relayer_service.get_quote(&url).await {
+                Ok(quote) if quote.serves(note.denomination) => quotes.push(quote),
+                Ok(_) => log::info!("Relayer {} does not serve this pool", url),
+                Err(e) => log::warn!("Relayer {} unavailable: {}", url, e),
+            }
+        }
+        quotes.sort_by_key(|quote| (quote.fee, quote.estimated_confirmation_secs, quote.pending_jobs));
+        options.extend(quotes.into_iter().map(|quote| FeeOption {
+            label: format!("Relayer {}", quote.url),
+            fee_rate: 0,
+            relayer: Some(quote),
+        }));
+        Ok(options)
+    }
+
+    async fn prove_withdrawal(
+        &self,
+        deposit_note: &DepositNote,
+        recipient_outputs: &[TxOutput],
+        merkle_path: &MerklePath,
+        fee: &FeeOption,
+        on_progress: Rc<dyn Fn(ProofProgress)>,
+    ) -> Result<WithdrawalProof, ZKaneError> {
+        if self.wallet_service.connected_wallet.get().is_none() {
+            return Err(wallet_not_connected());
+        }
+        // A cache that can't be read, such as a locked vault, only costs the
+        // retry its head start
+        let witness = match &self.witness_cache {
+            Some(cache) => cache.load_witness().await.unwrap_or_else(|e| {
+                log::debug!("Cached witness not available: {}", e);
+                None
+            }),
+            None => None,
+        };
+        let request = ProveRequest {
+            proving_key_url: PROVING_KEY_URL.to_string(),
+            verifying_key_url: VERIFYING_KEY_URL.to_string(),
+            note: prover_note_json(deposit_note)?.to_string(),
+            outputs: prover_outputs_json(recipient_outputs)?,
+            relayer_output_hash: fee.relayer_output_hash(),
+            fee: fee.relayer_fee().to_string(),
+            witness,
+        };
+        let cache = self.witness_cache.clone();
+        let on_witness = Rc::new(move |witness: CachedWitness| {
+            let Some(cache) = cache.clone() else { return };
+            spawn_local(async move {
+                if let Err(e) = cache.save_witness(&witness).await {
+                    log::debug!("Witness not cached: {}", e);
+                }
+            });
+        });
+
+        let worker = Rc::new(ProofWorker::new()?);
+        *self.proof_worker.borrow_mut() = Some(worker.clone());
+        let result = worker.prove(&request, on_progress, on_witness).await;
+        self.proof_worker.borrow_mut().take();
+        let proved = result?;
+        if let Some(cache) = &self.witness_cache {
+            if let Err(e) = cache.clear_witness().await {
+                log::warn!("Failed to remove the cached witness: {}", e);
+            }
+        }
+
+        let nullifier_hash = generate_nullifier_hash_from_nullifier(deposit_note.nullifier.trim_start_matches("0x"))
+            .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+        let public_inputs = match fee.relayer {
+            Some(_) => relayed_withdrawal_public_inputs(
+                &merkle_path.root,
+                &nullifier_hash,
+                &proved.recipients_hash,
+                &fee.relayer_output_hash(),
+                &fee.relayer_fee().to_string(),
+            ),
+            None => withdrawal_public_inputs(&merkle_path.root, &nullifier_hash, &proved.recipients_hash),
+        }
+        .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+        Ok(WithdrawalProof {
+            proof: proved.proof,
+            merkle_root: merkle_path.root.clone(),
+            nullifier_hash,
+            outputs_hash: proved.recipients_hash,
+            public_inputs,
+            relayer_output_hash: fee.relayer_output_hash(),
+            fee: fee.relayer_fee(),
+        })
+    }
+
+    fn cancel_withdrawal_proof(&self) {
+        if let Some(worker) = self.proof_worker.borrow_mut().take() {
+            worker.cancel();
+        }
+    }
+
+    async fn preview_withdrawal(
+        &self,
+        deposit_note: &DepositNote,
+        proof: WithdrawalProof,
+        merkle_path: &MerklePath,
+        recipient: &str,
+        recipient_outputs: Vec<TxOutput>,
+        fee: FeeOption,
+    ) -> Result<WithdrawalPreview, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        let transaction = match fee.relayer {
+            Some(_) => None,
+            None => {
+                let amount = recipient_outputs.iter().map(|output| output.value).sum();
+                Some(
+                    self.alkanes_service
+                        .create_withdrawal_transaction(
+                            &wallet_provider,
+                            deposit_note,
+                            &proof,
+                            merkle_path,
+                            recipient,
+                            amount,
+                            fee.fee_rate,
+                        )
+                        .await?,
+                )
+            }
+        };
+        Ok(WithdrawalPreview {
+            proof,
+            outputs: recipient_outputs,
+            fee,
+            merkle_path: merkle_path.clone(),
+            commitment: deposit_note.commitment.clone(),
+            transaction,
+        })
+    }
+
+    async fn submit_withdrawal(&self, preview: &WithdrawalPreview) -> Result<WithdrawalSubmission, ZKaneError> {
+        match (&preview.fee.relayer, &preview.transaction) {
+            (Some(relayer), _) => {
+                let job_id = self.relayer_service.relay(&relayer.url, preview).await?;
+                Ok(WithdrawalSubmission::Relayed {
+                    relayer: relayer.url.clone(),
+                    job_id,
+                })
+            }
+            (None, Some(transaction)) => {
+                let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+                let response = self.alkanes_service.broadcast_transaction(&wallet_provider, transaction).await?;
+                Ok(WithdrawalSubmission::Broadcast(response))
+            }
+            (None, None) => Err(ZKaneError::TransactionFailed("Withdrawal has no transaction".to_string())),
+        }
+    }
+
+    async fn get_relay_job(&self, relayer: &str, job_id: u64) -> Result<RelayJobStatus, ZKaneError> {
+        self.relayer_service.get_job(relayer, job_id).await
+    }
+
+    async fn create_deposit_transaction(&self, note: &DepositNote) -> Result<TransactionRequest, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        let pool_id = self.zkane_service.generate_pool_id(&note.asset_id, note.denomination)?;
+        self.alkanes_service
+            .create_deposit_transaction(&wallet_provider, &note.asset_id, note.denomination, &pool_id, &note.commitment)
+            .await
+    }
+
+    async fn broadcast_transaction(&self, tx_request: &TransactionRequest) -> Result<TransactionResponse, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        self.alkanes_service.broadcast_transaction(&wallet_provider, tx_request).await
+    }
+
+    async fn get_transaction_status(&self, txid: &str) -> Result<TransactionResponse, ZKaneError> {
+        let wallet_provider = self.wallet_service.connected_wallet.get().ok_or_else(wallet_not_connected)?;
+        self.alkanes_service.get_transaction_status(&wallet_provider, txid).await
+    }
+}
+
+/// Provider serving canned data, for headless component tests.
+///
+/// Notes and proofs are fixed placeholders: nothing is proven or broadcast.
+#[derive(Clone, Debug, Default)]
+pub struct MockProvider {
+    pub assets: Vec<AssetBalance>,
+    pub pools: Vec<PoolInfo>,
+    /// Error every call returns instead of the canned data
+    pub error: Option<String>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_assets(mut self, assets: Vec<AssetBalance>) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn with_pools(mut self, pools: Vec<PoolInfo>) -> Self {
+        self.pools = pools;
+        self
+    }
+
+    /// Make every call fail with `message`.
+    pub fn failing(mut self, message: &str) -> Self {
+        self.error = Some(message.to_string());
+        self
+    }
+
+    fn check(&self) -> Result<(), ZKaneError> {
+        match &self.error {
+            Some(message) => Err(ZKaneError::NetworkError(message.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FrontendProvider for MockProvider {
+    async fn get_user_assets(&self) -> Result<Vec<AssetBalance>, ZKaneError> {
+        self.check()?;
+        Ok(self.assets.clone())
+    }
+
+    async fn get_privacy_pools(&self) -> Result<Vec<PoolInfo>, ZKaneError> {
+        self.check()?;
+        Ok(self.pools.clone())
+    }
+
+    async fn create_deposit_note(&self, asset_id: AlkaneId, amount: u128) -> Result<DepositNote, ZKaneError> {
+        self.check()?;
+        Ok(DepositNote {
+            secret: format!("0x{}", "11".repeat(32)),
+            nullifier: format!("0x{}", "22".repeat(32)),
+            commitment: format!("0x{}", "33".repeat(32)),
+            asset_id,
+            denomination: amount,
+            leaf_index: 0,
+            created_at: 0.0,
+        })
+    }
+
+    async fn sync_pool(&self, note: &DepositNote) -> Result<PoolSyncState, ZKaneError> {
+        self.check()?;
+        Ok(PoolSyncState {
+            merkle_path: MerklePath {
+                root: format!("0x{}", "77".repeat(32)),
+                elements: vec![format!("0x{}", "88".repeat(32)); 20],
+                indices: vec![false; 20],
+                leaf_index: note.leaf_index,
+            },
+            deposit_count: self.pools.first().map_or(1, |pool| pool.total_deposits),
+            nullifier_spent: false,
+        })
+    }
+
+    async fn get_fee_options(&self, _note: &DepositNote) -> Result<Vec<FeeOption>, ZKaneError> {
+        self.check()?;
+        Ok(vec![
+            FeeOption {
+                label: "Normal".to_string(),
+                fee_rate: 10,
+                relayer: None,
+            },
+            FeeOption {
+                label: "Relayer".to_string(),
+                fee_rate: 0,
+                relayer: Some(RelayerQuote {
+                    url: "https://relayer.test".to_string(),
+                    fee: 1_000,
+                    fee_output_hash: "99".repeat(32),
+                    min_denomination: 0,
+                    max_denomination: u128::MAX,
+                    estimated_confirmation_secs: 600,
+                    pending_jobs: 0,
+                }),
+            },
+        ])
+    }
+
+    async fn prove_withdrawal(
+        &self,
+        _deposit_note: &DepositNote,
+        _recipient_outputs: &[TxOutput],
+        merkle_path: &MerklePath,
+        fee: &FeeOption,
+        on_progress: Rc<dyn Fn(ProofProgress)>,
+    ) -> Result<WithdrawalProof, ZKaneError> {
+        self.check()?;
+        on_progress(ProofProgress {
+            stage: "done".to_string(),
+            progress: 1.0,
+        });
+        let nullifier_hash = format!("0x{}", "44".repeat(32));
+        let outputs_hash = format!("0x{}", "55".repeat(32));
+        Ok(WithdrawalProof {
+            proof: format!("0x{}", "66".repeat(192)),
+            merkle_root: merkle_path.root.clone(),
+            nullifier_hash: nullifier_hash.clone(),
+            outputs_hash: outputs_hash.clone(),
+            public_inputs: vec![merkle_path.root.clone(), nullifier_hash, outputs_hash],
+            relayer_output_hash: fee.relayer_output_hash(),
+            fee: fee.relayer_fee(),
+        })
+    }
+
+    fn cancel_withdrawal_proof(&self) {}
+
+    async fn preview_withdrawal(
+        &self,
+        deposit_note: &DepositNote,
+        proof: WithdrawalProof,
+        merkle_path: &MerklePath,
+        _recipient: &str,
+        recipient_outputs: Vec<TxOutput>,
+        fee: FeeOption,
+    ) -> Result<WithdrawalPreview, ZKaneError> {
+        self.check()?;
+        let transaction = fee.relayer.is_none().then(|| TransactionRequest {
+            tx_hex: "00".to_string(),
+            witness_data: proof.proof.clone(),
+            fee_rate: fee.fee_rate,
+        });
+        Ok(WithdrawalPreview {
+            proof,
+            outputs: recipient_outputs,
+            fee,
+            merkle_path: merkle_path.clone(),
+            commitment: deposit_note.commitment.clone(),
+            transaction,
+        })
+    }
+
+    async fn submit_withdrawal(&self, preview: &WithdrawalPreview) -> Result<WithdrawalSubmission, ZKaneError> {
+        self.check()?;
+        Ok(match &preview.fee.relayer {
+            Some(relayer) => WithdrawalSubmission::Relayed {
+                relayer: relayer.url.clone(),
+                job_id: 1,
+            },
+            None => WithdrawalSubmission::Broadcast(TransactionResponse {
+                txid: "ab".repeat(32),
+                status: TransactionStatus::Pending,
+                confirmations: 0,
+                block_height: None,
+            }),
+        })
+    }
+
+    async fn get_relay_job(&self, _relayer: &str, _job_id: u64) -> Result<RelayJobStatus, ZKaneError> {
+        self.check()?;
+        Ok(RelayJobStatus::Confirmed { txid: "ab".repeat(32) })
+    }
+
+    async fn create_deposit_transaction(&self, note: &DepositNote) -> Result<TransactionRequest, ZKaneError> {
+        self.check()?;
+        Ok(TransactionRequest {
+            tx_hex: "00".to_string(),
+            witness_data: note.commitment.clone(),
+            fee_rate: 10,
+        })
+    }
+
+    async fn broadcast_transaction(&self, _tx_request: &TransactionRequest) -> Result<TransactionResponse, ZKaneError> {
+        self.check()?;
+        Ok(TransactionResponse {
+            txid: "ab".repeat(32),
+            status: TransactionStatus::Pending,
+            confirmations: 0,
+            block_height: None,
+        })
+    }
+
+    async fn get_transaction_status(&self, txid: &str) -> Result<TransactionResponse, ZKaneError> {
+        self.check()?;
+        Ok(TransactionResponse {
+            txid: txid.to_string(),
+            status: TransactionStatus::Confirmed,
+            confirmations: 1,
+            block_height: Some(100),
+        })
+    }
+}
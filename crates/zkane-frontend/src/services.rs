@@ -1,10 +1,21 @@
 //! Service layer for ZKane Frontend application
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use crate::prover_worker::CachedWitness;
 use crate::types::*;
 use crate::wasm_bindings::*;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
 use leptos::*;
-use wasm_bindgen_futures::spawn_local;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use zeroize::Zeroizing;
 
 #[derive(Clone)]
 pub struct ZKaneService;
@@ -59,16 +70,17 @@ impl ZKaneService {
         let nullifier_hash = generate_nullifier_hash_from_nullifier(&deposit_note.nullifier)
             .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
 
+        let public_inputs = withdrawal_public_inputs(&merkle_path.root, &nullifier_hash, &outputs_hash)
+            .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+
         Ok(WithdrawalProof {
             proof: proof_hex,
             merkle_root: merkle_path.root.clone(),
-            nullifier_hash: nullifier_hash.clone(),
-            outputs_hash: outputs_hash.clone(),
-            public_inputs: vec![
-                merkle_path.root.clone(),
-                nullifier_hash.clone(),
-                outputs_hash.clone(),
-            ],
+            nullifier_hash,
+            outputs_hash,
+            public_inputs,
+            relayer_output_hash: "00".repeat(32),
+            fee: 0,
         })
     }
 
@@ -226,34 +238,80 @@ impl AlkanesService {
         })
     }
 
-    /// Create withdrawal transaction
+    /// Get the state of a note's pool: the note's Merkle path to the
+    /// current root and whether its nullifier was already spent
+    pub async fn get_pool_state(
+        &self,
+        wallet_provider: &BrowserWalletProvider,
+        pool_id: &AlkaneId,
+        commitment: &str,
+        nullifier_hash: &str,
+    ) -> Result<PoolSyncState, ZKaneError> {
+        let params = serde_json::json!({
+            "pool_id": pool_id.to_string(),
+            "commitment": commitment,
+            "nullifier_hash": nullifier_hash,
+        });
+        let result = wallet_provider
+            .call(&wallet_provider.web_provider().sandshrew_rpc_url(), "get_pool_state", params, 1)
+            .await
+            .map_err(|e| ZKaneError::NetworkError(e.to_string()))?;
+
+        serde_json::from_value(result).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+    }
+
+    /// Get network fee rates in sat/vB, keyed by confirmation target in
+    /// blocks
+    pub async fn get_fee_estimates(
+        &self,
+        wallet_provider: &BrowserWalletProvider,
+    ) -> Result<Vec<(u32, f64)>, ZKaneError> {
+        let result = wallet_provider
+            .call(
+                &wallet_provider.web_provider().sandshrew_rpc_url(),
+                "esplora_fee-estimates",
+                serde_json::Value::Null,
+                1,
+            )
+            .await
+            .map_err(|e| ZKaneError::NetworkError(e.to_string()))?;
+
+        let estimates: std::collections::BTreeMap<String, f64> =
+            serde_json::from_value(result).map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+        Ok(estimates
+            .into_iter()
+            .filter_map(|(target, rate)| Some((target.parse().ok()?, rate)))
+            .collect())
+    }
+
+    /// Create a self-relayed withdrawal transaction paying `amount` to
+    /// `recipient`
     pub async fn create_withdrawal_transaction(
         &self,
         wallet_provider: &BrowserWalletProvider,
+        note: &DepositNote,
         proof: &WithdrawalProof,
-        outputs: &[TxOutput],
+        merkle_path: &MerklePath,
+        recipient: &str,
+        amount: u128,
+        fee_rate: u64,
     ) -> Result<TransactionRequest, ZKaneError> {
         let witness_data = generate_withdrawal_witness(
             &proof.proof,
             &proof.merkle_root,
             &proof.nullifier_hash,
-            &serde_json::to_string(&vec!["0xabcd"]).unwrap(), // Mock path elements
-            &serde_json::to_string(&vec![false]).unwrap(), // Mock path indices
-            0, // Mock leaf index
-            &"0x1234".repeat(16), // Mock commitment
+            &serde_json::to_string(&merkle_path.elements).unwrap(),
+            &serde_json::to_string(&merkle_path.indices).unwrap(),
+            merkle_path.leaf_index,
+            &note.commitment,
             &proof.outputs_hash,
         )
         .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
 
-        let send_outputs = outputs
-            .iter()
-            .map(|o| (o.script_pubkey.clone(), o.value))
-            .collect::<Vec<(String, u128)>>();
-
         let params = deezel_web::SendParams {
-            address: send_outputs[0].0.clone(),
-            amount: send_outputs[0].1 as u64,
-            fee_rate: Some(10.0),
+            address: recipient.to_string(),
+            amount: amount as u64,
+            fee_rate: Some(fee_rate as _),
             from_address: None,
             change_address: None,
             send_all: false,
@@ -268,7 +326,7 @@ impl AlkanesService {
         Ok(TransactionRequest {
             tx_hex,
             witness_data,
-            fee_rate: 10,
+            fee_rate,
         })
     }
 
@@ -292,6 +350,7 @@ impl AlkanesService {
             txid,
             status: TransactionStatus::Pending,
             confirmations: 0,
+            block_height: None,
         })
     }
 
@@ -326,10 +385,155 @@ impl AlkanesService {
             txid: txid.to_string(),
             status,
             confirmations,
+            block_height,
         })
     }
 }
 
+/// Client of the withdrawal relayers' HTTP API
+#[derive(Clone)]
+pub struct RelayerService;
+
+impl RelayerService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Get a relayer's fee terms and pool state
+    pub async fn get_status(&self, relayer_url: &str) -> Result<RelayerStatus, ZKaneError> {
+        let init = web_sys::RequestInit::new();
+        init.set_method("GET");
+        let status = fetch_json(&format!("{}/status", relayer_url.trim_end_matches('/')), &init).await?;
+        serde_json::from_value(status).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+    }
+
+    /// Get a relayer's quote, from its published terms
+    pub async fn get_quote(&self, relayer_url: &str) -> Result<RelayerQuote, ZKaneError> {
+        let status = self.get_status(relayer_url).await?;
+        Ok(RelayerQuote::from_status(relayer_url, status))
+    }
+
+    /// Get the relayer URLs listed at `list_url`, a JSON array of strings
+    pub async fn get_relayers(&self, list_url: &str) -> Result<Vec<String>, ZKaneError> {
+        let init = web_sys::RequestInit::new();
+        init.set_method("GET");
+        let list = fetch_json(list_url, &init).await?;
+        serde_json::from_value(list).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+    }
+
+    /// Get the progress of a relayed withdrawal
+    pub async fn get_job(&self, relayer_url: &str, job_id: u64) -> Result<RelayJobStatus, ZKaneError> {
+        let init = web_sys::RequestInit::new();
+        init.set_method("GET");
+        let status = fetch_json(&format!("{}/jobs/{}", relayer_url.trim_end_matches('/'), job_id), &init).await?;
+        serde_json::from_value(status).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+    }
+
+    /// Submit a relayed withdrawal, returning the relayer's job ID
+    ///
+    /// The relayer gets the full withdrawal witness, so it can build the
+    /// pool call and envelope, together with the recipient outputs the
+    /// proof commits to.
+    pub async fn relay(&self, relayer_url: &str, preview: &WithdrawalPreview) -> Result<u64, ZKaneError> {
+        let request = serde_json::json!({
+            "witness": relay_witness(preview)?,
+            "outputs": preview
+                .outputs
+                .iter()
+                .map(|output| serde_json::json!({
+                    "value": output.value as u64,
+                    "script_pubkey": output.script_pubkey,
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        let init = web_sys::RequestInit::new();
+        init.set_method("POST");
+        let headers = web_sys::Headers::new().map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+        headers
+            .set("Content-Type", "application/json")
+            .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?;
+        init.set_headers(&headers);
+        init.set_body(&JsValue::from_str(&request.to_string()));
+
+        let response = fetch_json(&format!("{}/relay", relayer_url.trim_end_matches('/')), &init).await?;
+        response
+            .get("job_id")
+            .and_then(|id| id.as_u64())
+            .ok_or_else(|| ZKaneError::SerializationError("Relayer response has no job_id".to_string()))
+    }
+}
+
+/// Decode a 32-byte hex value, naming it in the error
+fn decode_hash(value_hex: &str, name: &str) -> Result<[u8; 32], ZKaneError> {
+    hex::decode(value_hex.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ZKaneError::SerializationError(format!("Invalid {}", name)))
+}
+
+/// Convert a previewed withdrawal to the `zkane-common` witness relayers accept
+fn relay_witness(preview: &WithdrawalPreview) -> Result<zkane_common::WithdrawalWitness, ZKaneError> {
+    let elements = preview
+        .merkle_path
+        .elements
+        .iter()
+        .map(|element| decode_hash(element, "Merkle path element"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let path = zkane_common::MerklePath::new(elements, preview.merkle_path.indices.clone())
+        .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+    Ok(zkane_common::WithdrawalWitness {
+        proof: relay_proof(&preview.proof)?,
+        path,
+        leaf_index: preview.merkle_path.leaf_index,
+        commitment: zkane_common::Commitment::new(decode_hash(&preview.commitment, "commitment")?),
+        // Relayers hash the outputs of the transaction they build
+        outputs_hash: [0u8; 32],
+    })
+}
+
+/// Convert a proof to the `zkane-common` encoding relayers accept
+fn relay_proof(proof: &WithdrawalProof) -> Result<zkane_common::WithdrawalProof, ZKaneError> {
+    let proof_bytes = hex::decode(proof.proof.trim_start_matches("0x"))
+        .map_err(|e| ZKaneError::SerializationError(format!("Invalid proof: {}", e)))?;
+
+    let mut relayed = zkane_common::WithdrawalProof::new(
+        proof_bytes,
+        decode_hash(&proof.merkle_root, "merkle root")?,
+        zkane_common::NullifierHash::new(decode_hash(&proof.nullifier_hash, "nullifier hash")?),
+        zkane_common::Recipient::default(),
+    )
+    .with_relayer(decode_hash(&proof.relayer_output_hash, "relayer output hash")?, proof.fee);
+    relayed.recipients_hash = decode_hash(&proof.outputs_hash, "outputs hash")?;
+    Ok(relayed)
+}
+
+/// Fetch `url` and parse its JSON response
+async fn fetch_json(url: &str, init: &web_sys::RequestInit) -> Result<serde_json::Value, ZKaneError> {
+    let window = web_sys::window().ok_or_else(|| ZKaneError::NetworkError("No window".to_string()))?;
+    let failed = |e: JsValue| ZKaneError::NetworkError(format!("{}: {:?}", url, e));
+
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str_and_init(url, init))
+        .await
+        .map_err(failed)?
+        .unchecked_into();
+    let body = JsFuture::from(response.text().map_err(failed)?)
+        .await
+        .map_err(failed)?
+        .as_string()
+        .unwrap_or_default();
+
+    if !response.ok() {
+        // Relayers explain rejections in an `error` field
+        let reason = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|value| value.get("error")?.as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("HTTP {}", response.status()));
+        return Err(ZKaneError::NetworkError(format!("{}: {}", url, reason)));
+    }
+    serde_json::from_str(&body).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+}
+
 #[derive(Clone)]
 pub struct NotificationService {
     pub notifications: RwSignal<Vec<Notification>>,
@@ -406,11 +610,314 @@ impl StorageService {
         Self
     }
 
+    /// Get asset symbol for a given asset ID (helper for display)
+    pub fn get_asset_symbol(&self, asset_id: &AlkaneId) -> String {
+        // In a real implementation, this would query the asset registry
+        // For now, return mock data based on known assets
+        match (asset_id.block, asset_id.tx) {
+            (1, 1) => "ALKS".to_string(),
+            (2, 1) => "TEST".to_string(),
+            (3, 1) => "PRIV".to_string(),
+            _ => format!("{}:{}", asset_id.block, asset_id.tx),
+        }
+    }
+
+    /// Save user preferences
+    pub fn save_preferences(&self, preferences: &UserPreferences) -> Result<(), ZKaneError> {
+        let storage = web_sys::window()
+            .and_then(|w| w.local_storage().ok().flatten())
+            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+
+        let value = serde_json::to_string(preferences)
+            .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+
+        storage.set_item("zkane_preferences", &value)
+            .map_err(|e| ZKaneError::WasmError(format!("Failed to save preferences: {:?}", e)))?;
+
+        Ok(())
+    }
+
+    /// Load user preferences
+    pub fn load_preferences(&self) -> Result<UserPreferences, ZKaneError> {
+        let storage = web_sys::window()
+            .and_then(|w| w.local_storage().ok().flatten())
+            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+
+        match storage.get_item("zkane_preferences") {
+            Ok(Some(value)) => {
+                serde_json::from_str(&value)
+                    .map_err(|e| ZKaneError::SerializationError(e.to_string()))
+            },
+            _ => Ok(UserPreferences::default()),
+        }
+    }
+}
+
+/// Minutes without vault activity before the note vault locks itself
+pub const DEFAULT_AUTO_LOCK_MINUTES: u32 = 15;
+
+/// Version of the encrypted note vault format
+pub const NOTE_VAULT_VERSION: u32 = 1;
+
+const VAULT_DB_NAME: &str = "zkane";
+const VAULT_DB_VERSION: u32 = 1;
+const VAULT_STORE: &str = "vault";
+const VAULT_META_KEY: &str = "meta";
+const VAULT_NOTES_KEY: &str = "notes";
+const VAULT_CHECKPOINTS_KEY: &str = "checkpoints";
+const VAULT_HISTORY_KEY: &str = "history";
+const VAULT_WITNESS_KEY: &str = "witness";
+const VAULT_KDF_ITERATIONS: u32 = 600_000;
+const VAULT_CHECK_VALUE: &[u8] = b"zkane-note-vault";
+const VAULT_SALT_SIZE: usize = 16;
+const VAULT_NONCE_SIZE: usize = 12;
+const AUTO_LOCK_POLL_MS: u32 = 10_000;
+const LEGACY_NOTE_PREFIX: &str = "zkane_deposit_note_";
+
+/// Key derivation parameters of a vault, with a sealed value to check a
+/// passphrase against
+#[derive(Serialize, Deserialize)]
+struct VaultMeta {
+    version: u32,
+    salt: String,
+    iterations: u32,
+    check: SealedRecord,
+}
+
+/// A vault record sealed with AES-256-GCM, its key as additional data
+#[derive(Serialize, Deserialize)]
+struct SealedRecord {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypted storage of deposit notes and pool sync checkpoints.
+///
+/// Records live in IndexedDB, each sealed with AES-256-GCM under a WebCrypto
+/// key derived from the user's passphrase with PBKDF2. The key is
+/// non-extractable and only held in memory: the vault is locked on every page
+/// load and locks itself again after [`DEFAULT_AUTO_LOCK_MINUTES`] without
+/// activity. Notes saved in plaintext local storage by earlier versions are
+/// moved into the vault when it is first unlocked.
+#[derive(Clone)]
+pub struct SecureStorageService {
+    key: RwSignal<Option<web_sys::CryptoKey>>,
+    /// Bumped whenever the vault is locked, unlocked or written
+    revision: RwSignal<u64>,
+    /// Bumped on every lock and unlock, to stop stale auto-lock timers
+    session: RwSignal<u64>,
+    last_activity: RwSignal<f64>,
+    auto_lock_minutes: RwSignal<u32>,
+    /// Note handed from the history page to the withdraw page
+    prefill_note: RwSignal<Option<DepositNote>>,
+}
+
+impl SecureStorageService {
+    pub fn new() -> Self {
+        Self {
+            key: RwSignal::new(None),
+            revision: RwSignal::new(0),
+            session: RwSignal::new(0),
+            last_activity: RwSignal::new(0.0),
+            auto_lock_minutes: RwSignal::new(DEFAULT_AUTO_LOCK_MINUTES),
+            prefill_note: RwSignal::new(None),
+        }
+    }
+
+    /// Whether the vault is locked, for components to react to
+    pub fn locked(&self) -> Signal<bool> {
+        let key = self.key;
+        Signal::derive(move || key.with(Option::is_none))
+    }
+
+    /// Changes on every lock, unlock and write, so views can reload
+    pub fn revision(&self) -> ReadSignal<u64> {
+        self.revision.read_only()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.key.with_untracked(Option::is_none)
+    }
+
+    /// Check if a vault has been created in this browser
+    pub async fn has_vault(&self) -> Result<bool, ZKaneError> {
+        let db = open_vault_db().await?;
+        Ok(idb_get(&db, VAULT_META_KEY).await?.is_some())
+    }
+
+    /// Unlock the vault with a passphrase, creating it on first use
+    pub async fn unlock(&self, passphrase: &str) -> Result<(), ZKaneError> {
+        let db = open_vault_db().await?;
+        let key = match idb_get(&db, VAULT_META_KEY).await? {
+            Some(meta) => {
+                let meta: VaultMeta = serde_json::from_str(&meta)
+                    .map_err(|_| ZKaneError::StorageFailed("Malformed note vault".to_string()))?;
+                if meta.version != NOTE_VAULT_VERSION {
+                    return Err(ZKaneError::StorageFailed(format!(
+                        "Unsupported note vault version {}",
+                        meta.version
+                    )));
+                }
+                let salt = hex::decode(&meta.salt)
+                    .map_err(|_| ZKaneError::StorageFailed("Malformed note vault".to_string()))?;
+                let key = derive_vault_key(passphrase, &salt, meta.iterations).await?;
+                match open_record(&key, VAULT_META_KEY, &meta.check).await {
+                    Ok(check) if check.as_slice() == VAULT_CHECK_VALUE => key,
+                    _ => return Err(ZKaneError::StorageFailed("Wrong passphrase".to_string())),
+                }
+            }
+            None => {
+                if passphrase.chars().count() < MIN_BACKUP_PASSWORD_LEN {
+                    return Err(ZKaneError::StorageFailed(format!(
+                        "Passphrase must be at least {} characters",
+                        MIN_BACKUP_PASSWORD_LEN
+                    )));
+                }
+                let mut salt = [0u8; VAULT_SALT_SIZE];
+                getrandom::getrandom(&mut salt).map_err(|e| ZKaneError::StorageFailed(e.to_string()))?;
+                let key = derive_vault_key(passphrase, &salt, VAULT_KDF_ITERATIONS).await?;
+                let meta = VaultMeta {
+                    version: NOTE_VAULT_VERSION,
+                    salt: hex::encode(salt),
+                    iterations: VAULT_KDF_ITERATIONS,
+                    check: seal_record(&key, VAULT_META_KEY, VAULT_CHECK_VALUE).await?,
+                };
+                let meta = serde_json::to_string(&meta).map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+                idb_put(&db, VAULT_META_KEY, &meta).await?;
+                key
+            }
+        };
+
+        self.key.set(Some(key));
+        self.session.update(|session| *session += 1);
+        self.touch();
+        self.start_auto_lock();
+        if let Err(e) = self.migrate_plaintext_notes().await {
+            log::warn!("Failed to move plaintext deposit notes into the vault: {:?}", e);
+        }
+        self.revision.update(|revision| *revision += 1);
+        Ok(())
+    }
+
+    /// Forget the vault key
+    pub fn lock(&self) {
+        if self.key.with_untracked(Option::is_some) {
+            self.key.set(None);
+            self.session.update(|session| *session += 1);
+            self.revision.update(|revision| *revision += 1);
+        }
+    }
+
+    /// Record user activity, postponing the auto-lock
+    pub fn touch(&self) {
+        self.last_activity.set(js_sys::Date::now());
+    }
+
+    /// Set the minutes without activity before the vault locks itself
+    pub fn set_auto_lock_minutes(&self, minutes: u32) {
+        self.auto_lock_minutes.set(minutes.max(1));
+    }
+
+    /// Lock the vault once it has been idle for the auto-lock timeout. The
+    /// timer stops when the vault is locked or unlocked again.
+    fn start_auto_lock(&self) {
+        let service = self.clone();
+        let session = self.session.get_untracked();
+        spawn_local(async move {
+            loop {
+                gloo_timers::future::TimeoutFuture::new(AUTO_LOCK_POLL_MS).await;
+                // The signals are disposed once the app unmounts
+                if service.session.try_get_untracked() != Some(session) {
+                    return;
+                }
+                let idle = js_sys::Date::now() - service.last_activity.get_untracked();
+                if idle >= f64::from(service.auto_lock_minutes.get_untracked()) * 60_000.0 {
+                    service.lock();
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Save a deposit note, replacing any note with the same commitment
+    pub async fn save_deposit_note(&self, note: &DepositNote) -> Result<(), ZKaneError> {
+        let mut notes = self.read_notes().await?;
+        notes.retain(|saved| saved.commitment != note.commitment);
+        notes.push(note.clone());
+        self.write_record(VAULT_NOTES_KEY, &notes).await
+    }
+
+    /// Load the saved deposit notes, newest first
+    pub async fn load_deposit_notes(&self) -> Result<Vec<DepositNote>, ZKaneError> {
+        let mut notes = self.read_notes().await?;
+        notes.sort_by(|a, b| b.created_at.partial_cmp(&a.created_at).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(notes)
+    }
+
+    /// Delete a saved deposit note
+    pub async fn delete_deposit_note(&self, commitment: &str) -> Result<(), ZKaneError> {
+        let mut notes = self.read_notes().await?;
+        notes.retain(|note| note.commitment != commitment);
+        self.write_record(VAULT_NOTES_KEY, &notes).await
+    }
+
+    /// Save the pool state a note was last synced against
+    pub async fn save_sync_checkpoint(&self, commitment: &str, state: &PoolSyncState) -> Result<(), ZKaneError> {
+        let mut checkpoints: BTreeMap<String, PoolSyncState> =
+            self.read_record(VAULT_CHECKPOINTS_KEY).await?.unwrap_or_default();
+        checkpoints.insert(commitment.to_string(), state.clone());
+        self.write_record(VAULT_CHECKPOINTS_KEY, &checkpoints).await
+    }
+
+    /// Load the pool state a note was last synced against
+    pub async fn load_sync_checkpoint(&self, commitment: &str) -> Result<Option<PoolSyncState>, ZKaneError> {
+        let mut checkpoints: BTreeMap<String, PoolSyncState> =
+            self.read_record(VAULT_CHECKPOINTS_KEY).await?.unwrap_or_default();
+        Ok(checkpoints.remove(commitment))
+    }
+
+    /// Load the pool states the saved notes were last synced against, by
+    /// commitment
+    pub async fn load_sync_checkpoints(&self) -> Result<BTreeMap<String, PoolSyncState>, ZKaneError> {
+        Ok(self.read_record(VAULT_CHECKPOINTS_KEY).await?.unwrap_or_default())
+    }
+
+    /// Record a deposit or withdrawal, replacing any record of the same kind
+    /// for the same note
+    pub async fn record_transaction(&self, record: &TransactionRecord) -> Result<(), ZKaneError> {
+        let mut records = self.load_transactions().await?;
+        records.retain(|saved| saved.kind != record.kind || saved.commitment != record.commitment);
+        records.push(record.clone());
+        self.write_record(VAULT_HISTORY_KEY, &records).await
+    }
+
+    /// Load the recorded deposits and withdrawals
+    pub async fn load_transactions(&self) -> Result<Vec<TransactionRecord>, ZKaneError> {
+        Ok(self.read_record(VAULT_HISTORY_KEY).await?.unwrap_or_default())
+    }
+
+    /// Keep the witness of a withdrawal being proven, replacing the one kept
+    /// for any other withdrawal
+    pub async fn save_witness(&self, witness: &CachedWitness) -> Result<(), ZKaneError> {
+        self.write_record(VAULT_WITNESS_KEY, witness).await
+    }
+
+    /// Load the witness kept for the last withdrawal that wasn't proven
+    pub async fn load_witness(&self) -> Result<Option<CachedWitness>, ZKaneError> {
+        self.read_record(VAULT_WITNESS_KEY).await
+    }
+
+    /// Remove the kept witness, if any. Works while the vault is locked.
+    pub async fn clear_witness(&self) -> Result<(), ZKaneError> {
+        idb_delete(&open_vault_db().await?, VAULT_WITNESS_KEY).await?;
+        self.revision.update(|revision| *revision += 1);
+        Ok(())
+    }
+
     /// Preload test deposit notes for demonstration purposes
-    pub fn preload_test_deposit_notes(&self) -> Result<(), ZKaneError> {
+    pub async fn preload_test_deposit_notes(&self) -> Result<(), ZKaneError> {
         // Check if test notes already exist to avoid duplicates
-        let existing_notes = self.load_deposit_notes().unwrap_or_default();
-        if !existing_notes.is_empty() {
+        if !self.read_notes().await?.is_empty() {
             return Ok(()); // Already have notes, don't add test data
         }
 
@@ -462,110 +969,488 @@ impl StorageService {
             },
         ];
 
-        // Save each test note
-        for note in test_notes {
-            self.save_deposit_note(&note)?;
+        self.write_record(VAULT_NOTES_KEY, &test_notes).await
+    }
+
+    /// Hand a note to the withdraw page, in memory only
+    pub fn set_prefill_note(&self, note: DepositNote) {
+        self.prefill_note.set(Some(note));
+    }
+
+    /// Take the note handed to the withdraw page, if any
+    pub fn take_prefill_note(&self) -> Option<DepositNote> {
+        let note = self.prefill_note.get_untracked()?;
+        self.prefill_note.set(None);
+        Some(note)
+    }
+
+    /// Move notes saved in plaintext local storage into the vault
+    async fn migrate_plaintext_notes(&self) -> Result<(), ZKaneError> {
+        let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) else {
+            return Ok(());
+        };
+        let length = storage.length().unwrap_or(0);
+        let keys: Vec<String> = (0..length)
+            .filter_map(|i| storage.key(i).ok().flatten())
+            .filter(|key| key.starts_with(LEGACY_NOTE_PREFIX))
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut notes = self.read_notes().await?;
+        for key in &keys {
+            let Ok(Some(value)) = storage.get_item(key) else { continue };
+            if let Ok(note) = serde_json::from_str::<DepositNote>(&value) {
+                if !notes.iter().any(|saved| saved.commitment == note.commitment) {
+                    notes.push(note);
+                }
+            }
         }
+        self.write_record(VAULT_NOTES_KEY, &notes).await?;
 
+        // Only drop the plaintext copies once the vault holds the notes
+        for key in &keys {
+            let _ = storage.remove_item(key);
+        }
+        log::info!("Moved {} deposit notes into the encrypted note vault", keys.len());
         Ok(())
     }
 
-    /// Save deposit note to local storage
-    pub fn save_deposit_note(&self, note: &DepositNote) -> Result<(), ZKaneError> {
-        let storage = web_sys::window()
-            .and_then(|w| w.local_storage().ok().flatten())
-            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+    fn unlocked_key(&self) -> Result<web_sys::CryptoKey, ZKaneError> {
+        self.touch();
+        self.key.get_untracked().ok_or(ZKaneError::StorageLocked)
+    }
 
-        let key = format!("zkane_deposit_note_{}", note.commitment);
-        let value = serde_json::to_string(note)
-            .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+    async fn read_notes(&self) -> Result<Vec<DepositNote>, ZKaneError> {
+        Ok(self.read_record(VAULT_NOTES_KEY).await?.unwrap_or_default())
+    }
 
-        storage.set_item(&key, &value)
-            .map_err(|e| ZKaneError::WasmError(format!("Failed to save note: {:?}", e)))?;
+    async fn read_record<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>, ZKaneError> {
+        let key = self.unlocked_key()?;
+        let db = open_vault_db().await?;
+        let Some(record) = idb_get(&db, name).await? else { return Ok(None) };
+        let record: SealedRecord = serde_json::from_str(&record)
+            .map_err(|_| ZKaneError::StorageFailed(format!("Malformed vault record {}", name)))?;
+        let plaintext = open_record(&key, name, &record).await?;
+        serde_json::from_slice(&plaintext)
+            .map(Some)
+            .map_err(|e| ZKaneError::SerializationError(e.to_string()))
+    }
 
+    async fn write_record<T: Serialize>(&self, name: &str, value: &T) -> Result<(), ZKaneError> {
+        let key = self.unlocked_key()?;
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(value).map_err(|e| ZKaneError::SerializationError(e.to_string()))?,
+        );
+        let record = seal_record(&key, name, &plaintext).await?;
+        let record = serde_json::to_string(&record).map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+        idb_put(&open_vault_db().await?, name, &record).await?;
+        self.revision.update(|revision| *revision += 1);
         Ok(())
     }
+}
 
-    /// Load deposit notes from local storage
-    pub fn load_deposit_notes(&self) -> Result<Vec<DepositNote>, ZKaneError> {
-        let storage = web_sys::window()
-            .and_then(|w| w.local_storage().ok().flatten())
-            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+/// Derive the user's deposits and withdrawals, newest first.
+///
+/// Every recorded transaction is listed, and every saved note has a deposit.
+/// A note also has a withdrawal if its last sync found its nullifier spent,
+/// as when it was withdrawn from another browser. A sync finding the note's
+/// commitment in its pool confirms the deposit, and finding its nullifier
+/// spent confirms the withdrawal; [`TransactionStatus::Pending`] only means
+/// neither has been seen yet.
+pub fn build_history(
+    notes: &[DepositNote],
+    records: &[TransactionRecord],
+    checkpoints: &BTreeMap<String, PoolSyncState>,
+) -> Vec<HistoryEntry> {
+    let recorded = |kind: HistoryKind, commitment: &str| {
+        records.iter().any(|record| record.kind == kind && record.commitment == commitment)
+    };
+    let mut all = records.to_vec();
+    for note in notes {
+        let spent = checkpoints.get(&note.commitment).is_some_and(|state| state.nullifier_spent);
+        for kind in [HistoryKind::Deposit, HistoryKind::Withdrawal] {
+            if (kind == HistoryKind::Deposit || spent) && !recorded(kind, &note.commitment) {
+                all.push(TransactionRecord {
+                    kind,
+                    commitment: note.commitment.clone(),
+                    asset_id: note.asset_id.clone(),
+                    denomination: note.denomination,
+                    txid: None,
+                    relay_job: None,
+                    anonymity_set: None,
+                    created_at: note.created_at,
+                });
+            }
+        }
+    }
 
-        let mut notes = Vec::new();
-        let length = storage.length()
-            .map_err(|e| ZKaneError::WasmError(format!("Failed to get storage length: {:?}", e)))?;
-
-        for i in 0..length {
-            if let Ok(Some(key)) = storage.key(i) {
-                if key.starts_with("zkane_deposit_note_") {
-                    if let Ok(Some(value)) = storage.get_item(&key) {
-                        if let Ok(note) = serde_json::from_str::<DepositNote>(&value) {
-                            notes.push(note);
-                        }
-                    }
-                }
+    let mut history: Vec<HistoryEntry> = all
+        .into_iter()
+        .map(|record| {
+            let synced = checkpoints.get(&record.commitment);
+            let confirmed = match record.kind {
+                HistoryKind::Deposit => synced.is_some(),
+                HistoryKind::Withdrawal => synced.is_some_and(|state| state.nullifier_spent),
+            };
+            HistoryEntry {
+                kind: record.kind,
+                status: if confirmed { TransactionStatus::Confirmed } else { TransactionStatus::Pending },
+                relayer: record.relay_job.map(|(relayer, _)| relayer),
+                commitment: record.commitment,
+                asset_id: record.asset_id,
+                denomination: record.denomination,
+                txid: record.txid,
+                block_height: None,
+                anonymity_set: record.anonymity_set,
+                timestamp: record.created_at,
             }
+        })
+        .collect();
+    history.sort_by(|a, b| b.timestamp.partial_cmp(&a.timestamp).unwrap_or(std::cmp::Ordering::Equal));
+    history
+}
+
+/// Export history entries as CSV, one row per entry.
+///
+/// Only what the history page shows is exported: no note secrets and no
+/// addresses.
+pub fn history_csv(entries: &[HistoryEntry]) -> String {
+    let field = |value: String| {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
         }
+    };
+    let optional = |value: Option<String>| value.unwrap_or_default();
+
+    let mut csv = String::from("date,type,asset,denomination,commitment,txid,relayer,status,block_height,anonymity_set\n");
+    for entry in entries {
+        let date = js_sys::Date::new(&JsValue::from_f64(entry.timestamp)).to_iso_string().as_string();
+        let row = [
+            optional(date),
+            entry.kind.as_str().to_string(),
+            entry.asset_id.to_string(),
+            entry.denomination.to_string(),
+            entry.commitment.clone(),
+            optional(entry.txid.clone()),
+            optional(entry.relayer.clone()),
+            format!("{:?}", entry.status),
+            optional(entry.block_height.map(|height| height.to_string())),
+            optional(entry.anonymity_set.map(|size| size.to_string())),
+        ];
+        csv.push_str(&row.map(field).join(","));
+        csv.push('\n');
+    }
+    csv
+}
 
-        // Sort notes by creation date (newest first)
-        notes.sort_by(|a, b| b.created_at.partial_cmp(&a.created_at).unwrap_or(std::cmp::Ordering::Equal));
-        
-        Ok(notes)
+/// Offer `contents` to the user as a file download
+pub fn download_file(contents: &str, file_name: &str, mime_type: &str) -> Result<(), ZKaneError> {
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| ZKaneError::WasmError("Document not available".to_string()))?;
+
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(|e| ZKaneError::WasmError(format!("Failed to create {}: {:?}", file_name, e)))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)
+        .map_err(|e| ZKaneError::WasmError(format!("Failed to create {}: {:?}", file_name, e)))?;
+
+    let anchor: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))?
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+        .map_err(|e| ZKaneError::WasmError(format!("{:?}", e)))
+}
+
+fn storage_error(error: JsValue) -> ZKaneError {
+    ZKaneError::StorageFailed(format!("{:?}", error))
+}
+
+/// Await an IndexedDB request, resolving to its result
+async fn idb_request(request: &web_sys::IdbRequest) -> Result<JsValue, ZKaneError> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let on_success = {
+            let request = request.clone();
+            Closure::once_into_js(move || {
+                let _ = resolve.call1(&JsValue::NULL, &request.result().unwrap_or(JsValue::UNDEFINED));
+            })
+        };
+        let on_error = Closure::once_into_js(move || {
+            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str("IndexedDB request failed"));
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map_err(storage_error)
+}
+
+/// Open the vault database, creating its object store on first use
+async fn open_vault_db() -> Result<web_sys::IdbDatabase, ZKaneError> {
+    let factory = web_sys::window()
+        .and_then(|w| w.indexed_db().ok().flatten())
+        .ok_or_else(|| ZKaneError::StorageFailed("IndexedDB not available".to_string()))?;
+    let request = factory.open_with_u32(VAULT_DB_NAME, VAULT_DB_VERSION).map_err(storage_error)?;
+
+    let on_upgrade = {
+        let request = request.clone();
+        Closure::once_into_js(move || {
+            if let Ok(db) = request.result() {
+                let _ = db.unchecked_into::<web_sys::IdbDatabase>().create_object_store(VAULT_STORE);
+            }
+        })
+    };
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+    Ok(idb_request(&request).await?.unchecked_into())
+}
+
+async fn idb_get(db: &web_sys::IdbDatabase, name: &str) -> Result<Option<String>, ZKaneError> {
+    let store = db
+        .transaction_with_str(VAULT_STORE)
+        .and_then(|tx| tx.object_store(VAULT_STORE))
+        .map_err(storage_error)?;
+    let request = store.get(&JsValue::from_str(name)).map_err(storage_error)?;
+    Ok(idb_request(&request).await?.as_string())
+}
+
+async fn idb_put(db: &web_sys::IdbDatabase, name: &str, value: &str) -> Result<(), ZKaneError> {
+    let store = db
+        .transaction_with_str_and_mode(VAULT_STORE, web_sys::IdbTransactionMode::Readwrite)
+        .and_then(|tx| tx.object_store(VAULT_STORE))
+        .map_err(storage_error)?;
+    let request = store
+        .put_with_key(&JsValue::from_str(value), &JsValue::from_str(name))
+        .map_err(storage_error)?;
+    idb_request(&request).await.map(|_| ())
+}
+
+async fn idb_delete(db: &web_sys::IdbDatabase, name: &str) -> Result<(), ZKaneError> {
+    let store = db
+        .transaction_with_str_and_mode(VAULT_STORE, web_sys::IdbTransactionMode::Readwrite)
+        .and_then(|tx| tx.object_store(VAULT_STORE))
+        .map_err(storage_error)?;
+    let request = store.delete(&JsValue::from_str(name)).map_err(storage_error)?;
+    idb_request(&request).await.map(|_| ())
+}
+
+fn subtle_crypto() -> Result<web_sys::SubtleCrypto, ZKaneError> {
+    web_sys::window()
+        .and_then(|w| w.crypto().ok())
+        .map(|crypto| crypto.subtle())
+        .ok_or_else(|| ZKaneError::StorageFailed("WebCrypto not available".to_string()))
+}
+
+/// Build a WebCrypto algorithm parameter object
+fn js_object(entries: &[(&str, JsValue)]) -> js_sys::Object {
+    let object = js_sys::Object::new();
+    for (name, value) in entries {
+        let _ = js_sys::Reflect::set(&object, &JsValue::from_str(name), value);
     }
+    object
+}
 
-    /// Delete a specific deposit note from local storage
-    pub fn delete_deposit_note(&self, commitment: &str) -> Result<(), ZKaneError> {
-        let storage = web_sys::window()
-            .and_then(|w| w.local_storage().ok().flatten())
-            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+/// Derive a non-extractable AES-256-GCM vault key from a passphrase with
+/// PBKDF2-SHA256
+async fn derive_vault_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<web_sys::CryptoKey, ZKaneError> {
+    let subtle = subtle_crypto()?;
+    let material = Zeroizing::new(passphrase.as_bytes().to_vec());
+    let material = subtle
+        .import_key_with_str(
+            "raw",
+            &js_sys::Uint8Array::from(material.as_slice()),
+            "PBKDF2",
+            false,
+            &js_sys::Array::of1(&JsValue::from_str("deriveKey")),
+        )
+        .map_err(storage_error)?;
+    let material: web_sys::CryptoKey = JsFuture::from(material).await.map_err(storage_error)?.unchecked_into();
+
+    let pbkdf2 = js_object(&[
+        ("name", JsValue::from_str("PBKDF2")),
+        ("hash", JsValue::from_str("SHA-256")),
+        ("salt", js_sys::Uint8Array::from(salt).into()),
+        ("iterations", JsValue::from(iterations)),
+    ]);
+    let aes = js_object(&[("name", JsValue::from_str("AES-GCM")), ("length", JsValue::from(256))]);
+    let usages = js_sys::Array::of2(&JsValue::from_str("encrypt"), &JsValue::from_str("decrypt"));
+    let key = subtle
+        .derive_key_with_object_and_object(&pbkdf2, &material, &aes, false, &usages)
+        .map_err(storage_error)?;
+    Ok(JsFuture::from(key).await.map_err(storage_error)?.unchecked_into())
+}
 
-        let key = format!("zkane_deposit_note_{}", commitment);
-        storage.remove_item(&key)
-            .map_err(|e| ZKaneError::WasmError(format!("Failed to delete note: {:?}", e)))?;
+fn aes_gcm_params(nonce: &[u8], name: &str) -> js_sys::Object {
+    js_object(&[
+        ("name", JsValue::from_str("AES-GCM")),
+        ("iv", js_sys::Uint8Array::from(nonce).into()),
+        // Binding the record name stops records being swapped
+        ("additionalData", js_sys::Uint8Array::from(name.as_bytes()).into()),
+    ])
+}
 
-        Ok(())
+async fn seal_record(key: &web_sys::CryptoKey, name: &str, plaintext: &[u8]) -> Result<SealedRecord, ZKaneError> {
+    let mut nonce = [0u8; VAULT_NONCE_SIZE];
+    getrandom::getrandom(&mut nonce).map_err(|e| ZKaneError::StorageFailed(e.to_string()))?;
+    let ciphertext = subtle_crypto()?
+        .encrypt_with_object_and_buffer_source(&aes_gcm_params(&nonce, name), key, &js_sys::Uint8Array::from(plaintext))
+        .map_err(storage_error)?;
+    let ciphertext = JsFuture::from(ciphertext).await.map_err(storage_error)?;
+    Ok(SealedRecord {
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(js_sys::Uint8Array::new(&ciphertext).to_vec()),
+    })
+}
+
+async fn open_record(key: &web_sys::CryptoKey, name: &str, record: &SealedRecord) -> Result<Zeroizing<Vec<u8>>, ZKaneError> {
+    let malformed = |_| ZKaneError::StorageFailed(format!("Malformed vault record {}", name));
+    let nonce = hex::decode(&record.nonce).map_err(malformed)?;
+    let ciphertext = hex::decode(&record.ciphertext).map_err(malformed)?;
+    if nonce.len() != VAULT_NONCE_SIZE {
+        return Err(ZKaneError::StorageFailed(format!("Malformed vault record {}", name)));
     }
+    let plaintext = subtle_crypto()?
+        .decrypt_with_object_and_buffer_source(&aes_gcm_params(&nonce, name), key, &js_sys::Uint8Array::from(ciphertext.as_slice()))
+        .map_err(storage_error)?;
+    let plaintext = JsFuture::from(plaintext)
+        .await
+        .map_err(|_| ZKaneError::StorageFailed(format!("Vault record {} failed to decrypt", name)))?;
+    Ok(Zeroizing::new(js_sys::Uint8Array::new(&plaintext).to_vec()))
+}
 
-    /// Get asset symbol for a given asset ID (helper for display)
-    pub fn get_asset_symbol(&self, asset_id: &AlkaneId) -> String {
-        // In a real implementation, this would query the asset registry
-        // For now, return mock data based on known assets
-        match (asset_id.block, asset_id.tx) {
-            (1, 1) => "ALKS".to_string(),
-            (2, 1) => "TEST".to_string(),
-            (3, 1) => "PRIV".to_string(),
-            _ => format!("{}:{}", asset_id.block, asset_id.tx),
+/// Version of the encrypted note backup format
+pub const NOTE_BACKUP_VERSION: u32 = 1;
+
+/// Minimum length of a note backup password
+pub const MIN_BACKUP_PASSWORD_LEN: usize = 8;
+
+const BACKUP_SALT_SIZE: usize = 16;
+const BACKUP_NONCE_SIZE: usize = 12;
+
+/// Password-encrypted note backup, in the same envelope as the CLI note store
+#[derive(Serialize, Deserialize)]
+struct NoteBackup {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone)]
+pub struct BackupService;
+
+impl BackupService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Encrypt a deposit note with a password, returning the backup file contents
+    ///
+    /// The key is derived with Argon2id and the note is sealed with AES-256-GCM.
+    pub fn encrypt_note(&self, note: &DepositNote, password: &str) -> Result<String, ZKaneError> {
+        if password.chars().count() < MIN_BACKUP_PASSWORD_LEN {
+            return Err(ZKaneError::BackupFailed(format!(
+                "Password must be at least {} characters",
+                MIN_BACKUP_PASSWORD_LEN
+            )));
         }
+
+        let mut salt = [0u8; BACKUP_SALT_SIZE];
+        let mut nonce = [0u8; BACKUP_NONCE_SIZE];
+        getrandom::getrandom(&mut salt)
+            .and_then(|_| getrandom::getrandom(&mut nonce))
+            .map_err(|e| ZKaneError::BackupFailed(e.to_string()))?;
+
+        let key = derive_backup_key(password, &salt)?;
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(note).map_err(|e| ZKaneError::SerializationError(e.to_string()))?,
+        );
+        let ciphertext = Aes256Gcm::new_from_slice(key.as_slice())
+            .map_err(|e| ZKaneError::BackupFailed(e.to_string()))?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| ZKaneError::BackupFailed("Failed to encrypt note".to_string()))?;
+
+        let backup = NoteBackup {
+            version: NOTE_BACKUP_VERSION,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        serde_json::to_string_pretty(&backup).map_err(|e| ZKaneError::SerializationError(e.to_string()))
     }
 
-    /// Save user preferences
-    pub fn save_preferences(&self, preferences: &UserPreferences) -> Result<(), ZKaneError> {
-        let storage = web_sys::window()
-            .and_then(|w| w.local_storage().ok().flatten())
-            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+    /// Decrypt a backup created by [`BackupService::encrypt_note`]
+    pub fn decrypt_note(&self, backup: &str, password: &str) -> Result<DepositNote, ZKaneError> {
+        let backup: NoteBackup = serde_json::from_str(backup.trim())
+            .map_err(|_| ZKaneError::BackupFailed("Not a ZKane note backup".to_string()))?;
+        if backup.version != NOTE_BACKUP_VERSION {
+            return Err(ZKaneError::BackupFailed(format!("Unsupported backup version {}", backup.version)));
+        }
 
-        let value = serde_json::to_string(preferences)
-            .map_err(|e| ZKaneError::SerializationError(e.to_string()))?;
+        let malformed = |_| ZKaneError::BackupFailed("Malformed note backup".to_string());
+        let salt = hex::decode(&backup.salt).map_err(malformed)?;
+        let nonce = hex::decode(&backup.nonce).map_err(malformed)?;
+        let ciphertext = hex::decode(&backup.ciphertext).map_err(malformed)?;
+        if salt.len() != BACKUP_SALT_SIZE || nonce.len() != BACKUP_NONCE_SIZE {
+            return Err(ZKaneError::BackupFailed("Malformed note backup".to_string()));
+        }
 
-        storage.set_item("zkane_preferences", &value)
-            .map_err(|e| ZKaneError::WasmError(format!("Failed to save preferences: {:?}", e)))?;
+        let key = derive_backup_key(password, &salt)?;
+        let plaintext = Zeroizing::new(
+            Aes256Gcm::new_from_slice(key.as_slice())
+                .map_err(|e| ZKaneError::BackupFailed(e.to_string()))?
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| ZKaneError::BackupFailed("Wrong password or corrupted backup".to_string()))?,
+        );
+        serde_json::from_slice(&plaintext).map_err(|_| ZKaneError::InvalidDepositNote)
+    }
 
+    /// Check that a backup decrypts to the given note
+    pub fn verify_backup(&self, note: &DepositNote, backup: &str, password: &str) -> Result<(), ZKaneError> {
+        let restored = self.decrypt_note(backup, password)?;
+        let matches = restored.secret == note.secret
+            && restored.nullifier == note.nullifier
+            && restored.commitment == note.commitment
+            && restored.asset_id == note.asset_id
+            && restored.denomination == note.denomination;
+        if !matches {
+            return Err(ZKaneError::BackupFailed("Backup is for a different note".to_string()));
+        }
         Ok(())
     }
 
-    /// Load user preferences
-    pub fn load_preferences(&self) -> Result<UserPreferences, ZKaneError> {
-        let storage = web_sys::window()
-            .and_then(|w| w.local_storage().ok().flatten())
-            .ok_or_else(|| ZKaneError::WasmError("Local storage not available".to_string()))?;
+    /// Render backup contents as an SVG QR code
+    pub fn qr_code_svg(&self, backup: &str) -> Result<String, ZKaneError> {
+        let code = QrCode::new(backup.as_bytes()).map_err(|e| ZKaneError::BackupFailed(e.to_string()))?;
+        Ok(code.render::<svg::Color>().min_dimensions(256, 256).build())
+    }
 
-        match storage.get_item("zkane_preferences") {
-            Ok(Some(value)) => {
-                serde_json::from_str(&value)
-                    .map_err(|e| ZKaneError::SerializationError(e.to_string()))
-            },
-            _ => Ok(UserPreferences::default()),
-        }
+    /// File name for a note's backup
+    pub fn file_name(&self, note: &DepositNote) -> String {
+        let commitment = note.commitment.trim_start_matches("0x");
+        format!("zkane-note-{}.backup.json", &commitment[..commitment.len().min(16)])
     }
-}
\ No newline at end of file
+
+    /// Offer backup contents to the user as a file download
+    pub fn download(&self, contents: &str, file_name: &str) -> Result<(), ZKaneError> {
+        download_file(contents, file_name, "application/json")
+    }
+}
+
+/// Derive a note backup key from a password with Argon2id
+fn derive_backup_key(password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, ZKaneError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| ZKaneError::BackupFailed(e.to_string()))?;
+    Ok(key)
+}
@@ -1,6 +1,7 @@
 //! Type definitions for ZKane Frontend application
 
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 // Local AlkaneId definition for frontend compatibility
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -70,6 +71,13 @@ impl JsDepositNote {
     pub fn leaf_index(&self) -> u32 { self.leaf_index }
 }
 
+impl Drop for JsDepositNote {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+        self.nullifier.zeroize();
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AssetBalance {
     pub asset_id: AlkaneId,
@@ -105,6 +113,14 @@ impl DepositNote {
     }
 }
 
+impl Drop for DepositNote {
+    fn drop(&mut self) {
+        // Every clone holds its own copy of the secrets, so each one is wiped
+        self.secret.zeroize();
+        self.nullifier.zeroize();
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WithdrawalProof {
     pub proof: String,
@@ -112,6 +128,12 @@ pub struct WithdrawalProof {
     pub nullifier_hash: String,
     pub outputs_hash: String,
     pub public_inputs: Vec<String>,
+    /// Hash of the relayer's fee output, all zeros for self-relayed withdrawals
+    #[serde(default)]
+    pub relayer_output_hash: String,
+    /// Fee paid to the relayer out of the note
+    #[serde(default)]
+    pub fee: u128,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -142,13 +164,7 @@ pub struct PoolInfo {
 
 impl PoolInfo {
     pub fn anonymity_level(&self) -> AnonymityLevel {
-        match self.anonymity_set {
-            0..=9 => AnonymityLevel::VeryLow,
-            10..=49 => AnonymityLevel::Low,
-            50..=99 => AnonymityLevel::Medium,
-            100..=499 => AnonymityLevel::High,
-            _ => AnonymityLevel::VeryHigh,
-        }
+        AnonymityLevel::for_set_size(self.anonymity_set)
     }
 }
 
@@ -162,6 +178,17 @@ pub enum AnonymityLevel {
 }
 
 impl AnonymityLevel {
+    /// Level of an anonymity set of `size` deposits
+    pub fn for_set_size(size: u64) -> Self {
+        match size {
+            0..=9 => AnonymityLevel::VeryLow,
+            10..=49 => AnonymityLevel::Low,
+            50..=99 => AnonymityLevel::Medium,
+            100..=499 => AnonymityLevel::High,
+            _ => AnonymityLevel::VeryHigh,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             AnonymityLevel::VeryLow => "Very Low",
@@ -188,6 +215,7 @@ pub enum DepositStatus {
     Idle,
     ValidatingAmount,
     CreatingNote,
+    AwaitingBackup(DepositNote),
     BuildingTransaction,
     WaitingForSignature,
     Broadcasting,
@@ -195,18 +223,220 @@ pub enum DepositStatus {
     Error(String),
 }
 
-#[derive(Clone, Debug)]
-pub enum WithdrawalStatus {
-    Idle,
-    ParsingNote,
-    ValidatingRecipient,
-    FetchingMerklePath,
-    GeneratingProof,
-    BuildingTransaction,
-    WaitingForSignature,
+/// Pool state a withdrawal is proven against
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolSyncState {
+    /// Path from the note's commitment to the current root
+    pub merkle_path: MerklePath,
+    /// Number of deposits in the pool
+    pub deposit_count: u64,
+    /// Whether the note's nullifier has already been spent
+    pub nullifier_spent: bool,
+}
+
+/// Terms a relayer publishes at `GET /status`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelayerStatus {
+    /// Minimum fee accepted, in units of the pool asset
+    pub min_fee: u128,
+    pub denomination: u128,
+    /// Hex hash of the output the fee must be paid to
+    pub fee_output_hash: String,
+    pub merkle_root: String,
+    pub commitment_count: u64,
+    pub pending_jobs: usize,
+    /// Expected seconds from submission to confirmation, zero if not quoted
+    #[serde(default)]
+    pub estimated_confirmation_secs: u64,
+}
+
+/// A relayer's offer to broadcast a withdrawal
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RelayerQuote {
+    pub url: String,
+    /// Fee taken out of the note, in units of the pool asset
+    pub fee: u128,
+    /// Hex hash of the output the fee is paid to
+    pub fee_output_hash: String,
+    /// Smallest note the relayer withdraws
+    pub min_denomination: u128,
+    /// Largest note the relayer withdraws
+    pub max_denomination: u128,
+    /// Expected seconds from submission to confirmation, zero if not quoted
+    pub estimated_confirmation_secs: u64,
+    /// Jobs queued ahead of a new withdrawal
+    pub pending_jobs: usize,
+}
+
+impl RelayerQuote {
+    /// Quote a relayer's published terms. A relayer serves a single pool,
+    /// so its denomination bounds the quote both ways.
+    pub fn from_status(url: &str, status: RelayerStatus) -> Self {
+        Self {
+            url: url.to_string(),
+            fee: status.min_fee,
+            fee_output_hash: status.fee_output_hash,
+            min_denomination: status.denomination,
+            max_denomination: status.denomination,
+            estimated_confirmation_secs: status.estimated_confirmation_secs,
+            pending_jobs: status.pending_jobs,
+        }
+    }
+
+    /// Whether the relayer withdraws notes of `denomination`, leaving the
+    /// recipient something after its fee
+    pub fn serves(&self, denomination: u128) -> bool {
+        (self.min_denomination..=self.max_denomination).contains(&denomination) && self.fee < denomination
+    }
+}
+
+/// Progress of a relayed withdrawal, as served at `GET /jobs/{id}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RelayJobStatus {
+    Queued,
     Broadcasting,
-    Complete(WithdrawalProof),
-    Error(String),
+    Broadcast { txid: String },
+    Confirmed { txid: String },
+    Failed { reason: String },
+}
+
+impl RelayJobStatus {
+    /// Whether the job is done, confirmed by the pool or failed
+    pub fn is_final(&self) -> bool {
+        matches!(self, RelayJobStatus::Confirmed { .. } | RelayJobStatus::Failed { .. })
+    }
+}
+
+/// How a withdrawal is paid for and broadcast
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeOption {
+    pub label: String,
+    /// Network fee rate in sat/vB, zero when a relayer pays it
+    pub fee_rate: u64,
+    /// Relayer broadcasting the withdrawal, `None` to broadcast from the
+    /// connected wallet
+    pub relayer: Option<RelayerQuote>,
+}
+
+impl FeeOption {
+    /// Fee paid out of the note
+    pub fn relayer_fee(&self) -> u128 {
+        self.relayer.as_ref().map_or(0, |relayer| relayer.fee)
+    }
+
+    /// Amount the recipient receives from a note of `denomination`
+    pub fn recipient_amount(&self, denomination: u128) -> Option<u128> {
+        denomination.checked_sub(self.relayer_fee())
+    }
+
+    /// Relayer output hash the proof commits to, all zeros when self-relayed
+    pub fn relayer_output_hash(&self) -> String {
+        match &self.relayer {
+            Some(relayer) => relayer.fee_output_hash.clone(),
+            None => "00".repeat(32),
+        }
+    }
+}
+
+/// Progress of a proof, as reported by the prover worker
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProofProgress {
+    /// `"loading"`, `"synthesizing"`, `"proving"` or `"done"`
+    pub stage: String,
+    /// Approximate fraction of the work done, from 0 to 1
+    pub progress: f64,
+}
+
+/// A proven withdrawal, shown to the user before it is submitted
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WithdrawalPreview {
+    pub proof: WithdrawalProof,
+    pub outputs: Vec<TxOutput>,
+    pub fee: FeeOption,
+    /// Merkle path of the withdrawn note, sent to relayers with the proof
+    pub merkle_path: MerklePath,
+    /// Commitment of the withdrawn note
+    pub commitment: String,
+    /// Wallet transaction of a self-relayed withdrawal; relayers build
+    /// their own
+    pub transaction: Option<TransactionRequest>,
+}
+
+/// Where a submitted withdrawal went
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum WithdrawalSubmission {
+    /// Broadcast from the connected wallet
+    Broadcast(TransactionResponse),
+    /// Queued by a relayer
+    Relayed { relayer: String, job_id: u64 },
+}
+
+/// Whether a history entry put a note into its pool or took it out
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryKind {
+    Deposit,
+    Withdrawal,
+}
+
+impl HistoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryKind::Deposit => "Deposit",
+            HistoryKind::Withdrawal => "Withdrawal",
+        }
+    }
+}
+
+/// A deposit or withdrawal submitted from this browser, kept in the note
+/// vault.
+///
+/// Records hold no addresses, so the history is labeled by note and pool
+/// only.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub kind: HistoryKind,
+    /// Commitment of the note deposited or withdrawn
+    pub commitment: String,
+    pub asset_id: AlkaneId,
+    pub denomination: u128,
+    /// `None` for a withdrawal a relayer has yet to broadcast
+    pub txid: Option<String>,
+    /// Relayer URL and job ID of a relayed withdrawal
+    pub relay_job: Option<(String, u64)>,
+    /// Deposits in the pool when the withdrawal was proven
+    pub anonymity_set: Option<u64>,
+    pub created_at: f64, // timestamp
+}
+
+/// A row of the history page.
+///
+/// Rows are derived from the saved notes, their sync checkpoints and the
+/// recorded transactions, never from which addresses look like the user's.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    pub commitment: String,
+    pub asset_id: AlkaneId,
+    pub denomination: u128,
+    pub txid: Option<String>,
+    /// Relayer of a relayed withdrawal
+    pub relayer: Option<String>,
+    pub status: TransactionStatus,
+    pub block_height: Option<u64>,
+    /// Deposits in the pool when the withdrawal was proven
+    pub anonymity_set: Option<u64>,
+    /// When the transaction was submitted, or the note was created if it
+    /// was submitted elsewhere
+    pub timestamp: f64,
+}
+
+impl HistoryEntry {
+    /// Label naming the entry by its note only, e.g. `Withdrawal of note 1a2b3c4d`
+    pub fn label(&self) -> String {
+        let commitment = self.commitment.trim_start_matches("0x");
+        format!("{} of note {}", self.kind.as_str(), &commitment[..commitment.len().min(8)])
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, thiserror::Error)]
@@ -240,6 +470,15 @@ pub enum ZKaneError {
     
     #[error("Transaction failed: {0}")]
     TransactionFailed(String),
+
+    #[error("Note backup failed: {0}")]
+    BackupFailed(String),
+
+    #[error("Note storage failed: {0}")]
+    StorageFailed(String),
+
+    #[error("Note vault is locked")]
+    StorageLocked,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -254,9 +493,12 @@ pub struct TransactionResponse {
     pub txid: String,
     pub status: TransactionStatus,
     pub confirmations: u32,
+    /// Block the transaction confirmed in
+    #[serde(default)]
+    pub block_height: Option<u64>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TransactionStatus {
     Pending,
     Confirmed,
@@ -270,6 +512,11 @@ pub struct AppConfig {
     pub default_fee_rate: u64,
     pub min_anonymity_set: u64,
     pub supported_assets: Vec<AlkaneId>,
+    /// Relayers offered for withdrawals
+    pub relayers: Vec<String>,
+    /// URL of a JSON list of further relayer URLs, fetched when fees are
+    /// quoted
+    pub relayer_list_url: Option<String>,
 }
 
 impl Default for AppConfig {
@@ -282,6 +529,8 @@ impl Default for AppConfig {
             supported_assets: vec![
                 AlkaneId { block: 1, tx: 1 }, // Example asset
             ],
+            relayers: Vec::new(),
+            relayer_list_url: None,
         }
     }
 }
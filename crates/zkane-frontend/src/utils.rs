@@ -81,6 +81,14 @@ pub fn is_valid_bitcoin_address(address: &str) -> bool {
     address.starts_with('1') || address.starts_with('3') || address.starts_with("bc1")
 }
 
+/// Get the hex scriptPubKey paying a Bitcoin address, of any network
+pub fn address_script_hex(address: &str) -> Option<String> {
+    let address = address
+        .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()
+        .ok()?;
+    Some(hex::encode(address.assume_checked().script_pubkey().as_bytes()))
+}
+
 /// Format Bitcoin amount with proper decimal places
 pub fn format_bitcoin_amount(satoshis: u64) -> String {
     let btc = satoshis as f64 / 100_000_000.0;
@@ -7,6 +7,8 @@ use wasm_bindgen::prelude::*;
 use serde::Deserialize;
 use crate::types::*;
 use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+use zkane_common::{EnvelopeFormat, WithdrawalWitness, ZkAssetId};
 
 // Utility macro for error handling
 macro_rules! js_error {
@@ -22,24 +24,24 @@ macro_rules! js_error {
 #[wasm_bindgen]
 #[derive(Clone, Debug)]
 pub struct WasmAlkaneId {
-    block: u64, // Use u64 for JS compatibility
-    tx: u64,
+    block: u128, // Exposed to JS as BigInt
+    tx: u128,
 }
 
 #[wasm_bindgen]
 impl WasmAlkaneId {
     #[wasm_bindgen(constructor)]
-    pub fn new(block: u64, tx: u64) -> WasmAlkaneId {
+    pub fn new(block: u128, tx: u128) -> WasmAlkaneId {
         WasmAlkaneId { block, tx }
     }
 
     #[wasm_bindgen(getter)]
-    pub fn block(&self) -> u64 {
+    pub fn block(&self) -> u128 {
         self.block
     }
 
     #[wasm_bindgen(getter)]
-    pub fn tx(&self) -> u64 {
+    pub fn tx(&self) -> u128 {
         self.tx
     }
 }
@@ -47,8 +49,8 @@ impl WasmAlkaneId {
 impl From<&AlkaneId> for WasmAlkaneId {
     fn from(id: &AlkaneId) -> Self {
         WasmAlkaneId {
-            block: id.block as u64,
-            tx: id.tx as u64,
+            block: id.block,
+            tx: id.tx,
         }
     }
 }
@@ -56,8 +58,23 @@ impl From<&AlkaneId> for WasmAlkaneId {
 impl From<WasmAlkaneId> for AlkaneId {
     fn from(id: WasmAlkaneId) -> Self {
         AlkaneId {
-            block: id.block as u128,
-            tx: id.tx as u128,
+            block: id.block,
+            tx: id.tx,
+        }
+    }
+}
+
+impl From<&WasmAlkaneId> for ZkAssetId {
+    fn from(id: &WasmAlkaneId) -> Self {
+        ZkAssetId::new(id.block, id.tx)
+    }
+}
+
+impl From<ZkAssetId> for WasmAlkaneId {
+    fn from(id: ZkAssetId) -> Self {
+        WasmAlkaneId {
+            block: id.block,
+            tx: id.tx,
         }
     }
 }
@@ -125,14 +142,22 @@ impl WasmDepositNote {
     }
 }
 
+impl Drop for WasmDepositNote {
+    fn drop(&mut self) {
+        // Wipe the hex-encoded secrets when JS frees the note
+        self.secret.zeroize();
+        self.nullifier.zeroize();
+    }
+}
+
 impl From<WasmDepositNote> for JsDepositNote {
     fn from(wasm_note: WasmDepositNote) -> Self {
         JsDepositNote::new(
-            wasm_note.secret,
-            wasm_note.nullifier,
-            wasm_note.commitment,
-            wasm_note.asset_id.into(),
-            wasm_note.denomination,
+            wasm_note.secret.clone(),
+            wasm_note.nullifier.clone(),
+            wasm_note.commitment.clone(),
+            wasm_note.asset_id.clone().into(),
+            wasm_note.denomination.clone(),
             wasm_note.leaf_index,
         )
     }
@@ -145,17 +170,17 @@ impl From<WasmDepositNote> for JsDepositNote {
 /// Generate a random secret (32 bytes as hex string)
 #[wasm_bindgen]
 pub fn generate_random_secret() -> String {
-    let mut secret = [0u8; 32];
-    getrandom::getrandom(&mut secret).expect("Failed to generate random bytes");
-    hex::encode(secret)
+    let mut secret = Zeroizing::new([0u8; 32]);
+    getrandom::getrandom(secret.as_mut()).expect("Failed to generate random bytes");
+    hex::encode(secret.as_ref())
 }
 
 /// Generate a random nullifier (32 bytes as hex string)
 #[wasm_bindgen]
 pub fn generate_random_nullifier() -> String {
-    let mut nullifier = [0u8; 32];
-    getrandom::getrandom(&mut nullifier).expect("Failed to generate random bytes");
-    hex::encode(nullifier)
+    let mut nullifier = Zeroizing::new([0u8; 32]);
+    getrandom::getrandom(nullifier.as_mut()).expect("Failed to generate random bytes");
+    hex::encode(nullifier.as_ref())
 }
 
 /// Generate a commitment from secret and nullifier (simplified using SHA256)
@@ -164,10 +189,11 @@ pub fn generate_commitment_from_secret_nullifier(
     secret_hex: &str,
     nullifier_hex: &str,
 ) -> Result<String, JsValue> {
-    let secret_bytes = hex::decode(secret_hex)
-        .map_err(|e| js_error!(format!("Invalid secret hex: {}", e)))?;
-    let nullifier_bytes = hex::decode(nullifier_hex)
-        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?;
+    // Decoded secrets are wiped on every return path
+    let secret_bytes = Zeroizing::new(hex::decode(secret_hex)
+        .map_err(|e| js_error!(format!("Invalid secret hex: {}", e)))?);
+    let nullifier_bytes = Zeroizing::new(hex::decode(nullifier_hex)
+        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?);
 
     if secret_bytes.len() != 32 {
         return Err(js_error!("Secret must be 32 bytes"));
@@ -178,8 +204,8 @@ pub fn generate_commitment_from_secret_nullifier(
 
     // Simplified commitment generation using SHA256
     let mut hasher = Sha256::new();
-    hasher.update(&secret_bytes);
-    hasher.update(&nullifier_bytes);
+    hasher.update(secret_bytes.as_slice());
+    hasher.update(nullifier_bytes.as_slice());
     hasher.update(b"commitment"); // Domain separator
     let commitment: [u8; 32] = hasher.finalize().into();
 
@@ -189,8 +215,8 @@ pub fn generate_commitment_from_secret_nullifier(
 /// Generate a nullifier hash from nullifier (simplified using SHA256)
 #[wasm_bindgen]
 pub fn generate_nullifier_hash_from_nullifier(nullifier_hex: &str) -> Result<String, JsValue> {
-    let nullifier_bytes = hex::decode(nullifier_hex)
-        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?;
+    let nullifier_bytes = Zeroizing::new(hex::decode(nullifier_hex)
+        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?);
 
     if nullifier_bytes.len() != 32 {
         return Err(js_error!("Nullifier must be 32 bytes"));
@@ -198,7 +224,7 @@ pub fn generate_nullifier_hash_from_nullifier(nullifier_hex: &str) -> Result<Str
 
     // Simplified nullifier hash using SHA256
     let mut hasher = Sha256::new();
-    hasher.update(&nullifier_bytes);
+    hasher.update(nullifier_bytes.as_slice());
     hasher.update(b"nullifier_hash"); // Domain separator
     let nullifier_hash: [u8; 32] = hasher.finalize().into();
 
@@ -284,25 +310,8 @@ pub fn generate_pool_id(asset_id: &WasmAlkaneId, denomination: &str) -> Result<W
     let denom: u128 = denomination.parse()
         .map_err(|e| js_error!(format!("Invalid denomination: {}", e)))?;
 
-    // Use same logic as factory contract for deterministic pool ID generation
-    let mut hasher_input = Vec::new();
-    hasher_input.extend_from_slice(&asset_id.block.to_le_bytes());
-    hasher_input.extend_from_slice(&asset_id.tx.to_le_bytes());
-    hasher_input.extend_from_slice(&denom.to_le_bytes());
-    
-    let mut hash_value = 0u128;
-    for chunk in hasher_input.chunks(16) {
-        let mut bytes = [0u8; 16];
-        bytes[..chunk.len()].copy_from_slice(chunk);
-        hash_value ^= u128::from_le_bytes(bytes);
-    }
-    
-    let pool_id = WasmAlkaneId {
-        block: 6, // ZKANE_INSTANCE_BLOCK
-        tx: hash_value as u64, // Truncate for JS compatibility
-    };
-
-    Ok(pool_id)
+    // Same derivation as the factory contract
+    Ok(zkane_common::derive_pool_id(&asset_id.into(), denom).into())
 }
 
 // ============================================================================
@@ -327,6 +336,9 @@ pub fn generate_deposit_witness(commitment_hex: &str) -> Result<String, JsValue>
 }
 
 /// Generate withdrawal witness envelope data
+///
+/// Returns the hex of the `WithdrawalWitness` envelope the pool contract
+/// reads, compressed when that makes it smaller.
 #[wasm_bindgen]
 pub fn generate_withdrawal_witness(
     proof_hex: &str,
@@ -341,44 +353,130 @@ pub fn generate_withdrawal_witness(
     // Parse all inputs
     let proof = hex::decode(proof_hex)
         .map_err(|e| js_error!(format!("Invalid proof hex: {}", e)))?;
-    
-    let merkle_root = hex::decode(merkle_root_hex)
-        .map_err(|e| js_error!(format!("Invalid merkle root hex: {}", e)))?;
-    
-    let nullifier_hash = hex::decode(nullifier_hash_hex)
-        .map_err(|e| js_error!(format!("Invalid nullifier hash hex: {}", e)))?;
-    
-    let commitment = hex::decode(commitment_hex)
-        .map_err(|e| js_error!(format!("Invalid commitment hex: {}", e)))?;
-    
-    let outputs_hash = hex::decode(outputs_hash_hex)
-        .map_err(|e| js_error!(format!("Invalid outputs hash hex: {}", e)))?;
+
+    let merkle_root = decode_hash(merkle_root_hex, "merkle root")?;
+    let nullifier_hash = decode_hash(nullifier_hash_hex, "nullifier hash")?;
+    let commitment = decode_hash(commitment_hex, "commitment")?;
+    let outputs_hash = decode_hash(outputs_hash_hex, "outputs hash")?;
 
     // Parse path elements and indices
     let path_elements: Vec<String> = serde_json::from_str(path_elements_json)
         .map_err(|e| js_error!(format!("Invalid path elements JSON: {}", e)))?;
-    
+
     let path_indices: Vec<bool> = serde_json::from_str(path_indices_json)
         .map_err(|e| js_error!(format!("Invalid path indices JSON: {}", e)))?;
 
-    // Validate lengths
-    if merkle_root.len() != 32 || nullifier_hash.len() != 32 || 
-       commitment.len() != 32 || outputs_hash.len() != 32 {
-        return Err(js_error!("Hash values must be 32 bytes"));
-    }
+    let path_elements = path_elements
+        .iter()
+        .map(|element| decode_hash(element, "path element"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let path = zkane_common::MerklePath::new(path_elements, path_indices)
+        .map_err(|e| js_error!(e.to_string()))?;
+
+    // Recipients are determined by the transaction outputs, not the proof
+    let witness = WithdrawalWitness {
+        proof: zkane_common::WithdrawalProof::new(
+            proof,
+            merkle_root,
+            zkane_common::NullifierHash::new(nullifier_hash),
+            zkane_common::Recipient::default(),
+        ),
+        path,
+        leaf_index,
+        commitment: zkane_common::Commitment::new(commitment),
+        outputs_hash,
+    };
 
-    let witness_data = serde_json::json!({
-        "proof": hex::encode(proof),
-        "merkle_root": hex::encode(merkle_root),
-        "nullifier_hash": hex::encode(nullifier_hash),
-        "path_elements": path_elements,
-        "path_indices": path_indices,
-        "leaf_index": leaf_index,
-        "commitment": hex::encode(commitment),
-        "outputs_hash": hex::encode(outputs_hash)
-    });
+    let bytes = witness
+        .to_envelope(EnvelopeFormat::Compressed)
+        .map_err(|e| js_error!(e.to_string()))?;
+    Ok(hex::encode(bytes))
+}
 
-    Ok(witness_data.to_string())
+/// Generate withdrawal witness envelope data for a relayed withdrawal
+///
+/// The relayer output hash and fee are public inputs of the proof, so they
+/// must match the values the proof was generated with.
+#[wasm_bindgen]
+pub fn generate_relayed_withdrawal_witness(
+    withdrawal_witness_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: &str,
+) -> Result<String, JsValue> {
+    let witness_bytes = hex::decode(withdrawal_witness_hex)
+        .map_err(|e| js_error!(format!("Invalid withdrawal witness hex: {}", e)))?;
+    let mut witness = WithdrawalWitness::from_envelope(&witness_bytes)
+        .map_err(|e| js_error!(format!("Invalid withdrawal witness: {}", e)))?;
+
+    let relayer_output_hash = decode_hash(relayer_output_hash_hex, "relayer output hash")?;
+
+    let fee_amount: u128 = fee.parse()
+        .map_err(|e| js_error!(format!("Invalid fee: {}", e)))?;
+
+    witness.proof = witness.proof.with_relayer(relayer_output_hash, fee_amount);
+
+    let bytes = witness
+        .to_envelope(EnvelopeFormat::Compressed)
+        .map_err(|e| js_error!(e.to_string()))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Get the public inputs of a self-relayed withdrawal proof
+///
+/// Returns the `0x`-prefixed field elements in the order the Noir circuit
+/// declares them, encoded by `zkane_common::PublicInputs`.
+#[wasm_bindgen]
+pub fn withdrawal_public_inputs(
+    merkle_root_hex: &str,
+    nullifier_hash_hex: &str,
+    outputs_hash_hex: &str,
+) -> Result<Vec<String>, JsValue> {
+    let inputs = zkane_common::PublicInputs::new(
+        decode_hash(merkle_root_hex, "merkle root")?,
+        decode_hash(nullifier_hash_hex, "nullifier hash")?,
+        decode_hash(outputs_hash_hex, "outputs hash")?,
+        [0u8; 32],
+        [0u8; 32],
+        0,
+        0,
+    );
+    Ok(inputs.to_hex_fields())
+}
+
+/// Get the public inputs of a relayed withdrawal proof
+///
+/// Like [`withdrawal_public_inputs`], with the relayer's fee output hash and
+/// fee the proof commits to.
+#[wasm_bindgen]
+pub fn relayed_withdrawal_public_inputs(
+    merkle_root_hex: &str,
+    nullifier_hash_hex: &str,
+    outputs_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: &str,
+) -> Result<Vec<String>, JsValue> {
+    let fee_amount: u128 = fee.parse()
+        .map_err(|e| js_error!(format!("Invalid fee: {}", e)))?;
+
+    let inputs = zkane_common::PublicInputs::new(
+        decode_hash(merkle_root_hex, "merkle root")?,
+        decode_hash(nullifier_hash_hex, "nullifier hash")?,
+        decode_hash(outputs_hash_hex, "outputs hash")?,
+        [0u8; 32],
+        decode_hash(relayer_output_hash_hex, "relayer output hash")?,
+        fee_amount,
+        0,
+    );
+    Ok(inputs.to_hex_fields())
+}
+
+/// Decode a 32-byte hex value
+fn decode_hash(value_hex: &str, name: &str) -> Result<[u8; 32], JsValue> {
+    let bytes = hex::decode(value_hex.trim_start_matches("0x"))
+        .map_err(|e| js_error!(format!("Invalid {} hex: {}", name, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| js_error!(format!("{} must be 32 bytes", name)))
 }
 
 // ============================================================================
@@ -396,10 +494,10 @@ pub fn generate_withdrawal_proof_placeholder(
     // This is a placeholder implementation
     // In production, this would call the Noir prover
     
-    let secret = hex::decode(secret_hex)
-        .map_err(|e| js_error!(format!("Invalid secret hex: {}", e)))?;
-    let nullifier = hex::decode(nullifier_hex)
-        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?;
+    let secret = Zeroizing::new(hex::decode(secret_hex)
+        .map_err(|e| js_error!(format!("Invalid secret hex: {}", e)))?);
+    let nullifier = Zeroizing::new(hex::decode(nullifier_hex)
+        .map_err(|e| js_error!(format!("Invalid nullifier hex: {}", e)))?);
     let outputs_hash = hex::decode(outputs_hash_hex)
         .map_err(|e| js_error!(format!("Invalid outputs hash hex: {}", e)))?;
 
@@ -408,7 +506,7 @@ pub fn generate_withdrawal_proof_placeholder(
     }
 
     // Generate a deterministic mock proof
-    let mut proof = Vec::new();
+    let mut proof = Zeroizing::new(Vec::new());
     proof.extend_from_slice(&secret);
     proof.extend_from_slice(&nullifier);
     proof.extend_from_slice(&outputs_hash);
@@ -418,7 +516,7 @@ pub fn generate_withdrawal_proof_placeholder(
         proof.push(0x42);
     }
 
-    Ok(hex::encode(proof))
+    Ok(hex::encode(proof.as_slice()))
 }
 
 // ============================================================================
@@ -1,13 +1,31 @@
 use leptos::*;
 use wasm_bindgen_test::*;
-use zkane_frontend::components::DepositComponent;
-use zkane_frontend::services::{AlkanesService, NotificationService, StorageService, WalletService, ZKaneService};
-use zkane_frontend::types::UserPreferences;
+use zkane_frontend::components::{DepositComponent, PoolListComponent, WithdrawComponent};
+use zkane_frontend::provider::{provide_frontend_provider, MockProvider};
+use zkane_frontend::services::{
+    build_history, history_csv, AlkanesService, BackupService, NotificationService, SecureStorageService,
+    StorageService, WalletService, ZKaneService,
+};
+use zkane_frontend::types::{
+    AlkaneId, AssetBalance, DepositNote, HistoryKind, MerklePath, PoolInfo, PoolSyncState, TransactionRecord,
+    TransactionStatus, UserPreferences, ZKaneError,
+};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
 /// A helper function to render a component with all necessary services provided.
+///
+/// Network and prover calls are served by a [`MockProvider`], so components
+/// render headless.
 fn with_services<F, IV>(f: F)
+where
+    F: Fn() -> IV + 'static,
+    IV: IntoView,
+{
+    with_provider(mock_provider(), f)
+}
+
+fn with_provider<F, IV>(provider: MockProvider, f: F)
 where
     F: Fn() -> IV + 'static,
     IV: IntoView,
@@ -16,17 +34,22 @@ where
     let (user_preferences, _) = create_signal(UserPreferences::default());
     let notification_service = NotificationService::new();
     let storage_service = StorageService::new();
+    let secure_storage = SecureStorageService::new();
     let wallet_service = WalletService::new();
     let alkanes_service = AlkanesService::new();
     let zkane_service = ZKaneService::new();
+    let backup_service = BackupService::new();
 
     // Provide contexts
     provide_context(user_preferences);
     provide_context(notification_service);
     provide_context(storage_service);
+    provide_context(secure_storage);
     provide_context(wallet_service);
     provide_context(alkanes_service);
     provide_context(zkane_service);
+    provide_context(backup_service);
+    provide_frontend_provider(provider);
 
     // Mount the component
     mount_to_body(f);
@@ -42,4 +65,333 @@ fn test_deposit_component_renders_with_services() {
 
     // If we reach here without panicking, the test is considered a success.
     // We can add more assertions later to check for specific elements.
-}
\ No newline at end of file
+}
+
+fn mock_provider() -> MockProvider {
+    let asset_id = AlkaneId { block: 2, tx: 1 };
+    MockProvider::new()
+        .with_assets(vec![AssetBalance {
+            asset_id: asset_id.clone(),
+            symbol: "TEST".to_string(),
+            name: "Test Token".to_string(),
+            balance: 1_000_000_000,
+            decimals: 8,
+            icon_url: None,
+        }])
+        .with_pools(vec![PoolInfo {
+            pool_id: AlkaneId { block: 6, tx: 1 },
+            asset_id,
+            asset_symbol: "TEST".to_string(),
+            denomination: 100_000_000,
+            total_deposits: 42,
+            anonymity_set: 42,
+            created_at: 0.0,
+            last_deposit: 0.0,
+        }])
+}
+
+#[wasm_bindgen_test]
+fn test_withdraw_component_renders_with_mock_provider() {
+    with_services(|| {
+        view! { <WithdrawComponent /> }
+    });
+}
+
+#[wasm_bindgen_test]
+fn test_pool_list_component_renders_with_mock_provider() {
+    with_services(|| {
+        view! { <PoolListComponent /> }
+    });
+}
+
+#[wasm_bindgen_test]
+fn test_pool_list_component_renders_provider_errors() {
+    with_provider(mock_provider().failing("esplora unreachable"), || {
+        view! { <PoolListComponent /> }
+    });
+}
+
+#[wasm_bindgen_test]
+async fn test_mock_provider_serves_canned_data() {
+    use zkane_frontend::provider::FrontendProvider;
+
+    let provider = mock_provider();
+    assert_eq!(provider.get_privacy_pools().await.unwrap()[0].anonymity_set, 42);
+    let note = provider.create_deposit_note(AlkaneId { block: 2, tx: 1 }, 500).await.unwrap();
+    assert_eq!(note.denomination, 500);
+
+    let failing = mock_provider().failing("offline");
+    assert!(failing.get_user_assets().await.is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_withdraw_component_renders_provider_errors() {
+    with_provider(mock_provider().failing("esplora unreachable"), || {
+        view! { <WithdrawComponent /> }
+    });
+}
+
+#[wasm_bindgen_test]
+async fn test_mock_provider_withdrawal_flow() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use zkane_frontend::provider::FrontendProvider;
+    use zkane_frontend::types::{ProofProgress, TxOutput, WithdrawalSubmission};
+
+    let provider = mock_provider();
+    let note = test_note();
+
+    let state = provider.sync_pool(&note).await.unwrap();
+    assert!(!state.nullifier_spent);
+    assert_eq!(state.deposit_count, 42);
+
+    let options = provider.get_fee_options(&note).await.unwrap();
+    let relayed = options.iter().find(|option| option.relayer.is_some()).unwrap().clone();
+    assert_eq!(relayed.recipient_amount(note.denomination), Some(note.denomination - 1_000));
+
+    let reported = Rc::new(Cell::new(false));
+    let on_progress = {
+        let reported = reported.clone();
+        Rc::new(move |_: ProofProgress| reported.set(true))
+    };
+    let outputs = vec![TxOutput {
+        value: note.denomination - 1_000,
+        script_pubkey: "0014".to_string() + &"00".repeat(20),
+    }];
+    let proof = provider
+        .prove_withdrawal(&note, &outputs, &state.merkle_path, &relayed, on_progress)
+        .await
+        .unwrap();
+    assert!(reported.get());
+    assert_eq!(proof.fee, 1_000);
+
+    // Relayers build their own transaction
+    let preview = provider
+        .preview_withdrawal(&note, proof, &state.merkle_path, "bc1q", outputs, relayed)
+        .await
+        .unwrap();
+    assert!(preview.transaction.is_none());
+    let WithdrawalSubmission::Relayed { relayer, job_id } = provider.submit_withdrawal(&preview).await.unwrap() else {
+        panic!("relayed withdrawal was broadcast from the wallet");
+    };
+    assert_eq!(job_id, 1);
+    assert!(provider.get_relay_job(&relayer, job_id).await.unwrap().is_final());
+}
+
+#[wasm_bindgen_test]
+fn test_relayer_quotes() {
+    use zkane_frontend::components::{format_confirmation, relay_job_label};
+    use zkane_frontend::types::{RelayJobStatus, RelayerQuote, RelayerStatus};
+
+    // Relayers from before confirmation estimates quote none
+    let status: RelayerStatus = serde_json::from_value(serde_json::json!({
+        "min_fee": 1_000,
+        "denomination": 100_000,
+        "fee_output_hash": "99".repeat(32),
+        "merkle_root": "00".repeat(32),
+        "commitment_count": 5,
+        "pending_jobs": 2,
+    }))
+    .unwrap();
+    let quote = RelayerQuote::from_status("https://relayer.example", status.clone());
+    assert_eq!(quote.estimated_confirmation_secs, 0);
+    assert_eq!(format_confirmation(quote.estimated_confirmation_secs), "Confirmation time not quoted");
+    assert!(quote.serves(100_000));
+    assert!(!quote.serves(1_000_000));
+
+    let quote = RelayerQuote::from_status(
+        "https://relayer.example",
+        RelayerStatus { min_fee: 100_000, estimated_confirmation_secs: 1_200, ..status },
+    );
+    assert_eq!(format_confirmation(quote.estimated_confirmation_secs), "Confirms in about 20 min");
+    // A fee taking the whole note leaves nothing to withdraw
+    assert!(!quote.serves(100_000));
+
+    let job: RelayJobStatus = serde_json::from_str(r#"{"state":"failed","reason":"Nullifier already spent"}"#).unwrap();
+    assert!(job.is_final());
+    assert_eq!(relay_job_label(&job), "The relayer failed the withdrawal: Nullifier already spent");
+    let job: RelayJobStatus = serde_json::from_str(r#"{"state":"broadcasting"}"#).unwrap();
+    assert!(!job.is_final());
+    // A broadcast withdrawal is pending until the pool accepts it
+    let job: RelayJobStatus = serde_json::from_str(r#"{"state":"broadcast","txid":"ab"}"#).unwrap();
+    assert!(!job.is_final());
+    let job: RelayJobStatus = serde_json::from_str(r#"{"state":"confirmed","txid":"ab"}"#).unwrap();
+    assert!(job.is_final());
+    assert_eq!(relay_job_label(&job), "Withdrawn in transaction ab");
+}
+
+#[wasm_bindgen_test]
+fn test_prover_note_json() {
+    use zkane_frontend::prover_worker::prover_note_json;
+
+    let json = prover_note_json(&test_note()).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["secret"], format!("0x{}", "11".repeat(32)));
+    assert_eq!(value["asset_id"]["block"], 2);
+
+    let mut bad_note = test_note();
+    bad_note.secret = "0x1234".to_string();
+    assert!(prover_note_json(&bad_note).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_worker_witness_message() {
+    use zkane_frontend::prover_worker::{CachedWitness, WorkerMessage};
+
+    let message: WorkerMessage =
+        serde_json::from_str(r#"{"type":"witness","key":"ab","witness":"0102"}"#).unwrap();
+    assert_eq!(
+        message,
+        WorkerMessage::Witness(CachedWitness { key: "ab".to_string(), witness: "0102".to_string() })
+    );
+}
+
+#[wasm_bindgen_test]
+async fn test_witness_cache_roundtrip() {
+    use zkane_frontend::prover_worker::CachedWitness;
+
+    let secure_storage = SecureStorageService::new();
+    secure_storage.unlock("correct horse").await.unwrap();
+    let witness = CachedWitness { key: "ab".repeat(32), witness: "0102".to_string() };
+    secure_storage.save_witness(&witness).await.unwrap();
+    assert_eq!(secure_storage.load_witness().await.unwrap(), Some(witness));
+
+    // Clearing doesn't need the vault unlocked
+    secure_storage.lock();
+    secure_storage.clear_witness().await.unwrap();
+    secure_storage.unlock("correct horse").await.unwrap();
+    assert_eq!(secure_storage.load_witness().await.unwrap(), None);
+}
+
+fn test_note() -> DepositNote {
+    DepositNote {
+        secret: "0x".to_string() + &"11".repeat(32),
+        nullifier: "0x".to_string() + &"22".repeat(32),
+        commitment: "0x".to_string() + &"33".repeat(32),
+        asset_id: AlkaneId { block: 2, tx: 1 },
+        denomination: 100000000,
+        leaf_index: 0,
+        created_at: 0.0,
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_note_backup_roundtrip() {
+    let backup_service = BackupService::new();
+    let note = test_note();
+
+    let backup = backup_service.encrypt_note(&note, "correct horse").unwrap();
+    assert!(!backup.contains(&note.secret[2..]));
+    assert!(backup_service.verify_backup(&note, &backup, "correct horse").is_ok());
+
+    let restored = backup_service.decrypt_note(&backup, "correct horse").unwrap();
+    assert_eq!(restored.commitment, note.commitment);
+    assert_eq!(restored.denomination, note.denomination);
+}
+
+#[wasm_bindgen_test]
+fn test_note_backup_rejects_bad_input() {
+    let backup_service = BackupService::new();
+    let note = test_note();
+
+    assert!(backup_service.encrypt_note(&note, "short").is_err());
+
+    let backup = backup_service.encrypt_note(&note, "correct horse").unwrap();
+    assert!(backup_service.verify_backup(&note, &backup, "wrong password").is_err());
+    assert!(backup_service.verify_backup(&note, "{}", "correct horse").is_err());
+
+    let mut other_note = test_note();
+    other_note.commitment = "0x".to_string() + &"44".repeat(32);
+    assert!(backup_service.verify_backup(&other_note, &backup, "correct horse").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_note_backup_qr_code() {
+    let backup_service = BackupService::new();
+    let backup = backup_service.encrypt_note(&test_note(), "correct horse").unwrap();
+    assert!(backup_service.qr_code_svg(&backup).unwrap().contains("<svg"));
+}
+
+#[wasm_bindgen_test]
+async fn test_note_vault_roundtrip() {
+    let secure_storage = SecureStorageService::new();
+    let note = test_note();
+    assert!(secure_storage.is_locked());
+    assert!(matches!(secure_storage.load_deposit_notes().await, Err(ZKaneError::StorageLocked)));
+
+    secure_storage.unlock("correct horse").await.unwrap();
+    secure_storage.save_deposit_note(&note).await.unwrap();
+    secure_storage.save_deposit_note(&note).await.unwrap();
+    let notes = secure_storage.load_deposit_notes().await.unwrap();
+    assert_eq!(notes.iter().filter(|saved| saved.commitment == note.commitment).count(), 1);
+
+    secure_storage.lock();
+    assert!(secure_storage.is_locked());
+    assert!(secure_storage.unlock("wrong password").await.is_err());
+    assert!(secure_storage.is_locked());
+
+    secure_storage.unlock("correct horse").await.unwrap();
+    secure_storage.delete_deposit_note(&note.commitment).await.unwrap();
+    let notes = secure_storage.load_deposit_notes().await.unwrap();
+    assert!(notes.iter().all(|saved| saved.commitment != note.commitment));
+}
+
+#[wasm_bindgen_test]
+fn test_history_from_notes_and_sync() {
+    let note = test_note();
+    let mut pending_note = test_note();
+    pending_note.commitment = "0x".to_string() + &"44".repeat(32);
+    pending_note.created_at = 1_000.0;
+
+    // The first note was withdrawn through a relayer, the second not synced yet
+    let withdrawal = TransactionRecord {
+        kind: HistoryKind::Withdrawal,
+        commitment: note.commitment.clone(),
+        asset_id: note.asset_id.clone(),
+        denomination: note.denomination,
+        txid: None,
+        relay_job: Some(("https://relayer.example".to_string(), 7)),
+        anonymity_set: Some(42),
+        created_at: 2_000.0,
+    };
+    let checkpoint = PoolSyncState {
+        merkle_path: MerklePath { root: String::new(), elements: vec![], indices: vec![], leaf_index: 0 },
+        deposit_count: 50,
+        nullifier_spent: true,
+    };
+    let checkpoints = [(note.commitment.clone(), checkpoint)].into_iter().collect();
+
+    let history = build_history(&[note.clone(), pending_note.clone()], &[withdrawal], &checkpoints);
+    let summary: Vec<_> = history.iter().map(|entry| (entry.kind, entry.commitment.as_str(), &entry.status)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (HistoryKind::Withdrawal, note.commitment.as_str(), &TransactionStatus::Confirmed),
+            (HistoryKind::Deposit, pending_note.commitment.as_str(), &TransactionStatus::Pending),
+            (HistoryKind::Deposit, note.commitment.as_str(), &TransactionStatus::Confirmed),
+        ]
+    );
+    assert_eq!(history[0].anonymity_set, Some(42));
+    assert_eq!(history[0].relayer.as_deref(), Some("https://relayer.example"));
+
+    let csv = history_csv(&history);
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("date,type,asset,denomination,commitment,txid,relayer,status,block_height,anonymity_set")
+    );
+    assert!(lines.next().unwrap().ends_with(",,https://relayer.example,Confirmed,,42"));
+    assert_eq!(lines.count(), 2);
+    assert!(!csv.contains(&note.secret[2..]));
+    assert!(!csv.contains(&note.nullifier[2..]));
+}
+
+#[wasm_bindgen_test]
+async fn test_mock_provider_transaction_status() {
+    use zkane_frontend::provider::FrontendProvider;
+
+    let provider = mock_provider();
+    let response = provider.get_transaction_status("ab".repeat(32).as_str()).await.unwrap();
+    assert_eq!(response.status, TransactionStatus::Confirmed);
+    assert_eq!(response.block_height, Some(100));
+}
@@ -0,0 +1,88 @@
+//! HTTP/JSON API of the relayer.
+//!
+//! - `POST /relay` - submit a [`RelayRequest`], returns `{"job_id": n}`
+//! - `GET /jobs/{id}` - status of a job
+//! - `GET /status` - relayer terms, pool state and expected confirmation
+//!   time, the quote wallets compare relayers by
+
+use crate::cache::VerificationCache;
+use crate::queue::JobQueue;
+use crate::rate_limit::RateLimiter;
+use crate::relayer::RelayerConfig;
+use crate::types::{JobStatus, RelayRequest, RelayerError, RelayerStatus};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Shared state of the HTTP handlers.
+#[derive(Clone)]
+pub struct ApiState {
+    /// Queue the relay jobs are pushed to
+    pub queue: Arc<JobQueue>,
+    /// Per-client rate limiter for `POST /relay`
+    pub limiter: Arc<RateLimiter>,
+    /// Relayer terms checked before queuing
+    pub config: RelayerConfig,
+    /// Status published by the relay worker
+    pub status: Arc<Mutex<RelayerStatus>>,
+    /// Proof verdicts of the relay worker, to turn away known-bad proofs
+    pub cache: Arc<VerificationCache>,
+}
+
+impl IntoResponse for RelayerError {
+    fn into_response(self) -> Response {
+        let code = match self {
+            RelayerError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            RelayerError::JobNotFound => StatusCode::NOT_FOUND,
+            RelayerError::AlreadyQueued | RelayerError::NullifierSpent => StatusCode::CONFLICT,
+            RelayerError::BroadcastFailed(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = serde_json::json!({ "error": self.to_string() });
+        (code, Json(body)).into_response()
+    }
+}
+
+/// Build the relayer router.
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/relay", post(relay))
+        .route("/jobs/:id", get(job_status))
+        .route("/status", get(status))
+        .with_state(state)
+}
+
+async fn relay(
+    State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<RelayRequest>,
+) -> Result<Json<serde_json::Value>, RelayerError> {
+    if !state.limiter.check(&addr.ip().to_string()) {
+        return Err(RelayerError::RateLimited);
+    }
+
+    state.config.check_terms(&request)?;
+    if state.cache.is_rejected(&request.witness.proof) {
+        return Err(RelayerError::ProofRejected);
+    }
+    let job_id = state.queue.push(request)?;
+
+    Ok(Json(serde_json::json!({ "job_id": job_id })))
+}
+
+async fn job_status(
+    State(state): State<ApiState>,
+    Path(id): Path<u64>,
+) -> Result<Json<JobStatus>, RelayerError> {
+    state.queue.status(id).map(Json)
+}
+
+async fn status(State(state): State<ApiState>) -> Json<RelayerStatus> {
+    let mut status = state.status.lock().unwrap().clone();
+    status.pending_jobs = state.queue.pending_count();
+    Json(status)
+}
@@ -0,0 +1,113 @@
+//! Cache of withdrawal proof verification results.
+//!
+//! Verifying a proof is the most expensive thing the relayer does, so each
+//! verdict is remembered under the proof's nullifier hash and the hash of the
+//! encoded proof. A proof that failed verification is then turned away by
+//! the API without being queued again, and a resubmitted valid proof isn't
+//! verified twice.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use zkane_common::WithdrawalProof;
+use zkane_crypto::hash::sha256;
+
+/// Key of a cached verdict: the nullifier hash and the proof hash.
+pub type CacheKey = ([u8; 32], [u8; 32]);
+
+#[derive(Default)]
+struct CacheState {
+    verdicts: HashMap<CacheKey, bool>,
+    order: VecDeque<CacheKey>,
+}
+
+/// Bounded cache of proof verdicts, evicting the oldest first.
+pub struct VerificationCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+impl VerificationCache {
+    /// Create a cache holding up to `capacity` verdicts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Get the cache key of a proof.
+    ///
+    /// The proof hash covers the public inputs as well as the proof bytes, so
+    /// changing the fee or root gives a different key.
+    pub fn key(proof: &WithdrawalProof) -> CacheKey {
+        (*proof.nullifier_hash.as_bytes(), sha256(&proof.to_bytes()))
+    }
+
+    /// Get the cached verdict of a proof, if any.
+    pub fn get(&self, proof: &WithdrawalProof) -> Option<bool> {
+        self.state.lock().unwrap().verdicts.get(&Self::key(proof)).copied()
+    }
+
+    /// Check whether a proof is known to fail verification.
+    pub fn is_rejected(&self, proof: &WithdrawalProof) -> bool {
+        self.get(proof) == Some(false)
+    }
+
+    /// Record the verdict of a proof.
+    pub fn insert(&self, proof: &WithdrawalProof, valid: bool) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = Self::key(proof);
+        let mut state = self.state.lock().unwrap();
+        if state.verdicts.insert(key, valid).is_none() {
+            state.order.push_back(key);
+        }
+        while state.order.len() > self.capacity {
+            if let Some(oldest) = state.order.pop_front() {
+                state.verdicts.remove(&oldest);
+            }
+        }
+    }
+
+    /// Get the number of cached verdicts.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().verdicts.len()
+    }
+
+    /// Check whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::{NullifierHash, Recipient};
+
+    fn proof(nullifier: u8, fee: u128) -> WithdrawalProof {
+        WithdrawalProof::new(vec![1], [0u8; 32], NullifierHash::new([nullifier; 32]), Recipient::default()).with_relayer([0u8; 32], fee)
+    }
+
+    #[test]
+    fn test_cache_verdicts() {
+        let cache = VerificationCache::new(2);
+        cache.insert(&proof(1, 100), false);
+        assert!(cache.is_rejected(&proof(1, 100)));
+        // The same nullifier with other public inputs is a different proof
+        assert_eq!(cache.get(&proof(1, 200)), None);
+
+        cache.insert(&proof(2, 100), true);
+        assert_eq!(cache.get(&proof(2, 100)), Some(true));
+
+        // The oldest verdict is evicted first
+        cache.insert(&proof(3, 100), true);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&proof(1, 100)), None);
+
+        let disabled = VerificationCache::new(0);
+        disabled.insert(&proof(1, 100), false);
+        assert!(disabled.is_empty());
+    }
+}
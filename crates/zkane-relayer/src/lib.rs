@@ -0,0 +1,52 @@
+//! # ZKane Relayer
+//!
+//! A relayer broadcasts withdrawals on behalf of users who withdraw to a fresh
+//! address and therefore have no UTXO to pay Bitcoin transaction fees with.
+//! In exchange the relayer collects the fee declared in the withdrawal proof.
+//!
+//! ## Flow
+//!
+//! 1. A user generates a withdrawal proof that commits to the relayer's fee
+//!    output (`relayer_output_hash`) and the fee amount.
+//! 2. The user submits the withdrawal witness, the proof with the Merkle path
+//!    of its commitment, and the transaction outputs to `POST /relay`.
+//! 3. The relayer validates the request against its synced [`PrivacyPool`]
+//!    and queues it as a job.
+//! 4. A background worker builds the withdrawal with the
+//!    [`WithdrawalBuilder`], funds the output its witness envelope is revealed
+//!    from, then signs and broadcasts the withdrawal through the
+//!    [`DeezelProvider`]. Up to [`RelayerConfig::max_batch`] queued
+//!    withdrawals share one transaction, each proof bound to its own outputs
+//!    and revealed by its own input, which its protostone calls the pool's
+//!    `WithdrawBatched` with.
+//! 5. Once the pool contract has spent the withdrawal's nullifier, the
+//!    relayer records it in its pool and confirms the job.
+//!
+//! Job progress can be queried at `GET /jobs/{id}` and the relayer's terms
+//! (fee, denomination, current root, expected confirmation time) at
+//! `GET /status`, which wallets compare as quotes.
+//!
+//! ## Spam Protection
+//!
+//! `POST /relay` is rate limited per client IP with a token bucket. Before a
+//! proof is verified, the cheap checks run first: the terms and outputs, the
+//! root and the spent nullifiers. Verdicts are kept in a
+//! [`VerificationCache`], so a proof that failed is turned away at the API
+//! instead of being verified again.
+//!
+//! [`PrivacyPool`]: zkane_core::PrivacyPool
+//! [`WithdrawalBuilder`]: zkane_core::WithdrawalBuilder
+//! [`DeezelProvider`]: deezel_common::traits::DeezelProvider
+
+pub mod api;
+pub mod cache;
+pub mod queue;
+pub mod rate_limit;
+pub mod relayer;
+pub mod types;
+
+pub use cache::VerificationCache;
+pub use queue::{Job, JobQueue};
+pub use rate_limit::RateLimiter;
+pub use relayer::{Relayer, RelayerConfig, BLOCK_INTERVAL_SECS, CONFIRMATION_TIMEOUT_BLOCKS, MAX_REQUEST_OUTPUTS};
+pub use types::{JobStatus, OutputDescriptor, RelayRequest, RelayerError, RelayerStatus};
@@ -0,0 +1,147 @@
+//! # ZKane Relayer
+//!
+//! The entry point of the relayer daemon.
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use deezel_common::traits::DeezelProvider;
+use deezel_common::System;
+use deezel_sys::SystemDeezel;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use zkane_common::{derive_pool_id, ZKaneConfig, ZkAssetId};
+use zkane_core::PrivacyPool;
+use zkane_relayer::api::{self, ApiState};
+use zkane_relayer::{JobQueue, OutputDescriptor, RateLimiter, Relayer, RelayerConfig};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    #[clap(flatten)]
+    pub deezel_args: deezel_common::commands::Args,
+
+    /// Address to serve the HTTP API on
+    #[clap(long, default_value = "127.0.0.1:8420")]
+    pub listen: SocketAddr,
+
+    /// Asset id of the pool, as block:tx
+    #[clap(long)]
+    pub asset_id: String,
+
+    /// Pool denomination
+    #[clap(long)]
+    pub denomination: u128,
+
+    /// Alkane id of the pool contract, as block:tx (defaults to the pool the
+    /// factory derives for the asset and denomination)
+    #[clap(long)]
+    pub pool_id: Option<String>,
+
+    /// Pool merkle tree height
+    #[clap(long, default_value_t = 20)]
+    pub tree_height: u32,
+
+    /// Minimum relayer fee, in units of the pool asset
+    #[clap(long)]
+    pub min_fee: u128,
+
+    /// Hex-encoded scriptPubKey the relayer fee is paid to
+    #[clap(long)]
+    pub fee_script_pubkey: String,
+
+    /// Value in satoshis of the relayer fee output
+    #[clap(long, default_value_t = 546)]
+    pub fee_output_value: u64,
+
+    /// File with one deposit txid per line, used to sync the pool on startup
+    #[clap(long)]
+    pub deposits_file: Option<String>,
+
+    /// Maximum relay requests per client per minute, also the burst allowed
+    #[clap(long, default_value_t = 10)]
+    pub rate_limit: u32,
+
+    /// Number of proof verification verdicts to cache
+    #[clap(long, default_value_t = 10_000)]
+    pub cache_size: usize,
+
+    /// Maximum number of withdrawals batched in one transaction
+    #[clap(long, default_value_t = 1)]
+    pub max_batch: usize,
+
+    /// Blocks withdrawals are expected to confirm within, quoted at `/status`
+    #[clap(long, default_value_t = 1)]
+    pub confirmation_target: u32,
+}
+
+fn parse_asset_id(s: &str) -> Result<ZkAssetId> {
+    let (block, tx) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("asset id must be block:tx, got {}", s))?;
+    Ok(ZkAssetId {
+        block: block.parse()?,
+        tx: tx.parse()?,
+    })
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&args.deezel_args.log_level))
+        .init();
+
+    let deezel = SystemDeezel::new(&args.deezel_args).await?;
+    let provider = Arc::new(deezel.provider().clone_box());
+
+    let asset_id = parse_asset_id(&args.asset_id)?;
+    let pool_id = match &args.pool_id {
+        Some(pool_id) => parse_asset_id(pool_id)?,
+        None => derive_pool_id(&asset_id, args.denomination),
+    };
+    let config = ZKaneConfig::try_new(
+        asset_id,
+        args.denomination,
+        args.tree_height,
+        vec![],
+    )?;
+    let mut pool = PrivacyPool::new(config, provider.clone())?;
+
+    if let Some(path) = &args.deposits_file {
+        let txids = std::fs::read_to_string(path)?;
+        for txid in txids.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            pool.add_commitment(txid).await?;
+        }
+        log::info!("synced {} deposits", pool.commitment_count());
+    }
+
+    let relayer_config = RelayerConfig {
+        pool_id,
+        min_fee: args.min_fee,
+        fee_output: OutputDescriptor::parse(args.fee_output_value, &args.fee_script_pubkey)?,
+        max_batch: args.max_batch,
+        cache_size: args.cache_size,
+        confirmation_target: args.confirmation_target,
+    };
+    let queue = Arc::new(JobQueue::new());
+    let relayer = Relayer::new(pool, provider, relayer_config.clone(), queue.clone());
+
+    let state = ApiState {
+        queue,
+        limiter: Arc::new(RateLimiter::new(args.rate_limit, Duration::from_secs(60))),
+        config: relayer_config,
+        status: relayer.status_handle(),
+        cache: relayer.cache_handle(),
+    };
+    let app = api::router(state).into_make_service_with_connect_info::<SocketAddr>();
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    log::info!("relayer listening on {}", args.listen);
+
+    // Provider futures are not Send, so the worker runs on the local task set
+    let local = tokio::task::LocalSet::new();
+    local.spawn_local(relayer.run(Duration::from_secs(1)));
+    local.run_until(async { axum::serve(listener, app).await }).await?;
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! In-memory job queue for relay requests.
+
+use crate::types::{JobStatus, RelayRequest, RelayerError};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// A queued relay request and its current status.
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Job identifier
+    pub id: u64,
+    /// The request being relayed
+    pub request: RelayRequest,
+    /// Current status
+    pub status: JobStatus,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: u64,
+    pending: VecDeque<u64>,
+    jobs: HashMap<u64, Job>,
+}
+
+/// FIFO queue of relay jobs.
+///
+/// Jobs stay in the queue after they complete so their status can still be
+/// queried. Only one unfinished job per nullifier hash is accepted.
+#[derive(Default)]
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+}
+
+impl JobQueue {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a request to the queue and return its job id.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RelayerError::AlreadyQueued`] if an unfinished job exists for
+    /// the same nullifier hash.
+    pub fn push(&self, request: RelayRequest) -> Result<u64, RelayerError> {
+        let mut state = self.state.lock().unwrap();
+
+        let duplicate = state.jobs.values().any(|job| {
+            !job.status.is_final() && job.request.witness.proof.nullifier_hash == request.witness.proof.nullifier_hash
+        });
+        if duplicate {
+            return Err(RelayerError::AlreadyQueued);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.push_back(id);
+        state.jobs.insert(id, Job { id, request, status: JobStatus::Queued });
+        Ok(id)
+    }
+
+    /// Take the next queued job and mark it as broadcasting.
+    pub fn next(&self) -> Option<Job> {
+        self.next_batch(1).pop()
+    }
+
+    /// Take up to `max` queued jobs, oldest first, and mark them as
+    /// broadcasting.
+    pub fn next_batch(&self, max: usize) -> Vec<Job> {
+        let mut state = self.state.lock().unwrap();
+        let count = max.min(state.pending.len());
+        let ids: Vec<u64> = state.pending.drain(..count).collect();
+        ids.into_iter()
+            .filter_map(|id| {
+                let job = state.jobs.get_mut(&id)?;
+                job.status = JobStatus::Broadcasting;
+                Some(job.clone())
+            })
+            .collect()
+    }
+
+    /// Update the status of a job.
+    pub fn set_status(&self, id: u64, status: JobStatus) -> Result<(), RelayerError> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.jobs.get_mut(&id).ok_or(RelayerError::JobNotFound)?;
+        job.status = status;
+        Ok(())
+    }
+
+    /// Get the status of a job.
+    pub fn status(&self, id: u64) -> Result<JobStatus, RelayerError> {
+        let state = self.state.lock().unwrap();
+        state
+            .jobs
+            .get(&id)
+            .map(|job| job.status.clone())
+            .ok_or(RelayerError::JobNotFound)
+    }
+
+    /// Number of jobs that have not reached a final state.
+    pub fn pending_count(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.jobs.values().filter(|job| !job.status.is_final()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::{Commitment, MerklePath, NullifierHash, Recipient, WithdrawalProof, WithdrawalWitness};
+
+    fn request(nullifier: u8) -> RelayRequest {
+        RelayRequest {
+            witness: WithdrawalWitness {
+                proof: WithdrawalProof::new(vec![1], [0u8; 32], NullifierHash::new([nullifier; 32]), Recipient::default()),
+                path: MerklePath::new(vec![], vec![]).unwrap(),
+                leaf_index: 0,
+                commitment: Commitment::new([0u8; 32]),
+                outputs_hash: [0u8; 32],
+            },
+            outputs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_queue_fifo_and_status() {
+        let queue = JobQueue::new();
+        let first = queue.push(request(1)).unwrap();
+        let second = queue.push(request(2)).unwrap();
+        assert_eq!(queue.pending_count(), 2);
+
+        let job = queue.next().unwrap();
+        assert_eq!(job.id, first);
+        assert_eq!(queue.status(first).unwrap(), JobStatus::Broadcasting);
+
+        // Broadcast jobs are pending until the pool accepts them
+        queue
+            .set_status(first, JobStatus::Broadcast { txid: "abc".to_string() })
+            .unwrap();
+        assert_eq!(queue.pending_count(), 2);
+        queue
+            .set_status(first, JobStatus::Confirmed { txid: "abc".to_string() })
+            .unwrap();
+        assert_eq!(queue.pending_count(), 1);
+        assert_eq!(queue.next().unwrap().id, second);
+        assert!(queue.next().is_none());
+    }
+
+    #[test]
+    fn test_queue_next_batch() {
+        let queue = JobQueue::new();
+        let ids: Vec<u64> = (1..=3).map(|n| queue.push(request(n)).unwrap()).collect();
+
+        let batch = queue.next_batch(2);
+        assert_eq!(batch.iter().map(|job| job.id).collect::<Vec<_>>(), ids[..2]);
+        assert_eq!(queue.status(ids[1]).unwrap(), JobStatus::Broadcasting);
+        assert_eq!(queue.status(ids[2]).unwrap(), JobStatus::Queued);
+
+        assert_eq!(queue.next_batch(5).len(), 1);
+        assert!(queue.next_batch(5).is_empty());
+    }
+
+    #[test]
+    fn test_queue_rejects_duplicate_nullifier() {
+        let queue = JobQueue::new();
+        let id = queue.push(request(1)).unwrap();
+        assert!(matches!(queue.push(request(1)), Err(RelayerError::AlreadyQueued)));
+
+        // Allowed again once the previous attempt failed
+        queue
+            .set_status(id, JobStatus::Failed { reason: "test".to_string() })
+            .unwrap();
+        assert!(queue.push(request(1)).is_ok());
+    }
+}
@@ -0,0 +1,99 @@
+//! Token bucket rate limiting per client.
+//!
+//! Each client gets a bucket of tokens, one spent per request and refilled
+//! at a steady rate, so short bursts are allowed while a sustained flood is
+//! held to the refill rate.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Tokens left in a client's bucket.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Limits the number of requests a client can make within a time window.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    clients: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter allowing bursts of `max_requests`, refilled at
+    /// `max_requests` per `window`.
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a request from `client` and check whether it is allowed.
+    pub fn check(&self, client: &str) -> bool {
+        self.check_at(client, Instant::now())
+    }
+
+    /// Same as [`RateLimiter::check`] with an explicit current time.
+    pub fn check_at(&self, client: &str, now: Instant) -> bool {
+        let capacity = f64::from(self.max_requests);
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * capacity / self.window.as_secs_f64()).min(capacity)
+        };
+        let mut clients = self.clients.lock().unwrap();
+
+        // Drop full buckets so the map doesn't grow without bound
+        clients.retain(|_, bucket| refill(bucket) < capacity);
+
+        let bucket = clients.entry(client.to_string()).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at("a", now));
+        assert!(limiter.check_at("a", now));
+        assert!(!limiter.check_at("a", now));
+
+        // Other clients are tracked separately
+        assert!(limiter.check_at("b", now));
+
+        // A new window starts after the old one expires
+        assert!(limiter.check_at("a", now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_rate_limiter_refills_gradually() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at("a", now));
+        assert!(limiter.check_at("a", now));
+
+        // One token comes back every 30 seconds
+        assert!(!limiter.check_at("a", now + Duration::from_secs(20)));
+        assert!(limiter.check_at("a", now + Duration::from_secs(31)));
+        assert!(!limiter.check_at("a", now + Duration::from_secs(32)));
+
+        let disabled = RateLimiter::new(0, Duration::from_secs(60));
+        assert!(!disabled.check_at("a", now));
+    }
+}
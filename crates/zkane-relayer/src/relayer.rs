@@ -0,0 +1,1086 @@
+//! Validation and broadcasting of relay jobs.
+
+use crate::cache::VerificationCache;
+use crate::queue::{Job, JobQueue};
+use crate::types::{JobStatus, OutputDescriptor, RelayRequest, RelayerError, RelayerStatus};
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::psbt::{self, Psbt};
+use bitcoin::transaction::Version;
+use bitcoin::{Address, Amount, OutPoint, Transaction, TxIn, TxOut};
+use deezel_common::traits::{DeezelProvider, WalletProvider};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zkane_common::{NullifierHash, ZkAssetId};
+use zkane_core::provider::PoolProvider;
+use zkane_core::signer::finalize_psbt;
+use zkane_core::{
+    batched_withdrawal_protostone, EnvelopeCommit, FundingUtxo, PoolClient, PrivacyPool, ProviderSigner, TxSigner,
+    WithdrawalBuilder, WithdrawalTransaction,
+};
+
+/// Terms under which the relayer accepts withdrawals.
+#[derive(Debug, Clone)]
+pub struct RelayerConfig {
+    /// The pool contract withdrawals are relayed to
+    pub pool_id: ZkAssetId,
+    /// Minimum fee accepted, in units of the pool asset
+    pub min_fee: u128,
+    /// The output the relayer fee must be paid to
+    pub fee_output: OutputDescriptor,
+    /// Maximum number of withdrawals batched in one transaction
+    ///
+    /// Batching spreads the base cost of a transaction, its funding input
+    /// and change, over several withdrawals. One disables batching.
+    pub max_batch: usize,
+    /// Number of proof verdicts kept in the [`VerificationCache`]
+    pub cache_size: usize,
+    /// Blocks the relayer's withdrawals are expected to confirm within, as
+    /// quoted to users
+    pub confirmation_target: u32,
+}
+
+/// Maximum number of outputs a relay request may ask for
+pub const MAX_REQUEST_OUTPUTS: usize = 16;
+
+/// Average seconds between blocks, for confirmation time estimates
+pub const BLOCK_INTERVAL_SECS: u64 = 600;
+
+/// Blocks a broadcast withdrawal has to be accepted by the pool within
+/// before its job fails
+pub const CONFIRMATION_TIMEOUT_BLOCKS: u64 = 144;
+
+impl RelayerConfig {
+    /// Get the expected seconds from submitting a withdrawal to its
+    /// confirmation.
+    pub fn estimated_confirmation_secs(&self) -> u64 {
+        u64::from(self.confirmation_target.max(1)) * BLOCK_INTERVAL_SECS
+    }
+
+    /// Check a request against the relayer's terms.
+    ///
+    /// This only looks at the request itself and does not need the pool state,
+    /// so it is done before a job is queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fee is too low, the proof pays a different
+    /// relayer, the fee output is missing from the requested outputs, or the
+    /// outputs are malformed.
+    pub fn check_terms(&self, request: &RelayRequest) -> Result<(), RelayerError> {
+        if request.outputs.is_empty() {
+            return Err(RelayerError::InvalidRequest("no outputs".to_string()));
+        }
+        if request.outputs.len() > MAX_REQUEST_OUTPUTS {
+            return Err(RelayerError::InvalidRequest(format!(
+                "{} outputs, at most {} are allowed",
+                request.outputs.len(),
+                MAX_REQUEST_OUTPUTS
+            )));
+        }
+        for output in &request.outputs {
+            if output.value == 0 {
+                return Err(RelayerError::InvalidRequest("zero-value output".to_string()));
+            }
+            output.script()?;
+        }
+
+        let proof = &request.witness.proof;
+        if proof.fee < self.min_fee {
+            return Err(RelayerError::FeeTooLow {
+                minimum: self.min_fee,
+                fee: proof.fee,
+            });
+        }
+
+        if proof.relayer_output_hash != self.fee_output.hash() {
+            return Err(RelayerError::WrongRelayer);
+        }
+
+        if !request.outputs.contains(&self.fee_output) {
+            return Err(RelayerError::InvalidRequest(
+                "relayer fee output missing from outputs".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A broadcast withdrawal waiting for the pool to spend its nullifier.
+struct InFlight {
+    job_id: u64,
+    nullifier_hash: NullifierHash,
+    txid: String,
+    broadcast_height: u64,
+}
+
+/// Processes queued relay jobs against a synced privacy pool.
+///
+/// The relayer owns the pool so the spent nullifier set is updated once the
+/// pool contract accepts a broadcast withdrawal, and publishes a
+/// [`RelayerStatus`] snapshot for the HTTP API. Withdrawals are funded and
+/// signed by the provider's wallet unless another signer is set with
+/// [`Relayer::with_signer`].
+pub struct Relayer<P: DeezelProvider> {
+    pool: PrivacyPool<P>,
+    client: PoolClient<P>,
+    provider: Arc<P>,
+    signer: Box<dyn TxSigner>,
+    config: RelayerConfig,
+    queue: Arc<JobQueue>,
+    status: Arc<Mutex<RelayerStatus>>,
+    cache: Arc<VerificationCache>,
+    in_flight: Vec<InFlight>,
+    stranded_commits: Vec<OutPoint>,
+    checked_height: u64,
+}
+
+impl<P: DeezelProvider + 'static> Relayer<P> {
+    /// Create a relayer for a synced pool.
+    pub fn new(pool: PrivacyPool<P>, provider: Arc<P>, config: RelayerConfig, queue: Arc<JobQueue>) -> Self {
+        let status = Arc::new(Mutex::new(RelayerStatus {
+            min_fee: config.min_fee,
+            denomination: pool.config().denomination,
+            fee_output_hash: hex::encode(config.fee_output.hash()),
+            fee_output: config.fee_output.clone(),
+            merkle_root: String::new(),
+            commitment_count: 0,
+            pending_jobs: 0,
+            estimated_confirmation_secs: config.estimated_confirmation_secs(),
+        }));
+        let relayer = Self {
+            cache: Arc::new(VerificationCache::new(config.cache_size)),
+            pool,
+            client: PoolClient::new(provider.clone(), config.pool_id),
+            signer: Box::new(ProviderSigner::new(provider.clone())),
+            provider,
+            config,
+            queue,
+            status,
+            in_flight: Vec::new(),
+            stranded_commits: Vec::new(),
+            checked_height: 0,
+        };
+        relayer.publish_status();
+        relayer
+    }
+
+    /// Sign withdrawals with `signer` instead of the provider's wallet.
+    ///
+    /// The signer must fund the envelope commit transaction, whose PSBT only
+    /// holds outputs, and sign the withdrawal's script-path spend of the
+    /// commit output with the provider's internal key. It must not add
+    /// inputs or outputs to the withdrawal, such as change: the envelope
+    /// commits to the hash of its outputs, so the withdrawal is not broadcast
+    /// if they changed.
+    pub fn with_signer(mut self, signer: Box<dyn TxSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Get the envelope commit outputs whose withdrawal failed to broadcast
+    /// after their commit transaction went out.
+    ///
+    /// Nothing reveals them, so the wallet should sweep them back.
+    pub fn stranded_commits(&self) -> &[OutPoint] {
+        &self.stranded_commits
+    }
+
+    /// Get the relayer's terms.
+    pub fn config(&self) -> &RelayerConfig {
+        &self.config
+    }
+
+    /// Get a shared handle to the published status.
+    pub fn status_handle(&self) -> Arc<Mutex<RelayerStatus>> {
+        self.status.clone()
+    }
+
+    /// Get a shared handle to the proof verification cache.
+    ///
+    /// The API checks it to turn away proofs that already failed.
+    pub fn cache_handle(&self) -> Arc<VerificationCache> {
+        self.cache.clone()
+    }
+
+    /// Validate a request against the relayer's terms and the pool state.
+    ///
+    /// Cheap checks run first: the terms, the proof's root against the
+    /// pool's and the nullifier against the spent set. Only then is the proof
+    /// verified, unless its verdict is cached.
+    pub fn validate(&self, request: &RelayRequest) -> Result<(), RelayerError> {
+        self.config.check_terms(request)?;
+        self.precheck(request)?;
+
+        let proof = &request.witness.proof;
+        let valid = match self.cache.get(proof) {
+            Some(valid) => valid,
+            None => {
+                let valid = self.pool.verify_withdrawal_proof(proof);
+                self.cache.insert(proof, valid);
+                valid
+            }
+        };
+        if !valid {
+            return Err(RelayerError::ProofRejected);
+        }
+
+        Ok(())
+    }
+
+    /// Check the parts of a request that depend on the pool state without
+    /// verifying the proof.
+    fn precheck(&self, request: &RelayRequest) -> Result<(), RelayerError> {
+        let proof = &request.witness.proof;
+        if proof.merkle_root != self.pool.merkle_root() {
+            return Err(RelayerError::ProofRejected);
+        }
+        if self.pool.is_nullifier_spent(proof.nullifier_hash.as_bytes()) {
+            return Err(RelayerError::NullifierSpent);
+        }
+        Ok(())
+    }
+
+    /// Process the next queued job, if any.
+    ///
+    /// # Returns
+    ///
+    /// The id of the processed job, or `None` if the queue was empty.
+    pub async fn process_next(&mut self) -> Option<u64> {
+        let job = self.queue.next()?;
+        let id = job.id;
+        self.relay_jobs(vec![job]).await;
+        Some(id)
+    }
+
+    /// Process up to [`RelayerConfig::max_batch`] queued jobs in a single
+    /// transaction.
+    ///
+    /// Jobs that no longer validate fail on their own without holding back
+    /// the rest of the batch.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the processed jobs, empty if the queue was empty.
+    pub async fn process_batch(&mut self) -> Vec<u64> {
+        let jobs = self.queue.next_batch(self.config.max_batch.max(1));
+        let ids = jobs.iter().map(|job| job.id).collect();
+        self.relay_jobs(jobs).await;
+        ids
+    }
+
+    /// Record the broadcast withdrawals the pool accepted.
+    ///
+    /// Whenever the chain tip moves, the nullifiers of broadcast withdrawals
+    /// are checked against the pool contract. Spent ones are recorded in the
+    /// synced pool and their jobs confirmed; ones still unspent
+    /// [`CONFIRMATION_TIMEOUT_BLOCKS`] after their broadcast fail.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the confirmed jobs.
+    pub async fn poll_confirmations(&mut self) -> Vec<u64> {
+        if self.in_flight.is_empty() {
+            return Vec::new();
+        }
+        let height = match PoolProvider::get_tip_height(self.provider.as_ref()).await {
+            Ok(height) if height > self.checked_height => height,
+            Ok(_) => return Vec::new(),
+            Err(e) => {
+                log::warn!("failed to get the chain tip: {}", e);
+                return Vec::new();
+            }
+        };
+        let nullifier_hashes: Vec<NullifierHash> = self.in_flight.iter().map(|w| w.nullifier_hash).collect();
+        let spent = match self.client.check_spent(&nullifier_hashes).await {
+            Ok(spent) => spent,
+            Err(e) => {
+                log::warn!("failed to check broadcast withdrawals: {}", e);
+                return Vec::new();
+            }
+        };
+        self.checked_height = height;
+
+        let mut confirmed = Vec::new();
+        for (withdrawal, spent) in std::mem::take(&mut self.in_flight).into_iter().zip(spent) {
+            if spent {
+                if let Err(e) = self.pool.process_withdrawal(withdrawal.nullifier_hash.as_bytes()) {
+                    log::warn!("relay job {} confirmed, but not recorded: {}", withdrawal.job_id, e);
+                }
+                let _ = self.queue.set_status(withdrawal.job_id, JobStatus::Confirmed { txid: withdrawal.txid });
+                confirmed.push(withdrawal.job_id);
+            } else if height >= withdrawal.broadcast_height + CONFIRMATION_TIMEOUT_BLOCKS {
+                let error = RelayerError::BroadcastFailed(format!(
+                    "{} was not accepted by the pool within {} blocks",
+                    withdrawal.txid, CONFIRMATION_TIMEOUT_BLOCKS
+                ));
+                self.fail(withdrawal.job_id, &error);
+            } else {
+                self.in_flight.push(withdrawal);
+            }
+        }
+
+        self.publish_status();
+        confirmed
+    }
+
+    /// Process jobs forever, sleeping whenever the queue is empty.
+    pub async fn run(mut self, poll_interval: Duration) {
+        loop {
+            self.poll_confirmations().await;
+            if self.process_batch().await.is_empty() {
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    }
+
+    async fn relay_jobs(&mut self, jobs: Vec<Job>) {
+        let mut valid = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            match self.validate(&job.request) {
+                Ok(()) => valid.push(job),
+                Err(e) => self.fail(job.id, &e),
+            }
+        }
+
+        // Whether the withdrawals are batched is settled before they are
+        // built, as batched ones commit to a different outputs window
+        let batched = valid.len() > 1;
+        let mut batch = Vec::with_capacity(valid.len());
+        let mut withdrawals = Vec::with_capacity(valid.len());
+        for job in valid {
+            match self.build_withdrawal(&job.request, batched).await {
+                Ok(withdrawal) => {
+                    batch.push(job);
+                    withdrawals.push(withdrawal);
+                }
+                Err(e) => self.fail(job.id, &e),
+            }
+        }
+
+        if !batch.is_empty() {
+            match self.relay(withdrawals, batched).await {
+                Ok(txid) => {
+                    // The confirmation timeout counts from the current tip
+                    let height = PoolProvider::get_tip_height(self.provider.as_ref())
+                        .await
+                        .unwrap_or(self.checked_height);
+                    for job in batch {
+                        // The job was taken from the queue, so it always exists
+                        let _ = self.queue.set_status(job.id, JobStatus::Broadcast { txid: txid.clone() });
+                        self.in_flight.push(InFlight {
+                            job_id: job.id,
+                            nullifier_hash: job.request.witness.proof.nullifier_hash,
+                            txid: txid.clone(),
+                            broadcast_height: height,
+                        });
+                    }
+                }
+                Err(e) => batch.iter().for_each(|job| self.fail(job.id, &e)),
+            }
+        }
+
+        self.publish_status();
+    }
+
+    /// Broadcast built withdrawals in one transaction.
+    ///
+    /// A batch is assembled by [`build_batch_transaction`]; a single
+    /// withdrawal keeps the transaction it was built with.
+    ///
+    /// # Returns
+    ///
+    /// The txid of the withdrawal transaction.
+    ///
+    /// The commit transaction and the withdrawal are both signed and checked
+    /// before either is broadcast, so a withdrawal the signer spoiled strands
+    /// no commit outputs. If the withdrawal fails to broadcast after its
+    /// commit transaction went out, the commit outputs are recorded in
+    /// [`Relayer::stranded_commits`].
+    async fn relay(&mut self, withdrawals: Vec<WithdrawalTransaction>, batched: bool) -> Result<String, RelayerError> {
+        let (commit_tx, reveals) = self.sign_envelope_commits(&withdrawals).await?;
+        let commits: Vec<OutPoint> = reveals.iter().map(|(txin, _)| txin.previous_output).collect();
+        let psbt = if batched {
+            build_batch_transaction(&self.config.pool_id, withdrawals, reveals)?
+        } else {
+            let mut psbt = withdrawals.into_iter().next().expect("relayed without withdrawals").psbt;
+            for (txin, input) in reveals {
+                psbt.unsigned_tx.input.push(txin);
+                psbt.inputs.push(input);
+            }
+            psbt
+        };
+        let tx = self.sign_withdrawal(psbt).await?;
+
+        self.broadcast(&commit_tx).await?;
+        let result = self.broadcast(&tx).await;
+        if let Err(e) = &result {
+            log::warn!(
+                "withdrawal {} failed to broadcast, stranding its commit outputs in {}: {}",
+                tx.compute_txid(),
+                commit_tx.compute_txid(),
+                e
+            );
+            self.stranded_commits.extend(commits);
+        }
+        result
+    }
+
+    /// Broadcast a signed transaction.
+    async fn broadcast(&self, tx: &Transaction) -> Result<String, RelayerError> {
+        PoolProvider::broadcast(self.provider.as_ref(), &serialize_hex(tx))
+            .await
+            .map_err(broadcast_failed)
+    }
+
+    /// Sign a withdrawal, unless the signer changed it.
+    ///
+    /// Each envelope commits to the hash of its withdrawal's outputs, and
+    /// each batched protostone to the index of its envelope input, so a
+    /// withdrawal whose signer added inputs or outputs would be rejected by
+    /// the pool. The inputs spend the signed commit transaction's outputs.
+    async fn sign_withdrawal(&self, psbt: Psbt) -> Result<Transaction, RelayerError> {
+        let unsigned = psbt.unsigned_tx.clone();
+        let signed = self.signer.sign_psbt(psbt).await.map_err(broadcast_failed)?;
+        let tx = finalize_psbt(signed).map_err(broadcast_failed)?;
+
+        let spent = |tx: &Transaction| tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+        if tx.output != unsigned.output || spent(&tx) != spent(&unsigned) {
+            return Err(RelayerError::BroadcastFailed(
+                "the signer changed the inputs or outputs of the withdrawal".to_string(),
+            ));
+        }
+        Ok(tx)
+    }
+
+    /// Sign the transaction committing to the withdrawals' envelopes.
+    ///
+    /// Each withdrawal's envelope is revealed by spending a commit output,
+    /// which the signer funds in a transaction of its own. The commit output
+    /// covers the withdrawal's outputs and fee, so the withdrawal needs no
+    /// other input and gets no change.
+    ///
+    /// # Returns
+    ///
+    /// The signed commit transaction, not yet broadcast, and the inputs
+    /// revealing the envelopes, in the order of the withdrawals.
+    async fn sign_envelope_commits(
+        &self,
+        withdrawals: &[WithdrawalTransaction],
+    ) -> Result<(Transaction, Vec<(TxIn, psbt::Input)>), RelayerError> {
+        let key = WalletProvider::get_internal_key(self.provider.as_ref())
+            .await
+            .map_err(broadcast_failed)?;
+        let mut commits = Vec::with_capacity(withdrawals.len());
+        for withdrawal in withdrawals {
+            let commit = EnvelopeCommit::new(key, &withdrawal.envelope).map_err(broadcast_failed)?;
+            let output = TxOut {
+                value: withdrawal.psbt.unsigned_tx.output.iter().map(|output| output.value).sum::<Amount>()
+                    + withdrawal.fee,
+                script_pubkey: commit.script_pubkey.clone(),
+            };
+            commits.push((commit, output));
+        }
+
+        let commit_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: commits.iter().map(|(_, output)| output.clone()).collect(),
+        };
+        let psbt = Psbt::from_unsigned_tx(commit_tx).map_err(broadcast_failed)?;
+        let signed = self.signer.sign_psbt(psbt).await.map_err(broadcast_failed)?;
+        let commit_tx = finalize_psbt(signed).map_err(broadcast_failed)?;
+        let txid = commit_tx.compute_txid();
+
+        // Each envelope commits to its own script, so the outputs are unique
+        let reveals = commits
+            .iter()
+            .map(|(commit, output)| {
+                let vout = commit_tx.output.iter().position(|candidate| candidate == output).ok_or_else(|| {
+                    RelayerError::BroadcastFailed("the signer dropped an envelope commit output".to_string())
+                })?;
+                let utxo = FundingUtxo {
+                    outpoint: OutPoint::new(txid, vout as u32),
+                    txout: output.clone(),
+                };
+                Ok(commit.reveal_input(&utxo))
+            })
+            .collect::<Result<Vec<_>, RelayerError>>()?;
+        Ok((commit_tx, reveals))
+    }
+
+    /// Build the unsigned withdrawal of a request.
+    ///
+    /// The recipients are the requested outputs other than the relayer's fee
+    /// output, in the order requested. The PSBT has no inputs yet.
+    async fn build_withdrawal(
+        &self,
+        request: &RelayRequest,
+        batched: bool,
+    ) -> Result<WithdrawalTransaction, RelayerError> {
+        let fee_output = TxOut {
+            value: Amount::from_sat(self.config.fee_output.value),
+            script_pubkey: self.config.fee_output.script()?,
+        };
+        let mut builder = WithdrawalBuilder::new(self.provider.clone(), self.config.pool_id).relayer(fee_output);
+        if batched {
+            builder = builder.batched();
+        }
+
+        let mut recipients = request.outputs.clone();
+        if let Some(index) = recipients.iter().position(|output| *output == self.config.fee_output) {
+            recipients.remove(index);
+        }
+        for output in recipients {
+            let address = Address::from_script(&output.script()?, self.provider.get_network())
+                .map_err(|e| RelayerError::InvalidRequest(format!("unsupported output script: {}", e)))?;
+            builder = builder.recipient(address.to_string(), Amount::from_sat(output.value));
+        }
+
+        let witness = request.witness.clone();
+        builder
+            .build(witness.proof, witness.path, witness.leaf_index, witness.commitment)
+            .await
+            .map_err(|e| RelayerError::InvalidRequest(e.to_string()))
+    }
+
+    fn fail(&self, id: u64, error: &RelayerError) {
+        log::warn!("relay job {} failed: {}", id, error);
+        let _ = self.queue.set_status(id, JobStatus::Failed { reason: error.to_string() });
+    }
+
+    fn publish_status(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.merkle_root = hex::encode(self.pool.merkle_root());
+        status.commitment_count = self.pool.commitment_count();
+        status.pending_jobs = self.queue.pending_count();
+    }
+}
+
+fn broadcast_failed(error: impl std::fmt::Display) -> RelayerError {
+    RelayerError::BroadcastFailed(error.to_string())
+}
+
+/// Build one unsigned transaction for a batch of withdrawals.
+///
+/// Input `i` is `reveals[i]`, revealing the envelope of withdrawal `i`. The
+/// outputs of each withdrawal are kept together and in order, as each proof
+/// commits to the hash of its own outputs; the pool finds them with
+/// [`zkane_common::find_outputs_window`]. The last output is a runestone with
+/// one protostone per withdrawal calling the pool's `WithdrawBatched` with its
+/// input, paying out to the withdrawal's first output.
+///
+/// # Arguments
+///
+/// * `pool_id` - The pool contract
+/// * `withdrawals` - The withdrawals, built with [`WithdrawalBuilder::batched`]
+/// * `reveals` - The inputs revealing the withdrawals' envelopes, in order
+pub fn build_batch_transaction(
+    pool_id: &ZkAssetId,
+    withdrawals: Vec<WithdrawalTransaction>,
+    reveals: Vec<(TxIn, psbt::Input)>,
+) -> Result<Psbt, RelayerError> {
+    if withdrawals.len() != reveals.len() {
+        return Err(RelayerError::BroadcastFailed(format!(
+            "{} withdrawals, but {} envelope inputs",
+            withdrawals.len(),
+            reveals.len()
+        )));
+    }
+
+    let mut output = Vec::new();
+    let mut pointers = Vec::with_capacity(withdrawals.len());
+    for withdrawal in withdrawals {
+        pointers.push(output.len() as u32);
+        output.extend(withdrawal.psbt.unsigned_tx.output);
+    }
+    output.push(TxOut {
+        value: Amount::ZERO,
+        script_pubkey: batched_withdrawal_protostone(pool_id, &pointers).map_err(broadcast_failed)?,
+    });
+
+    let (input, inputs): (Vec<TxIn>, Vec<psbt::Input>) = reveals.into_iter().unzip();
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input,
+        output,
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(broadcast_failed)?;
+    psbt.inputs = inputs;
+    Ok(psbt)
+}
+
+/// Build the unsigned withdrawal transaction from the requested outputs.
+pub fn build_transaction(outputs: &[OutputDescriptor]) -> Result<Transaction, RelayerError> {
+    let output = outputs
+        .iter()
+        .map(|o| {
+            Ok(TxOut {
+                value: Amount::from_sat(o.value),
+                script_pubkey: o.script()?,
+            })
+        })
+        .collect::<Result<Vec<_>, RelayerError>>()?;
+
+    Ok(Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alkanes_support::cellpack::Cellpack;
+    use async_trait::async_trait;
+    use alkanes_support::witness::find_witness_payload;
+    use bitcoin::consensus::encode::deserialize_hex;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{schnorr, Message, Secp256k1};
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use bitcoin::taproot::{LeafVersion, TapLeafHash};
+    use metashrew_support::utils::decode_varint_list;
+    use ordinals::{Artifact, Runestone};
+    use protorune_support::protostone::Protostone;
+    use std::io::Cursor;
+    use zkane_abi::pool::WITHDRAW_BATCHED;
+    use zkane_common::{
+        calculate_outputs_hash, derive_pool_id, find_outputs_window, Commitment, EnvelopeFormat, MerklePath,
+        Recipient, WithdrawalProof, WithdrawalWitness, ZKaneConfig,
+    };
+    use zkane_core::mock_provider::{MockFailure, MockProvider};
+    use zkane_core::withdrawal::envelope_script;
+
+    fn fee_output() -> OutputDescriptor {
+        OutputDescriptor::new(546, "0014".to_string() + &"11".repeat(20))
+    }
+
+    fn recipient_output() -> OutputDescriptor {
+        OutputDescriptor::new(546, "0014".to_string() + &"22".repeat(20))
+    }
+
+    fn create_relayer() -> Relayer<MockProvider> {
+        let config = ZKaneConfig::new(ZkAssetId { block: 2, tx: 1 }, 1000000, 4, vec![]);
+        let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+        let pool = PrivacyPool::new(config, provider.clone()).unwrap();
+        let relayer_config = RelayerConfig {
+            pool_id: derive_pool_id(&ZkAssetId { block: 2, tx: 1 }, 1000000),
+            min_fee: 100,
+            fee_output: fee_output(),
+            max_batch: 8,
+            cache_size: 16,
+            confirmation_target: 2,
+        };
+        Relayer::new(pool, provider, relayer_config, Arc::new(JobQueue::new()))
+    }
+
+    /// Script the pool's answer to whether the nullifiers are spent.
+    fn script_spent(relayer: &Relayer<MockProvider>, spent: &[(u8, bool)]) {
+        let mut params = vec![zkane_abi::pool::ARE_NULLIFIERS_SPENT, spent.len() as u128];
+        for (nullifier, _) in spent {
+            let (low, high) = NullifierHash::new([*nullifier; 32]).to_u128_pair();
+            params.extend([low, high]);
+        }
+        let params = params.iter().map(u128::to_string).collect::<Vec<_>>().join(",");
+        let flags: Vec<u8> = spent.iter().map(|(_, spent)| *spent as u8).collect();
+        relayer
+            .provider
+            .add_simulation_data(&relayer.config.pool_id.to_string(), &params, &flags);
+    }
+
+    /// Decode the pool calls of a transaction's runestone, with the output
+    /// each pays out to.
+    fn pool_calls(relayer: &Relayer<MockProvider>, tx: &Transaction) -> Vec<(Vec<u128>, Option<u32>)> {
+        let Some(Artifact::Runestone(runestone)) = Runestone::decipher(tx) else {
+            panic!("no runestone");
+        };
+        Protostone::from_runestone(&runestone)
+            .unwrap()
+            .into_iter()
+            .map(|protostone| {
+                let values = decode_varint_list(&mut Cursor::new(protostone.message)).unwrap();
+                let cellpack = Cellpack::try_from(values).unwrap();
+                assert_eq!(ZkAssetId::from(cellpack.target), relayer.config.pool_id);
+                (cellpack.inputs, protostone.pointer)
+            })
+            .collect()
+    }
+
+    fn request(relayer: &Relayer<MockProvider>, fee: u128) -> RelayRequest {
+        request_with_nullifier(relayer, fee, 1)
+    }
+
+    fn request_with_nullifier(relayer: &Relayer<MockProvider>, fee: u128, nullifier: u8) -> RelayRequest {
+        let proof = WithdrawalProof::new(vec![1], relayer.pool.merkle_root(), NullifierHash::new([nullifier; 32]), Recipient::default())
+            .with_relayer(fee_output().hash(), fee);
+        let witness = WithdrawalWitness {
+            proof,
+            path: MerklePath::new(vec![[5u8; 32]; 4], vec![false; 4]).unwrap(),
+            leaf_index: 0,
+            commitment: Commitment::new([nullifier; 32]),
+            outputs_hash: [0u8; 32],
+        };
+        RelayRequest {
+            witness,
+            outputs: vec![recipient_output(), fee_output()],
+        }
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_request() {
+        let relayer = create_relayer();
+        assert!(relayer.validate(&request(&relayer, 100)).is_ok());
+    }
+
+    #[test]
+    fn test_status_quotes_confirmation_time() {
+        let relayer = create_relayer();
+        let status = relayer.status_handle().lock().unwrap().clone();
+        assert_eq!(status.estimated_confirmation_secs, 2 * BLOCK_INTERVAL_SECS);
+
+        // Relayers from before the estimate quote none
+        let mut json = serde_json::to_value(&status).unwrap();
+        json.as_object_mut().unwrap().remove("estimated_confirmation_secs");
+        let old: RelayerStatus = serde_json::from_value(json).unwrap();
+        assert_eq!(old.estimated_confirmation_secs, 0);
+    }
+
+    #[test]
+    fn test_validate_rejects_low_fee() {
+        let relayer = create_relayer();
+        assert!(matches!(
+            relayer.validate(&request(&relayer, 99)),
+            Err(RelayerError::FeeTooLow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_other_relayer() {
+        let relayer = create_relayer();
+        let mut req = request(&relayer, 100);
+        req.witness.proof.relayer_output_hash = recipient_output().hash();
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::WrongRelayer)));
+
+        let mut req = request(&relayer, 100);
+        req.outputs = vec![recipient_output()];
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_fee_output_hash_ignores_hex_case() {
+        let upper = OutputDescriptor::new(546, "0014".to_string() + &"AB".repeat(20));
+        let parsed = OutputDescriptor::parse(546, &upper.script_pubkey).unwrap();
+        assert_eq!(parsed.script_pubkey, "0014".to_string() + &"ab".repeat(20));
+
+        // Proofs commit to the hash of the output the pool sees
+        let tx_out = TxOut { value: Amount::from_sat(546), script_pubkey: upper.script().unwrap() };
+        assert_eq!(upper.hash(), calculate_outputs_hash(&[tx_out]));
+        assert_eq!(upper.hash(), parsed.hash());
+
+        assert!(matches!(OutputDescriptor::parse(546, "0014zz"), Err(RelayerError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_validate_rejects_stale_root() {
+        let relayer = create_relayer();
+        let mut req = request(&relayer, 100);
+        req.witness.proof.merkle_root = [9u8; 32];
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::ProofRejected)));
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed_outputs() {
+        let relayer = create_relayer();
+        let mut req = request(&relayer, 100);
+        req.outputs[0].value = 0;
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::InvalidRequest(_))));
+
+        let mut req = request(&relayer, 100);
+        req.outputs[0].script_pubkey = "zz".to_string();
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::InvalidRequest(_))));
+
+        let mut req = request(&relayer, 100);
+        req.outputs.resize(MAX_REQUEST_OUTPUTS + 1, recipient_output());
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_validate_caches_verdicts() {
+        let mut relayer = create_relayer();
+        let req = request(&relayer, 100);
+        assert!(relayer.validate(&req).is_ok());
+        assert_eq!(relayer.cache.get(&req.witness.proof), Some(true));
+
+        // A cached rejection is returned without verifying again
+        let other = request_with_nullifier(&relayer, 100, 2);
+        relayer.cache.insert(&other.witness.proof, false);
+        assert!(matches!(relayer.validate(&other), Err(RelayerError::ProofRejected)));
+
+        // Spent nullifiers are caught before the cache is consulted
+        relayer.pool.process_withdrawal(&[1u8; 32]).unwrap();
+        assert!(matches!(relayer.validate(&req), Err(RelayerError::NullifierSpent)));
+    }
+
+    #[tokio::test]
+    async fn test_process_broadcasts_through_signer() {
+        let mut relayer = create_relayer();
+        let request = request(&relayer, 100);
+        let id = relayer.queue.push(request.clone()).unwrap();
+
+        assert_eq!(relayer.process_next().await, Some(id));
+        let Ok(JobStatus::Broadcast { txid }) = relayer.queue.status(id) else {
+            panic!("job was not broadcast");
+        };
+
+        // The envelope commit, then the withdrawal revealing it
+        let broadcasts = relayer.provider.broadcasts();
+        assert_eq!(broadcasts.len(), 2);
+        let commit: Transaction = deserialize_hex(&broadcasts[0]).unwrap();
+        let reveal: Transaction = deserialize_hex(&broadcasts[1]).unwrap();
+        assert_eq!(reveal.compute_txid().to_string(), txid);
+        assert_eq!(reveal.input.len(), 1);
+        assert_eq!(reveal.input[0].previous_output, OutPoint::new(commit.compute_txid(), 0));
+
+        // The recipient, the relayer's fee and the protostone, no change
+        assert_eq!(reveal.output.len(), 3);
+        assert_eq!(reveal.output[0].script_pubkey, recipient_output().script().unwrap());
+        assert_eq!(reveal.output[1].script_pubkey, fee_output().script().unwrap());
+        assert!(reveal.output[2].script_pubkey.is_op_return());
+
+        // The witness reveals the request's envelope, bound to these outputs
+        let mut expected = request.witness.clone();
+        expected.outputs_hash = calculate_outputs_hash(&reveal.output);
+        let envelope = expected.to_envelope(EnvelopeFormat::Compressed).unwrap();
+        let key = WalletProvider::get_internal_key(relayer.provider.as_ref()).await.unwrap();
+        let script = envelope_script(&key, &envelope);
+        assert_eq!(reveal.input[0].witness.len(), 3);
+        assert_eq!(reveal.input[0].witness.nth(1).unwrap(), script.as_bytes());
+        let revealed = WithdrawalWitness::from_envelope(&find_witness_payload(&reveal, 0).unwrap()).unwrap();
+        assert_eq!(revealed.outputs_hash, calculate_outputs_hash(&reveal.output));
+
+        // Signed by the wallet's key for the envelope script
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        let sighash = SighashCache::new(&reveal)
+            .taproot_script_spend_signature_hash(0, &Prevouts::All(&commit.output), leaf_hash, TapSighashType::Default)
+            .unwrap();
+        let signature = schnorr::Signature::from_slice(reveal.input[0].witness.nth(0).unwrap()).unwrap();
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &Message::from_digest(sighash.to_byte_array()), &key)
+            .unwrap();
+        assert!(commit.output[0].value > reveal.output.iter().map(|output| output.value).sum::<Amount>());
+
+        // The nullifier is only recorded once the pool has spent it
+        assert!(!relayer.pool.is_nullifier_spent(&[1u8; 32]));
+        script_spent(&relayer, &[(1, false)]);
+        relayer.provider.mine_empty_blocks(1);
+        assert!(relayer.poll_confirmations().await.is_empty());
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Broadcast { .. })));
+
+        script_spent(&relayer, &[(1, true)]);
+        // Nothing is checked until the next block
+        assert!(relayer.poll_confirmations().await.is_empty());
+        relayer.provider.mine_empty_blocks(1);
+        assert_eq!(relayer.poll_confirmations().await, vec![id]);
+        assert_eq!(relayer.queue.status(id).unwrap(), JobStatus::Confirmed { txid });
+        assert!(relayer.pool.is_nullifier_spent(&[1u8; 32]));
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_withdrawal_times_out() {
+        let mut relayer = create_relayer();
+        let id = relayer.queue.push(request(&relayer, 100)).unwrap();
+        relayer.process_next().await;
+        script_spent(&relayer, &[(1, false)]);
+
+        relayer.provider.mine_empty_blocks(CONFIRMATION_TIMEOUT_BLOCKS - 1);
+        relayer.poll_confirmations().await;
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Broadcast { .. })));
+
+        relayer.provider.mine_empty_blocks(1);
+        assert!(relayer.poll_confirmations().await.is_empty());
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Failed { .. })));
+        assert!(!relayer.pool.is_nullifier_spent(&[1u8; 32]));
+
+        // The withdrawal may be submitted again
+        assert!(relayer.queue.push(request(&relayer, 100)).is_ok());
+    }
+
+    /// Signs with the wallet after adding a change output.
+    struct ChangeSigner(ProviderSigner<MockProvider>);
+
+    #[async_trait(?Send)]
+    impl TxSigner for ChangeSigner {
+        async fn sign_psbt(&self, mut psbt: Psbt) -> zkane_common::ZKaneResult<Psbt> {
+            // The commit transaction is funded by the wallet as usual
+            if !psbt.unsigned_tx.input.is_empty() {
+                psbt.unsigned_tx.output.push(TxOut {
+                    value: Amount::from_sat(1_000),
+                    script_pubkey: recipient_output().script().unwrap(),
+                });
+                psbt.outputs.push(Default::default());
+            }
+            self.0.sign_psbt(psbt).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changed_withdrawal_is_not_broadcast() {
+        let relayer = create_relayer();
+        let signer = ChangeSigner(ProviderSigner::new(relayer.provider.clone()));
+        let mut relayer = relayer.with_signer(Box::new(signer));
+        let id = relayer.queue.push(request(&relayer, 100)).unwrap();
+
+        relayer.process_next().await;
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Failed { .. })));
+        // Not even the envelope commit went out, so nothing is stranded
+        assert!(relayer.provider.broadcasts().is_empty());
+        assert!(relayer.stranded_commits().is_empty());
+        assert!(relayer.in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_reveal_strands_commit() {
+        let mut relayer = create_relayer();
+        let id = relayer.queue.push(request(&relayer, 100)).unwrap();
+        relayer
+            .provider
+            .inject_failure_after("broadcast_transaction", 1, MockFailure::Error("mempool full".to_string()));
+
+        relayer.process_next().await;
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Failed { .. })));
+        let broadcasts = relayer.provider.broadcasts();
+        assert_eq!(broadcasts.len(), 1);
+        let commit: Transaction = deserialize_hex(&broadcasts[0]).unwrap();
+        // The envelope's commit output is left for the wallet to sweep
+        assert_eq!(relayer.stranded_commits(), &[OutPoint::new(commit.compute_txid(), 0)][..]);
+        assert!(relayer.in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unbuildable_request_fails() {
+        let mut relayer = create_relayer();
+        let mut req = request(&relayer, 100);
+        // Valid hex, but no address pays to it
+        req.outputs[0].script_pubkey = "51".to_string();
+        let id = relayer.queue.push(req).unwrap();
+
+        relayer.process_next().await;
+        assert!(matches!(relayer.queue.status(id), Ok(JobStatus::Failed { .. })));
+        assert!(relayer.provider.broadcasts().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_shares_one_transaction() {
+        let mut relayer = create_relayer();
+        let mut other = request_with_nullifier(&relayer, 150, 2);
+        other.outputs[0] = OutputDescriptor::new(1000, "0014".to_string() + &"33".repeat(20));
+        let mut stale = request_with_nullifier(&relayer, 100, 3);
+        stale.witness.proof.merkle_root = [9u8; 32];
+
+        let first = relayer.queue.push(request(&relayer, 100)).unwrap();
+        let second = relayer.queue.push(other.clone()).unwrap();
+        let third = relayer.queue.push(stale).unwrap();
+
+        assert_eq!(relayer.process_batch().await, vec![first, second, third]);
+        let (Ok(JobStatus::Broadcast { txid: a }), Ok(JobStatus::Broadcast { txid: b })) =
+            (relayer.queue.status(first), relayer.queue.status(second))
+        else {
+            panic!("batched jobs were not broadcast");
+        };
+        assert_eq!(a, b);
+        assert!(matches!(relayer.queue.status(third), Ok(JobStatus::Failed { .. })));
+        assert!(relayer.process_batch().await.is_empty());
+
+        // One commit transaction funds both envelopes
+        let broadcasts = relayer.provider.broadcasts();
+        assert_eq!(broadcasts.len(), 2);
+        let commit: Transaction = deserialize_hex(&broadcasts[0]).unwrap();
+        let batch: Transaction = deserialize_hex(&broadcasts[1]).unwrap();
+        assert_eq!(batch.compute_txid().to_string(), a);
+        assert_eq!(commit.output.len(), 2);
+
+        // Input i reveals the envelope of withdrawal i, bound to its outputs
+        assert_eq!(batch.input.len(), 2);
+        for (i, (nullifier, window)) in [(1u8, 0..2), (2u8, 2..4)].into_iter().enumerate() {
+            assert_eq!(batch.input[i].previous_output, OutPoint::new(commit.compute_txid(), i as u32));
+            let payload = find_witness_payload(&batch, i).unwrap();
+            let witness = WithdrawalWitness::from_envelope(&payload).unwrap();
+            assert_eq!(witness.proof.nullifier_hash, NullifierHash::new([nullifier; 32]));
+            assert_eq!(find_outputs_window(&batch.output, &witness.outputs_hash), Some(window));
+        }
+
+        // Each withdrawal is its own pool call, paid out to its first output
+        assert_eq!(batch.output.len(), 5);
+        assert_eq!(batch.output[2].script_pubkey, other.outputs[0].script().unwrap());
+        assert_eq!(
+            pool_calls(&relayer, &batch),
+            vec![(vec![WITHDRAW_BATCHED, 0], Some(0)), (vec![WITHDRAW_BATCHED, 1], Some(2))]
+        );
+
+        // Both withdrawals are confirmed by the same block
+        script_spent(&relayer, &[(1, true), (2, true)]);
+        relayer.provider.mine_empty_blocks(1);
+        assert_eq!(relayer.poll_confirmations().await, vec![first, second]);
+        assert!(relayer.pool.is_nullifier_spent(&[2u8; 32]));
+        assert!(!relayer.pool.is_nullifier_spent(&[3u8; 32]));
+    }
+
+    #[tokio::test]
+    async fn test_batch_transaction_binds_each_proof() {
+        let relayer = create_relayer();
+        let first = request(&relayer, 100);
+        let mut second = request_with_nullifier(&relayer, 100, 2);
+        second.outputs[0] = OutputDescriptor::new(1000, "0014".to_string() + &"33".repeat(20));
+
+        let mut withdrawals = Vec::new();
+        let mut reveals = Vec::new();
+        for (vout, request) in [&first, &second].into_iter().enumerate() {
+            let withdrawal = relayer.build_withdrawal(request, true).await.unwrap();
+            let key = WalletProvider::get_internal_key(relayer.provider.as_ref()).await.unwrap();
+            let commit = EnvelopeCommit::new(key, &withdrawal.envelope).unwrap();
+            let utxo = FundingUtxo {
+                outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), vout as u32),
+                txout: TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey: commit.script_pubkey.clone(),
+                },
+            };
+            withdrawals.push(withdrawal);
+            reveals.push(commit.reveal_input(&utxo));
+        }
+        let outputs_hashes: Vec<[u8; 32]> = withdrawals.iter().map(|withdrawal| withdrawal.outputs_hash).collect();
+
+        let pool_id = relayer.config.pool_id;
+        let psbt = build_batch_transaction(&pool_id, withdrawals.clone(), reveals.clone()).unwrap();
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(psbt.inputs[1], reveals[1].1);
+        assert_eq!(tx.output.len(), 5);
+        for (outputs_hash, window) in outputs_hashes.iter().zip([0..2, 2..4]) {
+            assert_eq!(find_outputs_window(&tx.output, outputs_hash), Some(window));
+        }
+        assert_eq!(
+            pool_calls(&relayer, tx),
+            vec![(vec![WITHDRAW_BATCHED, 0], Some(0)), (vec![WITHDRAW_BATCHED, 1], Some(2))]
+        );
+
+        // Every withdrawal needs the input revealing its envelope
+        reveals.pop();
+        assert!(build_batch_transaction(&pool_id, withdrawals, reveals).is_err());
+    }
+
+    #[test]
+    fn test_build_transaction() {
+        let tx = build_transaction(&[recipient_output(), fee_output()]).unwrap();
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[1].script_pubkey.as_bytes()[0], 0x00);
+
+        let bad = OutputDescriptor::new(546, "zz".to_string());
+        assert!(build_transaction(&[bad]).is_err());
+    }
+}
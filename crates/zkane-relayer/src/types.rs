@@ -0,0 +1,148 @@
+//! Request, response and error types for the relayer API.
+
+use serde::{Deserialize, Serialize};
+use zkane_common::WithdrawalWitness;
+use zkane_crypto::hash::sha256;
+
+/// A transaction output requested by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputDescriptor {
+    /// Output value in satoshis
+    pub value: u64,
+    /// Hex-encoded scriptPubKey
+    pub script_pubkey: String,
+}
+
+impl OutputDescriptor {
+    /// Create a new output descriptor.
+    pub fn new(value: u64, script_pubkey: String) -> Self {
+        Self { value, script_pubkey }
+    }
+
+    /// Create an output descriptor from a scriptPubKey in hex of any case,
+    /// validating it and keeping it in lowercase hex.
+    pub fn parse(value: u64, script_pubkey: &str) -> Result<Self, RelayerError> {
+        let script = Self::new(value, script_pubkey.to_string()).script()?;
+        Ok(Self::new(value, hex::encode(script.as_bytes())))
+    }
+
+    /// Hash this output the same way withdrawal proofs commit to outputs.
+    ///
+    /// Proofs commit to the lowercase hex of the script, so the
+    /// scriptPubKey is hashed in lowercase whatever case it is written in.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut input = Vec::with_capacity(8 + self.script_pubkey.len());
+        input.extend_from_slice(&self.value.to_le_bytes());
+        input.extend_from_slice(self.script_pubkey.to_ascii_lowercase().as_bytes());
+        sha256(&input)
+    }
+
+    /// Decode the scriptPubKey into a bitcoin script.
+    pub fn script(&self) -> Result<bitcoin::ScriptBuf, RelayerError> {
+        let bytes = hex::decode(&self.script_pubkey)
+            .map_err(|e| RelayerError::InvalidRequest(format!("invalid scriptPubKey hex: {}", e)))?;
+        Ok(bitcoin::ScriptBuf::from_bytes(bytes))
+    }
+}
+
+/// A withdrawal submitted to the relayer.
+///
+/// The proof commits to the relayer output and fee, so a request cannot be
+/// replayed with different fee terms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayRequest {
+    /// The withdrawal witness revealed to the pool: the proof, including
+    /// relayer fee information, and the Merkle path of the commitment.
+    ///
+    /// Its outputs hash is ignored, the relayer sets it for the transaction
+    /// it builds.
+    pub witness: WithdrawalWitness,
+    /// Outputs of the withdrawal transaction, including the relayer output
+    pub outputs: Vec<OutputDescriptor>,
+}
+
+/// Lifecycle of a relay job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting in the queue
+    Queued,
+    /// Transaction is being built and broadcast
+    Broadcasting,
+    /// Transaction was broadcast and waits for the pool to accept it
+    Broadcast { txid: String },
+    /// The pool accepted the withdrawal
+    Confirmed { txid: String },
+    /// Job failed
+    Failed { reason: String },
+}
+
+impl JobStatus {
+    /// Check if the job has reached a final state.
+    pub fn is_final(&self) -> bool {
+        matches!(self, JobStatus::Confirmed { .. } | JobStatus::Failed { .. })
+    }
+}
+
+/// Public information about the relayer, served at `GET /status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayerStatus {
+    /// Minimum fee accepted, in units of the pool asset
+    pub min_fee: u128,
+    /// Pool denomination
+    pub denomination: u128,
+    /// Hex-encoded hash of the output the fee must be paid to
+    pub fee_output_hash: String,
+    /// The output the fee must be paid to, to include in relay requests
+    pub fee_output: OutputDescriptor,
+    /// Hex-encoded current merkle root of the synced pool
+    pub merkle_root: String,
+    /// Number of commitments in the synced pool
+    pub commitment_count: u64,
+    /// Number of jobs that have not reached a final state
+    pub pending_jobs: usize,
+    /// Expected seconds from submission to confirmation, zero from relayers
+    /// that don't quote it
+    #[serde(default)]
+    pub estimated_confirmation_secs: u64,
+}
+
+/// Errors returned by the relayer.
+#[derive(Debug, thiserror::Error)]
+pub enum RelayerError {
+    /// The request is malformed
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    /// The declared fee is below the relayer's minimum
+    #[error("Fee too low: minimum is {minimum}, got {fee}")]
+    FeeTooLow { minimum: u128, fee: u128 },
+
+    /// The proof does not pay the relayer's fee output
+    #[error("Proof does not pay this relayer")]
+    WrongRelayer,
+
+    /// The proof was rejected by the pool
+    #[error("Withdrawal proof rejected by pool")]
+    ProofRejected,
+
+    /// The nullifier was already spent in the pool
+    #[error("Nullifier already spent")]
+    NullifierSpent,
+
+    /// A job for the same nullifier is already in progress
+    #[error("Withdrawal already queued")]
+    AlreadyQueued,
+
+    /// Client exceeded the rate limit
+    #[error("Rate limit exceeded")]
+    RateLimited,
+
+    /// Unknown job id
+    #[error("Job not found")]
+    JobNotFound,
+
+    /// Building or broadcasting the transaction failed
+    #[error("Broadcast failed: {0}")]
+    BroadcastFailed(String),
+}
@@ -0,0 +1,34 @@
+//! # ZKane Test Kit
+//!
+//! Scenario builders for integration tests running the ZKane contracts in the
+//! alkanes test indexer. A [`ScenarioBuilder`] deploys the factory and pool,
+//! keeps track of each user's tokens and indexes one block per step, so a test
+//! reads as the flow it checks instead of the transactions behind it:
+//!
+//! ```rust,ignore
+//! use zkane_testkit::{ContractBuilds, ScenarioBuilder, DEFAULT_ASSET, DEFAULT_DENOMINATION};
+//!
+//! let builds = ContractBuilds { factory: zkane_factory_build::get_bytes(), pool: zkane_pool_build::get_bytes() };
+//! let note = zkane_core::generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+//! let nullifier_hash = witness.proof.nullifier_hash;
+//!
+//! ScenarioBuilder::deploy_ecosystem(&builds)?
+//!     .mint("alice", DEFAULT_DENOMINATION)?
+//!     .deposit("alice", &note)?
+//!     .assert_deposit_count(1)?
+//!     .withdraw("bob", witness)?
+//!     .assert_nullifier_spent(&nullifier_hash)?
+//!     .assert_balance("bob", DEFAULT_DENOMINATION)?;
+//! ```
+//!
+//! The contract WASM comes from the caller, usually the `<crate>_build`
+//! modules the `zkane` build script embeds from the contracts in the repo, so
+//! the kit doesn't depend on a particular build of the contracts.
+//!
+//! Lower-level helpers for building the transactions of a step are in
+//! [`transactions`].
+
+pub mod scenario;
+pub mod transactions;
+
+pub use scenario::{ContractBuilds, ScenarioBuilder, DEFAULT_ASSET, DEFAULT_DENOMINATION};
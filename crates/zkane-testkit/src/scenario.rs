@@ -0,0 +1,464 @@
+//! The [`ScenarioBuilder`].
+
+use crate::transactions::{call_transaction, cellpack, envelope_witness, protostone_vout, user_output};
+use alkanes::indexer::index_block;
+use alkanes::message::AlkaneMessageContext;
+use alkanes::tests::helpers::{self as alkane_helpers, clear};
+use alkanes::view;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use alkanes_support::proto::alkanes::AlkanesTrace;
+use alkanes_support::trace::{Trace, TraceEvent};
+use anyhow::{anyhow, bail, ensure, Result};
+use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxOut, Witness};
+use metashrew_support::index_pointer::KeyValuePointer;
+use metashrew_support::utils::consensus_encode;
+use protobuf::Message;
+use protorune::balance_sheet::load_sheet;
+use protorune::message::MessageContext;
+use protorune::tables::RuneTable;
+use protorune::test_helpers as protorune_helpers;
+use protorune_support::balance_sheet::{BalanceSheetOperations, ProtoruneRuneId};
+use protorune_support::protostone::ProtostoneEdict;
+use std::collections::HashMap;
+use zkane_abi::{decode_u128, factory, pool, split_hash};
+use zkane_common::{
+    calculate_outputs_hash, derive_pool_id, Commitment, DepositNote, EnvelopeFormat, NullifierHash, Recipient,
+    WithdrawalProof, WithdrawalWitness, ZKaneConfig, ZkAssetId,
+};
+use zkane_crypto::{generate_nullifier_hash, MerkleTree};
+
+/// Asset of the pool a scenario deploys by default
+pub const DEFAULT_ASSET: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+/// Denomination of the pool a scenario deploys by default
+pub const DEFAULT_DENOMINATION: u128 = 50_000;
+
+/// Template of the factory, as deployed at block 0
+pub const FACTORY_TEMPLATE: AlkaneId = AlkaneId { block: 4, tx: 0x2FA };
+
+/// Template of the pools, as deployed at block 0
+pub const POOL_TEMPLATE: AlkaneId = AlkaneId { block: 4, tx: 0x2FB };
+
+/// Opcode the pool asset is minted with
+pub const MINT_OPCODE: u128 = 77;
+
+/// Compiled contracts deployed by a scenario.
+#[derive(Debug, Clone)]
+pub struct ContractBuilds {
+    /// WASM of the factory
+    pub factory: Vec<u8>,
+    /// WASM of the pool
+    pub pool: Vec<u8>,
+}
+
+/// Builds an integration test scenario step by step.
+///
+/// Each step indexes one block holding one transaction, at increasing
+/// heights. Steps return the builder so they chain with `?`, and fail if the
+/// contract call they make reverts, with the revert message so tests can
+/// check its [`ZKaneError`](zkane_common::ZKaneError) code.
+///
+/// Users are named by the test. Each user's tokens are held by the outpoint
+/// the last step involving the user paid them through.
+pub struct ScenarioBuilder {
+    factory_id: AlkaneId,
+    pool_id: AlkaneId,
+    asset_id: AlkaneId,
+    denomination: u128,
+    height: u32,
+    users: HashMap<String, OutPoint>,
+    commitments: Vec<Commitment>,
+    last_tx: Option<Transaction>,
+}
+
+impl ScenarioBuilder {
+    /// Deploy the factory and a pool of [`DEFAULT_DENOMINATION`] for
+    /// [`DEFAULT_ASSET`] in a fresh indexer.
+    pub fn deploy_ecosystem(builds: &ContractBuilds) -> Result<Self> {
+        Self::deploy_ecosystem_with(builds, DEFAULT_ASSET, DEFAULT_DENOMINATION)
+    }
+
+    /// Deploy the factory and a pool for `asset_id` and `denomination` in a
+    /// fresh indexer.
+    pub fn deploy_ecosystem_with(builds: &ContractBuilds, asset_id: ZkAssetId, denomination: u128) -> Result<Self> {
+        clear();
+        let templates = alkane_helpers::init_with_multiple_cellpacks_with_tx(
+            vec![builds.factory.clone(), builds.pool.clone()],
+            vec![
+                Cellpack { target: AlkaneId { block: 3, tx: FACTORY_TEMPLATE.tx }, inputs: vec![0] },
+                Cellpack { target: AlkaneId { block: 3, tx: POOL_TEMPLATE.tx }, inputs: vec![0] },
+            ],
+        );
+        index_block(&templates, 0)?;
+
+        let mut scenario = Self {
+            factory_id: FACTORY_TEMPLATE,
+            pool_id: derive_pool_id(&asset_id, denomination).into(),
+            asset_id: asset_id.into(),
+            denomination,
+            height: 1,
+            users: HashMap::new(),
+            commitments: Vec::new(),
+            last_tx: None,
+        };
+        // Steps call contracts directly, as the zero alkane, which is made the
+        // factory admin so tests can use the admin opcodes
+        let factory = scenario.factory_id;
+        scenario.call(OutPoint::null(), cellpack(factory, factory::INITIALIZE, &[0, 0]))?;
+        scenario.call(
+            OutPoint::null(),
+            cellpack(factory, factory::CREATE_POOL, &[asset_id.block, asset_id.tx, denomination]),
+        )?;
+        Ok(scenario)
+    }
+
+    /// Create the pool of another asset or denomination through the factory,
+    /// and make it the pool later steps deposit into and withdraw from.
+    ///
+    /// The scenario still mints and deposits its own asset, so deposits into
+    /// the pool of another asset send it the wrong one.
+    pub fn with_pool(mut self, asset_id: ZkAssetId, denomination: u128) -> Result<Self> {
+        let factory = self.factory_id;
+        self.call(
+            OutPoint::null(),
+            cellpack(factory, factory::CREATE_POOL, &[asset_id.block, asset_id.tx, denomination]),
+        )?;
+        self.pool_id = derive_pool_id(&asset_id, denomination).into();
+        self.denomination = denomination;
+        self.commitments.clear();
+        Ok(self)
+    }
+
+    /// Make the pool of another asset or denomination the one later steps
+    /// deposit into, without creating it, so the first deposit through the
+    /// factory does.
+    pub fn select_pool(mut self, asset_id: ZkAssetId, denomination: u128) -> Self {
+        self.pool_id = derive_pool_id(&asset_id, denomination).into();
+        self.denomination = denomination;
+        self.commitments.clear();
+        self
+    }
+
+    /// Create the pool of another asset or denomination through the factory
+    /// with a raw configuration envelope, which is sent as it is, without
+    /// making it the scenario's pool.
+    pub fn try_create_pool_envelope(&mut self, asset_id: ZkAssetId, denomination: u128, envelope: &[u8]) -> Result<()> {
+        let tx = call_transaction(
+            OutPoint::null(),
+            envelope_witness(envelope),
+            vec![user_output()],
+            cellpack(self.factory_id, factory::CREATE_POOL, &[asset_id.block, asset_id.tx, denomination]),
+            vec![],
+        )?;
+        self.index(tx)?;
+        Ok(())
+    }
+
+    /// Get the factory.
+    pub fn factory_id(&self) -> AlkaneId {
+        self.factory_id
+    }
+
+    /// Get the pool the scenario deposits into.
+    pub fn pool_id(&self) -> AlkaneId {
+        self.pool_id
+    }
+
+    /// Get the pool's asset.
+    pub fn asset_id(&self) -> AlkaneId {
+        self.asset_id
+    }
+
+    /// Get the height the next step is indexed at.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the transaction of the last step.
+    pub fn last_transaction(&self) -> Option<&Transaction> {
+        self.last_tx.as_ref()
+    }
+
+    /// Mint `amount` of the pool asset to `user`.
+    ///
+    /// The asset must mint to the caller on [`MINT_OPCODE`].
+    pub fn mint(mut self, user: &str, amount: u128) -> Result<Self> {
+        let tx = self.call(OutPoint::null(), cellpack(self.asset_id, MINT_OPCODE, &[amount]))?;
+        self.pay(user, &tx);
+        Ok(self)
+    }
+
+    /// Deposit one denomination of `user`'s tokens into the pool, under the
+    /// note's commitment.
+    ///
+    /// The commitment is carried in an OP_RETURN output, and the user's
+    /// remaining tokens come back to them.
+    pub fn deposit(mut self, user: &str, note: &DepositNote) -> Result<Self> {
+        self.try_deposit(user, note, false)?;
+        Ok(self)
+    }
+
+    /// Pre-register a note's commitment for a front-run-protected deposit.
+    pub fn register(mut self, note: &DepositNote) -> Result<Self> {
+        let hash = note.commitment.registration_hash();
+        let halves = split_hash(&hash);
+        self.call(OutPoint::null(), cellpack(self.pool_id, pool::REGISTER_COMMITMENT, &halves))?;
+        Ok(self)
+    }
+
+    /// Reveal a note registered in an earlier step, depositing one
+    /// denomination of `user`'s tokens as [`deposit`](Self::deposit) does.
+    pub fn deposit_registered(mut self, user: &str, note: &DepositNote) -> Result<Self> {
+        self.try_deposit(user, note, true)?;
+        Ok(self)
+    }
+
+    /// Make a plain or registered deposit without consuming the scenario, so
+    /// a test can go on after a deposit that reverts.
+    ///
+    /// A reverted deposit refunds the user through output 0 like any other,
+    /// so the user holds their tokens there either way.
+    pub fn try_deposit(&mut self, user: &str, note: &DepositNote, registered: bool) -> Result<()> {
+        let opcode = if registered { pool::DEPOSIT_REGISTERED } else { pool::DEPOSIT };
+        self.send_deposit(user, note, opcode, self.denomination)
+    }
+
+    /// Make a plain deposit of `amount` of `user`'s tokens instead of one
+    /// denomination, without consuming the scenario.
+    pub fn try_deposit_amount(&mut self, user: &str, note: &DepositNote, amount: u128) -> Result<()> {
+        self.send_deposit(user, note, pool::DEPOSIT, amount)
+    }
+
+    /// Deposit one denomination of `user`'s tokens through the factory's
+    /// `GetOrCreatePool`, which creates the pool if it doesn't exist yet.
+    ///
+    /// The commitment is carried in the witness envelope of input 0, where
+    /// `CreatePool` takes a configuration envelope.
+    pub fn deposit_through_factory(mut self, user: &str, note: &DepositNote) -> Result<Self> {
+        self.try_deposit_through_factory(user, note)?;
+        Ok(self)
+    }
+
+    /// Deposit through the factory as
+    /// [`deposit_through_factory`](Self::deposit_through_factory) does,
+    /// without consuming the scenario.
+    pub fn try_deposit_through_factory(&mut self, user: &str, note: &DepositNote) -> Result<()> {
+        let denomination = self.denomination;
+        let call = cellpack(
+            self.factory_id,
+            factory::GET_OR_CREATE_POOL,
+            &[self.asset_id.block, self.asset_id.tx, denomination],
+        );
+        let witness = envelope_witness(note.commitment.as_bytes());
+        self.send(user, note, witness, vec![user_output()], call, denomination)
+    }
+
+    fn send_deposit(&mut self, user: &str, note: &DepositNote, opcode: u128, amount: u128) -> Result<()> {
+        let outputs = vec![
+            user_output(),
+            TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return(note.commitment.as_bytes()) },
+        ];
+        let call = cellpack(self.pool_id, opcode, &[]);
+        self.send(user, note, Witness::new(), outputs, call, amount)
+    }
+
+    /// Send `amount` of `user`'s tokens with a deposit call, recording the
+    /// note's commitment if the call succeeds.
+    fn send(
+        &mut self,
+        user: &str,
+        note: &DepositNote,
+        witness: Witness,
+        outputs: Vec<TxOut>,
+        call: Cellpack,
+        amount: u128,
+    ) -> Result<()> {
+        let input = self.outpoint(user)?;
+        let edicts = vec![ProtostoneEdict {
+            id: ProtoruneRuneId { block: self.asset_id.block, tx: self.asset_id.tx },
+            amount,
+            output: protostone_vout(outputs.len()) as u128,
+        }];
+        let tx = call_transaction(input, witness, outputs, call, edicts)?;
+        let indexed = self.index(tx.clone());
+        if self.last_tx.as_ref() == Some(&tx) {
+            self.pay(user, &tx);
+        }
+        if indexed.is_ok() {
+            self.commitments.push(note.commitment);
+        }
+        indexed.map(drop)
+    }
+
+    /// Build the witness withdrawing a note the scenario deposited into the
+    /// pool, paying the output of a withdrawal step.
+    ///
+    /// The path comes from the commitments the scenario deposited. The proof
+    /// bytes are a placeholder, which the pool accepts until it verifies
+    /// proofs.
+    pub fn witness(&mut self, note: &DepositNote) -> Result<WithdrawalWitness> {
+        let config = self.config()?;
+
+        let leaf_index = self
+            .commitments
+            .iter()
+            .position(|commitment| *commitment == note.commitment)
+            .ok_or_else(|| anyhow!("note {} wasn't deposited", note.commitment.to_hex()))?;
+        let leaf_index = u32::try_from(leaf_index)?;
+        let tree = MerkleTree::from_leaves_with_hasher(config.tree_height, &self.commitments, config.tree_hash)?;
+
+        let proof = WithdrawalProof::new(
+            vec![1],
+            tree.root(),
+            generate_nullifier_hash(&note.nullifier)?,
+            Recipient::new(user_output().script_pubkey),
+        )
+        .with_recipients(&[user_output()]);
+        Ok(WithdrawalWitness {
+            proof,
+            path: tree.generate_path(leaf_index)?,
+            leaf_index,
+            commitment: note.commitment,
+            outputs_hash: [0u8; 32],
+        })
+    }
+
+    /// Withdraw a note to `user`.
+    ///
+    /// The witness's outputs hash is set to the withdrawal transaction's, so
+    /// the test only supplies the proof and path.
+    pub fn withdraw(mut self, user: &str, witness: WithdrawalWitness) -> Result<Self> {
+        self.try_withdraw(user, witness)?;
+        Ok(self)
+    }
+
+    /// Withdraw a note as [`withdraw`](Self::withdraw) does, without
+    /// consuming the scenario, so a test can go on after a withdrawal that
+    /// reverts.
+    pub fn try_withdraw(&mut self, user: &str, mut witness: WithdrawalWitness) -> Result<()> {
+        witness.outputs_hash = calculate_outputs_hash(&[user_output()]);
+        let envelope = witness.to_envelope(EnvelopeFormat::Compressed)?;
+        self.try_withdraw_envelope(user, &envelope)
+    }
+
+    /// Withdraw with a raw witness envelope, which is sent as it is.
+    pub fn try_withdraw_envelope(&mut self, user: &str, envelope: &[u8]) -> Result<()> {
+        let tx = call_transaction(
+            OutPoint::null(),
+            envelope_witness(envelope),
+            vec![user_output()],
+            cellpack(self.pool_id, pool::WITHDRAW, &[]),
+            vec![],
+        )?;
+        let tx = self.index(tx)?;
+        self.pay(user, &tx);
+        Ok(())
+    }
+
+    /// Get the configuration of the pool.
+    pub fn config(&mut self) -> Result<ZKaneConfig> {
+        let data = self.query(cellpack(self.pool_id, pool::GET_CONFIG, &[]))?;
+        let info: serde_json::Value = serde_json::from_slice(&data)?;
+        Ok(serde_json::from_value(info["config"].clone())?)
+    }
+
+    /// Get the balance of the pool asset held by `user`.
+    pub fn balance(&self, user: &str) -> Result<u128> {
+        let outpoint = self.outpoint(user)?;
+        let sheet = load_sheet(
+            &RuneTable::for_protocol(AlkaneMessageContext::protocol_tag())
+                .OUTPOINT_TO_RUNES
+                .select(&consensus_encode(&outpoint)?),
+        );
+        Ok(sheet.get(&ProtoruneRuneId { block: self.asset_id.block, tx: self.asset_id.tx }))
+    }
+
+    /// Check that `user` holds `amount` of the pool asset.
+    pub fn assert_balance(self, user: &str, amount: u128) -> Result<Self> {
+        let balance = self.balance(user)?;
+        ensure!(balance == amount, "{} holds {}, expected {}", user, balance, amount);
+        Ok(self)
+    }
+
+    /// Check that the pool holds `count` deposits.
+    pub fn assert_deposit_count(mut self, count: u128) -> Result<Self> {
+        let data = self.query(cellpack(self.pool_id, pool::GET_DEPOSIT_COUNT, &[]))?;
+        let deposits = decode_u128(&data)?;
+        ensure!(deposits == count, "pool holds {} deposits, expected {}", deposits, count);
+        Ok(self)
+    }
+
+    /// Check that the pool has spent a nullifier hash.
+    pub fn assert_nullifier_spent(mut self, nullifier_hash: &NullifierHash) -> Result<Self> {
+        let (low, high) = nullifier_hash.to_u128_pair();
+        let data = self.query(cellpack(self.pool_id, pool::IS_NULLIFIER_SPENT, &[low, high]))?;
+        ensure!(decode_u128(&data)? == 1, "nullifier hash {} is not spent", nullifier_hash.to_hex());
+        Ok(self)
+    }
+
+    /// Call a contract in a step of its own, failing if the call reverts.
+    ///
+    /// Returns the step's transaction.
+    pub fn call(&mut self, input: OutPoint, cellpack: Cellpack) -> Result<Transaction> {
+        let tx = call_transaction(input, Witness::new(), vec![user_output()], cellpack, vec![])?;
+        self.index(tx)
+    }
+
+    /// Call a contract and get the data it returned.
+    pub fn query(&mut self, cellpack: Cellpack) -> Result<Vec<u8>> {
+        let tx = self.call(OutPoint::null(), cellpack)?;
+        let events = trace_events(&tx)?;
+        events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                TraceEvent::ReturnContext(response) => Some(response.inner.data.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("call returned nothing"))
+    }
+
+    /// Index a transaction in a block of its own, failing if its call reverts.
+    ///
+    /// The error carries the revert message, coded if the contract reverted
+    /// with a [`ZKaneError`](zkane_common::ZKaneError).
+    pub fn index(&mut self, tx: Transaction) -> Result<Transaction> {
+        let block = protorune_helpers::create_block_with_txs(vec![tx.clone()]);
+        index_block(&block, self.height)?;
+        self.height += 1;
+        self.last_tx = Some(tx.clone());
+
+        let revert = trace_events(&tx)?.into_iter().find_map(|event| match event {
+            TraceEvent::RevertContext(response) => Some(response.inner.data),
+            _ => None,
+        });
+        if let Some(data) = revert {
+            bail!("call in block {} reverted: {}", self.height - 1, String::from_utf8_lossy(&data));
+        }
+        Ok(tx)
+    }
+
+    fn outpoint(&self, user: &str) -> Result<OutPoint> {
+        self.users
+            .get(user)
+            .copied()
+            .ok_or_else(|| anyhow!("{} holds nothing", user))
+    }
+
+    /// Record that a step paid `user` through output 0 of `tx`.
+    fn pay(&mut self, user: &str, tx: &Transaction) {
+        self.users.insert(user.to_string(), OutPoint { txid: tx.compute_txid(), vout: 0 });
+    }
+}
+
+/// Get the trace of the protostone of a [`call_transaction`].
+fn trace_events(tx: &Transaction) -> Result<Vec<TraceEvent>> {
+    let outpoint = OutPoint {
+        txid: tx.compute_txid(),
+        vout: tx.output.len() as u32 + 1,
+    };
+    let trace: Trace = AlkanesTrace::parse_from_bytes(&view::trace(&outpoint)?)?.into();
+    let events = trace.0.lock().unwrap().clone();
+    Ok(events)
+}
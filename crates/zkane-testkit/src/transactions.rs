@@ -0,0 +1,129 @@
+//! Transactions of scenario steps.
+//!
+//! Every step is a single transaction spending one input, paying the user's
+//! output first and ending with a runestone carrying one protostone. Witness
+//! envelopes are revealed by the first input, as the pool expects.
+
+use alkanes::message::AlkaneMessageContext;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::opcodes::{all::*, OP_FALSE};
+use bitcoin::script::{Builder, PushBytes};
+use bitcoin::transaction::Version;
+use bitcoin::{absolute::LockTime, Address, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use ordinals::Runestone;
+use protorune::message::MessageContext;
+use protorune::protostone::Protostones;
+use protorune::test_helpers::{get_btc_network, ADDRESS1};
+use protorune_support::protostone::{Protostone, ProtostoneEdict};
+use std::str::FromStr;
+
+/// Value of the outputs paying users, in sats
+pub const OUTPUT_VALUE: Amount = Amount::from_sat(546);
+
+/// Protocol id pushed at the start of an alkanes envelope
+const ENVELOPE_PROTOCOL_ID: &[u8; 3] = b"BIN";
+
+/// Largest push allowed in a tapscript
+const MAX_SCRIPT_PUSH: usize = 520;
+
+/// Key the envelope script is locked to; the test indexer never checks it
+const ENVELOPE_KEY: [u8; 32] = [0x02; 32];
+
+/// Build the cellpack calling `opcode` on `target` with `inputs`.
+pub fn cellpack(target: AlkaneId, opcode: u128, inputs: &[u128]) -> Cellpack {
+    Cellpack {
+        target,
+        inputs: [&[opcode], inputs].concat(),
+    }
+}
+
+/// Get the output paying the test user.
+///
+/// All scenario users share the test indexer's address; they are told apart
+/// by the outpoints holding their tokens.
+pub fn user_output() -> TxOut {
+    TxOut {
+        value: OUTPUT_VALUE,
+        script_pubkey: Address::from_str(ADDRESS1().as_str())
+            .unwrap()
+            .require_network(get_btc_network())
+            .unwrap()
+            .script_pubkey(),
+    }
+}
+
+/// Build the witness of an input revealing `payload` in an envelope.
+///
+/// The script is `<key> OP_CHECKSIG OP_FALSE OP_IF "BIN" OP_0 <payload
+/// chunks> OP_ENDIF`, followed by a dummy control block.
+pub fn envelope_witness(payload: &[u8]) -> Witness {
+    let mut builder = Builder::new()
+        .push_slice(ENVELOPE_KEY)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF)
+        .push_slice(ENVELOPE_PROTOCOL_ID)
+        .push_opcode(OP_PUSHBYTES_0);
+    for chunk in payload.chunks(MAX_SCRIPT_PUSH) {
+        builder = builder.push_slice(<&PushBytes>::try_from(chunk).unwrap());
+    }
+    let script = builder.push_opcode(OP_ENDIF).into_script();
+
+    let control_block = [&[0xc0][..], &ENVELOPE_KEY[..]].concat();
+    Witness::from_slice(&[vec![0u8; 64], script.into_bytes(), control_block])
+}
+
+/// Build a transaction calling a contract.
+///
+/// Spends `input` with `witness`, pays `outputs` and appends the runestone.
+/// The protostone's results and refunds go to output 0, and `edicts` move
+/// tokens of the input into the call.
+pub fn call_transaction(
+    input: OutPoint,
+    witness: Witness,
+    outputs: Vec<TxOut>,
+    cellpack: Cellpack,
+    edicts: Vec<ProtostoneEdict>,
+) -> Result<Transaction> {
+    let protostone = Protostone {
+        message: cellpack.encipher(),
+        protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
+        pointer: Some(0),
+        refund: Some(0),
+        from: None,
+        burn: None,
+        edicts,
+    };
+    let runestone = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: Runestone {
+            protocol: Some(vec![protostone].encipher()?),
+            ..Default::default()
+        }
+        .encipher(),
+    };
+
+    Ok(Transaction {
+        version: Version::ONE,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: input,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness,
+        }],
+        output: [outputs, vec![runestone]].concat(),
+    })
+}
+
+/// Get the virtual output of the protostone of a [`call_transaction`] paying
+/// `outputs` outputs.
+///
+/// Protostones are numbered after the real outputs plus one, and the
+/// runestone is the last real output. Edicts sending tokens into the call
+/// target this output.
+pub fn protostone_vout(outputs: usize) -> u32 {
+    outputs as u32 + 2
+}
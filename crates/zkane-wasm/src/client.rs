@@ -0,0 +1,260 @@
+//! # Esplora Pool Client
+//!
+//! Syncs a pool straight from an Esplora endpoint, so a dapp doesn't need a
+//! backend indexer. Blocks are scanned in order from the pool's creation
+//! height, their transactions fed to a [`DepositScanner`], and the client
+//! remembers where it stopped so later syncs only fetch new blocks.
+//!
+//! [`EsploraSync`] holds the logic and takes the HTTP fetch as a function, so
+//! it can be tested natively; [`JsPoolClient`] runs it with the browser's
+//! `fetch`.
+
+use crate::discovery::DepositScanner;
+use crate::js_error;
+use crate::proof::JsMerklePath;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use zkane_common::{MerklePath, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// Number of transactions Esplora returns per `/block/:hash/txs` page
+pub const ESPLORA_TXS_PAGE_SIZE: usize = 25;
+
+/// Incremental pool sync against an Esplora endpoint.
+#[derive(Debug, Clone)]
+pub struct EsploraSync {
+    base_url: String,
+    scanner: DepositScanner,
+    next_height: u64,
+}
+
+impl EsploraSync {
+    /// Create a sync for a pool created at `from_height`.
+    ///
+    /// Fails with `InvalidTreeHeight` if no tree has `tree_height` levels.
+    pub fn new(base_url: &str, pool_id: ZkAssetId, tree_height: u32, from_height: u64) -> ZKaneResult<Self> {
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            scanner: DepositScanner::new(pool_id, tree_height)?,
+            next_height: from_height,
+        })
+    }
+
+    /// Fetch and scan the blocks mined since the last sync.
+    ///
+    /// `fetch` gets a URL and returns the response body. Blocks are scanned
+    /// one by one, so after an error the next sync resumes at the failed
+    /// block.
+    ///
+    /// # Returns
+    ///
+    /// The number of new deposits found.
+    pub async fn sync<F, Fut>(&mut self, fetch: F) -> ZKaneResult<usize>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = ZKaneResult<String>>,
+    {
+        let tip = parse_height(&fetch(format!("{}/blocks/tip/height", self.base_url)).await?)?;
+        let mut found = 0;
+        while self.next_height <= tip {
+            let hash = fetch(format!("{}/block-height/{}", self.base_url, self.next_height)).await?;
+            let mut scanner = self.scanner.clone();
+            let mut start = 0;
+            loop {
+                let page = fetch(format!("{}/block/{}/txs/{}", self.base_url, hash.trim(), start)).await?;
+                let txs: Value = serde_json::from_str(&page)?;
+                found += scanner.push_esplora_json(&txs)?;
+                let count = txs.as_array().map_or(0, Vec::len);
+                if count < ESPLORA_TXS_PAGE_SIZE {
+                    break;
+                }
+                start += count;
+            }
+            // A block is only recorded once all its pages were scanned
+            self.scanner = scanner;
+            self.next_height += 1;
+        }
+        Ok(found)
+    }
+
+    /// Get the scanner holding the pool's deposits and tree.
+    pub fn scanner(&self) -> &DepositScanner {
+        &self.scanner
+    }
+
+    /// Get the height of the next block to scan.
+    pub fn next_height(&self) -> u64 {
+        self.next_height
+    }
+
+    /// Get the Merkle path of a deposit.
+    pub fn merkle_path(&self, leaf_index: u32) -> ZKaneResult<MerklePath> {
+        self.scanner.tree().generate_path(leaf_index)
+    }
+}
+
+fn parse_height(body: &str) -> ZKaneResult<u64> {
+    body.trim()
+        .parse()
+        .map_err(|_| ZKaneError::PoolQueryFailed(format!("invalid block height: {}", body)))
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, available in windows and workers
+    #[wasm_bindgen(js_name = fetch)]
+    fn global_fetch(url: &str) -> js_sys::Promise;
+}
+
+/// Fetch a URL with the browser's `fetch`, returning the response body.
+async fn fetch_text(url: String) -> ZKaneResult<String> {
+    let failed = |e: JsValue| ZKaneError::PoolQueryFailed(format!("{}: {:?}", url, e));
+    let response: web_sys::Response = JsFuture::from(global_fetch(&url)).await.map_err(failed)?.unchecked_into();
+    if !response.ok() {
+        return Err(ZKaneError::PoolQueryFailed(format!("{}: HTTP {}", url, response.status())));
+    }
+    let text = JsFuture::from(response.text().map_err(failed)?).await.map_err(failed)?;
+    text.as_string()
+        .ok_or_else(|| ZKaneError::PoolQueryFailed(format!("{}: response is not text", url)))
+}
+
+/// Syncs a pool from an Esplora endpoint with `fetch`.
+#[wasm_bindgen]
+pub struct JsPoolClient {
+    inner: Rc<RefCell<EsploraSync>>,
+}
+
+#[wasm_bindgen]
+impl JsPoolClient {
+    /// Create a client for the pool `pool_block:pool_tx`, created at
+    /// `from_height`, served by the Esplora API at `base_url`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        base_url: &str,
+        pool_block: u128,
+        pool_tx: u128,
+        tree_height: u32,
+        from_height: u64,
+    ) -> Result<JsPoolClient, JsValue> {
+        let pool_id = ZkAssetId {
+            block: pool_block,
+            tx: pool_tx,
+        };
+        let sync = EsploraSync::new(base_url, pool_id, tree_height, from_height).map_err(js_error)?;
+        Ok(JsPoolClient {
+            inner: Rc::new(RefCell::new(sync)),
+        })
+    }
+
+    /// Fetch new blocks, resolving to the number of new deposits found.
+    pub fn sync(&self) -> js_sys::Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            // The state isn't borrowed across awaits, so the client stays
+            // usable while syncing
+            let mut sync = inner.borrow().clone();
+            let result = sync.sync(fetch_text).await;
+            if sync.next_height() > inner.borrow().next_height() {
+                *inner.borrow_mut() = sync;
+            }
+            result.map(|found| JsValue::from(found as u32)).map_err(js_error)
+        })
+    }
+
+    /// The current Merkle root, hex encoded.
+    #[wasm_bindgen(getter)]
+    pub fn root(&self) -> String {
+        hex::encode(self.inner.borrow().scanner().root())
+    }
+
+    /// The number of deposits found.
+    #[wasm_bindgen(getter, js_name = leafCount)]
+    pub fn leaf_count(&self) -> u32 {
+        self.inner.borrow().scanner().leaf_count()
+    }
+
+    /// The height of the next block to scan.
+    #[wasm_bindgen(getter, js_name = nextHeight)]
+    pub fn next_height(&self) -> u64 {
+        self.inner.borrow().next_height()
+    }
+
+    /// The deposits found, as a JSON array.
+    #[wasm_bindgen(js_name = depositsJson)]
+    pub fn deposits_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string(self.inner.borrow().scanner().deposits()).map_err(js_error)
+    }
+
+    /// The Merkle path of a deposit.
+    #[wasm_bindgen(js_name = merklePath)]
+    pub fn merkle_path(&self, leaf_index: u32) -> Result<JsMerklePath, JsValue> {
+        self.inner.borrow().merkle_path(leaf_index).map(JsMerklePath::from).map_err(js_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const BASE_URL: &str = "https://esplora.test/api";
+
+    /// Esplora JSON of a transaction that isn't a deposit
+    fn plain_tx(n: usize) -> Value {
+        serde_json::json!({
+            "txid": format!("{:064x}", n),
+            "version": 2,
+            "locktime": 0,
+            "vin": [],
+            "vout": [{ "scriptpubkey": "51", "value": n }],
+            "status": { "confirmed": true, "block_height": 100 }
+        })
+    }
+
+    fn fetcher(responses: &HashMap<String, String>) -> impl Fn(String) -> std::future::Ready<ZKaneResult<String>> + '_ {
+        move |url: String| {
+            let path = url.trim_start_matches(BASE_URL).to_string();
+            std::future::ready(responses.get(&path).cloned().ok_or(ZKaneError::PoolQueryFailed(path)))
+        }
+    }
+
+    #[test]
+    fn test_sync_scans_new_blocks() {
+        let full_page = Value::Array((0..ESPLORA_TXS_PAGE_SIZE).map(plain_tx).collect());
+        let mut responses: HashMap<String, String> = [
+            ("/blocks/tip/height", "101".to_string()),
+            ("/block-height/100", "aa\n".to_string()),
+            ("/block/aa/txs/0", full_page.to_string()),
+            ("/block/aa/txs/25", Value::Array(vec![plain_tx(25)]).to_string()),
+        ]
+        .into_iter()
+        .map(|(path, body)| (path.to_string(), body))
+        .collect();
+
+        let mut sync = EsploraSync::new(&format!("{}/", BASE_URL), ZkAssetId { block: 6, tx: 1 }, 4, 100).unwrap();
+
+        // Block 101 can't be fetched: block 100 is kept, 101 is retried
+        assert!(futures::executor::block_on(sync.sync(fetcher(&responses))).is_err());
+        assert_eq!(sync.next_height(), 101);
+
+        responses.insert("/block-height/101".to_string(), "bb".to_string());
+        responses.insert("/block/bb/txs/0".to_string(), "[]".to_string());
+        assert_eq!(futures::executor::block_on(sync.sync(fetcher(&responses))).unwrap(), 0);
+        assert_eq!(sync.next_height(), 102);
+        assert_eq!(sync.scanner().leaf_count(), 0);
+
+        // Nothing new to fetch
+        assert_eq!(futures::executor::block_on(sync.sync(fetcher(&responses))).unwrap(), 0);
+        assert_eq!(sync.scanner().root(), zkane_crypto::MerkleTree::new(4).root());
+    }
+
+    #[test]
+    fn test_parse_height() {
+        assert_eq!(parse_height("812345\n").unwrap(), 812345);
+        assert!(matches!(parse_height("<html>"), Err(ZKaneError::PoolQueryFailed(_))));
+    }
+}
@@ -0,0 +1,165 @@
+//! # Note Hashing
+//!
+//! Commitment and nullifier hashes of deposit notes, for dapps that hold the
+//! note parts themselves. Built with the `crypto` feature.
+//!
+//! The batch functions take and return `Uint8Array`s of packed 32-byte
+//! values, so a wallet with hundreds of notes crosses the JavaScript
+//! boundary once instead of once per note.
+
+use crate::js_error;
+use wasm_bindgen::prelude::*;
+use zkane_common::{Nullifier, Secret, ZKaneError, ZKaneResult, ZkAssetId};
+
+/// Size of each value in a packed batch
+const PACKED_SIZE: usize = 32;
+
+fn parse_hex<T>(hex: &str, parse: fn(&str) -> anyhow::Result<T>) -> ZKaneResult<T> {
+    parse(hex.trim().trim_start_matches("0x")).map_err(|e| ZKaneError::SerializationError(e.to_string()))
+}
+
+/// Get the hex commitment of a note, bound to its pool's asset and
+/// denomination.
+#[wasm_bindgen(js_name = assetCommitment)]
+pub fn asset_commitment(
+    nullifier_hex: &str,
+    secret_hex: &str,
+    asset_block: u128,
+    asset_tx: u128,
+    denomination: u128,
+) -> Result<String, JsValue> {
+    let asset_id = ZkAssetId { block: asset_block, tx: asset_tx };
+    compute_asset_commitment(nullifier_hex, secret_hex, &asset_id, denomination).map_err(js_error)
+}
+
+/// Get the hex hash a note's nullifier is revealed as when it is withdrawn.
+#[wasm_bindgen(js_name = nullifierHash)]
+pub fn nullifier_hash(nullifier_hex: &str) -> Result<String, JsValue> {
+    let nullifier = parse_hex(nullifier_hex, Nullifier::from_hex).map_err(js_error)?;
+    let hash = zkane_crypto::generate_nullifier_hash(&nullifier).map_err(js_error)?;
+    Ok(hash.to_hex())
+}
+
+/// Get the commitments of a batch of notes of one pool.
+///
+/// `nullifiers` and `secrets` hold the notes' parts packed in the same
+/// order; the commitments are returned packed in that order too.
+#[wasm_bindgen(js_name = generateCommitments)]
+pub fn generate_commitments(
+    nullifiers: &[u8],
+    secrets: &[u8],
+    asset_block: u128,
+    asset_tx: u128,
+    denomination: u128,
+) -> Result<Vec<u8>, JsValue> {
+    let asset_id = ZkAssetId { block: asset_block, tx: asset_tx };
+    compute_asset_commitments(nullifiers, secrets, &asset_id, denomination).map_err(js_error)
+}
+
+/// Get the nullifier hashes of a batch of packed nullifiers, packed in the
+/// same order.
+#[wasm_bindgen(js_name = computeNullifierHashes)]
+pub fn compute_nullifier_hashes(nullifiers: &[u8]) -> Result<Vec<u8>, JsValue> {
+    nullifier_hashes(nullifiers).map_err(js_error)
+}
+
+/// Split a packed batch into its 32-byte values
+fn unpack<'a>(packed: &'a [u8], name: &str) -> ZKaneResult<impl Iterator<Item = [u8; 32]> + 'a> {
+    let chunks = packed.chunks_exact(PACKED_SIZE);
+    if !chunks.remainder().is_empty() {
+        return Err(ZKaneError::SerializationError(format!(
+            "packed {} must be a multiple of {} bytes, got {}",
+            name,
+            PACKED_SIZE,
+            packed.len()
+        )));
+    }
+    Ok(chunks.map(|chunk| chunk.try_into().unwrap()))
+}
+
+/// Compute the packed commitments of packed nullifiers and secrets.
+pub fn compute_asset_commitments(
+    nullifiers: &[u8],
+    secrets: &[u8],
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> ZKaneResult<Vec<u8>> {
+    if nullifiers.len() != secrets.len() {
+        return Err(ZKaneError::SerializationError(format!(
+            "got {} bytes of nullifiers for {} bytes of secrets",
+            nullifiers.len(),
+            secrets.len()
+        )));
+    }
+    let mut commitments = Vec::with_capacity(nullifiers.len());
+    for (nullifier, secret) in unpack(nullifiers, "nullifiers")?.zip(unpack(secrets, "secrets")?) {
+        let commitment = zkane_crypto::generate_asset_commitment(
+            &Nullifier::new(nullifier),
+            &Secret::new(secret),
+            asset_id,
+            denomination,
+        )?;
+        commitments.extend_from_slice(commitment.as_bytes());
+    }
+    Ok(commitments)
+}
+
+/// Compute the packed nullifier hashes of packed nullifiers.
+pub fn nullifier_hashes(nullifiers: &[u8]) -> ZKaneResult<Vec<u8>> {
+    let mut hashes = Vec::with_capacity(nullifiers.len());
+    for nullifier in unpack(nullifiers, "nullifiers")? {
+        let hash = zkane_crypto::generate_nullifier_hash(&Nullifier::new(nullifier))?;
+        hashes.extend_from_slice(hash.as_bytes());
+    }
+    Ok(hashes)
+}
+
+/// Compute the hex commitment of a note.
+pub fn compute_asset_commitment(
+    nullifier_hex: &str,
+    secret_hex: &str,
+    asset_id: &ZkAssetId,
+    denomination: u128,
+) -> ZKaneResult<String> {
+    let nullifier = parse_hex(nullifier_hex, Nullifier::from_hex)?;
+    let secret = parse_hex(secret_hex, Secret::from_hex)?;
+    let commitment = zkane_crypto::generate_asset_commitment(&nullifier, &secret, asset_id, denomination)?;
+    Ok(commitment.to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_commitment_matches_crypto() {
+        let nullifier = Nullifier::new([1u8; 32]);
+        let secret = Secret::new([2u8; 32]);
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let expected = zkane_crypto::generate_asset_commitment(&nullifier, &secret, &asset_id, 1000).unwrap();
+
+        let commitment = compute_asset_commitment(&"01".repeat(32), &format!("0x{}", "02".repeat(32)), &asset_id, 1000).unwrap();
+        assert_eq!(commitment, expected.to_hex());
+        assert!(compute_asset_commitment("zz", &"02".repeat(32), &asset_id, 1000).is_err());
+    }
+
+    #[test]
+    fn test_batch_hashes_match_single() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let nullifiers = [[1u8; 32], [3u8; 32]].concat();
+        let secrets = [[2u8; 32], [4u8; 32]].concat();
+
+        let commitments = compute_asset_commitments(&nullifiers, &secrets, &asset_id, 1000).unwrap();
+        assert_eq!(commitments.len(), 64);
+        let second = compute_asset_commitment(&"03".repeat(32), &"04".repeat(32), &asset_id, 1000).unwrap();
+        assert_eq!(hex::encode(&commitments[32..]), second);
+
+        let hashes = nullifier_hashes(&nullifiers).unwrap();
+        let first = zkane_crypto::generate_nullifier_hash(&Nullifier::new([1u8; 32])).unwrap();
+        assert_eq!(&hashes[..32], first.as_bytes());
+        assert!(nullifier_hashes(&[]).unwrap().is_empty());
+
+        assert!(nullifier_hashes(&nullifiers[..40]).is_err());
+        assert!(compute_asset_commitments(&nullifiers, &secrets[..32], &asset_id, 1000).is_err());
+    }
+}
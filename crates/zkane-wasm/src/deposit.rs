@@ -0,0 +1,117 @@
+//! # Deposit Plans
+//!
+//! Lays out the deposit transaction of a note with zkane-core's
+//! `build_deposit_request`, so a dapp only has to add the change script and
+//! have the wallet sign. UTXOs are passed as a JSON list of
+//! `{ txid, vout, value, script_pubkey }`, the envelope commit output first.
+
+use crate::js_error;
+use crate::note_json::deposit_note_from_json;
+use bitcoin::{Amount, FeeRate, OutPoint, ScriptBuf, TxOut, Txid};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+use zkane_common::{ZKaneError, ZKaneResult};
+use zkane_core::{build_deposit_request, DepositPlan, FundingUtxo};
+
+/// A UTXO, as passed from JavaScript.
+#[derive(Debug, Deserialize)]
+struct JsUtxo {
+    txid: String,
+    vout: u32,
+    /// Value in satoshis
+    value: u64,
+    /// The scriptPubKey, hex encoded
+    script_pubkey: String,
+}
+
+/// A deposit plan, as returned to JavaScript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositPlanJson {
+    /// The pool, as bech32m
+    pub pool_id: String,
+    /// The runestone output script, hex encoded
+    pub runestone: String,
+    /// The witness envelope revealed by the first input, hex encoded
+    pub envelope: String,
+    /// Value of the change output, the first output, in satoshis
+    pub change: u64,
+    /// Estimated size of the signed transaction, in vbytes
+    pub vsize: u64,
+    /// Fee paid by the transaction, in satoshis
+    pub fee: u64,
+}
+
+impl From<DepositPlan> for DepositPlanJson {
+    fn from(plan: DepositPlan) -> Self {
+        Self {
+            pool_id: plan.pool_id.to_bech32(),
+            runestone: hex::encode(plan.runestone.as_bytes()),
+            envelope: hex::encode(&plan.envelope),
+            change: plan.change.to_sat(),
+            vsize: plan.vsize,
+            fee: plan.fee.to_sat(),
+        }
+    }
+}
+
+/// Parse a JSON list of `{ txid, vout, value, script_pubkey }` UTXOs.
+pub fn parse_utxos(utxos_json: &str) -> ZKaneResult<Vec<FundingUtxo>> {
+    let utxos: Vec<JsUtxo> = serde_json::from_str(utxos_json)?;
+    utxos
+        .into_iter()
+        .map(|utxo| {
+            let txid = Txid::from_str(&utxo.txid)
+                .map_err(|e| ZKaneError::TransactionBuildFailed(format!("invalid txid: {}", e)))?;
+            let script = hex::decode(&utxo.script_pubkey)
+                .map_err(|e| ZKaneError::TransactionBuildFailed(format!("invalid script hex: {}", e)))?;
+            Ok(FundingUtxo {
+                outpoint: OutPoint::new(txid, utxo.vout),
+                txout: TxOut { value: Amount::from_sat(utxo.value), script_pubkey: ScriptBuf::from_bytes(script) },
+            })
+        })
+        .collect()
+}
+
+/// Plan the deposit of a note funded by UTXOs at `fee_rate` sat/vB.
+pub fn plan_deposit(note_json: &str, utxos_json: &str, fee_rate: u64) -> ZKaneResult<DepositPlanJson> {
+    let note = deposit_note_from_json(note_json)?;
+    let utxos = parse_utxos(utxos_json)?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate)
+        .ok_or_else(|| ZKaneError::TransactionBuildFailed("fee rate overflow".to_string()))?;
+    Ok(build_deposit_request(&note, &utxos, fee_rate)?.into())
+}
+
+/// Plan the deposit of a JSON note, returning the plan as JSON.
+///
+/// The transaction spends the UTXOs in order and pays the change, then a
+/// zero-value output with the runestone. The first input reveals the
+/// envelope.
+#[wasm_bindgen(js_name = buildDepositRequest)]
+pub fn build_deposit_request_json(note_json: &str, utxos_json: &str, fee_rate: u64) -> Result<String, JsValue> {
+    let plan = plan_deposit(note_json, utxos_json, fee_rate).map_err(js_error)?;
+    serde_json::to_string(&plan).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::ZkAssetId;
+
+    #[test]
+    fn test_plan_deposit() {
+        let note = zkane_core::generate_deposit_note(ZkAssetId { block: 2, tx: 1 }, 1000).unwrap();
+        let utxos = format!(
+            r#"[{{"txid":"{}","vout":0,"value":10000,"script_pubkey":"5120{}"}}]"#,
+            "11".repeat(32),
+            "22".repeat(32)
+        );
+        let plan = plan_deposit(&note.to_json(), &utxos, 2).unwrap();
+        assert_eq!(plan.envelope, note.commitment.to_hex());
+        assert_eq!(plan.change + plan.fee, 10_000);
+        assert_eq!(plan.fee, plan.vsize * 2);
+
+        assert!(plan_deposit(&note.to_json(), "[]", 2).is_err());
+        assert!(parse_utxos(r#"[{"txid":"zz","vout":0,"value":1,"script_pubkey":""}]"#).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! # Deposit Discovery
+//!
+//! Lets a dapp sync a pool with plain `fetch()` calls: transactions are fed in
+//! batches, as raw hex or Esplora JSON, and deposits into the pool are
+//! extracted and inserted into a Merkle tree kept across calls.
+//!
+//! A transaction is a deposit into the pool if one of its alkanes protostones
+//! calls the pool's `Deposit` opcode, or the factory's `GetOrCreatePool`
+//! opcode for the pool's asset and denomination. The commitment is read by
+//! zkane-core's `DepositExtractor`, like the pool contract does.
+
+use alkanes_support::cellpack::Cellpack;
+use bitcoin::consensus::deserialize;
+use bitcoin::{Transaction, Txid};
+use metashrew_support::utils::decode_varint_list;
+use ordinals::{Artifact, Runestone};
+use protorune_support::protostone::Protostone;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Cursor;
+use wasm_bindgen::prelude::*;
+use zkane_abi::{factory, pool};
+use zkane_common::{derive_pool_id, Commitment, ZKaneError, ZKaneResult, ZkAssetId};
+use zkane_core::extractor::DepositExtractor;
+pub use zkane_core::extractor::transaction_from_esplora;
+use zkane_crypto::MerkleTree;
+
+use crate::js_error;
+
+/// Protocol tag of alkanes protostones
+const ALKANES_PROTOCOL_TAG: u128 = 1;
+
+/// A deposit found while scanning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveredDeposit {
+    /// The deposit transaction
+    pub txid: String,
+    /// The deposited commitment
+    pub commitment: Commitment,
+    /// Leaf index of the commitment in the pool tree
+    pub leaf_index: u32,
+    /// Height of the block containing the deposit, if known
+    pub block_height: Option<u64>,
+}
+
+/// Incrementally extracts the deposits of a pool and builds its Merkle tree.
+///
+/// Transactions must be fed in chain order. Transactions seen before are
+/// skipped, so overlapping batches are harmless.
+#[derive(Debug, Clone)]
+pub struct DepositScanner {
+    pool_id: ZkAssetId,
+    tree: MerkleTree,
+    seen: HashSet<Txid>,
+    deposits: Vec<DiscoveredDeposit>,
+}
+
+impl DepositScanner {
+    /// Create a scanner for a pool.
+    ///
+    /// Fails with `InvalidTreeHeight` if no tree has `tree_height` levels.
+    pub fn new(pool_id: ZkAssetId, tree_height: u32) -> ZKaneResult<Self> {
+        Ok(Self {
+            pool_id,
+            tree: MerkleTree::try_new(tree_height)?,
+            seen: HashSet::new(),
+            deposits: Vec::new(),
+        })
+    }
+
+    /// Scan a transaction.
+    ///
+    /// # Returns
+    ///
+    /// The leaf index of the deposit, or `None` if the transaction is not a
+    /// new deposit into the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deposit doesn't carry a commitment or the tree
+    /// is full.
+    pub fn push_transaction(&mut self, tx: &Transaction, block_height: Option<u64>) -> ZKaneResult<Option<u32>> {
+        let txid = tx.compute_txid();
+        if self.seen.contains(&txid) || deposit_pool_id(tx).as_ref() != Some(&self.pool_id) {
+            return Ok(None);
+        }
+        let commitment = extract_commitment(tx).ok_or(ZKaneError::CommitmentNotFound)?;
+        self.record(txid, commitment, block_height).map(Some)
+    }
+
+    /// Scan a batch of raw transactions, hex encoded.
+    ///
+    /// # Returns
+    ///
+    /// The number of new deposits found.
+    pub fn push_raw_transactions(&mut self, raw_txs: &[String]) -> ZKaneResult<usize> {
+        let mut found = 0;
+        for raw_tx in raw_txs {
+            let bytes = hex::decode(raw_tx.trim()).map_err(|_| ZKaneError::TransactionParseError)?;
+            let tx: Transaction = deserialize(&bytes).map_err(|_| ZKaneError::TransactionParseError)?;
+            if self.push_transaction(&tx, None)?.is_some() {
+                found += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Scan Esplora transaction JSON: a single transaction or an array, such
+    /// as the response of `/address/:address/txs`.
+    ///
+    /// # Returns
+    ///
+    /// The number of new deposits found.
+    pub fn push_esplora_json(&mut self, json: &Value) -> ZKaneResult<usize> {
+        let txs = match json {
+            Value::Array(txs) => txs.as_slice(),
+            tx => std::slice::from_ref(tx),
+        };
+        let mut found = 0;
+        for tx_json in txs {
+            let tx = transaction_from_esplora(tx_json)?;
+            if self.push_transaction(&tx, tx_json["status"]["block_height"].as_u64())?.is_some() {
+                found += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Get the current Merkle root.
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    /// Get the number of deposits found.
+    pub fn leaf_count(&self) -> u32 {
+        self.tree.leaf_count()
+    }
+
+    /// Get the deposits found, in leaf order.
+    pub fn deposits(&self) -> &[DiscoveredDeposit] {
+        &self.deposits
+    }
+
+    /// Get the Merkle tree built from the deposits.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    fn record(&mut self, txid: Txid, commitment: Commitment, block_height: Option<u64>) -> ZKaneResult<u32> {
+        let leaf_index = self.tree.insert(&commitment)?;
+        self.seen.insert(txid);
+        self.deposits.push(DiscoveredDeposit {
+            txid: txid.to_string(),
+            commitment,
+            leaf_index,
+            block_height,
+        });
+        Ok(leaf_index)
+    }
+}
+
+/// Get the pool a transaction deposits into, if any.
+pub fn deposit_pool_id(tx: &Transaction) -> Option<ZkAssetId> {
+    let Some(Artifact::Runestone(runestone)) = Runestone::decipher(tx) else {
+        return None;
+    };
+    let protostones = Protostone::from_runestone(&runestone).ok()?;
+    protostones
+        .iter()
+        .filter(|protostone| protostone.protocol_tag == ALKANES_PROTOCOL_TAG)
+        .find_map(|protostone| {
+            let values = decode_varint_list(&mut Cursor::new(protostone.message.clone())).ok()?;
+            let cellpack = Cellpack::try_from(values).ok()?;
+            match cellpack.inputs.as_slice() {
+                [pool::DEPOSIT] => Some(cellpack.target.into()),
+                [factory::GET_OR_CREATE_POOL, asset_block, asset_tx, denomination] => {
+                    let asset_id = ZkAssetId {
+                        block: *asset_block,
+                        tx: *asset_tx,
+                    };
+                    Some(derive_pool_id(&asset_id, *denomination))
+                }
+                _ => None,
+            }
+        })
+}
+
+/// Extract the deposit commitment of a transaction.
+///
+/// Uses the same [`DepositExtractor`] as the pool contract and
+/// `PrivacyPool::add_commitment`, so the scanner finds exactly the deposits
+/// the pool accepted.
+pub fn extract_commitment(tx: &Transaction) -> Option<Commitment> {
+    DepositExtractor::default().extract(tx).map(|extracted| extracted.commitment)
+}
+
+/// JavaScript handle to a [`DepositScanner`].
+#[wasm_bindgen]
+pub struct JsDepositScanner {
+    inner: DepositScanner,
+}
+
+#[wasm_bindgen]
+impl JsDepositScanner {
+    /// Create a scanner for the pool `pool_block:pool_tx`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(pool_block: u128, pool_tx: u128, tree_height: u32) -> Result<JsDepositScanner, JsValue> {
+        let pool_id = ZkAssetId {
+            block: pool_block,
+            tx: pool_tx,
+        };
+        Ok(JsDepositScanner {
+            inner: DepositScanner::new(pool_id, tree_height).map_err(js_error)?,
+        })
+    }
+
+    /// Scan Esplora transaction JSON, returning the number of new deposits.
+    #[wasm_bindgen(js_name = pushEsploraJson)]
+    pub fn push_esplora_json(&mut self, json: &str) -> Result<u32, JsValue> {
+        let value: Value = serde_json::from_str(json).map_err(js_error)?;
+        self.inner.push_esplora_json(&value).map(|found| found as u32).map_err(js_error)
+    }
+
+    /// Scan a JSON array of raw transaction hex, returning the number of new
+    /// deposits.
+    #[wasm_bindgen(js_name = pushRawTransactions)]
+    pub fn push_raw_transactions(&mut self, raw_txs_json: &str) -> Result<u32, JsValue> {
+        let raw_txs: Vec<String> = serde_json::from_str(raw_txs_json).map_err(js_error)?;
+        self.inner.push_raw_transactions(&raw_txs).map(|found| found as u32).map_err(js_error)
+    }
+
+    /// The current Merkle root, hex encoded.
+    #[wasm_bindgen(getter)]
+    pub fn root(&self) -> String {
+        hex::encode(self.inner.root())
+    }
+
+    /// The number of deposits found.
+    #[wasm_bindgen(getter, js_name = leafCount)]
+    pub fn leaf_count(&self) -> u32 {
+        self.inner.leaf_count()
+    }
+
+    /// The deposits found, as a JSON array.
+    #[wasm_bindgen(js_name = depositsJson)]
+    pub fn deposits_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string(self.inner.deposits()).map_err(js_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Amount, Sequence};
+    use std::str::FromStr;
+
+    fn esplora_tx(commitment_script: &str, block_height: u64) -> Value {
+        serde_json::json!({
+            "txid": "00".repeat(32),
+            "version": 2,
+            "locktime": 0,
+            "vin": [{
+                "txid": "11".repeat(32),
+                "vout": 0,
+                "scriptsig": "",
+                "witness": ["aa", "bbcc"],
+                "sequence": 4294967293u64,
+                "is_coinbase": false
+            }],
+            "vout": [
+                { "scriptpubkey": "0014".to_string() + &"22".repeat(20), "value": 546 },
+                { "scriptpubkey": commitment_script, "value": 0 }
+            ],
+            "status": { "confirmed": true, "block_height": block_height }
+        })
+    }
+
+    #[test]
+    fn test_transaction_from_esplora() {
+        let json = esplora_tx("6a", 100);
+        let tx = transaction_from_esplora(&json).unwrap();
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].witness.len(), 2);
+        assert_eq!(tx.input[0].sequence, Sequence(0xfffffffd));
+        assert_eq!(tx.output[0].value, Amount::from_sat(546));
+
+        assert!(transaction_from_esplora(&serde_json::json!({ "vin": [] })).is_err());
+    }
+
+    #[test]
+    fn test_extract_commitment_from_op_return() {
+        let script = format!("6a{}", hex::encode([7u8; 32]));
+        let tx = transaction_from_esplora(&esplora_tx(&script, 100)).unwrap();
+        assert_eq!(extract_commitment(&tx), Some(Commitment::new([7u8; 32])));
+
+        let short = transaction_from_esplora(&esplora_tx("6a0102", 100)).unwrap();
+        assert_eq!(extract_commitment(&short), None);
+    }
+
+    #[test]
+    fn test_scanner_ignores_other_transactions() {
+        let mut scanner = DepositScanner::new(ZkAssetId { block: 6, tx: 1 }, 4).unwrap();
+        // A commitment without a call to the pool is not a deposit
+        let script = format!("6a{}", hex::encode([7u8; 32]));
+        let batch = Value::Array(vec![esplora_tx(&script, 100)]);
+        assert_eq!(scanner.push_esplora_json(&batch).unwrap(), 0);
+        assert_eq!(scanner.leaf_count(), 0);
+    }
+
+    #[test]
+    fn test_scanner_keeps_tree_state_across_batches() {
+        let mut scanner = DepositScanner::new(ZkAssetId { block: 6, tx: 1 }, 4).unwrap();
+        let mut expected = MerkleTree::new(4);
+        for n in 1..=3u8 {
+            let txid = Txid::from_str(&hex::encode([n; 32])).unwrap();
+            let leaf = scanner.record(txid, Commitment::new([n; 32]), Some(100 + n as u64)).unwrap();
+            assert_eq!(leaf, expected.insert(&Commitment::new([n; 32])).unwrap());
+        }
+        assert_eq!(scanner.root(), expected.root());
+        assert_eq!(scanner.deposits()[2].block_height, Some(103));
+        assert_eq!(scanner.deposits()[2].leaf_index, 2);
+    }
+}
@@ -0,0 +1,67 @@
+//! # Bech32m Encodings
+//!
+//! Converts commitments, nullifier hashes and pool IDs between hex or
+//! `block:tx` and their bech32m forms (`zkc1...`, `zkn1...`, `zkp1...`), for
+//! dapps displaying values users copy around.
+
+use crate::js_error;
+use wasm_bindgen::prelude::*;
+use zkane_common::{Commitment, NullifierHash, ZKaneError, ZkAssetId};
+
+fn parse_hex<T>(hex: &str, parse: fn(&str) -> anyhow::Result<T>) -> Result<T, JsValue> {
+    parse(hex.trim().trim_start_matches("0x")).map_err(|e| js_error(ZKaneError::SerializationError(e.to_string())))
+}
+
+/// Encode a hex commitment as bech32m.
+#[wasm_bindgen(js_name = commitmentToBech32)]
+pub fn commitment_to_bech32(commitment_hex: &str) -> Result<String, JsValue> {
+    Ok(parse_hex(commitment_hex, Commitment::from_hex)?.to_bech32())
+}
+
+/// Decode a bech32m commitment to hex.
+#[wasm_bindgen(js_name = commitmentFromBech32)]
+pub fn commitment_from_bech32(encoded: &str) -> Result<String, JsValue> {
+    Commitment::from_bech32(encoded).map(|commitment| commitment.to_hex()).map_err(js_error)
+}
+
+/// Encode a hex nullifier hash as bech32m.
+#[wasm_bindgen(js_name = nullifierHashToBech32)]
+pub fn nullifier_hash_to_bech32(nullifier_hash_hex: &str) -> Result<String, JsValue> {
+    Ok(parse_hex(nullifier_hash_hex, NullifierHash::from_hex)?.to_bech32())
+}
+
+/// Decode a bech32m nullifier hash to hex.
+#[wasm_bindgen(js_name = nullifierHashFromBech32)]
+pub fn nullifier_hash_from_bech32(encoded: &str) -> Result<String, JsValue> {
+    NullifierHash::from_bech32(encoded).map(|hash| hash.to_hex()).map_err(js_error)
+}
+
+/// Encode the pool `block:tx` as bech32m.
+#[wasm_bindgen(js_name = poolIdToBech32)]
+pub fn pool_id_to_bech32(block: u128, tx: u128) -> String {
+    ZkAssetId { block, tx }.to_bech32()
+}
+
+/// Decode a bech32m pool ID to `block:tx`.
+#[wasm_bindgen(js_name = poolIdFromBech32)]
+pub fn pool_id_from_bech32(encoded: &str) -> Result<String, JsValue> {
+    ZkAssetId::from_bech32(encoded).map(|id| id.to_string()).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bech32_bindings() {
+        let hex = "cd".repeat(32);
+        let encoded = commitment_to_bech32(&format!("0x{}", hex)).unwrap();
+        assert_eq!(commitment_from_bech32(&encoded).unwrap(), hex);
+
+        let encoded = nullifier_hash_to_bech32(&hex).unwrap();
+        assert!(encoded.starts_with("zkn1"));
+        assert_eq!(nullifier_hash_from_bech32(&encoded).unwrap(), hex);
+
+        assert_eq!(pool_id_from_bech32(&pool_id_to_bech32(6, 1)).unwrap(), "6:1");
+    }
+}
@@ -0,0 +1,124 @@
+//! # ZKane WASM Bindings
+//!
+//! Browser-compatible API for dapps integrating ZKane privacy pools. Build it
+//! with `wasm-pack build crates/zkane-wasm --target web`.
+//!
+//! The bindings are thin wrappers around plain Rust types, which hold the
+//! actual logic so it can be tested natively.
+//!
+//! ## Modules
+//!
+//! - `client` - Pool sync straight from an Esplora endpoint (`pool-client`)
+//! - `crypto` - Commitment and nullifier hashing, one note or a batch at a time (`crypto`)
+//! - `deposit` - Deposit transaction plans for a note and its UTXOs (`pool-client`)
+//! - `discovery` - Incremental discovery of pool deposits from fetched transactions (`pool-client`)
+//! - [`encoding`] - Bech32m encodings of commitments, nullifier hashes and pool IDs
+//! - [`note_json`] - The versioned JSON schema of deposit notes
+//! - `notes` - Deposit note generation and verification (`notes`)
+//! - [`proof`] - Typed Merkle path and withdrawal proof classes
+//! - `prover` - Withdrawal proof generation with progress and cancellation (`prover`)
+//! - `verifier` - Withdrawal proof verification against the verifying key (`verifier`)
+//!
+//! ## Features
+//!
+//! Every module is built by default. Dapps that only need part of the API
+//! build with `--no-default-features` and the features they use, so the
+//! bundle leaves out the prover or the pool client's parsing dependencies.
+//! `scripts/build-wasm-bundles.sh` builds one size-optimized bundle per
+//! feature.
+
+use wasm_bindgen::prelude::*;
+use zkane_common::ZKaneError;
+#[cfg(any(test, feature = "test-utils"))]
+use zkane_common::{DepositNote, ZKaneResult, ZkAssetId};
+
+#[cfg(feature = "pool-client")]
+pub mod client;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "pool-client")]
+pub mod deposit;
+#[cfg(feature = "pool-client")]
+pub mod discovery;
+pub mod encoding;
+pub mod note_json;
+#[cfg(feature = "notes")]
+pub mod notes;
+pub mod proof;
+#[cfg(feature = "prover")]
+pub mod prover;
+#[cfg(feature = "verifier")]
+pub mod verifier;
+
+#[cfg(feature = "pool-client")]
+pub use client::{EsploraSync, JsPoolClient};
+#[cfg(feature = "pool-client")]
+pub use deposit::DepositPlanJson;
+#[cfg(feature = "pool-client")]
+pub use discovery::{DepositScanner, DiscoveredDeposit, JsDepositScanner};
+pub use proof::{JsMerklePath, JsWithdrawalProof};
+#[cfg(feature = "prover")]
+pub use prover::JsProverHandle;
+#[cfg(feature = "verifier")]
+pub use verifier::{JsWithdrawalVerifier, WithdrawalPublicInputs};
+
+/// Convert an error into a JavaScript exception value
+///
+/// The exception is an `Error` whose `code` property holds the stable
+/// [`ZKaneError::code`], so dapps don't have to parse messages.
+pub(crate) fn js_error(error: impl Into<ZKaneError>) -> JsValue {
+    let error = error.into();
+    let exception = js_sys::Error::new(&format!("ZKane Error: {}", error));
+    // Setting a property on a fresh object can't fail
+    let _ = js_sys::Reflect::set(&exception, &JsValue::from_str("code"), &JsValue::from(error.code()));
+    exception.into()
+}
+
+/// Self-test the browser's secure random source, `crypto.getRandomValues`.
+///
+/// Dapps call this at startup to fail loudly, with error code 2007, before
+/// generating notes in an environment without usable randomness.
+#[wasm_bindgen(js_name = checkEntropy)]
+pub fn check_entropy() -> Result<(), JsValue> {
+    zkane_common::check_entropy().map_err(js_error)
+}
+
+/// Generate a deposit note from a fixed seed, as JSON.
+///
+/// The same seed always gives the same note, so dapp tests can be
+/// reproduced. Only built with the `test-utils` feature, as anyone knowing
+/// the seed can spend the note.
+#[cfg(feature = "test-utils")]
+#[wasm_bindgen(js_name = generateSeededDepositNote)]
+pub fn generate_seeded_deposit_note(
+    asset_block: u128,
+    asset_tx: u128,
+    denomination: u128,
+    seed: u64,
+) -> Result<String, JsValue> {
+    let note = seeded_deposit_note(asset_block, asset_tx, denomination, seed).map_err(js_error)?;
+    Ok(note.to_json())
+}
+
+/// Generate a deposit note from a fixed seed.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn seeded_deposit_note(asset_block: u128, asset_tx: u128, denomination: u128, seed: u64) -> ZKaneResult<DepositNote> {
+    let asset_id = ZkAssetId {
+        block: asset_block,
+        tx: asset_tx,
+    };
+    zkane_core::generate_deposit_note_with_rng(asset_id, denomination, &mut zkane_core::note_rng(Some(seed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_deposit_note() {
+        let note = seeded_deposit_note(2, 1, 1000, 7).unwrap();
+        assert_eq!(note.commitment, seeded_deposit_note(2, 1, 1000, 7).unwrap().commitment);
+        assert_ne!(note.commitment, seeded_deposit_note(2, 1, 1000, 8).unwrap().commitment);
+        assert!(zkane_core::verify_deposit_note(&note).unwrap());
+    }
+}
@@ -0,0 +1,74 @@
+//! # Note JSON
+//!
+//! Deposit notes cross the binding as JSON in the versioned note schema of
+//! zkane-common, which the CLI also imports, so a note saved by
+//! one dapp opens in any other. Notes generated by earlier builds were the
+//! plain serde encoding of `DepositNote`; they are still read, and
+//! `depositNoteToJson` upgrades them.
+
+use crate::js_error;
+use wasm_bindgen::prelude::*;
+use zkane_common::{DepositNote, ZKaneError, ZKaneResult, NOTE_JSON_VERSION};
+
+/// Get the version of the note JSON schema this build writes.
+#[wasm_bindgen(js_name = noteJsonVersion)]
+pub fn note_json_version() -> u32 {
+    NOTE_JSON_VERSION
+}
+
+/// Re-encode a JSON deposit note in the current note schema.
+///
+/// Accepts any note [`deposit_note_from_json`] reads, so dapps can upgrade
+/// notes stored by earlier builds.
+#[wasm_bindgen(js_name = depositNoteToJson)]
+pub fn deposit_note_to_json(note_json: &str) -> Result<String, JsValue> {
+    Ok(deposit_note_from_json(note_json).map_err(js_error)?.to_json())
+}
+
+/// Read a JSON deposit note, in the note schema or the plain serde encoding
+/// of earlier builds.
+///
+/// A note carrying a `checksum` is read as the schema, so one with an
+/// unknown version is rejected rather than misread. The serde encoding has
+/// its own `version` since deposit note version 2, and is rejected if newer
+/// than this build's.
+pub fn deposit_note_from_json(note_json: &str) -> ZKaneResult<DepositNote> {
+    let invalid = |e: serde_json::Error| ZKaneError::SerializationError(format!("invalid note JSON: {}", e));
+    let value: serde_json::Value = serde_json::from_str(note_json).map_err(invalid)?;
+    if value.get("checksum").is_some() {
+        return DepositNote::from_json(note_json);
+    }
+    // Reparsed from the text, as a JSON value can't hold the u128 fields
+    let note: DepositNote = serde_json::from_str(note_json).map_err(invalid)?;
+    note.check_version()?;
+    Ok(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::ZkAssetId;
+
+    #[test]
+    fn test_deposit_note_from_json() {
+        let note = DepositNote::random(ZkAssetId { block: 2, tx: 1 }, 1000);
+        let legacy = serde_json::to_string(&note).unwrap();
+        assert_eq!(deposit_note_from_json(&note.to_json()).unwrap().commitment, note.commitment);
+        assert_eq!(deposit_note_from_json(&legacy).unwrap().commitment, note.commitment);
+
+        // Upgrading a legacy note gives the schema encoding
+        assert_eq!(deposit_note_to_json(&legacy).unwrap(), note.to_json());
+
+        let mut future: serde_json::Value = serde_json::from_str(&note.to_json()).unwrap();
+        future["version"] = (NOTE_JSON_VERSION + 1).into();
+        let err = deposit_note_from_json(&future.to_string()).unwrap_err();
+        assert_eq!(err.code(), ZKaneError::SerializationError(String::new()).code());
+
+        let mut future: serde_json::Value = serde_json::from_str(&legacy).unwrap();
+        future["version"] = (zkane_common::DEPOSIT_NOTE_VERSION + 1).into();
+        assert!(matches!(
+            deposit_note_from_json(&future.to_string()),
+            Err(ZKaneError::UnsupportedNoteVersion { .. })
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+//! # Deposit Notes
+//!
+//! Generation and verification of deposit notes, exchanged as JSON in the
+//! note schema described in [`crate::note_json`]. Built
+//! with the `notes` feature, which only needs the hashing of [`crate::crypto`],
+//! so a dapp that just creates deposits doesn't ship the prover or the pool
+//! client.
+
+use crate::js_error;
+use crate::note_json::deposit_note_from_json;
+use wasm_bindgen::prelude::*;
+use zkane_common::{check_entropy, DepositNote, Nullifier, Secret, ZKaneResult, ZkAssetId};
+
+/// Generate a deposit note for a pool, as JSON.
+///
+/// The secret and nullifier are drawn from the browser's secure random
+/// source. Anyone holding the note can withdraw it.
+#[wasm_bindgen(js_name = generateDepositNote)]
+pub fn generate_deposit_note(asset_block: u128, asset_tx: u128, denomination: u128) -> Result<String, JsValue> {
+    let asset_id = ZkAssetId { block: asset_block, tx: asset_tx };
+    let note = new_deposit_note(asset_id, denomination).map_err(js_error)?;
+    Ok(note.to_json())
+}
+
+/// Check that a JSON deposit note's commitment matches its secret,
+/// nullifier, asset and denomination.
+#[wasm_bindgen(js_name = verifyDepositNote)]
+pub fn verify_deposit_note(note_json: &str) -> Result<bool, JsValue> {
+    let note = deposit_note_from_json(note_json).map_err(js_error)?;
+    is_valid_note(&note).map_err(js_error)
+}
+
+/// Check a batch of JSON deposit notes, given as a JSON array.
+///
+/// Returns one byte per note, in order: 1 if its commitment matches, 0 if it
+/// doesn't or the note can't be decoded.
+#[wasm_bindgen(js_name = verifyNotes)]
+pub fn verify_notes(notes_json: &str) -> Result<Vec<u8>, JsValue> {
+    check_notes(notes_json).map_err(js_error)
+}
+
+/// Check each note of a JSON array of notes.
+pub fn check_notes(notes_json: &str) -> ZKaneResult<Vec<u8>> {
+    let notes: Vec<serde_json::Value> = serde_json::from_str(notes_json)?;
+    Ok(notes
+        .iter()
+        .map(|note| {
+            deposit_note_from_json(&note.to_string())
+                .and_then(|note| is_valid_note(&note))
+                .unwrap_or(false) as u8
+        })
+        .collect())
+}
+
+/// Generate a deposit note with a random secret and nullifier.
+///
+/// Fails with `EntropyUnavailable` rather than drawing them from a broken
+/// random source.
+pub fn new_deposit_note(asset_id: ZkAssetId, denomination: u128) -> ZKaneResult<DepositNote> {
+    check_entropy()?;
+    let secret = Secret::random();
+    let nullifier = Nullifier::random();
+    let commitment = zkane_crypto::generate_asset_commitment(&nullifier, &secret, &asset_id, denomination)?;
+    Ok(DepositNote::new(secret, nullifier, commitment, asset_id, denomination, 0))
+}
+
+/// Check a deposit note's commitment.
+pub fn is_valid_note(note: &DepositNote) -> ZKaneResult<bool> {
+    Ok(zkane_crypto::verify_asset_commitment(
+        &note.commitment,
+        &note.nullifier,
+        &note.secret,
+        &note.asset_id,
+        note.denomination,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_notes_verify() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let note = new_deposit_note(asset_id, 1000).unwrap();
+        assert!(is_valid_note(&note).unwrap());
+        assert_ne!(note.commitment, new_deposit_note(asset_id, 1000).unwrap().commitment);
+
+        // A note moved to another denomination no longer matches its commitment
+        let mut moved = note.clone();
+        moved.denomination = 2000;
+        assert!(!is_valid_note(&moved).unwrap());
+    }
+
+    #[test]
+    fn test_check_notes() {
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let note = new_deposit_note(asset_id, 1000).unwrap();
+        let mut moved = note.clone();
+        moved.denomination = 2000;
+
+        let notes = format!("[{},{},{{}}]", note.to_json(), serde_json::to_string(&moved).unwrap());
+        assert_eq!(check_notes(&notes).unwrap(), vec![1, 0, 0]);
+        assert!(check_notes("[]").unwrap().is_empty());
+        assert!(check_notes("{}").is_err());
+    }
+}
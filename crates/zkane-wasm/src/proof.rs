@@ -0,0 +1,425 @@
+//! # Proof Types
+//!
+//! Typed JavaScript classes for Merkle paths and withdrawal proofs, so dapps
+//! don't have to pass path elements and indices around as JSON strings.
+//!
+//! Hashes are exchanged as hex strings, proofs and encoded values as byte
+//! arrays. Each class converts to and from its `zkane-common` counterpart.
+
+use crate::js_error;
+use bitcoin::{Amount, ScriptBuf, TxOut};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use zkane_common::{
+    calculate_outputs_hash, Commitment, EnvelopeFormat, MerklePath, NullifierHash, PublicInputs, Recipient, WithdrawalProof, WithdrawalWitness, ZKaneError,
+    ZKaneResult,
+};
+
+/// Decode a 32-byte hex value
+pub(crate) fn decode_hash(value_hex: &str, name: &str) -> ZKaneResult<[u8; 32]> {
+    let bytes = hex::decode(value_hex)
+        .map_err(|e| ZKaneError::InvalidProof(format!("invalid {} hex: {}", name, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| ZKaneError::InvalidProof(format!("{} must be 32 bytes", name)))
+}
+
+/// A transaction output, as passed from JavaScript.
+#[derive(Debug, Deserialize)]
+struct JsOutput {
+    /// Value in satoshis
+    value: u64,
+    /// The scriptPubKey, hex encoded
+    script_pubkey: String,
+}
+
+/// Parse a JSON list of `{ value, script_pubkey }` outputs.
+pub(crate) fn parse_outputs(outputs_json: &str) -> ZKaneResult<Vec<TxOut>> {
+    let outputs: Vec<JsOutput> = serde_json::from_str(outputs_json)?;
+    outputs
+        .into_iter()
+        .map(|output| {
+            let script = hex::decode(&output.script_pubkey)
+                .map_err(|e| ZKaneError::InvalidProof(format!("invalid script hex: {}", e)))?;
+            Ok(TxOut { value: Amount::from_sat(output.value), script_pubkey: ScriptBuf::from_bytes(script) })
+        })
+        .collect()
+}
+
+/// Hash the recipient outputs of a withdrawal.
+///
+/// Takes a JSON list of `{ value, script_pubkey }` outputs, in the order the
+/// withdrawal transaction pays them, and returns the hex hash to prove
+/// against.
+#[wasm_bindgen(js_name = recipientsHash)]
+pub fn recipients_hash(outputs_json: &str) -> Result<String, JsValue> {
+    parse_outputs(outputs_json)
+        .map(|outputs| hex::encode(calculate_outputs_hash(&outputs)))
+        .map_err(js_error)
+}
+
+/// A Merkle inclusion path.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct JsMerklePath {
+    inner: MerklePath,
+}
+
+#[wasm_bindgen]
+impl JsMerklePath {
+    /// Create a path from hex sibling hashes and direction flags, leaf level
+    /// first. A non-zero flag means the node is a right child.
+    #[wasm_bindgen(constructor)]
+    pub fn new(elements: Vec<String>, indices: Vec<u8>) -> Result<JsMerklePath, JsValue> {
+        Self::try_new(&elements, &indices).map_err(js_error)
+    }
+
+    /// Decode a path from its compact binary encoding.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<JsMerklePath, JsValue> {
+        MerklePath::from_bytes(bytes).map(Self::from).map_err(js_error)
+    }
+
+    /// Encode the path in its compact binary encoding.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        self.inner.to_bytes().map_err(js_error)
+    }
+
+    /// The sibling hashes, hex encoded.
+    #[wasm_bindgen(getter)]
+    pub fn elements(&self) -> Vec<String> {
+        self.inner.elements.iter().map(hex::encode).collect()
+    }
+
+    /// The direction flags, 1 where the node is a right child.
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> Vec<u8> {
+        self.inner.indices.iter().map(|&right| right as u8).collect()
+    }
+
+    /// The number of levels in the path.
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.inner.len() as u32
+    }
+}
+
+impl JsMerklePath {
+    /// Create a path from hex sibling hashes and direction flags.
+    pub fn try_new(elements: &[String], indices: &[u8]) -> ZKaneResult<Self> {
+        let elements = elements
+            .iter()
+            .map(|element| decode_hash(element, "path element"))
+            .collect::<ZKaneResult<Vec<_>>>()?;
+        let indices = indices.iter().map(|&flag| flag != 0).collect();
+        Ok(Self {
+            inner: MerklePath::new(elements, indices).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?,
+        })
+    }
+
+    /// Get the wrapped path.
+    pub fn inner(&self) -> &MerklePath {
+        &self.inner
+    }
+}
+
+impl From<MerklePath> for JsMerklePath {
+    fn from(inner: MerklePath) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<JsMerklePath> for MerklePath {
+    fn from(path: JsMerklePath) -> Self {
+        path.inner
+    }
+}
+
+/// A withdrawal proof and its public inputs.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct JsWithdrawalProof {
+    inner: WithdrawalProof,
+}
+
+#[wasm_bindgen]
+impl JsWithdrawalProof {
+    /// Create a self-relayed proof.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        proof: Vec<u8>,
+        merkle_root_hex: &str,
+        nullifier_hash_hex: &str,
+        recipient_script_hex: &str,
+    ) -> Result<JsWithdrawalProof, JsValue> {
+        Self::try_new(proof, merkle_root_hex, nullifier_hash_hex, recipient_script_hex).map_err(js_error)
+    }
+
+    /// Copy the proof with relayer fee information attached.
+    #[wasm_bindgen(js_name = withRelayer)]
+    pub fn with_relayer(&self, relayer_output_hash_hex: &str, fee: u128) -> Result<JsWithdrawalProof, JsValue> {
+        let relayer_output_hash = decode_hash(relayer_output_hash_hex, "relayer output hash").map_err(js_error)?;
+        Ok(self.inner.clone().with_relayer(relayer_output_hash, fee).into())
+    }
+
+    /// Copy the proof bound to a JSON list of `{ value, script_pubkey }`
+    /// recipient outputs.
+    #[wasm_bindgen(js_name = withRecipients)]
+    pub fn with_recipients(&self, outputs_json: &str) -> Result<JsWithdrawalProof, JsValue> {
+        let outputs = parse_outputs(outputs_json).map_err(js_error)?;
+        Ok(self.inner.clone().with_recipients(&outputs).into())
+    }
+
+    /// Copy the proof bound to a single output paying `value` sats to its
+    /// recipient.
+    #[wasm_bindgen(js_name = bindRecipient)]
+    pub fn bind_recipient(&self, value: u64) -> JsWithdrawalProof {
+        self.inner.clone().bind_recipient(Amount::from_sat(value)).into()
+    }
+
+    /// Copy the proof expiring after a block height (0 for no expiry).
+    #[wasm_bindgen(js_name = withExpiry)]
+    pub fn with_expiry(&self, not_after_height: u64) -> JsWithdrawalProof {
+        self.inner.clone().with_expiry(not_after_height).into()
+    }
+
+    /// Decode a proof from its canonical binary encoding.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<JsWithdrawalProof, JsValue> {
+        WithdrawalProof::from_bytes(bytes).map(Self::from).map_err(js_error)
+    }
+
+    /// Encode the proof in its canonical binary encoding.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    /// The proof bytes.
+    #[wasm_bindgen(getter)]
+    pub fn proof(&self) -> Vec<u8> {
+        self.inner.proof.clone()
+    }
+
+    /// The Merkle root the proof was generated against, hex encoded.
+    #[wasm_bindgen(getter, js_name = merkleRoot)]
+    pub fn merkle_root(&self) -> String {
+        hex::encode(self.inner.merkle_root)
+    }
+
+    /// The revealed nullifier hash, hex encoded.
+    #[wasm_bindgen(getter, js_name = nullifierHash)]
+    pub fn nullifier_hash(&self) -> String {
+        self.inner.nullifier_hash.to_hex()
+    }
+
+    /// The recipient scriptPubKey, hex encoded (empty if unnamed).
+    #[wasm_bindgen(getter)]
+    pub fn recipient(&self) -> String {
+        self.inner.recipient.to_hex()
+    }
+
+    /// Hash of the recipient outputs, hex encoded (all zeros if unbound).
+    #[wasm_bindgen(getter, js_name = recipientsHash)]
+    pub fn recipients_hash(&self) -> String {
+        hex::encode(self.inner.recipients_hash)
+    }
+
+    /// Hash of the output paying the relayer, hex encoded.
+    #[wasm_bindgen(getter, js_name = relayerOutputHash)]
+    pub fn relayer_output_hash(&self) -> String {
+        hex::encode(self.inner.relayer_output_hash)
+    }
+
+    /// Fee paid to the relayer.
+    #[wasm_bindgen(getter)]
+    pub fn fee(&self) -> u128 {
+        self.inner.fee
+    }
+
+    /// Last block height the proof may be spent at (0 if it never expires).
+    #[wasm_bindgen(getter, js_name = notAfterHeight)]
+    pub fn not_after_height(&self) -> u64 {
+        self.inner.not_after_height
+    }
+
+    /// Version of the circuit the proof was generated with.
+    #[wasm_bindgen(getter, js_name = circuitVersion)]
+    pub fn circuit_version(&self) -> u32 {
+        self.inner.circuit_version
+    }
+
+    /// Get the circuit's public inputs for a withdrawal paying outputs with
+    /// the given hash, as `0x`-prefixed field elements in circuit order.
+    #[wasm_bindgen(js_name = publicInputs)]
+    pub fn public_inputs(&self, outputs_hash_hex: &str) -> Result<Vec<String>, JsValue> {
+        let outputs_hash = decode_hash(outputs_hash_hex, "outputs hash").map_err(js_error)?;
+        let proof = &self.inner;
+        Ok(PublicInputs::new(
+            proof.merkle_root,
+            *proof.nullifier_hash.as_bytes(),
+            outputs_hash,
+            proof.recipients_hash,
+            proof.relayer_output_hash,
+            proof.fee,
+            proof.not_after_height,
+        )
+        .to_hex_fields())
+    }
+}
+
+impl JsWithdrawalProof {
+    /// Create a self-relayed proof from hex public inputs and the hex
+    /// scriptPubKey of its recipient.
+    pub fn try_new(
+        proof: Vec<u8>,
+        merkle_root_hex: &str,
+        nullifier_hash_hex: &str,
+        recipient_script_hex: &str,
+    ) -> ZKaneResult<Self> {
+        let merkle_root = decode_hash(merkle_root_hex, "merkle root")?;
+        let nullifier_hash = NullifierHash::new(decode_hash(nullifier_hash_hex, "nullifier hash")?);
+        let recipient = Recipient::from_hex(recipient_script_hex)?;
+        Ok(WithdrawalProof::new(proof, merkle_root, nullifier_hash, recipient).into())
+    }
+
+    /// Get the wrapped proof.
+    pub fn inner(&self) -> &WithdrawalProof {
+        &self.inner
+    }
+}
+
+impl From<WithdrawalProof> for JsWithdrawalProof {
+    fn from(inner: WithdrawalProof) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<JsWithdrawalProof> for WithdrawalProof {
+    fn from(proof: JsWithdrawalProof) -> Self {
+        proof.inner
+    }
+}
+
+/// Build the withdrawal witness envelope of a proof.
+///
+/// Returns the [`WithdrawalWitness`] the pool contract reads from the
+/// envelope, compressed when that makes it smaller. With `json` set the
+/// witness is encoded as JSON instead, which the pool also accepts and which
+/// is easier to inspect while debugging.
+#[wasm_bindgen(js_name = generateWithdrawalWitness)]
+pub fn generate_withdrawal_witness(
+    proof: &JsWithdrawalProof,
+    path: &JsMerklePath,
+    leaf_index: u32,
+    commitment_hex: &str,
+    outputs_hash_hex: &str,
+    json: bool,
+) -> Result<Vec<u8>, JsValue> {
+    let format = if json { EnvelopeFormat::Json } else { EnvelopeFormat::Compressed };
+    build_withdrawal_witness(proof, path, leaf_index, commitment_hex, outputs_hash_hex)
+        .and_then(|witness| witness.to_envelope(format))
+        .map_err(js_error)
+}
+
+/// Assemble the withdrawal witness of a proof.
+pub fn build_withdrawal_witness(
+    proof: &JsWithdrawalProof,
+    path: &JsMerklePath,
+    leaf_index: u32,
+    commitment_hex: &str,
+    outputs_hash_hex: &str,
+) -> ZKaneResult<WithdrawalWitness> {
+    // The direction flags are the bits of the leaf index
+    let index = leaf_index as u64;
+    let matches_path = index >> path.inner.len().min(63) == 0
+        && path.inner.indices.iter().enumerate().all(|(level, &right)| (index >> level) & 1 == right as u64);
+    if !matches_path {
+        return Err(ZKaneError::InvalidProof(format!(
+            "leaf index {} doesn't match the path",
+            leaf_index
+        )));
+    }
+    Ok(WithdrawalWitness {
+        proof: proof.inner.clone(),
+        path: path.inner.clone(),
+        leaf_index,
+        commitment: Commitment::new(decode_hash(commitment_hex, "commitment")?),
+        outputs_hash: decode_hash(outputs_hash_hex, "outputs hash")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_path_conversion() {
+        let elements = vec!["11".repeat(32), "22".repeat(32)];
+        let path = JsMerklePath::try_new(&elements, &[1, 0]).unwrap();
+        assert_eq!(path.elements(), elements);
+        assert_eq!(path.indices(), vec![1, 0]);
+        assert_eq!(path.height(), 2);
+
+        let inner: MerklePath = path.clone().into();
+        assert_eq!(inner.indices, vec![true, false]);
+        assert_eq!(JsMerklePath::from(inner).elements(), elements);
+
+        assert!(JsMerklePath::try_new(&["11".repeat(31)], &[0]).is_err());
+        assert!(JsMerklePath::try_new(&elements, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_witness_from_classes() {
+        let proof = JsWithdrawalProof::try_new(vec![7u8; 4], &"aa".repeat(32), &"bb".repeat(32), "51").unwrap();
+        assert_eq!(proof.merkle_root(), "aa".repeat(32));
+        assert_eq!(proof.nullifier_hash(), "bb".repeat(32));
+        assert_eq!(proof.recipient(), "51");
+
+        let path = JsMerklePath::try_new(&["11".repeat(32), "22".repeat(32)], &[1, 0]).unwrap();
+        let witness = build_withdrawal_witness(&proof, &path, 1, &"cc".repeat(32), &"dd".repeat(32)).unwrap();
+        let decoded = WithdrawalWitness::from_bytes(&witness.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.proof.to_bytes(), proof.to_bytes());
+        assert_eq!(decoded.path.indices, vec![true, false]);
+        assert_eq!(decoded.leaf_index, 1);
+        assert_eq!(decoded.commitment, Commitment::new([0xcc; 32]));
+
+        // The leaf index must match the path directions
+        assert!(build_withdrawal_witness(&proof, &path, 0, &"cc".repeat(32), &"dd".repeat(32)).is_err());
+        assert!(build_withdrawal_witness(&proof, &path, 5, &"cc".repeat(32), &"dd".repeat(32)).is_err());
+        assert!(build_withdrawal_witness(&proof, &path, 1, "cc", &"dd".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_proof_recipients() {
+        let outputs = r#"[{"value":546,"script_pubkey":"51"},{"value":600,"script_pubkey":"52"}]"#;
+        let proof = JsWithdrawalProof::try_new(vec![7u8; 4], &"aa".repeat(32), &"bb".repeat(32), "51").unwrap();
+        assert_eq!(proof.recipients_hash(), "00".repeat(32));
+
+        let bound = proof.with_recipients(outputs).unwrap();
+        assert_eq!(bound.recipients_hash(), recipients_hash(outputs).unwrap());
+        assert_ne!(bound.recipients_hash(), recipients_hash(r#"[{"value":546,"script_pubkey":"51"}]"#).unwrap());
+        let decoded = JsWithdrawalProof::from_bytes(&bound.to_bytes()).unwrap();
+        assert_eq!(decoded.recipients_hash(), bound.recipients_hash());
+        // Binding the recipient commits to the first of those outputs
+        assert_eq!(proof.bind_recipient(546).recipients_hash(), recipients_hash(r#"[{"value":546,"script_pubkey":"51"}]"#).unwrap());
+        assert!(JsWithdrawalProof::try_new(vec![7u8; 4], &"aa".repeat(32), &"bb".repeat(32), "zz").is_err());
+
+        assert!(parse_outputs(r#"[{"value":546,"script_pubkey":"zz"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        let proof = JsWithdrawalProof::try_new(vec![7u8; 4], &"11".repeat(32), &"22".repeat(32), "51").unwrap();
+        let relayed = proof.with_relayer(&"0c".repeat(32), 300).unwrap();
+        let inputs = relayed.public_inputs(&"dd".repeat(32)).unwrap();
+        assert_eq!(inputs.len(), zkane_common::PUBLIC_INPUT_COUNT);
+        assert_eq!(inputs[0], format!("0x{}", "11".repeat(32)));
+        assert_eq!(inputs[1], format!("0x{}", "22".repeat(32)));
+        assert_eq!(inputs[4], format!("0x{}", "0c".repeat(32)));
+        assert_eq!(inputs[5], format!("0x{}{}", "00".repeat(30), "012c"));
+        // Hashes above the field order are reduced, as in the circuit
+        assert_ne!(inputs[2], format!("0x{}", "dd".repeat(32)));
+    }
+}
@@ -0,0 +1,364 @@
+//! # Proof Generation
+//!
+//! Generates withdrawal proofs and deposit receipts in the browser. A [`JsProverHandle`] reports
+//! each proving stage to a JavaScript callback and lets the dapp cancel the
+//! proof, so the page can show progress instead of appearing frozen.
+//!
+//! Proving blocks the calling thread, so dapps should run it in a web worker.
+//! Cancellation takes effect at the next stage boundary; call `cancel()`
+//! before proving or from the progress callback.
+
+use crate::js_error;
+use crate::note_json::deposit_note_from_json;
+use crate::proof::decode_hash;
+use send_wrapper::SendWrapper;
+use wasm_bindgen::prelude::*;
+use zkane_common::{DepositNote, MerklePath, ZKaneError, ZKaneResult};
+use zkane_crypto::zkp::receipt::DepositReceipt;
+use zkane_crypto::zkp::{
+    proof_to_bytes, prove_with_handle, prove_witness, proving_key_from_bytes, synthesize_witness,
+    verifying_key_from_bytes, ProverHandle, SynthesizedWitness, WithdrawalCircuit, WitnessKey,
+};
+
+/// Observes and cancels proof generation.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct JsProverHandle {
+    inner: ProverHandle,
+}
+
+#[wasm_bindgen]
+impl JsProverHandle {
+    /// Create a handle.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsProverHandle {
+        Self::default()
+    }
+
+    /// Register a callback called with the stage name (`"synthesizing"`,
+    /// `"proving"` or `"done"`) and the approximate fraction of work done.
+    #[wasm_bindgen(js_name = onProgress)]
+    pub fn on_progress(&self, callback: js_sys::Function) {
+        // JS functions can't leave the thread they were created on, which is
+        // the only thread a wasm instance runs on
+        let callback = SendWrapper::new(callback);
+        self.inner.on_progress(move |stage| {
+            let _ = callback.call2(
+                &JsValue::NULL,
+                &JsValue::from_str(stage.name()),
+                &JsValue::from_f64(stage.progress()),
+            );
+        });
+    }
+
+    /// Cancel the proof at the next stage boundary.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    /// Whether the proof has been cancelled.
+    #[wasm_bindgen(getter, js_name = isCancelled)]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+impl JsProverHandle {
+    /// Get the wrapped handle.
+    pub fn inner(&self) -> &ProverHandle {
+        &self.inner
+    }
+}
+
+/// Generate a withdrawal proof for a note.
+///
+/// Takes the compressed proving key and the deposit note as JSON, and returns
+/// the compressed proof. The recipients hash is that of the outputs the
+/// withdrawal pays (see `recipientsHash`), all zeros to leave the proof
+/// unbound to a recipient set. The proof can't be spent after block
+/// `not_after_height`, 0 to never expire. Throws `"Proof generation
+/// cancelled"` if the handle is cancelled.
+#[wasm_bindgen(js_name = generateWithdrawalProof)]
+pub fn generate_withdrawal_proof(
+    proving_key: &[u8],
+    note_json: &str,
+    recipients_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: u128,
+    not_after_height: u64,
+    handle: &JsProverHandle,
+) -> Result<Vec<u8>, JsValue> {
+    let note = deposit_note_from_json(note_json).map_err(js_error)?;
+    build_withdrawal_proof(
+        proving_key,
+        &note,
+        recipients_hash_hex,
+        relayer_output_hash_hex,
+        fee,
+        not_after_height,
+        handle,
+    )
+    .map_err(js_error)
+}
+
+/// Generate a compressed withdrawal proof for a note.
+///
+/// The proof is bound to the note's asset ID and denomination.
+pub fn build_withdrawal_proof(
+    proving_key: &[u8],
+    note: &DepositNote,
+    recipients_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: u128,
+    not_after_height: u64,
+    handle: &JsProverHandle,
+) -> ZKaneResult<Vec<u8>> {
+    let circuit = withdrawal_circuit(note, recipients_hash_hex, relayer_output_hash_hex, fee, not_after_height)?;
+    let pk = proving_key_from_bytes(proving_key).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+
+    let proof = prove_with_handle(&pk, circuit, &handle.inner)?;
+    proof_to_bytes(&proof).map_err(|e| ZKaneError::CryptoError(e.to_string()))
+}
+
+/// Get the key a withdrawal's witness is cached under, as hex.
+///
+/// The key is a hash of the withdrawal's public inputs, so it identifies the
+/// withdrawal without revealing the note. Takes the same arguments as
+/// `generateWithdrawalProof`.
+#[wasm_bindgen(js_name = withdrawalWitnessKey)]
+pub fn withdrawal_witness_key(
+    note_json: &str,
+    recipients_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: u128,
+    not_after_height: u64,
+) -> Result<String, JsValue> {
+    let note = deposit_note_from_json(note_json).map_err(js_error)?;
+    let circuit = withdrawal_circuit(&note, recipients_hash_hex, relayer_output_hash_hex, fee, not_after_height)
+        .map_err(js_error)?;
+    WitnessKey::for_circuit(&circuit).map(|key| key.to_hex()).map_err(js_error)
+}
+
+/// Synthesize the witness of a withdrawal proof, the first stage of
+/// `generateWithdrawalProof`.
+///
+/// Returns the encoded witness, which `proveWithdrawalWitness` turns into
+/// the proof. A dapp can cache it under `withdrawalWitnessKey`, so a proof
+/// that fails partway is retried without synthesizing the witness again. The
+/// witness holds the note's secrets: store it as carefully as the note, and
+/// delete it once the proof is done.
+#[wasm_bindgen(js_name = synthesizeWithdrawalWitness)]
+pub fn synthesize_withdrawal_witness(
+    note_json: &str,
+    recipients_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: u128,
+    not_after_height: u64,
+    handle: &JsProverHandle,
+) -> Result<Vec<u8>, JsValue> {
+    let note = deposit_note_from_json(note_json).map_err(js_error)?;
+    let circuit = withdrawal_circuit(&note, recipients_hash_hex, relayer_output_hash_hex, fee, not_after_height)
+        .map_err(js_error)?;
+    synthesize_witness(circuit, &handle.inner)
+        .and_then(|witness| witness.to_bytes())
+        .map_err(js_error)
+}
+
+/// Generate a withdrawal proof from a witness encoded by
+/// `synthesizeWithdrawalWitness`.
+///
+/// Returns the compressed proof, the same one `generateWithdrawalProof`
+/// returns for the same withdrawal.
+#[wasm_bindgen(js_name = proveWithdrawalWitness)]
+pub fn prove_withdrawal_witness(proving_key: &[u8], witness: &[u8], handle: &JsProverHandle) -> Result<Vec<u8>, JsValue> {
+    let witness = SynthesizedWitness::from_bytes(witness).map_err(js_error)?;
+    let pk = proving_key_from_bytes(proving_key).map_err(|e| js_error(ZKaneError::InvalidProof(e.to_string())))?;
+    let proof = prove_witness(&pk, &witness, &handle.inner).map_err(js_error)?;
+    proof_to_bytes(&proof).map_err(|e| js_error(ZKaneError::CryptoError(e.to_string())))
+}
+
+/// Build the withdrawal circuit for a note, bound to the note's asset ID and
+/// denomination.
+fn withdrawal_circuit(
+    note: &DepositNote,
+    recipients_hash_hex: &str,
+    relayer_output_hash_hex: &str,
+    fee: u128,
+    not_after_height: u64,
+) -> ZKaneResult<WithdrawalCircuit> {
+    Ok(WithdrawalCircuit::from_note(
+        note.secret.as_bytes(),
+        note.nullifier.as_bytes(),
+        &note.asset_id,
+        note.denomination,
+        &decode_hash(recipients_hash_hex, "recipients hash")?,
+        &decode_hash(relayer_output_hash_hex, "relayer output hash")?,
+        fee,
+        not_after_height,
+    )?)
+}
+
+/// Generate a deposit receipt for a note.
+///
+/// Takes the compressed proving key of the receipt circuit, the deposit note
+/// and the note's path in the receipt tree as JSON, and returns the receipt
+/// as JSON.
+#[wasm_bindgen(js_name = generateDepositReceipt)]
+#[allow(clippy::too_many_arguments)]
+pub fn generate_deposit_receipt(
+    proving_key: &[u8],
+    note_json: &str,
+    path_json: &str,
+    root_hex: &str,
+    from_block: u64,
+    to_block: u64,
+    challenge_hex: &str,
+    handle: &JsProverHandle,
+) -> Result<String, JsValue> {
+    let note = deposit_note_from_json(note_json).map_err(js_error)?;
+    let path: MerklePath = serde_json::from_str(path_json).map_err(js_error)?;
+    let receipt = build_deposit_receipt(
+        proving_key,
+        &note,
+        &path,
+        root_hex,
+        (from_block, to_block),
+        challenge_hex,
+        handle,
+    )
+    .map_err(js_error)?;
+    serde_json::to_string(&receipt).map_err(js_error)
+}
+
+/// Verify a deposit receipt with the compressed verifying key of the receipt
+/// circuit.
+///
+/// The caller must also check the receipt's root and challenge; see
+/// [`DepositReceipt::verify`].
+#[wasm_bindgen(js_name = verifyDepositReceipt)]
+pub fn verify_deposit_receipt(verifying_key: &[u8], receipt_json: &str) -> Result<bool, JsValue> {
+    let receipt: DepositReceipt = serde_json::from_str(receipt_json).map_err(js_error)?;
+    let vk = verifying_key_from_bytes(verifying_key)
+        .map_err(|e| js_error(ZKaneError::InvalidVerifierKey(e.to_string())))?;
+    receipt.verify(&vk).map_err(js_error)
+}
+
+/// Generate a deposit receipt for a note in the receipt tree with root
+/// `root_hex`, covering the inclusive block range `blocks`.
+pub fn build_deposit_receipt(
+    proving_key: &[u8],
+    note: &DepositNote,
+    path: &MerklePath,
+    root_hex: &str,
+    blocks: (u64, u64),
+    challenge_hex: &str,
+    handle: &JsProverHandle,
+) -> ZKaneResult<DepositReceipt> {
+    let pk = proving_key_from_bytes(proving_key).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+    DepositReceipt::generate(
+        &pk,
+        (note.secret.as_bytes(), note.nullifier.as_bytes()),
+        (&note.asset_id, note.denomination),
+        path,
+        decode_hash(root_hex, "root")?,
+        blocks,
+        decode_hash(challenge_hex, "challenge")?,
+        &handle.inner,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_common::{Commitment, Nullifier, Secret, ZkAssetId};
+    use zkane_crypto::zkp::receipt::{circuit_commitment, setup_receipt};
+    use zkane_crypto::zkp::{proving_key_to_bytes, setup, verifying_key_to_bytes};
+    use zkane_crypto::{MerkleTree, PoseidonBls12Hash};
+
+    #[test]
+    fn test_build_withdrawal_proof() {
+        let pk = proving_key_to_bytes(&setup().0).unwrap();
+        let note = DepositNote::new(
+            Secret::new([1u8; 32]),
+            Nullifier::new([2u8; 32]),
+            Commitment::new([0u8; 32]),
+            ZkAssetId { block: 2, tx: 1 },
+            1000,
+            0,
+        );
+        let relayer = "00".repeat(32);
+        let recipients = "11".repeat(32);
+
+        let handle = JsProverHandle::new();
+        let proof = build_withdrawal_proof(&pk, &note, &recipients, &relayer, 0, 0, &handle).unwrap();
+        assert!(!proof.is_empty());
+
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        assert!(matches!(
+            build_withdrawal_proof(&pk, &note, &recipients, &relayer, 0, 0, &handle),
+            Err(ZKaneError::ProofCancelled)
+        ));
+        assert!(build_withdrawal_proof(&pk[1..], &note, &recipients, &relayer, 0, 0, &JsProverHandle::new()).is_err());
+    }
+
+    #[test]
+    fn test_prove_withdrawal_witness() {
+        let pk = proving_key_to_bytes(&setup().0).unwrap();
+        let note = DepositNote::new(
+            Secret::new([1u8; 32]),
+            Nullifier::new([2u8; 32]),
+            Commitment::new([0u8; 32]),
+            ZkAssetId { block: 2, tx: 1 },
+            1000,
+            0,
+        );
+        let note_json = note.to_json();
+        let relayer = "00".repeat(32);
+        let recipients = "11".repeat(32);
+
+        let key = withdrawal_witness_key(&note_json, &recipients, &relayer, 0, 0).unwrap();
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, withdrawal_witness_key(&note_json, &recipients, &relayer, 1, 0).unwrap());
+
+        let handle = JsProverHandle::new();
+        let witness = synthesize_withdrawal_witness(&note_json, &recipients, &relayer, 0, 0, &handle).unwrap();
+        let proof = prove_withdrawal_witness(&pk, &witness, &handle).unwrap();
+        assert_eq!(proof, build_withdrawal_proof(&pk, &note, &recipients, &relayer, 0, 0, &handle).unwrap());
+    }
+
+    #[test]
+    fn test_deposit_receipt() {
+        let (pk, vk) = setup_receipt(2);
+        let (pk, vk) = (proving_key_to_bytes(&pk).unwrap(), verifying_key_to_bytes(&vk).unwrap());
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let commitment = circuit_commitment(&[2u8; 32], &[1u8; 32], &asset_id, 1000).unwrap();
+        let note = DepositNote::new(
+            Secret::new([1u8; 32]),
+            Nullifier::new([2u8; 32]),
+            Commitment::new(commitment),
+            asset_id,
+            1000,
+            0,
+        );
+        let tree = MerkleTree::from_leaves_with_hasher(2, &[note.commitment], PoseidonBls12Hash).unwrap();
+        let path = tree.generate_path(0).unwrap();
+
+        let receipt = build_deposit_receipt(
+            &pk,
+            &note,
+            &path,
+            &hex::encode(tree.root()),
+            (10, 20),
+            &"07".repeat(32),
+            &JsProverHandle::new(),
+        )
+        .unwrap();
+        assert_eq!((receipt.from_block, receipt.to_block), (10, 20));
+
+        let json = serde_json::to_string(&receipt).unwrap();
+        assert!(verify_deposit_receipt(&vk, &json).unwrap());
+        let json = serde_json::to_string(&DepositReceipt { challenge: [8u8; 32], ..receipt }).unwrap();
+        assert!(!verify_deposit_receipt(&vk, &json).unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+//! # Proof Verification
+//!
+//! Checks withdrawal proofs against the withdrawal circuit's verifying key,
+//! so the frontend and relayer clients can reject a bad proof locally rather
+//! than paying to broadcast it or handing it to a relayer. Built with the
+//! `verifier` feature.
+//!
+//! A [`JsWithdrawalVerifier`] parses the compressed verifying key artifact
+//! once, then checks any number of proofs. The nullifier hash a proof reveals
+//! is the circuit's, see `circuitNullifierHash`.
+
+use crate::js_error;
+use crate::proof::decode_hash;
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_ff::PrimeField;
+use ark_groth16::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use zkane_common::{ZKaneError, ZKaneResult, ZkAssetId};
+use zkane_crypto::zkp::split::circuit_nullifier_hash;
+use zkane_crypto::zkp::{proof_from_bytes, verify, verifying_key_from_bytes};
+
+/// The public inputs of a withdrawal proof, as passed from JavaScript.
+///
+/// Hashes are hex encoded. The asset ID and denomination are those of the
+/// pool the withdrawal is made from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalPublicInputs {
+    /// Hash of the nullifier the withdrawal spends
+    pub nullifier_hash: String,
+    /// Hash of the outputs the withdrawal pays, see `recipientsHash`
+    pub recipients_hash: String,
+    /// Hash of the relayer's fee output, all zeros without a relayer
+    pub relayer_output_hash: String,
+    /// Relayer fee
+    #[serde(default)]
+    pub fee: u128,
+    /// Last block height the proof may be spent at, 0 if it never expires
+    #[serde(default)]
+    pub not_after_height: u64,
+    pub asset_id: ZkAssetId,
+    pub denomination: u128,
+}
+
+/// Verifies withdrawal proofs with the withdrawal circuit's verifying key.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct JsWithdrawalVerifier {
+    vk: VerifyingKey<Bls12_381>,
+}
+
+#[wasm_bindgen]
+impl JsWithdrawalVerifier {
+    /// Create a verifier from the compressed verifying key.
+    #[wasm_bindgen(constructor)]
+    pub fn new(verifying_key: &[u8]) -> Result<JsWithdrawalVerifier, JsValue> {
+        Self::from_bytes(verifying_key).map_err(js_error)
+    }
+
+    /// Check a hex withdrawal proof against its public inputs, given as JSON.
+    ///
+    /// Returns `false` for a well-formed proof that doesn't verify, and
+    /// throws if the proof or the inputs can't be parsed.
+    #[wasm_bindgen(js_name = verifyWithdrawalProof)]
+    pub fn verify_withdrawal_proof(&self, proof_hex: &str, public_inputs_json: &str) -> Result<bool, JsValue> {
+        let inputs: WithdrawalPublicInputs = serde_json::from_str(public_inputs_json).map_err(js_error)?;
+        self.verify(proof_hex, &inputs).map_err(js_error)
+    }
+}
+
+impl JsWithdrawalVerifier {
+    /// Create a verifier from the compressed verifying key.
+    pub fn from_bytes(verifying_key: &[u8]) -> ZKaneResult<Self> {
+        let vk = verifying_key_from_bytes(verifying_key).map_err(|e| ZKaneError::InvalidVerifierKey(e.to_string()))?;
+        Ok(Self { vk })
+    }
+
+    /// Check a hex withdrawal proof against its public inputs.
+    pub fn verify(&self, proof_hex: &str, inputs: &WithdrawalPublicInputs) -> ZKaneResult<bool> {
+        let proof_bytes = hex::decode(proof_hex.trim_start_matches("0x"))
+            .map_err(|e| ZKaneError::InvalidProof(format!("invalid proof hex: {}", e)))?;
+        let proof = proof_from_bytes(&proof_bytes).map_err(|e| ZKaneError::InvalidProof(e.to_string()))?;
+        let field = |value_hex: &str, name: &str| -> ZKaneResult<Fr> {
+            Ok(Fr::from_be_bytes_mod_order(&decode_hash(value_hex, name)?))
+        };
+
+        Ok(verify(
+            &self.vk,
+            &proof,
+            field(&inputs.nullifier_hash, "nullifier hash")?,
+            field(&inputs.recipients_hash, "recipients hash")?,
+            field(&inputs.relayer_output_hash, "relayer output hash")?,
+            Fr::from(inputs.fee),
+            Fr::from(inputs.not_after_height),
+            &inputs.asset_id,
+            inputs.denomination,
+        ))
+    }
+}
+
+/// Get the hex nullifier hash a withdrawal proof reveals for a note's
+/// nullifier.
+#[wasm_bindgen(js_name = circuitNullifierHash)]
+pub fn circuit_nullifier_hash_hex(nullifier_hex: &str) -> Result<String, JsValue> {
+    let nullifier = decode_hash(nullifier_hex.trim_start_matches("0x"), "nullifier").map_err(js_error)?;
+    circuit_nullifier_hash(&nullifier).map(hex::encode).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkane_crypto::zkp::{proof_to_bytes, prove, setup, verifying_key_to_bytes, WithdrawalCircuit};
+
+    #[test]
+    fn test_verify_withdrawal_proof() {
+        let (pk, vk) = setup();
+        let asset_id = ZkAssetId { block: 2, tx: 1 };
+        let (recipients, relayer) = ([0x11u8; 32], [0x22u8; 32]);
+        let circuit = WithdrawalCircuit::from_note(&[1u8; 32], &[2u8; 32], &asset_id, 1000, &recipients, &relayer, 5, 0)
+            .unwrap();
+        let proof = hex::encode(proof_to_bytes(&prove(&pk, circuit)).unwrap());
+
+        let inputs = WithdrawalPublicInputs {
+            nullifier_hash: circuit_nullifier_hash_hex(&"02".repeat(32)).unwrap(),
+            recipients_hash: hex::encode(recipients),
+            relayer_output_hash: hex::encode(relayer),
+            fee: 5,
+            not_after_height: 0,
+            asset_id,
+            denomination: 1000,
+        };
+        let verifier = JsWithdrawalVerifier::from_bytes(&verifying_key_to_bytes(&vk).unwrap()).unwrap();
+        assert!(verifier.verify(&proof, &inputs).unwrap());
+        assert!(verifier.verify(&format!("0x{}", proof), &inputs).unwrap());
+
+        assert!(!verifier.verify(&proof, &WithdrawalPublicInputs { fee: 6, ..inputs.clone() }).unwrap());
+        assert!(!verifier.verify(&proof, &WithdrawalPublicInputs { not_after_height: 1, ..inputs.clone() }).unwrap());
+        assert!(!verifier
+            .verify(&proof, &WithdrawalPublicInputs { recipients_hash: "33".repeat(32), ..inputs.clone() })
+            .unwrap());
+        assert!(!verifier.verify(&proof, &WithdrawalPublicInputs { denomination: 100, ..inputs.clone() }).unwrap());
+
+        assert!(matches!(verifier.verify("zz", &inputs), Err(ZKaneError::InvalidProof(_))));
+        assert!(matches!(verifier.verify(&proof[2..], &inputs), Err(ZKaneError::InvalidProof(_))));
+        assert!(matches!(
+            verifier.verify(&proof, &WithdrawalPublicInputs { nullifier_hash: "00".into(), ..inputs }),
+            Err(ZKaneError::InvalidProof(_))
+        ));
+        assert!(matches!(JsWithdrawalVerifier::from_bytes(&[0u8; 8]), Err(ZKaneError::InvalidVerifierKey(_))));
+    }
+
+    #[test]
+    fn test_public_inputs_json() {
+        let json = format!(
+            r#"{{"nullifier_hash":"{0}","recipients_hash":"{0}","relayer_output_hash":"{0}","asset_id":{{"block":2,"tx":1}},"denomination":1000}}"#,
+            "00".repeat(32)
+        );
+        let inputs: WithdrawalPublicInputs = serde_json::from_str(&json).unwrap();
+        assert_eq!(inputs.fee, 0);
+        assert_eq!(inputs.asset_id, ZkAssetId { block: 2, tx: 1 });
+    }
+}
@@ -0,0 +1,66 @@
+//! # Deposit Flow
+//!
+//! Creates a deposit note, lays out its deposit transaction the two ways a
+//! wallet can, and syncs the confirmed deposit into a [`PrivacyPool`].
+//!
+//! ```text
+//! cargo run -p zkane-examples --bin deposit_flow
+//! ```
+
+mod mock_chain;
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::FeeRate;
+use mock_chain::{address, config, funding_utxo, ASSET, DENOMINATION};
+use std::sync::Arc;
+use zkane_common::DepositNote;
+use zkane_core::mock_provider::MockProvider;
+use zkane_core::{build_deposit_request, generate_deposit_note, verify_deposit_note, DepositBuilder, PrivacyPool};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+    let fee_rate = FeeRate::from_sat_per_vb(2).expect("a small fee rate");
+
+    // The note is the only way to withdraw the deposit, so it is saved
+    // before anything is broadcast
+    let note = generate_deposit_note(ASSET, DENOMINATION)?;
+    assert!(verify_deposit_note(&note)?);
+    println!("note: {}", note.to_json());
+
+    // A wallet that funds and signs deposits itself only needs the layout
+    let utxos = vec![funding_utxo("depositor", 20_000)];
+    let plan = build_deposit_request(&note, &utxos, fee_rate)?;
+    println!(
+        "plan: pool {}, {} vbytes, fee {}, change {}",
+        plan.pool_id.to_bech32(),
+        plan.vsize,
+        plan.fee,
+        plan.change
+    );
+    let outputs = plan.outputs(address("depositor").script_pubkey());
+    assert_eq!(outputs.len(), 2);
+
+    // A deezel provider can assemble the PSBT for its signer instead
+    let deposit = DepositBuilder::new(provider.clone(), plan.pool_id, note.commitment)
+        .utxos(utxos)
+        .change_address(address("depositor").to_string())
+        .fee_rate(fee_rate)
+        .build()
+        .await?;
+    assert_eq!(deposit.envelope, plan.envelope);
+    assert_eq!(deposit.fee, plan.fee);
+    println!("unsigned tx: {}", serialize_hex(&deposit.psbt.unsigned_tx));
+
+    // Once confirmed, indexers insert the commitment into the pool's tree
+    let mut pool = PrivacyPool::new(config(vec![]), provider.clone())?;
+    let note = mock_chain::deposit(&provider, &mut pool, &note).await?;
+    println!("root: {}, {} deposits", hex::encode(pool.merkle_root()), pool.commitment_count());
+
+    // The leaf index is part of the note from now on
+    let saved = DepositNote::from_json(&note.to_json())?;
+    assert_eq!(saved.leaf_index, note.leaf_index);
+    assert_eq!(pool.leaf_index_of(&saved.commitment), Some(saved.leaf_index.into()));
+
+    Ok(())
+}
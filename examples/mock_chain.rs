@@ -0,0 +1,74 @@
+//! Pool setup shared by the example flows.
+//!
+//! The flows run against a [`MockProvider`] chain, so they need no node,
+//! indexer or wallet.
+
+// Each flow uses part of the setup
+#![allow(dead_code)]
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, TxOut, Txid};
+use zkane_common::{DepositNote, ZKaneConfig, ZkAssetId};
+use zkane_core::mock_provider::MockProvider;
+use zkane_core::{FundingUtxo, PrivacyPool};
+
+/// The alkane held by the example pool
+pub const ASSET: ZkAssetId = ZkAssetId { block: 2, tx: 1 };
+
+/// Denomination of the example pool
+pub const DENOMINATION: u128 = 100_000;
+
+/// Height of the example pool's commitment tree
+pub const TREE_HEIGHT: u32 = 20;
+
+/// Configuration of the example pool, verifying proofs with `verifier_key`
+/// if it isn't empty.
+pub fn config(verifier_key: Vec<u8>) -> ZKaneConfig {
+    let config = ZKaneConfig::new(ASSET, DENOMINATION, TREE_HEIGHT, vec![]);
+    if verifier_key.is_empty() {
+        config
+    } else {
+        config.with_verifier_key(1, verifier_key)
+    }
+}
+
+/// A regtest address standing in for a wallet's, distinct per `label`.
+pub fn address(label: &str) -> Address {
+    Address::p2wsh(&ScriptBuf::from_bytes(label.as_bytes().to_vec()), Network::Regtest)
+}
+
+/// A UTXO of the wallet worth `sats`, the envelope commit output when spent
+/// first.
+pub fn funding_utxo(label: &str, sats: u64) -> FundingUtxo {
+    FundingUtxo {
+        outpoint: OutPoint::new(Txid::hash(label.as_bytes()), 0),
+        txout: TxOut { value: Amount::from_sat(sats), script_pubkey: address(label).script_pubkey() },
+    }
+}
+
+/// Confirm a deposit of `note` in a new block and sync it into `pool`.
+///
+/// The mock chain doesn't reveal witness envelopes, so the commitment is
+/// carried in an OP_RETURN, which the pool's extractor falls back to.
+///
+/// # Returns
+///
+/// The note, with the leaf index the commitment was inserted at.
+pub async fn deposit(
+    provider: &MockProvider,
+    pool: &mut PrivacyPool<MockProvider>,
+    note: &DepositNote,
+) -> anyhow::Result<DepositNote> {
+    let txid = note.commitment.to_hex();
+    let tx = serde_json::json!({
+        "vout": [{ "scriptpubkey": format!("6a20{}", txid), "value": 0 }]
+    });
+    let height = provider.mine_block(vec![(txid.as_str(), tx)]);
+
+    let leaf_index = pool.add_commitment(&txid).await?;
+    println!("deposit {} confirmed at height {}, leaf {}", txid, height, leaf_index);
+
+    let mut note = note.clone();
+    note.leaf_index = leaf_index.try_into()?;
+    Ok(note)
+}
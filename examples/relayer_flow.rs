@@ -0,0 +1,117 @@
+//! # Relayer Flow
+//!
+//! Runs a relayer over a synced pool and has a user with no bitcoin of
+//! their own withdraw through it: the user reads the relayer's status, proves
+//! a withdrawal paying the relayer's fee output, and submits its witness to
+//! the job queue. The relayer broadcasts it and confirms the job once the
+//! pool has spent the nullifier.
+//!
+//! ```text
+//! cargo run -p zkane-examples --release --bin relayer_flow
+//! ```
+
+mod mock_chain;
+
+use mock_chain::{address, config, ASSET, DENOMINATION};
+use std::sync::Arc;
+use zkane_abi::pool;
+use zkane_common::{calculate_outputs_hash, derive_pool_id, NullifierHash, Recipient, WithdrawalProof, WithdrawalWitness};
+use zkane_core::mock_provider::MockProvider;
+use zkane_core::{generate_deposit_note, PrivacyPool};
+use zkane_crypto::zkp::split::circuit_nullifier_hash;
+use zkane_crypto::zkp::{prove, proof_to_bytes, setup, verifying_key_to_bytes, WithdrawalCircuit};
+use zkane_relayer::{JobQueue, JobStatus, OutputDescriptor, RelayRequest, Relayer, RelayerConfig};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let (proving_key, verifying_key) = setup();
+    let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+    let mut pool = PrivacyPool::new(config(verifying_key_to_bytes(&verifying_key)?), provider.clone())?;
+
+    let note = generate_deposit_note(ASSET, DENOMINATION)?;
+    let note = mock_chain::deposit(&provider, &mut pool, &note).await?;
+    let path = pool.generate_merkle_proof(note.leaf_index.into())?;
+
+    // The relayer takes over the synced pool and funds withdrawals from the
+    // provider's wallet
+    let pool_id = derive_pool_id(&ASSET, DENOMINATION);
+    let queue = Arc::new(JobQueue::new());
+    let relayer_config = RelayerConfig {
+        pool_id,
+        min_fee: 500,
+        fee_output: OutputDescriptor::new(546, address("relayer").script_pubkey().to_hex_string()),
+        max_batch: 8,
+        cache_size: 64,
+        confirmation_target: 1,
+    };
+    let mut relayer = Relayer::new(pool, provider.clone(), relayer_config, queue.clone());
+
+    // The user learns the relayer's terms and the root to prove against
+    let status = relayer.status_handle().lock().unwrap().clone();
+    println!("relayer: minimum fee {}, root {}", status.min_fee, status.merkle_root);
+    let mut merkle_root = [0u8; 32];
+    hex::decode_to_slice(&status.merkle_root, &mut merkle_root)?;
+
+    // The proof commits to the relayer's fee output and fee, so the relayer
+    // can't raise its fee or redirect it after the fact
+    let fee = status.min_fee;
+    let recipient = OutputDescriptor::new(1_000, address("recipient").script_pubkey().to_hex_string());
+    let recipients = [recipient.script().map(|script_pubkey| bitcoin::TxOut {
+        value: bitcoin::Amount::from_sat(recipient.value),
+        script_pubkey,
+    })?];
+    let circuit = WithdrawalCircuit::from_note(
+        note.secret.as_bytes(),
+        note.nullifier.as_bytes(),
+        &note.asset_id,
+        note.denomination,
+        &calculate_outputs_hash(&recipients),
+        &status.fee_output.hash(),
+        fee,
+        0,
+    )?;
+    let proof = WithdrawalProof::new(
+        proof_to_bytes(&prove(&proving_key, circuit))?,
+        merkle_root,
+        NullifierHash::new(circuit_nullifier_hash(note.nullifier.as_bytes())?),
+        Recipient::default(),
+    )
+    .with_recipients(&recipients)
+    .with_relayer(status.fee_output.hash(), fee);
+
+    // The witness carries the Merkle path the pool checks. Requests are
+    // checked against the terms when queued, and against the pool when
+    // relayed
+    let witness = WithdrawalWitness {
+        proof,
+        path,
+        leaf_index: note.leaf_index,
+        commitment: note.commitment,
+        outputs_hash: [0u8; 32],
+    };
+    let request = RelayRequest { witness, outputs: vec![recipient, status.fee_output.clone()] };
+    relayer.config().check_terms(&request)?;
+    let id = queue.push(request.clone())?;
+    println!("queued job {}", id);
+
+    // The relayer funds the envelope commit, then broadcasts the withdrawal
+    // revealing it
+    assert_eq!(relayer.process_batch().await, vec![id]);
+    let status = queue.status(id)?;
+    println!("job {}: {:?}", id, status);
+    assert!(matches!(status, JobStatus::Broadcast { .. }));
+    assert_eq!(provider.broadcasts().len(), 2);
+
+    // The withdrawal is mined and the pool contract spends the nullifier
+    let (low, high) = request.witness.proof.nullifier_hash.to_u128_pair();
+    let params = format!("{},1,{},{}", pool::ARE_NULLIFIERS_SPENT, low, high);
+    provider.add_simulation_data(&pool_id.to_string(), &params, &[1]);
+    provider.mine_empty_blocks(1);
+    assert_eq!(relayer.poll_confirmations().await, vec![id]);
+    println!("job {}: {:?}", id, queue.status(id)?);
+
+    // The nullifier is spent, so the same request is refused
+    assert!(relayer.validate(&request).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! # Withdraw Flow
+//!
+//! Deposits a note, proves its withdrawal to two recipients with the
+//! Groth16 prover, checks the proof against the pool as the contract would,
+//! and assembles the self-funded withdrawal transaction.
+//!
+//! ```text
+//! cargo run -p zkane-examples --release --bin withdraw_flow
+//! ```
+
+mod mock_chain;
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::{Amount, FeeRate, TxOut};
+use mock_chain::{address, config, funding_utxo, ASSET, DENOMINATION};
+use std::sync::Arc;
+use zkane_common::{derive_pool_id, NullifierHash, Recipient, WithdrawalProof};
+use zkane_core::mock_provider::MockProvider;
+use zkane_core::{generate_deposit_note, PrivacyPool, SimulationStep, WithdrawalBuilder};
+use zkane_crypto::zkp::split::circuit_nullifier_hash;
+use zkane_crypto::zkp::{prove, proof_to_bytes, setup, verifying_key_to_bytes, WithdrawalCircuit};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // The pool verifies proofs with the key from the circuit's setup
+    let (proving_key, verifying_key) = setup();
+    let provider = Arc::new(MockProvider::new(bitcoin::Network::Regtest));
+    let mut pool = PrivacyPool::new(config(verifying_key_to_bytes(&verifying_key)?), provider.clone())?;
+
+    let note = generate_deposit_note(ASSET, DENOMINATION)?;
+    let note = mock_chain::deposit(&provider, &mut pool, &note).await?;
+
+    // Pay a merchant and send change back to a fresh address of our own.
+    // The proof commits to both outputs, so neither can be swapped out
+    let builder = WithdrawalBuilder::new(provider.clone(), derive_pool_id(&ASSET, DENOMINATION))
+        .recipient(address("merchant").to_string(), Amount::from_sat(1_000))
+        .recipient(address("fresh").to_string(), Amount::from_sat(600))
+        .self_funded(vec![funding_utxo("withdrawer", 20_000)], Some(address("withdrawer").to_string()))
+        .fee_rate(FeeRate::from_sat_per_vb(2).expect("a small fee rate"));
+    let recipients = [
+        TxOut { value: Amount::from_sat(1_000), script_pubkey: address("merchant").script_pubkey() },
+        TxOut { value: Amount::from_sat(600), script_pubkey: address("fresh").script_pubkey() },
+    ];
+
+    let circuit = WithdrawalCircuit::from_note(
+        note.secret.as_bytes(),
+        note.nullifier.as_bytes(),
+        &note.asset_id,
+        note.denomination,
+        &builder.recipients_hash()?,
+        &builder.relayer_output_hash(),
+        0,
+        0,
+    )?;
+    let proof = WithdrawalProof::new(
+        proof_to_bytes(&prove(&proving_key, circuit))?,
+        pool.merkle_root(),
+        NullifierHash::new(circuit_nullifier_hash(note.nullifier.as_bytes())?),
+        Recipient::default(),
+    )
+    .with_recipients(&recipients);
+
+    // Every check the contract makes, before paying for a transaction
+    let report = pool.simulate_withdrawal_with_outputs(&proof, &recipients);
+    print!("{}", report);
+    assert!(report.would_succeed());
+
+    let path = pool.generate_merkle_proof(note.leaf_index.into())?;
+    let withdrawal = builder.build(proof.clone(), path, note.leaf_index, note.commitment).await?;
+    println!(
+        "withdrawal: {} vbytes, fee {}, {} byte envelope",
+        withdrawal.vsize,
+        withdrawal.fee,
+        withdrawal.envelope.len()
+    );
+    println!("unsigned tx: {}", serialize_hex(&withdrawal.psbt.unsigned_tx));
+
+    // Once the withdrawal confirms, the nullifier can't be spent again
+    pool.process_withdrawal(proof.nullifier_hash.as_bytes())?;
+    let report = pool.simulate_withdrawal(&proof);
+    assert_eq!(report.first_failure().map(|check| check.step), Some(SimulationStep::Nullifier));
+    print!("replayed:\n{}", report);
+
+    Ok(())
+}
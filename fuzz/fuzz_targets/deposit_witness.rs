@@ -0,0 +1,15 @@
+//! Extracts deposit commitments from arbitrary transactions, as the pool's
+//! `Deposit` opcode and the indexers do.
+
+#![no_main]
+
+use bitcoin::consensus::deserialize;
+use bitcoin::Transaction;
+use libfuzzer_sys::fuzz_target;
+use zkane_core::DepositExtractor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(tx) = deserialize::<Transaction>(data) {
+        let _ = DepositExtractor::default().extract(&tx);
+    }
+});
@@ -0,0 +1,23 @@
+//! Parses arbitrary strings with the hex and text parsers fed by the
+//! browser and the CLI, and arbitrary bytes with the opcode response codecs.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkane_common::{Commitment, Nullifier, NullifierHash, PoolRecord, ProtocolFee, Secret, ZkAssetId};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ProtocolFee::from_bytes(data);
+    let _ = PoolRecord::from_bytes(data);
+
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(commitment) = Commitment::from_hex(text) {
+        assert_eq!(Commitment::from_hex(&commitment.to_hex()).unwrap(), commitment);
+    }
+    let _ = NullifierHash::from_hex(text);
+    let _ = Secret::from_hex(text);
+    let _ = Nullifier::from_hex(text);
+    let _ = text.parse::<ZkAssetId>();
+});
@@ -0,0 +1,16 @@
+//! Decodes arbitrary bytes as a compact `MerklePath`.
+//!
+//! Anything that decodes must round-trip and have one index per element.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkane_common::MerklePath;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(path) = MerklePath::from_bytes(data) {
+        assert_eq!(path.elements.len(), path.indices.len());
+        let encoded = path.to_bytes().expect("decoded path must encode");
+        assert_eq!(MerklePath::from_bytes(&encoded).unwrap().to_bytes().unwrap(), encoded);
+    }
+});
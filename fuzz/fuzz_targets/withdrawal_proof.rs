@@ -0,0 +1,16 @@
+//! Decodes arbitrary bytes as a binary `WithdrawalProof`.
+//!
+//! Anything that decodes must encode back to the same bytes.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkane_common::WithdrawalProof;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proof) = WithdrawalProof::from_bytes(data) {
+        let encoded = proof.to_bytes();
+        let decoded = WithdrawalProof::from_bytes(&encoded).expect("encoded proof must decode");
+        assert_eq!(decoded.to_bytes(), encoded);
+    }
+});
@@ -0,0 +1,22 @@
+//! Decodes arbitrary bytes as the witness envelopes read by the pool's
+//! `Withdraw` and `WithdrawSplit` opcodes, in any envelope format.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkane_common::{EnvelopeFormat, SplitWitness, WithdrawalWitness};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(witness) = WithdrawalWitness::from_envelope(data) {
+        let envelope = witness.to_envelope(EnvelopeFormat::Binary).expect("decoded witness must encode");
+        let decoded = WithdrawalWitness::from_envelope(&envelope).expect("encoded witness must decode");
+        assert_eq!(decoded.to_bytes().unwrap(), witness.to_bytes().unwrap());
+    }
+    let _ = WithdrawalWitness::from_bytes(data);
+
+    if let Ok(witness) = SplitWitness::from_envelope(data) {
+        let bytes = witness.to_bytes().expect("decoded split must encode");
+        assert_eq!(SplitWitness::from_bytes(&bytes).unwrap().to_bytes().unwrap(), bytes);
+    }
+    let _ = SplitWitness::from_bytes(data);
+});
@@ -0,0 +1,69 @@
+#!/bin/bash
+
+# ZKane WASM Bundle Build Script
+# Builds one size-optimized bundle of zkane-wasm per feature, so dapps only
+# load the part of the API they use.
+#
+# Usage: ./scripts/build-wasm-bundles.sh [bundle...]
+# Bundles: crypto notes prover verifier pool-client (default: all of them)
+
+set -e
+
+# Colors for output
+RED='\033[0;31m'
+GREEN='\033[0;32m'
+YELLOW='\033[1;33m'
+BLUE='\033[0;34m'
+NC='\033[0m' # No Color
+
+# Configuration
+CRATE="zkane-wasm"
+PROFILE="wasm-bundle"
+TARGET="wasm32-unknown-unknown"
+OUT_DIR="crates/zkane-wasm/pkg"
+BUNDLES=("$@")
+if [ ${#BUNDLES[@]} -eq 0 ]; then
+    BUNDLES=(crypto notes prover verifier pool-client)
+fi
+
+echo -e "${BLUE}📦 ZKane WASM Bundle Build Script${NC}"
+echo -e "${BLUE}=================================${NC}"
+
+# Check prerequisites
+for tool in wasm-bindgen wasm-opt; do
+    if ! command -v "$tool" &> /dev/null; then
+        echo -e "${RED}❌ $tool is not installed${NC}"
+        if [ "$tool" = "wasm-bindgen" ]; then
+            echo -e "${YELLOW}💡 Install with: cargo install wasm-bindgen-cli${NC}"
+        else
+            echo -e "${YELLOW}💡 Install binaryen: https://github.com/WebAssembly/binaryen${NC}"
+        fi
+        exit 1
+    fi
+done
+
+for bundle in "${BUNDLES[@]}"; do
+    case "$bundle" in
+        crypto|notes|prover|verifier|pool-client) ;;
+        *)
+            echo -e "${RED}❌ Unknown bundle: $bundle${NC}"
+            exit 1
+            ;;
+    esac
+
+    echo -e "${YELLOW}🔨 Building $bundle bundle...${NC}"
+    cargo build -p "$CRATE" --target "$TARGET" --profile "$PROFILE" \
+        --no-default-features --features "$bundle"
+
+    rm -rf "$OUT_DIR/$bundle"
+    wasm-bindgen "target/$TARGET/$PROFILE/zkane_wasm.wasm" \
+        --target web --out-dir "$OUT_DIR/$bundle"
+
+    wasm-opt -Oz --enable-mutable-globals \
+        "$OUT_DIR/$bundle/zkane_wasm_bg.wasm" -o "$OUT_DIR/$bundle/zkane_wasm_bg.wasm"
+
+    size=$(wc -c < "$OUT_DIR/$bundle/zkane_wasm_bg.wasm")
+    echo -e "${GREEN}✅ $OUT_DIR/$bundle ($size bytes)${NC}"
+done
+
+echo -e "${GREEN}🎉 Built ${#BUNDLES[@]} bundle(s)${NC}"
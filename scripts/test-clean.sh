@@ -16,11 +16,9 @@ NC='\033[0m'
 echo -e "${BLUE}🧪 ZKane Clean Test Suite (Boiler-Aligned)${NC}"
 echo -e "${BLUE}===========================================${NC}"
 
-# BOILER PATTERN: Multiple environment variables to ensure ZERO bytecode generation
+# Embed stub contract WASM instead of building the contracts (see build.rs)
 export ZKANE_SKIP_BUILD=1
-export CARGO_CFG_TEST=1
 export RUST_TEST_THREADS=1
-export ZKANE_TEST_MODE=1
 export CARGO_TARGET_DIR="target/test-clean"
 
 # Test configuration
@@ -1,4 +1,6 @@
 pub mod zkane_indexer_verification_test;
+pub mod zkane_pool_negative_test;
+pub mod zkane_scenario_test;
 
 pub mod std;
 
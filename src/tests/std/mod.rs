@@ -1,2 +1,5 @@
-pub mod zkane_factory_build;
-pub mod zkane_pool_build;
+//! Contract WASM embedded by the build script, see `build.rs`. Each
+//! `<crate>_build` module has the contract's `WASM`, `VERSION`, `SHA256` and
+//! `get_bytes()`.
+
+include!(concat!(env!("OUT_DIR"), "/contract_builds.rs"));
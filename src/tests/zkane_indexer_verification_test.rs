@@ -29,6 +29,7 @@ use protorune_support::{balance_sheet::ProtoruneRuneId, protostone::{Protostone,
 use protorune::protostone::Protostones;
 use metashrew_core::{println, stdio::stdout};
 use protobuf::Message;
+use zkane_abi::pool;
 
 // Import precompiled builds - ENABLED following boiler pattern
 use crate::tests::std::zkane_factory_build;
@@ -238,7 +239,7 @@ fn perform_privacy_deposit_with_traces(
                                 message: into_cellpack(vec![
                                     zkane_pool_id.block,
                                     zkane_pool_id.tx,
-                                    1u128, // privacy deposit opcode
+                                    pool::DEPOSIT,
                                     u128::from_le_bytes(commitment[0..16].try_into().unwrap()),
                                     u128::from_le_bytes(commitment[16..32].try_into().unwrap()),
                                 ]).encipher(),
@@ -340,7 +341,7 @@ fn perform_privacy_withdrawal_with_traces(
                                 message: into_cellpack(vec![
                                     zkane_pool_id.block,
                                     zkane_pool_id.tx,
-                                    2u128, // privacy withdraw opcode
+                                    pool::WITHDRAW,
                                     u128::from_le_bytes(nullifier[0..16].try_into().unwrap()),
                                     u128::from_le_bytes(nullifier[16..32].try_into().unwrap()),
                                     withdrawal_amount,
@@ -780,23 +781,19 @@ fn test_comprehensive_privacy_multi_user_flow() -> Result<()> {
     }
     
     // Test privacy pool getter functions
-    println!("\n🔍 Testing GetCommitmentCount (opcode 10)");
-    call_privacy_getter(&zkane_pool_id, 10, "GetCommitmentCount", 22)?;
-    
-    println!("\n🔍 Testing GetNullifierCount (opcode 11)");
-    call_privacy_getter(&zkane_pool_id, 11, "GetNullifierCount", 23)?;
-    
-    println!("\n🔍 Testing GetAnonymitySetSize (opcode 12)");
-    call_privacy_getter(&zkane_pool_id, 12, "GetAnonymitySetSize", 24)?;
-    
-    println!("\n🔍 Testing GetCommitmentTreeRoot (opcode 13)");
-    call_privacy_getter(&zkane_pool_id, 13, "GetCommitmentTreeRoot", 25)?;
-    
-    println!("\n🔍 Testing GetMinDeposit (opcode 14)");
-    call_privacy_getter(&zkane_pool_id, 14, "GetMinDeposit", 26)?;
-    
-    println!("\n🔍 Testing GetMaxDeposit (opcode 15)");
-    call_privacy_getter(&zkane_pool_id, 15, "GetMaxDeposit", 27)?;
+    let getters = [
+        pool::GET_ROOT,
+        pool::GET_DEPOSIT_COUNT,
+        pool::GET_DENOMINATION,
+        pool::GET_PROTOCOL_FEE,
+        pool::GET_CIRCUIT_VERSION,
+        pool::GET_CONFIG,
+    ];
+    for (block_height, opcode) in (22..).zip(getters) {
+        let name = pool::method(opcode).unwrap().name;
+        println!("\n🔍 Testing {} (opcode {})", name, opcode);
+        call_privacy_getter(&zkane_pool_id, opcode, name, block_height)?;
+    }
     
     // PHASE 5: Privacy withdrawals with anonymity verification
     println!("\n🔓 PHASE 5: Privacy Withdrawal Operations");
@@ -0,0 +1,183 @@
+// Pool calls that must revert, checked by the code of the error they revert
+// with
+
+use crate::tests::zkane_scenario_test::builds;
+use anyhow::Result;
+use wasm_bindgen_test::wasm_bindgen_test;
+use zkane_common::{pool_config_envelope, EnvelopeFormat, PoolMode, ZKaneError, ZkAssetId, MAX_TREE_HEIGHT, MIN_TREE_HEIGHT};
+use zkane_core::generate_deposit_note;
+use zkane_testkit::{ScenarioBuilder, DEFAULT_ASSET, DEFAULT_DENOMINATION};
+
+/// Get the code of the error a step reverted with.
+fn revert_code(result: Result<()>) -> Option<u16> {
+    ZKaneError::code_in(&result.expect_err("call should revert").to_string())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_deposit_of_wrong_asset() -> Result<()> {
+    let other_asset = ZkAssetId { block: 2, tx: 99 };
+    let note = generate_deposit_note(other_asset, DEFAULT_DENOMINATION)?;
+
+    // The scenario mints its own asset, which the other asset's pool refuses
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION)?
+        .with_pool(other_asset, DEFAULT_DENOMINATION)?;
+    let result = scenario.try_deposit("alice", &note, false);
+    assert_eq!(revert_code(result), Some(ZKaneError::InvalidAmount(String::new()).code()));
+
+    scenario.assert_deposit_count(0)?.assert_balance("alice", DEFAULT_DENOMINATION)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_deposit_of_wrong_amount() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?.mint("alice", DEFAULT_DENOMINATION * 2)?;
+    for amount in [DEFAULT_DENOMINATION - 1, DEFAULT_DENOMINATION + 1] {
+        let error = scenario.try_deposit_amount("alice", &note, amount).unwrap_err().to_string();
+        assert_eq!(ZKaneError::code_in(&error), Some(ZKaneError::InvalidAmount(String::new()).code()));
+        assert!(error.contains(&format!("expected {}, got {}", DEFAULT_DENOMINATION, amount)));
+    }
+
+    // Refunded deposits leave the note free to deposit
+    scenario
+        .assert_balance("alice", DEFAULT_DENOMINATION * 2)?
+        .deposit("alice", &note)?
+        .assert_deposit_count(1)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_deposit_of_duplicate_commitment() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION * 2)?
+        .deposit("alice", &note)?;
+    let result = scenario.try_deposit("alice", &note, false);
+    assert_eq!(revert_code(result), Some(ZKaneError::DuplicateCommitment(String::new()).code()));
+
+    scenario.assert_deposit_count(1)?.assert_balance("alice", DEFAULT_DENOMINATION)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_withdrawal_with_unknown_root() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION)?
+        .deposit("alice", &note)?;
+    let mut witness = scenario.witness(&note)?;
+    witness.proof.merkle_root = [7u8; 32];
+    let result = scenario.try_withdraw("bob", witness);
+    assert_eq!(revert_code(result), Some(ZKaneError::InvalidMerkleRoot.code()));
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_withdrawal_with_reused_nullifier() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+    let other = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION * 2)?
+        .deposit("alice", &note)?
+        .deposit("alice", &other)?;
+    let witness = scenario.witness(&note)?;
+    let mut scenario = scenario
+        .withdraw("bob", witness.clone())?
+        .assert_nullifier_spent(&witness.proof.nullifier_hash)?;
+
+    // The same withdrawal again, and the other note withdrawn with the spent
+    // nullifier hash
+    let mut stolen = scenario.witness(&other)?;
+    stolen.proof.nullifier_hash = witness.proof.nullifier_hash;
+    for replay in [witness, stolen] {
+        let result = scenario.try_withdraw("mallory", replay);
+        assert_eq!(revert_code(result), Some(ZKaneError::NullifierAlreadySpent.code()));
+    }
+    scenario.assert_balance("bob", DEFAULT_DENOMINATION)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_withdrawal_with_mismatched_outputs() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION)?
+        .deposit("alice", &note)?;
+    // A proof bound to other outputs, as a front-runner copying it would send
+    let mut witness = scenario.witness(&note)?;
+    witness.outputs_hash = [9u8; 32];
+    let result = scenario.try_withdraw_envelope("mallory", &witness.to_envelope(EnvelopeFormat::Compressed)?);
+    assert_eq!(revert_code(result), Some(ZKaneError::OutputsMismatch.code()));
+
+    // The note is still spendable by its owner
+    scenario.withdraw("bob", witness)?.assert_balance("bob", DEFAULT_DENOMINATION)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_withdrawal_with_malformed_witness() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION)?
+        .deposit("alice", &note)?;
+    let envelope = scenario.witness(&note)?.to_envelope(EnvelopeFormat::Binary)?;
+    for malformed in [&envelope[..envelope.len() - 1], &[0xff; 64][..]] {
+        let result = scenario.try_withdraw_envelope("bob", malformed);
+        assert_eq!(revert_code(result), Some(ZKaneError::InvalidProof(String::new()).code()));
+    }
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_pool_creation_with_invalid_tree_height() -> Result<()> {
+    let other_asset = ZkAssetId { block: 2, tx: 99 };
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?;
+
+    // The factory reads the height from the creation envelope and refuses
+    // heights outside the supported range instead of spawning the pool
+    for height in [MIN_TREE_HEIGHT - 1, MAX_TREE_HEIGHT + 1] {
+        let result = scenario.try_create_pool_envelope(other_asset, DEFAULT_DENOMINATION, &pool_config_envelope(height));
+        let code = ZKaneError::InvalidTreeHeight { height: height.into(), min: MIN_TREE_HEIGHT, max: MAX_TREE_HEIGHT }.code();
+        assert_eq!(revert_code(result), Some(code));
+    }
+
+    // A supported height creates the pool
+    scenario.try_create_pool_envelope(other_asset, DEFAULT_DENOMINATION, &pool_config_envelope(MIN_TREE_HEIGHT))?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_variable_pool_creation_refused() -> Result<()> {
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?;
+
+    // Deposits into a variable-amount pool could commit to more than they
+    // pay in until their amount proofs are verified, so no pool takes them
+    let result = scenario.try_create_pool_envelope(DEFAULT_ASSET, 0, &[]);
+    assert_eq!(revert_code(result), Some(ZKaneError::WrongPoolMode(PoolMode::Variable).code()));
+    Ok(())
+}
@@ -0,0 +1,100 @@
+// Deposit flows built with the zkane-testkit scenario builders
+
+use crate::tests::std::{zkane_factory_build, zkane_pool_build};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use wasm_bindgen_test::wasm_bindgen_test;
+use zkane_core::generate_deposit_note;
+use zkane_factory::DEFAULT_TREE_HEIGHT;
+use zkane_testkit::{ContractBuilds, ScenarioBuilder, DEFAULT_ASSET, DEFAULT_DENOMINATION};
+
+pub fn builds() -> ContractBuilds {
+    assert!(
+        zkane_factory_build::BUILT && zkane_pool_build::BUILT,
+        "contracts weren't built, unset ZKANE_SKIP_BUILD to deploy them"
+    );
+    ContractBuilds {
+        factory: zkane_factory_build::get_bytes(),
+        pool: zkane_pool_build::get_bytes(),
+    }
+}
+
+#[test]
+#[wasm_bindgen_test]
+fn test_embedded_contracts() {
+    for (wasm, sha256, version) in [
+        (zkane_factory_build::WASM, zkane_factory_build::SHA256, zkane_factory_build::VERSION),
+        (zkane_pool_build::WASM, zkane_pool_build::SHA256, zkane_pool_build::VERSION),
+    ] {
+        assert!(wasm.starts_with(b"\0asm"));
+        assert_eq!(hex::encode(Sha256::digest(wasm)), sha256);
+        assert!(!version.is_empty());
+    }
+    assert_eq!(zkane_pool_build::get_bytes(), zkane_pool_build::WASM);
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_scenario_deposits() -> Result<()> {
+    let alice = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+    let bob = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION * 2)?
+        .mint("bob", DEFAULT_DENOMINATION)?
+        .deposit("alice", &alice)?
+        .deposit("bob", &bob)?
+        .assert_deposit_count(2)?
+        .assert_balance("alice", DEFAULT_DENOMINATION)?;
+
+    // A commitment can only be deposited once
+    assert!(scenario.deposit("alice", &alice).is_err());
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_scenario_registered_deposit_race() -> Result<()> {
+    let note = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+    let unregistered = generate_deposit_note(DEFAULT_ASSET, DEFAULT_DENOMINATION)?;
+
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", DEFAULT_DENOMINATION)?
+        .mint("mallory", DEFAULT_DENOMINATION)?
+        .register(&note)?;
+
+    // Mallory copies the commitment of Alice's reveal into a plain deposit
+    // that confirms first
+    assert!(scenario.try_deposit("mallory", &note, false).is_err());
+    // Only registered commitments can be revealed
+    assert!(scenario.try_deposit("alice", &unregistered, true).is_err());
+
+    scenario
+        .deposit_registered("alice", &note)?
+        .assert_deposit_count(1)?
+        .assert_balance("alice", 0)?;
+    Ok(())
+}
+
+#[test]
+#[wasm_bindgen_test]
+#[ignore]
+fn test_scenario_deposit_creates_pool() -> Result<()> {
+    let denomination = DEFAULT_DENOMINATION * 2;
+    let first = generate_deposit_note(DEFAULT_ASSET, denomination)?;
+    let second = generate_deposit_note(DEFAULT_ASSET, denomination)?;
+
+    // The commitment in the envelope of the deposit creating the pool isn't
+    // read as its configuration
+    let mut scenario = ScenarioBuilder::deploy_ecosystem(&builds())?
+        .mint("alice", denomination * 2)?
+        .select_pool(DEFAULT_ASSET, denomination)
+        .deposit_through_factory("alice", &first)?
+        .deposit_through_factory("alice", &second)?
+        .assert_deposit_count(2)?
+        .assert_balance("alice", 0)?;
+    assert_eq!(scenario.config()?.tree_height, DEFAULT_TREE_HEIGHT);
+    Ok(())
+}